@@ -0,0 +1,371 @@
+//! `pulse agent`: serves the stats of the machine it runs on (and its Docker
+//! containers, if Docker is there) as JSON over HTTP, for the `pulse_agent`
+//! provider on another machine to read. The HTTP side is a few lines over a
+//! plain `TcpListener`: one route, one request per connection.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::config::LocalConfig;
+use crate::models::{Container, ContainerStatus, ContainerType, GuestId, Node};
+use crate::providers::{LocalProvider, Provider};
+
+/// Bumped whenever the snapshot's shape changes incompatibly; the provider
+/// refuses agents speaking another version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything one request to the agent returns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub nodes: Vec<Node>,
+    pub containers: Vec<Container>,
+}
+
+/// `:9898` listens on every interface, like Go-style listen addresses.
+pub fn listen_addr(listen: &str) -> String {
+    match listen.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{}", port),
+        None => listen.to_string(),
+    }
+}
+
+/// Serve snapshots until the process is killed.
+pub fn serve(listen: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let addr = listen_addr(listen);
+    let listener =
+        TcpListener::bind(&addr).map_err(|e| format!("cannot listen on {}: {}", addr, e))?;
+    // One provider for the agent's lifetime: CPU usage is the difference
+    // between two samples, so it needs the previous one
+    let local = LocalProvider::new(&LocalConfig::default());
+    println!("pulse agent listening on {}", addr);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle(stream, &local) {
+                    eprintln!("agent: {}", e);
+                }
+            }
+            Err(e) => eprintln!("agent: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, local: &LocalProvider) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Only Accept-Encoding matters, but all are read so the client sees its request consumed
+    let mut gzip = false;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        gzip |= accepts_gzip(&header);
+        header.clear();
+    }
+
+    let (status, body) = respond(&request_line, || snapshot(local));
+    let (encoding, body) = if gzip {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.as_bytes())?;
+        ("Content-Encoding: gzip\r\n", encoder.finish()?)
+    } else {
+        ("", body.into_bytes())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        encoding,
+        body.len(),
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+/// Whether a request header line asks for a gzip response.
+fn accepts_gzip(header: &str) -> bool {
+    header.split_once(':').is_some_and(|(name, value)| {
+        name.trim().eq_ignore_ascii_case("accept-encoding")
+            && value.split(',').any(|coding| {
+                coding
+                    .split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .eq_ignore_ascii_case("gzip")
+            })
+    })
+}
+
+/// Status line and JSON body for a request line such as `GET /snapshot HTTP/1.1`.
+fn respond(
+    request_line: &str,
+    snapshot: impl FnOnce() -> Result<Snapshot, Box<dyn std::error::Error>>,
+) -> (&'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let error = |message: String| serde_json::json!({ "error": message }).to_string();
+    if path != "/snapshot" {
+        return ("404 Not Found", error(format!("no such path: {}", path)));
+    }
+    if method != "GET" {
+        return (
+            "405 Method Not Allowed",
+            error(format!("{} is not supported", method)),
+        );
+    }
+    match snapshot().and_then(|s| Ok(serde_json::to_string(&s)?)) {
+        Ok(body) => ("200 OK", body),
+        Err(e) => ("500 Internal Server Error", error(e.to_string())),
+    }
+}
+
+fn snapshot(local: &LocalProvider) -> Result<Snapshot, Box<dyn std::error::Error>> {
+    let nodes = local.fetch_nodes()?;
+    let containers = match nodes.first() {
+        Some(node) => docker_containers(node),
+        None => Vec::new(),
+    };
+    Ok(Snapshot {
+        version: SCHEMA_VERSION,
+        nodes,
+        containers,
+    })
+}
+
+/// A line of `docker ps --format '{{json .}}'`.
+#[derive(Debug, Deserialize)]
+struct DockerPs {
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(rename = "Names")]
+    names: String,
+    #[serde(rename = "State")]
+    state: String,
+    #[serde(rename = "Status")]
+    status: String,
+}
+
+/// A line of `docker stats --format '{{json .}}'`.
+#[derive(Debug, Deserialize)]
+struct DockerStats {
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(rename = "CPUPerc")]
+    cpu: String,
+    #[serde(rename = "MemUsage")]
+    memory: String,
+}
+
+/// Docker containers on this machine; none if Docker isn't installed or
+/// running, which is the common case rather than an error.
+fn docker_containers(node: &Node) -> Vec<Container> {
+    let Some(ps) = docker(&["ps", "--all", "--format", "{{json .}}"]) else {
+        return Vec::new();
+    };
+    let stats =
+        docker(&["stats", "--all", "--no-stream", "--format", "{{json .}}"]).unwrap_or_default();
+    parse_docker(&ps, &stats, node)
+}
+
+fn docker(args: &[&str]) -> Option<String> {
+    let output = Command::new("docker").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn parse_docker(ps: &str, stats: &str, node: &Node) -> Vec<Container> {
+    let stats: Vec<DockerStats> = stats
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let cores = node.cpu_cores.max(1);
+    ps.lines()
+        .filter_map(|line| serde_json::from_str::<DockerPs>(line).ok())
+        .map(|ps| {
+            let usage = stats.iter().find(|s| s.id == ps.id);
+            let cpu = usage
+                .and_then(|s| s.cpu.trim_end_matches('%').parse::<f64>().ok())
+                .unwrap_or(0.0);
+            let (memory_used, limit) = usage
+                .and_then(|s| s.memory.split_once('/'))
+                .map(|(used, limit)| (parse_size(used).unwrap_or(0), parse_size(limit)))
+                .unwrap_or((0, None));
+            let running = ps.state == "running";
+            Container {
+                id: GuestId::new("local", &ps.id),
+                vmid: 0,
+                name: ps.names,
+                node: node.name.clone(),
+                container_type: ContainerType::Docker,
+                status: if running {
+                    ContainerStatus::Running
+                } else {
+                    ContainerStatus::Stopped
+                },
+                // `docker stats` reports percent of one core, like processes
+                cpu_usage: cpu / cores as f64,
+                max_cpu: cores,
+                memory_used,
+                memory_max: limit.filter(|&l| l > 0).unwrap_or(node.memory_total),
+                uptime: if running { parse_up(&ps.status) } else { 0 },
+                lock: None,
+                missing: false,
+                pool: None,
+                is_template: false,
+                disk_used: None,
+                disk_max: 0,
+                os_info: None,
+            }
+        })
+        .collect()
+}
+
+/// Bytes in a Docker size such as `21.5MiB` or `1.2GB`.
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let factor: u64 = match unit {
+        "B" | "" => 1,
+        "kB" | "KB" => 1000,
+        "KiB" => 1 << 10,
+        "MB" => 1000 * 1000,
+        "MiB" => 1 << 20,
+        "GB" => 1000 * 1000 * 1000,
+        "GiB" => 1 << 30,
+        "TB" => 1000 * 1000 * 1000 * 1000,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    Some((number.parse::<f64>().ok()? * factor as f64) as u64)
+}
+
+/// Seconds up from a status such as `Up 3 hours (healthy)`; Docker rounds
+/// it the same way, so this is as precise as it gets without `inspect`.
+fn parse_up(status: &str) -> u64 {
+    let mut words = status.split_whitespace().skip_while(|w| *w != "Up").skip(1);
+    let count = match words.next() {
+        Some("Less") => return 0,
+        Some("About") => {
+            words.next();
+            1
+        }
+        Some(n) => n.parse().unwrap_or(0),
+        None => 0,
+    };
+    let unit = match words.next().map(|u| u.trim_end_matches('s')) {
+        Some("second") => 1,
+        Some("minute") => 60,
+        Some("hour") => 3600,
+        Some("day") => 86400,
+        Some("week") => 7 * 86400,
+        Some("month") => 30 * 86400,
+        Some("year") => 365 * 86400,
+        _ => 0,
+    };
+    count * unit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host() -> Node {
+        Node {
+            cpu_usage: 10.0,
+            cpu_cores: 4,
+            memory_used: 1 << 30,
+            memory_total: 8 << 30,
+            uptime: 3600,
+            ..Node::test("local", "nas")
+        }
+    }
+
+    #[test]
+    fn test_listen_addr() {
+        assert_eq!(listen_addr(":9898"), "0.0.0.0:9898");
+        assert_eq!(listen_addr("127.0.0.1:9898"), "127.0.0.1:9898");
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        assert!(accepts_gzip("Accept-Encoding: gzip, deflate\r\n"));
+        assert!(accepts_gzip("accept-encoding: deflate, gzip;q=0.5\r\n"));
+        assert!(!accepts_gzip("Accept-Encoding: deflate\r\n"));
+        assert!(!accepts_gzip("X-Note: gzip\r\n"));
+    }
+
+    #[test]
+    fn test_respond_routes() {
+        let ok = || {
+            Ok(Snapshot {
+                version: SCHEMA_VERSION,
+                nodes: vec![host()],
+                containers: Vec::new(),
+            })
+        };
+        let (status, body) = respond("GET /snapshot HTTP/1.1\r\n", ok);
+        assert_eq!(status, "200 OK");
+        let parsed: Snapshot = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.version, SCHEMA_VERSION);
+        assert_eq!(parsed.nodes[0].name, "nas");
+
+        assert_eq!(respond("GET / HTTP/1.1", ok).0, "404 Not Found");
+        assert_eq!(
+            respond("POST /snapshot HTTP/1.1", ok).0,
+            "405 Method Not Allowed"
+        );
+        let (status, body) = respond("GET /snapshot HTTP/1.1", || Err("no /proc".into()));
+        assert_eq!(status, "500 Internal Server Error");
+        assert_eq!(body, r#"{"error":"no /proc"}"#);
+    }
+
+    #[test]
+    fn test_parse_docker() {
+        let ps = r#"{"ID":"a1b2c3","Names":"web","State":"running","Status":"Up 3 hours (healthy)"}
+{"ID":"d4e5f6","Names":"backup","State":"exited","Status":"Exited (0) 2 days ago"}
+not json"#;
+        let stats = r#"{"ID":"a1b2c3","CPUPerc":"150.00%","MemUsage":"512MiB / 2GiB"}"#;
+        let containers = parse_docker(ps, stats, &host());
+        assert_eq!(containers.len(), 2);
+
+        let web = &containers[0];
+        assert_eq!(web.name, "web");
+        assert_eq!(web.container_type, ContainerType::Docker);
+        assert_eq!(web.status, ContainerStatus::Running);
+        assert_eq!(web.cpu_usage, 37.5);
+        assert_eq!(web.max_cpu, 4);
+        assert_eq!((web.memory_used, web.memory_max), (512 << 20, 2 << 30));
+        assert_eq!(web.uptime, 3 * 3600);
+
+        // Without stats the host's memory is the limit
+        let backup = &containers[1];
+        assert_eq!(backup.status, ContainerStatus::Stopped);
+        assert_eq!((backup.memory_used, backup.memory_max), (0, 8 << 30));
+        assert_eq!(backup.uptime, 0);
+    }
+
+    #[test]
+    fn test_parse_size_and_up() {
+        assert_eq!(parse_size("21.5MiB "), Some(22544384));
+        assert_eq!(parse_size(" 1.5GB"), Some(1_500_000_000));
+        assert_eq!(parse_size("12B"), Some(12));
+        assert_eq!(parse_size("3 parsecs"), None);
+
+        assert_eq!(parse_up("Up About a minute"), 60);
+        assert_eq!(parse_up("Up Less than a second"), 0);
+        assert_eq!(parse_up("Up 2 days"), 2 * 86400);
+        assert_eq!(parse_up("Exited (1) 3 hours ago"), 0);
+    }
+}
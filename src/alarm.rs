@@ -0,0 +1,162 @@
+//! Getting attention on a display nobody is reading all the time: the
+//! terminal bell and a brief header flash when a critical condition appears.
+//!
+//! Conditions are compared by key between refreshes, so one that persists
+//! doesn't ring again on every refresh, and one that clears and comes back
+//! does.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// How long the header stays inverted; a few redraws of the main loop.
+pub const FLASH_DURATION: Duration = Duration::from_millis(500);
+
+/// Conditions in `current` that weren't in `previous`, sorted.
+pub fn raised<'a>(previous: &HashSet<String>, current: &'a HashSet<String>) -> Vec<&'a str> {
+    let mut raised: Vec<&str> = current
+        .iter()
+        .filter(|key| !previous.contains(*key))
+        .map(String::as_str)
+        .collect();
+    raised.sort_unstable();
+    raised
+}
+
+#[derive(Debug, Default)]
+pub struct Alarm {
+    /// `[alerts] terminal_bell`
+    pub bell: bool,
+    /// `[alerts] visual_flash`
+    pub flash: bool,
+    /// Silenced from the keyboard for this session.
+    pub muted: bool,
+    /// Conditions at the last refresh; None before the first, so what is
+    /// already wrong at startup doesn't ring.
+    known: Option<HashSet<String>>,
+    /// A bell the main loop hasn't rung yet.
+    bell_pending: bool,
+    flash_until: Option<Instant>,
+}
+
+impl Alarm {
+    /// Take the conditions of a refresh; returns those that are new. They
+    /// are remembered while muted too, so unmuting doesn't ring for them.
+    pub fn update(&mut self, current: HashSet<String>, now: Instant) -> Vec<String> {
+        let raised: Vec<String> = match &self.known {
+            Some(known) => raised(known, &current)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            None => Vec::new(),
+        };
+        self.known = Some(current);
+        if !raised.is_empty() && !self.muted {
+            self.bell_pending |= self.bell;
+            if self.flash {
+                self.flash_until = Some(now + FLASH_DURATION);
+            }
+        }
+        raised
+    }
+
+    /// Whether to ring the bell now; true once per new condition.
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell_pending)
+    }
+
+    pub fn flashing(&self, now: Instant) -> bool {
+        self.flash_until.is_some_and(|until| now < until)
+    }
+
+    /// Mute or unmute; muting also stops a flash or bell under way.
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        if self.muted {
+            self.bell_pending = false;
+            self.flash_until = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(keys: &[&str]) -> HashSet<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn test_raised_is_only_what_is_new() {
+        let before = set(&["node pve1 down"]);
+        let after = set(&["node pve1 down", "guest 101 critical", "guest 100 down"]);
+        assert_eq!(
+            raised(&before, &after),
+            ["guest 100 down", "guest 101 critical"]
+        );
+        assert!(raised(&after, &before).is_empty());
+    }
+
+    #[test]
+    fn test_first_refresh_and_ongoing_conditions_stay_quiet() {
+        let now = Instant::now();
+        let mut alarm = Alarm {
+            bell: true,
+            flash: true,
+            ..Alarm::default()
+        };
+        assert!(alarm.update(set(&["node pve1 down"]), now).is_empty());
+        assert!(!alarm.take_bell());
+        assert!(!alarm.flashing(now));
+
+        assert!(alarm.update(set(&["node pve1 down"]), now).is_empty());
+        assert!(!alarm.take_bell());
+    }
+
+    #[test]
+    fn test_new_condition_rings_once_and_flashes_briefly() {
+        let now = Instant::now();
+        let mut alarm = Alarm {
+            bell: true,
+            flash: true,
+            ..Alarm::default()
+        };
+        alarm.update(set(&[]), now);
+        assert_eq!(
+            alarm.update(set(&["node pve1 down"]), now),
+            ["node pve1 down"]
+        );
+        assert!(alarm.take_bell());
+        assert!(!alarm.take_bell());
+        assert!(alarm.flashing(now));
+        assert!(!alarm.flashing(now + FLASH_DURATION));
+
+        // Cleared and back again is new again
+        alarm.update(set(&[]), now);
+        alarm.update(set(&["node pve1 down"]), now);
+        assert!(alarm.take_bell());
+    }
+
+    #[test]
+    fn test_muted_or_unconfigured_stays_quiet() {
+        let now = Instant::now();
+        let mut alarm = Alarm {
+            flash: true,
+            ..Alarm::default()
+        };
+        alarm.update(set(&[]), now);
+        alarm.update(set(&["a"]), now);
+        assert!(!alarm.take_bell(), "only the flash is configured");
+        assert!(alarm.flashing(now));
+
+        alarm.toggle_mute();
+        assert!(!alarm.flashing(now));
+        assert_eq!(alarm.update(set(&["a", "b"]), now), ["b"]);
+        assert!(!alarm.flashing(now));
+
+        // Unmuting doesn't ring for what appeared meanwhile
+        alarm.toggle_mute();
+        assert!(alarm.update(set(&["a", "b"]), now).is_empty());
+        assert!(!alarm.flashing(now));
+    }
+}
@@ -1,20 +1,106 @@
-use std::time::Instant;
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::models::{Container, ContainerStatus, Node, NodeStatus};
-use crate::providers::Provider;
+use chrono::{DateTime, FixedOffset, Local, Offset, TimeZone, Utc};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use crate::alarm::Alarm;
+use crate::audit::{self, AuditEntry, AuditEvent, AuditLog};
+use crate::backoff::Backoff;
+use crate::changes::{self, CHANGE_FADE, Change};
+use crate::cli::OutputFormat;
+use crate::clipboard::Handoff;
+use crate::clone::{CloneForm, ClonePhase};
+use crate::commands::{self, Action};
+use crate::config::{Accessibility, Clock, Config, CpuMode, ThresholdsConfig, Truncation};
+use crate::console;
+use crate::diagnostics::Diagnostics;
+use crate::drain::{self, Drain, DrainAction, DrainPhase, DrainStep, StepState};
+use crate::duplicates::{self, Duplicate};
+use crate::events::{self, Event, EventLog};
+use crate::filter_cache::{FilterCache, FilterKey};
+use crate::format::{DurationStyle, Units, format_duration, format_uptime};
+use crate::groups::{self, Group, GroupBy, GroupSummary, NamePrefix};
+use crate::loader::{Fetched, Loaded, Probe};
+use crate::logging::{LogBuffer, LogLine, LogView};
+use crate::metrics;
+use crate::models::{
+    ClusterStatus, Container, ContainerStatus, ContainerType, EventLevel, GuestDetails, GuestId,
+    HaStatus, Node, NodeId, NodeStatus, PermissionIssue, ReplicationJob, TaskStatus,
+    host_cpu_percent,
+};
+use crate::node_power::{GuestImpact, NodePower, NodePowerPhase, NodePowerRequest};
+use crate::notes::{self, Note, NotePrompt, Notes};
+use crate::output;
+use crate::presets::{self, Preset, PresetPrompt, Presets};
+use crate::providers::{self, EndpointInUse, Provider};
+use crate::query::{self, MatchOptions, ParseError};
+use crate::queue::{ActionQueue, ItemState, QueuePhase, QueuedAction};
+use crate::resolve::{self, ResolveError};
+use crate::resources::{ResourceForm, ResourcePhase};
+use crate::restarts::{self, RecentFilter, RestartCount};
+use crate::scheduler::{RefreshKind, RefreshScheduler};
+use crate::severity::Severity;
+use crate::snooze::{self, Rule, Snooze, SnoozeLength, SnoozeMenu, Snoozes};
+use crate::start::{GuestStart, StartPhase};
+use crate::stats::UsageStats;
+use crate::totals::{self, GuestTotals, NodeTotals};
+use crate::trend::{self, Trend};
+use crate::view;
+
+/// Replication state changes slowly, so it is polled less often than metrics.
+const REPLICATION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The cluster log is fetched whole each time, so not on every refresh.
+pub const CLUSTER_LOG_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Data older than this many refresh intervals is shown as stale.
+const STALE_AFTER_INTERVALS: u32 = 2;
+
+/// How many distinct errors the error log keeps.
+const ERROR_LOG_CAPACITY: usize = 50;
+
+/// Panels shown when `[ui] panels` is unset.
+const DEFAULT_PANELS: [Panel; 2] = [Panel::Nodes, Panel::Containers];
+
+/// How often a running action's task is polled.
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Panel {
     Nodes,
+    /// Proxmox pools with their totals; selecting one filters the containers.
+    Groups,
     Containers,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+impl Panel {
+    /// The name used for the panel in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Panel::Nodes => "nodes",
+            Panel::Groups => "groups",
+            Panel::Containers => "containers",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
 pub enum SortField {
     Name,
     Status,
     Cpu,
+    /// Percent of the item's memory limit.
     Memory,
+    /// Bytes used, so the biggest consumers come first whatever their limit.
+    MemoryAbs,
+    /// Percent of the guest's volume; nodes sort by name.
+    Disk,
 }
 
 impl SortField {
@@ -23,7 +109,9 @@ impl SortField {
             SortField::Name => SortField::Status,
             SortField::Status => SortField::Cpu,
             SortField::Cpu => SortField::Memory,
-            SortField::Memory => SortField::Name,
+            SortField::Memory => SortField::MemoryAbs,
+            SortField::MemoryAbs => SortField::Disk,
+            SortField::Disk => SortField::Name,
         }
     }
 
@@ -32,7 +120,9 @@ impl SortField {
             SortField::Name => "Name",
             SortField::Status => "Status",
             SortField::Cpu => "CPU",
-            SortField::Memory => "Memory",
+            SortField::Memory => "Mem%",
+            SortField::MemoryAbs => "MemGB",
+            SortField::Disk => "Disk",
         }
     }
 }
@@ -41,22 +131,331 @@ impl SortField {
 pub enum InputMode {
     Normal,
     Search,
+    Command,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProviderStatus {
+    pub name: String,
+    pub last_success: Option<Instant>,
+    pub last_error: Option<String>,
+    /// API requests made during the last refresh, for providers that count them.
+    pub requests: Option<u32>,
+    /// Response bytes received during the last refresh, likewise.
+    pub bytes: Option<u64>,
+    /// Quorum state, for providers that are part of a cluster.
+    pub cluster: Option<ClusterStatus>,
+    /// Why the last cluster status read failed. It is only extra detail, so
+    /// the provider still counts as refreshed and `cluster` keeps its last
+    /// answer.
+    pub cluster_error: Option<String>,
+    /// The API endpoint in use, for providers with several.
+    pub endpoint: Option<EndpointInUse>,
+    pub backoff: Backoff,
+    /// Privileges the credentials lack, from the last permissions probe.
+    pub permission_issues: Vec<PermissionIssue>,
+    /// False when the token lacks privileges actions need, listed in
+    /// `action_issues`; those actions are refused before they are sent.
+    pub actions_available: bool,
+    pub action_issues: Vec<PermissionIssue>,
+    /// Turned off for this session from the providers popup: not polled,
+    /// and its items are hidden until it is turned back on.
+    pub disabled: bool,
+    /// Left out at startup, so not built yet; turning it on starts it.
+    pub dormant: bool,
+    /// Not fetched since startup yet; the first load fetches these one per
+    /// loop iteration, so each provider's items show as they arrive.
+    pub loading: bool,
+}
+
+impl ProviderStatus {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            last_success: None,
+            last_error: None,
+            requests: None,
+            bytes: None,
+            cluster: None,
+            cluster_error: None,
+            endpoint: None,
+            backoff: Backoff::new(),
+            permission_issues: Vec::new(),
+            actions_available: true,
+            action_issues: Vec::new(),
+            disabled: false,
+            dormant: false,
+            loading: false,
+        }
+    }
+
+    /// Whether the provider failed its last refresh and is being retried
+    /// on a backoff schedule.
+    pub fn is_down(&self) -> bool {
+        self.backoff.failures() > 0
+    }
+
+    /// Age of this provider's last good snapshot, if it is old enough to be
+    /// considered stale at `now`.
+    pub fn staleness(&self, now: Instant, refresh_interval: Duration) -> Option<Duration> {
+        let age = now.saturating_duration_since(self.last_success?);
+        if age > refresh_interval * STALE_AFTER_INTERVALS {
+            Some(age)
+        } else {
+            None
+        }
+    }
+}
+
+/// Memory and cores promised to the guests of one node.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Allocation {
+    /// Configured memory of the running guests.
+    pub memory: u64,
+    pub cores: u32,
+    /// Running and stopped guests together: the load if everything started.
+    pub memory_all: u64,
+    pub cores_all: u32,
+}
+
+/// How many times over `capacity` the `allocated` amount is.
+pub fn overcommit_ratio(allocated: u64, capacity: u64) -> Option<f64> {
+    (capacity > 0).then(|| allocated as f64 / capacity as f64)
+}
+
+/// One provider's share of the inventory, as the providers popup lists it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderTotals {
+    pub nodes_online: usize,
+    pub nodes_total: usize,
+    pub guests_running: usize,
+    pub guests_total: usize,
+    /// Core-weighted CPU of its online nodes.
+    pub cpu: Option<f64>,
+    /// Memory in use across its online nodes, in percent.
+    pub memory: Option<f64>,
+}
+
+/// Either kind of item, for features that work on nodes and guests alike.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ItemId {
+    Node(NodeId),
+    Guest(GuestId),
+}
+
+/// The `'` prompt: typing jumps to the first guest whose VMID starts with
+/// the digits typed, or in the nodes panel the first node whose name starts
+/// with the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GotoPrompt {
+    pub text: String,
+    /// The selection before the prompt opened, restored by Esc.
+    previous: (Option<NodeId>, Option<GuestId>),
+    /// Enter was pressed with several matches: further Enters cycle
+    /// through them and any other key leaves the prompt.
+    pub confirmed: bool,
+}
+
+/// Index of the first label at or after `from` (wrapping around) that
+/// starts with `prefix`, ignoring case.
+pub fn prefix_match(labels: &[String], prefix: &str, from: usize) -> Option<usize> {
+    let prefix = prefix.to_lowercase();
+    (0..labels.len())
+        .map(|i| (from + i) % labels.len())
+        .find(|&i| labels[i].to_lowercase().starts_with(&prefix))
+}
+
+/// One provider error, kept in full for the error log popup.
+#[derive(Debug, Clone)]
+pub struct ErrorEntry {
+    pub provider: String,
+    pub message: String,
+    /// When the error last occurred.
+    pub at: SystemTime,
+    /// How many consecutive refreshes reported this same error.
+    pub count: u32,
 }
 
 pub struct App {
     pub running: bool,
     pub active_panel: Panel,
+    /// The panels shown, left to right, in Tab order.
+    pub panels: Vec<Panel>,
     pub nodes: Vec<Node>,
     pub containers: Vec<Container>,
+    pub replication_jobs: Vec<ReplicationJob>,
+    pub provider_status: Vec<ProviderStatus>,
+    /// Providers reporting the same machines, from the last refresh.
+    pub duplicates: Vec<Duplicate>,
+    /// Show each duplicated node and its guests once, from the provider
+    /// listed first (`[general] hide_duplicates`).
+    pub hide_duplicates: bool,
+    /// The later provider's copies, out of `nodes` and `containers` while
+    /// `hide_duplicates` is on, kept to compare against at the next refresh.
+    pub hidden_duplicates: (Vec<Node>, Vec<Container>),
+    pub guest_details: HashMap<GuestId, GuestDetails>,
     pub node_index: usize,
     pub container_index: usize,
+    /// Totals per pool, recomputed after every refresh.
+    pub pools: Vec<GroupSummary>,
+    /// Totals over all containers, for the "All" row.
+    pub pools_total: Option<GroupSummary>,
+    /// Selected row of the groups panel: 0 is "All", then `pools`.
+    pub pool_index: usize,
     pub error_message: Option<String>,
+    /// Confirmation shown in the status bar until the next refresh.
+    pub notice: Option<String>,
+    pub error_log: VecDeque<ErrorEntry>,
+    pub show_errors: bool,
+    pub error_scroll: u16,
     pub last_refresh: Option<Instant>,
+    /// Whether any provider has ever refreshed successfully; until then the
+    /// UI shows a per-provider failure summary instead of empty panels.
+    pub has_ever_loaded: bool,
+    pub refresh_interval: Duration,
+    /// Pending manual refreshes and follow-ups after actions.
+    pub schedule: RefreshScheduler,
+    /// Cluster CPU after each refresh, for the header sparkline.
+    pub cpu_trend: Trend,
+    /// Console command line waiting for the main loop to hand it the terminal.
+    pub console_request: Option<Vec<String>>,
+    /// Web console URL waiting for the main loop to copy or open it.
+    pub url_request: Option<(Handoff, String)>,
+    /// Bumped whenever nodes or containers change, invalidating `filter_cache`.
+    data_generation: u64,
+    /// The filtered containers, reused across calls until their inputs change.
+    filter_cache: RefCell<FilterCache>,
+    pub last_replication_refresh: Option<Instant>,
+    /// Provider logs and the transitions pulse noticed, for the events popup.
+    pub events: EventLog,
+    pub last_cluster_log_refresh: Option<Instant>,
+    pub show_events: bool,
+    /// pulse's own log, when started with `--log-file`.
+    pub log: Option<LogBuffer>,
+    /// The log popup (`l`) and where it is scrolled to, while open.
+    pub log_view: Option<LogView>,
+    /// Refresh and frame timings for the debug panel (`F12`), and whether
+    /// it is open.
+    pub diagnostics: Diagnostics,
+    pub show_debug: bool,
+    pub events_scroll: u16,
     pub sort_field: SortField,
     pub sort_ascending: bool,
+    /// The sortable container columns as last drawn, left to right, for
+    /// `1`–`9`; narrow terminals drop some.
+    pub sort_columns: RefCell<Vec<SortField>>,
+    /// Down and over-threshold items sort above the rest (`!`).
+    pub problems_first: bool,
+    /// Containers sort by node name first, then by the sort field (`n`).
+    pub group_sort_by_node: bool,
+    /// Actions that change guests are refused (`[general] read_only`).
+    pub read_only: bool,
+    /// Actions only show and audit the API calls they would make
+    /// (`[general] dry_run`, `--dry-run`); the providers fake them.
+    pub dry_run: bool,
+    pub thresholds: ThresholdsConfig,
     pub input_mode: InputMode,
     pub search_query: String,
+    /// Case-sensitive and whole-word matching of the search (`Ctrl+I`, `Ctrl+O`).
+    pub search_options: MatchOptions,
+    /// Enter moves focus to the other list when only it has matches
+    /// (`[ui] search_switches_panel`).
+    pub search_switches_panel: bool,
+    /// Saved filter presets (`presets.rs`), and the state file they and the
+    /// notes are kept in.
+    pub presets: Presets,
+    pub state_path: Option<PathBuf>,
+    /// Name prompt for saving the current filters (`Ctrl+s` in search mode).
+    pub preset_prompt: Option<PresetPrompt>,
+    /// Selected row of the presets popup (`F`), while it is open.
+    pub presets_popup: Option<usize>,
+    /// Selected row of the providers popup (`P`), while it is open.
+    pub providers_popup: Option<usize>,
+    /// Only this provider's items are listed (Enter in the providers popup).
+    pub provider_filter: Option<String>,
+    /// Guest notes (`notes.rs`), kept in the same state file as the presets.
+    pub notes: Notes,
+    /// Note being typed for the selected guest (`A`).
+    pub note_prompt: Option<NotePrompt>,
+    /// How long a note outlives its guest (`[general] note_retention`).
+    pub note_retention: Duration,
+    /// Snoozed alerts (`snooze.rs`), also kept in the state file.
+    pub snoozes: Snoozes,
+    /// The snooze popup (`z`), while it is open.
+    pub snooze_menu: Option<SnoozeMenu>,
+    /// What has been typed after `:` in command mode.
+    pub command_input: String,
+    /// Completions offered by the last Tab, until the input changes.
+    pub command_candidates: Vec<&'static str>,
+    /// Why the last submitted command failed, until the input changes.
+    pub command_error: Option<String>,
     pub show_help: bool,
+    pub help_scroll: u16,
+    pub name_width: Option<usize>,
+    pub truncation: Truncation,
+    pub full_names: bool,
+    pub cpu_mode: CpuMode,
+    pub accessibility: Accessibility,
+    /// Zone of the header clock and refresh timestamp (`[ui] clock`).
+    pub clock: Clock,
+    /// Keep the terminal title to `health_title()` (`[ui] terminal_title`).
+    pub terminal_title: bool,
+    /// Pin a totals row under the nodes and containers lists (`[ui] summary_row`).
+    pub summary_row: bool,
+    /// Panel titles count items by status (`[ui] detailed_counts`).
+    pub detailed_counts: bool,
+    /// Guest rows show disk usage (`[ui] disk_column`).
+    pub disk_column: bool,
+    /// How sizes are written (`[ui] units`).
+    pub units: Units,
+    /// How the containers panel groups its rows.
+    pub group_by: GroupBy,
+    pub name_prefix: NamePrefix,
+    /// Show only one summary row per group.
+    pub groups_collapsed: bool,
+    /// Only guests up for less than this (`u`); composes with the search.
+    pub recent_filter: RecentFilter,
+    /// Leave templates out of the containers panel entirely (`T`).
+    pub hide_templates: bool,
+    /// First item picked for a side-by-side comparison.
+    pub pinned: Option<ItemId>,
+    /// Two items of the same kind shown side by side in the detail panel.
+    pub comparison: Option<(ItemId, ItemId)>,
+    /// Guests marked for acting on several at once (Space, or a range in
+    /// visual mode).
+    pub marked: HashSet<GuestId>,
+    /// Where visual mode (`V`) started, while it is on; the range runs from
+    /// here to the selection in display order.
+    pub visual_anchor: Option<GuestId>,
+    /// VMID or node name being typed after `'`.
+    pub goto_prompt: Option<GotoPrompt>,
+    /// Node drain being planned, executed or reported on.
+    pub drain: Option<Drain>,
+    /// Guest clone being filled in, running or reported on.
+    pub clone: Option<CloneForm>,
+    /// Guest cores and memory being edited or reported on.
+    pub resources: Option<ResourceForm>,
+    /// Guest start being confirmed, running or reported on.
+    pub start: Option<GuestStart>,
+    /// Bulk action on the marked guests being confirmed, run or reported on.
+    pub queue: Option<ActionQueue>,
+    /// Node reboot or shutdown being confirmed or reported on.
+    pub node_power: Option<NodePowerRequest>,
+    /// Tasks a bulk action runs at once.
+    pub action_concurrency: usize,
+    /// Restarts (uptime going down) seen since pulse started.
+    pub restarts: HashMap<ItemId, RestartCount>,
+    /// Rolling average and peak of each item's CPU and memory.
+    pub usage: HashMap<ItemId, UsageStats>,
+    /// What the last refresh changed per guest, and when, for the list to
+    /// highlight until `CHANGE_FADE` has passed.
+    pub changes: HashMap<GuestId, (Instant, Change)>,
+    /// Where actions taken through pulse are recorded, if configured.
+    pub audit: Option<AuditLog>,
+    /// Bell and header flash on new critical conditions.
+    pub alarm: Alarm,
+    /// Set by `Ctrl+e`; the main loop re-reads the config, which it owns.
+    pub reload_requested: bool,
 }
 
 impl App {
@@ -64,61 +463,564 @@ impl App {
         Self {
             running: true,
             active_panel: Panel::Nodes,
+            panels: DEFAULT_PANELS.to_vec(),
             nodes: Vec::new(),
             containers: Vec::new(),
+            replication_jobs: Vec::new(),
+            provider_status: Vec::new(),
+            duplicates: Vec::new(),
+            hide_duplicates: false,
+            hidden_duplicates: (Vec::new(), Vec::new()),
+            guest_details: HashMap::new(),
             node_index: 0,
             container_index: 0,
+            pools: Vec::new(),
+            pools_total: None,
+            pool_index: 0,
             error_message: None,
+            notice: None,
+            error_log: VecDeque::new(),
+            show_errors: false,
+            error_scroll: 0,
             last_refresh: None,
+            has_ever_loaded: false,
+            refresh_interval: Duration::from_secs(5),
+            schedule: RefreshScheduler::new(),
+            cpu_trend: Trend::default(),
+            console_request: None,
+            url_request: None,
+            data_generation: 0,
+            filter_cache: RefCell::new(FilterCache::default()),
+            last_replication_refresh: None,
+            events: EventLog::default(),
+            last_cluster_log_refresh: None,
+            show_events: false,
+            log: None,
+            log_view: None,
+            diagnostics: Diagnostics::default(),
+            show_debug: false,
+            events_scroll: 0,
             sort_field: SortField::Name,
             sort_ascending: true,
+            sort_columns: RefCell::new(vec![
+                SortField::Status,
+                SortField::Name,
+                SortField::Cpu,
+                SortField::MemoryAbs,
+            ]),
+            problems_first: false,
+            group_sort_by_node: false,
+            read_only: false,
+            dry_run: false,
+            thresholds: ThresholdsConfig::default(),
             input_mode: InputMode::Normal,
             search_query: String::new(),
+            search_options: MatchOptions::default(),
+            search_switches_panel: true,
+            presets: Presets::new(),
+            state_path: None,
+            preset_prompt: None,
+            presets_popup: None,
+            providers_popup: None,
+            provider_filter: None,
+            notes: Notes::new(),
+            note_prompt: None,
+            snoozes: Snoozes::new(),
+            snooze_menu: None,
+            note_retention: Duration::from_secs(30 * 24 * 60 * 60),
+            command_input: String::new(),
+            command_candidates: Vec::new(),
+            command_error: None,
             show_help: false,
+            help_scroll: 0,
+            name_width: None,
+            truncation: Truncation::End,
+            full_names: false,
+            cpu_mode: CpuMode::Guest,
+            accessibility: Accessibility::Standard,
+            clock: Clock::Off,
+            terminal_title: true,
+            summary_row: true,
+            detailed_counts: false,
+            disk_column: false,
+            units: Units::default(),
+            group_by: GroupBy::Off,
+            name_prefix: NamePrefix::default(),
+            groups_collapsed: false,
+            recent_filter: RecentFilter::Off,
+            hide_templates: false,
+            pinned: None,
+            comparison: None,
+            marked: HashSet::new(),
+            visual_anchor: None,
+            goto_prompt: None,
+            drain: None,
+            clone: None,
+            resources: None,
+            start: None,
+            queue: None,
+            node_power: None,
+            action_concurrency: 1,
+            restarts: HashMap::new(),
+            usage: HashMap::new(),
+            changes: HashMap::new(),
+            audit: None,
+            alarm: Alarm::default(),
+            reload_requested: false,
+        }
+    }
+
+    /// Seed the view state from the command line, as if typed interactively.
+    pub fn apply_startup_options(
+        &mut self,
+        filter: Option<&str>,
+        sort: Option<SortField>,
+        desc: bool,
+    ) {
+        if let Some(query) = filter {
+            self.search_query = query.to_string();
+        }
+        if let Some(field) = sort {
+            self.sort_field = field;
+        }
+        self.sort_ascending = !desc;
+        self.apply_sort();
+    }
+
+    /// Show these panels in this order, starting on the first.
+    pub fn set_panels(&mut self, panels: Vec<Panel>) -> Result<(), String> {
+        check_panels(&panels)?;
+        self.active_panel = panels[0];
+        self.panels = panels;
+        Ok(())
+    }
+
+    /// Settings that follow the config file, at startup and on reload.
+    /// Runtime toggles (CPU mode, sorting, grouping, filters) are left as
+    /// they are. Nothing is applied unless all of it is valid.
+    pub fn apply_config(&mut self, config: &Config) -> Result<(), String> {
+        check_config(config)?;
+        let panels = config.ui.panels.as_deref().unwrap_or(&DEFAULT_PANELS);
+
+        self.refresh_interval = config.general.refresh_interval()?;
+        self.schedule.set_adaptive(config.general.adaptive_refresh);
+        self.schedule.set_stagger(config.general.stagger_refresh);
+        self.note_retention = config.general.note_retention()?;
+        self.action_concurrency = config.general.action_concurrency()?;
+        self.hide_duplicates = config.general.hide_duplicates;
+        self.name_width = config.ui.name_width;
+        self.truncation = config.ui.truncate;
+        self.accessibility = config.ui.accessibility;
+        self.clock = config.ui.clock;
+        self.terminal_title = config.ui.terminal_title.unwrap_or(true);
+        self.summary_row = config.ui.summary_row.unwrap_or(true);
+        self.detailed_counts = config.ui.detailed_counts;
+        self.disk_column = config.ui.disk_column;
+        self.units = config.ui.units;
+        self.search_switches_panel = config.ui.search_switches_panel.unwrap_or(true);
+        self.thresholds = config.thresholds;
+        self.name_prefix = NamePrefix {
+            separator: config
+                .ui
+                .name_group_separator
+                .clone()
+                .unwrap_or_else(|| NamePrefix::default().separator),
+            depth: config
+                .ui
+                .name_group_depth
+                .unwrap_or(NamePrefix::default().depth),
+        };
+        // Keep the active panel unless it was removed
+        if self.panels != panels {
+            let active = self.active_panel;
+            self.set_panels(panels.to_vec())?;
+            if self.panels.contains(&active) {
+                self.active_panel = active;
+            }
+        }
+        self.audit = config.audit.log_path().map(AuditLog::new);
+        self.alarm.bell = config.alerts.terminal_bell;
+        self.alarm.flash = config.alerts.visual_flash;
+        Ok(())
+    }
+
+    /// Ask every provider which privileges its credentials are missing.
+    /// Run at startup and on demand, since admins fix permissions live.
+    pub fn probe_permissions(&mut self, providers: &[Box<dyn Provider>]) {
+        for provider in providers {
+            self.record_probe(provider.name(), Probe::run(provider.as_ref()));
+        }
+    }
+
+    fn record_probe(&mut self, name: &str, probe: Probe) {
+        match probe.permissions {
+            Ok(issues) => {
+                for issue in &issues {
+                    self.record_error(name, issue.to_string());
+                }
+                self.provider_status_entry(name).permission_issues = issues;
+            }
+            Err(e) => {
+                self.record_error(name, format!("permission probe failed: {}", e));
+                return;
+            }
+        }
+        // Left available when unsure; the API has the last word anyway
+        match probe.actions {
+            Some(Ok(issues)) => {
+                let status = self.provider_status_entry(name);
+                status.actions_available = issues.is_empty();
+                status.action_issues = issues;
+            }
+            Some(Err(e)) => self.record_error(name, format!("action probe failed: {}", e)),
+            None => {}
+        }
+    }
+
+    /// Draw first, fetch after: mark every provider that will be polled as
+    /// loading. A `Loader` fetches them on its own thread and `load` applies
+    /// each as it arrives; until then scheduled refreshes wait.
+    pub fn start_loading(&mut self, providers: &[Box<dyn Provider>]) {
+        for provider in providers {
+            let status = self.provider_status_entry(provider.name());
+            status.loading = !status.disabled;
+        }
+    }
+
+    pub fn is_provider_loading(&self, name: &str) -> bool {
+        self.provider_status
+            .iter()
+            .any(|s| s.name == name && s.loading)
+    }
+
+    /// Whether the first load still has providers to fetch. One turned
+    /// off before its turn waits until it is turned back on.
+    pub fn is_loading(&self) -> bool {
+        self.provider_status
+            .iter()
+            .any(|s| s.loading && !s.disabled)
+    }
+
+    /// Apply a provider's first load. One turned off meanwhile, or no
+    /// longer configured, is done loading without it.
+    pub fn load(&mut self, providers: &[Box<dyn Provider>], loaded: Loaded, now: Instant) {
+        let name = loaded.fetched.name.clone();
+        let configured = providers.iter().any(|p| p.name() == name);
+        let status = self.provider_status_entry(&name);
+        if !configured || status.disabled {
+            status.loading = false;
+            return;
         }
+        self.record_probe(&name, loaded.probe);
+        self.refresh_providers(providers, RefreshKind::Loading, now, Some(loaded.fetched));
     }
 
+    /// End the first load for providers whose results will never come (the
+    /// loader's thread died): the regular refreshes take them over.
+    pub fn stop_loading(&mut self) {
+        for status in &mut self.provider_status {
+            status.loading = false;
+        }
+    }
+
+    /// Refresh every provider now, ignoring any backoff (manual refresh).
     pub fn refresh(&mut self, providers: &[Box<dyn Provider>]) {
+        self.refresh_providers(providers, RefreshKind::Manual, Instant::now(), None);
+    }
+
+    /// Scheduled refresh: providers that are down are only retried once
+    /// their backoff delay has passed.
+    pub fn refresh_due(&mut self, providers: &[Box<dyn Provider>]) {
+        self.refresh_providers(providers, RefreshKind::Scheduled, Instant::now(), None);
+    }
+
+    /// Run whichever refresh the schedule has due at `now`, if any.
+    /// Run the refresh due at `now`, if any. Called once per loop iteration
+    /// with both clocks: after a suspend everything is refreshed once right
+    /// away instead of showing data from before it.
+    pub fn refresh_if_due(
+        &mut self,
+        providers: &[Box<dyn Provider>],
+        now: Instant,
+        wall: SystemTime,
+    ) {
+        if let Some(asleep) = self.schedule.tick(now, wall) {
+            self.refresh(providers);
+            self.notice = Some(format!(
+                "Refreshed after {} asleep",
+                format_uptime(asleep.as_secs())
+            ));
+            return;
+        }
+        // The first load is fetched elsewhere; anything due waits for it
+        if self.is_loading() {
+            return;
+        }
+        match self.schedule.due(now, self.refresh_interval) {
+            Some(RefreshKind::Manual) => self.refresh(providers),
+            Some(RefreshKind::Scheduled) => self.refresh_due(providers),
+            Some(kind @ (RefreshKind::Staggered | RefreshKind::Loading)) => {
+                self.refresh_providers(providers, kind, now, None)
+            }
+            None => {}
+        }
+    }
+
+    /// Fetch from the providers `kind` calls for: all of them (a manual
+    /// refresh ignoring backoff), those not backing off, while staggering
+    /// those whose slot came up at `now`, or during the first load only the
+    /// provider `loaded` was fetched from.
+    fn refresh_providers(
+        &mut self,
+        providers: &[Box<dyn Provider>],
+        kind: RefreshKind,
+        now: Instant,
+        mut loaded: Option<Fetched>,
+    ) {
+        let force = kind == RefreshKind::Manual;
         self.error_message = None;
-        let mut all_nodes = Vec::new();
-        let mut all_containers = Vec::new();
-        let mut had_error = false;
+        // Staggered refreshes come several times an interval; a notice
+        // still lasts one, until the first provider's turn
+        let first_due = providers.first().is_none_or(|p| {
+            self.schedule
+                .provider_due(p.name(), now, self.refresh_interval)
+        });
+        if kind != RefreshKind::Staggered || first_due {
+            self.notice = None;
+        }
+        let wall_now = unix_now();
+        let selection = self.selected_ids();
+        let before = (self.nodes.clone(), self.containers.clone());
+
+        // Forget providers that are no longer configured
+        self.provider_status
+            .retain(|s| providers.iter().any(|p| p.name() == s.name));
+        self.nodes
+            .retain(|n| providers.iter().any(|p| p.name() == n.provider()));
+        self.containers
+            .retain(|c| providers.iter().any(|p| p.name() == c.provider()));
+        self.hidden_duplicates
+            .0
+            .retain(|n| providers.iter().any(|p| p.name() == n.provider()));
+        self.hidden_duplicates
+            .1
+            .retain(|c| providers.iter().any(|p| p.name() == c.provider()));
+        self.diagnostics
+            .retain_providers(|name| providers.iter().any(|p| p.name() == name));
+        let mut timings = Vec::new();
 
         for provider in providers {
-            match provider.fetch_nodes() {
-                Ok(nodes) => {
-                    all_nodes.extend(nodes);
+            let name = provider.name();
+            let skipped = match kind {
+                RefreshKind::Staggered => {
+                    !self.schedule.provider_due(name, now, self.refresh_interval)
                 }
-                Err(e) => {
-                    self.error_message = Some(format!("Error fetching nodes: {}", e));
-                    had_error = true;
+                RefreshKind::Loading => loaded.as_ref().is_none_or(|l| l.name != name),
+                RefreshKind::Manual | RefreshKind::Scheduled => false,
+            };
+            if skipped {
+                // Not its turn: keep its last error visible, as when backing off
+                if let Some(error) = self.provider_status_entry(name).last_error.clone() {
+                    self.error_message = Some(format!("{}: {}", name, error));
+                }
+                continue;
+            }
+            if kind == RefreshKind::Staggered {
+                self.schedule.provider_refreshed(name, now);
+            }
+            let status = self.provider_status_entry(name);
+            if status.disabled {
+                continue;
+            }
+            if !force && !status.backoff.is_due(now) {
+                // Keep the last error visible without re-logging it
+                if let Some(ref error) = status.last_error {
+                    self.error_message = Some(format!("{}: {}", name, error));
+                }
+                continue;
+            }
+
+            let fetched = match loaded.take() {
+                Some(fetched) => fetched,
+                None => Fetched::fetch(provider.as_ref()),
+            };
+            let mut errors = Vec::new();
+
+            // A failing provider keeps its previous items so the UI doesn't go
+            // blank on transient network errors; they are shown as stale instead
+            match fetched.nodes {
+                Ok(nodes) => {
+                    self.track_node_events(name, &nodes, wall_now);
+                    self.track_node_restarts(&nodes, wall_now);
+                    self.nodes.retain(|n| n.provider() != name);
+                    self.hidden_duplicates.0.retain(|n| n.provider() != name);
+                    self.nodes.extend(nodes);
+                    self.record_node_usage(name, now);
                 }
+                Err(e) => errors.push(format!("error fetching nodes: {}", e)),
             }
 
-            match provider.fetch_containers() {
+            match fetched.containers {
                 Ok(containers) => {
-                    all_containers.extend(containers);
+                    self.track_container_events(name, &containers, wall_now);
+                    self.track_container_restarts(&containers, wall_now);
+                    let gone = self.track_container_changes(name, &containers, now);
+                    self.containers.retain(|c| c.provider() != name);
+                    self.hidden_duplicates.1.retain(|c| c.provider() != name);
+                    self.containers.extend(containers);
+                    self.containers.extend(gone);
+                    self.record_container_usage(name, now);
                 }
+                Err(e) => errors.push(format!("error fetching containers: {}", e)),
+            }
+
+            let status = self.provider_status_entry(name);
+            status.loading = false;
+            let cluster_error = match fetched.cluster {
+                Ok(cluster) => {
+                    status.cluster = cluster;
+                    status.cluster_error = None;
+                    None
+                }
+                // A provider that is down fails this too; its error says enough
+                Err(_) if !errors.is_empty() => None,
                 Err(e) => {
-                    self.error_message = Some(format!("Error fetching containers: {}", e));
-                    had_error = true;
+                    let message = format!("error fetching cluster status: {}", e);
+                    let new = status.cluster_error.as_ref() != Some(&message);
+                    status.cluster_error = Some(message.clone());
+                    new.then_some(message)
                 }
+            };
+            timings.push((name, fetched.elapsed, !errors.is_empty()));
+            log::debug!("{}: refreshed in {:?}", name, fetched.elapsed);
+            if errors.is_empty() {
+                status.last_success = Some(now);
+                status.last_error = None;
+                status.backoff.record_success();
+                self.has_ever_loaded = true;
+            } else {
+                let message = errors.join("; ");
+                status.last_error = Some(message.clone());
+                status.backoff.record_failure(now);
+                self.record_error(name, message);
+            }
+            if let Some(message) = cluster_error {
+                self.record_error(name, message);
             }
         }
 
-        // Only update data if we got new data, otherwise keep existing data
-        // This prevents the UI from going blank on transient network errors
-        if !all_nodes.is_empty() || !had_error {
-            self.nodes = all_nodes;
+        self.find_duplicates(providers);
+        self.data_changed();
+        self.last_refresh = Some(Instant::now());
+        self.schedule.refreshed(Instant::now());
+        // The first load hands out slots once it has fetched everything
+        let loading = self.is_loading();
+        if kind != RefreshKind::Staggered && !loading {
+            let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+            self.schedule.spread(&names, now, self.refresh_interval);
         }
-        if !all_containers.is_empty() || !had_error {
-            self.containers = all_containers;
+        self.schedule.record_outcome(changes::snapshot_changed(
+            (&before.0, &before.1),
+            (&self.nodes, &self.containers),
+        ));
+        if let Some(cpu) = trend::cluster_cpu(&self.nodes) {
+            self.cpu_trend.push(now, cpu);
         }
+        let (nodes, containers) = (&self.nodes, &self.containers);
+        self.usage.retain(|id, _| match id {
+            ItemId::Node(id) => nodes.iter().any(|n| &n.id == id),
+            ItemId::Guest(id) => containers.iter().any(|c| &c.id == id && !c.missing),
+        });
 
-        self.last_refresh = Some(Instant::now());
+        // Details are only kept for the current selection and re-fetched each cycle
+        self.guest_details.clear();
+        self.ensure_selected_details(providers);
 
-        self.apply_sort();
+        // These ask every provider, so they wait for the slow ones to load
+        let replication_due = !loading
+            && self
+                .last_replication_refresh
+                .is_none_or(|t| t.elapsed() >= REPLICATION_REFRESH_INTERVAL);
+        if replication_due {
+            self.refresh_replication(providers);
+        }
+        let cluster_log_due = !loading
+            && self
+                .last_cluster_log_refresh
+                .is_none_or(|t| t.elapsed() >= CLUSTER_LOG_REFRESH_INTERVAL);
+        if cluster_log_due {
+            self.refresh_cluster_log(providers);
+        }
+
+        for provider in providers {
+            let status = self.provider_status_entry(provider.name());
+            status.requests = provider.request_count();
+            status.bytes = provider.bytes_received();
+            status.endpoint = provider.endpoint();
+        }
+        for (name, duration, failed) in timings {
+            let requests = providers
+                .iter()
+                .find(|p| p.name() == name)
+                .and_then(|p| p.request_count());
+            self.diagnostics
+                .record_provider(name, duration, requests, failed);
+        }
+        self.diagnostics.record_refresh(now.elapsed());
+
+        self.sweep_notes(wall_now);
+        self.sweep_snoozes(wall_now);
+        let conditions = self.critical_conditions();
+        self.alarm.update(conditions, now);
+        self.aggregate_pools();
+        self.sort_items();
+        self.select_ids(selection);
+        self.sweep_marks();
+        self.clamp_selection();
+    }
+
+    /// Compare the providers' items for machines reported twice, with the
+    /// copies hidden last time back in, then hide the later provider's
+    /// copies again if configured to.
+    fn find_duplicates(&mut self, providers: &[Box<dyn Provider>]) {
+        let (nodes, containers) = std::mem::take(&mut self.hidden_duplicates);
+        self.nodes.extend(nodes);
+        self.containers.extend(containers);
+
+        let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+        let found = duplicates::find(&names, &self.nodes, &self.containers);
+        for duplicate in &found {
+            if !self
+                .duplicates
+                .iter()
+                .any(|d| d.first == duplicate.first && d.second == duplicate.second)
+            {
+                log::warn!("{}", duplicate.describe());
+            }
+        }
+        self.duplicates = found;
+        if !self.hide_duplicates {
+            return;
+        }
+        for duplicate in &self.duplicates {
+            let copy = |provider: &str, node: &str| {
+                provider == duplicate.second && duplicate.nodes.iter().any(|n| n == node)
+            };
+            let (hidden, kept) = std::mem::take(&mut self.nodes)
+                .into_iter()
+                .partition(|n| copy(n.provider(), &n.name));
+            self.nodes = kept;
+            self.hidden_duplicates.0.extend::<Vec<Node>>(hidden);
+            let (hidden, kept) = std::mem::take(&mut self.containers)
+                .into_iter()
+                .partition(|c| copy(c.provider(), &c.node));
+            self.containers = kept;
+            self.hidden_duplicates.1.extend::<Vec<Container>>(hidden);
+        }
+    }
 
+    /// Keep the selection within lists that got shorter.
+    fn clamp_selection(&mut self) {
         if self.node_index >= self.filtered_nodes().len() {
             self.node_index = self.filtered_nodes().len().saturating_sub(1);
         }
@@ -127,507 +1029,7307 @@ impl App {
         }
     }
 
-    fn apply_sort(&mut self) {
-        let ascending = self.sort_ascending;
+    /// Record what a provider's fresh guest list changed, and return the
+    /// guests that disappeared, marked missing so they stay listed for one
+    /// more cycle. A provider's first load flags nothing.
+    fn track_container_changes(
+        &mut self,
+        provider: &str,
+        containers: &[Container],
+        now: Instant,
+    ) -> Vec<Container> {
+        self.changes.retain(|id, _| id.provider != provider);
+        let loaded_before = self
+            .provider_status
+            .iter()
+            .any(|s| s.name == provider && s.last_success.is_some());
+        if !loaded_before {
+            return Vec::new();
+        }
 
-        match self.sort_field {
-            SortField::Name => {
-                self.nodes.sort_by(|a, b| {
-                    if ascending {
-                        a.name.cmp(&b.name)
-                    } else {
-                        b.name.cmp(&a.name)
-                    }
-                });
-                self.containers.sort_by(|a, b| {
-                    if ascending {
-                        a.name.cmp(&b.name)
-                    } else {
-                        b.name.cmp(&a.name)
-                    }
-                });
+        let previous: Vec<&Container> = self
+            .containers
+            .iter()
+            .filter(|c| c.provider() == provider && !c.missing)
+            .collect();
+        let by_id: HashMap<&GuestId, &Container> = previous.iter().map(|c| (&c.id, *c)).collect();
+        for container in containers {
+            if let Some(change) = changes::diff(by_id.get(&container.id).copied(), container) {
+                self.changes.insert(container.id.clone(), (now, change));
             }
-            SortField::Status => {
-                self.nodes.sort_by(|a, b| {
-                    let a_val = matches!(a.status, NodeStatus::Online);
-                    let b_val = matches!(b.status, NodeStatus::Online);
-                    if ascending {
-                        b_val.cmp(&a_val)
-                    } else {
-                        a_val.cmp(&b_val)
+        }
+
+        let gone: Vec<Container> = previous
+            .into_iter()
+            .filter(|before| !containers.iter().any(|c| c.id == before.id))
+            .map(|before| Container {
+                missing: true,
+                ..before.clone()
+            })
+            .collect();
+        for container in &gone {
+            self.changes
+                .insert(container.id.clone(), (now, Change::Removed));
+        }
+        gone
+    }
+
+    /// The change the last refresh made to a guest, until it has faded at `now`.
+    pub fn container_change(&self, container: &Container, now: Instant) -> Option<Change> {
+        let &(at, change) = self.changes.get(&container.id)?;
+        (now.saturating_duration_since(at) < CHANGE_FADE).then_some(change)
+    }
+
+    /// Log nodes of `provider` going offline, coming back or rebooting.
+    fn track_node_events(&mut self, provider: &str, nodes: &[Node], now: u64) {
+        let events: Vec<Event> = restarts::matched(&self.nodes, nodes, |n| n.id.clone())
+            .into_iter()
+            .filter_map(|(before, after)| {
+                let (level, what) = match after.status {
+                    NodeStatus::Offline if before.status == NodeStatus::Online => {
+                        (EventLevel::Error, "went offline")
                     }
-                });
-                self.containers.sort_by(|a, b| {
-                    let a_val = matches!(a.status, ContainerStatus::Running);
-                    let b_val = matches!(b.status, ContainerStatus::Running);
-                    if ascending {
-                        b_val.cmp(&a_val)
-                    } else {
-                        a_val.cmp(&b_val)
+                    NodeStatus::Online if before.status == NodeStatus::Offline => {
+                        (EventLevel::Info, "came back online")
                     }
-                });
-            }
-            SortField::Cpu => {
-                self.nodes.sort_by(|a, b| {
-                    if ascending {
-                        a.cpu_usage.partial_cmp(&b.cpu_usage).unwrap()
-                    } else {
-                        b.cpu_usage.partial_cmp(&a.cpu_usage).unwrap()
+                    _ if restarts::node_rebooted(before, after) => {
+                        (EventLevel::Warning, "rebooted")
                     }
-                });
-                self.containers.sort_by(|a, b| {
-                    if ascending {
-                        a.cpu_usage.partial_cmp(&b.cpu_usage).unwrap()
-                    } else {
-                        b.cpu_usage.partial_cmp(&a.cpu_usage).unwrap()
+                    _ => return None,
+                };
+                let message = format!("node {} {}", after.name, what);
+                Some(Event::detected(
+                    provider,
+                    now,
+                    Some(&after.name),
+                    level,
+                    message,
+                ))
+            })
+            .collect();
+        for event in events {
+            self.events.push(event);
+        }
+    }
+
+    /// Log guests of `provider` starting, stopping or restarting.
+    fn track_container_events(&mut self, provider: &str, containers: &[Container], now: u64) {
+        let events: Vec<Event> = restarts::matched(&self.containers, containers, |c| c.id.clone())
+            .into_iter()
+            .filter_map(|(before, after)| {
+                let (level, what) = match after.status {
+                    ContainerStatus::Stopped if before.status == ContainerStatus::Running => {
+                        (EventLevel::Warning, "stopped")
                     }
-                });
-            }
-            SortField::Memory => {
-                self.nodes.sort_by(|a, b| {
-                    let a_pct = a.memory_percent();
-                    let b_pct = b.memory_percent();
-                    if ascending {
-                        a_pct.partial_cmp(&b_pct).unwrap()
-                    } else {
-                        b_pct.partial_cmp(&a_pct).unwrap()
+                    ContainerStatus::Running if before.status == ContainerStatus::Stopped => {
+                        (EventLevel::Info, "started")
                     }
-                });
-                self.containers.sort_by(|a, b| {
-                    let a_pct = a.memory_percent();
-                    let b_pct = b.memory_percent();
-                    if ascending {
-                        a_pct.partial_cmp(&b_pct).unwrap()
-                    } else {
-                        b_pct.partial_cmp(&a_pct).unwrap()
+                    _ if restarts::container_restarted(before, after) => {
+                        (EventLevel::Warning, "restarted")
                     }
-                });
-            }
+                    _ => return None,
+                };
+                let message = format!("{} {} {}", after.type_label(), after.name, what);
+                Some(Event::detected(
+                    provider,
+                    now,
+                    Some(&after.node),
+                    level,
+                    message,
+                ))
+            })
+            .collect();
+        for event in events {
+            self.events.push(event);
         }
     }
 
-    pub fn filtered_nodes(&self) -> Vec<&Node> {
-        if self.search_query.is_empty() {
-            self.nodes.iter().collect()
-        } else {
-            let query = self.search_query.to_lowercase();
-            self.nodes
-                .iter()
-                .filter(|n| n.name.to_lowercase().contains(&query))
-                .collect()
+    /// Compare a fresh snapshot with the items it replaces and count reboots.
+    fn track_node_restarts(&mut self, nodes: &[Node], now: u64) {
+        let rebooted: Vec<ItemId> = restarts::matched(&self.nodes, nodes, |n| n.id.clone())
+            .into_iter()
+            .filter(|(before, after)| restarts::node_rebooted(before, after))
+            .map(|(_, after)| ItemId::Node(after.id.clone()))
+            .collect();
+        for id in rebooted {
+            self.count_restart(id, now);
         }
     }
 
-    pub fn filtered_containers(&self) -> Vec<&Container> {
-        if self.search_query.is_empty() {
-            self.containers.iter().collect()
-        } else {
-            let query = self.search_query.to_lowercase();
-            self.containers
-                .iter()
-                .filter(|c| {
-                    c.name.to_lowercase().contains(&query) || c.node.to_lowercase().contains(&query)
-                })
-                .collect()
+    fn track_container_restarts(&mut self, containers: &[Container], now: u64) {
+        let restarted: Vec<ItemId> =
+            restarts::matched(&self.containers, containers, |c| c.id.clone())
+                .into_iter()
+                .filter(|(before, after)| restarts::container_restarted(before, after))
+                .map(|(_, after)| ItemId::Guest(after.id.clone()))
+                .collect();
+        for id in restarted {
+            self.count_restart(id, now);
         }
     }
 
-    pub fn selected_node(&self) -> Option<&Node> {
-        self.filtered_nodes().get(self.node_index).copied()
+    fn count_restart(&mut self, id: ItemId, now: u64) {
+        self.restarts
+            .entry(id)
+            .and_modify(|r| r.record(now))
+            .or_insert_with(|| RestartCount::new(now));
     }
 
-    pub fn selected_container(&self) -> Option<&Container> {
-        self.filtered_containers()
-            .get(self.container_index)
-            .copied()
+    /// Add a freshly fetched provider's node figures to their rolling stats.
+    fn record_node_usage(&mut self, provider: &str, now: Instant) {
+        let wall = SystemTime::now();
+        for node in self.nodes.iter().filter(|n| n.provider() == provider) {
+            let stats = self.usage.entry(ItemId::Node(node.id.clone())).or_default();
+            stats.cpu.push(now, wall, node.cpu_usage);
+            stats.memory.push(now, wall, node.memory_percent());
+        }
     }
 
-    pub fn quit(&mut self) {
-        self.running = false;
+    /// Same for guests; CPU is kept in guest terms and scaled when shown.
+    fn record_container_usage(&mut self, provider: &str, now: Instant) {
+        let wall = SystemTime::now();
+        for container in self
+            .containers
+            .iter()
+            .filter(|c| c.provider() == provider && !c.missing && !c.is_template)
+        {
+            let stats = self
+                .usage
+                .entry(ItemId::Guest(container.id.clone()))
+                .or_default();
+            stats.cpu.push(now, wall, container.cpu_usage);
+            stats.memory.push(now, wall, container.memory_percent());
+        }
     }
 
-    pub fn next_panel(&mut self) {
-        self.active_panel = match self.active_panel {
-            Panel::Nodes => Panel::Containers,
-            Panel::Containers => Panel::Nodes,
+    /// What the guests of each known node are configured with. Guests that
+    /// name a node the provider didn't list (e.g. mid-migration, or a node
+    /// that left the cluster) count towards no node.
+    pub fn allocations(&self) -> HashMap<NodeId, Allocation> {
+        let mut allocations: HashMap<NodeId, Allocation> = self
+            .nodes
+            .iter()
+            .map(|n| (n.id.clone(), Allocation::default()))
+            .collect();
+        for container in self.allocated_guests() {
+            let Some(allocation) =
+                allocations.get_mut(&NodeId::new(container.provider(), &container.node))
+            else {
+                continue;
+            };
+            allocation.memory_all += container.memory_max;
+            allocation.cores_all += container.max_cpu;
+            if container.status == ContainerStatus::Running {
+                allocation.memory += container.memory_max;
+                allocation.cores += container.max_cpu;
+            }
+        }
+        allocations
+    }
+
+    /// The guests `allocations()` adds up: VMs and containers still on the
+    /// provider, templates aside.
+    fn allocated_guests(&self) -> impl Iterator<Item = &Container> {
+        self.containers.iter().filter(|c| {
+            !c.missing
+                && !c.is_template
+                && matches!(c.container_type, ContainerType::VM | ContainerType::LXC)
+        })
+    }
+
+    pub fn node_restarts(&self, node: &Node) -> Option<&RestartCount> {
+        self.restarts.get(&ItemId::Node(node.id.clone()))
+    }
+
+    pub fn container_restarts(&self, container: &Container) -> Option<&RestartCount> {
+        self.restarts.get(&ItemId::Guest(container.id.clone()))
+    }
+
+    /// Fetch on-demand details for the selected container unless already cached.
+    pub fn ensure_selected_details(&mut self, providers: &[Box<dyn Provider>]) {
+        if self.active_panel != Panel::Containers {
+            return;
+        }
+        let Some(container) = self.selected_container().cloned() else {
+            return;
+        };
+        if self.guest_details.contains_key(&container.id) {
+            return;
+        }
+        let Some(provider) = providers.iter().find(|p| p.name() == container.provider()) else {
+            return;
         };
+        if self.provider_is_down(provider.name()) {
+            return;
+        }
+
+        // Details are best-effort: on failure the panel falls back to list data
+        let details = provider.fetch_guest_details(&container).unwrap_or_default();
+        self.store_details(container.id, details);
     }
 
-    pub fn select_next(&mut self) {
+    /// Keep `details` for the guest, and its OS on the listed guest, where
+    /// search finds it.
+    fn store_details(&mut self, id: GuestId, details: GuestDetails) {
+        if let Some(os) = &details.os_info
+            && let Some(container) = self.containers.iter_mut().find(|c| c.id == id)
+            && container.os_info.as_ref() != Some(os)
+        {
+            container.os_info = Some(os.clone());
+            self.data_changed();
+        }
+        self.guest_details.insert(id, details);
+    }
+
+    /// Re-fetch only the selected item (`R`): its entry in its node's list
+    /// and, for a guest, its details. Everything else is left as it is.
+    pub fn refresh_selected(&mut self, providers: &[Box<dyn Provider>]) {
         match self.active_panel {
             Panel::Nodes => {
-                let max = self.filtered_nodes().len().saturating_sub(1);
-                if self.node_index < max {
-                    self.node_index += 1;
+                if let Some(node) = self.selected_node().cloned() {
+                    self.refresh_node(providers, &node);
                 }
             }
+            // A pool has no single item to re-fetch
+            Panel::Groups => {}
             Panel::Containers => {
-                let max = self.filtered_containers().len().saturating_sub(1);
-                if self.container_index < max {
-                    self.container_index += 1;
+                if let Some(container) = self.selected_container().cloned() {
+                    self.refresh_guest(providers, &container);
                 }
             }
         }
+        self.apply_sort();
     }
 
-    pub fn select_previous(&mut self) {
-        match self.active_panel {
-            Panel::Nodes => {
-                self.node_index = self.node_index.saturating_sub(1);
+    fn refresh_node(&mut self, providers: &[Box<dyn Provider>], node: &Node) {
+        let Some(provider) = providers.iter().find(|p| p.name() == node.provider()) else {
+            return;
+        };
+        provider.begin_refresh();
+        match provider.fetch_node(&node.name) {
+            Ok(Some(fresh)) => {
+                self.track_node_restarts(std::slice::from_ref(&fresh), unix_now());
+                if let Some(slot) = self.nodes.iter_mut().find(|n| n.id == fresh.id) {
+                    *slot = fresh;
+                    self.data_changed();
+                }
+                self.notice = Some(format!("Refreshed node {}", node.name));
             }
-            Panel::Containers => {
-                self.container_index = self.container_index.saturating_sub(1);
+            Ok(None) => {
+                self.notice = Some(format!(
+                    "{} is no longer listed by {}",
+                    node.name,
+                    provider.name()
+                ));
+            }
+            Err(e) => {
+                self.record_error(
+                    provider.name(),
+                    format!("error refreshing {}: {}", node.name, e),
+                );
             }
         }
     }
 
-    pub fn cycle_sort(&mut self) {
-        self.sort_field = self.sort_field.next();
-        self.apply_sort();
+    fn refresh_guest(&mut self, providers: &[Box<dyn Provider>], container: &Container) {
+        let Some(provider) = providers.iter().find(|p| p.name() == container.provider()) else {
+            return;
+        };
+        provider.begin_refresh();
+        let mut fresh = match provider.fetch_guest(&container.node, container.vmid) {
+            Ok(Some(fresh)) => fresh,
+            Ok(None) => {
+                self.notice = Some(format!(
+                    "{} is no longer listed on {}",
+                    container.name, container.node
+                ));
+                return;
+            }
+            Err(e) => {
+                self.record_error(
+                    provider.name(),
+                    format!("error refreshing {}: {}", container.name, e),
+                );
+                return;
+            }
+        };
+
+        self.track_container_restarts(std::slice::from_ref(&fresh), unix_now());
+        match provider.fetch_guest_details(&fresh) {
+            Ok(details) => {
+                if details.os_info.is_some() {
+                    fresh.os_info = details.os_info.clone();
+                }
+                self.guest_details.insert(fresh.id.clone(), details);
+                self.notice = Some(format!("Refreshed {} and its details", container.name));
+            }
+            Err(e) => {
+                self.guest_details
+                    .insert(fresh.id.clone(), GuestDetails::default());
+                self.notice = Some(format!("Refreshed {}", container.name));
+                self.record_error(
+                    provider.name(),
+                    format!("error fetching details for {}: {}", container.name, e),
+                );
+            }
+        }
+        if let Some(slot) = self.containers.iter_mut().find(|c| c.id == fresh.id) {
+            *slot = fresh;
+            self.data_changed();
+        }
+        self.aggregate_pools();
     }
 
-    pub fn toggle_sort_order(&mut self) {
-        self.sort_ascending = !self.sort_ascending;
-        self.apply_sort();
+    /// Recompute the groups panel from all containers, keeping the selected
+    /// pool if it still exists.
+    pub fn aggregate_pools(&mut self) {
+        let selected = self.selected_pool().map(|p| p.key.clone());
+        let summarize = |groups: Vec<Group>| -> Vec<GroupSummary> {
+            groups
+                .iter()
+                .map(|g| g.summary(self.group_cpu(g)))
+                .collect()
+        };
+        let pools = summarize(groups::group(self.containers.iter().collect(), |c| {
+            c.pool.clone()
+        }));
+        let total = summarize(groups::group(self.containers.iter().collect(), |_| None));
+        self.pools = pools;
+        self.pools_total = total.into_iter().next();
+        self.pool_index = selected
+            .and_then(|key| self.pools.iter().position(|p| p.key == key))
+            .map_or(0, |i| i + 1);
     }
 
-    pub fn enter_search_mode(&mut self) {
-        self.input_mode = InputMode::Search;
+    /// The pool the containers panel is narrowed to, if any.
+    pub fn selected_pool(&self) -> Option<&GroupSummary> {
+        self.pool_index
+            .checked_sub(1)
+            .and_then(|i| self.pools.get(i))
     }
 
-    pub fn exit_search_mode(&mut self) {
-        self.input_mode = InputMode::Normal;
+    fn select_pool(&mut self, index: usize) {
+        if index != self.pool_index {
+            self.pool_index = index;
+            self.container_index = 0;
+        }
     }
 
-    pub fn clear_search(&mut self) {
-        self.search_query.clear();
-        self.node_index = 0;
-        self.container_index = 0;
+    pub fn details_for(&self, container: &Container) -> Option<&GuestDetails> {
+        self.guest_details.get(&container.id)
     }
 
-    pub fn push_search_char(&mut self, c: char) {
-        self.search_query.push(c);
-        self.node_index = 0;
-        self.container_index = 0;
+    /// Show an error in the status bar and keep the full text in the error log.
+    /// A provider repeating its last error bumps that entry instead of adding one.
+    pub fn record_error(&mut self, provider: &str, message: String) {
+        self.error_message = Some(format!("{}: {}", provider, message));
+
+        if let Some(entry) = self.error_log.iter_mut().find(|e| e.provider == provider)
+            && entry.message == message
+        {
+            entry.at = SystemTime::now();
+            entry.count += 1;
+            return;
+        }
+        // Repeats only bump the count, so the log gets each error once
+        log::warn!("{}: {}", provider, message);
+
+        self.error_log.push_front(ErrorEntry {
+            provider: provider.to_string(),
+            message,
+            at: SystemTime::now(),
+            count: 1,
+        });
+        self.error_log.truncate(ERROR_LOG_CAPACITY);
     }
 
-    pub fn pop_search_char(&mut self) {
-        self.search_query.pop();
-        self.node_index = 0;
-        self.container_index = 0;
+    fn provider_status_entry(&mut self, name: &str) -> &mut ProviderStatus {
+        let index = match self.provider_status.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.provider_status.push(ProviderStatus::new(name));
+                self.provider_status.len() - 1
+            }
+        };
+        &mut self.provider_status[index]
     }
 
-    pub fn toggle_help(&mut self) {
-        self.show_help = !self.show_help;
+    pub fn provider_is_down(&self, provider: &str) -> bool {
+        self.provider_status
+            .iter()
+            .any(|s| s.name == provider && s.is_down())
     }
 
-    pub fn time_since_refresh(&self) -> String {
-        match self.last_refresh {
-            Some(instant) => {
-                let secs = instant.elapsed().as_secs();
-                if secs < 60 {
-                    format!("{}s ago", secs)
-                } else {
-                    format!("{}m ago", secs / 60)
+    pub fn provider_disabled(&self, provider: &str) -> bool {
+        self.provider_status
+            .iter()
+            .any(|s| s.name == provider && s.disabled)
+    }
+
+    /// Counts and usage of one provider's items, for the providers popup.
+    pub fn provider_totals(&self, provider: &str) -> ProviderTotals {
+        let nodes: Vec<&Node> = self
+            .nodes
+            .iter()
+            .filter(|n| n.provider() == provider)
+            .collect();
+        let guests = totals::guests(
+            self.containers
+                .iter()
+                .filter(|c| c.provider() == provider && !c.missing),
+            |c| c.cpu_usage,
+        );
+        let nodes = totals::nodes(nodes);
+        ProviderTotals {
+            nodes_online: nodes.online,
+            nodes_total: nodes.total,
+            guests_running: guests.running,
+            guests_total: guests.total,
+            cpu: nodes.cpu,
+            memory: nodes.memory_percent(),
+        }
+    }
+
+    /// How stale the data from `provider` is at `now`, if it is stale at all.
+    pub fn provider_staleness(&self, provider: &str, now: Instant) -> Option<Duration> {
+        self.provider_status
+            .iter()
+            .find(|s| s.name == provider)
+            .and_then(|s| s.staleness(now, self.effective_refresh_interval()))
+    }
+
+    /// The configured interval, stretched while adaptive refresh finds
+    /// nothing changing.
+    pub fn effective_refresh_interval(&self) -> Duration {
+        self.schedule.interval(self.refresh_interval)
+    }
+
+    fn refresh_replication(&mut self, providers: &[Box<dyn Provider>]) {
+        let mut all_jobs = Vec::new();
+        let mut had_error = false;
+
+        for provider in providers {
+            if self.provider_is_down(provider.name()) || self.provider_disabled(provider.name()) {
+                continue;
+            }
+            match provider.fetch_replication() {
+                Ok(jobs) => {
+                    all_jobs.extend(jobs);
+                }
+                Err(e) => {
+                    self.record_error(
+                        provider.name(),
+                        format!("error fetching replication: {}", e),
+                    );
+                    had_error = true;
                 }
             }
-            None => "never".to_string(),
         }
+
+        if !all_jobs.is_empty() || !had_error {
+            self.replication_jobs = all_jobs;
+        }
+        self.last_replication_refresh = Some(Instant::now());
     }
 
-    pub fn nodes_summary(&self) -> (usize, usize) {
-        let online = self
-            .nodes
-            .iter()
-            .filter(|n| n.status == NodeStatus::Online)
-            .count();
-        (online, self.nodes.len())
+    fn refresh_cluster_log(&mut self, providers: &[Box<dyn Provider>]) {
+        for provider in providers {
+            if self.provider_is_down(provider.name()) || self.provider_disabled(provider.name()) {
+                continue;
+            }
+            match provider.fetch_cluster_log(events::CLUSTER_LOG_ENTRIES) {
+                Ok(entries) => {
+                    self.events.add_cluster_log(provider.name(), entries);
+                }
+                Err(e) => {
+                    self.record_error(
+                        provider.name(),
+                        format!("error fetching cluster log: {}", e),
+                    );
+                }
+            }
+        }
+        self.last_cluster_log_refresh = Some(Instant::now());
     }
 
-    pub fn containers_summary(&self) -> (usize, usize) {
-        let running = self
+    /// Re-sort, keeping the same items selected.
+    fn apply_sort(&mut self) {
+        let selection = self.selected_ids();
+        self.sort_items();
+        self.select_ids(selection);
+    }
+
+    fn selected_ids(&self) -> (Option<NodeId>, Option<GuestId>) {
+        (
+            self.selected_node().map(|n| n.id.clone()),
+            self.selected_container().map(|c| c.id.clone()),
+        )
+    }
+
+    /// Point the selection at these items, wherever they now are in the
+    /// filtered lists. Items that disappeared leave the index untouched.
+    fn select_ids(&mut self, (node, container): (Option<NodeId>, Option<GuestId>)) {
+        if let Some(id) = node
+            && let Some(index) = self.filtered_nodes().iter().position(|n| n.id == id)
+        {
+            self.node_index = index;
+        }
+        if let Some(id) = container
+            && let Some(index) = self.filtered_containers().iter().position(|c| c.id == id)
+        {
+            self.container_index = index;
+        }
+    }
+
+    fn sort_items(&mut self) {
+        // Sort by what is on screen, which depends on the CPU mode
+        let cpu: HashMap<GuestId, f64> = self
             .containers
             .iter()
-            .filter(|c| c.status == ContainerStatus::Running)
-            .count();
-        (running, self.containers.len())
+            .map(|c| (c.id.clone(), self.container_cpu(c)))
+            .collect();
+        view::sort_nodes(&mut self.nodes, self.sort_field, self.sort_ascending);
+        view::sort_containers(
+            &mut self.containers,
+            self.sort_field,
+            self.sort_ascending,
+            |c| cpu[&c.id],
+        );
+
+        if self.problems_first {
+            // Stable, so each band keeps the order sorted above
+            let severity: HashMap<NodeId, Severity> = self
+                .nodes
+                .iter()
+                .map(|n| (n.id.clone(), self.node_severity(n)))
+                .collect();
+            self.nodes.sort_by_key(|n| severity[&n.id]);
+            let severity: HashMap<GuestId, Severity> = self
+                .containers
+                .iter()
+                .map(|c| (c.id.clone(), self.container_severity(c)))
+                .collect();
+            self.containers.sort_by_key(|c| severity[&c.id]);
+        }
+
+        if self.group_sort_by_node {
+            // Stable, so each node's containers keep the order sorted above
+            self.containers.sort_by(|a, b| a.node.cmp(&b.node));
+        }
+        self.data_changed();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::models::ContainerType;
+    /// Note that nodes or containers changed, so cached filter results are stale.
+    pub fn data_changed(&mut self) {
+        self.data_generation += 1;
+    }
+
+    pub fn filtered_nodes(&self) -> Vec<&Node> {
+        let in_view = |n: &&Node| {
+            self.provider_filter
+                .as_ref()
+                .is_none_or(|provider| n.provider() == provider)
+        };
+        if self.search_query.is_empty() {
+            return self.nodes.iter().filter(in_view).collect();
+        }
+        let options = self.search_options;
+        match query::parse_with(&self.search_query, options) {
+            Ok(query) => view::filter_nodes(&self.nodes, &query)
+                .into_iter()
+                .map(|i| &self.nodes[i])
+                .filter(in_view)
+                .collect(),
+            Err(_) => {
+                let text = search_text(&self.search_query, options);
+                self.nodes
+                    .iter()
+                    .filter(in_view)
+                    .filter(|n| options.matches(&n.name, &text))
+                    .collect()
+            }
+        }
+    }
+
+    /// Containers matching the search, in display order: grouped together
+    /// when a grouping is on.
+    pub fn filtered_containers(&self) -> Vec<&Container> {
+        self.container_filter()
+            .display
+            .iter()
+            .filter_map(|&i| self.containers.get(i))
+            .collect()
+    }
+
+    /// The filtered containers split into the current groups (one group
+    /// holding everything when grouping is off).
+    pub fn container_groups(&self) -> Vec<Group<'_>> {
+        groups::group(self.filtered_containers(), |c| self.group_key(c))
+    }
+
+    fn group_key(&self, container: &Container) -> Option<String> {
+        match self.group_by {
+            GroupBy::Off => None,
+            GroupBy::Node => Some(container.node.clone()),
+            GroupBy::Prefix => self.name_prefix.key(&container.name),
+        }
+    }
+
+    /// CPU of a whole group in the current CPU mode: percent of the cores
+    /// allocated to the group, or its members' shares of their hosts added up.
+    pub fn group_cpu(&self, group: &Group) -> f64 {
+        match self.cpu_mode {
+            CpuMode::Guest => group.cpu_of_allocation(),
+            CpuMode::Host => group.members.iter().map(|c| self.container_cpu(c)).sum(),
+        }
+    }
+
+    fn filter_key(&self) -> FilterKey {
+        FilterKey {
+            generation: self.data_generation,
+            containers: self.containers.len(),
+            query: self.search_query.clone(),
+            options: self.search_options,
+            pool: self.selected_pool().map(|p| p.key.clone()),
+            provider: self.provider_filter.clone(),
+            cpu_mode: self.cpu_mode,
+            recent: self.recent_filter,
+            hide_templates: self.hide_templates,
+            group_by: self.group_by,
+            name_prefix: self.name_prefix.clone(),
+        }
+    }
+
+    /// The cached filter result, brought up to date first if needed. When
+    /// the query only got narrower (typically a character was appended),
+    /// just the previous matches are checked again.
+    fn container_filter(&self) -> Ref<'_, FilterCache> {
+        let key = self.filter_key();
+        if !self.filter_cache.borrow().is_for(&key) {
+            let (matching, evaluated) = {
+                let cache = self.filter_cache.borrow();
+                match cache.narrowed_from(&key) {
+                    Some(previous) => self.matching_containers(previous.iter().copied(), &key),
+                    None => self.matching_containers(0..self.containers.len(), &key),
+                }
+            };
+            // Stable, so each group keeps the containers' order, as `groups::group` does
+            let mut display = matching.clone();
+            if self.group_by != GroupBy::Off {
+                let keys: HashMap<usize, Option<String>> = display
+                    .iter()
+                    .map(|&i| (i, self.group_key(&self.containers[i])))
+                    .collect();
+                display.sort_by(|a, b| {
+                    let (a, b) = (&keys[a], &keys[b]);
+                    (a.is_none(), a).cmp(&(b.is_none(), b))
+                });
+            }
+            let mut cache = self.filter_cache.borrow_mut();
+            cache.evaluated += evaluated;
+            cache.store(key, matching, display);
+        }
+        self.filter_cache.borrow()
+    }
+
+    /// Those of `candidates` in the selected pool that match the search, and
+    /// how many were checked against it.
+    fn matching_containers(
+        &self,
+        candidates: impl Iterator<Item = usize>,
+        key: &FilterKey,
+    ) -> (Vec<usize>, usize) {
+        let in_pool = candidates.filter(|&i| {
+            key.pool
+                .as_ref()
+                .is_none_or(|pool| &self.containers[i].pool == pool)
+                && key
+                    .provider
+                    .as_ref()
+                    .is_none_or(|provider| self.containers[i].provider() == provider)
+                && key.recent.matches(&self.containers[i])
+                && !(key.hide_templates && self.containers[i].is_template)
+        });
+        if key.query.is_empty() {
+            return (in_pool.collect(), 0);
+        }
+        let in_pool: Vec<usize> = in_pool.collect();
+        let evaluated = in_pool.len();
+        let matching = match query::parse_with(&key.query, key.options) {
+            Ok(query) => view::filter_containers(&self.containers, in_pool, &query, |c| {
+                self.container_cpu(c)
+            }),
+            // Keep filtering while the query is mid-edit or mistyped
+            Err(_) => {
+                let text = search_text(&key.query, key.options);
+                in_pool
+                    .into_iter()
+                    .filter(|&i| {
+                        let c = &self.containers[i];
+                        key.options.matches(&c.name, &text) || key.options.matches(&c.node, &text)
+                    })
+                    .collect()
+            }
+        };
+        (matching, evaluated)
+    }
+
+    /// Why the search query does not parse; filtering falls back to a plain
+    /// substring match meanwhile.
+    pub fn search_error(&self) -> Option<ParseError> {
+        query::parse_with(&self.search_query, self.search_options).err()
+    }
+
+    pub fn selected_node(&self) -> Option<&Node> {
+        self.filtered_nodes().get(self.node_index).copied()
+    }
+
+    pub fn selected_container(&self) -> Option<&Container> {
+        let index = *self.container_filter().display.get(self.container_index)?;
+        self.containers.get(index)
+    }
+
+    pub fn node_by_id(&self, id: &NodeId) -> Option<&Node> {
+        self.nodes.iter().find(|n| &n.id == id)
+    }
+
+    pub fn container_by_id(&self, id: &GuestId) -> Option<&Container> {
+        self.containers.iter().find(|c| &c.id == id)
+    }
+
+    fn selected_item_id(&self) -> Option<ItemId> {
+        match self.active_panel {
+            Panel::Nodes => self.selected_node().map(|n| ItemId::Node(n.id.clone())),
+            Panel::Groups => None,
+            Panel::Containers => self
+                .selected_container()
+                .map(|c| ItemId::Guest(c.id.clone())),
+        }
+    }
+
+    /// Pin the selected item; pinning a second item of the same kind starts a
+    /// comparison between the two. Pinning the pinned item again unpins it.
+    pub fn toggle_pin(&mut self) {
+        let Some(selected) = self.selected_item_id() else {
+            return;
+        };
+        match self.pinned.take() {
+            Some(pinned) if pinned == selected => {}
+            Some(pinned)
+                if std::mem::discriminant(&pinned) == std::mem::discriminant(&selected) =>
+            {
+                self.comparison = Some((pinned, selected));
+            }
+            _ => {
+                self.comparison = None;
+                self.pinned = Some(selected);
+            }
+        }
+    }
+
+    pub fn exit_comparison(&mut self) {
+        self.pinned = None;
+        self.comparison = None;
+    }
+
+    /// Mark the selected guest, or unmark it.
+    pub fn toggle_mark(&mut self) -> Result<(), String> {
+        let id = match self.active_panel {
+            Panel::Containers => self.selected_container().map(|c| c.id.clone()),
+            _ => None,
+        }
+        .ok_or("mark: select a guest first")?;
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+        Ok(())
+    }
+
+    /// Start visual mode at the selected guest.
+    pub fn start_visual(&mut self) -> Result<(), String> {
+        if self.active_panel != Panel::Containers {
+            return Err("visual: select a guest first".to_string());
+        }
+        if self.groups_collapsed {
+            return Err("visual: expand the groups first".to_string());
+        }
+        let id = self
+            .selected_container()
+            .map(|c| c.id.clone())
+            .ok_or("visual: select a guest first")?;
+        self.visual_anchor = Some(id);
+        Ok(())
+    }
+
+    /// Rows of `filtered_containers()` in the visual range, while visual
+    /// mode is on and its anchor is listed.
+    pub fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+        let anchor = self.visual_anchor.as_ref()?;
+        let start = self
+            .filtered_containers()
+            .iter()
+            .position(|c| &c.id == anchor)?;
+        Some(start.min(self.container_index)..=start.max(self.container_index))
+    }
+
+    /// Mark every guest in the visual range and leave visual mode.
+    pub fn confirm_visual(&mut self) {
+        let Some(range) = self.visual_range() else {
+            self.visual_anchor = None;
+            return;
+        };
+        let ids: Vec<GuestId> = self.filtered_containers()[range]
+            .iter()
+            .map(|c| c.id.clone())
+            .collect();
+        self.notice = Some(format!(
+            "Marked {} guest{}",
+            ids.len(),
+            if ids.len() == 1 { "" } else { "s" }
+        ));
+        self.marked.extend(ids);
+        self.visual_anchor = None;
+    }
+
+    pub fn cancel_visual(&mut self) {
+        self.visual_anchor = None;
+    }
+
+    /// `'`: open the goto prompt on the nodes or guests list.
+    pub fn open_goto_prompt(&mut self) -> Result<(), String> {
+        match self.active_panel {
+            Panel::Groups => return Err("goto: select the nodes or guests list".to_string()),
+            Panel::Containers if self.groups_collapsed => {
+                return Err("goto: expand the groups first".to_string());
+            }
+            _ => {}
+        }
+        self.goto_prompt = Some(GotoPrompt {
+            text: String::new(),
+            previous: self.selected_ids(),
+            confirmed: false,
+        });
+        Ok(())
+    }
+
+    /// What the goto prompt matches against: VMIDs of the listed guests, or
+    /// names of the listed nodes.
+    fn goto_labels(&self) -> Vec<String> {
+        match self.active_panel {
+            Panel::Nodes => self
+                .filtered_nodes()
+                .iter()
+                .map(|n| n.name.clone())
+                .collect(),
+            _ => self
+                .filtered_containers()
+                .iter()
+                .map(|c| c.vmid.to_string())
+                .collect(),
+        }
+    }
+
+    fn goto_index(&mut self) -> &mut usize {
+        match self.active_panel {
+            Panel::Nodes => &mut self.node_index,
+            _ => &mut self.container_index,
+        }
+    }
+
+    /// Select the first match of the prompt at or after `from`. Without one
+    /// the selection stays where it is.
+    fn jump_to_goto_match(&mut self, from: usize) {
+        let Some(prompt) = &self.goto_prompt else {
+            return;
+        };
+        if let Some(index) = prefix_match(&self.goto_labels(), &prompt.text, from) {
+            *self.goto_index() = index;
+        }
+    }
+
+    /// Type into the goto prompt; guests are matched by VMID, so only
+    /// digits are taken for them.
+    pub fn push_goto_char(&mut self, c: char) {
+        let numeric = self.active_panel != Panel::Nodes;
+        let Some(prompt) = &mut self.goto_prompt else {
+            return;
+        };
+        if numeric && !c.is_ascii_digit() {
+            return;
+        }
+        prompt.text.push(c);
+        self.jump_to_goto_match(0);
+    }
+
+    pub fn pop_goto_char(&mut self) {
+        let Some(prompt) = &mut self.goto_prompt else {
+            return;
+        };
+        prompt.text.pop();
+        if prompt.text.is_empty() {
+            let previous = prompt.previous.clone();
+            self.select_ids(previous);
+        } else {
+            self.jump_to_goto_match(0);
+        }
+    }
+
+    /// Matches of the goto prompt, and which of them is selected (from 1).
+    pub fn goto_matches(&self) -> (usize, Option<usize>) {
+        let Some(prompt) = &self.goto_prompt else {
+            return (0, None);
+        };
+        let selected = match self.active_panel {
+            Panel::Nodes => self.node_index,
+            _ => self.container_index,
+        };
+        let prefix = prompt.text.to_lowercase();
+        let matches: Vec<usize> = self
+            .goto_labels()
+            .iter()
+            .enumerate()
+            .filter(|(_, label)| label.to_lowercase().starts_with(&prefix))
+            .map(|(i, _)| i)
+            .collect();
+        let position = matches.iter().position(|&i| i == selected).map(|p| p + 1);
+        (matches.len(), position)
+    }
+
+    /// Enter: keep the selection. With several matches the prompt stays
+    /// for further Enters to cycle through them.
+    pub fn confirm_goto(&mut self) {
+        let Some(prompt) = &self.goto_prompt else {
+            return;
+        };
+        if prompt.confirmed {
+            let next = *self.goto_index() + 1;
+            self.jump_to_goto_match(next);
+        } else if !prompt.text.is_empty() && self.goto_matches().0 > 1 {
+            if let Some(prompt) = &mut self.goto_prompt {
+                prompt.confirmed = true;
+            }
+        } else {
+            self.goto_prompt = None;
+        }
+    }
+
+    /// Esc: close the prompt and go back to the previous selection.
+    pub fn cancel_goto(&mut self) {
+        if let Some(prompt) = self.goto_prompt.take() {
+            self.select_ids(prompt.previous);
+        }
+    }
+
+    /// Forget marks on guests that are gone, and leave visual mode when its
+    /// anchor is no longer listed.
+    fn sweep_marks(&mut self) {
+        let ids: HashSet<&GuestId> = self.containers.iter().map(|c| &c.id).collect();
+        self.marked.retain(|id| ids.contains(id));
+        if self.visual_anchor.is_some() && self.visual_range().is_none() {
+            self.visual_anchor = None;
+        }
+    }
+
+    /// Plan a drain of the selected node and show it for confirmation.
+    pub fn plan_drain(&mut self) {
+        if self.active_panel != Panel::Nodes {
+            return;
+        }
+        if let Some(node) = self.selected_node() {
+            self.drain = Some(drain::plan(node, &self.nodes, &self.containers, self.units));
+        }
+    }
+
+    pub fn confirm_drain(&mut self) {
+        if let Some(drain) = &mut self.drain
+            && drain.phase == DrainPhase::Confirm
+        {
+            drain.phase = if drain.steps.is_empty() {
+                DrainPhase::Finished
+            } else {
+                DrainPhase::Running
+            };
+        }
+    }
+
+    /// Dismiss the plan or the final report; a running drain can't be closed.
+    pub fn close_drain(&mut self) {
+        if self
+            .drain
+            .as_ref()
+            .is_some_and(|d| d.phase != DrainPhase::Running)
+        {
+            self.drain = None;
+        }
+    }
+
+    /// Move a running drain forward: start the next guest's task, or poll the
+    /// current one. Stops at the first failure.
+    pub fn advance_drain(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(mut drain) = self.drain.take() else {
+            return;
+        };
+        if drain.phase == DrainPhase::Running {
+            self.step_drain(&mut drain, providers, now);
+            if drain.phase == DrainPhase::Finished {
+                // Show where everything ended up
+                self.refresh(providers);
+            }
+        }
+        self.drain = Some(drain);
+    }
+
+    fn step_drain(&mut self, drain: &mut Drain, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(index) = drain.current() else {
+            drain.phase = DrainPhase::Finished;
+            return;
+        };
+        let Some(provider) = providers.iter().find(|p| p.name() == drain.node.provider) else {
+            drain.fail(index, "provider is no longer configured".to_string());
+            return;
+        };
+
+        let step = &drain.steps[index];
+        match &step.state {
+            StepState::Pending => {
+                let Some(container) = self.container_by_id(&step.guest) else {
+                    drain.fail(index, "guest is no longer listed".to_string());
+                    return;
+                };
+                let started = match &step.action {
+                    DrainAction::Migrate { target } => provider.migrate_guest(container, target),
+                    DrainAction::Shutdown { .. } => provider.shutdown_guest(container),
+                    // current() never returns a skipped step
+                    DrainAction::Skip { .. } => return,
+                };
+                drain.last_poll = Some(now);
+                match started {
+                    Ok(task) => {
+                        self.audit_step(provider.as_ref(), step, AuditEvent::Dispatched, Ok(&task));
+                        self.note_dry_run(&task);
+                        drain.steps[index].state = StepState::Running { task };
+                    }
+                    Err(e) => {
+                        let error = e.to_string();
+                        self.audit_step(
+                            provider.as_ref(),
+                            step,
+                            AuditEvent::Dispatched,
+                            Err(&error),
+                        );
+                        drain.fail(index, error);
+                    }
+                }
+            }
+            StepState::Running { task } => {
+                let Some(outcome) = Self::poll_task(
+                    provider.as_ref(),
+                    &drain.node.name,
+                    task,
+                    &mut drain.last_poll,
+                    now,
+                ) else {
+                    return;
+                };
+                let action = step.action.describe();
+                let label = step.label.clone();
+                match self.finish_task(provider.as_ref(), &action, &label, outcome, now) {
+                    Err(error) => drain.fail(index, error),
+                    Ok(()) => {
+                        drain.steps[index].state = StepState::Done;
+                        if drain.current().is_none() {
+                            drain.phase = DrainPhase::Finished;
+                        }
+                    }
+                }
+            }
+            StepState::Done | StepState::Failed(_) => {}
+        }
+    }
+
+    /// Poll `task` on `node` once `TASK_POLL_INTERVAL` has passed since
+    /// `last_poll`: `None` while it runs or isn't due yet, else how it ended.
+    fn poll_task(
+        provider: &dyn Provider,
+        node: &str,
+        task: &str,
+        last_poll: &mut Option<Instant>,
+        now: Instant,
+    ) -> Option<Result<(), String>> {
+        if last_poll.is_some_and(|t| now.saturating_duration_since(t) < TASK_POLL_INTERVAL) {
+            return None;
+        }
+        *last_poll = Some(now);
+        match provider.task_status(node, task) {
+            Ok(TaskStatus::Running) => None,
+            Ok(TaskStatus::Succeeded) => Some(Ok(())),
+            Ok(TaskStatus::Failed(message)) => Some(Err(message)),
+            Err(e) => Some(Err(format!("cannot check task: {}", e))),
+        }
+    }
+
+    /// Record how an action's task ended in the audit log and schedule the
+    /// follow-up refreshes; returns the outcome for the caller's own state.
+    fn finish_task(
+        &mut self,
+        provider: &dyn Provider,
+        action: &str,
+        label: &str,
+        outcome: Result<(), String>,
+        now: Instant,
+    ) -> Result<(), String> {
+        let result = outcome.as_ref().map(|_| "done").map_err(String::as_str);
+        self.audit_action(provider, action, label, AuditEvent::Completed, result);
+        self.schedule.action_completed(now);
+        outcome
+    }
+
+    /// Show the call a dry-run action stood in for; real tasks show nothing.
+    fn note_dry_run(&mut self, task: &str) {
+        if let Some(call) = providers::dry_run_call(task) {
+            self.notice = Some(format!("Dry run: would {}", call));
+        }
+    }
+
+    fn audit_step(
+        &mut self,
+        provider: &dyn Provider,
+        step: &DrainStep,
+        event: AuditEvent,
+        result: Result<&str, &str>,
+    ) {
+        let action = step.action.describe();
+        self.audit_action(provider, &action, &step.label, event, result);
+    }
+
+    /// Record an action in the audit log; the first write failure is
+    /// reported like a provider error.
+    fn audit_action(
+        &mut self,
+        provider: &dyn Provider,
+        action: &str,
+        target: &str,
+        event: AuditEvent,
+        result: Result<&str, &str>,
+    ) {
+        log::info!(
+            "{}: {} {} {:?}: {}",
+            provider.name(),
+            action,
+            target,
+            event,
+            result.unwrap_or_else(|e| e)
+        );
+        let Some(log) = self.audit.as_mut() else {
+            return;
+        };
+        let entry = AuditEntry {
+            ts: unix_now() as i64,
+            user: provider
+                .user()
+                .map(str::to_string)
+                .unwrap_or_else(audit::local_user),
+            provider: provider.name().to_string(),
+            action: action.to_string(),
+            target: target.to_string(),
+            event,
+            ok: result.is_ok(),
+            result: result.unwrap_or_else(|e| e).to_string(),
+        };
+        if let Some(warning) = log.append(&entry) {
+            self.record_error("audit", warning);
+        }
+    }
+
+    /// The selected VM or container, if an action can be sent for it: still
+    /// on its provider and not locked. Errors start with `verb`.
+    fn actionable_guest(&self, verb: &str) -> Result<&Container, String> {
+        let container = match self.active_panel {
+            Panel::Containers => self.selected_container(),
+            _ => None,
+        }
+        .filter(|c| matches!(c.container_type, ContainerType::VM | ContainerType::LXC))
+        .ok_or_else(|| format!("{}: select a VM or container first", verb))?;
+        if container.missing {
+            return Err(format!(
+                "{}: {} is not on the provider",
+                verb, container.name
+            ));
+        }
+        if let Some(message) = container.lock_message() {
+            return Err(format!("{}: {}", verb, message));
+        }
+        Ok(container)
+    }
+
+    /// Open the clone form for the selected guest, suggesting the next free
+    /// VMID from the provider.
+    fn open_clone(&mut self, providers: &[Box<dyn Provider>]) -> Result<(), String> {
+        let container = self.actionable_guest("clone")?;
+        let provider = providers
+            .iter()
+            .find(|p| p.name() == container.provider())
+            .ok_or_else(|| format!("clone: provider {} is gone", container.provider()))?;
+        let suggested = provider.next_vmid();
+        let mut form = CloneForm::new(container, &self.nodes, suggested.as_ref().ok().copied());
+        if let Err(e) = suggested {
+            form.error = Some(format!("no VMID suggested: {}", e));
+        }
+        self.clone = Some(form);
+        Ok(())
+    }
+
+    /// Validate the form and start the clone; a request that can't be sent
+    /// leaves the form open with the reason.
+    pub fn submit_clone(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(mut form) = self.clone.take() else {
+            return;
+        };
+        if form.phase == ClonePhase::Editing {
+            self.start_clone(&mut form, providers, now);
+        }
+        self.clone = Some(form);
+    }
+
+    fn start_clone(&mut self, form: &mut CloneForm, providers: &[Box<dyn Provider>], now: Instant) {
+        let request = match form.validate(&self.containers) {
+            Ok(request) => request,
+            Err(e) => {
+                form.error = Some(e);
+                return;
+            }
+        };
+        let Some(container) = self.container_by_id(&form.source) else {
+            form.error = Some("guest is no longer listed".to_string());
+            return;
+        };
+        let Some(provider) = providers.iter().find(|p| p.name() == form.source.provider) else {
+            form.error = Some("provider is no longer configured".to_string());
+            return;
+        };
+        let action = format!("clone to {} {}", request.vmid, request.name);
+        let started = provider.clone_guest(container, &request);
+        form.last_poll = Some(now);
+        match started {
+            Ok(task) => {
+                self.audit_action(
+                    provider.as_ref(),
+                    &action,
+                    &form.label,
+                    AuditEvent::Dispatched,
+                    Ok(&task),
+                );
+                self.note_dry_run(&task);
+                form.phase = ClonePhase::Running { task };
+            }
+            Err(e) => {
+                let error = e.to_string();
+                self.audit_action(
+                    provider.as_ref(),
+                    &action,
+                    &form.label,
+                    AuditEvent::Dispatched,
+                    Err(&error),
+                );
+                form.error = Some(error);
+            }
+        }
+    }
+
+    /// Dismiss the form or the result; a running clone can't be closed.
+    pub fn close_clone(&mut self) {
+        if !self
+            .clone
+            .as_ref()
+            .is_some_and(|c| matches!(c.phase, ClonePhase::Running { .. }))
+        {
+            self.clone = None;
+        }
+    }
+
+    /// Poll a running clone task. The new guest shows up with the refresh
+    /// that follows it finishing.
+    pub fn advance_clone(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(form) = self.clone.as_mut() else {
+            return;
+        };
+        let ClonePhase::Running { task } = &form.phase else {
+            return;
+        };
+        let Some(provider) = providers.iter().find(|p| p.name() == form.source.provider) else {
+            form.phase = ClonePhase::Failed("provider is no longer configured".to_string());
+            return;
+        };
+        let Some(outcome) = Self::poll_task(
+            provider.as_ref(),
+            &form.source_node,
+            task,
+            &mut form.last_poll,
+            now,
+        ) else {
+            return;
+        };
+        let (label, action) = (
+            form.label.clone(),
+            format!("clone to {} {}", form.vmid.trim(), form.name.trim()),
+        );
+        let phase = match self.finish_task(provider.as_ref(), &action, &label, outcome, now) {
+            Ok(()) => ClonePhase::Done,
+            Err(error) => ClonePhase::Failed(error),
+        };
+        if let Some(form) = self.clone.as_mut() {
+            form.phase = phase;
+        }
+    }
+
+    /// Open the resources form for the selected guest, prefilled from its
+    /// config as the provider has it now.
+    fn open_resources(&mut self, providers: &[Box<dyn Provider>]) -> Result<(), String> {
+        let container = self.actionable_guest("resources")?;
+        let provider = providers
+            .iter()
+            .find(|p| p.name() == container.provider())
+            .ok_or_else(|| format!("resources: provider {} is gone", container.provider()))?;
+        let current = provider
+            .fetch_guest_resources(container)
+            .map_err(|e| format!("resources: {}", e))?;
+        let node = self
+            .nodes
+            .iter()
+            .find(|n| n.provider() == container.provider() && n.name == container.node);
+        self.resources = Some(ResourceForm::new(container, node, current));
+        Ok(())
+    }
+
+    /// Validate the form and change the guest's config; a change that
+    /// can't be sent leaves the form open with the reason.
+    pub fn submit_resources(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(mut form) = self.resources.take() else {
+            return;
+        };
+        if form.phase == ResourcePhase::Editing {
+            self.save_resources(&mut form, providers, now);
+        }
+        self.resources = Some(form);
+    }
+
+    fn save_resources(
+        &mut self,
+        form: &mut ResourceForm,
+        providers: &[Box<dyn Provider>],
+        now: Instant,
+    ) {
+        let request = match form.validate() {
+            Ok(request) => request,
+            Err(e) => {
+                form.error = Some(e);
+                return;
+            }
+        };
+        let Some(container) = self.container_by_id(&form.guest) else {
+            form.error = Some("guest is no longer listed".to_string());
+            return;
+        };
+        let Some(provider) = providers.iter().find(|p| p.name() == form.guest.provider) else {
+            form.error = Some("provider is no longer configured".to_string());
+            return;
+        };
+        let saved = provider
+            .set_guest_resources(container, &request)
+            .map_err(|e| e.to_string());
+        let pending = form.pending(&request);
+        let result = match &saved {
+            Ok(task) if !task.is_empty() => Ok(task.as_str()),
+            Ok(_) if pending.is_some() => Ok("pending until restart"),
+            Ok(_) => Ok("applied"),
+            Err(e) => Err(e.as_str()),
+        };
+        // Old and new values both go in the log, as the config keeps only the new
+        let action = format!("set {}", form.describe(&request));
+        self.audit_action(
+            provider.as_ref(),
+            &action,
+            &form.label,
+            AuditEvent::Completed,
+            result,
+        );
+        form.phase = match saved {
+            Ok(task) => {
+                self.note_dry_run(&task);
+                self.schedule.action_completed(now);
+                ResourcePhase::Saved { pending }
+            }
+            Err(e) => ResourcePhase::Failed(e),
+        };
+    }
+
+    pub fn close_resources(&mut self) {
+        self.resources = None;
+    }
+
+    /// Ask before starting the selected stopped guest, with a warning if
+    /// its node had less memory free than it wants at the last refresh.
+    fn open_start(&mut self) -> Result<(), String> {
+        let container = self.actionable_guest("start")?;
+        if container.is_template {
+            return Err(format!("start: {} is a template", container.name));
+        }
+        if container.status != ContainerStatus::Stopped {
+            return Err(format!("start: {} is already running", container.name));
+        }
+        let node = self
+            .nodes
+            .iter()
+            .find(|n| n.provider() == container.provider() && n.name == container.node);
+        self.start = Some(GuestStart::new(container, node));
+        Ok(())
+    }
+
+    /// Start the confirmed guest; a shortfall never stops it.
+    pub fn confirm_start(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(mut start) = self.start.take() else {
+            return;
+        };
+        if start.phase == StartPhase::Confirm {
+            start.phase = match (
+                self.container_by_id(&start.guest),
+                providers.iter().find(|p| p.name() == start.guest.provider),
+            ) {
+                (Some(container), Some(provider)) => {
+                    let started = provider.start_guest(container);
+                    let result = started.as_deref().map_err(|e| e.to_string());
+                    self.audit_action(
+                        provider.as_ref(),
+                        "start",
+                        &start.label,
+                        AuditEvent::Dispatched,
+                        result.as_deref().map_err(String::as_str),
+                    );
+                    match result {
+                        Ok(task) => {
+                            self.note_dry_run(task);
+                            StartPhase::Running {
+                                task: task.to_string(),
+                            }
+                        }
+                        Err(e) => StartPhase::Failed(e),
+                    }
+                }
+                (None, _) => StartPhase::Failed("guest is no longer listed".to_string()),
+                (_, None) => StartPhase::Failed("provider is no longer configured".to_string()),
+            };
+            start.last_poll = Some(now);
+        }
+        self.start = Some(start);
+    }
+
+    /// Dismiss the confirmation or the result; a running start can't be
+    /// closed.
+    pub fn close_start(&mut self) {
+        if !self
+            .start
+            .as_ref()
+            .is_some_and(|s| matches!(s.phase, StartPhase::Running { .. }))
+        {
+            self.start = None;
+        }
+    }
+
+    /// Poll a running start task; the guest shows as running with the
+    /// refresh that follows it finishing.
+    pub fn advance_start(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(start) = self.start.as_mut() else {
+            return;
+        };
+        let StartPhase::Running { task } = &start.phase else {
+            return;
+        };
+        let Some(provider) = providers.iter().find(|p| p.name() == start.guest.provider) else {
+            start.phase = StartPhase::Failed("provider is no longer configured".to_string());
+            return;
+        };
+        let Some(outcome) = Self::poll_task(
+            provider.as_ref(),
+            &start.node,
+            task,
+            &mut start.last_poll,
+            now,
+        ) else {
+            return;
+        };
+        let label = start.label.clone();
+        let phase = match self.finish_task(provider.as_ref(), "start", &label, outcome, now) {
+            Ok(()) => StartPhase::Done,
+            Err(error) => StartPhase::Failed(error),
+        };
+        if let Some(start) = self.start.as_mut() {
+            start.phase = phase;
+        }
+    }
+
+    /// Ask before rebooting or shutting down the selected node, previewing
+    /// its running guests from the last refresh and which of them the HA
+    /// manager will move (read from the provider now).
+    fn open_node_power(
+        &mut self,
+        command: NodePower,
+        providers: &[Box<dyn Provider>],
+    ) -> Result<(), String> {
+        let verb = command.as_str();
+        let node = match self.active_panel {
+            Panel::Nodes => self.selected_node(),
+            _ => None,
+        }
+        .ok_or_else(|| format!("{}: select a node first", verb))?;
+        if node.status != NodeStatus::Online {
+            return Err(format!("{}: {} is offline", verb, node.name));
+        }
+        let provider = providers
+            .iter()
+            .find(|p| p.name() == node.provider())
+            .ok_or_else(|| format!("{}: provider is no longer configured", verb))?;
+        let ha = provider.fetch_ha_resources().map_err(|e| e.to_string());
+        let guests: Vec<&Container> = self
+            .allocated_guests()
+            .filter(|c| {
+                c.provider() == node.provider()
+                    && c.node == node.name
+                    && c.status == ContainerStatus::Running
+            })
+            .collect();
+        let memory = self
+            .allocations()
+            .get(&node.id)
+            .map_or(0, |allocation| allocation.memory);
+        let impact = GuestImpact::new(&guests, ha.as_deref().map_err(Clone::clone), memory);
+        self.node_power = Some(NodePowerRequest::new(node.id.clone(), command, impact));
+        Ok(())
+    }
+
+    /// Send the command once the node's name has been typed; until then
+    /// Enter does nothing.
+    pub fn confirm_node_power(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(mut request) = self.node_power.take() else {
+            return;
+        };
+        if request.phase == NodePowerPhase::Confirm && request.confirmed() {
+            let command = request.command;
+            request.phase = match providers.iter().find(|p| p.name() == request.node.provider) {
+                Some(provider) => {
+                    let sent = provider
+                        .node_power(&request.node.name, command)
+                        .map_err(|e| e.to_string());
+                    self.audit_action(
+                        provider.as_ref(),
+                        command.describe(),
+                        &request.node.name,
+                        AuditEvent::Dispatched,
+                        sent.as_deref()
+                            .map(|task| if task.is_empty() { "sent" } else { task })
+                            .map_err(String::as_str),
+                    );
+                    match sent {
+                        Ok(task) => {
+                            self.note_dry_run(&task);
+                            self.schedule.action_completed(now);
+                            NodePowerPhase::Sent
+                        }
+                        Err(e) => NodePowerPhase::Failed(e),
+                    }
+                }
+                None => NodePowerPhase::Failed("provider is no longer configured".to_string()),
+            };
+        }
+        self.node_power = Some(request);
+    }
+
+    pub fn close_node_power(&mut self) {
+        self.node_power = None;
+    }
+
+    /// Queue `action` for the marked guests it applies to, or else for the
+    /// selected guest, and ask for confirmation.
+    fn queue_marked(&mut self, action: QueuedAction) -> Result<(), String> {
+        let verb = action.describe();
+        let candidates: Vec<&Container> = if self.marked.is_empty() {
+            match self.active_panel {
+                Panel::Containers => self.selected_container().into_iter().collect(),
+                _ => Vec::new(),
+            }
+        } else {
+            self.containers
+                .iter()
+                .filter(|c| self.marked.contains(&c.id))
+                .collect()
+        };
+        if candidates.is_empty() {
+            return Err(format!("{}: select or mark guests first", verb));
+        }
+        let guests: Vec<&Container> = candidates
+            .iter()
+            .copied()
+            .filter(|c| action.applies_to(c))
+            .collect();
+        let skipped = candidates.len() - guests.len();
+        if guests.is_empty() {
+            return Err(format!(
+                "{}: nothing to do ({} skipped as locked or not {})",
+                verb,
+                skipped,
+                match action {
+                    QueuedAction::Start => "stopped",
+                    QueuedAction::Shutdown => "running",
+                }
+            ));
+        }
+        self.queue = Some(ActionQueue::new(action, &guests, self.action_concurrency));
+        if skipped > 0 {
+            self.notice = Some(format!("{}: skipped {} guest(s)", verb, skipped));
+        }
+        Ok(())
+    }
+
+    pub fn confirm_queue(&mut self) {
+        if let Some(queue) = &mut self.queue
+            && queue.phase == QueuePhase::Confirm
+        {
+            queue.phase = QueuePhase::Running;
+        }
+    }
+
+    /// Before it runs, drop the queue; while it runs, cancel what hasn't
+    /// started; once finished, dismiss the report.
+    pub fn cancel_queue(&mut self) {
+        let Some(queue) = &mut self.queue else {
+            return;
+        };
+        match queue.phase {
+            QueuePhase::Running => {
+                let cancelled = queue.cancel_pending();
+                if cancelled > 0 {
+                    self.notice = Some(format!("Cancelled {} queued action(s)", cancelled));
+                }
+            }
+            QueuePhase::Confirm | QueuePhase::Finished => self.queue = None,
+        }
+    }
+
+    /// Start queued tasks as slots free up and poll the running ones. Each
+    /// item fails on its own; the rest carry on.
+    pub fn advance_queue(&mut self, providers: &[Box<dyn Provider>], now: Instant) {
+        let Some(mut queue) = self.queue.take() else {
+            return;
+        };
+        if queue.phase == QueuePhase::Running {
+            for index in 0..queue.items.len() {
+                self.poll_queue_item(&mut queue, index, providers, now);
+            }
+            for index in queue.startable() {
+                self.start_queue_item(&mut queue, index, providers, now);
+            }
+            queue.finish_if_done();
+        }
+        self.queue = Some(queue);
+    }
+
+    fn start_queue_item(
+        &mut self,
+        queue: &mut ActionQueue,
+        index: usize,
+        providers: &[Box<dyn Provider>],
+        now: Instant,
+    ) {
+        let action = queue.action;
+        let item = &mut queue.items[index];
+        let Some(provider) = providers.iter().find(|p| p.name() == item.guest.provider) else {
+            item.state = ItemState::Failed("provider is no longer configured".to_string());
+            return;
+        };
+        let Some(container) = self.container_by_id(&item.guest) else {
+            item.state = ItemState::Failed("guest is no longer listed".to_string());
+            return;
+        };
+        let started = match action {
+            QueuedAction::Start => provider.start_guest(container),
+            QueuedAction::Shutdown => provider.shutdown_guest(container),
+        };
+        item.last_poll = Some(now);
+        let label = item.label.clone();
+        match started {
+            Ok(task) => {
+                self.audit_action(
+                    provider.as_ref(),
+                    action.describe(),
+                    &label,
+                    AuditEvent::Dispatched,
+                    Ok(&task),
+                );
+                self.note_dry_run(&task);
+                queue.items[index].state = ItemState::Running { task };
+            }
+            Err(e) => {
+                let error = e.to_string();
+                self.audit_action(
+                    provider.as_ref(),
+                    action.describe(),
+                    &label,
+                    AuditEvent::Dispatched,
+                    Err(&error),
+                );
+                queue.items[index].state = ItemState::Failed(error);
+            }
+        }
+    }
+
+    fn poll_queue_item(
+        &mut self,
+        queue: &mut ActionQueue,
+        index: usize,
+        providers: &[Box<dyn Provider>],
+        now: Instant,
+    ) {
+        let action = queue.action;
+        let item = &mut queue.items[index];
+        let ItemState::Running { task } = &item.state else {
+            return;
+        };
+        let Some(provider) = providers.iter().find(|p| p.name() == item.guest.provider) else {
+            item.state = ItemState::Failed("provider is no longer configured".to_string());
+            return;
+        };
+        let Some(outcome) = Self::poll_task(
+            provider.as_ref(),
+            &item.node,
+            task,
+            &mut item.last_poll,
+            now,
+        ) else {
+            return;
+        };
+        let label = item.label.clone();
+        queue.items[index].state =
+            match self.finish_task(provider.as_ref(), action.describe(), &label, outcome, now) {
+                Ok(()) => ItemState::Done,
+                Err(error) => ItemState::Failed(error),
+            };
+    }
+
+    /// CPU usage of a guest in the current display mode.
+    pub fn container_cpu(&self, container: &Container) -> f64 {
+        self.scale_cpu(container, container.cpu_usage)
+    }
+
+    /// A CPU figure of `container`, in guest terms, in the current mode.
+    pub fn scale_cpu(&self, container: &Container, guest_percent: f64) -> f64 {
+        match self.cpu_mode {
+            CpuMode::Guest => guest_percent,
+            CpuMode::Host => {
+                let host_cores = self
+                    .nodes
+                    .iter()
+                    .find(|n| n.provider() == container.provider() && n.name == container.node)
+                    .map(|n| n.cpu_cores)
+                    .unwrap_or(0);
+                host_cpu_percent(guest_percent, container.max_cpu, host_cores)
+            }
+        }
+    }
+
+    /// The first failing replication job whose guest is this container.
+    pub fn failing_replication(&self, container: &Container) -> Option<&ReplicationJob> {
+        self.replication_jobs
+            .iter()
+            .find(|j| j.guest == container.id && j.source == container.node && j.is_failing())
+    }
+
+    pub fn clusters(&self) -> impl Iterator<Item = &ClusterStatus> {
+        self.provider_status
+            .iter()
+            .filter_map(|s| s.cluster.as_ref())
+    }
+
+    /// The failing replication job of a guest, unless its alert is snoozed.
+    pub fn replication_alert(&self, container: &Container) -> Option<&ReplicationJob> {
+        self.failing_replication(container)
+            .filter(|_| self.alert_active(&container.id.to_string(), Rule::Replication))
+    }
+
+    /// Whether a node has failing disks and that alert isn't snoozed.
+    pub fn disk_alert(&self, node: &Node) -> bool {
+        !node.disks.problems().is_empty() && self.alert_active(&node.id.to_string(), Rule::Disk)
+    }
+
+    /// Every alert goes through here, so a snooze silences it everywhere.
+    fn alert_active(&self, item: &str, rule: Rule) -> bool {
+        self.snooze_for(item, rule).is_none()
+    }
+
+    /// The snooze holding an item's alert on `rule`, if any. Restart
+    /// snoozes are checked against uptime after each refresh.
+    pub fn snooze_for(&self, item: &str, rule: Rule) -> Option<&Snooze> {
+        self.snoozes
+            .get(&snooze::key(item, rule))
+            .filter(|s| s.is_active(unix_now(), None))
+    }
+
+    pub fn alert_count(&self) -> usize {
+        let failing_jobs = self
+            .replication_jobs
+            .iter()
+            .filter(|j| j.is_failing())
+            .filter(|j| self.alert_active(&j.guest.to_string(), Rule::Replication))
+            .count();
+        let lost_quorum = self.clusters().filter(|c| !c.quorate).count();
+        let failing_disks: usize = self
+            .nodes
+            .iter()
+            .filter(|n| self.alert_active(&n.id.to_string(), Rule::Disk))
+            .map(|n| n.disks.problems().len())
+            .sum();
+        failing_jobs + lost_quorum + failing_disks
+    }
+
+    pub fn quit(&mut self) {
+        self.running = false;
+    }
+
+    pub fn next_panel(&mut self) {
+        let next = self
+            .panels
+            .iter()
+            .position(|&p| p == self.active_panel)
+            .map_or(0, |i| (i + 1) % self.panels.len());
+        self.active_panel = self.panels[next];
+    }
+
+    pub fn select_next(&mut self) {
+        match self.active_panel {
+            Panel::Nodes => {
+                let max = self.filtered_nodes().len().saturating_sub(1);
+                if self.node_index < max {
+                    self.node_index += 1;
+                }
+            }
+            Panel::Groups => {
+                if self.pool_index < self.pools.len() {
+                    self.select_pool(self.pool_index + 1);
+                }
+            }
+            Panel::Containers if self.groups_collapsed => {
+                let starts = self.group_starts();
+                if let Some(&next) = starts.iter().find(|&&s| s > self.container_index) {
+                    self.container_index = next;
+                }
+            }
+            Panel::Containers => {
+                let max = self.filtered_containers().len().saturating_sub(1);
+                if self.container_index < max {
+                    self.container_index += 1;
+                }
+            }
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        match self.active_panel {
+            Panel::Nodes => {
+                self.node_index = self.node_index.saturating_sub(1);
+            }
+            Panel::Groups => {
+                self.select_pool(self.pool_index.saturating_sub(1));
+            }
+            Panel::Containers if self.groups_collapsed => {
+                let starts = self.group_starts();
+                if let Some(&previous) = starts.iter().rev().find(|&&s| s < self.container_index) {
+                    self.container_index = previous;
+                }
+            }
+            Panel::Containers => {
+                self.container_index = self.container_index.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Index of each group's first container in `filtered_containers()`.
+    fn group_starts(&self) -> Vec<usize> {
+        self.container_groups()
+            .iter()
+            .scan(0, |start, group| {
+                let this = *start;
+                *start += group.members.len();
+                Some(this)
+            })
+            .collect()
+    }
+
+    /// Group the containers panel this way, keeping the selected container.
+    pub fn set_group_by(&mut self, group_by: GroupBy) {
+        let selection = self.selected_ids();
+        self.group_by = group_by;
+        if group_by == GroupBy::Off {
+            self.groups_collapsed = false;
+        }
+        self.select_ids(selection);
+    }
+
+    pub fn cycle_group_by(&mut self) {
+        self.set_group_by(self.group_by.next());
+    }
+
+    /// Keeps the selected guest selected if it still passes the filter.
+    pub fn set_recent_filter(&mut self, filter: RecentFilter) {
+        let (_, container) = self.selected_ids();
+        self.recent_filter = filter;
+        self.container_index = 0;
+        self.select_ids((None, container));
+    }
+
+    /// Keeps the selected guest selected unless it is a template being hidden.
+    pub fn toggle_templates(&mut self) {
+        let (_, container) = self.selected_ids();
+        self.hide_templates = !self.hide_templates;
+        self.container_index = 0;
+        self.select_ids((None, container));
+    }
+
+    /// Guests passing the recent filter, whatever the search; None when the
+    /// filter is off.
+    pub fn recent_restarts(&self) -> Option<usize> {
+        if self.recent_filter == RecentFilter::Off {
+            return None;
+        }
+        Some(
+            self.containers
+                .iter()
+                .filter(|c| self.recent_filter.matches(c))
+                .count(),
+        )
+    }
+
+    /// Collapse the groups to their summary rows, or expand them again. The
+    /// selection moves to the first container of its group.
+    pub fn toggle_groups_collapsed(&mut self) {
+        if self.group_by == GroupBy::Off {
+            return;
+        }
+        self.groups_collapsed = !self.groups_collapsed;
+        if self.groups_collapsed
+            && let Some(&start) = self
+                .group_starts()
+                .iter()
+                .rev()
+                .find(|&&s| s <= self.container_index)
+        {
+            self.container_index = start;
+        }
+    }
+
+    pub fn cycle_sort(&mut self) {
+        self.sort_field = self.sort_field.next();
+        self.apply_sort();
+    }
+
+    /// Sort by the `n`th sortable column on screen (from 1), or reverse the
+    /// order if it is sorted by that already.
+    pub fn sort_by_column(&mut self, n: usize) -> Result<(), String> {
+        let field = n
+            .checked_sub(1)
+            .and_then(|i| self.sort_columns.borrow().get(i).copied())
+            .ok_or_else(|| format!("column: no column {}", n))?;
+        if field == self.sort_field {
+            self.toggle_sort_order();
+        } else {
+            self.sort_field = field;
+            self.apply_sort();
+        }
+        Ok(())
+    }
+
+    pub fn toggle_problems_first(&mut self) {
+        self.problems_first = !self.problems_first;
+        self.apply_sort();
+    }
+
+    pub fn toggle_group_sort_by_node(&mut self) {
+        self.group_sort_by_node = !self.group_sort_by_node;
+        self.apply_sort();
+    }
+
+    pub fn toggle_sort_order(&mut self) {
+        self.sort_ascending = !self.sort_ascending;
+        self.apply_sort();
+    }
+
+    pub fn enter_search_mode(&mut self) {
+        self.input_mode = InputMode::Search;
+    }
+
+    pub fn exit_search_mode(&mut self) {
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// Enter in search mode: keep the query, and if nothing in the focused
+    /// list matches but something in the other does, focus that one.
+    pub fn submit_search(&mut self) {
+        self.exit_search_mode();
+        if !self.search_switches_panel || self.search_query.is_empty() {
+            return;
+        }
+        let nodes = self.filtered_nodes().len();
+        let containers = self.filtered_containers().len();
+        let (target, matches) = match self.active_panel {
+            Panel::Nodes if nodes == 0 => (Panel::Containers, containers),
+            Panel::Containers if containers == 0 => (Panel::Nodes, nodes),
+            _ => return,
+        };
+        if matches == 0 || !self.panels.contains(&target) {
+            return;
+        }
+        self.active_panel = target;
+        self.node_index = 0;
+        self.container_index = 0;
+        let name = match target {
+            Panel::Nodes => "Nodes",
+            _ => "Containers",
+        };
+        self.notice = Some(format!(
+            "switched to {}: {} {}",
+            name,
+            matches,
+            if matches == 1 { "match" } else { "matches" }
+        ));
+    }
+
+    /// Read the presets, notes and snoozes saved at `path`. A broken state
+    /// file is reported and left alone; saving fails until it is fixed.
+    pub fn load_state(&mut self, path: Option<PathBuf>) {
+        self.notes = match path.as_deref().map(notes::load) {
+            Some(Ok(notes)) => notes,
+            Some(Err(e)) => {
+                self.record_error("notes", e);
+                Notes::new()
+            }
+            None => Notes::new(),
+        };
+        self.snoozes = match path.as_deref().map(snooze::load) {
+            Some(Ok(snoozes)) => snoozes,
+            Some(Err(e)) => {
+                self.record_error("snooze", e);
+                Snoozes::new()
+            }
+            None => Snoozes::new(),
+        };
+        self.load_presets(path);
+    }
+
+    pub fn load_presets(&mut self, path: Option<PathBuf>) {
+        self.presets = match path.as_deref().map(presets::load) {
+            Some(Ok(presets)) => presets,
+            Some(Err(e)) => {
+                self.record_error("presets", e);
+                Presets::new()
+            }
+            None => Presets::new(),
+        };
+        self.state_path = path;
+    }
+
+    /// The query and filter toggles as they are now.
+    pub fn current_preset(&self) -> Preset {
+        Preset {
+            query: self.search_query.clone(),
+            case_sensitive: self.search_options.case_sensitive,
+            whole_word: self.search_options.whole_word,
+            recent: self.recent_filter,
+            hide_templates: self.hide_templates,
+        }
+    }
+
+    /// `Ctrl+s` in search mode.
+    pub fn open_preset_prompt(&mut self) {
+        self.preset_prompt = Some(PresetPrompt::default());
+    }
+
+    pub fn close_preset_prompt(&mut self) {
+        self.preset_prompt = None;
+    }
+
+    /// Enter in the name prompt. A taken name asks for a second Enter; then
+    /// the preset is saved and search mode ends as with Enter.
+    pub fn submit_preset_name(&mut self) {
+        let Some(prompt) = &mut self.preset_prompt else {
+            return;
+        };
+        let name = prompt.name.trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        if self.presets.contains_key(&name) && !prompt.confirm_overwrite {
+            prompt.confirm_overwrite = true;
+            return;
+        }
+        self.preset_prompt = None;
+        self.save_preset(&name);
+        self.exit_search_mode();
+    }
+
+    /// Save the current filters as `name`, replacing any preset of that name.
+    pub fn save_preset(&mut self, name: &str) {
+        self.presets.insert(name.to_string(), self.current_preset());
+        if self.persist("presets", |path, app| presets::save(path, &app.presets)) {
+            self.notice = Some(format!("Saved preset {}", name));
+        }
+    }
+
+    /// Replace the query and all filter toggles with those of preset `name`.
+    pub fn apply_preset(&mut self, name: &str) -> Result<(), String> {
+        let preset = self
+            .presets
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no preset named {}", name))?;
+        self.search_query = preset.query.clone();
+        self.search_options = preset.options();
+        self.recent_filter = preset.recent;
+        self.hide_templates = preset.hide_templates;
+        self.node_index = 0;
+        self.container_index = 0;
+        self.notice = Some(format!("Applied preset {}", name));
+        Ok(())
+    }
+
+    pub fn delete_preset(&mut self, name: &str) -> Result<(), String> {
+        self.presets
+            .remove(name)
+            .ok_or_else(|| format!("no preset named {}", name))?;
+        if self.persist("presets", |path, app| presets::save(path, &app.presets)) {
+            self.notice = Some(format!("Deleted preset {}", name));
+        }
+        Ok(())
+    }
+
+    /// Write one feature's table of the state file with `save`; false (and
+    /// logged under `section`) if it could only be kept for this session.
+    fn persist(
+        &mut self,
+        section: &str,
+        save: impl FnOnce(&Path, &Self) -> Result<(), String>,
+    ) -> bool {
+        let saved = match &self.state_path {
+            Some(path) => save(path, self),
+            None => Err("no state file ($HOME is unset)".to_string()),
+        };
+        match saved {
+            Ok(()) => true,
+            Err(e) => {
+                self.record_error(section, format!("kept for this session only: {}", e));
+                false
+            }
+        }
+    }
+
+    /// `F`: the presets popup, with the first preset selected.
+    pub fn toggle_presets_popup(&mut self) {
+        self.presets_popup = match self.presets_popup {
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+
+    pub fn select_next_preset(&mut self) {
+        if let Some(index) = &mut self.presets_popup {
+            *index = (*index + 1).min(self.presets.len().saturating_sub(1));
+        }
+    }
+
+    pub fn select_previous_preset(&mut self) {
+        if let Some(index) = &mut self.presets_popup {
+            *index = index.saturating_sub(1);
+        }
+    }
+
+    /// Apply the `index`th preset (Enter, or a number key from 1) and close
+    /// the popup.
+    pub fn apply_preset_at(&mut self, index: usize) {
+        if let Some(name) = self.presets.keys().nth(index).cloned() {
+            self.presets_popup = None;
+            // The name comes from the map, so it is there
+            let _ = self.apply_preset(&name);
+        }
+    }
+
+    /// `d` in the popup.
+    pub fn delete_selected_preset(&mut self) {
+        let Some(index) = self.presets_popup else {
+            return;
+        };
+        if let Some(name) = self.presets.keys().nth(index).cloned() {
+            let _ = self.delete_preset(&name);
+            self.presets_popup = Some(index.min(self.presets.len().saturating_sub(1)));
+        }
+    }
+
+    /// `P`: the providers popup, with the first provider selected.
+    pub fn toggle_providers_popup(&mut self) {
+        self.providers_popup = match self.providers_popup {
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+
+    pub fn select_next_provider(&mut self) {
+        if let Some(index) = &mut self.providers_popup {
+            *index = (*index + 1).min(self.provider_status.len().saturating_sub(1));
+        }
+    }
+
+    pub fn select_previous_provider(&mut self) {
+        if let Some(index) = &mut self.providers_popup {
+            *index = index.saturating_sub(1);
+        }
+    }
+
+    /// Enter in the popup: list only the selected provider's items, or all
+    /// of them again if it already is the only one. Closes the popup.
+    pub fn filter_selected_provider(&mut self) {
+        let Some(name) = self
+            .providers_popup
+            .and_then(|i| self.provider_status.get(i))
+            .map(|s| s.name.clone())
+        else {
+            return;
+        };
+        self.providers_popup = None;
+        self.provider_filter = match self.provider_filter.take() {
+            Some(current) if current == name => None,
+            _ => Some(name),
+        };
+        self.node_index = 0;
+        self.container_index = 0;
+        self.select_pool(0);
+    }
+
+    /// `d` in the popup: stop polling the selected provider for this
+    /// session and hide its items, or turn it back on and fetch it now.
+    pub fn toggle_selected_provider(&mut self) {
+        let Some(index) = self.providers_popup else {
+            return;
+        };
+        let Some(status) = self.provider_status.get_mut(index) else {
+            return;
+        };
+        status.disabled = !status.disabled;
+        let name = status.name.clone();
+        if status.dormant {
+            // Built by the main loop, which calls `provider_started`
+            self.notice = Some(if status.disabled {
+                format!("{} left off", name)
+            } else {
+                format!("{} starting", name)
+            });
+        } else if status.disabled {
+            status.last_error = None;
+            status.backoff.record_success();
+            let selection = self.selected_ids();
+            self.nodes.retain(|n| n.provider() != name);
+            self.containers.retain(|c| c.provider() != name);
+            self.hidden_duplicates.0.retain(|n| n.provider() != name);
+            self.hidden_duplicates.1.retain(|c| c.provider() != name);
+            self.duplicates.retain(|d| !d.involves(&name));
+            self.data_changed();
+            self.aggregate_pools();
+            self.select_ids(selection);
+            self.clamp_selection();
+            self.notice = Some(format!("{} disabled for this session", name));
+        } else {
+            self.schedule.request();
+            self.notice = Some(format!("{} enabled, refreshing", name));
+        }
+    }
+
+    /// Mark the providers left out at startup, which are not polled until
+    /// turned on. Statuses are added in the providers' order.
+    pub fn mark_dormant(&mut self, providers: &[Box<dyn Provider>], dormant: &[String]) {
+        for provider in providers {
+            let status = self.provider_status_entry(provider.name());
+            if dormant.iter().any(|d| d == &status.name) {
+                status.disabled = true;
+                status.dormant = true;
+            }
+        }
+    }
+
+    /// Dormant providers turned on in the popup, waiting to be built.
+    pub fn providers_to_start(&self) -> Vec<String> {
+        self.provider_status
+            .iter()
+            .filter(|s| s.dormant && !s.disabled)
+            .map(|s| s.name.clone())
+            .collect()
+    }
+
+    pub fn provider_started(&mut self, name: &str) {
+        self.provider_status_entry(name).dormant = false;
+        self.schedule.request();
+        self.notice = Some(format!("{} started, refreshing", name));
+    }
+
+    /// Turned off again; it can be retried from the popup.
+    pub fn provider_failed_to_start(&mut self, name: &str, error: String) {
+        self.provider_status_entry(name).disabled = true;
+        self.notice = None;
+        self.record_error(name, format!("failed to start: {}", error));
+    }
+
+    /// The note on a guest, if it has one.
+    pub fn note_for(&self, container: &Container) -> Option<&Note> {
+        self.notes.get(&notes::key(&container.id))
+    }
+
+    /// `A`: edit the selected guest's note, starting from the current text.
+    pub fn open_note_prompt(&mut self) -> Result<(), String> {
+        let container = self
+            .selected_container()
+            .ok_or_else(|| "select a guest to annotate".to_string())?;
+        let text = self
+            .note_for(container)
+            .map(|note| note.text.clone())
+            .unwrap_or_default();
+        self.note_prompt = Some(NotePrompt {
+            guest: container.id.clone(),
+            text,
+        });
+        Ok(())
+    }
+
+    pub fn close_note_prompt(&mut self) {
+        self.note_prompt = None;
+    }
+
+    /// Enter in the note prompt: save the note, or remove it if empty.
+    pub fn submit_note(&mut self) {
+        if let Some(prompt) = self.note_prompt.take() {
+            self.set_note(&prompt.guest, &prompt.text, unix_now());
+        }
+    }
+
+    pub fn set_note(&mut self, guest: &GuestId, text: &str, now: u64) {
+        let text = text.trim();
+        let key = notes::key(guest);
+        let notice = if text.is_empty() {
+            if self.notes.remove(&key).is_none() {
+                return;
+            }
+            format!("Removed note on {}", guest)
+        } else {
+            self.notes.insert(
+                key,
+                Note {
+                    text: text.to_string(),
+                    updated: now,
+                    seen: now,
+                },
+            );
+            format!("Saved note on {}", guest)
+        };
+        if self.persist("notes", |path, app| notes::save(path, &app.notes)) {
+            self.notice = Some(notice);
+        }
+    }
+
+    /// After a refresh: keep notes of listed guests, drop long-gone ones.
+    fn sweep_notes(&mut self, now: u64) {
+        if self.notes.is_empty() {
+            return;
+        }
+        let present = self.containers.iter().filter(|c| !c.missing).map(|c| &c.id);
+        if notes::sweep(&mut self.notes, present, now, self.note_retention) {
+            self.persist("notes", |path, app| notes::save(path, &app.notes));
+        }
+    }
+
+    /// The selected item's identity, name, uptime, and the rules it is
+    /// alerting on (snoozed or not).
+    fn alert_target(&self) -> Option<(String, String, u64, Vec<Rule>)> {
+        match self.active_panel {
+            Panel::Nodes => {
+                let node = self.selected_node()?;
+                let rules = if node.disks.problems().is_empty() {
+                    vec![]
+                } else {
+                    vec![Rule::Disk]
+                };
+                Some((node.id.to_string(), node.name.clone(), node.uptime, rules))
+            }
+            Panel::Containers => {
+                let container = self.selected_container()?;
+                let rules = if self.failing_replication(container).is_some() {
+                    vec![Rule::Replication]
+                } else {
+                    vec![]
+                };
+                Some((
+                    container.id.to_string(),
+                    container.name.clone(),
+                    container.uptime,
+                    rules,
+                ))
+            }
+            Panel::Groups => None,
+        }
+    }
+
+    /// `z`: choose how long to snooze the selected item's alerts.
+    pub fn open_snooze_menu(&mut self) -> Result<(), String> {
+        let (item, label, uptime, rules) = self
+            .alert_target()
+            .ok_or_else(|| "select a node or guest to snooze".to_string())?;
+        if rules.is_empty() {
+            return Err(format!("{} has no alert to snooze", label));
+        }
+        let snoozed = rules
+            .iter()
+            .any(|&rule| self.snooze_for(&item, rule).is_some());
+        self.snooze_menu = Some(SnoozeMenu {
+            item,
+            label,
+            rules,
+            uptime,
+            snoozed,
+            selected: 0,
+        });
+        Ok(())
+    }
+
+    pub fn close_snooze_menu(&mut self) {
+        self.snooze_menu = None;
+    }
+
+    pub fn select_next_snooze(&mut self) {
+        if let Some(menu) = &mut self.snooze_menu {
+            menu.selected = (menu.selected + 1).min(menu.choices().len() - 1);
+        }
+    }
+
+    pub fn select_previous_snooze(&mut self) {
+        if let Some(menu) = &mut self.snooze_menu {
+            menu.selected = menu.selected.saturating_sub(1);
+        }
+    }
+
+    /// Enter (or a number key from 1) in the popup.
+    pub fn choose_snooze(&mut self, index: usize) {
+        let Some(menu) = self.snooze_menu.take() else {
+            return;
+        };
+        match menu.choices().get(index) {
+            Some(&length) => self.set_snooze(&menu, length, unix_now()),
+            None => self.snooze_menu = Some(menu),
+        }
+    }
+
+    /// `snooze <length>` (`off` wakes): the selected item, without the popup.
+    pub fn snooze_selected(&mut self, length: Option<SnoozeLength>) -> Result<(), String> {
+        self.open_snooze_menu()?;
+        if let Some(menu) = self.snooze_menu.take() {
+            self.set_snooze(&menu, length, unix_now());
+        }
+        Ok(())
+    }
+
+    fn set_snooze(&mut self, menu: &SnoozeMenu, length: Option<SnoozeLength>, now: u64) {
+        for &rule in &menu.rules {
+            let key = snooze::key(&menu.item, rule);
+            match length {
+                Some(length) => {
+                    self.snoozes
+                        .insert(key, Snooze::new(length, now, menu.uptime));
+                }
+                None => {
+                    self.snoozes.remove(&key);
+                }
+            }
+        }
+        let notice = match length {
+            Some(length) => format!(
+                "Snoozed alerts on {} {}",
+                menu.label,
+                Snooze::new(length, now, menu.uptime).describe(now)
+            ),
+            None => format!("Woke alerts on {}", menu.label),
+        };
+        if self.persist("snooze", |path, app| snooze::save(path, &app.snoozes)) {
+            self.notice = Some(notice);
+        }
+    }
+
+    /// After a refresh: drop snoozes that ran out or whose item restarted.
+    fn sweep_snoozes(&mut self, now: u64) {
+        if self.snoozes.is_empty() {
+            return;
+        }
+        let uptimes: HashMap<String, u64> = self
+            .nodes
+            .iter()
+            .map(|n| (n.id.to_string(), n.uptime))
+            .chain(
+                self.containers
+                    .iter()
+                    .filter(|c| !c.missing)
+                    .map(|c| (c.id.to_string(), c.uptime)),
+            )
+            .collect();
+        if snooze::sweep(&mut self.snoozes, now, |item| uptimes.get(item).copied()) {
+            self.persist("snooze", |path, app| snooze::save(path, &app.snoozes));
+        }
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search_query.clear();
+        self.node_index = 0;
+        self.container_index = 0;
+    }
+
+    /// `Ctrl+I` in search mode.
+    pub fn toggle_search_case(&mut self) {
+        self.search_options.case_sensitive = !self.search_options.case_sensitive;
+        self.node_index = 0;
+        self.container_index = 0;
+    }
+
+    /// `Ctrl+O` in search mode.
+    pub fn toggle_search_whole_word(&mut self) {
+        self.search_options.whole_word = !self.search_options.whole_word;
+        self.node_index = 0;
+        self.container_index = 0;
+    }
+
+    pub fn push_search_char(&mut self, c: char) {
+        self.search_query.push(c);
+        self.node_index = 0;
+        self.container_index = 0;
+    }
+
+    pub fn pop_search_char(&mut self) {
+        self.search_query.pop();
+        self.node_index = 0;
+        self.container_index = 0;
+    }
+
+    pub fn enter_command_mode(&mut self) {
+        self.input_mode = InputMode::Command;
+        self.command_input.clear();
+        self.command_candidates.clear();
+        self.command_error = None;
+    }
+
+    pub fn exit_command_mode(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.command_input.clear();
+        self.command_candidates.clear();
+        self.command_error = None;
+    }
+
+    pub fn push_command_char(&mut self, c: char) {
+        self.command_input.push(c);
+        self.command_candidates.clear();
+        self.command_error = None;
+    }
+
+    pub fn pop_command_char(&mut self) {
+        self.command_input.pop();
+        self.command_candidates.clear();
+        self.command_error = None;
+    }
+
+    /// Complete the command line as far as it is unambiguous, listing the
+    /// candidates when there is more than one.
+    pub fn complete_command(&mut self) {
+        let completion = commands::complete(&self.command_input);
+        self.command_input = completion.input;
+        self.command_candidates = completion.candidates;
+        self.command_error = None;
+    }
+
+    /// Run the command line. A command that fails stays on the line with
+    /// its error, so it can be corrected.
+    pub fn submit_command(&mut self, providers: &[Box<dyn Provider>]) {
+        let result = commands::parse(&self.command_input).and_then(|action| {
+            // Leave command mode first, so commands can switch modes
+            self.input_mode = InputMode::Normal;
+            self.run(action, providers)
+        });
+        match result {
+            Ok(()) => {
+                self.command_input.clear();
+                self.command_candidates.clear();
+                self.command_error = None;
+            }
+            Err(e) => {
+                self.input_mode = InputMode::Command;
+                self.command_error = Some(e);
+            }
+        }
+    }
+
+    /// Carry out an action, whether it came from a key or the palette.
+    pub fn run(&mut self, action: Action, providers: &[Box<dyn Provider>]) -> Result<(), String> {
+        if let Some(reason) = self.refusal(&action) {
+            return Err(reason);
+        }
+        match action {
+            Action::Quit => self.quit(),
+            Action::NextPanel => self.next_panel(),
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious => self.select_previous(),
+            Action::Refresh => self.schedule.request(),
+            Action::RefreshSelected => self.refresh_selected(providers),
+            Action::Sort { field, descending } => {
+                if let Some(descending) = descending {
+                    self.sort_ascending = !descending;
+                }
+                match field {
+                    Some(field) => {
+                        self.sort_field = field;
+                        self.apply_sort();
+                    }
+                    None => self.cycle_sort(),
+                }
+            }
+            Action::SortColumn(n) => self.sort_by_column(n)?,
+            Action::ToggleSortOrder => self.toggle_sort_order(),
+            Action::ProblemsFirst => self.toggle_problems_first(),
+            Action::SortByNode => self.toggle_group_sort_by_node(),
+            Action::Filter(Some(query)) => {
+                self.search_query = query;
+                self.node_index = 0;
+                self.container_index = 0;
+            }
+            Action::Filter(None) => self.enter_search_mode(),
+            Action::Clear => {
+                if self.comparison.is_some() || self.pinned.is_some() {
+                    self.exit_comparison();
+                } else if !self.search_query.is_empty() {
+                    self.clear_search();
+                } else if self.provider_filter.is_some() {
+                    self.provider_filter = None;
+                } else {
+                    self.marked.clear();
+                }
+            }
+            Action::Cpu(Some(mode)) => {
+                self.cpu_mode = mode;
+                self.apply_sort();
+            }
+            Action::Cpu(None) => self.toggle_cpu_mode(),
+            Action::Pin => self.toggle_pin(),
+            Action::Mark => self.toggle_mark()?,
+            Action::Visual => self.start_visual()?,
+            Action::Permissions => self.probe_permissions(providers),
+            Action::Drain => self.plan_drain(),
+            Action::Start if !self.marked.is_empty() => self.queue_marked(QueuedAction::Start)?,
+            Action::Start => self.open_start()?,
+            Action::Shutdown => self.queue_marked(QueuedAction::Shutdown)?,
+            Action::NodePower(command) => self.open_node_power(command, providers)?,
+            Action::Console => self.request_console(providers)?,
+            Action::WebConsole(handoff) => self.request_web_console(providers, handoff)?,
+            Action::Clone => self.open_clone(providers)?,
+            Action::Resources => self.open_resources(providers)?,
+            Action::Errors => {
+                if !self.error_log.is_empty() {
+                    self.toggle_error_log();
+                }
+            }
+            Action::Events => self.toggle_events(),
+            Action::Logs => self.toggle_logs()?,
+            Action::Debug => self.toggle_debug(),
+            Action::Wide => self.toggle_full_names(),
+            Action::Group(Some(group_by)) => self.set_group_by(group_by),
+            Action::Group(None) => self.cycle_group_by(),
+            Action::Collapse => self.toggle_groups_collapsed(),
+            Action::Recent(Some(filter)) => self.set_recent_filter(filter),
+            Action::Recent(None) => self.set_recent_filter(self.recent_filter.next()),
+            Action::Templates => self.toggle_templates(),
+            Action::Presets => self.toggle_presets_popup(),
+            Action::Providers => self.toggle_providers_popup(),
+            Action::Preset(name) => self.apply_preset(&name)?,
+            Action::Snooze(None) => self.open_snooze_menu()?,
+            Action::Snooze(Some(length)) => self.snooze_selected(Some(length))?,
+            Action::Wake => self.snooze_selected(None)?,
+            Action::Note(None) => self.open_note_prompt()?,
+            Action::Note(Some(text)) => {
+                let guest = self
+                    .selected_container()
+                    .map(|c| c.id.clone())
+                    .ok_or_else(|| "select a guest to annotate".to_string())?;
+                self.set_note(&guest, &text, unix_now());
+            }
+            Action::Reload => self.reload_requested = true,
+            Action::Mute => {
+                self.alarm.toggle_mute();
+                self.notice = Some(
+                    if self.alarm.muted {
+                        "Bell and flash muted"
+                    } else {
+                        "Bell and flash unmuted"
+                    }
+                    .to_string(),
+                );
+            }
+            Action::Help => self.toggle_help(),
+            Action::Goto(Some(target)) => self.goto(&target)?,
+            Action::Goto(None) => self.open_goto_prompt()?,
+            Action::Theme(mode) => self.accessibility = mode,
+            Action::Export { format, path } => self.export(format, path)?,
+        }
+        Ok(())
+    }
+
+    /// Why `action` would be refused: read-only mode, or the token of the
+    /// selected item's provider lacking the privilege it needs.
+    pub fn refusal(&self, action: &Action) -> Option<String> {
+        if self.read_only && action.mutates() {
+            return Some("read-only mode: actions are disabled".to_string());
+        }
+        let privilege = action.privilege()?;
+        let provider = match action {
+            Action::Drain | Action::NodePower(_) => self.selected_node()?.provider(),
+            _ => self.selected_container()?.provider(),
+        };
+        let status = self
+            .provider_status
+            .iter()
+            .find(|s| s.name == provider && !s.actions_available)?;
+        let issue = status
+            .action_issues
+            .iter()
+            .find(|issue| issue.privilege == privilege)?;
+        Some(format!("{}: {}", provider, issue))
+    }
+
+    /// Select the guest `target` names exactly, or else a node by name, or
+    /// else the guest whose name it is the only prefix of; see `resolve`.
+    fn goto(&mut self, target: &str) -> Result<(), String> {
+        let node = self
+            .nodes
+            .iter()
+            .find(|n| n.name.eq_ignore_ascii_case(target));
+        let item = match (resolve::exact_guest(&self.containers, target), node) {
+            (Ok(container), _) => ItemId::Guest(container.id.clone()),
+            (Err(ResolveError::NotFound { .. }), Some(node)) => ItemId::Node(node.id.clone()),
+            (Err(ResolveError::NotFound { .. }), None) => {
+                match resolve::guest(&self.containers, target) {
+                    Ok(container) => ItemId::Guest(container.id.clone()),
+                    Err(ResolveError::NotFound { .. }) => {
+                        return Err(format!("goto: nothing named {}", target));
+                    }
+                    Err(e) => return Err(format!("goto: {}", e)),
+                }
+            }
+            (Err(e), _) => return Err(format!("goto: {}", e)),
+        };
+        self.reveal(item);
+        Ok(())
+    }
+
+    /// Select `item` on its panel, clearing the search if it hides the item.
+    fn reveal(&mut self, item: ItemId) {
+        let visible = match &item {
+            ItemId::Node(id) => self.filtered_nodes().iter().any(|n| &n.id == id),
+            ItemId::Guest(id) => self.filtered_containers().iter().any(|c| &c.id == id),
+        };
+        if !visible {
+            self.clear_search();
+            self.select_pool(0);
+        }
+        match item {
+            ItemId::Node(id) => {
+                self.active_panel = Panel::Nodes;
+                self.select_ids((Some(id), None));
+            }
+            ItemId::Guest(id) => {
+                self.active_panel = Panel::Containers;
+                self.select_ids((None, Some(id)));
+            }
+        }
+    }
+
+    /// Queue `action` for the one guest `target` resolves to, as `pulse
+    /// guest` does; the caller confirms and advances the queue.
+    pub fn queue_guest(&mut self, action: QueuedAction, target: &str) -> Result<(), String> {
+        let container = resolve::guest(&self.containers, target)
+            .map_err(|e| format!("{}: {}", action.describe(), e))?;
+        self.reveal(ItemId::Guest(container.id.clone()));
+        self.marked.clear();
+        let command = match action {
+            QueuedAction::Start => Action::Start,
+            QueuedAction::Shutdown => Action::Shutdown,
+        };
+        if let Some(reason) = self.refusal(&command) {
+            return Err(reason);
+        }
+        self.queue_marked(action)
+    }
+
+    /// Write the current view to a file, named after the format by default.
+    fn export(&mut self, format: OutputFormat, path: Option<String>) -> Result<(), String> {
+        let (contents, default_path) = match format {
+            OutputFormat::Table => (output::render_table(self), "pulse-export.txt"),
+            OutputFormat::Json => (output::render_json(self), "pulse-export.json"),
+            OutputFormat::Prometheus => (metrics::render(self), "pulse-export.prom"),
+        };
+        let path = path.unwrap_or_else(|| default_path.to_string());
+        std::fs::write(&path, contents).map_err(|e| format!("export: {}: {}", path, e))?;
+        self.notice = Some(format!("Exported to {}", path));
+        Ok(())
+    }
+
+    /// Queue the selected guest's console command; the main loop runs it.
+    fn request_console(&mut self, providers: &[Box<dyn Provider>]) -> Result<(), String> {
+        let container = match self.active_panel {
+            Panel::Containers => self.selected_container(),
+            _ => None,
+        }
+        .ok_or("console: select a guest first")?;
+        let provider = providers
+            .iter()
+            .find(|p| p.name() == container.provider())
+            .ok_or_else(|| format!("console: provider {} is gone", container.provider()))?;
+        let template = provider.console_template(container).ok_or_else(|| {
+            format!(
+                "console: no {} console command configured for {}",
+                container.type_label(),
+                provider.name()
+            )
+        })?;
+        let command =
+            console::render(template, container).map_err(|e| format!("console: {}", e))?;
+        self.console_request = Some(command);
+        Ok(())
+    }
+
+    /// Fetch a console ticket for the selected guest now, as it is only
+    /// good for a short while, and queue its URL for the main loop.
+    fn request_web_console(
+        &mut self,
+        providers: &[Box<dyn Provider>],
+        handoff: Handoff,
+    ) -> Result<(), String> {
+        let container = match self.active_panel {
+            Panel::Containers => self.selected_container(),
+            _ => None,
+        }
+        .ok_or("webconsole: select a guest first")?;
+        let provider = providers
+            .iter()
+            .find(|p| p.name() == container.provider())
+            .ok_or_else(|| format!("webconsole: provider {} is gone", container.provider()))?;
+        let url = provider
+            .web_console_url(container)
+            .map_err(|e| format!("webconsole: {}", e))?;
+        self.url_request = Some((handoff, url));
+        Ok(())
+    }
+
+    /// Report whether a web console URL reached the clipboard or browser.
+    pub fn url_handed_off(&mut self, handoff: Handoff, result: io::Result<()>) {
+        match (handoff, result) {
+            (Handoff::Copy, Ok(())) => {
+                self.notice = Some("Console URL copied; open it right away".to_string())
+            }
+            (Handoff::Open, Ok(())) => {
+                self.notice = Some("Console URL opened in the browser".to_string())
+            }
+            (Handoff::Copy, Err(e)) => {
+                self.record_error("webconsole", format!("cannot copy: {}", e))
+            }
+            (Handoff::Open, Err(e)) => {
+                self.record_error("webconsole", format!("cannot open a browser: {}", e))
+            }
+        }
+    }
+
+    /// Report how a console session ended.
+    pub fn console_closed(&mut self, program: &str, result: io::Result<ExitStatus>) {
+        match result {
+            Ok(status) if status.success() => self.notice = Some("Console closed".to_string()),
+            Ok(status) => self.record_error("console", format!("{} exited ({})", program, status)),
+            Err(e) => self.record_error("console", format!("cannot run {}: {}", program, e)),
+        }
+    }
+
+    pub fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+        self.help_scroll = 0;
+    }
+
+    pub fn scroll_help_down(&mut self) {
+        self.help_scroll = self.help_scroll.saturating_add(1);
+    }
+
+    pub fn scroll_help_up(&mut self) {
+        self.help_scroll = self.help_scroll.saturating_sub(1);
+    }
+
+    pub fn toggle_error_log(&mut self) {
+        self.show_errors = !self.show_errors;
+        self.error_scroll = 0;
+    }
+
+    pub fn scroll_errors_down(&mut self) {
+        self.error_scroll = self.error_scroll.saturating_add(1);
+    }
+
+    pub fn scroll_errors_up(&mut self) {
+        self.error_scroll = self.error_scroll.saturating_sub(1);
+    }
+
+    pub fn toggle_debug(&mut self) {
+        self.show_debug = !self.show_debug;
+    }
+
+    /// The buffers that grow while pulse runs, as (name, entries, estimated
+    /// bytes) for the debug panel.
+    pub fn buffer_sizes(&self) -> Vec<(&'static str, usize, usize)> {
+        let strings =
+            |texts: &mut dyn Iterator<Item = &String>| texts.map(String::len).sum::<usize>();
+        vec![
+            (
+                "cpu trend",
+                self.cpu_trend.sample_count(),
+                self.cpu_trend.sample_count() * std::mem::size_of::<(Instant, f64)>(),
+            ),
+            (
+                "error log",
+                self.error_log.len(),
+                self.error_log.len() * std::mem::size_of::<ErrorEntry>()
+                    + strings(&mut self.error_log.iter().map(|e| &e.message)),
+            ),
+            (
+                "events",
+                self.events.len(),
+                self.events.len() * std::mem::size_of::<Event>()
+                    + strings(&mut self.events.iter().map(|e| &e.message)),
+            ),
+            (
+                "restarts",
+                self.restarts.len(),
+                self.restarts.len() * std::mem::size_of::<(ItemId, RestartCount)>(),
+            ),
+        ]
+    }
+
+    pub fn toggle_events(&mut self) {
+        self.show_events = !self.show_events;
+        self.events_scroll = 0;
+    }
+
+    pub fn scroll_events_down(&mut self) {
+        self.events_scroll = self.events_scroll.saturating_add(1);
+    }
+
+    pub fn scroll_events_up(&mut self) {
+        self.events_scroll = self.events_scroll.saturating_sub(1);
+    }
+
+    pub fn toggle_logs(&mut self) -> Result<(), String> {
+        if self.log.is_none() {
+            return Err("logs: start pulse with --log-file to keep a log".to_string());
+        }
+        self.log_view = match self.log_view {
+            Some(_) => None,
+            None => Some(LogView::default()),
+        };
+        Ok(())
+    }
+
+    /// The log lines the popup's level filter lets through, oldest first.
+    pub fn log_lines(&self) -> Vec<LogLine> {
+        match (&self.log, &self.log_view) {
+            (Some(log), Some(view)) => log.lines(view.min_level),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Scroll the log popup, `height` rows tall, by `by` lines: up when
+    /// negative. Scrolling to the end follows new lines again.
+    pub fn scroll_logs(&mut self, by: isize, height: usize) {
+        let lines = self.log_lines();
+        let Some(view) = &mut self.log_view else {
+            return;
+        };
+        if by < 0 {
+            view.scroll_up(&lines, height, by.unsigned_abs());
+        } else {
+            view.scroll_down(&lines, height, by as usize);
+        }
+    }
+
+    pub fn toggle_cpu_mode(&mut self) {
+        self.cpu_mode = self.cpu_mode.toggle();
+        self.aggregate_pools();
+        self.apply_sort();
+    }
+
+    pub fn toggle_full_names(&mut self) {
+        self.full_names = !self.full_names;
+    }
+
+    /// "12s ago", or "14:03:27 (12s ago)" with the clock on.
+    pub fn time_since_refresh(&self) -> String {
+        let Some(instant) = self.last_refresh else {
+            return "never".to_string();
+        };
+        let elapsed = instant.elapsed();
+        let at = SystemTime::now().checked_sub(elapsed);
+        match at.and_then(|at| clock_time(at, self.clock)) {
+            Some(time) => format!("{} ({})", time, refresh_age(elapsed)),
+            None => refresh_age(elapsed),
+        }
+    }
+
+    pub fn nodes_summary(&self) -> (usize, usize) {
+        let totals = totals::nodes(&self.nodes);
+        (totals.online, totals.total)
+    }
+
+    /// Running and total guests; templates count as neither.
+    pub fn containers_summary(&self) -> (usize, usize) {
+        let totals = totals::guests(&self.containers, |c| c.cpu_usage);
+        (totals.running, totals.total)
+    }
+
+    /// Totals of the nodes listed right now, for the row under the panel.
+    pub fn filtered_node_totals(&self) -> NodeTotals {
+        totals::nodes(self.filtered_nodes())
+    }
+
+    /// Totals of the guests listed right now, CPU as displayed.
+    pub fn filtered_container_totals(&self) -> GuestTotals {
+        totals::guests(self.filtered_containers(), |c| self.container_cpu(c))
+    }
+
+    /// How urgently `node` needs attention, as problems-first sorts it.
+    pub fn node_severity(&self, node: &Node) -> Severity {
+        let ha_error = self.node_ha_problem(node).is_some();
+        Severity::of_node(node, ha_error, &self.thresholds)
+    }
+
+    /// Likewise for a guest, by its CPU in the current mode.
+    pub fn container_severity(&self, container: &Container) -> Severity {
+        Severity::of_container(
+            container,
+            self.container_cpu(container),
+            self.guest_ha_error(container),
+            &self.thresholds,
+        )
+    }
+
+    /// The HA manager's state of its provider's cluster, if it has one.
+    fn ha_status(&self, provider: &str) -> Option<&HaStatus> {
+        self.provider_status
+            .iter()
+            .find(|s| s.name == provider)
+            .and_then(|s| s.cluster.as_ref())
+            .map(|c| &c.ha)
+    }
+
+    /// Whether the guest's HA service is in the error state.
+    pub fn guest_ha_error(&self, container: &Container) -> bool {
+        self.ha_status(container.provider())
+            .is_some_and(|ha| ha.errors.contains(&container.id))
+    }
+
+    /// "fenced" or "HA unknown" when the HA manager reports either.
+    pub fn node_ha_problem(&self, node: &Node) -> Option<&'static str> {
+        let ha = self.ha_status(node.provider())?;
+        if ha.fenced.contains(&node.name) {
+            Some("fenced")
+        } else if ha.unknown.contains(&node.name) {
+            Some("HA unknown")
+        } else {
+            None
+        }
+    }
+
+    /// Guests in HA error plus nodes fenced or unknown, for the header.
+    pub fn ha_error_count(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|n| self.node_ha_problem(n).is_some())
+            .count()
+            + self
+                .containers
+                .iter()
+                .filter(|c| self.guest_ha_error(c))
+                .count()
+    }
+
+    /// What the bell and flash watch, one key per item and problem: nodes
+    /// and guests in HA error, down or over a critical threshold, and
+    /// unsnoozed alerts.
+    pub fn critical_conditions(&self) -> HashSet<String> {
+        let severity = |item: String, severity: Severity| match severity {
+            Severity::HaError => Some(format!("{} ha", item)),
+            Severity::Down => Some(format!("{} down", item)),
+            Severity::Critical => Some(format!("{} critical", item)),
+            Severity::Normal => None,
+        };
+        let mut conditions: HashSet<String> = self
+            .nodes
+            .iter()
+            .filter_map(|n| severity(n.id.to_string(), self.node_severity(n)))
+            .chain(
+                self.containers
+                    .iter()
+                    .filter_map(|c| severity(c.id.to_string(), self.container_severity(c))),
+            )
+            .collect();
+        conditions.extend(
+            self.containers
+                .iter()
+                .filter(|c| self.replication_alert(c).is_some())
+                .map(|c| format!("{} replication", c.id)),
+        );
+        conditions.extend(
+            self.clusters()
+                .filter(|c| !c.quorate)
+                .map(|c| format!("{} quorum", c.name)),
+        );
+        conditions.extend(
+            self.nodes
+                .iter()
+                .filter(|n| self.disk_alert(n))
+                .map(|n| format!("{} disks", n.id)),
+        );
+        conditions
+    }
+
+    /// One line for the terminal title: nodes and guests in HA error, down
+    /// or over a critical threshold, and alerts, e.g. "pulse: 2 down, 1 crit".
+    pub fn health_title(&self) -> String {
+        let severities = self
+            .nodes
+            .iter()
+            .map(|n| self.node_severity(n))
+            .chain(self.containers.iter().map(|c| self.container_severity(c)));
+        let (mut ha, mut down, mut critical) = (0, 0, 0);
+        for severity in severities {
+            match severity {
+                Severity::HaError => ha += 1,
+                Severity::Down => down += 1,
+                Severity::Critical => critical += 1,
+                Severity::Normal => {}
+            }
+        }
+        let alerts = self.alert_count();
+        let problems: Vec<String> = [
+            (ha, "HA error"),
+            (down, "down"),
+            (critical, "crit"),
+            (alerts, if alerts == 1 { "alert" } else { "alerts" }),
+        ]
+        .into_iter()
+        .filter(|&(count, _)| count > 0)
+        .map(|(count, label)| format!("{} {}", count, label))
+        .collect();
+        if problems.is_empty() {
+            "pulse: ok".to_string()
+        } else {
+            format!("pulse: {}", problems.join(", "))
+        }
+    }
+}
+
+/// The whole query as one text term, for matching a query that doesn't
+/// parse: lowercased like the names it is compared to, unless case matters.
+fn search_text(query: &str, options: MatchOptions) -> String {
+    if options.case_sensitive {
+        query.to_string()
+    } else {
+        query.to_lowercase()
+    }
+}
+
+/// What `App::apply_config` would refuse, without applying anything.
+pub fn check_config(config: &Config) -> Result<(), String> {
+    config
+        .general
+        .refresh_interval()
+        .map_err(|e| format!("general.refresh_rate: {}", e))?;
+    config
+        .general
+        .note_retention()
+        .map_err(|e| format!("general.note_retention: {}", e))?;
+    config
+        .general
+        .action_concurrency()
+        .map_err(|e| format!("general.action_concurrency: {}", e))?;
+    if let Some(panels) = &config.ui.panels {
+        check_panels(panels)?;
+    }
+    Ok(())
+}
+
+fn check_panels(panels: &[Panel]) -> Result<(), String> {
+    if panels.is_empty() {
+        return Err("ui.panels must list at least one panel".to_string());
+    }
+    if let Some((i, panel)) = panels
+        .iter()
+        .enumerate()
+        .find(|(i, p)| panels[..*i].contains(p))
+    {
+        return Err(format!(
+            "ui.panels lists {} twice (entry {})",
+            panel.name(),
+            i + 1
+        ));
+    }
+    Ok(())
+}
+
+/// Past this, the header stops counting and calls the data stale.
+const STALE_AFTER: Duration = Duration::from_secs(3600);
+
+/// "12s ago" or "5m ago"; anything from an hour on reads as stale.
+fn refresh_age(elapsed: Duration) -> String {
+    if elapsed >= STALE_AFTER {
+        "> 1h, data stale".to_string()
+    } else {
+        format!(
+            "{} ago",
+            format_duration(elapsed.as_secs(), DurationStyle::AGE)
+        )
+    }
+}
+
+/// "14:03:27" in the clock's zone; `None` with the clock off.
+pub fn clock_time(at: SystemTime, clock: Clock) -> Option<String> {
+    let at = DateTime::<Utc>::from(at);
+    let offset = match clock {
+        Clock::Off => return None,
+        Clock::Utc => Utc.fix(),
+        Clock::Local => Local.offset_from_utc_datetime(&at.naive_utc()).fix(),
+    };
+    Some(format_time(at, offset))
+}
+
+fn format_time(at: DateTime<Utc>, offset: FixedOffset) -> String {
+    at.with_timezone(&offset).format("%H:%M:%S").to_string()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changes::Delta;
+    use crate::clone::CloneRequest;
+    use crate::models::{ClusterLogEntry, ContainerType, Disk, HaResource, NodeDisks};
+    use crate::resources::{GuestResources, ResourceRequest};
+
+    fn create_test_node(name: &str, status: NodeStatus, cpu: f64) -> Node {
+        Node {
+            status,
+            cpu_usage: cpu,
+            cpu_cores: 4,
+            memory_used: 512,
+            memory_total: 1024,
+            uptime: 3600,
+            ..Node::test("test", name)
+        }
+    }
+
+    fn create_test_container(
+        name: &str,
+        node: &str,
+        status: ContainerStatus,
+        cpu: f64,
+    ) -> Container {
+        // Test guests all share vmid 100, so key them by name instead
+        Container {
+            id: GuestId::new("test", name),
+            status,
+            cpu_usage: cpu,
+            memory_used: 256,
+            memory_max: 1024,
+            uptime: 3600,
+            ..Container::test("test", 100, name, node)
+        }
+    }
+
+    struct FakeProvider {
+        name: String,
+        nodes: Vec<Node>,
+        containers: Vec<Container>,
+        cluster: Option<ClusterStatus>,
+        fail: std::cell::Cell<bool>,
+        /// Only the cluster status read fails, as for a token without Sys.Audit.
+        cluster_forbidden: std::cell::Cell<bool>,
+        calls: std::rc::Rc<std::cell::Cell<usize>>,
+        missing: Vec<PermissionIssue>,
+        /// Privileges for actions the token lacks.
+        missing_actions: Vec<PermissionIssue>,
+        /// Actions taken, e.g. "migrate ct-pve1 -> pve2".
+        actions: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+        /// Guest whose action task ends in failure.
+        failing_task: Option<String>,
+        console: Option<String>,
+        cluster_log: Vec<ClusterLogEntry>,
+        ha_resources: Vec<HaResource>,
+    }
+
+    impl FakeProvider {
+        fn new(name: &str, node_names: &[&str]) -> Self {
+            let nodes = node_names
+                .iter()
+                .map(|n| Node {
+                    id: NodeId::new(name, *n),
+                    ..create_test_node(n, NodeStatus::Online, 10.0)
+                })
+                .collect();
+            let containers = node_names
+                .iter()
+                .map(|n| Container {
+                    id: GuestId::new(name, format!("ct-{}", n)),
+                    ..create_test_container(&format!("ct-{}", n), n, ContainerStatus::Running, 5.0)
+                })
+                .collect();
+            Self {
+                name: name.to_string(),
+                nodes,
+                containers,
+                cluster: None,
+                fail: std::cell::Cell::new(false),
+                cluster_forbidden: std::cell::Cell::new(false),
+                calls: std::rc::Rc::new(std::cell::Cell::new(0)),
+                missing: Vec::new(),
+                missing_actions: Vec::new(),
+                actions: Default::default(),
+                failing_task: None,
+                console: None,
+                cluster_log: Vec::new(),
+                ha_resources: Vec::new(),
+            }
+        }
+    }
+
+    impl Provider for FakeProvider {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fail.get() {
+                return Err("connection refused".into());
+            }
+            Ok(self.nodes.clone())
+        }
+
+        fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+            if self.fail.get() {
+                return Err("connection refused".into());
+            }
+            Ok(self.containers.clone())
+        }
+
+        fn fetch_cluster_status(
+            &self,
+        ) -> Result<Option<ClusterStatus>, Box<dyn std::error::Error>> {
+            if self.fail.get() {
+                return Err("connection refused".into());
+            }
+            if self.cluster_forbidden.get() {
+                return Err("403 Permission check failed".into());
+            }
+            Ok(self.cluster.clone())
+        }
+
+        fn probe_permissions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+            if self.fail.get() {
+                return Err("connection refused".into());
+            }
+            Ok(self.missing.clone())
+        }
+
+        fn probe_actions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+            Ok(self.missing_actions.clone())
+        }
+
+        fn fetch_cluster_log(
+            &self,
+            max: usize,
+        ) -> Result<Vec<ClusterLogEntry>, Box<dyn std::error::Error>> {
+            Ok(self.cluster_log.iter().take(max).cloned().collect())
+        }
+
+        fn console_template(&self, _container: &Container) -> Option<&str> {
+            self.console.as_deref()
+        }
+
+        fn fetch_ha_resources(&self) -> Result<Vec<HaResource>, Box<dyn std::error::Error>> {
+            Ok(self.ha_resources.clone())
+        }
+
+        fn node_power(
+            &self,
+            node: &str,
+            command: NodePower,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            self.actions
+                .borrow_mut()
+                .push(format!("{} {}", command.as_str(), node));
+            Ok(String::new())
+        }
+
+        fn migrate_guest(
+            &self,
+            container: &Container,
+            target: &str,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            self.actions
+                .borrow_mut()
+                .push(format!("migrate {} -> {}", container.name, target));
+            Ok(container.name.clone())
+        }
+
+        fn start_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+            self.actions
+                .borrow_mut()
+                .push(format!("start {}", container.name));
+            Ok(container.name.clone())
+        }
+
+        fn shutdown_guest(
+            &self,
+            container: &Container,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            self.actions
+                .borrow_mut()
+                .push(format!("shutdown {}", container.name));
+            Ok(container.name.clone())
+        }
+
+        fn next_vmid(&self) -> Result<u32, Box<dyn std::error::Error>> {
+            Ok(self.containers.iter().map(|c| c.vmid).max().unwrap_or(99) + 1)
+        }
+
+        fn clone_guest(
+            &self,
+            container: &Container,
+            request: &CloneRequest,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            self.actions.borrow_mut().push(format!(
+                "clone {} -> {} {}",
+                container.name, request.vmid, request.name
+            ));
+            Ok(request.name.clone())
+        }
+
+        fn fetch_guest_resources(
+            &self,
+            container: &Container,
+        ) -> Result<GuestResources, Box<dyn std::error::Error>> {
+            Ok(GuestResources {
+                cores: Some(container.max_cpu),
+                sockets: 1,
+                memory_mb: 512,
+                balloon_mb: None,
+                memory_hotplug: false,
+            })
+        }
+
+        fn set_guest_resources(
+            &self,
+            container: &Container,
+            request: &ResourceRequest,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            if self.failing_task.as_deref() == Some(container.name.as_str()) {
+                return Err("VM is locked (backup)".into());
+            }
+            self.actions.borrow_mut().push(format!(
+                "resources {} {:?} {:?}",
+                container.name, request.cores, request.memory_mb
+            ));
+            Ok(String::new())
+        }
+
+        fn web_console_url(
+            &self,
+            container: &Container,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            Ok(format!(
+                "https://{}:8006/?vmid={}",
+                container.node, container.vmid
+            ))
+        }
+
+        fn task_status(
+            &self,
+            _node: &str,
+            task: &str,
+        ) -> Result<TaskStatus, Box<dyn std::error::Error>> {
+            if self.failing_task.as_deref() == Some(task) {
+                return Ok(TaskStatus::Failed("migration aborted".to_string()));
+            }
+            Ok(TaskStatus::Succeeded)
+        }
+    }
+
+    /// pve1 with two guests, pve2 with room for one of them.
+    fn drain_fixture() -> FakeProvider {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        provider.nodes[1].memory_total = 1800;
+        let mut extra = provider.containers[0].clone();
+        extra.id = GuestId::new("homelab", "ct-extra");
+        extra.name = "ct-extra".to_string();
+        extra.memory_max = 512;
+        provider.containers.push(extra);
+        provider
+    }
+
+    // App initialization tests
+    #[test]
+    fn test_app_new() {
+        let app = App::new();
+        assert!(app.running);
+        assert_eq!(app.active_panel, Panel::Nodes);
+        assert!(app.nodes.is_empty());
+        assert!(app.containers.is_empty());
+        assert_eq!(app.node_index, 0);
+        assert_eq!(app.container_index, 0);
+        assert!(app.error_message.is_none());
+        assert_eq!(app.sort_field, SortField::Name);
+        assert!(app.sort_ascending);
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert!(app.search_query.is_empty());
+        assert!(!app.show_help);
+    }
+
+    // Navigation tests
+    #[test]
+    fn test_next_panel() {
+        let mut app = App::new();
+        assert_eq!(app.active_panel, Panel::Nodes);
+
+        app.next_panel();
+        assert_eq!(app.active_panel, Panel::Containers);
+
+        app.next_panel();
+        assert_eq!(app.active_panel, Panel::Nodes);
+    }
+
+    #[test]
+    fn test_configured_panels_set_tab_order() {
+        let mut app = App::new();
+        app.set_panels(vec![Panel::Groups, Panel::Containers, Panel::Nodes])
+            .unwrap();
+        assert_eq!(app.active_panel, Panel::Groups);
+
+        app.next_panel();
+        assert_eq!(app.active_panel, Panel::Containers);
+        app.next_panel();
+        assert_eq!(app.active_panel, Panel::Nodes);
+        app.next_panel();
+        assert_eq!(app.active_panel, Panel::Groups);
+
+        assert_eq!(
+            app.set_panels(Vec::new()).unwrap_err(),
+            "ui.panels must list at least one panel"
+        );
+        assert_eq!(
+            app.set_panels(vec![Panel::Nodes, Panel::Groups, Panel::Nodes])
+                .unwrap_err(),
+            "ui.panels lists nodes twice (entry 3)"
+        );
+    }
+
+    #[test]
+    fn test_selected_pool_filters_containers() {
+        let mut fake = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        fake.containers[0].pool = Some("media".to_string());
+        fake.containers[2].pool = Some("media".to_string());
+        fake.containers[1].pool = Some("infra".to_string());
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(fake)];
+        let mut app = App::new();
+        app.set_panels(vec![Panel::Nodes, Panel::Groups, Panel::Containers])
+            .unwrap();
+        app.refresh(&providers);
+
+        let pools: Vec<(&str, usize)> = app.pools.iter().map(|p| (p.label(), p.total)).collect();
+        assert_eq!(pools, [("infra", 1), ("media", 2)]);
+        assert_eq!(app.pools_total.as_ref().unwrap().total, 3);
+
+        app.active_panel = Panel::Groups;
+        app.select_next();
+        app.select_next();
+        app.select_next();
+        assert_eq!(app.selected_pool().unwrap().label(), "media");
+        let names: Vec<&str> = app
+            .filtered_containers()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["ct-pve1", "ct-pve3"]);
+
+        // The choice survives a refresh
+        app.refresh(&providers);
+        assert_eq!(app.selected_pool().unwrap().label(), "media");
+
+        app.select_previous();
+        app.select_previous();
+        assert!(app.selected_pool().is_none());
+        assert_eq!(app.filtered_containers().len(), 3);
+    }
+
+    #[test]
+    fn test_select_next_nodes() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("node1", NodeStatus::Online, 10.0),
+            create_test_node("node2", NodeStatus::Online, 20.0),
+            create_test_node("node3", NodeStatus::Online, 30.0),
+        ];
+        app.active_panel = Panel::Nodes;
+
+        assert_eq!(app.node_index, 0);
+        app.select_next();
+        assert_eq!(app.node_index, 1);
+        app.select_next();
+        assert_eq!(app.node_index, 2);
+        app.select_next(); // Should not go past last item
+        assert_eq!(app.node_index, 2);
+    }
+
+    #[test]
+    fn test_select_previous_nodes() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("node1", NodeStatus::Online, 10.0),
+            create_test_node("node2", NodeStatus::Online, 20.0),
+        ];
+        app.active_panel = Panel::Nodes;
+        app.node_index = 1;
+
+        app.select_previous();
+        assert_eq!(app.node_index, 0);
+        app.select_previous(); // Should not go below 0
+        assert_eq!(app.node_index, 0);
+    }
+
+    #[test]
+    fn test_select_next_containers() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("ct1", "node1", ContainerStatus::Running, 10.0),
+            create_test_container("ct2", "node1", ContainerStatus::Running, 20.0),
+        ];
+        app.active_panel = Panel::Containers;
+
+        assert_eq!(app.container_index, 0);
+        app.select_next();
+        assert_eq!(app.container_index, 1);
+    }
+
+    // Sort tests
+    #[test]
+    fn test_sort_field_cycle() {
+        assert_eq!(SortField::Name.next(), SortField::Status);
+        assert_eq!(SortField::Status.next(), SortField::Cpu);
+        assert_eq!(SortField::Cpu.next(), SortField::Memory);
+        assert_eq!(SortField::Memory.next(), SortField::MemoryAbs);
+        assert_eq!(SortField::MemoryAbs.next(), SortField::Disk);
+        assert_eq!(SortField::Disk.next(), SortField::Name);
+    }
+
+    #[test]
+    fn test_sort_field_label() {
+        assert_eq!(SortField::Name.label(), "Name");
+        assert_eq!(SortField::Status.label(), "Status");
+        assert_eq!(SortField::Cpu.label(), "CPU");
+        assert_eq!(SortField::Memory.label(), "Mem%");
+        assert_eq!(SortField::MemoryAbs.label(), "MemGB");
+        assert_eq!(SortField::Disk.label(), "Disk");
+    }
+
+    #[test]
+    fn test_cycle_sort() {
+        let mut app = App::new();
+        assert_eq!(app.sort_field, SortField::Name);
+
+        app.cycle_sort();
+        assert_eq!(app.sort_field, SortField::Status);
+
+        app.cycle_sort();
+        assert_eq!(app.sort_field, SortField::Cpu);
+    }
+
+    #[test]
+    fn test_grouping_orders_and_collapses() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = vec![
+            create_test_container("prod-web-01", "pve1", ContainerStatus::Running, 10.0),
+            create_test_container("lab-db-01", "pve2", ContainerStatus::Running, 10.0),
+            create_test_container("prod-web-02", "pve2", ContainerStatus::Running, 10.0),
+            create_test_container("nas", "pve1", ContainerStatus::Running, 10.0),
+        ];
+        let names = |app: &App| -> Vec<String> {
+            app.filtered_containers()
+                .iter()
+                .map(|c| c.name.clone())
+                .collect()
+        };
+
+        // The selection stays on the same container as rows move
+        app.container_index = 2;
+        app.set_group_by(GroupBy::Prefix);
+        assert_eq!(
+            names(&app),
+            ["lab-db-01", "prod-web-01", "prod-web-02", "nas"]
+        );
+        assert_eq!(app.selected_container().unwrap().name, "prod-web-02");
+
+        app.set_group_by(GroupBy::Node);
+        assert_eq!(
+            names(&app),
+            ["prod-web-01", "nas", "lab-db-01", "prod-web-02"]
+        );
+
+        // Collapsed, j/k move a group at a time
+        app.set_group_by(GroupBy::Prefix);
+        app.toggle_groups_collapsed();
+        assert_eq!(app.selected_container().unwrap().name, "prod-web-01");
+        app.select_next();
+        assert_eq!(app.selected_container().unwrap().name, "nas");
+        app.select_next();
+        assert_eq!(app.selected_container().unwrap().name, "nas");
+        app.select_previous();
+        app.select_previous();
+        assert_eq!(app.selected_container().unwrap().name, "lab-db-01");
+
+        app.set_group_by(GroupBy::Off);
+        assert!(!app.groups_collapsed);
+        assert_eq!(names(&app)[0], "prod-web-01");
+    }
+
+    #[test]
+    fn test_group_cpu_follows_cpu_mode() {
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("pve1", NodeStatus::Online, 10.0)];
+        let mut big = create_test_container("prod-a", "pve1", ContainerStatus::Running, 50.0);
+        big.max_cpu = 2;
+        app.containers = vec![
+            big,
+            create_test_container("prod-b", "pve1", ContainerStatus::Running, 20.0),
+        ];
+        app.set_group_by(GroupBy::Prefix);
+        let group_cpu = |app: &App| app.group_cpu(&app.container_groups()[0]);
+
+        // 1 + 0.2 of 3 allocated cores
+        assert!((group_cpu(&app) - 40.0).abs() < 1e-9);
+        app.cpu_mode = CpuMode::Host;
+        // 1.2 of the 4 host cores
+        assert!((group_cpu(&app) - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_problems_first_keeps_sort_within_bands() {
+        let mut app = App::new();
+        let mut busy_node = create_test_node("pve3", NodeStatus::Online, 95.0);
+        busy_node.memory_used = 100;
+        app.nodes = vec![
+            create_test_node("pve2", NodeStatus::Online, 10.0),
+            busy_node,
+            create_test_node("pve4", NodeStatus::Offline, 0.0),
+            create_test_node("pve1", NodeStatus::Online, 20.0),
+        ];
+        let mut full = create_test_container("e-full", "pve1", ContainerStatus::Running, 5.0);
+        full.memory_used = 1000;
+        app.containers = vec![
+            create_test_container("a-idle", "pve1", ContainerStatus::Running, 5.0),
+            create_test_container("f-busy", "pve1", ContainerStatus::Running, 99.0),
+            create_test_container("d-off", "pve1", ContainerStatus::Stopped, 0.0),
+            full,
+            create_test_container("b-off", "pve1", ContainerStatus::Stopped, 0.0),
+            create_test_container("c-idle", "pve1", ContainerStatus::Running, 5.0),
+        ];
+        let names = |app: &App| -> (Vec<String>, Vec<String>) {
+            (
+                app.nodes.iter().map(|n| n.name.clone()).collect(),
+                app.containers.iter().map(|c| c.name.clone()).collect(),
+            )
+        };
+        app.apply_sort();
+        let plain = names(&app);
+
+        app.toggle_problems_first();
+        let (nodes, containers) = names(&app);
+        assert_eq!(nodes, ["pve4", "pve3", "pve1", "pve2"]);
+        assert_eq!(
+            containers,
+            ["b-off", "d-off", "e-full", "f-busy", "a-idle", "c-idle"]
+        );
+
+        // Bands follow the underlying sort, here name descending
+        app.toggle_sort_order();
+        let (_, containers) = names(&app);
+        assert_eq!(
+            containers,
+            ["d-off", "b-off", "f-busy", "e-full", "c-idle", "a-idle"]
+        );
+
+        app.toggle_sort_order();
+        app.toggle_problems_first();
+        assert_eq!(names(&app), plain);
+    }
+
+    #[test]
+    fn test_group_sort_by_node_sorts_within_each_node() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("a", "pve2", ContainerStatus::Running, 10.0),
+            create_test_container("b", "pve3", ContainerStatus::Running, 90.0),
+            create_test_container("c", "pve1", ContainerStatus::Running, 30.0),
+            create_test_container("d", "pve2", ContainerStatus::Running, 70.0),
+            create_test_container("e", "pve1", ContainerStatus::Running, 50.0),
+            create_test_container("f", "pve3", ContainerStatus::Running, 20.0),
+        ];
+        let order = |app: &App| -> Vec<(String, String)> {
+            app.containers
+                .iter()
+                .map(|c| (c.node.clone(), c.name.clone()))
+                .collect()
+        };
+        app.sort_field = SortField::Cpu;
+        app.sort_ascending = false;
+        app.apply_sort();
+        let plain: Vec<String> = order(&app).into_iter().map(|(_, name)| name).collect();
+        assert_eq!(plain, ["b", "d", "e", "c", "f", "a"]);
+
+        app.toggle_group_sort_by_node();
+        let expected = [
+            ("pve1", "e"),
+            ("pve1", "c"),
+            ("pve2", "d"),
+            ("pve2", "a"),
+            ("pve3", "b"),
+            ("pve3", "f"),
+        ]
+        .map(|(node, name)| (node.to_string(), name.to_string()));
+        assert_eq!(order(&app), expected);
+
+        // The field sort still applies within each node
+        app.toggle_sort_order();
+        let names: Vec<String> = order(&app).into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, ["c", "e", "a", "d", "f", "b"]);
+
+        app.toggle_sort_order();
+        app.toggle_group_sort_by_node();
+        let names: Vec<String> = order(&app).into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, plain);
+    }
+
+    #[test]
+    fn test_problems_first_uses_thresholds() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("a", "pve1", ContainerStatus::Running, 10.0),
+            create_test_container("b", "pve1", ContainerStatus::Running, 75.0),
+        ];
+        app.toggle_problems_first();
+        assert_eq!(app.containers[0].name, "a");
+
+        app.thresholds.cpu_critical = 70.0;
+        app.apply_sort();
+        assert_eq!(app.containers[0].name, "b");
+    }
+
+    /// A "test" provider whose cluster's HA manager reports `ha`.
+    fn with_ha(app: &mut App, ha: HaStatus) {
+        let mut status = ProviderStatus::new("test");
+        status.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: true,
+            nodes_online: 3,
+            nodes_total: 3,
+            firewall: None,
+            ha,
+        });
+        app.provider_status = vec![status];
+    }
+
+    #[test]
+    fn test_ha_errors_come_before_everything_else() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("node1", NodeStatus::Offline, 0.0),
+            create_test_node("node2", NodeStatus::Online, 10.0),
+            create_test_node("node3", NodeStatus::Online, 10.0),
+        ];
+        app.containers = vec![
+            create_test_container("down", "node1", ContainerStatus::Stopped, 0.0),
+            create_test_container("hot", "node2", ContainerStatus::Running, 99.0),
+            create_test_container("web", "node2", ContainerStatus::Running, 10.0),
+        ];
+        with_ha(
+            &mut app,
+            HaStatus {
+                errors: vec![GuestId::new("test", "web")],
+                fenced: vec!["node3".to_string()],
+                unknown: Vec::new(),
+            },
+        );
+        app.toggle_problems_first();
+        let names: Vec<&str> = app.containers.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["web", "down", "hot"]);
+        let names: Vec<&str> = app.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["node3", "node1", "node2"]);
+
+        assert_eq!(app.ha_error_count(), 2);
+        assert_eq!(app.node_ha_problem(&app.nodes[0]), Some("fenced"));
+        assert_eq!(app.health_title(), "pulse: 2 HA error, 2 down, 1 crit");
+        let conditions = app.critical_conditions();
+        assert!(conditions.contains("test/web ha"));
+        assert!(conditions.contains("test/node3 ha"));
+        assert!(!conditions.contains("test/web down"));
+
+        // Another provider's cluster says nothing about these
+        app.provider_status[0].name = "office".to_string();
+        assert_eq!(app.ha_error_count(), 0);
+        assert!(!app.guest_ha_error(&app.containers[0]));
+    }
+
+    #[test]
+    fn test_sort_keeps_selected_item() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = vec![
+            create_test_container("alpha", "node1", ContainerStatus::Running, 10.0),
+            create_test_container("bravo", "node1", ContainerStatus::Running, 90.0),
+            create_test_container("charlie", "node1", ContainerStatus::Running, 50.0),
+        ];
+        app.container_index = 0;
+
+        app.sort_field = SortField::Cpu;
+        app.toggle_sort_order();
+
+        assert_eq!(app.selected_container().unwrap().name, "alpha");
+        assert_eq!(app.container_index, 2);
+    }
+
+    #[test]
+    fn test_memory_sorts_by_percent_or_bytes() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        let mut app = App::new();
+        app.containers = vec![
+            // 40% of 32 GB: the biggest consumer, but not by percent
+            Container {
+                memory_used: 13 * GB,
+                memory_max: 32 * GB,
+                ..create_test_container("big", "node1", ContainerStatus::Running, 1.0)
+            },
+            Container {
+                memory_used: GB / 2,
+                memory_max: GB / 2,
+                ..create_test_container("full", "node1", ContainerStatus::Running, 1.0)
+            },
+            Container {
+                memory_used: 2 * GB,
+                memory_max: 4 * GB,
+                ..create_test_container("half", "node1", ContainerStatus::Running, 1.0)
+            },
+        ];
+        app.nodes = vec![
+            Node {
+                memory_used: 60 * GB,
+                memory_total: 256 * GB,
+                ..create_test_node("large", NodeStatus::Online, 1.0)
+            },
+            Node {
+                memory_used: 14 * GB,
+                memory_total: 16 * GB,
+                ..create_test_node("small", NodeStatus::Online, 1.0)
+            },
+        ];
+        let order = |app: &App| {
+            (
+                app.nodes.iter().map(|n| n.name.clone()).collect::<Vec<_>>(),
+                app.containers
+                    .iter()
+                    .map(|c| c.name.clone())
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        app.sort_field = SortField::Memory;
+        app.sort_ascending = false;
+        app.apply_sort();
+        assert_eq!(
+            order(&app),
+            (
+                vec!["small".into(), "large".into()],
+                vec!["full".into(), "half".into(), "big".into()]
+            )
+        );
+
+        app.sort_field = SortField::MemoryAbs;
+        app.apply_sort();
+        assert_eq!(
+            order(&app),
+            (
+                vec!["large".into(), "small".into()],
+                vec!["big".into(), "half".into(), "full".into()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_container_cpu_host_mode() {
+        let mut app = App::new();
+        app.nodes = vec![Node {
+            cpu_cores: 16,
+            ..create_test_node("node1", NodeStatus::Online, 10.0)
+        }];
+        let small = Container {
+            max_cpu: 1,
+            ..create_test_container("small", "node1", ContainerStatus::Running, 100.0)
+        };
+        let orphan = Container {
+            max_cpu: 1,
+            ..create_test_container("orphan", "gone", ContainerStatus::Running, 100.0)
+        };
+
+        assert_eq!(app.container_cpu(&small), 100.0);
+
+        app.toggle_cpu_mode();
+        assert_eq!(app.cpu_mode, CpuMode::Host);
+        assert_eq!(app.container_cpu(&small), 6.25);
+        // Without the node's core count the guest figure is kept
+        assert_eq!(app.container_cpu(&orphan), 100.0);
+    }
+
+    #[test]
+    fn test_cpu_sort_follows_cpu_mode() {
+        let mut app = App::new();
+        app.nodes = vec![Node {
+            cpu_cores: 16,
+            ..create_test_node("node1", NodeStatus::Online, 10.0)
+        }];
+        app.containers = vec![
+            Container {
+                max_cpu: 1,
+                ..create_test_container("busy-lxc", "node1", ContainerStatus::Running, 100.0)
+            },
+            Container {
+                max_cpu: 8,
+                ..create_test_container("big-vm", "node1", ContainerStatus::Running, 20.0)
+            },
+        ];
+        app.sort_field = SortField::Cpu;
+        app.sort_ascending = false;
+
+        app.apply_sort();
+        assert_eq!(app.containers[0].name, "busy-lxc");
+
+        // 100% of 1 core is 6.25% of the host; 20% of 8 cores is 10%
+        app.toggle_cpu_mode();
+        assert_eq!(app.containers[0].name, "big-vm");
+    }
+
+    #[test]
+    fn test_toggle_sort_order() {
+        let mut app = App::new();
+        assert!(app.sort_ascending);
+
+        app.toggle_sort_order();
+        assert!(!app.sort_ascending);
+
+        app.toggle_sort_order();
+        assert!(app.sort_ascending);
+    }
+
+    #[test]
+    fn test_apply_startup_options() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("pve1", NodeStatus::Online, 10.0),
+            create_test_node("media", NodeStatus::Online, 80.0),
+            create_test_node("media2", NodeStatus::Online, 30.0),
+        ];
+
+        app.apply_startup_options(Some("media"), Some(SortField::Cpu), true);
+
+        assert_eq!(app.search_query, "media");
+        assert_eq!(app.sort_field, SortField::Cpu);
+        assert!(!app.sort_ascending);
+        let names: Vec<&str> = app
+            .filtered_nodes()
+            .iter()
+            .map(|n| n.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["media", "media2"]);
+    }
+
+    #[test]
+    fn test_apply_startup_options_defaults() {
+        let mut app = App::new();
+        app.apply_startup_options(None, None, false);
+
+        assert!(app.search_query.is_empty());
+        assert_eq!(app.sort_field, SortField::Name);
+        assert!(app.sort_ascending);
+    }
+
+    // Search/filter tests
+    #[test]
+    fn test_search_mode() {
+        let mut app = App::new();
+        assert_eq!(app.input_mode, InputMode::Normal);
+
+        app.enter_search_mode();
+        assert_eq!(app.input_mode, InputMode::Search);
+
+        app.exit_search_mode();
+        assert_eq!(app.input_mode, InputMode::Normal);
+    }
+
+    #[test]
+    fn test_submit_search_focuses_the_panel_with_matches() {
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("pve1", NodeStatus::Online, 10.0)];
+        app.containers = ["web1", "web2", "db"]
+            .into_iter()
+            .map(|name| create_test_container(name, "pve1", ContainerStatus::Running, 10.0))
+            .collect();
+        let search = |app: &mut App, query: &str| {
+            app.active_panel = Panel::Nodes;
+            app.enter_search_mode();
+            app.search_query = query.to_string();
+            app.notice = None;
+            app.submit_search();
+            assert_eq!(app.input_mode, InputMode::Normal);
+        };
+
+        // Nothing anywhere: stay
+        search(&mut app, "mail");
+        assert_eq!(app.active_panel, Panel::Nodes);
+        assert!(app.notice.is_none());
+
+        // Nothing here, some there: switch
+        search(&mut app, "web");
+        assert_eq!(app.active_panel, Panel::Containers);
+        assert_eq!(
+            app.notice.as_deref(),
+            Some("switched to Containers: 2 matches")
+        );
+        assert_eq!(app.selected_container().unwrap().name, "web1");
+
+        // Matches in both: stay
+        search(&mut app, "node:pve1");
+        assert_eq!(app.active_panel, Panel::Nodes);
+        assert!(app.notice.is_none());
+
+        // And back the other way
+        app.search_query = "pve".to_string();
+        app.active_panel = Panel::Containers;
+        app.submit_search();
+        assert_eq!(app.active_panel, Panel::Nodes);
+        assert_eq!(app.notice.as_deref(), Some("switched to Nodes: 1 match"));
+
+        app.search_switches_panel = false;
+        search(&mut app, "web");
+        assert_eq!(app.active_panel, Panel::Nodes);
+    }
+
+    #[test]
+    fn test_search_options_apply_to_both_panels() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("db1", NodeStatus::Online, 10.0),
+            create_test_node("mariadb-host", NodeStatus::Online, 10.0),
+        ];
+        app.containers = ["mariadb", "db-test", "DB01"]
+            .into_iter()
+            .map(|name| create_test_container(name, "db1", ContainerStatus::Running, 10.0))
+            .collect();
+        let names = |app: &App| {
+            (
+                app.filtered_nodes()
+                    .iter()
+                    .map(|n| n.name.clone())
+                    .collect::<Vec<_>>(),
+                app.filtered_containers()
+                    .iter()
+                    .map(|c| c.name.clone())
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        app.search_query = "db".to_string();
+        assert_eq!(names(&app).1, ["mariadb", "db-test", "DB01"]);
+
+        app.toggle_search_whole_word();
+        assert_eq!(
+            names(&app),
+            (
+                vec!["db1".to_string()],
+                vec!["db-test".to_string(), "DB01".to_string()]
+            )
+        );
+
+        app.toggle_search_case();
+        assert_eq!(names(&app).1, ["db-test"]);
+        app.search_query = "DB".to_string();
+        assert_eq!(names(&app), (vec![], vec!["DB01".to_string()]));
+    }
+
+    #[test]
+    fn test_presets_save_apply_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        let mut app = App::new();
+        app.load_presets(Some(path.clone()));
+        assert!(app.presets.is_empty());
+
+        app.enter_search_mode();
+        app.search_query = "status:stopped".to_string();
+        app.hide_templates = true;
+        app.open_preset_prompt();
+        app.preset_prompt.as_mut().unwrap().name = " stopped ".to_string();
+        app.submit_preset_name();
+        assert!(app.preset_prompt.is_none());
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.notice.as_deref(), Some("Saved preset stopped"));
+        assert!(app.presets["stopped"].hide_templates);
+
+        // Saved to the state file, and read back by a new session
+        let mut other = App::new();
+        other.load_presets(Some(path.clone()));
+        assert_eq!(other.presets, app.presets);
+
+        app.search_query = "node:media".to_string();
+        app.hide_templates = false;
+        app.recent_filter = RecentFilter::Hour;
+        app.search_options.whole_word = true;
+        app.save_preset("media");
+
+        // Applying replaces the query and every toggle at once
+        app.container_index = 3;
+        app.apply_preset("stopped").unwrap();
+        assert_eq!(app.search_query, "status:stopped");
+        assert!(app.hide_templates);
+        assert_eq!(app.recent_filter, RecentFilter::Off);
+        assert_eq!(app.search_options, MatchOptions::default());
+        assert_eq!(app.container_index, 0);
+        assert_eq!(
+            app.apply_preset("nope"),
+            Err("no preset named nope".to_string())
+        );
+
+        // The popup numbers presets by name
+        app.toggle_presets_popup();
+        app.apply_preset_at(0);
+        assert!(app.presets_popup.is_none());
+        assert_eq!(app.search_query, "node:media");
+        assert_eq!(app.recent_filter, RecentFilter::Hour);
+
+        app.toggle_presets_popup();
+        app.select_next_preset();
+        app.select_next_preset();
+        assert_eq!(app.presets_popup, Some(1));
+        app.delete_selected_preset();
+        assert_eq!(app.presets_popup, Some(0));
+        assert_eq!(app.presets.keys().collect::<Vec<_>>(), ["media"]);
+        app.load_presets(Some(path));
+        assert_eq!(app.presets.keys().collect::<Vec<_>>(), ["media"]);
+        assert!(app.delete_preset("stopped").is_err());
+    }
+
+    #[test]
+    fn test_preset_names_are_unique_with_overwrite_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = App::new();
+        app.load_presets(Some(dir.path().join("state.toml")));
+        app.search_query = "web".to_string();
+        app.save_preset("daily");
+
+        app.enter_search_mode();
+        app.search_query = "db".to_string();
+        app.open_preset_prompt();
+        app.preset_prompt.as_mut().unwrap().name = "daily".to_string();
+        app.submit_preset_name();
+        assert!(app.preset_prompt.as_ref().unwrap().confirm_overwrite);
+        assert_eq!(app.presets["daily"].query, "web");
+
+        app.submit_preset_name();
+        assert!(app.preset_prompt.is_none());
+        assert_eq!(app.presets.len(), 1);
+        assert_eq!(app.presets["daily"].query, "db");
+
+        // Esc keeps the old one
+        app.enter_search_mode();
+        app.search_query = "mail".to_string();
+        app.open_preset_prompt();
+        app.preset_prompt.as_mut().unwrap().name = "daily".to_string();
+        app.submit_preset_name();
+        app.close_preset_prompt();
+        assert_eq!(app.presets["daily"].query, "db");
+        assert_eq!(app.input_mode, InputMode::Search);
+    }
+
+    #[test]
+    fn test_presets_without_state_file_last_the_session() {
+        let mut app = App::new();
+        app.search_query = "web".to_string();
+        app.save_preset("web");
+        assert!(app.presets.contains_key("web"));
+        assert!(app.notice.is_none());
+        assert!(
+            app.error_log[0]
+                .message
+                .starts_with("kept for this session only")
+        );
+    }
+
+    #[test]
+    fn test_guest_notes_follow_the_guest_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        let provider = FakeProvider::new("pve", &["pve1", "pve2"]);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.load_state(Some(path.clone()));
+        app.refresh(&providers);
+        app.active_panel = Panel::Containers;
+
+        app.run(Action::Note(None), &providers).unwrap();
+        let prompt = app.note_prompt.as_mut().unwrap();
+        assert_eq!(prompt.guest, GuestId::new("pve", "ct-pve1"));
+        prompt.text = "left stopped on purpose ".to_string();
+        app.submit_note();
+        assert!(app.note_prompt.is_none());
+        assert_eq!(app.notice.as_deref(), Some("Saved note on pve/ct-pve1"));
+        let container = app.selected_container().unwrap();
+        assert_eq!(
+            app.note_for(container).unwrap().text,
+            "left stopped on purpose"
+        );
+
+        // Survives a restart, and doesn't disturb the presets
+        app.save_preset("all");
+        let mut other = App::new();
+        other.load_state(Some(path.clone()));
+        assert_eq!(other.notes, app.notes);
+        assert!(other.presets.contains_key("all"));
+
+        // Editing starts from the text; an empty note removes it
+        app.open_note_prompt().unwrap();
+        assert_eq!(
+            app.note_prompt.as_ref().unwrap().text,
+            "left stopped on purpose"
+        );
+        app.note_prompt.as_mut().unwrap().text.clear();
+        app.submit_note();
+        assert!(app.notes.is_empty());
+        assert_eq!(app.notice.as_deref(), Some("Removed note on pve/ct-pve1"));
+        other.load_state(Some(path));
+        assert!(other.notes.is_empty());
+    }
+
+    #[test]
+    fn test_notes_of_long_gone_guests_are_pruned() {
+        let day = 24 * 60 * 60;
+        let mut app = App::new();
+        app.containers = vec![create_test_container(
+            "web",
+            "pve1",
+            ContainerStatus::Running,
+            1.0,
+        )];
+        app.set_note(&GuestId::new("test", "web"), "keep", 0);
+        app.set_note(&GuestId::new("test", "gone"), "drop", 0);
+        app.sweep_notes(29 * day);
+        assert_eq!(app.notes.len(), 2);
+        app.sweep_notes(31 * day);
+        assert_eq!(app.notes.keys().collect::<Vec<_>>(), ["test/web"]);
+    }
+
+    #[test]
+    fn test_search_query() {
+        let mut app = App::new();
+
+        app.push_search_char('t');
+        app.push_search_char('e');
+        app.push_search_char('s');
+        app.push_search_char('t');
+        assert_eq!(app.search_query, "test");
+
+        app.pop_search_char();
+        assert_eq!(app.search_query, "tes");
+
+        app.clear_search();
+        assert!(app.search_query.is_empty());
+    }
+
+    #[test]
+    fn test_command_palette_runs_and_keeps_errors() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("web", "pve1", ContainerStatus::Running, 10.0),
+            create_test_container("db", "pve1", ContainerStatus::Running, 90.0),
+        ];
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+
+        app.enter_command_mode();
+        for c in "sort cpu desc".chars() {
+            app.push_command_char(c);
+        }
+        app.submit_command(&providers);
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.sort_field, SortField::Cpu);
+        assert!(!app.sort_ascending);
+        assert_eq!(app.containers[0].name, "db");
+
+        // A mistake stays on the line to be fixed
+        app.enter_command_mode();
+        for c in "sort fast".chars() {
+            app.push_command_char(c);
+        }
+        app.submit_command(&providers);
+        assert_eq!(app.input_mode, InputMode::Command);
+        assert_eq!(app.command_input, "sort fast");
+        assert!(app.command_error.as_deref().unwrap().starts_with("sort: "));
+
+        app.pop_command_char();
+        assert_eq!(app.command_error, None);
+    }
+
+    #[test]
+    fn test_complete_command_lists_candidates() {
+        let mut app = App::new();
+        app.enter_command_mode();
+        app.push_command_char('r');
+        app.complete_command();
+        assert_eq!(app.command_input, "re");
+        assert_eq!(
+            app.command_candidates,
+            [
+                "refresh",
+                "refresh-selected",
+                "reverse",
+                "reboot",
+                "resources",
+                "recent",
+                "reload"
+            ]
+        );
+
+        app.push_command_char('v');
+        app.complete_command();
+        assert_eq!(app.command_input, "reverse ");
+        assert!(app.command_candidates.is_empty());
+    }
+
+    #[test]
+    fn test_goto_selects_and_reveals_item() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("pve1", NodeStatus::Online, 10.0),
+            create_test_node("pve2", NodeStatus::Online, 10.0),
+        ];
+        let mut db = create_test_container("db", "pve2", ContainerStatus::Running, 10.0);
+        db.vmid = 105;
+        app.containers = vec![
+            create_test_container("web", "pve1", ContainerStatus::Running, 10.0),
+            db,
+        ];
+        app.search_query = "web".to_string();
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+
+        assert_eq!(
+            app.run(Action::Goto(Some("105".to_string())), &providers),
+            Ok(())
+        );
+        assert_eq!(app.active_panel, Panel::Containers);
+        assert!(app.search_query.is_empty());
+        assert_eq!(app.selected_container().unwrap().name, "db");
+
+        assert_eq!(
+            app.run(Action::Goto(Some("PVE2".to_string())), &providers),
+            Ok(())
+        );
+        assert_eq!(app.active_panel, Panel::Nodes);
+        assert_eq!(app.selected_node().unwrap().name, "pve2");
+
+        assert_eq!(
+            app.run(Action::Goto(Some("nope".to_string())), &providers),
+            Err("goto: nothing named nope".to_string())
+        );
+    }
+
+    #[test]
+    fn test_goto_resolves_guests_before_and_after_nodes() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("pve1", NodeStatus::Online, 10.0),
+            create_test_node("pve2", NodeStatus::Online, 10.0),
+        ];
+        app.containers = vec![
+            create_test_container("web-01", "pve1", ContainerStatus::Running, 10.0),
+            create_test_container("web-02", "pve2", ContainerStatus::Running, 10.0),
+            create_test_container("pve1-backup", "pve2", ContainerStatus::Running, 10.0),
+        ];
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+        let goto = |app: &mut App, target: &str| {
+            app.run(Action::Goto(Some(target.to_string())), &providers)
+        };
+
+        // A node's full name comes before a guest it is a prefix of
+        assert_eq!(goto(&mut app, "pve1"), Ok(()));
+        assert_eq!(app.selected_node().unwrap().name, "pve1");
+        assert_eq!(goto(&mut app, "pve1-b"), Ok(()));
+        assert_eq!(app.selected_container().unwrap().name, "pve1-backup");
+
+        assert_eq!(goto(&mut app, "pve2:web"), Ok(()));
+        assert_eq!(app.selected_container().unwrap().name, "web-02");
+        assert_eq!(
+            goto(&mut app, "web"),
+            Err("goto: 'web' matches web-01 (pve1), web-02 (pve2)".to_string())
+        );
+    }
+
+    fn goto_app() -> App {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.nodes = ["pve1", "pve2", "backup"]
+            .iter()
+            .map(|name| create_test_node(name, NodeStatus::Online, 10.0))
+            .collect();
+        app.containers = [("a", 101), ("b", 147), ("c", 14), ("d", 1470), ("e", 200)]
+            .iter()
+            .map(|&(name, vmid)| Container {
+                vmid,
+                ..create_test_container(name, "pve1", ContainerStatus::Running, 1.0)
+            })
+            .collect();
+        app
+    }
+
+    #[test]
+    fn test_prefix_match_wraps_around() {
+        let labels: Vec<String> = ["147", "14", "200", "1470"].map(String::from).to_vec();
+        assert_eq!(prefix_match(&labels, "14", 0), Some(0));
+        assert_eq!(prefix_match(&labels, "14", 2), Some(3));
+        assert_eq!(prefix_match(&labels, "14", 4), Some(0));
+        assert_eq!(prefix_match(&labels, "3", 0), None);
+        assert_eq!(prefix_match(&[], "1", 0), None);
+    }
+
+    #[test]
+    fn test_goto_prompt_jumps_as_digits_are_typed() {
+        let mut app = goto_app();
+        app.container_index = 4;
+        app.run(Action::Goto(None), &[]).unwrap();
+
+        app.push_goto_char('1');
+        assert_eq!(app.selected_container().unwrap().vmid, 101);
+        app.push_goto_char('4');
+        assert_eq!(app.selected_container().unwrap().vmid, 147);
+        assert_eq!(app.goto_matches(), (3, Some(1)));
+        // Guests are matched by VMID: letters are ignored
+        app.push_goto_char('x');
+        assert_eq!(app.goto_prompt.as_ref().unwrap().text, "14");
+        app.push_goto_char('9');
+        assert_eq!(app.selected_container().unwrap().vmid, 147);
+        assert_eq!(app.goto_matches(), (0, None));
+        app.pop_goto_char();
+
+        // Enter keeps the selection; with several matches further Enters cycle
+        app.confirm_goto();
+        assert!(app.goto_prompt.as_ref().unwrap().confirmed);
+        app.confirm_goto();
+        assert_eq!(app.selected_container().unwrap().vmid, 14);
+        app.confirm_goto();
+        app.confirm_goto();
+        assert_eq!(app.selected_container().unwrap().vmid, 147);
+    }
+
+    #[test]
+    fn test_goto_prompt_escape_restores_the_selection() {
+        let mut app = goto_app();
+        app.container_index = 4;
+        app.open_goto_prompt().unwrap();
+        app.push_goto_char('1');
+        app.push_goto_char('4');
+        app.push_goto_char('7');
+        app.push_goto_char('0');
+        assert_eq!(app.selected_container().unwrap().vmid, 1470);
+
+        app.cancel_goto();
+        assert!(app.goto_prompt.is_none());
+        assert_eq!(app.selected_container().unwrap().vmid, 200);
+
+        // Deleting everything typed also goes back
+        app.open_goto_prompt().unwrap();
+        app.push_goto_char('1');
+        app.pop_goto_char();
+        assert_eq!(app.selected_container().unwrap().vmid, 200);
+
+        // A single match closes on Enter
+        app.push_goto_char('2');
+        app.confirm_goto();
+        assert!(app.goto_prompt.is_none());
+    }
+
+    #[test]
+    fn test_goto_prompt_matches_the_filtered_list_and_node_names() {
+        let mut app = goto_app();
+        app.search_query = "b".to_string();
+        app.open_goto_prompt().unwrap();
+        app.push_goto_char('1');
+        // Only "b" (147) is listed, so 101 can't be reached
+        assert_eq!(app.selected_container().unwrap().vmid, 147);
+        assert_eq!(app.goto_matches(), (1, Some(1)));
+        app.cancel_goto();
+        app.search_query.clear();
+
+        app.active_panel = Panel::Nodes;
+        app.open_goto_prompt().unwrap();
+        app.push_goto_char('B');
+        assert_eq!(app.selected_node().unwrap().name, "backup");
+        app.pop_goto_char();
+        app.push_goto_char('p');
+        assert_eq!(app.selected_node().unwrap().name, "pve1");
+        app.push_goto_char('v');
+        app.push_goto_char('e');
+        app.push_goto_char('2');
+        assert_eq!(app.selected_node().unwrap().name, "pve2");
+    }
+
+    #[test]
+    fn test_export_writes_the_view() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("view.json").to_string_lossy().into_owned();
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("pve1", NodeStatus::Online, 10.0)];
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+
+        let export = Action::Export {
+            format: OutputFormat::Json,
+            path: Some(path.clone()),
+        };
+        assert_eq!(app.run(export, &providers), Ok(()));
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"pve1\""));
+        assert_eq!(app.notice, Some(format!("Exported to {}", path)));
+
+        let export = Action::Export {
+            format: OutputFormat::Table,
+            path: Some(
+                dir.path()
+                    .join("missing/view.txt")
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+        };
+        assert!(
+            app.run(export, &providers)
+                .unwrap_err()
+                .starts_with("export: ")
+        );
+    }
+
+    #[test]
+    fn test_console_needs_a_configured_command() {
+        let mut provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.containers[0].vmid = 105;
+        let containers = provider.containers.clone();
+        let mut app = App::new();
+        app.containers = containers.clone();
+
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        assert_eq!(
+            app.run(Action::Console, &providers),
+            Err("console: select a guest first".to_string())
+        );
+        app.active_panel = Panel::Containers;
+        assert_eq!(
+            app.run(Action::Console, &providers),
+            Err("console: no LXC console command configured for homelab".to_string())
+        );
+        assert_eq!(app.console_request, None);
+
+        let mut provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.containers = containers;
+        provider.console = Some("ssh root@{node} -t pct enter {vmid}".to_string());
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        assert_eq!(app.run(Action::Console, &providers), Ok(()));
+        assert_eq!(
+            app.console_request.as_deref(),
+            Some(&["ssh", "root@pve1", "-t", "pct", "enter", "105"].map(String::from)[..])
+        );
+    }
+
+    #[test]
+    fn test_web_console_url_is_queued_for_the_main_loop() {
+        let provider = FakeProvider::new("homelab", &["pve1"]);
+        let mut app = App::new();
+        app.containers = provider.containers.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+
+        assert_eq!(
+            app.run(Action::WebConsole(Handoff::Copy), &providers),
+            Err("webconsole: select a guest first".to_string())
+        );
+        app.active_panel = Panel::Containers;
+        let vmid = app.selected_container().unwrap().vmid;
+        assert_eq!(
+            app.run(Action::WebConsole(Handoff::Open), &providers),
+            Ok(())
+        );
+        assert_eq!(
+            app.url_request,
+            Some((Handoff::Open, format!("https://pve1:8006/?vmid={}", vmid)))
+        );
+
+        app.url_handed_off(Handoff::Copy, Err(io::Error::other("broken pipe")));
+        assert_eq!(
+            app.error_message.as_deref(),
+            Some("webconsole: cannot copy: broken pipe")
+        );
+    }
+
+    #[test]
+    fn test_actions_refused_for_a_token_without_privileges() {
+        let mut app = App::new();
+        let mut audit_only = FakeProvider::new("homelab", &["pve1"]);
+        audit_only.missing_actions = vec![
+            PermissionIssue::new("VM.Migrate", "/vms", "guests cannot be migrated"),
+            PermissionIssue::new("VM.Clone", "/vms", "guests cannot be cloned"),
+        ];
+        let actions = audit_only.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(audit_only),
+            Box::new(FakeProvider::new("office", &["pve9"])),
+        ];
+        app.probe_permissions(&providers);
+        app.refresh(&providers);
+
+        let status = |app: &App, name: &str| {
+            app.provider_status
+                .iter()
+                .find(|s| s.name == name)
+                .unwrap()
+                .actions_available
+        };
+        assert!(!status(&app, "homelab"));
+        assert!(status(&app, "office"));
+
+        // Drain on homelab's node is refused with the reason
+        assert_eq!(app.selected_node().unwrap().name, "pve1");
+        assert_eq!(
+            app.run(Action::Drain, &providers),
+            Err("homelab: token lacks VM.Migrate on /vms — guests cannot be migrated".to_string())
+        );
+        assert!(app.drain.is_none());
+        // The other provider's node can still be drained
+        app.select_next();
+        assert_eq!(app.run(Action::Drain, &providers), Ok(()));
+
+        app.active_panel = Panel::Containers;
+        assert_eq!(app.selected_container().unwrap().name, "ct-pve1");
+        assert!(app.refusal(&Action::Clone).is_some());
+        // Consoles don't go through the API
+        assert!(app.refusal(&Action::Console).is_none());
+        assert!(actions.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_read_only_refuses_actions_in_the_ui() {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        provider.console = Some("ssh root@{node} -t pct enter {vmid}".to_string());
+        let mut app = App::new();
+        app.nodes = provider.nodes.clone();
+        app.containers = provider.containers.clone();
+        app.read_only = true;
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+
+        let refused = Err("read-only mode: actions are disabled".to_string());
+        assert_eq!(app.run(Action::Drain, &providers), refused);
+        assert!(app.drain.is_none());
+        app.active_panel = Panel::Containers;
+        assert_eq!(app.run(Action::Console, &providers), refused);
+        assert_eq!(app.console_request, None);
+        assert_eq!(app.run(Action::Clone, &providers), refused);
+        assert!(app.clone.is_none());
+
+        // Looking around still works
+        assert_eq!(app.run(Action::Pin, &providers), Ok(()));
+        assert_eq!(app.run(Action::SelectNext, &providers), Ok(()));
+    }
+
+    #[test]
+    fn test_filtered_nodes() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("alpha", NodeStatus::Online, 10.0),
+            create_test_node("beta", NodeStatus::Online, 20.0),
+            create_test_node("alphabeta", NodeStatus::Online, 30.0),
+        ];
+
+        // No filter - all nodes
+        assert_eq!(app.filtered_nodes().len(), 3);
+
+        // Filter by "alpha"
+        app.search_query = "alpha".to_string();
+        let filtered = app.filtered_nodes();
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().any(|n| n.name == "alpha"));
+        assert!(filtered.iter().any(|n| n.name == "alphabeta"));
+    }
+
+    #[test]
+    fn test_filtered_containers_by_name() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("web-server", "node1", ContainerStatus::Running, 10.0),
+            create_test_container("database", "node1", ContainerStatus::Running, 20.0),
+            create_test_container("web-cache", "node2", ContainerStatus::Running, 30.0),
+        ];
+
+        app.search_query = "web".to_string();
+        let filtered = app.filtered_containers();
+        assert_eq!(filtered.len(), 2);
+    }
+
+    #[test]
+    fn test_typing_a_search_reuses_previous_matches() {
+        let mut app = App::new();
+        app.containers = (0..10_000)
+            .map(|i| {
+                let role = ["web", "db", "cache", "media"][i % 4];
+                let name = format!("{}-{:05}", role, i);
+                create_test_container(&name, "node1", ContainerStatus::Running, 10.0)
+            })
+            .collect();
+        app.data_changed();
+        let evaluated = |app: &App| app.filter_cache.borrow().evaluated;
+
+        // Without a query nothing is matched, and asking again is free
+        assert_eq!(app.filtered_containers().len(), 10_000);
+        assert_eq!(evaluated(&app), 0);
+
+        app.push_search_char('w');
+        assert_eq!(app.filtered_containers().len(), 2_500);
+        assert_eq!(evaluated(&app), 10_000);
+
+        // Each further character only re-checks what still matched
+        app.push_search_char('e');
+        app.push_search_char('b');
+        assert_eq!(app.filtered_containers().len(), 2_500);
+        assert_eq!(evaluated(&app), 12_500);
+        app.push_search_char('-');
+        app.push_search_char('0');
+        app.push_search_char('0');
+        app.push_search_char('0');
+        assert_eq!(app.filtered_containers().len(), 25);
+        assert_eq!(evaluated(&app), 15_000);
+
+        // Repeated lookups within a frame reuse the result
+        for _ in 0..10 {
+            app.selected_container();
+            app.filtered_containers();
+        }
+        assert_eq!(evaluated(&app), 15_000);
+
+        // Deleting a character widens the search, so everything is checked
+        app.pop_search_char();
+        assert_eq!(app.filtered_containers().len(), 250);
+        assert_eq!(evaluated(&app), 25_000);
+
+        // As does any change to the data
+        app.containers.truncate(100);
+        app.data_changed();
+        assert_eq!(app.filtered_containers().len(), 25);
+        assert_eq!(evaluated(&app), 25_100);
+    }
+
+    #[test]
+    fn test_filtered_containers_by_node_field() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("ct1", "production", ContainerStatus::Running, 10.0),
+            create_test_container("ct2", "staging", ContainerStatus::Running, 20.0),
+            create_test_container("ct3", "production", ContainerStatus::Running, 30.0),
+        ];
+
+        app.search_query = "node:prod".to_string();
+        let filtered = app.filtered_containers();
+        assert_eq!(filtered.len(), 2);
+    }
+
+    #[test]
+    fn test_filtered_containers_by_lock() {
+        let mut app = App::new();
+        let mut backup = create_test_container("ct1", "node1", ContainerStatus::Running, 10.0);
+        backup.lock = Some("backup".to_string());
+        let mut migrate = create_test_container("ct2", "node1", ContainerStatus::Running, 10.0);
+        migrate.lock = Some("migrate".to_string());
+        let unlocked = create_test_container("lock-test", "node1", ContainerStatus::Running, 10.0);
+        app.containers = vec![backup, migrate, unlocked];
+
+        app.search_query = "lock:".to_string();
+        assert_eq!(app.filtered_containers().len(), 2);
+
+        app.search_query = "lock:backup".to_string();
+        let filtered = app.filtered_containers();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].name, "ct1");
+    }
+
+    #[test]
+    fn test_recent_filter_composes_with_search() {
+        let mut app = App::new();
+        let uptimes = [
+            ("web-new", ContainerStatus::Running, 300),
+            ("web-old", ContainerStatus::Running, 7200),
+            ("db-new", ContainerStatus::Running, 600),
+            ("web-stopped", ContainerStatus::Stopped, 0),
+        ];
+        app.containers = uptimes
+            .into_iter()
+            .map(|(name, status, uptime)| {
+                let mut c = create_test_container(name, "node1", status, 10.0);
+                c.uptime = uptime;
+                c
+            })
+            .collect();
+        assert_eq!(app.recent_restarts(), None);
+
+        app.container_index = 1;
+        app.run(Action::Recent(None), &[]).unwrap();
+        assert_eq!(app.recent_filter, RecentFilter::Minutes15);
+        let names: Vec<&str> = app
+            .filtered_containers()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["web-new", "db-new"]);
+        // web-old is filtered out, so the selection starts over
+        assert_eq!(app.container_index, 0);
+        assert_eq!(app.recent_restarts(), Some(2));
+
+        app.search_query = "web".to_string();
+        assert_eq!(app.filtered_containers().len(), 1);
+        // The counter ignores the search
+        assert_eq!(app.recent_restarts(), Some(2));
+
+        app.run(Action::Recent(Some(RecentFilter::Day)), &[])
+            .unwrap();
+        assert_eq!(app.filtered_containers().len(), 2);
+        app.run(Action::Recent(Some(RecentFilter::Off)), &[])
+            .unwrap();
+        assert_eq!(app.filtered_containers().len(), 3);
+    }
+
+    #[test]
+    fn test_filtered_by_query_terms() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("pve1", NodeStatus::Online, 10.0),
+            create_test_node("pve2", NodeStatus::Online, 60.0),
+        ];
+        app.containers = vec![
+            create_test_container("media-server", "pve2", ContainerStatus::Running, 75.0),
+            create_test_container("media-cache", "pve2", ContainerStatus::Running, 20.0),
+            create_test_container("media-old", "pve2", ContainerStatus::Stopped, 0.0),
+            create_test_container("media-backup", "pve1", ContainerStatus::Running, 90.0),
+        ];
+
+        app.search_query = "node:pve2 status:running cpu>50 media".to_string();
+        let names: Vec<&str> = app
+            .filtered_containers()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["media-server"]);
+        assert!(app.search_error().is_none());
+
+        app.search_query = "cpu>50".to_string();
+        let nodes: Vec<&str> = app
+            .filtered_nodes()
+            .iter()
+            .map(|n| n.name.as_str())
+            .collect();
+        assert_eq!(nodes, ["pve2"]);
+    }
+
+    #[test]
+    fn test_invalid_query_falls_back_to_substring() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("web", "pve1", ContainerStatus::Running, 10.0),
+            create_test_container("cpu>x", "pve1", ContainerStatus::Running, 10.0),
+        ];
+
+        app.search_query = "cpu>x".to_string();
+        assert_eq!(
+            app.search_error().map(|e| e.to_string()).as_deref(),
+            Some("'x' is not a number")
+        );
+        let filtered = app.filtered_containers();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].name, "cpu>x");
+    }
+
+    #[test]
+    fn test_search_case_insensitive() {
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("ProductionNode", NodeStatus::Online, 10.0)];
+
+        app.search_query = "production".to_string();
+        assert_eq!(app.filtered_nodes().len(), 1);
+
+        app.search_query = "PRODUCTION".to_string();
+        assert_eq!(app.filtered_nodes().len(), 1);
+    }
+
+    // Summary tests
+    #[test]
+    fn test_nodes_summary() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("node1", NodeStatus::Online, 10.0),
+            create_test_node("node2", NodeStatus::Offline, 0.0),
+            create_test_node("node3", NodeStatus::Online, 30.0),
+        ];
+
+        let (online, total) = app.nodes_summary();
+        assert_eq!(online, 2);
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn test_allocations_per_node() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("pve1", NodeStatus::Online, 0.0),
+            create_test_node("pve2", NodeStatus::Online, 0.0),
+        ];
+        let mut vm = create_test_container("vm", "pve1", ContainerStatus::Running, 0.0);
+        vm.container_type = ContainerType::VM;
+        vm.max_cpu = 4;
+        let mut unit = create_test_container("unit", "pve1", ContainerStatus::Running, 0.0);
+        unit.container_type = ContainerType::Service;
+        let mut gone = create_test_container("gone", "pve1", ContainerStatus::Running, 0.0);
+        gone.missing = true;
+        app.containers = vec![
+            vm,
+            create_test_container("ct", "pve1", ContainerStatus::Running, 0.0),
+            create_test_container("off", "pve1", ContainerStatus::Stopped, 0.0),
+            unit,
+            gone,
+            // A node the provider didn't list
+            create_test_container("stray", "pve9", ContainerStatus::Running, 0.0),
+        ];
+
+        let allocations = app.allocations();
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(
+            allocations[&NodeId::new("test", "pve1")],
+            Allocation {
+                memory: 2048,
+                cores: 5,
+                memory_all: 3072,
+                cores_all: 6,
+            }
+        );
+        assert_eq!(
+            allocations[&NodeId::new("test", "pve2")],
+            Allocation::default()
+        );
+        assert_eq!(overcommit_ratio(3072, 1024), Some(3.0));
+        assert_eq!(overcommit_ratio(3072, 0), None);
+    }
+
+    #[test]
+    fn test_containers_summary() {
+        let mut app = App::new();
+        app.containers = vec![
+            create_test_container("ct1", "node1", ContainerStatus::Running, 10.0),
+            create_test_container("ct2", "node1", ContainerStatus::Stopped, 0.0),
+            create_test_container("ct3", "node1", ContainerStatus::Running, 30.0),
+            create_test_container("ct4", "node1", ContainerStatus::Stopped, 0.0),
+        ];
+
+        let (running, total) = app.containers_summary();
+        assert_eq!(running, 2);
+        assert_eq!(total, 4);
+    }
+
+    #[test]
+    fn test_health_title() {
+        let mut app = App::new();
+        assert_eq!(app.health_title(), "pulse: ok");
+
+        let mut template = create_test_container("tpl", "node1", ContainerStatus::Stopped, 0.0);
+        template.is_template = true;
+        app.nodes = vec![
+            create_test_node("node1", NodeStatus::Online, 10.0),
+            create_test_node("node2", NodeStatus::Offline, 0.0),
+        ];
+        app.containers = vec![
+            create_test_container("ct1", "node1", ContainerStatus::Running, 10.0),
+            create_test_container("ct2", "node1", ContainerStatus::Stopped, 0.0),
+            create_test_container("ct3", "node1", ContainerStatus::Running, 95.0),
+            template,
+        ];
+        assert_eq!(app.health_title(), "pulse: 2 down, 1 crit");
+
+        app.replication_jobs = vec![create_test_job(100, "node1", 2)];
+        assert_eq!(app.health_title(), "pulse: 2 down, 1 crit, 1 alert");
+    }
+
+    #[test]
+    fn test_templates_leave_summary_and_stopped_filter() {
+        let mut app = App::new();
+        let mut template =
+            create_test_container("debian-tpl", "node1", ContainerStatus::Stopped, 0.0);
+        template.is_template = true;
+        app.containers = vec![
+            create_test_container("web", "node1", ContainerStatus::Running, 10.0),
+            create_test_container("old", "node1", ContainerStatus::Stopped, 0.0),
+            template,
+        ];
+        assert_eq!(app.containers_summary(), (1, 2));
+
+        app.search_query = "status:stopped".to_string();
+        let names: Vec<&str> = app
+            .filtered_containers()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["old"]);
+
+        app.search_query.clear();
+        assert_eq!(app.filtered_containers().len(), 3);
+        app.run(Action::Templates, &[]).unwrap();
+        assert!(app.hide_templates);
+        assert_eq!(app.filtered_containers().len(), 2);
+        // Hiding them doesn't change the totals, which never counted them
+        assert_eq!(app.containers_summary(), (1, 2));
+        app.run(Action::Templates, &[]).unwrap();
+        assert_eq!(app.filtered_containers().len(), 3);
+    }
+
+    #[test]
+    fn test_templates_are_not_problems_or_allocations() {
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("node1", NodeStatus::Online, 10.0)];
+        let mut template =
+            create_test_container("debian-tpl", "node1", ContainerStatus::Stopped, 0.0);
+        template.is_template = true;
+        app.containers = vec![
+            create_test_container("old", "node1", ContainerStatus::Stopped, 0.0),
+            template,
+        ];
+        let allocation = &app.allocations()[&app.nodes[0].id];
+        assert_eq!(allocation.memory_all, 1024);
+
+        app.problems_first = true;
+        app.sort_field = SortField::Name;
+        app.sort_items();
+        assert_eq!(app.containers[0].name, "old");
+    }
+
+    // Replication tests
+    fn create_test_job(guest: u32, source: &str, fail_count: u32) -> ReplicationJob {
+        ReplicationJob {
+            guest: GuestId::new("test", guest),
+            source: source.to_string(),
+            target: "pve2".to_string(),
+            last_sync: 1_700_000_000,
+            fail_count,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_failing_replication_matches_guest_and_node() {
+        let mut app = App::new();
+        let mut ct = create_test_container("ct1", "pve1", ContainerStatus::Running, 10.0);
+        ct.vmid = 100;
+        ct.id = GuestId::new("test", 100);
+        app.replication_jobs = vec![
+            create_test_job(100, "pve3", 2),
+            create_test_job(100, "pve1", 0),
+        ];
+        assert!(app.failing_replication(&ct).is_none());
+
+        app.replication_jobs.push(create_test_job(100, "pve1", 1));
+        let job = app.failing_replication(&ct).unwrap();
+        assert_eq!(job.source, "pve1");
+        assert_eq!(job.fail_count, 1);
+    }
+
+    #[test]
+    fn test_alarm_rings_for_new_critical_conditions_only() {
+        let mut app = App::new();
+        app.alarm.bell = true;
+        app.nodes = vec![create_test_node("pve1", NodeStatus::Online, 10.0)];
+        app.containers = vec![
+            create_test_container("web", "pve1", ContainerStatus::Running, 10.0),
+            create_test_container("db", "pve1", ContainerStatus::Stopped, 0.0),
+        ];
+        let now = Instant::now();
+        // Already stopped at startup: nothing new
+        assert!(app.alarm.update(app.critical_conditions(), now).is_empty());
+
+        app.containers[0].cpu_usage = 99.0;
+        app.nodes[0].status = NodeStatus::Offline;
+        let raised = app.alarm.update(app.critical_conditions(), now);
+        assert_eq!(raised, ["test/pve1 down", "test/web critical"]);
+        assert!(app.alarm.take_bell());
+
+        // Still the same problems next refresh
+        assert!(app.alarm.update(app.critical_conditions(), now).is_empty());
+        assert!(!app.alarm.take_bell());
+
+        // Muted from the keyboard: noted, but quiet
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+        app.run(Action::Mute, &providers).unwrap();
+        app.replication_jobs = vec![create_test_job(100, "pve1", 2)];
+        app.containers[0].id = app.replication_jobs[0].guest.clone();
+        assert!(!app.alarm.update(app.critical_conditions(), now).is_empty());
+        assert!(!app.alarm.take_bell());
+    }
+
+    #[test]
+    fn test_alert_count_counts_failing_jobs() {
+        let mut app = App::new();
+        assert_eq!(app.alert_count(), 0);
+
+        app.replication_jobs = vec![
+            create_test_job(100, "pve1", 0),
+            create_test_job(101, "pve1", 3),
+            create_test_job(102, "pve1", 1),
+        ];
+        assert_eq!(app.alert_count(), 2);
+    }
+
+    #[test]
+    fn test_snoozed_alert_is_silenced_until_restart_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+        let mut app = App::new();
+        app.load_state(Some(path.clone()));
+        let mut ct = create_test_container("ct1", "pve1", ContainerStatus::Running, 10.0);
+        ct.id = GuestId::new("test", 100);
+        ct.uptime = 3600;
+        app.containers = vec![ct];
+        app.active_panel = Panel::Containers;
+
+        assert_eq!(
+            app.run(Action::Snooze(None), &providers),
+            Err("ct1 has no alert to snooze".to_string())
+        );
+
+        app.replication_jobs = vec![create_test_job(100, "pve1", 2)];
+        assert_eq!(app.alert_count(), 1);
+        app.run(Action::Snooze(None), &providers).unwrap();
+        assert_eq!(app.snooze_menu.as_ref().unwrap().choices().len(), 3);
+        app.choose_snooze(2);
+        assert!(app.snooze_menu.is_none());
+        assert_eq!(
+            app.notice.as_deref(),
+            Some("Snoozed alerts on ct1 until restart")
+        );
+        assert_eq!(app.alert_count(), 0);
+        let container = app.selected_container().unwrap();
+        assert!(app.replication_alert(container).is_none());
+        assert!(app.failing_replication(container).is_some());
+
+        // A restarted pulse keeps it snoozed
+        let mut other = App::new();
+        other.load_state(Some(path.clone()));
+        assert_eq!(other.snoozes, app.snoozes);
+
+        // Still the same boot: kept; rebooted: the alert is back
+        app.sweep_snoozes(unix_now());
+        assert_eq!(app.alert_count(), 0);
+        app.containers[0].uptime = 10;
+        app.sweep_snoozes(unix_now());
+        assert!(app.snoozes.is_empty());
+        assert_eq!(app.alert_count(), 1);
+        other.load_state(Some(path));
+        assert!(other.snoozes.is_empty());
+    }
+
+    #[test]
+    fn test_snooze_can_be_woken_early() {
+        let providers: Vec<Box<dyn Provider>> = Vec::new();
+        let mut app = App::new();
+        let mut node = create_test_node("pve1", NodeStatus::Online, 5.0);
+        node.disks = NodeDisks::Listed(vec![Disk {
+            dev_path: "/dev/sda".to_string(),
+            model: "SSD".to_string(),
+            serial: "S1".to_string(),
+            size: 1024,
+            health: "FAILED".to_string(),
+            wearout: None,
+        }]);
+        app.nodes = vec![node];
+
+        app.run(Action::Snooze(Some(SnoozeLength::Minutes15)), &providers)
+            .unwrap();
+        assert!(!app.disk_alert(&app.nodes[0]));
+        assert_eq!(app.alert_count(), 0);
+
+        app.open_snooze_menu().unwrap();
+        let menu = app.snooze_menu.as_ref().unwrap();
+        assert_eq!(menu.choices().last(), Some(&None));
+        app.close_snooze_menu();
+        app.run(Action::Wake, &providers).unwrap();
+        assert!(app.disk_alert(&app.nodes[0]));
+        assert_eq!(app.alert_count(), 1);
+    }
+
+    #[test]
+    fn test_alert_count_counts_failing_disks() {
+        let mut app = App::new();
+        let disk = |health: &str, wearout: Option<u8>| Disk {
+            dev_path: "/dev/sda".to_string(),
+            model: "SSD".to_string(),
+            serial: "S1".to_string(),
+            size: 1024,
+            health: health.to_string(),
+            wearout,
+        };
+        let mut node = create_test_node("pve1", NodeStatus::Online, 10.0);
+        node.disks = NodeDisks::Listed(vec![
+            disk("PASSED", Some(95)),
+            disk("FAILED", None),
+            disk("PASSED", Some(4)),
+            disk("UNKNOWN", None),
+        ]);
+        app.nodes = vec![node];
+        assert_eq!(app.alert_count(), 2);
+    }
+
+    #[test]
+    fn test_lost_quorum_is_tracked_and_alerted() {
+        let mut app = App::new();
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        provider.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: true,
+            nodes_online: 3,
+            nodes_total: 3,
+            firewall: None,
+            ha: Default::default(),
+        });
+        let standalone = FakeProvider::new("garage", &["pve"]);
+        let mut providers: Vec<Box<dyn Provider>> = vec![Box::new(provider), Box::new(standalone)];
+
+        app.refresh(&providers);
+        assert_eq!(app.clusters().count(), 1);
+        assert_eq!(app.alert_count(), 0);
+
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        provider.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: false,
+            nodes_online: 1,
+            nodes_total: 3,
+            firewall: None,
+            ha: Default::default(),
+        });
+        providers[0] = Box::new(provider);
+
+        app.refresh(&providers);
+        assert!(!app.clusters().next().unwrap().quorate);
+        assert_eq!(app.alert_count(), 1);
+    }
+
+    // Identity tests
+    #[test]
+    fn test_guest_ids_unique_across_providers() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        let make = |provider: &str| {
+            let mut fake = FakeProvider::new(provider, &["pve1"]);
+            fake.containers[0].vmid = 100;
+            fake.containers[0].id = GuestId::new(provider, 100);
+            fake
+        };
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(make("homelab")), Box::new(make("office"))];
+
+        app.refresh(&providers);
+
+        assert_eq!(app.containers.len(), 2);
+        assert_eq!(app.containers[0].vmid, app.containers[1].vmid);
+        assert_ne!(app.containers[0].id, app.containers[1].id);
+        assert_eq!(app.containers[0].id.to_string(), "homelab/100");
+        assert_eq!(app.containers[1].id.to_string(), "office/100");
+
+        let ids: std::collections::HashSet<_> = app.containers.iter().map(|c| &c.id).collect();
+        assert_eq!(ids.len(), 2);
+    }
+
+    #[test]
+    fn test_replication_alert_does_not_leak_across_providers() {
+        let mut app = App::new();
+        let mut ours = create_test_container("vm", "pve1", ContainerStatus::Running, 1.0);
+        ours.id = GuestId::new("homelab", 100);
+        let mut theirs = ours.clone();
+        theirs.id = GuestId::new("office", 100);
+
+        let mut job = create_test_job(100, "pve1", 2);
+        job.guest = GuestId::new("office", 100);
+        app.replication_jobs = vec![job];
+
+        assert!(app.failing_replication(&ours).is_none());
+        assert!(app.failing_replication(&theirs).is_some());
+    }
+
+    #[test]
+    fn test_refresh_keeps_selection_when_items_move() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1", "pve2"]))];
+        app.refresh(&providers);
+        app.container_index = 1;
+        assert_eq!(app.selected_container().unwrap().name, "ct-pve2");
+
+        // A new guest sorting before the selection must not shift it
+        let mut fake = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        fake.containers.push(Container {
+            id: GuestId::new("homelab", "aaa"),
+            ..create_test_container("aaa", "pve1", ContainerStatus::Running, 1.0)
+        });
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(fake)];
+        app.refresh(&providers);
+
+        assert_eq!(app.selected_container().unwrap().name, "ct-pve2");
+    }
+
+    fn visual_app() -> App {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| create_test_container(name, "pve1", ContainerStatus::Running, 1.0))
+            .collect();
+        app
+    }
+
+    fn marked_names(app: &App) -> Vec<String> {
+        let mut names: Vec<String> = app.marked.iter().map(|id| id.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_visual_mode_marks_a_range() {
+        let mut app = visual_app();
+        app.container_index = 3;
+        app.toggle_mark().unwrap();
+        app.container_index = 1;
+
+        app.start_visual().unwrap();
+        assert_eq!(app.visual_range(), Some(1..=1));
+        app.select_next();
+        app.select_next();
+        assert_eq!(app.visual_range(), Some(1..=3));
+        // Nothing is marked until the range is confirmed
+        assert_eq!(marked_names(&app), ["test/d"]);
+
+        app.confirm_visual();
+        assert!(app.visual_anchor.is_none());
+        assert_eq!(app.notice.as_deref(), Some("Marked 3 guests"));
+        assert_eq!(marked_names(&app), ["test/b", "test/c", "test/d"]);
+    }
+
+    #[test]
+    fn test_visual_range_extends_upwards_and_cancels() {
+        let mut app = visual_app();
+        app.container_index = 2;
+        app.run(Action::Visual, &[]).unwrap();
+        app.select_previous();
+        app.select_previous();
+        assert_eq!(app.visual_range(), Some(0..=2));
+
+        app.cancel_visual();
+        assert_eq!(app.visual_range(), None);
+        assert!(app.marked.is_empty());
+
+        app.active_panel = Panel::Nodes;
+        assert_eq!(
+            app.run(Action::Visual, &[]),
+            Err("visual: select a guest first".to_string())
+        );
+    }
+
+    #[test]
+    fn test_visual_range_survives_a_refresh() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(FakeProvider::new(
+            "homelab",
+            &["pve1", "pve2", "pve3"],
+        ))];
+        app.refresh(&providers);
+        app.container_index = 1;
+        app.start_visual().unwrap();
+        app.select_next();
+
+        // New guests sorting first shift every row down by two
+        let mut fake = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        for name in ["aaa", "aab"] {
+            fake.containers.push(Container {
+                id: GuestId::new("homelab", name),
+                ..create_test_container(name, "pve1", ContainerStatus::Running, 1.0)
+            });
+        }
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(fake)];
+        app.refresh(&providers);
+
+        assert_eq!(app.visual_range(), Some(3..=4));
+        app.confirm_visual();
+        assert_eq!(marked_names(&app), ["homelab/ct-pve2", "homelab/ct-pve3"]);
+
+        // The anchor's guest going away ends visual mode; marks on gone guests go too
+        app.start_visual().unwrap();
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1", "pve2"]))];
+        app.refresh(&providers);
+        app.refresh(&providers);
+        assert!(app.visual_anchor.is_none());
+        assert_eq!(marked_names(&app), ["homelab/ct-pve2"]);
+    }
+
+    #[test]
+    fn test_mark_toggles_and_clear_unmarks() {
+        let mut app = visual_app();
+        app.run(Action::Mark, &[]).unwrap();
+        app.select_next();
+        app.run(Action::Mark, &[]).unwrap();
+        assert_eq!(marked_names(&app), ["test/a", "test/b"]);
+        app.run(Action::Mark, &[]).unwrap();
+        assert_eq!(marked_names(&app), ["test/a"]);
+
+        app.search_query = "a".to_string();
+        app.run(Action::Clear, &[]).unwrap();
+        assert_eq!(marked_names(&app), ["test/a"]);
+        app.run(Action::Clear, &[]).unwrap();
+        assert!(app.marked.is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_column_position() {
+        let mut app = App::new();
+        app.sort_columns
+            .replace(vec![SortField::Status, SortField::Name, SortField::Disk]);
+
+        app.run(Action::SortColumn(3), &[]).unwrap();
+        assert_eq!(app.sort_field, SortField::Disk);
+        assert!(app.sort_ascending);
+        // The same column again reverses
+        app.run(Action::SortColumn(3), &[]).unwrap();
+        assert_eq!(app.sort_field, SortField::Disk);
+        assert!(!app.sort_ascending);
+        app.run(Action::SortColumn(1), &[]).unwrap();
+        assert_eq!(app.sort_field, SortField::Status);
+
+        assert_eq!(
+            app.run(Action::SortColumn(4), &[]),
+            Err("column: no column 4".to_string())
+        );
+        assert_eq!(app.sort_field, SortField::Status);
+    }
+
+    // Comparison tests
+    #[test]
+    fn test_pin_two_nodes_starts_comparison() {
+        let mut app = App::new();
+        app.nodes = vec![
+            create_test_node("node1", NodeStatus::Online, 10.0),
+            create_test_node("node2", NodeStatus::Online, 20.0),
+        ];
+
+        app.toggle_pin();
+        assert_eq!(app.pinned, Some(ItemId::Node(NodeId::new("test", "node1"))));
+        assert!(app.comparison.is_none());
+
+        app.select_next();
+        app.toggle_pin();
+        assert!(app.pinned.is_none());
+        assert_eq!(
+            app.comparison,
+            Some((
+                ItemId::Node(NodeId::new("test", "node1")),
+                ItemId::Node(NodeId::new("test", "node2"))
+            ))
+        );
+
+        app.exit_comparison();
+        assert!(app.comparison.is_none());
+    }
+
+    #[test]
+    fn test_pin_same_item_twice_unpins() {
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("node1", NodeStatus::Online, 10.0)];
+
+        app.toggle_pin();
+        app.toggle_pin();
+
+        assert!(app.pinned.is_none());
+        assert!(app.comparison.is_none());
+    }
+
+    #[test]
+    fn test_pin_across_panels_repins() {
+        let mut app = App::new();
+        app.nodes = vec![create_test_node("node1", NodeStatus::Online, 10.0)];
+        app.containers = vec![create_test_container(
+            "ct1",
+            "node1",
+            ContainerStatus::Running,
+            5.0,
+        )];
+
+        app.toggle_pin();
+        app.next_panel();
+        app.toggle_pin();
+
+        assert_eq!(app.pinned, Some(ItemId::Guest(GuestId::new("test", "ct1"))));
+        assert!(app.comparison.is_none());
+    }
+
+    #[test]
+    fn test_compared_items_follow_refreshes() {
+        let mut app = App::new();
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1", "pve2"]))];
+        app.refresh(&providers);
+        app.toggle_pin();
+        app.select_next();
+        app.toggle_pin();
+        let Some((ItemId::Node(a), ItemId::Node(b))) = app.comparison.clone() else {
+            panic!("expected a node comparison");
+        };
+
+        let mut updated = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        updated.nodes[1].cpu_usage = 99.0;
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(updated)];
+        app.refresh(&providers);
+
+        assert_eq!(app.node_by_id(&a).unwrap().name, "pve1");
+        assert_eq!(app.node_by_id(&b).unwrap().cpu_usage, 99.0);
+    }
+
+    // Permission probe tests
+    #[test]
+    fn test_probe_records_permission_issues() {
+        let mut app = App::new();
+        let mut provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.missing = vec![PermissionIssue::new(
+            "VM.Audit",
+            "/vms",
+            "containers will not be shown",
+        )];
+        let mut providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+
+        app.probe_permissions(&providers);
+
+        assert_eq!(app.provider_status[0].permission_issues.len(), 1);
+        assert_eq!(
+            app.error_message.as_deref(),
+            Some("homelab: token lacks VM.Audit on /vms — containers will not be shown")
+        );
+
+        // Fixed on the server: probing again clears it
+        providers[0] = Box::new(FakeProvider::new("homelab", &["pve1"]));
+        app.probe_permissions(&providers);
+        assert!(app.provider_status[0].permission_issues.is_empty());
+    }
+
+    #[test]
+    fn test_probe_failure_is_reported() {
+        let mut app = App::new();
+        let provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+
+        app.probe_permissions(&providers);
+
+        assert!(app.provider_status.is_empty());
+        assert_eq!(
+            app.error_log[0].message,
+            "permission probe failed: connection refused"
+        );
+    }
+
+    // Backoff tests
+    #[test]
+    fn test_down_provider_is_backed_off() {
+        let mut app = App::new();
+        let failing = FakeProvider::new("remote", &["pve9"]);
+        failing.fail.set(true);
+        let calls = failing.calls.clone();
+        let healthy = FakeProvider::new("homelab", &["pve1"]);
+        let healthy_calls = healthy.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(healthy), Box::new(failing)];
+
+        app.refresh_due(&providers);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(app.error_log[0].count, 1);
+
+        // Within the backoff delay the dead provider is skipped, the healthy
+        // one still refreshes and the error stays on screen
+        app.refresh_due(&providers);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(healthy_calls.get(), 2);
+        assert!(app.error_message.as_ref().unwrap().starts_with("remote:"));
+        assert_eq!(app.error_log[0].count, 1);
+
+        let status = app
+            .provider_status
+            .iter()
+            .find(|s| s.name == "remote")
+            .unwrap();
+        assert!(status.is_down());
+        assert!(status.backoff.retry_in(Instant::now()).is_some());
+    }
+
+    #[test]
+    fn test_manual_refresh_bypasses_backoff() {
+        let mut app = App::new();
+        let failing = FakeProvider::new("remote", &["pve9"]);
+        failing.fail.set(true);
+        let calls = failing.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(failing)];
+
+        app.refresh_due(&providers);
+        app.refresh(&providers);
+        assert_eq!(calls.get(), 2);
+        assert_eq!(app.provider_status[0].backoff.failures(), 2);
+    }
+
+    #[test]
+    fn test_repeated_refresh_requests_run_once() {
+        let mut app = App::new();
+        let provider = FakeProvider::new("homelab", &["pve1"]);
+        let calls = provider.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        assert_eq!(calls.get(), 1);
+
+        for _ in 0..3 {
+            app.run(Action::Refresh, &providers).unwrap();
+        }
+        let (now, wall) = (Instant::now(), SystemTime::now());
+        app.refresh_if_due(&providers, now, wall);
+        app.refresh_if_due(&providers, now, wall);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_resume_refreshes_once_right_away() {
+        let mut app = App::new();
+        let provider = FakeProvider::new("homelab", &["pve1"]);
+        let calls = provider.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let (start, wall) = (Instant::now(), SystemTime::now());
+        app.refresh_if_due(&providers, start, wall);
+        assert_eq!(calls.get(), 1);
+
+        // Asleep for an hour; the regular interval hasn't passed on the monotonic clock
+        let woke = start + Duration::from_secs(1);
+        app.refresh_if_due(&providers, woke, wall + Duration::from_secs(3601));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(app.notice.as_deref(), Some("Refreshed after 1h 0m asleep"));
+        app.refresh_if_due(&providers, woke, wall + Duration::from_secs(3601));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_refresh_age_is_capped() {
+        assert_eq!(refresh_age(Duration::from_secs(0)), "0s ago");
+        assert_eq!(refresh_age(Duration::from_secs(59)), "59s ago");
+        assert_eq!(refresh_age(Duration::from_secs(3599)), "59m ago");
+        assert_eq!(refresh_age(Duration::from_secs(3600)), "> 1h, data stale");
+        assert_eq!(refresh_age(Duration::MAX), "> 1h, data stale");
+    }
+
+    #[test]
+    fn test_success_ends_backoff() {
+        let mut app = App::new();
+        let provider = FakeProvider::new("remote", &["pve9"]);
+        provider.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        assert!(app.provider_status[0].is_down());
+
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("remote", &["pve9"]))];
+        app.refresh(&providers);
+
+        assert!(!app.provider_status[0].is_down());
+        assert!(app.provider_status[0].backoff.is_due(Instant::now()));
+    }
+
+    #[test]
+    fn test_first_load_applies_each_provider_as_it_arrives() {
+        let mut app = App::new();
+        let failing = FakeProvider::new("remote", &["pve9"]);
+        failing.fail.set(true);
+        let homelab = FakeProvider::new("homelab", &["pve1"]);
+        let homelab_calls = homelab.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(failing),
+            Box::new(homelab),
+            Box::new(FakeProvider::new("office", &["pve5"])),
+        ];
+        app.start_loading(&providers);
+        assert!(app.is_loading());
+
+        // Nothing is fetched on this thread while the loader works
+        app.refresh_if_due(&providers, Instant::now(), SystemTime::now());
+        assert_eq!(homelab_calls.get(), 0);
+
+        // A failure doesn't hold up the rest
+        app.load(
+            &providers,
+            Loaded::fetch(providers[0].as_ref()),
+            Instant::now(),
+        );
+        assert!(app.nodes.is_empty());
+        assert!(app.error_message.as_deref().unwrap().starts_with("remote:"));
+        app.load(
+            &providers,
+            Loaded::fetch(providers[1].as_ref()),
+            Instant::now(),
+        );
+        assert_eq!(app.nodes.len(), 1);
+        assert!(app.is_loading());
+        // Its error stays visible while the others load
+        assert!(app.error_message.as_deref().unwrap().starts_with("remote:"));
+
+        // Turned off before it arrived: the load ends without it
+        app.provider_status_entry("office").disabled = true;
+        assert!(!app.is_loading());
+        app.load(
+            &providers,
+            Loaded::fetch(providers[2].as_ref()),
+            Instant::now(),
+        );
+        assert_eq!(app.nodes.len(), 1);
+        assert!(!app.is_provider_loading("office"));
+        assert!(app.has_ever_loaded);
+    }
+
+    #[test]
+    fn test_has_ever_loaded_after_first_success() {
+        let mut app = App::new();
+        let failing = FakeProvider::new("remote", &["pve9"]);
+        failing.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(failing)];
+        app.refresh(&providers);
+        assert!(!app.has_ever_loaded);
+
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(FakeProvider::new("homelab", &["pve1"])),
+            providers.into_iter().next().unwrap(),
+        ];
+        app.refresh(&providers);
+        assert!(app.has_ever_loaded);
+
+        // Later failures don't bring the startup summary back
+        let failing = FakeProvider::new("homelab", &["pve1"]);
+        failing.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(failing)];
+        app.refresh(&providers);
+        assert!(app.has_ever_loaded);
+    }
+
+    #[test]
+    fn test_restarts_counted_when_uptime_drops() {
+        let mut app = App::new();
+        let refresh = |app: &mut App, node_uptime: u64, guest_uptime: u64| {
+            let mut provider = FakeProvider::new("homelab", &["pve1"]);
+            provider.nodes[0].uptime = node_uptime;
+            provider.containers[0].uptime = guest_uptime;
+            app.refresh(&[Box::new(provider) as Box<dyn Provider>]);
+        };
+
+        refresh(&mut app, 1000, 500);
+        refresh(&mut app, 1005, 505);
+        assert!(app.restarts.is_empty());
+
+        // Guest restarted, twice
+        refresh(&mut app, 1010, 3);
+        refresh(&mut app, 1015, 8);
+        refresh(&mut app, 1020, 2);
+        let guest = app.containers[0].clone();
+        assert_eq!(app.container_restarts(&guest).map(|r| r.count), Some(2));
+        assert!(app.node_restarts(&app.nodes[0]).is_none());
+
+        // Node rebooted
+        refresh(&mut app, 30, 7);
+        assert_eq!(app.node_restarts(&app.nodes[0]).map(|r| r.count), Some(1));
+        assert_eq!(app.container_restarts(&guest).map(|r| r.count), Some(2));
+    }
+
+    #[test]
+    fn test_adaptive_refresh_follows_what_refreshes_change() {
+        let mut app = App::new();
+        app.schedule.set_adaptive(true);
+        let refresh = |app: &mut App, cpu: f64| {
+            let mut provider = FakeProvider::new("homelab", &["pve1"]);
+            provider.containers[0].cpu_usage = cpu;
+            app.refresh(&[Box::new(provider) as Box<dyn Provider>]);
+        };
+
+        // The first load is a change from nothing
+        refresh(&mut app, 5.0);
+        for _ in 0..4 {
+            refresh(&mut app, 5.2);
+        }
+        assert_eq!(app.effective_refresh_interval(), Duration::from_secs(20));
+
+        refresh(&mut app, 40.0);
+        assert_eq!(app.effective_refresh_interval(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_staggered_refresh_fetches_one_provider_per_slot() {
+        let names = ["a", "b", "c"];
+        let fakes: Vec<FakeProvider> = names.iter().map(|n| FakeProvider::new(n, &[n])).collect();
+        let calls: Vec<_> = fakes.iter().map(|p| p.calls.clone()).collect();
+        let providers: Vec<Box<dyn Provider>> = fakes
+            .into_iter()
+            .map(|p| Box::new(p) as Box<dyn Provider>)
+            .collect();
+        let counts = || calls.iter().map(|c| c.get()).collect::<Vec<_>>();
+        let mut app = App::new();
+        app.schedule.set_stagger(true);
+        let start = Instant::now();
+        let wall = SystemTime::now();
+
+        // The first refresh fetches everything at once
+        app.refresh_if_due(&providers, start, wall);
+        let first = counts();
+        assert!(first.iter().all(|&n| n > 0));
+
+        let third = app.refresh_interval / 3 + Duration::from_millis(1);
+        app.refresh_if_due(&providers, start + third, wall);
+        let after: Vec<usize> = counts().iter().zip(&first).map(|(n, f)| n - f).collect();
+        assert_eq!(after[1..], [0, 0]);
+        assert!(after[0] > 0);
+        app.refresh_if_due(&providers, start + third * 2, wall);
+        let after: Vec<usize> = counts().iter().zip(&first).map(|(n, f)| n - f).collect();
+        assert!(after[1] > 0);
+        assert_eq!(after[2], 0);
+
+        // Each provider's age is its own
+        let success = |name: &str| {
+            app.provider_status
+                .iter()
+                .find(|s| s.name == name)
+                .and_then(|s| s.last_success)
+        };
+        assert_eq!(success("a"), Some(start + third));
+        assert_eq!(success("b"), Some(start + third * 2));
+        assert!(success("c") < success("a"));
+
+        // A manual refresh still fetches every provider right away
+        let before = counts();
+        app.schedule.request();
+        app.refresh_if_due(&providers, start + third * 2, wall);
+        assert!(counts().iter().zip(&before).all(|(n, b)| n > b));
+    }
+
+    #[test]
+    fn test_cluster_log_is_kept_once_across_refreshes() {
+        let mut app = App::new();
+        let entry = |uid: &str, time: u64, pri: u8| ClusterLogEntry {
+            uid: uid.to_string(),
+            time,
+            node: "pve1".to_string(),
+            tag: "pvedaemon".to_string(),
+            user: Some("root@pam".to_string()),
+            level: EventLevel::from_priority(pri),
+            message: format!("entry {}", uid),
+        };
+        let mut provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.cluster_log = vec![entry("2", 200, 3), entry("1", 100, 6)];
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        assert_eq!(app.events.len(), 2);
+
+        // Not fetched again until the interval has passed
+        app.refresh(&providers);
+        assert_eq!(app.events.len(), 2);
+        app.last_cluster_log_refresh = None;
+        app.refresh(&providers);
+        assert_eq!(app.events.len(), 2);
+
+        let mut provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.cluster_log = vec![entry("3", 300, 4), entry("2", 200, 3)];
+        app.last_cluster_log_refresh = None;
+        app.refresh(&[Box::new(provider) as Box<dyn Provider>]);
+        let messages: Vec<&str> = app.events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["entry 3", "entry 2", "entry 1"]);
+        assert_eq!(app.events.iter().next().unwrap().level, EventLevel::Warning);
+    }
+
+    #[test]
+    fn test_state_transitions_become_events() {
+        let mut app = App::new();
+        let refresh = |app: &mut App, node: NodeStatus, guest: ContainerStatus, uptime: u64| {
+            let mut provider = FakeProvider::new("homelab", &["pve1"]);
+            provider.nodes[0].status = node;
+            provider.containers[0].status = guest;
+            provider.containers[0].uptime = uptime;
+            app.refresh(&[Box::new(provider) as Box<dyn Provider>]);
+        };
+        let messages = |app: &App| -> Vec<(EventLevel, String)> {
+            app.events
+                .iter()
+                .map(|e| (e.level, e.message.clone()))
+                .collect()
+        };
+
+        refresh(&mut app, NodeStatus::Online, ContainerStatus::Running, 500);
+        refresh(&mut app, NodeStatus::Online, ContainerStatus::Running, 505);
+        assert!(app.events.is_empty());
+
+        refresh(&mut app, NodeStatus::Offline, ContainerStatus::Stopped, 0);
+        assert_eq!(
+            messages(&app),
+            [
+                (EventLevel::Warning, "LXC ct-pve1 stopped".to_string()),
+                (EventLevel::Error, "node pve1 went offline".to_string()),
+            ]
+        );
+
+        refresh(&mut app, NodeStatus::Online, ContainerStatus::Running, 3);
+        refresh(&mut app, NodeStatus::Online, ContainerStatus::Running, 1);
+        let messages = messages(&app);
+        assert_eq!(messages.len(), 5);
+        assert_eq!(
+            messages[0],
+            (EventLevel::Warning, "LXC ct-pve1 restarted".to_string())
+        );
+        assert!(messages.contains(&(EventLevel::Info, "LXC ct-pve1 started".to_string())));
+        assert!(messages.contains(&(EventLevel::Info, "node pve1 came back online".to_string())));
+        assert!(app.events.iter().all(|e| e.origin() == "pulse"));
+    }
+
+    #[test]
+    fn test_refresh_records_changes_until_they_fade() {
+        let mut app = App::new();
+        let refresh = |app: &mut App, nodes: &[&str], cpu: f64| {
+            let mut provider = FakeProvider::new("homelab", nodes);
+            provider.containers[0].cpu_usage = cpu;
+            app.refresh(&[Box::new(provider) as Box<dyn Provider>]);
+        };
+        let change = |app: &App, name: &str, after: Duration| {
+            let container = app.containers.iter().find(|c| c.name == name).unwrap();
+            app.container_change(container, Instant::now() + after)
+        };
+
+        // Nothing is new on the first load
+        refresh(&mut app, &["pve1", "pve2"], 5.0);
+        assert!(app.changes.is_empty());
+
+        refresh(&mut app, &["pve1", "pve3"], 50.0);
+        assert_eq!(
+            change(&app, "ct-pve1", Duration::ZERO),
+            Some(Change::Usage {
+                cpu: Some(Delta::Up),
+                memory: None,
+            })
+        );
+        assert_eq!(change(&app, "ct-pve3", Duration::ZERO), Some(Change::Added));
+        // Gone guests stay listed, missing, for one cycle
+        assert_eq!(
+            change(&app, "ct-pve2", Duration::ZERO),
+            Some(Change::Removed)
+        );
+        assert!(
+            app.containers
+                .iter()
+                .any(|c| c.name == "ct-pve2" && c.missing)
+        );
+        assert_eq!(change(&app, "ct-pve1", CHANGE_FADE), None);
+
+        refresh(&mut app, &["pve1", "pve3"], 50.0);
+        assert!(app.changes.is_empty());
+        assert!(!app.containers.iter().any(|c| c.name == "ct-pve2"));
+    }
+
+    #[test]
+    fn test_providers_popup_filters_and_disables() {
+        let homelab = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        let office = FakeProvider::new("office", &["srv1"]);
+        let office_calls = office.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(homelab), Box::new(office)];
+        let mut app = App::new();
+        app.refresh(&providers);
+        assert_eq!(
+            app.provider_totals("homelab"),
+            ProviderTotals {
+                nodes_online: 2,
+                nodes_total: 2,
+                guests_running: 2,
+                guests_total: 2,
+                cpu: Some(10.0),
+                memory: Some(50.0),
+            }
+        );
+
+        // Enter shows only the selected provider, again shows all
+        app.run(Action::Providers, &providers).unwrap();
+        app.select_next_provider();
+        app.filter_selected_provider();
+        assert_eq!(app.providers_popup, None);
+        assert_eq!(app.provider_filter.as_deref(), Some("office"));
+        assert_eq!(app.filtered_nodes().len(), 1);
+        assert_eq!(app.filtered_containers().len(), 1);
+        app.run(Action::Clear, &providers).unwrap();
+        assert_eq!(app.filtered_nodes().len(), 3);
+
+        // A disabled provider's items go and it is not polled
+        app.toggle_providers_popup();
+        app.select_next_provider();
+        app.toggle_selected_provider();
+        assert!(app.provider_disabled("office"));
+        assert_eq!(app.nodes.len(), 2);
+        let calls = office_calls.get();
+        app.refresh(&providers);
+        assert_eq!(office_calls.get(), calls);
+        assert_eq!(app.containers.len(), 2);
+
+        // Turning it back on fetches it at the next loop iteration
+        app.toggle_selected_provider();
+        assert!(!app.provider_disabled("office"));
+        app.refresh_if_due(&providers, Instant::now(), SystemTime::now());
+        assert!(office_calls.get() > calls);
+        assert_eq!(app.nodes.len(), 3);
+    }
+
+    #[test]
+    fn test_duplicate_providers_warn_and_optionally_hide() {
+        let homelab = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        let old = FakeProvider::new("homelab-old", &["pve1", "pve2"]);
+        // Same names, other hardware: a different cluster
+        let mut office = FakeProvider::new("office", &["pve1"]);
+        office.nodes[0].memory_total *= 2;
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(homelab), Box::new(old), Box::new(office)];
+
+        let mut app = App::new();
+        app.refresh(&providers);
+        assert_eq!(app.duplicates.len(), 1);
+        assert_eq!(
+            app.duplicates[0].describe(),
+            "homelab and homelab-old appear to report the same cluster"
+        );
+        assert_eq!(app.containers.len(), 5);
+
+        // Hidden, the first listed provider's copies stay, and the warning
+        // holds across refreshes
+        app.hide_duplicates = true;
+        for _ in 0..2 {
+            app.refresh(&providers);
+            assert_eq!(app.duplicates.len(), 1);
+            assert_eq!(app.nodes.len(), 3);
+            assert!(app.nodes.iter().all(|n| n.provider() != "homelab-old"));
+            assert_eq!(app.containers.len(), 3);
+        }
+
+        app.hide_duplicates = false;
+        app.refresh(&providers);
+        assert_eq!(app.containers.len(), 5);
+    }
+
+    #[test]
+    fn test_refresh_samples_usage_and_forgets_gone_items() {
+        let mut app = App::new();
+        let refresh = |app: &mut App, nodes: &[&str], cpu: f64| {
+            let mut provider = FakeProvider::new("homelab", nodes);
+            provider.containers[0].cpu_usage = cpu;
+            app.refresh(&[Box::new(provider) as Box<dyn Provider>]);
+        };
+        let guest = |node: &str| ItemId::Guest(GuestId::new("homelab", format!("ct-{}", node)));
+
+        refresh(&mut app, &["pve1", "pve2"], 80.0);
+        refresh(&mut app, &["pve1", "pve2"], 20.0);
+        let cpu = &app.usage[&guest("pve1")].cpu;
+        assert_eq!(cpu.average(), Some(50.0));
+        assert_eq!(cpu.peak().map(|(peak, _)| peak), Some(80.0));
+        assert!(
+            app.usage
+                .contains_key(&ItemId::Node(NodeId::new("homelab", "pve2")))
+        );
+
+        // A guest that is gone (missing for a cycle) loses its figures
+        refresh(&mut app, &["pve1"], 20.0);
+        assert!(!app.usage.contains_key(&guest("pve2")));
+        assert!(
+            !app.usage
+                .contains_key(&ItemId::Node(NodeId::new("homelab", "pve2")))
+        );
+    }
+
+    // Drain tests
+    #[test]
+    fn test_drain_plans_from_selected_node() {
+        let mut app = App::new();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(drain_fixture())];
+        app.refresh(&providers);
+
+        app.active_panel = Panel::Containers;
+        app.plan_drain();
+        assert!(app.drain.is_none(), "only nodes can be drained");
+
+        app.active_panel = Panel::Nodes;
+        app.plan_drain();
+        let drain = app.drain.as_ref().unwrap();
+        assert_eq!(drain.node.name, "pve1");
+        assert_eq!(drain.phase, DrainPhase::Confirm);
+        // pve2 has 1288 bytes free: the 1024 guest fits, the 512 one then does not
+        assert_eq!(
+            drain.steps.iter().map(|s| &s.action).collect::<Vec<_>>(),
+            [
+                &DrainAction::Migrate {
+                    target: "pve2".to_string()
+                },
+                &DrainAction::Shutdown {
+                    reason: "no node has 512 B free".to_string()
+                },
+            ]
+        );
+
+        // Nothing happens until confirmed
+        app.advance_drain(&providers, Instant::now());
+        assert!(app.drain.as_ref().unwrap().steps[0].state == StepState::Pending);
+        app.close_drain();
+        assert!(app.drain.is_none());
+    }
+
+    #[test]
+    fn test_drain_runs_guests_in_sequence() {
+        let mut app = App::new();
+        let provider = drain_fixture();
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        app.plan_drain();
+        app.confirm_drain();
+
+        let start = Instant::now();
+        app.advance_drain(&providers, start);
+        assert_eq!(*actions.borrow(), ["migrate ct-pve1 -> pve2"]);
+        // Running drains can't be dismissed
+        app.close_drain();
+        assert!(app.drain.is_some());
+
+        // Not polled again until the interval has passed
+        app.advance_drain(&providers, start);
+        assert!(matches!(
+            app.drain.as_ref().unwrap().steps[0].state,
+            StepState::Running { .. }
+        ));
+
+        let later = start + TASK_POLL_INTERVAL;
+        app.advance_drain(&providers, later);
+        assert_eq!(app.drain.as_ref().unwrap().steps[0].state, StepState::Done);
+        // The migration shows up in a refresh a second later
+        let soon = later + Duration::from_secs(1);
+        assert_eq!(
+            app.schedule.due(soon, Duration::from_secs(60)),
+            Some(RefreshKind::Scheduled)
+        );
+
+        app.advance_drain(&providers, later);
+        assert_eq!(actions.borrow().len(), 2);
+        assert_eq!(actions.borrow()[1], "shutdown ct-extra");
+        app.advance_drain(&providers, later + TASK_POLL_INTERVAL);
+
+        let drain = app.drain.as_ref().unwrap();
+        assert_eq!(drain.phase, DrainPhase::Finished);
+        assert_eq!(drain.summary(), "Drained pve1: 1 migrated, 1 shut down");
+    }
+
+    #[test]
+    fn test_drain_stops_at_first_failure() {
+        let mut app = App::new();
+        let mut provider = drain_fixture();
+        provider.failing_task = Some("ct-pve1".to_string());
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        app.plan_drain();
+        app.confirm_drain();
+
+        let start = Instant::now();
+        app.advance_drain(&providers, start);
+        app.advance_drain(&providers, start + TASK_POLL_INTERVAL);
+        app.advance_drain(&providers, start + TASK_POLL_INTERVAL * 2);
+
+        let drain = app.drain.as_ref().unwrap();
+        assert_eq!(drain.phase, DrainPhase::Finished);
+        assert_eq!(
+            drain.steps[0].state,
+            StepState::Failed("migration aborted".to_string())
+        );
+        assert_eq!(drain.steps[1].state, StepState::Pending);
+        assert_eq!(actions.borrow().len(), 1);
+        assert!(drain.summary().contains("not done: 100 ct-extra"));
+
+        app.close_drain();
+        assert!(app.drain.is_none());
+    }
+
+    #[test]
+    fn test_clone_is_checked_then_tracked() {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        provider.containers[1].vmid = 101;
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.refresh(&providers);
+        assert_eq!(
+            app.run(Action::Clone, &providers),
+            Err("clone: select a VM or container first".to_string())
+        );
+
+        app.active_panel = Panel::Containers;
+        app.run(Action::Clone, &providers).unwrap();
+        let form = app.clone.as_mut().unwrap();
+        assert_eq!(
+            (form.label.as_str(), form.vmid.as_str()),
+            ("100 ct-pve1", "102")
+        );
+
+        // A VMID already listed is refused before anything is sent
+        form.vmid = "101".to_string();
+        let start = Instant::now();
+        app.submit_clone(&providers, start);
+        let form = app.clone.as_mut().unwrap();
+        assert_eq!(
+            form.error.as_deref(),
+            Some("VMID 101 is already used by ct-pve2")
+        );
+        assert_eq!(form.phase, ClonePhase::Editing);
+        assert!(actions.borrow().is_empty());
+
+        form.vmid = "102".to_string();
+        app.submit_clone(&providers, start);
+        assert_eq!(*actions.borrow(), ["clone ct-pve1 -> 102 ct-pve1-clone"]);
+        app.close_clone();
+        assert!(app.clone.is_some(), "a running clone can't be closed");
+
+        app.advance_clone(&providers, start);
+        assert!(matches!(
+            app.clone.as_ref().unwrap().phase,
+            ClonePhase::Running { .. }
+        ));
+        app.advance_clone(&providers, start + TASK_POLL_INTERVAL);
+        assert_eq!(app.clone.as_ref().unwrap().phase, ClonePhase::Done);
+        app.close_clone();
+        assert!(app.clone.is_none());
+    }
+
+    #[test]
+    fn test_clone_refuses_locked_and_missing_guests() {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        provider.containers[0].lock = Some("backup".to_string());
+        provider.containers[1].missing = true;
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.refresh(&providers);
+        app.active_panel = Panel::Containers;
+
+        assert_eq!(
+            app.run(Action::Clone, &providers),
+            Err("clone: guest is locked (backup)".to_string())
+        );
+        app.select_next();
+        assert_eq!(
+            app.run(Action::Clone, &providers),
+            Err("clone: ct-pve2 is not on the provider".to_string())
+        );
+        assert!(app.clone.is_none());
+    }
+
+    #[test]
+    fn test_resources_are_validated_then_changed_and_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        provider.containers[1].container_type = ContainerType::VM;
+        provider.failing_task = Some("ct-pve2".to_string());
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.audit = Some(AuditLog::new(path.clone()));
+        app.refresh(&providers);
+        assert_eq!(
+            app.run(Action::Resources, &providers),
+            Err("resources: select a VM or container first".to_string())
+        );
+
+        app.active_panel = Panel::Containers;
+        app.run(Action::Resources, &providers).unwrap();
+        let form = app.resources.as_mut().unwrap();
+        assert_eq!((form.cores.as_str(), form.memory.as_str()), ("1", "512"));
+
+        // Refused before anything is sent
+        let now = Instant::now();
+        app.submit_resources(&providers, now);
+        let form = app.resources.as_mut().unwrap();
+        assert_eq!(form.error.as_deref(), Some("nothing changed"));
+        assert!(actions.borrow().is_empty());
+
+        form.cores = "2".to_string();
+        form.memory = "1G".to_string();
+        app.submit_resources(&providers, now);
+        assert_eq!(*actions.borrow(), ["resources ct-pve1 Some(2) Some(1024)"]);
+        assert_eq!(
+            app.resources.as_ref().unwrap().phase,
+            ResourcePhase::Saved { pending: None }
+        );
+        // Enter again does not send it twice
+        app.submit_resources(&providers, now);
+        assert_eq!(actions.borrow().len(), 1);
+        app.close_resources();
+
+        // A running VM keeps new cores pending; a failure is shown and logged
+        app.select_next();
+        app.run(Action::Resources, &providers).unwrap();
+        let form = app.resources.as_mut().unwrap();
+        form.cores = "3".to_string();
+        assert_eq!(
+            form.warning().as_deref(),
+            Some("cores will only apply once the VM restarts")
+        );
+        app.submit_resources(&providers, now);
+        assert_eq!(
+            app.resources.as_ref().unwrap().phase,
+            ResourcePhase::Failed("VM is locked (backup)".to_string())
+        );
+
+        let entries: Vec<(String, String, bool, String)> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap())
+            .map(|e| (e.action, e.target, e.ok, e.result))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (
+                    "set cores 1 → 2, memory 512 → 1024 MiB".to_string(),
+                    "100 ct-pve1".to_string(),
+                    true,
+                    "applied".to_string()
+                ),
+                (
+                    "set cores 1 → 3".to_string(),
+                    "100 ct-pve2".to_string(),
+                    false,
+                    "VM is locked (backup)".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_start_warns_of_a_memory_shortfall_but_still_starts() {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        // pve1 has 512 bytes free; its guest wants 2048, pve2's fits
+        provider.containers[0].status = ContainerStatus::Stopped;
+        provider.containers[0].memory_max = 2048;
+        provider.containers[1].status = ContainerStatus::Stopped;
+        provider.containers[1].memory_max = 256;
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.refresh(&providers);
+        app.active_panel = Panel::Containers;
+
+        app.container_index = 1;
+        app.run(Action::Start, &providers).unwrap();
+        assert_eq!(app.start.as_ref().unwrap().shortfall, None);
+        app.close_start();
+
+        app.container_index = 0;
+        app.run(Action::Start, &providers).unwrap();
+        let start = app.start.as_ref().unwrap();
+        assert_eq!(
+            start
+                .shortfall
+                .as_ref()
+                .map(|s| (s.node.as_str(), s.free, s.wanted)),
+            Some(("pve1", 512, 2048))
+        );
+        assert!(actions.borrow().is_empty(), "nothing starts unconfirmed");
+
+        let now = Instant::now();
+        app.confirm_start(&providers, now);
+        assert_eq!(*actions.borrow(), ["start ct-pve1"]);
+        app.close_start();
+        assert!(app.start.is_some(), "a running start can't be closed");
+        app.advance_start(&providers, now + TASK_POLL_INTERVAL);
+        assert_eq!(app.start.as_ref().unwrap().phase, StartPhase::Done);
+        app.close_start();
+        assert!(app.start.is_none());
+
+        // Only stopped guests start
+        app.containers[0].status = ContainerStatus::Running;
+        assert_eq!(
+            app.run(Action::Start, &providers),
+            Err("start: ct-pve1 is already running".to_string())
+        );
+    }
+
+    #[test]
+    fn test_node_reboot_previews_guests_and_needs_the_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut provider = drain_fixture();
+        // ct-pve1 is under HA; ct-extra and the stopped guest are not
+        provider.ha_resources = vec![HaResource {
+            guest: GuestId::new("homelab", "ct-pve1"),
+            state: "started".to_string(),
+        }];
+        let mut stopped = provider.containers[0].clone();
+        stopped.id = GuestId::new("homelab", "ct-off");
+        stopped.status = ContainerStatus::Stopped;
+        provider.containers.push(stopped);
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.audit = Some(AuditLog::new(path.clone()));
+        app.refresh(&providers);
+
+        app.active_panel = Panel::Containers;
+        assert_eq!(
+            app.run(Action::NodePower(NodePower::Reboot), &providers),
+            Err("reboot: select a node first".to_string())
+        );
+        app.active_panel = Panel::Nodes;
+        app.run(Action::NodePower(NodePower::Reboot), &providers)
+            .unwrap();
+        let impact = &app.node_power.as_ref().unwrap().impact;
+        assert_eq!(impact.migrated, ["100 ct-pve1"]);
+        assert_eq!(impact.stopped, ["100 ct-extra"]);
+        assert_eq!(impact.memory, 1024 + 512);
+
+        // Enter does nothing until the name is typed exactly
+        let now = Instant::now();
+        app.node_power.as_mut().unwrap().typed = "pve".to_string();
+        app.confirm_node_power(&providers, now);
+        assert!(actions.borrow().is_empty());
+        app.node_power.as_mut().unwrap().typed = "pve1".to_string();
+        app.confirm_node_power(&providers, now);
+        assert_eq!(*actions.borrow(), ["reboot pve1"]);
+        assert_eq!(app.node_power.as_ref().unwrap().phase, NodePowerPhase::Sent);
+
+        let entry: AuditEntry =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(
+            (entry.action.as_str(), entry.target.as_str(), entry.event),
+            ("node reboot", "pve1", AuditEvent::Dispatched)
+        );
+        assert_eq!((entry.ok, entry.result.as_str()), (true, "sent"));
+        app.close_node_power();
+        assert!(app.node_power.is_none());
+    }
+
+    #[test]
+    fn test_node_power_needs_sys_powermgmt() {
+        let mut provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.missing_actions = vec![PermissionIssue::new(
+            "Sys.PowerMgmt",
+            "/nodes",
+            "nodes cannot be rebooted or shut down",
+        )];
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.probe_permissions(&providers);
+        app.refresh(&providers);
+        assert_eq!(
+            app.run(Action::NodePower(NodePower::Shutdown), &providers),
+            Err(
+                "homelab: token lacks Sys.PowerMgmt on /nodes — nodes cannot be rebooted or shut down"
+                    .to_string()
+            )
+        );
+        assert!(app.node_power.is_none());
+    }
+
+    #[test]
+    fn test_queue_runs_marked_guests_a_few_at_a_time() {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        provider.failing_task = Some("ct-pve2".to_string());
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.action_concurrency = 2;
+        app.refresh(&providers);
+        app.active_panel = Panel::Containers;
+        app.marked = app.containers.iter().map(|c| c.id.clone()).collect();
+
+        // All running: nothing to start
+        assert_eq!(
+            app.run(Action::Start, &providers),
+            Err("start: nothing to do (3 skipped as locked or not stopped)".to_string())
+        );
+        app.run(Action::Shutdown, &providers).unwrap();
+        let start = Instant::now();
+        app.advance_queue(&providers, start);
+        assert!(actions.borrow().is_empty(), "nothing runs unconfirmed");
+
+        app.confirm_queue();
+        app.advance_queue(&providers, start);
+        assert_eq!(*actions.borrow(), ["shutdown ct-pve1", "shutdown ct-pve2"]);
+
+        // A refresh in between leaves the queue alone
+        app.refresh(&providers);
+        app.advance_queue(&providers, start + TASK_POLL_INTERVAL);
+        let queue = app.queue.as_ref().unwrap();
+        assert_eq!(queue.items[0].state, ItemState::Done);
+        assert_eq!(
+            queue.items[1].state,
+            ItemState::Failed("migration aborted".to_string())
+        );
+        assert!(matches!(queue.items[2].state, ItemState::Running { .. }));
 
-    fn create_test_node(name: &str, status: NodeStatus, cpu: f64) -> Node {
-        Node {
-            name: name.to_string(),
-            status,
-            cpu_usage: cpu,
-            memory_used: 512,
-            memory_total: 1024,
-            uptime: 3600,
-        }
+        app.advance_queue(&providers, start + TASK_POLL_INTERVAL * 2);
+        let queue = app.queue.as_ref().unwrap();
+        assert_eq!(queue.phase, QueuePhase::Finished);
+        assert_eq!(queue.summary(), "2 ok, 1 failed");
+        app.cancel_queue();
+        assert!(app.queue.is_none());
     }
 
-    fn create_test_container(
-        name: &str,
-        node: &str,
-        status: ContainerStatus,
-        cpu: f64,
-    ) -> Container {
-        Container {
-            vmid: 100,
-            name: name.to_string(),
-            node: node.to_string(),
-            container_type: ContainerType::LXC,
-            status,
-            cpu_usage: cpu,
-            memory_used: 256,
-            memory_max: 1024,
-            uptime: 3600,
-        }
+    #[test]
+    fn test_queue_leaves_locked_guests_out() {
+        let mut provider = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        provider.containers[1].lock = Some("backup".to_string());
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.action_concurrency = 3;
+        app.refresh(&providers);
+        app.active_panel = Panel::Containers;
+        app.marked = app.containers.iter().map(|c| c.id.clone()).collect();
+
+        app.run(Action::Shutdown, &providers).unwrap();
+        assert_eq!(app.notice.as_deref(), Some("shutdown: skipped 1 guest(s)"));
+        assert_eq!(app.queue.as_ref().unwrap().items.len(), 2);
+
+        app.confirm_queue();
+        app.advance_queue(&providers, Instant::now());
+        assert_eq!(*actions.borrow(), ["shutdown ct-pve1", "shutdown ct-pve3"]);
     }
 
-    // App initialization tests
     #[test]
-    fn test_app_new() {
-        let app = App::new();
-        assert!(app.running);
-        assert_eq!(app.active_panel, Panel::Nodes);
-        assert!(app.nodes.is_empty());
-        assert!(app.containers.is_empty());
-        assert_eq!(app.node_index, 0);
-        assert_eq!(app.container_index, 0);
-        assert!(app.error_message.is_none());
-        assert_eq!(app.sort_field, SortField::Name);
-        assert!(app.sort_ascending);
-        assert_eq!(app.input_mode, InputMode::Normal);
-        assert!(app.search_query.is_empty());
-        assert!(!app.show_help);
+    fn test_queue_cancels_only_pending_items() {
+        let provider = FakeProvider::new("homelab", &["pve1", "pve2", "pve3"]);
+        let actions = provider.actions.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        let mut app = App::new();
+        app.refresh(&providers);
+        app.marked = app.containers.iter().map(|c| c.id.clone()).collect();
+        app.run(Action::Shutdown, &providers).unwrap();
+        app.confirm_queue();
+        let start = Instant::now();
+        app.advance_queue(&providers, start);
+
+        app.cancel_queue();
+        assert_eq!(app.notice.as_deref(), Some("Cancelled 2 queued action(s)"));
+        app.advance_queue(&providers, start + TASK_POLL_INTERVAL);
+        assert_eq!(*actions.borrow(), ["shutdown ct-pve1"]);
+        let queue = app.queue.as_ref().unwrap();
+        assert_eq!(queue.phase, QueuePhase::Finished);
+        assert_eq!(queue.summary(), "1 ok, 0 failed, 2 cancelled");
     }
 
-    // Navigation tests
     #[test]
-    fn test_next_panel() {
+    fn test_drain_actions_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
         let mut app = App::new();
-        assert_eq!(app.active_panel, Panel::Nodes);
+        app.audit = Some(AuditLog::new(path.clone()));
+        let mut provider = drain_fixture();
+        provider.failing_task = Some("ct-pve1".to_string());
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        app.plan_drain();
+        app.confirm_drain();
 
-        app.next_panel();
-        assert_eq!(app.active_panel, Panel::Containers);
+        let start = Instant::now();
+        app.advance_drain(&providers, start);
+        app.advance_drain(&providers, start + TASK_POLL_INTERVAL);
 
-        app.next_panel();
-        assert_eq!(app.active_panel, Panel::Nodes);
+        let entries: Vec<AuditEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let summary: Vec<(&str, &str, AuditEvent, bool, &str)> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.action.as_str(),
+                    e.target.as_str(),
+                    e.event,
+                    e.ok,
+                    e.result.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "migrate to pve2",
+                    "100 ct-pve1",
+                    AuditEvent::Dispatched,
+                    true,
+                    "ct-pve1"
+                ),
+                (
+                    "migrate to pve2",
+                    "100 ct-pve1",
+                    AuditEvent::Completed,
+                    false,
+                    "migration aborted"
+                ),
+            ]
+        );
+        assert!(entries.iter().all(|e| e.provider == "homelab"));
     }
 
     #[test]
-    fn test_select_next_nodes() {
+    fn test_dry_run_clone_is_audited_without_a_request() {
+        let mut server = mockito::Server::new();
+        let posts = server
+            .mock("POST", mockito::Matcher::Any)
+            .expect(0)
+            .create();
+        let gets = server.mock("GET", mockito::Matcher::Any).expect(0).create();
+        let config = crate::config::ProxmoxConfig {
+            name: "pve".to_string(),
+            host: server.url(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: None,
+            read_only: false,
+            dry_run: true,
+        };
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(
+            crate::providers::ProxmoxProvider::new(&config).unwrap(),
+        )];
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
         let mut app = App::new();
-        app.nodes = vec![
-            create_test_node("node1", NodeStatus::Online, 10.0),
-            create_test_node("node2", NodeStatus::Online, 20.0),
-            create_test_node("node3", NodeStatus::Online, 30.0),
-        ];
-        app.active_panel = Panel::Nodes;
+        app.dry_run = true;
+        app.audit = Some(AuditLog::new(path.clone()));
+        let source = Container {
+            id: GuestId::new("pve", 100),
+            ..create_test_container("web", "pve1", ContainerStatus::Running, 1.0)
+        };
+        app.clone = Some(CloneForm::new(&source, &app.nodes, Some(101)));
+        app.containers = vec![source];
+        app.clone.as_mut().unwrap().name = "web-copy".to_string();
 
-        assert_eq!(app.node_index, 0);
-        app.select_next();
-        assert_eq!(app.node_index, 1);
-        app.select_next();
-        assert_eq!(app.node_index, 2);
-        app.select_next(); // Should not go past last item
-        assert_eq!(app.node_index, 2);
+        let start = Instant::now();
+        app.submit_clone(&providers, start);
+        assert!(
+            app.notice
+                .as_deref()
+                .unwrap()
+                .starts_with("Dry run: would POST http://"),
+            "{:?}",
+            app.notice
+        );
+        app.advance_clone(&providers, start + TASK_POLL_INTERVAL);
+        assert_eq!(app.clone.as_ref().unwrap().phase, ClonePhase::Done);
+
+        let entries: Vec<AuditEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert!(
+            entries[0].result.ends_with(
+                "/api2/json/nodes/pve1/lxc/100/clone newid=101 hostname=web-copy full=1"
+            ),
+            "{}",
+            entries[0].result
+        );
+        assert_eq!(entries[1].result, "done");
+        posts.assert();
+        gets.assert();
     }
 
     #[test]
-    fn test_select_previous_nodes() {
+    fn test_unwritable_audit_log_warns_once() {
+        let dir = tempfile::tempdir().unwrap();
         let mut app = App::new();
-        app.nodes = vec![
-            create_test_node("node1", NodeStatus::Online, 10.0),
-            create_test_node("node2", NodeStatus::Online, 20.0),
-        ];
-        app.active_panel = Panel::Nodes;
-        app.node_index = 1;
+        app.audit = Some(AuditLog::new(dir.path().to_path_buf()));
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(drain_fixture())];
+        app.refresh(&providers);
+        app.plan_drain();
+        app.confirm_drain();
 
-        app.select_previous();
-        assert_eq!(app.node_index, 0);
-        app.select_previous(); // Should not go below 0
-        assert_eq!(app.node_index, 0);
+        let start = Instant::now();
+        for i in 0..4 {
+            app.advance_drain(&providers, start + TASK_POLL_INTERVAL * i);
+        }
+
+        // The drain itself is unaffected
+        assert_eq!(app.drain.as_ref().unwrap().phase, DrainPhase::Finished);
+        let warnings: Vec<&ErrorEntry> = app
+            .error_log
+            .iter()
+            .filter(|e| e.provider == "audit")
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].count, 1);
     }
 
+    // Error log tests
     #[test]
-    fn test_select_next_containers() {
+    fn test_error_log_keeps_full_message_per_provider() {
         let mut app = App::new();
-        app.containers = vec![
-            create_test_container("ct1", "node1", ContainerStatus::Running, 10.0),
-            create_test_container("ct2", "node1", ContainerStatus::Running, 20.0),
+        let failing = FakeProvider::new("remote", &["pve9"]);
+        failing.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(FakeProvider::new("homelab", &["pve1"])),
+            Box::new(failing),
         ];
-        app.active_panel = Panel::Containers;
 
-        assert_eq!(app.container_index, 0);
-        app.select_next();
-        assert_eq!(app.container_index, 1);
+        app.refresh(&providers);
+
+        assert_eq!(app.error_log.len(), 1);
+        let entry = &app.error_log[0];
+        assert_eq!(entry.provider, "remote");
+        assert_eq!(
+            entry.message,
+            "error fetching nodes: connection refused; error fetching containers: connection refused"
+        );
+        assert_eq!(entry.count, 1);
     }
 
-    // Sort tests
     #[test]
-    fn test_sort_field_cycle() {
-        assert_eq!(SortField::Name.next(), SortField::Status);
-        assert_eq!(SortField::Status.next(), SortField::Cpu);
-        assert_eq!(SortField::Cpu.next(), SortField::Memory);
-        assert_eq!(SortField::Memory.next(), SortField::Name);
+    fn test_error_log_collapses_repeats() {
+        let mut app = App::new();
+        let failing = FakeProvider::new("remote", &["pve9"]);
+        failing.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(failing)];
+
+        app.refresh(&providers);
+        app.refresh(&providers);
+        app.refresh(&providers);
+
+        assert_eq!(app.error_log.len(), 1);
+        assert_eq!(app.error_log[0].count, 3);
     }
 
     #[test]
-    fn test_sort_field_label() {
-        assert_eq!(SortField::Name.label(), "Name");
-        assert_eq!(SortField::Status.label(), "Status");
-        assert_eq!(SortField::Cpu.label(), "CPU");
-        assert_eq!(SortField::Memory.label(), "Memory");
+    fn test_failed_cluster_status_does_not_fail_the_provider() {
+        let mut app = App::new();
+        let forbidden = FakeProvider::new("homelab", &["pve1"]);
+        forbidden.cluster_forbidden.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(forbidden)];
+
+        app.refresh(&providers);
+        app.refresh(&providers);
+
+        // The rows are fresh and the provider isn't backing off
+        let status = &app.provider_status[0];
+        assert!(status.last_success.is_some());
+        assert!(status.last_error.is_none());
+        assert!(!status.is_down());
+        assert_eq!(app.nodes.len(), 1);
+        assert_eq!(
+            status.cluster_error.as_deref(),
+            Some("error fetching cluster status: 403 Permission check failed")
+        );
+        // Logged once, not again on every refresh
+        assert_eq!(app.error_log.len(), 1);
+        assert_eq!(app.error_log[0].count, 1);
+        assert!(app.error_message.is_none());
+
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1"]))];
+        app.refresh(&providers);
+        assert!(app.provider_status[0].cluster_error.is_none());
     }
 
     #[test]
-    fn test_cycle_sort() {
+    fn test_error_log_is_bounded() {
         let mut app = App::new();
-        assert_eq!(app.sort_field, SortField::Name);
+        for i in 0..ERROR_LOG_CAPACITY + 10 {
+            app.record_error("homelab", format!("error {}", i));
+        }
 
-        app.cycle_sort();
-        assert_eq!(app.sort_field, SortField::Status);
+        assert_eq!(app.error_log.len(), ERROR_LOG_CAPACITY);
+        assert_eq!(
+            app.error_log[0].message,
+            format!("error {}", ERROR_LOG_CAPACITY + 9)
+        );
+    }
 
-        app.cycle_sort();
-        assert_eq!(app.sort_field, SortField::Cpu);
+    #[test]
+    fn test_toggle_error_log_resets_scroll() {
+        let mut app = App::new();
+        app.toggle_error_log();
+        app.scroll_errors_down();
+        app.scroll_errors_down();
+        app.scroll_errors_up();
+        assert_eq!(app.error_scroll, 1);
+
+        app.toggle_error_log();
+        assert!(!app.show_errors);
+        assert_eq!(app.error_scroll, 0);
     }
 
     #[test]
-    fn test_toggle_sort_order() {
+    fn test_logs_need_a_log_file_and_scroll_within_the_level() {
+        use crate::logging::LogBuffer;
+        use log::{Level, LevelFilter};
+
         let mut app = App::new();
-        assert!(app.sort_ascending);
+        assert!(app.toggle_logs().unwrap_err().contains("--log-file"));
+        assert!(app.log_view.is_none());
 
-        app.toggle_sort_order();
-        assert!(!app.sort_ascending);
+        let log = LogBuffer::new(100);
+        for i in 0..10 {
+            let level = if i % 2 == 0 {
+                Level::Debug
+            } else {
+                Level::Warn
+            };
+            log.push(level, format!("line {}", i));
+        }
+        app.log = Some(log.clone());
+        app.toggle_logs().unwrap();
+        assert_eq!(app.log_lines().len(), 10);
 
-        app.toggle_sort_order();
-        assert!(app.sort_ascending);
+        app.log_view.as_mut().unwrap().cycle_level();
+        app.log_view.as_mut().unwrap().cycle_level();
+        assert_eq!(app.log_view.as_ref().unwrap().min_level, LevelFilter::Warn);
+        assert_eq!(app.log_lines().len(), 5);
+
+        // Scrolled up, the view holds still while more is logged
+        app.scroll_logs(-1, 2);
+        log.push(Level::Warn, "later".to_string());
+        let lines = app.log_lines();
+        let window = app.log_view.as_ref().unwrap().window(&lines, 2);
+        assert_eq!(lines[window.end - 1].text, "line 7");
+        app.scroll_logs(isize::MAX, 2);
+        assert_eq!(app.log_view.as_ref().unwrap().anchor, None);
+        app.scroll_logs(isize::MIN, 2);
+        let lines = app.log_lines();
+        assert_eq!(app.log_view.as_ref().unwrap().window(&lines, 2), 0..2);
+
+        app.toggle_logs().unwrap();
+        assert!(app.log_view.is_none());
     }
 
-    // Search/filter tests
     #[test]
-    fn test_search_mode() {
+    fn test_toggle_help_resets_scroll() {
         let mut app = App::new();
-        assert_eq!(app.input_mode, InputMode::Normal);
+        app.toggle_help();
+        app.scroll_help_down();
+        app.scroll_help_down();
+        app.scroll_help_up();
+        assert_eq!(app.help_scroll, 1);
 
-        app.enter_search_mode();
-        assert_eq!(app.input_mode, InputMode::Search);
+        app.toggle_help();
+        app.toggle_help();
+        assert_eq!(app.help_scroll, 0);
+    }
 
-        app.exit_search_mode();
-        assert_eq!(app.input_mode, InputMode::Normal);
+    // Guest details tests
+    struct DetailsProvider {
+        calls: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Provider for DetailsProvider {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+            Ok(Vec::new())
+        }
+
+        fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+            Ok(vec![create_test_container(
+                "vm",
+                "node1",
+                ContainerStatus::Running,
+                1.0,
+            )])
+        }
+
+        fn fetch_guest_details(
+            &self,
+            _container: &Container,
+        ) -> Result<GuestDetails, Box<dyn std::error::Error>> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(GuestDetails {
+                memory: Some(crate::models::GuestMemory {
+                    allocated: 2048,
+                    guest_used: 512,
+                    guest_total: 1024,
+                }),
+                agent: None,
+                firewall: None,
+                os_info: Some("Debian GNU/Linux 12 (bookworm)".to_string()),
+                deferred: false,
+            })
+        }
     }
 
     #[test]
-    fn test_search_query() {
+    fn test_selected_details_fetched_once_per_refresh() {
         let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(DetailsProvider {
+            calls: calls.clone(),
+        })];
 
-        app.push_search_char('t');
-        app.push_search_char('e');
-        app.push_search_char('s');
-        app.push_search_char('t');
-        assert_eq!(app.search_query, "test");
+        app.refresh(&providers);
+        app.ensure_selected_details(&providers);
+        app.ensure_selected_details(&providers);
+        assert_eq!(calls.get(), 1);
 
-        app.pop_search_char();
-        assert_eq!(app.search_query, "tes");
+        app.refresh(&providers);
+        assert_eq!(calls.get(), 2);
 
-        app.clear_search();
-        assert!(app.search_query.is_empty());
+        let container = app.selected_container().unwrap().clone();
+        let details = app.details_for(&container).unwrap();
+        assert_eq!(details.memory.as_ref().unwrap().guest_used, 512);
+        assert_eq!(app.guest_details.len(), 1);
+
+        // The OS lands on the guest, where search finds it
+        assert_eq!(
+            container.os_info.as_deref(),
+            Some("Debian GNU/Linux 12 (bookworm)")
+        );
+        app.search_query = "os:debian".to_string();
+        assert_eq!(app.filtered_containers().len(), 1);
+        app.search_query = "os:alpine".to_string();
+        assert!(app.filtered_containers().is_empty());
     }
 
     #[test]
-    fn test_filtered_nodes() {
+    fn test_no_details_fetched_for_nodes_panel() {
         let mut app = App::new();
-        app.nodes = vec![
-            create_test_node("alpha", NodeStatus::Online, 10.0),
-            create_test_node("beta", NodeStatus::Online, 20.0),
-            create_test_node("alphabeta", NodeStatus::Online, 30.0),
-        ];
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(DetailsProvider {
+            calls: calls.clone(),
+        })];
 
-        // No filter - all nodes
-        assert_eq!(app.filtered_nodes().len(), 3);
+        app.refresh(&providers);
 
-        // Filter by "alpha"
-        app.search_query = "alpha".to_string();
-        let filtered = app.filtered_nodes();
-        assert_eq!(filtered.len(), 2);
-        assert!(filtered.iter().any(|n| n.name == "alpha"));
-        assert!(filtered.iter().any(|n| n.name == "alphabeta"));
+        assert!(app.guest_details.is_empty());
+        assert_eq!(calls.get(), 0);
     }
 
+    // Provider status tests
     #[test]
-    fn test_filtered_containers_by_name() {
-        let mut app = App::new();
-        app.containers = vec![
-            create_test_container("web-server", "node1", ContainerStatus::Running, 10.0),
-            create_test_container("database", "node1", ContainerStatus::Running, 20.0),
-            create_test_container("web-cache", "node2", ContainerStatus::Running, 30.0),
-        ];
+    fn test_staleness_threshold() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(5);
+        let mut status = ProviderStatus::new("homelab");
+        assert!(status.staleness(start, interval).is_none());
 
-        app.search_query = "web".to_string();
-        let filtered = app.filtered_containers();
-        assert_eq!(filtered.len(), 2);
+        status.last_success = Some(start);
+        assert!(
+            status
+                .staleness(start + Duration::from_secs(5), interval)
+                .is_none()
+        );
+        assert!(
+            status
+                .staleness(start + Duration::from_secs(10), interval)
+                .is_none()
+        );
+        assert_eq!(
+            status.staleness(start + Duration::from_secs(45), interval),
+            Some(Duration::from_secs(45))
+        );
     }
 
     #[test]
-    fn test_filtered_containers_by_node() {
+    fn test_refresh_selected_updates_only_that_item() {
         let mut app = App::new();
-        app.containers = vec![
-            create_test_container("ct1", "production", ContainerStatus::Running, 10.0),
-            create_test_container("ct2", "staging", ContainerStatus::Running, 20.0),
-            create_test_container("ct3", "production", ContainerStatus::Running, 30.0),
-        ];
+        let provider = FakeProvider::new("homelab", &["pve1", "pve2"]);
+        let calls = provider.calls.clone();
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
+        app.refresh(&providers);
+        assert_eq!(calls.get(), 1);
 
-        app.search_query = "prod".to_string();
-        let filtered = app.filtered_containers();
-        assert_eq!(filtered.len(), 2);
+        // Local copies drift from what the provider reports
+        for container in &mut app.containers {
+            container.cpu_usage = 99.0;
+        }
+        app.active_panel = Panel::Containers;
+        app.container_index = 1;
+        app.refresh_selected(&providers);
+
+        let cpu: Vec<f64> = app.containers.iter().map(|c| c.cpu_usage).collect();
+        assert_eq!(cpu, [99.0, 5.0]);
+        assert_eq!(app.selected_container().unwrap().name, "ct-pve2");
+        assert!(app.details_for(&app.containers[1]).is_some());
+        assert_eq!(
+            app.notice.as_deref(),
+            Some("Refreshed ct-pve2 and its details")
+        );
+        // The guest's node list is enough; nodes weren't re-fetched
+        assert_eq!(calls.get(), 1);
+
+        for node in &mut app.nodes {
+            node.cpu_usage = 99.0;
+        }
+        app.active_panel = Panel::Nodes;
+        app.refresh_selected(&providers);
+        let cpu: Vec<f64> = app.nodes.iter().map(|n| n.cpu_usage).collect();
+        assert_eq!(cpu, [10.0, 99.0]);
+        assert_eq!(app.notice.as_deref(), Some("Refreshed node pve1"));
+
+        // The next full refresh clears the confirmation
+        app.refresh(&providers);
+        assert_eq!(app.notice, None);
     }
 
     #[test]
-    fn test_search_case_insensitive() {
+    fn test_refresh_selected_reports_failure() {
         let mut app = App::new();
-        app.nodes = vec![create_test_node("ProductionNode", NodeStatus::Online, 10.0)];
+        let healthy: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1"]))];
+        app.refresh(&healthy);
 
-        app.search_query = "production".to_string();
-        assert_eq!(app.filtered_nodes().len(), 1);
+        let provider = FakeProvider::new("homelab", &["pve1"]);
+        provider.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(provider)];
 
-        app.search_query = "PRODUCTION".to_string();
-        assert_eq!(app.filtered_nodes().len(), 1);
+        app.refresh_selected(&providers);
+        assert_eq!(app.notice, None);
+        assert_eq!(
+            app.error_message.as_deref(),
+            Some("homelab: error refreshing pve1: connection refused")
+        );
+        assert_eq!(app.nodes.len(), 1);
     }
 
-    // Summary tests
     #[test]
-    fn test_nodes_summary() {
+    fn test_failing_provider_keeps_its_items() {
         let mut app = App::new();
-        app.nodes = vec![
-            create_test_node("node1", NodeStatus::Online, 10.0),
-            create_test_node("node2", NodeStatus::Offline, 0.0),
-            create_test_node("node3", NodeStatus::Online, 30.0),
+        let healthy = FakeProvider::new("homelab", &["pve1"]);
+        let flaky = FakeProvider::new("remote", &["pve9"]);
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(healthy), Box::new(flaky)];
+
+        app.refresh(&providers);
+        assert_eq!(app.nodes.len(), 2);
+        assert_eq!(app.containers.len(), 2);
+        assert!(app.error_message.is_none());
+
+        // Only the second provider fails: its items stay, tagged by provider
+        let flaky = FakeProvider::new("remote", &["pve9"]);
+        flaky.fail.set(true);
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(FakeProvider::new("homelab", &["pve1"])),
+            Box::new(flaky),
         ];
+        app.refresh(&providers);
 
-        let (online, total) = app.nodes_summary();
-        assert_eq!(online, 2);
-        assert_eq!(total, 3);
+        assert_eq!(app.nodes.len(), 2);
+        assert!(
+            app.nodes
+                .iter()
+                .any(|n| n.name == "pve9" && n.provider() == "remote")
+        );
+        assert!(app.error_message.as_ref().unwrap().starts_with("remote:"));
+
+        let remote = app
+            .provider_status
+            .iter()
+            .find(|s| s.name == "remote")
+            .unwrap();
+        assert!(remote.last_error.is_some());
+        assert!(remote.last_success.is_some());
+        let homelab = app
+            .provider_status
+            .iter()
+            .find(|s| s.name == "homelab")
+            .unwrap();
+        assert!(homelab.last_error.is_none());
     }
 
     #[test]
-    fn test_containers_summary() {
+    fn test_provider_staleness_recovers() {
         let mut app = App::new();
-        app.containers = vec![
-            create_test_container("ct1", "node1", ContainerStatus::Running, 10.0),
-            create_test_container("ct2", "node1", ContainerStatus::Stopped, 0.0),
-            create_test_container("ct3", "node1", ContainerStatus::Running, 30.0),
-            create_test_container("ct4", "node1", ContainerStatus::Stopped, 0.0),
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1"]))];
+        app.refresh(&providers);
+
+        let later = Instant::now() + Duration::from_secs(60);
+        assert!(app.provider_staleness("homelab", later).is_some());
+        assert!(app.provider_staleness("unknown", later).is_none());
+
+        app.refresh(&providers);
+        assert!(app.provider_staleness("homelab", Instant::now()).is_none());
+    }
+
+    #[test]
+    fn test_removed_provider_items_are_dropped() {
+        let mut app = App::new();
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(FakeProvider::new("homelab", &["pve1"])),
+            Box::new(FakeProvider::new("old", &["pve2"])),
         ];
+        app.refresh(&providers);
+        assert_eq!(app.nodes.len(), 2);
 
-        let (running, total) = app.containers_summary();
-        assert_eq!(running, 2);
-        assert_eq!(total, 4);
+        let providers: Vec<Box<dyn Provider>> =
+            vec![Box::new(FakeProvider::new("homelab", &["pve1"]))];
+        app.refresh(&providers);
+
+        assert_eq!(app.nodes.len(), 1);
+        assert_eq!(app.provider_status.len(), 1);
     }
 
     // Selection tests
@@ -674,9 +8376,51 @@ mod tests {
         assert!(!app.show_help);
     }
 
+    #[test]
+    fn test_toggle_full_names() {
+        let mut app = App::new();
+        assert!(!app.full_names);
+
+        app.toggle_full_names();
+        assert!(app.full_names);
+
+        app.toggle_full_names();
+        assert!(!app.full_names);
+    }
+
     #[test]
     fn test_time_since_refresh_never() {
         let app = App::new();
         assert_eq!(app.time_since_refresh(), "never");
     }
+
+    #[test]
+    fn test_time_since_refresh_with_clock() {
+        let mut app = App::new();
+        app.last_refresh = Some(Instant::now());
+        assert_eq!(app.time_since_refresh(), "0s ago");
+
+        app.clock = Clock::Utc;
+        let shown = app.time_since_refresh();
+        assert!(shown.ends_with(" (0s ago)"), "{}", shown);
+        assert_eq!(shown.len(), "14:03:27 (0s ago)".len(), "{}", shown);
+    }
+
+    #[test]
+    fn test_clock_time_per_zone() {
+        // 2024-03-01 14:03:27 UTC
+        let at = UNIX_EPOCH + Duration::from_secs(1_709_301_807);
+        assert_eq!(clock_time(at, Clock::Utc).as_deref(), Some("14:03:27"));
+        assert_eq!(clock_time(at, Clock::Off), None);
+
+        let utc = DateTime::<Utc>::from(at);
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(format_time(utc, cest), "16:03:27");
+        let newfoundland = FixedOffset::west_opt(3 * 3600 + 1800).unwrap();
+        assert_eq!(format_time(utc, newfoundland), "10:33:27");
+
+        // Local is whatever zone the machine is in, through the same path
+        let local = Local.offset_from_utc_datetime(&utc.naive_utc()).fix();
+        assert_eq!(clock_time(at, Clock::Local), Some(format_time(utc, local)));
+    }
 }
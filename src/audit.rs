@@ -0,0 +1,215 @@
+//! Audit trail of actions taken through pulse (migrations, shutdowns).
+//!
+//! Each action is logged twice as a JSON line: when it is dispatched (with
+//! the provider's task id or the error) and when its task completes.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::format_timestamp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditEvent {
+    Dispatched,
+    Completed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix seconds.
+    pub ts: i64,
+    pub user: String,
+    pub provider: String,
+    /// e.g. "migrate to pve2", "shutdown".
+    pub action: String,
+    /// Guest the action applies to, e.g. "101 nginx".
+    pub target: String,
+    pub event: AuditEvent,
+    pub ok: bool,
+    /// Task id when dispatched, otherwise "done" or the error.
+    pub result: String,
+}
+
+impl AuditEntry {
+    /// One line for `pulse audit tail`.
+    pub fn summary(&self) -> String {
+        let event = match self.event {
+            AuditEvent::Dispatched => "dispatched",
+            AuditEvent::Completed => "completed",
+        };
+        format!(
+            "{} UTC  {}  {} {} on {} ({}) {}: {}",
+            format_timestamp(self.ts),
+            self.user,
+            self.action,
+            self.target,
+            self.provider,
+            event,
+            if self.ok { "ok" } else { "FAILED" },
+            self.result
+        )
+    }
+}
+
+/// The local account, for providers that don't act as a configured user.
+pub fn local_user() -> String {
+    std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    /// Set after a failed write so the warning is shown once per outage.
+    failing: bool,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            failing: false,
+        }
+    }
+
+    /// Append an entry. Returns a warning the first time writing fails;
+    /// after that failures stay quiet until a write succeeds again.
+    pub fn append(&mut self, entry: &AuditEntry) -> Option<String> {
+        match self.write(entry) {
+            Ok(()) => {
+                self.failing = false;
+                None
+            }
+            Err(e) if !self.failing => {
+                self.failing = true;
+                Some(format!(
+                    "cannot write audit log {}: {}",
+                    self.path.display(),
+                    e
+                ))
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn write(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+}
+
+/// The last `count` entries of the log, formatted for the terminal. Lines
+/// that aren't valid entries are shown as they are.
+pub fn tail(path: &Path, count: usize) -> std::io::Result<String> {
+    let contents = fs::read_to_string(path)?;
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    let mut out = String::new();
+    for line in &lines[lines.len().saturating_sub(count)..] {
+        match serde_json::from_str::<AuditEntry>(line) {
+            Ok(entry) => out.push_str(&entry.summary()),
+            Err(_) => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ts: i64, event: AuditEvent, ok: bool, result: &str) -> AuditEntry {
+        AuditEntry {
+            ts,
+            user: "root@pam".to_string(),
+            provider: "homelab".to_string(),
+            action: "migrate to pve2".to_string(),
+            target: "101 nginx".to_string(),
+            event,
+            ok,
+            result: result.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_append_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("audit.jsonl");
+        let mut log = AuditLog::new(path.clone());
+
+        assert_eq!(
+            log.append(&entry(1, AuditEvent::Dispatched, true, "UPID:1")),
+            None
+        );
+        assert_eq!(
+            log.append(&entry(2, AuditEvent::Completed, true, "done")),
+            None
+        );
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"ts":1,"user":"root@pam","provider":"homelab","action":"migrate to pve2","target":"101 nginx","event":"dispatched","ok":true,"result":"UPID:1"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<AuditEntry>(lines[1]).unwrap(),
+            entry(2, AuditEvent::Completed, true, "done")
+        );
+    }
+
+    #[test]
+    fn test_unwritable_log_warns_once() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory can't be opened for appending
+        let mut log = AuditLog::new(dir.path().to_path_buf());
+        let record = entry(1, AuditEvent::Dispatched, true, "UPID:1");
+
+        let warning = log.append(&record).unwrap();
+        assert!(warning.starts_with("cannot write audit log"));
+        assert_eq!(log.append(&record), None);
+        assert_eq!(log.append(&record), None);
+    }
+
+    #[test]
+    fn test_tail_shows_last_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut log = AuditLog::new(path.clone());
+        log.append(&entry(
+            1_700_000_000,
+            AuditEvent::Dispatched,
+            true,
+            "UPID:1",
+        ));
+        log.append(&entry(
+            1_700_000_060,
+            AuditEvent::Completed,
+            false,
+            "migration aborted",
+        ));
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        assert_eq!(
+            tail(&path, 2).unwrap(),
+            "2023-11-14 22:14:20 UTC  root@pam  migrate to pve2 101 nginx on homelab (completed) FAILED: migration aborted\nnot json\n"
+        );
+        assert_eq!(tail(&path, 10).unwrap().lines().count(), 3);
+        assert!(tail(&dir.path().join("missing"), 10).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+/// Retry delays after 1, 2, 3 and 4+ consecutive failures.
+const RETRY_DELAYS: [Duration; 4] = [
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+];
+
+/// Per-provider retry schedule: a provider that keeps failing is polled less
+/// and less often, and back at full cadence after its first success.
+#[derive(Debug, Clone, Default)]
+pub struct Backoff {
+    failures: u32,
+    next_attempt: Option<Instant>,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+        self.next_attempt = None;
+    }
+
+    pub fn record_failure(&mut self, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+        self.next_attempt = Some(now + self.delay());
+    }
+
+    /// Consecutive failures so far.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Delay before the next attempt given the current failure count.
+    pub fn delay(&self) -> Duration {
+        match self.failures {
+            0 => Duration::ZERO,
+            n => RETRY_DELAYS[(n as usize - 1).min(RETRY_DELAYS.len() - 1)],
+        }
+    }
+
+    /// Whether the provider may be polled at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_attempt.is_none_or(|at| now >= at)
+    }
+
+    /// Time left until the next attempt, while backing off.
+    pub fn retry_in(&self, now: Instant) -> Option<Duration> {
+        let at = self.next_attempt?;
+        (at > now).then(|| at - now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_backoff_is_due() {
+        let backoff = Backoff::new();
+        let now = Instant::now();
+        assert!(backoff.is_due(now));
+        assert_eq!(backoff.retry_in(now), None);
+        assert_eq!(backoff.delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_delays_grow_and_cap() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+        let mut delays = Vec::new();
+        for _ in 0..6 {
+            backoff.record_failure(now);
+            delays.push(backoff.delay().as_secs());
+        }
+        assert_eq!(delays, vec![5, 10, 30, 60, 60, 60]);
+        assert_eq!(backoff.failures(), 6);
+    }
+
+    #[test]
+    fn test_not_due_until_delay_elapses() {
+        let mut backoff = Backoff::new();
+        let start = Instant::now();
+        backoff.record_failure(start);
+        backoff.record_failure(start);
+
+        assert!(!backoff.is_due(start + Duration::from_secs(9)));
+        assert_eq!(
+            backoff.retry_in(start + Duration::from_secs(7)),
+            Some(Duration::from_secs(3))
+        );
+        assert!(backoff.is_due(start + Duration::from_secs(10)));
+        assert_eq!(backoff.retry_in(start + Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn test_success_resets() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+        backoff.record_failure(now);
+        backoff.record_failure(now);
+        backoff.record_failure(now);
+
+        backoff.record_success();
+
+        assert_eq!(backoff.failures(), 0);
+        assert!(backoff.is_due(now));
+        backoff.record_failure(now);
+        assert_eq!(backoff.delay(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,145 @@
+//! What a refresh changed in the container list, so the list can flash it
+//! briefly: usage that rose or fell, guests that appeared, and guests that
+//! disappeared (kept struck through for one more cycle). Also whether a
+//! refresh changed anything at all, for the adaptive refresh interval.
+
+use std::time::Duration;
+
+use crate::models::{Container, Node};
+use crate::restarts;
+
+/// How long a change stays highlighted after the refresh that brought it.
+pub const CHANGE_FADE: Duration = Duration::from_secs(2);
+
+/// Usage moves smaller than this many percentage points are noise.
+const SIGNIFICANT_CHANGE: f64 = 5.0;
+
+/// Usage moves smaller than this many percentage points leave a refresh
+/// "quiet" for the adaptive interval.
+const QUIET_EPSILON: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delta {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Usage {
+        cpu: Option<Delta>,
+        memory: Option<Delta>,
+    },
+}
+
+/// The change from `before` (None for a new guest) to `after`, if any is
+/// worth showing.
+pub fn diff(before: Option<&Container>, after: &Container) -> Option<Change> {
+    let Some(before) = before else {
+        return Some(Change::Added);
+    };
+    let cpu = delta(before.cpu_usage, after.cpu_usage);
+    let memory = delta(before.memory_percent(), after.memory_percent());
+    (cpu.is_some() || memory.is_some()).then_some(Change::Usage { cpu, memory })
+}
+
+/// Whether going from `before` to `after` changed anything meaningful: an
+/// item came or went, a status flipped, or usage moved by `QUIET_EPSILON`.
+pub fn snapshot_changed(
+    (nodes_before, containers_before): (&[Node], &[Container]),
+    (nodes_after, containers_after): (&[Node], &[Container]),
+) -> bool {
+    let moved = |before: f64, after: f64| (after - before).abs() >= QUIET_EPSILON;
+    let nodes = restarts::matched(nodes_before, nodes_after, |n| n.id.clone());
+    let containers = restarts::matched(containers_before, containers_after, |c| c.id.clone());
+    nodes.len() != nodes_before.len()
+        || nodes.len() != nodes_after.len()
+        || containers.len() != containers_before.len()
+        || containers.len() != containers_after.len()
+        || nodes.iter().any(|(before, after)| {
+            before.status != after.status
+                || moved(before.cpu_usage, after.cpu_usage)
+                || moved(before.memory_percent(), after.memory_percent())
+        })
+        || containers.iter().any(|(before, after)| {
+            before.status != after.status
+                || before.missing != after.missing
+                || moved(before.cpu_usage, after.cpu_usage)
+                || moved(before.memory_percent(), after.memory_percent())
+        })
+}
+
+fn delta(before: f64, after: f64) -> Option<Delta> {
+    if after - before >= SIGNIFICANT_CHANGE {
+        Some(Delta::Up)
+    } else if before - after >= SIGNIFICANT_CHANGE {
+        Some(Delta::Down)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ContainerStatus, GuestId};
+
+    fn guest(cpu: f64, memory_used: u64) -> Container {
+        Container {
+            cpu_usage: cpu,
+            memory_used,
+            memory_max: 1000,
+            uptime: 60,
+            ..Container::test("homelab", 101, "nginx", "pve1")
+        }
+    }
+
+    #[test]
+    fn test_diff_ignores_small_moves() {
+        assert_eq!(diff(None, &guest(0.0, 0)), Some(Change::Added));
+        assert_eq!(diff(Some(&guest(10.0, 500)), &guest(14.0, 530)), None);
+        assert_eq!(
+            diff(Some(&guest(10.0, 500)), &guest(30.0, 300)),
+            Some(Change::Usage {
+                cpu: Some(Delta::Up),
+                memory: Some(Delta::Down),
+            })
+        );
+        assert_eq!(
+            diff(Some(&guest(30.0, 500)), &guest(25.0, 500)),
+            Some(Change::Usage {
+                cpu: Some(Delta::Down),
+                memory: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_snapshot_changed() {
+        let nodes: &[Node] = &[];
+        let before = [guest(10.0, 500)];
+        let quiet = [guest(10.5, 505)];
+        assert!(!snapshot_changed((nodes, &before), (nodes, &quiet)));
+        assert!(snapshot_changed(
+            (nodes, &before),
+            (nodes, &[guest(12.0, 500)])
+        ));
+        assert!(snapshot_changed((nodes, &before), (nodes, &[])));
+
+        let stopped = Container {
+            status: ContainerStatus::Stopped,
+            ..guest(10.0, 500)
+        };
+        assert!(snapshot_changed((nodes, &before), (nodes, &[stopped])));
+        let added = Container {
+            id: GuestId::new("homelab", 102),
+            ..guest(0.0, 0)
+        };
+        assert!(snapshot_changed(
+            (nodes, &before),
+            (nodes, &[guest(10.0, 500), added])
+        ));
+    }
+}
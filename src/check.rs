@@ -0,0 +1,92 @@
+use std::fmt::Write;
+
+use crate::app::App;
+
+/// Summarize provider health for `pulse check`: connection errors, missing
+/// permissions and lost quorum. Returns the report and whether all is well.
+pub fn report(app: &App) -> (String, bool) {
+    let mut out = String::new();
+    let mut healthy = true;
+
+    // Providers left out at startup weren't asked about
+    for status in app.provider_status.iter().filter(|s| !s.dormant) {
+        let mut problems = Vec::new();
+        if let Some(ref error) = status.last_error {
+            problems.push(format!("error: {}", error));
+        }
+        for issue in &status.permission_issues {
+            problems.push(issue.to_string());
+        }
+        if let Some(ref cluster) = status.cluster
+            && !cluster.quorate
+        {
+            problems.push(cluster.summary());
+        }
+
+        if problems.is_empty() {
+            let _ = writeln!(out, "{}: ok", status.name);
+        } else {
+            healthy = false;
+            for problem in problems {
+                let _ = writeln!(out, "{}: {}", status.name, problem);
+            }
+        }
+    }
+
+    (out, healthy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ProviderStatus;
+    use crate::models::{ClusterStatus, PermissionIssue};
+
+    #[test]
+    fn test_report_all_ok() {
+        let mut app = App::new();
+        app.provider_status = vec![ProviderStatus::new("homelab")];
+
+        let (text, healthy) = report(&app);
+
+        assert_eq!(text, "homelab: ok\n");
+        assert!(healthy);
+    }
+
+    #[test]
+    fn test_report_lists_problems() {
+        let mut app = App::new();
+        let mut homelab = ProviderStatus::new("homelab");
+        homelab.permission_issues = vec![PermissionIssue::new(
+            "VM.Audit",
+            "/vms",
+            "containers will not be shown",
+        )];
+        homelab.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: false,
+            nodes_online: 1,
+            nodes_total: 3,
+            firewall: None,
+            ha: Default::default(),
+        });
+        let mut remote = ProviderStatus::new("remote");
+        remote.last_error = Some("error fetching nodes: connection refused".to_string());
+        // Left out at startup: not reported at all
+        let mut office = ProviderStatus::new("office");
+        office.disabled = true;
+        office.dormant = true;
+        app.provider_status = vec![homelab, remote, ProviderStatus::new("garage"), office];
+
+        let (text, healthy) = report(&app);
+
+        assert!(!healthy);
+        assert_eq!(
+            text,
+            "homelab: token lacks VM.Audit on /vms — containers will not be shown\n\
+             homelab: cluster homelab: NO QUORUM (1/3)\n\
+             remote: error: error fetching nodes: connection refused\n\
+             garage: ok\n"
+        );
+    }
+}
@@ -1,10 +1,472 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::SortField;
+use crate::format::parse_duration;
 
 #[derive(Parser, Debug)]
 #[command(name = "pulse")]
 #[command(version = "0.1.0")]
 #[command(about = "Real-time homelab infrastructure monitor")]
 pub struct Args {
-    #[arg(short, long, default_value = "config.toml")]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(short, long, default_value = "config.toml", global = true)]
     pub config: String,
+
+    /// Start with this search query applied
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    /// Start sorted by this field
+    #[arg(long, value_enum)]
+    pub sort: Option<SortField>,
+
+    /// Sort in descending order
+    #[arg(long)]
+    pub desc: bool,
+
+    /// Start only these providers (comma-separated names); the others can
+    /// be turned on from the providers popup
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    pub provider: Vec<String>,
+
+    /// Also show the machine pulse runs on; works without a config file
+    #[arg(long)]
+    pub local: bool,
+
+    /// Refuse every action that changes guests (drain, console), in the UI
+    /// and in the providers
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Go through actions without changing anything: the API calls they
+    /// would make are shown and audited instead of sent
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Append pulse's own log (errors, actions, refresh timings) to this
+    /// file; `l` shows it in the UI
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Print a single snapshot to stdout and exit instead of starting the UI
+    #[arg(long)]
+    pub once: bool,
+
+    /// Output format for --once
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, requires = "once")]
+    pub format: OutputFormat,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Check connectivity, token permissions and quorum for every provider;
+    /// exits non-zero if anything is wrong
+    Check,
+    /// Print recorded history for a node or guest (by name or VMID), or
+    /// with `report` a summary of every one
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+        #[arg(required = true)]
+        name: Option<String>,
+        /// How far back to look, e.g. 30m, 6h, 7d
+        #[arg(long, default_value = "6h", value_parser = parse_duration)]
+        since: Duration,
+    },
+    /// Write a standalone HTML report of nodes and guests; with history
+    /// enabled it adds average and peak usage over `--since`
+    Report {
+        #[arg(short, long, default_value = "pulse-report.html")]
+        output: String,
+        /// How far back history averages reach, e.g. 24h, 7d
+        #[arg(long, default_value = "7d", value_parser = parse_duration)]
+        since: Duration,
+    },
+    /// Print a plain-text summary after every refresh instead of starting
+    /// the UI: no cursor movement and no colors, for screen readers, dumb
+    /// terminals and `tee`
+    Watch {
+        /// Time between refreshes [default: the config's refresh_rate]
+        #[arg(long, value_parser = parse_duration)]
+        interval: Option<Duration>,
+        /// Also print the full node and guest tables, as `--once` does
+        #[arg(long)]
+        full: bool,
+        /// Color the problems red and yellow
+        #[arg(long)]
+        color: bool,
+    },
+    /// Serve this machine's stats (and its Docker containers) over HTTP for
+    /// a `pulse_agent` provider elsewhere
+    Agent {
+        /// Address to listen on; ":9898" means every interface
+        #[arg(long, default_value = ":9898")]
+        listen: String,
+    },
+    /// Refresh the providers in the background and serve them over a unix
+    /// socket to any number of `pulse attach`; supports systemd socket
+    /// activation
+    Daemon {
+        /// Socket to listen on [default: $XDG_RUNTIME_DIR/pulse.sock]
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Show what a running `pulse daemon` fetches instead of polling the
+    /// providers; actions are taken by the daemon
+    Attach {
+        /// Socket the daemon listens on [default: $XDG_RUNTIME_DIR/pulse.sock]
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Start or shut down one guest and wait for its task, with the same
+    /// refusals and audit log as the UI
+    Guest {
+        #[command(subcommand)]
+        command: GuestCommand,
+    },
+    /// Inspect the audit log of actions taken through pulse
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum AuditCommand {
+    /// Print the most recent entries
+    Tail {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum HistoryCommand {
+    /// Peak and average CPU and memory, downtime and restarts of every node
+    /// and guest; time pulse wasn't recording isn't counted as downtime
+    Report {
+        /// How far back to look, e.g. 24h, 7d
+        #[arg(long, default_value = "7d", value_parser = parse_duration)]
+        since: Duration,
+        /// Order of the rows, highest first (names A to Z)
+        #[arg(long, value_enum, default_value_t = PeakSort::Cpu)]
+        sort: PeakSort,
+        #[arg(long, value_enum, default_value_t = HistoryFormat::Table)]
+        format: HistoryFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PeakSort {
+    /// Peak CPU
+    Cpu,
+    /// Peak memory
+    Memory,
+    Downtime,
+    Restarts,
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum HistoryFormat {
+    /// Aligned columns
+    Table,
+    /// One object per node and guest
+    Json,
+}
+
+/// Guests are named by VMID, name or a unique prefix of one, optionally
+/// narrowed as `provider/…`, `node:…` or both.
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum GuestCommand {
+    /// Start a stopped guest
+    Start { target: String },
+    /// Shut a running guest down cleanly
+    #[command(alias = "stop")]
+    Shutdown { target: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables
+    Table,
+    /// Nodes and containers as a JSON document
+    Json,
+    /// Prometheus text exposition, e.g. for node_exporter's textfile collector
+    Prometheus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filter_and_sort() {
+        let args = Args::try_parse_from(["pulse", "--filter", "media", "--sort", "cpu", "--desc"])
+            .unwrap();
+        assert_eq!(args.filter.as_deref(), Some("media"));
+        assert_eq!(args.sort, Some(SortField::Cpu));
+        assert!(args.desc);
+        assert!(!args.once);
+        assert!(!args.local);
+
+        let args = Args::try_parse_from(["pulse", "--local"]).unwrap();
+        assert!(args.local);
+        assert!(!args.read_only);
+
+        let args = Args::try_parse_from(["pulse", "--read-only"]).unwrap();
+        assert!(args.read_only);
+        assert_eq!(args.log_file, None);
+
+        let args = Args::try_parse_from(["pulse", "--log-file", "/tmp/pulse.log"]).unwrap();
+        assert_eq!(args.log_file, Some(PathBuf::from("/tmp/pulse.log")));
+    }
+
+    #[test]
+    fn test_parse_provider_list() {
+        let args = Args::try_parse_from(["pulse"]).unwrap();
+        assert!(args.provider.is_empty());
+
+        let args =
+            Args::try_parse_from(["pulse", "--provider", "homelab,office", "--provider", "nas"])
+                .unwrap();
+        assert_eq!(args.provider, ["homelab", "office", "nas"]);
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let args = Args::try_parse_from(["pulse", "--once"]).unwrap();
+        assert_eq!(args.format, OutputFormat::Table);
+
+        let args = Args::try_parse_from(["pulse", "--once", "--format", "prometheus"]).unwrap();
+        assert_eq!(args.format, OutputFormat::Prometheus);
+
+        let args = Args::try_parse_from(["pulse", "--once", "--format", "json"]).unwrap();
+        assert_eq!(args.format, OutputFormat::Json);
+
+        // Only meaningful for a one-shot snapshot
+        assert!(Args::try_parse_from(["pulse", "--format", "prometheus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_check_subcommand() {
+        let args = Args::try_parse_from(["pulse", "check", "--config", "lab.toml"]).unwrap();
+        assert_eq!(args.command, Some(Command::Check));
+        assert_eq!(args.config, "lab.toml");
+
+        let args = Args::try_parse_from(["pulse"]).unwrap();
+        assert_eq!(args.command, None);
+    }
+
+    #[test]
+    fn test_parse_history_subcommand() {
+        let args = Args::try_parse_from(["pulse", "history", "nginx", "--since", "2d"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::History {
+                command: None,
+                name: Some("nginx".to_string()),
+                since: Duration::from_secs(2 * 86400),
+            })
+        );
+
+        let args = Args::try_parse_from(["pulse", "history", "101"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::History {
+                command: None,
+                name: Some("101".to_string()),
+                since: Duration::from_secs(6 * 3600),
+            })
+        );
+
+        assert!(Args::try_parse_from(["pulse", "history", "nginx", "--since", "soon"]).is_err());
+        assert!(Args::try_parse_from(["pulse", "history"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_history_report() {
+        let args = Args::try_parse_from(["pulse", "history", "report"]).unwrap();
+        let Some(Command::History {
+            command: Some(report),
+            name: None,
+            ..
+        }) = args.command
+        else {
+            panic!("{:?}", args.command);
+        };
+        assert_eq!(
+            report,
+            HistoryCommand::Report {
+                since: Duration::from_secs(7 * 86400),
+                sort: PeakSort::Cpu,
+                format: HistoryFormat::Table,
+            }
+        );
+
+        let args = Args::try_parse_from([
+            "pulse", "history", "report", "--since", "24h", "--sort", "downtime", "--format",
+            "json",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History {
+                command: Some(HistoryCommand::Report {
+                    sort: PeakSort::Downtime,
+                    format: HistoryFormat::Json,
+                    ..
+                }),
+                ..
+            })
+        ));
+
+        // A name and the report don't mix
+        assert!(Args::try_parse_from(["pulse", "history", "nginx", "report"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_audit_tail() {
+        let args = Args::try_parse_from(["pulse", "audit", "tail", "-n", "5"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Audit {
+                command: AuditCommand::Tail { lines: 5 }
+            })
+        );
+
+        let args = Args::try_parse_from(["pulse", "audit", "tail"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Audit {
+                command: AuditCommand::Tail { lines: 20 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_watch_subcommand() {
+        let args = Args::try_parse_from(["pulse", "watch"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Watch {
+                interval: None,
+                full: false,
+                color: false,
+            })
+        );
+
+        let args =
+            Args::try_parse_from(["pulse", "watch", "--interval", "30s", "--full", "--color"])
+                .unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Watch {
+                interval: Some(Duration::from_secs(30)),
+                full: true,
+                color: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_guest_subcommands() {
+        let args = Args::try_parse_from(["pulse", "guest", "start", "homelab/105"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Guest {
+                command: GuestCommand::Start {
+                    target: "homelab/105".to_string()
+                }
+            })
+        );
+
+        for verb in ["shutdown", "stop"] {
+            let args = Args::try_parse_from(["pulse", "guest", verb, "pve2:web-01"]).unwrap();
+            assert_eq!(
+                args.command,
+                Some(Command::Guest {
+                    command: GuestCommand::Shutdown {
+                        target: "pve2:web-01".to_string()
+                    }
+                })
+            );
+        }
+
+        assert!(Args::try_parse_from(["pulse", "guest", "start"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_sort_lists_valid_values() {
+        let err = Args::try_parse_from(["pulse", "--sort", "swap"]).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("name"));
+        assert!(message.contains("status"));
+        assert!(message.contains("cpu"));
+        assert!(message.contains("memory"));
+    }
+
+    #[test]
+    fn test_parse_report_subcommand() {
+        let args = Args::try_parse_from(["pulse", "report", "--output", "lab.html"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Report {
+                output: "lab.html".to_string(),
+                since: Duration::from_secs(7 * 86400),
+            })
+        );
+
+        let args =
+            Args::try_parse_from(["pulse", "report", "-o", "a.html", "--since", "24h"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Report {
+                output: "a.html".to_string(),
+                since: Duration::from_secs(86400),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_daemon_and_attach() {
+        let args = Args::try_parse_from(["pulse", "daemon"]).unwrap();
+        assert_eq!(args.command, Some(Command::Daemon { socket: None }));
+        let args =
+            Args::try_parse_from(["pulse", "attach", "--socket", "/run/pulse/pulse.sock"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Attach {
+                socket: Some(PathBuf::from("/run/pulse/pulse.sock")),
+            })
+        );
+        // Top-level options still apply to an attached UI
+        let args = Args::try_parse_from(["pulse", "--read-only", "attach"]).unwrap();
+        assert!(args.read_only);
+    }
+
+    #[test]
+    fn test_parse_agent() {
+        let args = Args::try_parse_from(["pulse", "agent"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Agent {
+                listen: ":9898".to_string(),
+            })
+        );
+        let args = Args::try_parse_from(["pulse", "agent", "--listen", "127.0.0.1:9000"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Agent {
+                listen: "127.0.0.1:9000".to_string(),
+            })
+        );
+    }
 }
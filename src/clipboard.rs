@@ -0,0 +1,81 @@
+//! Handing a URL over to the user: onto the clipboard with the OSC 52
+//! escape, which the terminal passes to the local clipboard even when pulse
+//! runs over SSH (tmux needs `set-clipboard on`), or to the desktop's
+//! browser.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Where a URL goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handoff {
+    Copy,
+    Open,
+}
+
+/// Put `text` on the clipboard of the terminal pulse runs in.
+pub fn copy(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+/// Open `url` with the desktop's handler, without waiting for it.
+pub fn open(url: &str) -> io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_pads() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(
+            base64(b"https://pve:8006/?vmid=1"),
+            "aHR0cHM6Ly9wdmU6ODAwNi8/dm1pZD0x"
+        );
+    }
+
+    #[test]
+    fn test_copy_writes_osc52() {
+        let mut out = Vec::new();
+        copy(&mut out, "foo").unwrap();
+        assert_eq!(out, b"\x1b]52;c;Zm9v\x07");
+    }
+}
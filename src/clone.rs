@@ -0,0 +1,342 @@
+//! Cloning a guest: the form the `C` popup edits, its validation against the
+//! guests already listed, and the task it starts.
+//!
+//! Validation happens here, before anything is sent, so a taken VMID is
+//! caught from the current guest list rather than by a failed task.
+
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Container, GuestId, Node, NodeStatus};
+
+/// Proxmox accepts VMIDs in this range.
+const VMID_RANGE: std::ops::RangeInclusive<u32> = 100..=999_999_999;
+
+/// What `Provider::clone_guest` is asked to create.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloneRequest {
+    pub vmid: u32,
+    pub name: String,
+    /// Node to create the clone on; `None` keeps it on the source's node.
+    pub target: Option<String>,
+    /// A full copy, rather than a linked clone sharing the template's disks.
+    pub full: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloneField {
+    Vmid,
+    Name,
+    Target,
+    Full,
+}
+
+impl CloneField {
+    const ALL: [CloneField; 4] = [
+        CloneField::Vmid,
+        CloneField::Name,
+        CloneField::Target,
+        CloneField::Full,
+    ];
+
+    fn index(self) -> usize {
+        CloneField::ALL.iter().position(|&f| f == self).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClonePhase {
+    /// The form is open for input.
+    Editing,
+    /// Task started on the provider, polled until it finishes.
+    Running {
+        task: String,
+    },
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CloneForm {
+    pub source: GuestId,
+    /// Node the source is on, which runs the clone task.
+    pub source_node: String,
+    /// "105 web", for the title and the audit log.
+    pub label: String,
+    /// Only templates can be cloned linked.
+    pub is_template: bool,
+    pub vmid: String,
+    pub name: String,
+    /// Nodes the clone can be created on, the source's node first.
+    pub targets: Vec<String>,
+    pub target: usize,
+    pub full: bool,
+    pub field: CloneField,
+    /// Why the form can't be submitted as it is.
+    pub error: Option<String>,
+    pub phase: ClonePhase,
+    /// When the running task was last polled.
+    pub last_poll: Option<Instant>,
+}
+
+impl CloneForm {
+    /// A form for cloning `source`, with `suggested` as the VMID. Linked is
+    /// the default for templates, as in Proxmox.
+    pub fn new(source: &Container, nodes: &[Node], suggested: Option<u32>) -> Self {
+        let mut targets = vec![source.node.clone()];
+        targets.extend(
+            nodes
+                .iter()
+                .filter(|n| {
+                    n.provider() == source.provider()
+                        && n.name != source.node
+                        && n.status == NodeStatus::Online
+                })
+                .map(|n| n.name.clone()),
+        );
+        CloneForm {
+            source: source.id.clone(),
+            source_node: source.node.clone(),
+            label: format!("{} {}", source.vmid, source.name),
+            is_template: source.is_template,
+            vmid: suggested.map(|id| id.to_string()).unwrap_or_default(),
+            name: format!("{}-clone", source.name),
+            targets,
+            target: 0,
+            full: !source.is_template,
+            field: CloneField::Vmid,
+            error: None,
+            phase: ClonePhase::Editing,
+            last_poll: None,
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        self.field = CloneField::ALL[(self.field.index() + 1) % CloneField::ALL.len()];
+    }
+
+    pub fn previous_field(&mut self) {
+        let count = CloneField::ALL.len();
+        self.field = CloneField::ALL[(self.field.index() + count - 1) % count];
+    }
+
+    /// Type into the VMID or name field; other fields ignore it.
+    pub fn push_char(&mut self, c: char) {
+        match self.field {
+            CloneField::Vmid => self.vmid.push(c),
+            CloneField::Name => self.name.push(c),
+            CloneField::Target | CloneField::Full => return,
+        }
+        self.error = None;
+    }
+
+    pub fn pop_char(&mut self) {
+        match self.field {
+            CloneField::Vmid => self.vmid.pop(),
+            CloneField::Name => self.name.pop(),
+            CloneField::Target | CloneField::Full => return,
+        };
+        self.error = None;
+    }
+
+    /// Step the node or the full/linked choice; other fields ignore it.
+    pub fn cycle(&mut self) {
+        match self.field {
+            CloneField::Target => self.target = (self.target + 1) % self.targets.len(),
+            CloneField::Full => self.full = !self.full,
+            CloneField::Vmid | CloneField::Name => return,
+        }
+        self.error = None;
+    }
+
+    /// The request the form describes, or why it can't be sent. `guests`
+    /// is the current list, so a VMID already in use is refused here.
+    pub fn validate(&self, guests: &[Container]) -> Result<CloneRequest, String> {
+        let vmid = self
+            .vmid
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|id| VMID_RANGE.contains(id))
+            .ok_or_else(|| {
+                format!(
+                    "VMID must be a number from {} to {}",
+                    VMID_RANGE.start(),
+                    VMID_RANGE.end()
+                )
+            })?;
+        if let Some(taken) = guests
+            .iter()
+            .find(|c| c.provider() == self.source.provider && c.vmid == vmid)
+        {
+            return Err(format!("VMID {} is already used by {}", vmid, taken.name));
+        }
+        let name = self.name.trim();
+        if !is_dns_name(name) {
+            return Err("name must be letters, digits and inner '-' or '.'".to_string());
+        }
+        if !self.full && !self.is_template {
+            return Err("only templates can be cloned linked; choose full".to_string());
+        }
+        Ok(CloneRequest {
+            vmid,
+            name: name.to_string(),
+            target: (self.target > 0).then(|| self.targets[self.target].clone()),
+            full: self.full,
+        })
+    }
+}
+
+/// Proxmox requires guest names (and container hostnames) to be DNS names.
+fn is_dns_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest(vmid: u32, name: &str) -> Container {
+        Container {
+            memory_max: 512,
+            uptime: 60,
+            ..Container::test("homelab", vmid, name, "pve1")
+        }
+    }
+
+    fn node(name: &str, status: NodeStatus) -> Node {
+        Node {
+            status,
+            cpu_cores: 4,
+            memory_total: 8 << 30,
+            uptime: 3600,
+            ..Node::test("homelab", name)
+        }
+    }
+
+    #[test]
+    fn test_new_form_defaults() {
+        let nodes = [
+            node("pve1", NodeStatus::Online),
+            node("pve2", NodeStatus::Online),
+            node("pve3", NodeStatus::Offline),
+        ];
+        let form = CloneForm::new(&guest(105, "web"), &nodes, Some(106));
+        assert_eq!(form.label, "105 web");
+        assert_eq!(
+            (form.vmid.as_str(), form.name.as_str()),
+            ("106", "web-clone")
+        );
+        assert_eq!(form.targets, ["pve1", "pve2"]);
+        assert!(form.full);
+
+        let mut template = guest(9000, "debian");
+        template.is_template = true;
+        assert!(!CloneForm::new(&template, &nodes, None).full);
+    }
+
+    #[test]
+    fn test_editing_follows_the_focused_field() {
+        let nodes = [
+            node("pve1", NodeStatus::Online),
+            node("pve2", NodeStatus::Online),
+        ];
+        let mut form = CloneForm::new(&guest(105, "web"), &nodes, Some(106));
+        form.pop_char();
+        form.push_char('9');
+        assert_eq!(form.vmid, "109");
+
+        form.next_field();
+        form.push_char('2');
+        assert_eq!(form.name, "web-clone2");
+
+        form.next_field();
+        form.push_char('x');
+        form.cycle();
+        assert_eq!(form.targets[form.target], "pve2");
+
+        form.next_field();
+        form.cycle();
+        assert!(!form.full);
+        form.next_field();
+        assert_eq!(form.field, CloneField::Vmid);
+        form.previous_field();
+        assert_eq!(form.field, CloneField::Full);
+    }
+
+    #[test]
+    fn test_validate() {
+        let nodes = [
+            node("pve1", NodeStatus::Online),
+            node("pve2", NodeStatus::Online),
+        ];
+        let guests = [guest(105, "web"), guest(106, "db")];
+        let mut form = CloneForm::new(&guests[0], &nodes, Some(107));
+        assert_eq!(
+            form.validate(&guests),
+            Ok(CloneRequest {
+                vmid: 107,
+                name: "web-clone".to_string(),
+                target: None,
+                full: true,
+            })
+        );
+
+        form.target = 1;
+        assert_eq!(
+            form.validate(&guests).unwrap().target.as_deref(),
+            Some("pve2")
+        );
+
+        form.vmid = "106".to_string();
+        assert_eq!(
+            form.validate(&guests).unwrap_err(),
+            "VMID 106 is already used by db"
+        );
+        // Another provider's VMIDs are its own
+        let mut elsewhere = guest(107, "other");
+        elsewhere.id = GuestId::new("office", 107);
+        form.vmid = "107".to_string();
+        assert!(form.validate(&[elsewhere]).is_ok());
+
+        form.vmid = "99".to_string();
+        assert_eq!(
+            form.validate(&guests).unwrap_err(),
+            "VMID must be a number from 100 to 999999999"
+        );
+
+        form.vmid = "107".to_string();
+        form.name = "web_clone".to_string();
+        assert!(
+            form.validate(&guests)
+                .unwrap_err()
+                .starts_with("name must be")
+        );
+
+        form.name = "web.lab".to_string();
+        form.full = false;
+        assert_eq!(
+            form.validate(&guests).unwrap_err(),
+            "only templates can be cloned linked; choose full"
+        );
+        form.is_template = true;
+        assert!(!form.validate(&guests).unwrap().full);
+    }
+
+    #[test]
+    fn test_is_dns_name() {
+        assert!(is_dns_name("web-01"));
+        assert!(is_dns_name("web.lab"));
+        assert!(!is_dns_name(""));
+        assert!(!is_dns_name("-web"));
+        assert!(!is_dns_name("web."));
+        assert!(!is_dns_name("web 01"));
+    }
+}
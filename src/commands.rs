@@ -0,0 +1,1013 @@
+//! Command registry: every action pulse can take from the keyboard or the
+//! `:` palette. Key handling, palette parsing and completion, and the help
+//! popup all read `COMMANDS`, so a command can't exist in one and not the
+//! others.
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::app::{Panel, SortField};
+use crate::cli::OutputFormat;
+use crate::clipboard::Handoff;
+use crate::config::{Accessibility, CpuMode};
+use crate::groups::GroupBy;
+use crate::node_power::NodePower;
+use crate::restarts::RecentFilter;
+use crate::snooze::SnoozeLength;
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Keys that run the command without arguments, as shown in help.
+    pub keys: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub help: &'static str,
+}
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+pub enum ArgKind {
+    /// One of these values, offered by Tab.
+    Choice(&'static [&'static str]),
+    /// A single word.
+    Word,
+    /// Free text: the rest of the line.
+    Rest,
+}
+
+const SORT_FIELDS: &[&str] = &["name", "status", "cpu", "memory", "memory-abs", "disk"];
+const SORT_ORDERS: &[&str] = &["asc", "desc"];
+const COLUMNS: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+const CPU_MODES: &[&str] = &["guest", "host"];
+const GROUPINGS: &[&str] = &["off", "node", "prefix"];
+const RECENT_WINDOWS: &[&str] = &["off", "15m", "1h", "24h"];
+const THEMES: &[&str] = &["standard", "colorblind", "mono"];
+const SNOOZE_LENGTHS: &[&str] = &["15m", "1h", "restart", "off"];
+const EXPORT_FORMATS: &[&str] = &["table", "json", "prometheus"];
+const HANDOFFS: &[&str] = &["copy", "open"];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "quit",
+        keys: &["q"],
+        args: &[],
+        help: "Quit application",
+    },
+    CommandSpec {
+        name: "panel",
+        keys: &["Tab"],
+        args: &[],
+        help: "Switch between panels",
+    },
+    CommandSpec {
+        name: "down",
+        keys: &["j", "Down"],
+        args: &[],
+        help: "Move selection down",
+    },
+    CommandSpec {
+        name: "up",
+        keys: &["k", "Up"],
+        args: &[],
+        help: "Move selection up",
+    },
+    CommandSpec {
+        name: "refresh",
+        keys: &["r"],
+        args: &[],
+        help: "Refresh data",
+    },
+    CommandSpec {
+        name: "refresh-selected",
+        keys: &["R"],
+        args: &[],
+        help: "Refresh only the selected item",
+    },
+    CommandSpec {
+        name: "sort",
+        keys: &["s"],
+        args: &[
+            ArgSpec {
+                name: "field",
+                kind: ArgKind::Choice(SORT_FIELDS),
+                required: false,
+            },
+            ArgSpec {
+                name: "order",
+                kind: ArgKind::Choice(SORT_ORDERS),
+                required: false,
+            },
+        ],
+        help: "Cycle sort field",
+    },
+    CommandSpec {
+        name: "column",
+        keys: &["1-9"],
+        args: &[ArgSpec {
+            name: "n",
+            kind: ArgKind::Choice(COLUMNS),
+            required: true,
+        }],
+        help: "Sort by the nth column shown (again reverses)",
+    },
+    CommandSpec {
+        name: "reverse",
+        keys: &["S"],
+        args: &[],
+        help: "Toggle sort order",
+    },
+    CommandSpec {
+        name: "problems",
+        keys: &["!"],
+        args: &[],
+        help: "Toggle problems first (down, then over threshold)",
+    },
+    CommandSpec {
+        name: "by-node",
+        keys: &["n"],
+        args: &[],
+        help: "Toggle sorting containers by node first",
+    },
+    CommandSpec {
+        name: "filter",
+        keys: &["/"],
+        args: &[ArgSpec {
+            name: "query",
+            kind: ArgKind::Rest,
+            required: false,
+        }],
+        help: "Enter search mode (syntax below)",
+    },
+    CommandSpec {
+        name: "clear",
+        keys: &["Esc"],
+        args: &[],
+        help: "Clear search / Exit mode",
+    },
+    CommandSpec {
+        name: "cpu",
+        keys: &["c"],
+        args: &[ArgSpec {
+            name: "mode",
+            kind: ArgKind::Choice(CPU_MODES),
+            required: false,
+        }],
+        help: "Toggle CPU % of guest / host",
+    },
+    CommandSpec {
+        name: "pin",
+        keys: &["p"],
+        args: &[],
+        help: "Pin item; pin a second to compare",
+    },
+    CommandSpec {
+        name: "mark",
+        keys: &["Space"],
+        args: &[],
+        help: "Mark or unmark the selected guest (Esc clears marks)",
+    },
+    CommandSpec {
+        name: "visual",
+        keys: &["V"],
+        args: &[],
+        help: "Mark a range: j/k extend, Space/Enter mark, Esc cancel",
+    },
+    CommandSpec {
+        name: "permissions",
+        keys: &[],
+        args: &[],
+        help: "Re-check token permissions",
+    },
+    CommandSpec {
+        name: "drain",
+        keys: &["d"],
+        args: &[],
+        help: "Drain the selected node (plan, then confirm)",
+    },
+    CommandSpec {
+        name: "start",
+        keys: &["b"],
+        args: &[],
+        help: "Start the selected stopped guest (warns if memory is short), or the marked ones",
+    },
+    CommandSpec {
+        name: "shutdown",
+        keys: &["x"],
+        args: &[],
+        help: "Shut down the marked guests, or the selected one (queued, then confirm)",
+    },
+    CommandSpec {
+        name: "reboot",
+        keys: &["B"],
+        args: &[],
+        help: "Reboot the selected node (shows the guests affected; type its name to confirm)",
+    },
+    CommandSpec {
+        name: "poweroff",
+        keys: &["X"],
+        args: &[],
+        help: "Shut down the selected node (shows the guests affected; type its name to confirm)",
+    },
+    CommandSpec {
+        name: "console",
+        keys: &["t"],
+        args: &[],
+        help: "Open a console on the selected guest",
+    },
+    CommandSpec {
+        name: "webconsole",
+        keys: &["o"],
+        args: &[ArgSpec {
+            name: "copy|open",
+            kind: ArgKind::Choice(HANDOFFS),
+            required: false,
+        }],
+        help: "Copy (or open) a web UI console URL for the selected guest",
+    },
+    CommandSpec {
+        name: "clone",
+        keys: &["C"],
+        args: &[],
+        help: "Clone the selected guest (VMID, name, node, full/linked)",
+    },
+    CommandSpec {
+        name: "resources",
+        keys: &["M"],
+        args: &[],
+        help: "Change the selected guest's cores and memory (warns if it needs a restart)",
+    },
+    CommandSpec {
+        name: "note",
+        keys: &["A"],
+        args: &[ArgSpec {
+            name: "text",
+            kind: ArgKind::Rest,
+            required: false,
+        }],
+        help: "Annotate the selected guest (an empty note removes it)",
+    },
+    CommandSpec {
+        name: "snooze",
+        keys: &["z"],
+        args: &[ArgSpec {
+            name: "length",
+            kind: ArgKind::Choice(SNOOZE_LENGTHS),
+            required: false,
+        }],
+        help: "Snooze the selected item's alerts (off wakes them)",
+    },
+    CommandSpec {
+        name: "errors",
+        keys: &["E"],
+        args: &[],
+        help: "Show full error messages",
+    },
+    CommandSpec {
+        name: "events",
+        keys: &["L"],
+        args: &[],
+        help: "Show the cluster log and detected state changes",
+    },
+    CommandSpec {
+        name: "logs",
+        keys: &["l"],
+        args: &[],
+        help: "Follow pulse's own log (--log-file): f cycles the level shown, h/l scroll sideways",
+    },
+    CommandSpec {
+        name: "debug",
+        keys: &["F12"],
+        args: &[],
+        help: "Show refresh timings, scheduler state and buffer sizes",
+    },
+    CommandSpec {
+        name: "wide",
+        keys: &["w"],
+        args: &[],
+        help: "Toggle full-width names",
+    },
+    CommandSpec {
+        name: "group",
+        keys: &["g"],
+        args: &[ArgSpec {
+            name: "by",
+            kind: ArgKind::Choice(GROUPINGS),
+            required: false,
+        }],
+        help: "Group containers: off → node → name prefix",
+    },
+    CommandSpec {
+        name: "collapse",
+        keys: &["G"],
+        args: &[],
+        help: "Collapse groups to summary rows",
+    },
+    CommandSpec {
+        name: "recent",
+        keys: &["u"],
+        args: &[ArgSpec {
+            name: "uptime",
+            kind: ArgKind::Choice(RECENT_WINDOWS),
+            required: false,
+        }],
+        help: "Only guests up less than: off → 15m → 1h → 24h",
+    },
+    CommandSpec {
+        name: "templates",
+        keys: &["T"],
+        args: &[],
+        help: "Show or hide template guests",
+    },
+    CommandSpec {
+        name: "providers",
+        keys: &["P"],
+        args: &[],
+        help: "Providers: state, counts and totals; filter to one or disable one",
+    },
+    CommandSpec {
+        name: "presets",
+        keys: &["F"],
+        args: &[],
+        help: "Filter presets: apply (Enter, 1-9) or delete (d)",
+    },
+    CommandSpec {
+        name: "reload",
+        keys: &["Ctrl+e"],
+        args: &[],
+        help: "Reload the config file (also on SIGHUP)",
+    },
+    CommandSpec {
+        name: "mute",
+        keys: &["m"],
+        args: &[],
+        help: "Mute (or unmute) the alert bell and flash for this session",
+    },
+    CommandSpec {
+        name: "help",
+        keys: &["?"],
+        args: &[],
+        help: "Toggle this help",
+    },
+    CommandSpec {
+        name: "goto",
+        keys: &["'"],
+        args: &[ArgSpec {
+            name: "vmid|name",
+            kind: ArgKind::Word,
+            required: false,
+        }],
+        help: "Jump to a guest by VMID or a node by name as you type (:goto 105 selects directly)",
+    },
+    CommandSpec {
+        name: "preset",
+        keys: &[],
+        args: &[ArgSpec {
+            name: "name",
+            kind: ArgKind::Rest,
+            required: true,
+        }],
+        help: "Apply a saved filter preset (Ctrl+s in search saves one)",
+    },
+    CommandSpec {
+        name: "theme",
+        keys: &[],
+        args: &[ArgSpec {
+            name: "mode",
+            kind: ArgKind::Choice(THEMES),
+            required: true,
+        }],
+        help: "Switch accessibility mode",
+    },
+    CommandSpec {
+        name: "export",
+        keys: &[],
+        args: &[
+            ArgSpec {
+                name: "format",
+                kind: ArgKind::Choice(EXPORT_FORMATS),
+                required: true,
+            },
+            ArgSpec {
+                name: "path",
+                kind: ArgKind::Word,
+                required: false,
+            },
+        ],
+        help: "Write the current view to a file",
+    },
+];
+
+/// A parsed command, ready for `App::run`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    NextPanel,
+    SelectNext,
+    SelectPrevious,
+    Refresh,
+    RefreshSelected,
+    /// No field cycles to the next one; no order keeps the current one.
+    Sort {
+        field: Option<SortField>,
+        descending: Option<bool>,
+    },
+    /// A sortable column by its position on screen, from 1.
+    SortColumn(usize),
+    ToggleSortOrder,
+    ProblemsFirst,
+    SortByNode,
+    /// No query enters search mode.
+    Filter(Option<String>),
+    Clear,
+    /// No mode toggles.
+    Cpu(Option<CpuMode>),
+    Pin,
+    Mark,
+    Visual,
+    Permissions,
+    Drain,
+    Start,
+    Shutdown,
+    NodePower(NodePower),
+    Console,
+    /// The URL goes to the clipboard unless it is opened.
+    WebConsole(Handoff),
+    Clone,
+    Resources,
+    /// No text opens the note prompt.
+    Note(Option<String>),
+    /// No length opens the snooze popup.
+    Snooze(Option<SnoozeLength>),
+    Wake,
+    Errors,
+    Events,
+    Logs,
+    Debug,
+    Wide,
+    /// No grouping cycles to the next one.
+    Group(Option<GroupBy>),
+    Collapse,
+    /// No window cycles to the next one.
+    Recent(Option<RecentFilter>),
+    Templates,
+    Presets,
+    Providers,
+    Preset(String),
+    Reload,
+    Mute,
+    Help,
+    /// No target opens the prompt that jumps as a VMID or name is typed.
+    Goto(Option<String>),
+    Theme(Accessibility),
+    Export {
+        format: OutputFormat,
+        path: Option<String>,
+    },
+}
+
+impl Action {
+    /// Whether the action can change guests (directly, or through a shell
+    /// on them), so read-only mode refuses it.
+    pub fn mutates(&self) -> bool {
+        matches!(
+            self,
+            Action::Drain
+                | Action::Start
+                | Action::Shutdown
+                | Action::NodePower(_)
+                | Action::Console
+                | Action::WebConsole(_)
+                | Action::Clone
+                | Action::Resources
+        )
+    }
+
+    /// The API privilege the action needs on the provider's token, for the
+    /// ones that go through the API.
+    pub fn privilege(&self) -> Option<&'static str> {
+        match self {
+            Action::Drain => Some("VM.Migrate"),
+            Action::Start | Action::Shutdown => Some("VM.PowerMgmt"),
+            Action::NodePower(_) => Some("Sys.PowerMgmt"),
+            Action::Clone => Some("VM.Clone"),
+            Action::Resources => Some("VM.Config.Memory"),
+            Action::WebConsole(_) => Some("VM.Console"),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a command runs an action that read-only mode refuses.
+pub fn mutates(spec: &CommandSpec) -> bool {
+    parse(spec.name).is_ok_and(|action| action.mutates())
+}
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// Status bar hints per panel, most important first, as a command and a
+/// short label; the rest are in help. Hints that don't fit are dropped from
+/// the end.
+const NODE_HINTS: &[(&str, &str)] = &[
+    ("help", "Help"),
+    ("quit", "Quit"),
+    ("panel", "Panel"),
+    ("drain", "Drain"),
+    ("refresh-selected", "Refresh node"),
+    ("pin", "Pin"),
+    ("problems", "Problems"),
+    ("filter", "Search"),
+    ("sort", "Sort"),
+    ("reboot", "Reboot"),
+];
+const GROUP_HINTS: &[(&str, &str)] = &[
+    ("help", "Help"),
+    ("quit", "Quit"),
+    ("panel", "Panel"),
+    ("down", "Next pool"),
+    ("up", "Previous pool"),
+    ("filter", "Search"),
+    ("refresh", "Refresh"),
+];
+const CONTAINER_HINTS: &[(&str, &str)] = &[
+    ("help", "Help"),
+    ("quit", "Quit"),
+    ("panel", "Panel"),
+    ("console", "Console"),
+    ("refresh-selected", "Refresh guest"),
+    ("clone", "Clone"),
+    ("start", "Start"),
+    ("note", "Note"),
+    ("filter", "Search"),
+    ("pin", "Pin"),
+    ("sort", "Sort"),
+    ("group", "Group"),
+    ("recent", "Recent"),
+    ("by-node", "By node"),
+    ("wide", "Wide"),
+];
+
+/// Hints for a panel as (key, label), the key taken from the command's
+/// bindings so hints follow them. Actions `refused` (in read-only mode, or
+/// without the privilege) are left out.
+pub fn hints(panel: Panel, refused: impl Fn(&Action) -> bool) -> Vec<(&'static str, &'static str)> {
+    let hints = match panel {
+        Panel::Nodes => NODE_HINTS,
+        Panel::Groups => GROUP_HINTS,
+        Panel::Containers => CONTAINER_HINTS,
+    };
+    hints
+        .iter()
+        .filter_map(|&(name, label)| {
+            let spec = find(name)?;
+            if parse(spec.name).is_ok_and(|action| refused(&action)) {
+                return None;
+            }
+            Some((*spec.keys.first()?, label))
+        })
+        .collect()
+}
+
+/// `(command, label)` hints as `(key, label)`, each command shown by its
+/// first key; commands without one are left out.
+pub fn keyed(hints: &[(&'static str, &'static str)]) -> Vec<(&'static str, &'static str)> {
+    hints
+        .iter()
+        .filter_map(|&(name, label)| Some((*find(name)?.keys.first()?, label)))
+        .collect()
+}
+
+/// The command bound to a key, if any.
+pub fn for_key(code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+    let key = key_name(code, modifiers)?;
+    // "1-9" in help: the digit is the argument
+    if let KeyCode::Char(digit @ '1'..='9') = code
+        && !modifiers.contains(KeyModifiers::CONTROL)
+    {
+        return parse(&format!("column {}", digit)).ok();
+    }
+    let spec = COMMANDS.iter().find(|c| c.keys.contains(&key.as_str()))?;
+    parse(spec.name).ok()
+}
+
+fn key_name(code: KeyCode, modifiers: KeyModifiers) -> Option<String> {
+    Some(match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => format!("Ctrl+{}", c),
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => return None,
+    })
+}
+
+/// Parse a palette line such as "sort cpu desc" or "goto 105".
+pub fn parse(input: &str) -> Result<Action, String> {
+    let input = input.trim();
+    let (name, rest) = input.split_once(' ').unwrap_or((input, ""));
+    if name.is_empty() {
+        return Err("type a command, Tab to complete".to_string());
+    }
+    let spec = find(name).ok_or_else(|| format!("unknown command: {}", name))?;
+    let args = split_args(spec, rest.trim())?;
+    let arg = |i: usize| args.get(i).map(String::as_str);
+
+    Ok(match spec.name {
+        "quit" => Action::Quit,
+        "panel" => Action::NextPanel,
+        "down" => Action::SelectNext,
+        "up" => Action::SelectPrevious,
+        "refresh" => Action::Refresh,
+        "refresh-selected" => Action::RefreshSelected,
+        "sort" => Action::Sort {
+            field: arg(0).map(|f| match f {
+                "name" => SortField::Name,
+                "status" => SortField::Status,
+                "cpu" => SortField::Cpu,
+                "memory" => SortField::Memory,
+                "memory-abs" => SortField::MemoryAbs,
+                _ => SortField::Disk,
+            }),
+            descending: arg(1).map(|o| o == "desc"),
+        },
+        "column" => Action::SortColumn(args[0].parse().unwrap_or(1)),
+        "reverse" => Action::ToggleSortOrder,
+        "problems" => Action::ProblemsFirst,
+        "by-node" => Action::SortByNode,
+        "filter" => Action::Filter(arg(0).map(str::to_string)),
+        "clear" => Action::Clear,
+        "cpu" => Action::Cpu(arg(0).map(|m| match m {
+            "guest" => CpuMode::Guest,
+            _ => CpuMode::Host,
+        })),
+        "pin" => Action::Pin,
+        "mark" => Action::Mark,
+        "visual" => Action::Visual,
+        "permissions" => Action::Permissions,
+        "drain" => Action::Drain,
+        "start" => Action::Start,
+        "shutdown" => Action::Shutdown,
+        "reboot" => Action::NodePower(NodePower::Reboot),
+        "poweroff" => Action::NodePower(NodePower::Shutdown),
+        "console" => Action::Console,
+        "webconsole" => Action::WebConsole(match arg(0) {
+            Some("open") => Handoff::Open,
+            _ => Handoff::Copy,
+        }),
+        "clone" => Action::Clone,
+        "resources" => Action::Resources,
+        "note" => Action::Note(arg(0).map(str::to_string)),
+        "snooze" => match arg(0) {
+            Some("off") => Action::Wake,
+            length => Action::Snooze(length.and_then(SnoozeLength::parse)),
+        },
+        "errors" => Action::Errors,
+        "events" => Action::Events,
+        "logs" => Action::Logs,
+        "debug" => Action::Debug,
+        "wide" => Action::Wide,
+        "group" => Action::Group(arg(0).map(|g| match g {
+            "off" => GroupBy::Off,
+            "node" => GroupBy::Node,
+            _ => GroupBy::Prefix,
+        })),
+        "collapse" => Action::Collapse,
+        "recent" => Action::Recent(arg(0).map(|w| match w {
+            "off" => RecentFilter::Off,
+            "15m" => RecentFilter::Minutes15,
+            "1h" => RecentFilter::Hour,
+            _ => RecentFilter::Day,
+        })),
+        "templates" => Action::Templates,
+        "presets" => Action::Presets,
+        "providers" => Action::Providers,
+        "preset" => Action::Preset(args[0].clone()),
+        "reload" => Action::Reload,
+        "mute" => Action::Mute,
+        "help" => Action::Help,
+        "goto" => Action::Goto(arg(0).map(str::to_string)),
+        "theme" => Action::Theme(match args[0].as_str() {
+            "standard" => Accessibility::Standard,
+            "colorblind" => Accessibility::Colorblind,
+            _ => Accessibility::Mono,
+        }),
+        "export" => Action::Export {
+            format: match args[0].as_str() {
+                "table" => OutputFormat::Table,
+                "json" => OutputFormat::Json,
+                _ => OutputFormat::Prometheus,
+            },
+            path: arg(1).map(str::to_string),
+        },
+        other => unreachable!("command {} has no parser", other),
+    })
+}
+
+/// Split and check arguments against the spec: choices must be one of
+/// their values, required arguments present, and nothing left over.
+fn split_args(spec: &CommandSpec, mut rest: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for arg in spec.args {
+        if rest.is_empty() {
+            if arg.required {
+                return Err(format!("{}: missing <{}>", spec.name, arg.name));
+            }
+            break;
+        }
+        if let ArgKind::Rest = arg.kind {
+            args.push(rest.to_string());
+            rest = "";
+            break;
+        }
+        let (word, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        if let ArgKind::Choice(values) = arg.kind
+            && !values.contains(&word)
+        {
+            return Err(format!(
+                "{}: {} must be one of {}",
+                spec.name,
+                arg.name,
+                values.join(", ")
+            ));
+        }
+        args.push(word.to_string());
+        rest = tail.trim_start();
+    }
+    if !rest.is_empty() {
+        return Err(format!("{}: unexpected \"{}\"", spec.name, rest));
+    }
+    Ok(args)
+}
+
+/// Result of pressing Tab in the palette.
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    /// The input with the word under the cursor extended.
+    pub input: String,
+    /// Remaining choices when the word is still ambiguous.
+    pub candidates: Vec<&'static str>,
+}
+
+/// Complete the last word of `input`: a command name, or a value of the
+/// argument being typed. A unique match gets a trailing space.
+pub fn complete(input: &str) -> Completion {
+    let (done, partial) = match input.rfind(' ') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let words: Vec<&str> = done.split_whitespace().collect();
+    let options: Vec<&'static str> = match words.split_first() {
+        None => COMMANDS.iter().map(|c| c.name).collect(),
+        Some((name, args)) => find(name)
+            .and_then(|spec| spec.args.get(args.len()))
+            .map(|arg| match arg.kind {
+                ArgKind::Choice(values) => values.to_vec(),
+                ArgKind::Word | ArgKind::Rest => Vec::new(),
+            })
+            .unwrap_or_default(),
+    };
+    let candidates: Vec<&'static str> = options
+        .into_iter()
+        .filter(|o| o.starts_with(partial))
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Completion {
+            input: input.to_string(),
+            candidates: Vec::new(),
+        },
+        [only] => Completion {
+            input: format!("{}{} ", done, only),
+            candidates: Vec::new(),
+        },
+        _ => Completion {
+            input: format!("{}{}", done, common_prefix(&candidates)),
+            candidates,
+        },
+    }
+}
+
+fn common_prefix<'a>(words: &[&'a str]) -> &'a str {
+    let first = words[0];
+    let len = words.iter().skip(1).fold(first.len(), |len, word| {
+        first
+            .bytes()
+            .zip(word.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    &first[..len]
+}
+
+/// Usage line for help, e.g. "sort [field] [order]".
+pub fn usage(spec: &CommandSpec) -> String {
+    let mut usage = spec.name.to_string();
+    for arg in spec.args {
+        if arg.required {
+            usage.push_str(&format!(" <{}>", arg.name));
+        } else {
+            usage.push_str(&format!(" [{}]", arg.name));
+        }
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints_use_bound_keys() {
+        for hints in [NODE_HINTS, GROUP_HINTS, CONTAINER_HINTS] {
+            for (name, _) in hints {
+                assert!(
+                    find(name).is_some_and(|c| !c.keys.is_empty()),
+                    "hint for {} needs a bound command",
+                    name
+                );
+            }
+        }
+        let nodes = hints(Panel::Nodes, |_| false);
+        assert_eq!(nodes[0], ("?", "Help"));
+        assert!(nodes.contains(&("d", "Drain")));
+        assert!(nodes.contains(&("B", "Reboot")));
+        assert!(!nodes.contains(&("t", "Console")));
+        assert!(hints(Panel::Containers, |_| false).contains(&("t", "Console")));
+
+        // Read-only mode hides the actions
+        assert!(!hints(Panel::Nodes, Action::mutates).contains(&("d", "Drain")));
+        assert!(!hints(Panel::Containers, Action::mutates).contains(&("t", "Console")));
+        assert!(hints(Panel::Containers, Action::mutates).contains(&("p", "Pin")));
+        // A token without VM.Clone hides just that one
+        let containers = hints(Panel::Containers, |a| a.privilege() == Some("VM.Clone"));
+        assert!(!containers.contains(&("C", "Clone")));
+        assert!(containers.contains(&("t", "Console")));
+
+        // Fixed hint lists resolve the same way, leaving out unknown commands
+        assert_eq!(
+            keyed(&[("errors", "Details"), ("no-such-command", "Gone")]),
+            [("E", "Details")]
+        );
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            parse("sort cpu desc"),
+            Ok(Action::Sort {
+                field: Some(SortField::Cpu),
+                descending: Some(true),
+            })
+        );
+        assert_eq!(
+            parse("sort"),
+            Ok(Action::Sort {
+                field: None,
+                descending: None,
+            })
+        );
+        assert_eq!(
+            parse("filter status:stopped node:pve1"),
+            Ok(Action::Filter(Some("status:stopped node:pve1".to_string())))
+        );
+        assert_eq!(
+            parse("  goto 105 "),
+            Ok(Action::Goto(Some("105".to_string())))
+        );
+        assert_eq!(parse("goto"), Ok(Action::Goto(None)));
+        assert_eq!(
+            parse("export json /tmp/pulse.json"),
+            Ok(Action::Export {
+                format: OutputFormat::Json,
+                path: Some("/tmp/pulse.json".to_string()),
+            })
+        );
+        assert_eq!(parse("theme mono"), Ok(Action::Theme(Accessibility::Mono)));
+        assert_eq!(
+            parse("note left stopped on purpose"),
+            Ok(Action::Note(Some("left stopped on purpose".to_string())))
+        );
+        assert_eq!(parse("note"), Ok(Action::Note(None)));
+        assert_eq!(parse("snooze"), Ok(Action::Snooze(None)));
+        assert_eq!(
+            parse("snooze restart"),
+            Ok(Action::Snooze(Some(SnoozeLength::UntilRestart)))
+        );
+        assert_eq!(parse("snooze off"), Ok(Action::Wake));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("frobnicate"),
+            Err("unknown command: frobnicate".to_string())
+        );
+        assert_eq!(parse("preset"), Err("preset: missing <name>".to_string()));
+        assert_eq!(
+            parse("sort size"),
+            Err(
+                "sort: field must be one of name, status, cpu, memory, memory-abs, disk"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse("quit now"),
+            Err("quit: unexpected \"now\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_every_choice_and_key_parses() {
+        for spec in COMMANDS {
+            assert!(parse(spec.name).is_ok() || spec.args.iter().any(|a| a.required));
+            for (i, arg) in spec.args.iter().enumerate() {
+                let ArgKind::Choice(values) = arg.kind else {
+                    continue;
+                };
+                for value in values {
+                    let mut line = spec.name.to_string();
+                    for earlier in &spec.args[..i] {
+                        line.push(' ');
+                        line.push_str(match earlier.kind {
+                            ArgKind::Choice(values) => values[0],
+                            ArgKind::Word | ArgKind::Rest => "x",
+                        });
+                    }
+                    line.push(' ');
+                    line.push_str(value);
+                    assert!(parse(&line).is_ok(), "{}", line);
+                }
+            }
+        }
+        assert_eq!(
+            for_key(KeyCode::Char('R'), KeyModifiers::NONE),
+            Some(Action::RefreshSelected)
+        );
+        assert_eq!(
+            for_key(KeyCode::Down, KeyModifiers::NONE),
+            Some(Action::SelectNext)
+        );
+        assert_eq!(
+            for_key(KeyCode::Char('t'), KeyModifiers::NONE),
+            Some(Action::Console)
+        );
+        assert_eq!(
+            for_key(KeyCode::Char(' '), KeyModifiers::NONE),
+            Some(Action::Mark)
+        );
+        assert_eq!(
+            for_key(KeyCode::Char('3'), KeyModifiers::NONE),
+            Some(Action::SortColumn(3))
+        );
+        assert_eq!(for_key(KeyCode::Char('0'), KeyModifiers::NONE), None);
+        assert_eq!(for_key(KeyCode::Char('y'), KeyModifiers::NONE), None);
+        assert_eq!(
+            for_key(KeyCode::Char('e'), KeyModifiers::CONTROL),
+            Some(Action::Reload)
+        );
+        // Control doesn't fall through to the plain key's command
+        assert_eq!(for_key(KeyCode::Char('c'), KeyModifiers::CONTROL), None);
+    }
+
+    #[test]
+    fn test_complete_command_names() {
+        assert_eq!(
+            complete("so"),
+            Completion {
+                input: "sort ".to_string(),
+                candidates: Vec::new(),
+            }
+        );
+        // "refresh" and "refresh-selected" share a prefix
+        assert_eq!(
+            complete("ref"),
+            Completion {
+                input: "refresh".to_string(),
+                candidates: vec!["refresh", "refresh-selected"],
+            }
+        );
+        assert_eq!(complete("zz").input, "zz");
+    }
+
+    #[test]
+    fn test_complete_arguments() {
+        assert_eq!(complete("sort c").input, "sort cpu ");
+        assert_eq!(complete("sort cpu d").input, "sort cpu desc ");
+        assert_eq!(complete("theme ").candidates, THEMES);
+        // Free text has nothing to offer
+        assert_eq!(complete("goto 10").input, "goto 10");
+    }
+
+    #[test]
+    fn test_usage() {
+        assert_eq!(usage(find("sort").unwrap()), "sort [field] [order]");
+        assert_eq!(usage(find("export").unwrap()), "export <format> [path]");
+    }
+}
@@ -1,29 +1,461 @@
 use serde::Deserialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Debug, Deserialize)]
+use crate::app::Panel;
+use crate::format::{Units, parse_duration};
+use crate::process;
+
+#[derive(Debug, Default, Deserialize)]
 pub struct Config {
     pub general: GeneralConfig,
     pub providers: ProvidersConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub thresholds: ThresholdsConfig,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct GeneralConfig {
     pub refresh_rate: String,
+    /// Refresh less often, up to 4x `refresh_rate`, while nothing changes
+    #[serde(default)]
+    pub adaptive_refresh: bool,
+    /// Spread the providers' refreshes over the interval instead of
+    /// fetching from all of them at once
+    #[serde(default)]
+    pub stagger_refresh: bool,
+    /// Refuse every action that changes guests, in the UI and the providers
+    #[serde(default)]
+    pub read_only: bool,
+    /// Go through every action but only log the API calls it would make
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Where pulse keeps what it saves itself, such as filter presets
+    /// (`~/.local/state/pulse/state.toml` when unset)
+    pub state_file: Option<String>,
+    /// How long a guest note outlives its guest, e.g. "30d" (the default)
+    pub note_retention: Option<String>,
+    /// Tasks a bulk action runs at once (1, one after the other, by default)
+    pub action_concurrency: Option<usize>,
+    /// When two providers report the same machines, show them once, from
+    /// the provider listed first
+    #[serde(default)]
+    pub hide_duplicates: bool,
+    /// Providers to start with when `--provider` names none; the others
+    /// can be turned on from the providers popup
+    pub default_providers: Option<Vec<String>>,
+}
+
+impl GeneralConfig {
+    /// State file path with a leading `~/` expanded; None without a
+    /// configured path or `$HOME`.
+    pub fn state_path(&self) -> Option<PathBuf> {
+        match &self.state_file {
+            Some(path) => Some(expand_home(path)),
+            None => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".local/state/pulse/state.toml")),
+        }
+    }
+
+    /// `note_retention` parsed; 30 days when it is unset.
+    pub fn note_retention(&self) -> Result<Duration, String> {
+        match &self.note_retention {
+            Some(value) => parse_duration(value),
+            None => Ok(Duration::from_secs(30 * 24 * 60 * 60)),
+        }
+    }
+
+    /// `action_concurrency`, 1 when it is unset.
+    pub fn action_concurrency(&self) -> Result<usize, String> {
+        match self.action_concurrency {
+            Some(0) => Err("must be at least 1".to_string()),
+            Some(n) => Ok(n),
+            None => Ok(1),
+        }
+    }
+
+    /// `refresh_rate` parsed; 5s when it is unset (no config file).
+    pub fn refresh_interval(&self) -> Result<Duration, String> {
+        if self.refresh_rate.is_empty() {
+            return Ok(Duration::from_secs(5));
+        }
+        match parse_duration(&self.refresh_rate)? {
+            Duration::ZERO => Err("must be more than 0s".to_string()),
+            interval => Ok(interval),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UiConfig {
+    /// Fixed width of the container name column; computed from the names when unset
+    pub name_width: Option<usize>,
+    #[serde(default)]
+    pub truncate: Truncation,
+    #[serde(default)]
+    pub cpu_mode: CpuMode,
+    #[serde(default)]
+    pub accessibility: Accessibility,
+    /// Splits names into a group prefix for `g` grouping ("-" when unset)
+    pub name_group_separator: Option<String>,
+    /// How many leading name parts form the group (1 when unset)
+    pub name_group_depth: Option<usize>,
+    /// Panels left to right, e.g. ["nodes", "groups", "containers"]
+    pub panels: Option<Vec<Panel>>,
+    /// Start with containers sorted by node first (`n` toggles)
+    #[serde(default)]
+    pub group_sort_by_node: bool,
+    /// Start with template guests hidden (`T` toggles)
+    #[serde(default)]
+    pub hide_templates: bool,
+    /// Wall clock in the header and on the last refresh
+    #[serde(default)]
+    pub clock: Clock,
+    /// Keep the terminal title to a health summary (on when unset)
+    pub terminal_title: Option<bool>,
+    /// Pin a totals row under the nodes and containers lists (on when unset)
+    pub summary_row: Option<bool>,
+    /// Break the panel title counts down by status
+    #[serde(default)]
+    pub detailed_counts: bool,
+    /// On Enter, focus the other panel when only it has search matches (on when unset)
+    pub search_switches_panel: Option<bool>,
+    /// Show guest disk usage as a column (always shown while sorting by disk)
+    #[serde(default)]
+    pub disk_column: bool,
+    /// "binary" (KiB, MiB) or "decimal" (kB, MB); unset keeps KB, MB in powers of 1024
+    #[serde(default)]
+    pub units: Units,
+}
+
+/// Time zone of the header clock and refresh timestamp, or none of either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Clock {
+    Local,
+    Utc,
+    #[default]
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    #[default]
+    End,
+    Middle,
+}
+
+/// What guest CPU percentages are relative to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuMode {
+    /// Percent of the cores allocated to the guest, as Proxmox reports it
+    #[default]
+    Guest,
+    /// Percent of all cores on the host
+    Host,
+}
+
+impl CpuMode {
+    pub fn toggle(self) -> Self {
+        match self {
+            CpuMode::Guest => CpuMode::Host,
+            CpuMode::Host => CpuMode::Guest,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CpuMode::Guest => "guest",
+            CpuMode::Host => "host",
+        }
+    }
+}
+
+/// How state is told apart on screen, for users who can't rely on red/green.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Accessibility {
+    #[default]
+    Standard,
+    /// Blue/orange instead of green/red, and a distinct glyph for each state
+    Colorblind,
+    /// No color at all; state is shown by glyphs and reverse video
+    Mono,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct HistoryConfig {
+    /// SQLite file to record samples to; history is off when unset
+    pub database: Option<String>,
+    /// How long samples are kept, e.g. "7d"
+    #[serde(default = "default_retention")]
+    #[cfg_attr(not(feature = "history"), allow(dead_code))]
+    pub retention: String,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            database: None,
+            retention: default_retention(),
+        }
+    }
+}
+
+fn default_retention() -> String {
+    "7d".to_string()
+}
+
+impl HistoryConfig {
+    /// Database path with a leading `~/` expanded to `$HOME`.
+    pub fn database_path(&self) -> Option<PathBuf> {
+        self.database.as_deref().map(expand_home)
+    }
+}
+
+/// Usage (percent) at which an item counts as a problem for `!`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ThresholdsConfig {
+    #[serde(default = "default_critical")]
+    pub cpu_critical: f64,
+    #[serde(default = "default_critical")]
+    pub memory_critical: f64,
+    #[serde(default = "default_critical")]
+    pub disk_critical: f64,
+    /// Allocated over available memory or cores on a node above which the
+    /// node details show the ratio in yellow, and above which in red.
+    #[serde(default = "default_overcommit_warning")]
+    pub overcommit_warning: f64,
+    #[serde(default = "default_overcommit_critical")]
+    pub overcommit_critical: f64,
+}
+
+impl Default for ThresholdsConfig {
+    fn default() -> Self {
+        ThresholdsConfig {
+            cpu_critical: default_critical(),
+            memory_critical: default_critical(),
+            disk_critical: default_critical(),
+            overcommit_warning: default_overcommit_warning(),
+            overcommit_critical: default_overcommit_critical(),
+        }
+    }
+}
+
+/// Where gauges turn red.
+fn default_critical() -> f64 {
+    90.0
+}
+
+/// More promised than the node has.
+fn default_overcommit_warning() -> f64 {
+    1.0
+}
+
+fn default_overcommit_critical() -> f64 {
+    1.5
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditConfig {
+    /// JSON-lines file that actions taken through pulse are appended to
+    pub path: Option<String>,
+}
+
+impl AuditConfig {
+    /// Log path with a leading `~/` expanded to `$HOME`.
+    pub fn log_path(&self) -> Option<PathBuf> {
+        self.path.as_deref().map(expand_home)
+    }
+}
+
+/// Getting attention when something critical happens, for displays nobody
+/// is reading all the time.
+#[derive(Debug, Default, Deserialize)]
+pub struct AlertsConfig {
+    /// Ring the terminal bell when a critical condition appears
+    #[serde(default)]
+    pub terminal_bell: bool,
+    /// Invert the header colors briefly when a critical condition appears
+    #[serde(default)]
+    pub visual_flash: bool,
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct ProvidersConfig {
     pub proxmox: Option<Vec<ProxmoxConfig>>,
+    pub systemd: Option<Vec<SystemdConfig>>,
+    pub local: Option<Vec<LocalConfig>>,
+    pub pulse_agent: Option<Vec<PulseAgentConfig>>,
+    pub command: Option<Vec<CommandConfig>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct ProxmoxConfig {
     pub name: String,
+    #[serde(default)]
     pub host: String,
+    /// More API endpoints of the same cluster, tried in order when the ones
+    /// before them can't be reached
+    #[serde(default)]
+    pub hosts: Vec<String>,
     pub user: String,
     pub token_id: String,
-    pub token_secret: String,
+    pub token_secret: Option<String>,
+    /// Shell command printing the secret (e.g. `pass show pve/token`), instead of `token_secret`
+    pub token_secret_cmd: Option<String>,
+    /// Maximum API requests per refresh window; detail fetches beyond it are deferred
+    pub max_requests: Option<u32>,
+    /// Proxy for the API: http://, https://, socks5:// or socks5h:// URL
+    pub proxy: Option<String>,
+    /// Reach the API through `ssh -D` to this host (e.g. "admin@bastion")
+    pub ssh_jump: Option<String>,
+    #[serde(default)]
+    pub console: ConsoleConfig,
+    /// Set from `[general] read_only` or `--read-only`, not per provider
+    #[serde(skip)]
+    pub read_only: bool,
+    /// Set from `[general] dry_run` or `--dry-run`, not per provider
+    #[serde(skip)]
+    pub dry_run: bool,
+}
+
+/// Commands opening a guest's console (`t`), with `{node}`, `{vmid}` and
+/// `{name}` filled in, e.g. "ssh root@{node} -t pct enter {vmid}".
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ConsoleConfig {
+    pub lxc: Option<String>,
+    pub vm: Option<String>,
+}
+
+/// How long `token_secret_cmd` may take, e.g. waiting on a password prompt.
+const SECRET_CMD_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl ProxmoxConfig {
+    /// API endpoints in order of preference: `host`, then `hosts`.
+    pub fn endpoints(&self) -> Result<Vec<String>, String> {
+        let endpoints: Vec<String> = std::iter::once(&self.host)
+            .chain(&self.hosts)
+            .filter(|host| !host.is_empty())
+            .cloned()
+            .collect();
+        if endpoints.is_empty() {
+            return Err("host or hosts is required".to_string());
+        }
+        Ok(endpoints)
+    }
+
+    /// The token secret, from the config or from `token_secret_cmd`. Errors
+    /// never include the command's output, only its stderr.
+    pub fn resolve_token_secret(&self) -> Result<String, String> {
+        match (&self.token_secret, &self.token_secret_cmd) {
+            (Some(secret), None) => Ok(secret.clone()),
+            (None, Some(command)) => run_secret_command(command, SECRET_CMD_TIMEOUT),
+            (Some(_), Some(_)) => {
+                Err("set either token_secret or token_secret_cmd, not both".to_string())
+            }
+            (None, None) => Err("token_secret or token_secret_cmd is required".to_string()),
+        }
+    }
+}
+
+/// Run `command` through the shell and return its trimmed stdout.
+fn run_secret_command(command: &str, timeout: Duration) -> Result<String, String> {
+    let process::Output {
+        status,
+        stdout,
+        stderr,
+    } = process::run(command, timeout).map_err(|e| format!("token_secret_cmd {}", e))?;
+    if !status.success() {
+        let stderr = stderr.trim();
+        return Err(if stderr.is_empty() {
+            format!("token_secret_cmd failed ({})", status)
+        } else {
+            format!("token_secret_cmd failed ({}): {}", status, stderr)
+        });
+    }
+    let secret = stdout.trim();
+    if secret.is_empty() {
+        return Err("token_secret_cmd printed nothing".to_string());
+    }
+    Ok(secret.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SystemdConfig {
+    pub name: String,
+    /// Unit names ("nginx", "wg-quick@wg0.service") or globs ("docker-*")
+    pub units: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LocalConfig {
+    #[serde(default = "default_local_name")]
+    pub name: String,
+    /// Show this many of the busiest processes as containers; none when unset
+    pub top_processes: Option<usize>,
+}
+
+impl Default for LocalConfig {
+    fn default() -> Self {
+        LocalConfig {
+            name: default_local_name(),
+            top_processes: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PulseAgentConfig {
+    pub name: String,
+    /// Where `pulse agent` listens, e.g. "http://nas:9898"
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CommandConfig {
+    pub name: String,
+    /// Shell command printing a snapshot as JSON, run every refresh
+    pub command: String,
+    /// How long the command may run, e.g. "10s" (default 10s)
+    pub timeout: Option<String>,
+}
+
+/// How long a command provider's command runs before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl CommandConfig {
+    /// `timeout` parsed; 10 seconds when it is unset.
+    pub fn timeout(&self) -> Result<Duration, String> {
+        match &self.timeout {
+            Some(value) => match parse_duration(value)? {
+                Duration::ZERO => Err("must be more than 0s".to_string()),
+                timeout => Ok(timeout),
+            },
+            None => Ok(COMMAND_TIMEOUT),
+        }
+    }
+}
+
+fn default_local_name() -> String {
+    "local".to_string()
 }
 
 pub fn load(path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
@@ -35,6 +467,7 @@ pub fn load(path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
 
     #[test]
     fn test_parse_full_config() {
@@ -63,7 +496,64 @@ token_secret = "12345678-1234-1234-1234-123456789012"
         assert_eq!(proxmox.host, "https://192.168.1.100:8006");
         assert_eq!(proxmox.user, "root@pam");
         assert_eq!(proxmox.token_id, "root@pam!test-token");
-        assert_eq!(proxmox.token_secret, "12345678-1234-1234-1234-123456789012");
+        assert_eq!(
+            proxmox.resolve_token_secret().unwrap(),
+            "12345678-1234-1234-1234-123456789012"
+        );
+        assert!(proxmox.max_requests.is_none());
+    }
+
+    #[test]
+    fn test_parse_max_requests() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[[providers.proxmox]]
+name = "Test Server"
+host = "https://192.168.1.100:8006"
+user = "root@pam"
+token_id = "root@pam!test-token"
+token_secret = "secret"
+max_requests = 20
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let proxmox_configs = config.providers.proxmox.unwrap();
+        assert_eq!(proxmox_configs[0].max_requests, Some(20));
+    }
+
+    #[test]
+    fn test_parse_proxy_options() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[[providers.proxmox]]
+name = "Office"
+host = "https://10.0.0.2:8006"
+user = "root@pam"
+token_id = "root@pam!pulse"
+token_secret = "secret"
+proxy = "socks5h://127.0.0.1:1080"
+
+[[providers.proxmox]]
+name = "Colo"
+host = "https://pve.colo.internal:8006"
+user = "root@pam"
+token_id = "root@pam!pulse"
+token_secret = "secret"
+ssh_jump = "admin@bastion"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let proxmox = config.providers.proxmox.unwrap();
+        assert_eq!(
+            proxmox[0].proxy.as_deref(),
+            Some("socks5h://127.0.0.1:1080")
+        );
+        assert!(proxmox[0].ssh_jump.is_none());
+        assert_eq!(proxmox[1].ssh_jump.as_deref(), Some("admin@bastion"));
     }
 
     #[test]
@@ -108,6 +598,365 @@ refresh_rate = "5s"
         assert!(config.providers.proxmox.is_none());
     }
 
+    #[test]
+    fn test_parse_systemd_provider() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[[providers.systemd]]
+name = "local"
+units = ["nginx", "wg-quick@wg0.service", "smbd*"]
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.providers.proxmox.is_none());
+        let systemd = config.providers.systemd.unwrap();
+        assert_eq!(systemd[0].name, "local");
+        assert_eq!(systemd[0].units, ["nginx", "wg-quick@wg0.service", "smbd*"]);
+    }
+
+    #[test]
+    fn test_parse_local_provider() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[[providers.local]]
+
+[[providers.local]]
+name = "laptop"
+top_processes = 5
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let local = config.providers.local.unwrap();
+        assert_eq!(local[0].name, "local");
+        assert_eq!(local[0].top_processes, None);
+        assert_eq!(local[1].name, "laptop");
+        assert_eq!(local[1].top_processes, Some(5));
+    }
+
+    #[test]
+    fn test_parse_ui_section() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+
+[ui]
+name_width = 24
+truncate = "middle"
+cpu_mode = "host"
+accessibility = "colorblind"
+name_group_separator = "."
+name_group_depth = 2
+group_sort_by_node = true
+clock = "utc"
+terminal_title = false
+detailed_counts = true
+search_switches_panel = false
+units = "decimal"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.ui.name_width, Some(24));
+        assert_eq!(config.ui.truncate, Truncation::Middle);
+        assert_eq!(config.ui.cpu_mode, CpuMode::Host);
+        assert_eq!(config.ui.accessibility, Accessibility::Colorblind);
+        assert_eq!(config.ui.name_group_separator.as_deref(), Some("."));
+        assert_eq!(config.ui.name_group_depth, Some(2));
+        assert!(config.ui.group_sort_by_node);
+        assert_eq!(config.ui.clock, Clock::Utc);
+        assert_eq!(config.ui.terminal_title, Some(false));
+        assert!(config.ui.detailed_counts);
+        assert_eq!(config.ui.search_switches_panel, Some(false));
+        assert_eq!(config.ui.units, Units::Decimal);
+    }
+
+    #[test]
+    fn test_ui_section_defaults() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.ui.name_width.is_none());
+        assert_eq!(config.ui.truncate, Truncation::End);
+        assert_eq!(config.ui.cpu_mode, CpuMode::Guest);
+        assert_eq!(config.ui.accessibility, Accessibility::Standard);
+        assert!(config.ui.name_group_separator.is_none());
+        assert!(!config.ui.group_sort_by_node);
+        assert_eq!(config.ui.clock, Clock::Off);
+        assert!(config.ui.terminal_title.is_none());
+        assert!(!config.ui.detailed_counts);
+        assert!(config.ui.search_switches_panel.is_none());
+        assert_eq!(config.ui.units, Units::Short);
+    }
+
+    #[test]
+    fn test_parse_read_only() {
+        let config: Config =
+            toml::from_str("[general]\nrefresh_rate = \"5s\"\nread_only = true\n[providers]\n")
+                .unwrap();
+        assert!(config.general.read_only);
+
+        let config: Config =
+            toml::from_str("[general]\nrefresh_rate = \"5s\"\n[providers]\n").unwrap();
+        assert!(!config.general.read_only);
+    }
+
+    #[test]
+    fn test_parse_thresholds_section() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+
+[thresholds]
+cpu_critical = 80
+overcommit_critical = 2.0
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.thresholds.cpu_critical, 80.0);
+        assert_eq!(config.thresholds.memory_critical, 90.0);
+        assert_eq!(config.thresholds.overcommit_warning, 1.0);
+        assert_eq!(config.thresholds.overcommit_critical, 2.0);
+
+        let config: Config =
+            toml::from_str("[general]\nrefresh_rate = \"5s\"\n[providers]\n").unwrap();
+        assert_eq!(config.thresholds, ThresholdsConfig::default());
+    }
+
+    #[test]
+    fn test_parse_panels() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+
+[ui]
+panels = ["nodes", "groups", "containers"]
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.ui.panels,
+            Some(vec![Panel::Nodes, Panel::Groups, Panel::Containers])
+        );
+
+        let bad = "[general]\nrefresh_rate = \"5s\"\n[providers]\n[ui]\npanels = [\"pools\"]\n";
+        assert!(toml::from_str::<Config>(bad).is_err());
+    }
+
+    #[test]
+    fn test_parse_history_section() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+
+[history]
+database = "/var/lib/pulse/history.db"
+retention = "30d"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.history.database_path(),
+            Some(PathBuf::from("/var/lib/pulse/history.db"))
+        );
+        assert_eq!(config.history.retention, "30d");
+    }
+
+    #[test]
+    fn test_history_section_defaults() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.history.database_path().is_none());
+        assert_eq!(config.history.retention, "7d");
+    }
+
+    #[test]
+    fn test_audit_section() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+
+[audit]
+path = "/var/log/pulse/audit.jsonl"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.audit.log_path(),
+            Some(PathBuf::from("/var/log/pulse/audit.jsonl"))
+        );
+        assert!(!config.alerts.terminal_bell && !config.alerts.visual_flash);
+    }
+
+    #[test]
+    fn test_alerts_section() {
+        let toml_str = r#"
+[general]
+refresh_rate = "5s"
+
+[providers]
+
+[alerts]
+terminal_bell = true
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.alerts.terminal_bell);
+        assert!(!config.alerts.visual_flash);
+    }
+
+    #[test]
+    fn test_refresh_interval() {
+        let general = |rate: &str| GeneralConfig {
+            refresh_rate: rate.to_string(),
+            ..GeneralConfig::default()
+        };
+        assert_eq!(
+            general("10s").refresh_interval(),
+            Ok(Duration::from_secs(10))
+        );
+        assert_eq!(general("").refresh_interval(), Ok(Duration::from_secs(5)));
+        assert_eq!(
+            general("0s").refresh_interval(),
+            Err("must be more than 0s".to_string())
+        );
+        assert!(general("often").refresh_interval().is_err());
+    }
+
+    #[test]
+    fn test_action_concurrency() {
+        let general = |n: Option<usize>| GeneralConfig {
+            action_concurrency: n,
+            ..GeneralConfig::default()
+        };
+        assert_eq!(general(None).action_concurrency(), Ok(1));
+        assert_eq!(general(Some(3)).action_concurrency(), Ok(3));
+        assert_eq!(
+            general(Some(0)).action_concurrency(),
+            Err("must be at least 1".to_string())
+        );
+    }
+
+    fn proxmox_with(secret: Option<&str>, command: Option<&str>) -> ProxmoxConfig {
+        ProxmoxConfig {
+            name: "pve".to_string(),
+            host: "https://pve:8006".to_string(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: secret.map(str::to_string),
+            token_secret_cmd: command.map(str::to_string),
+            max_requests: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            read_only: false,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_endpoints_from_host_and_hosts() {
+        let toml_str = r#"
+name = "cluster"
+hosts = ["https://pve1:8006", "https://pve2:8006"]
+user = "root@pam"
+token_id = "root@pam!pulse"
+token_secret = "secret"
+"#;
+        let config: ProxmoxConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.endpoints().unwrap(),
+            ["https://pve1:8006", "https://pve2:8006"]
+        );
+
+        let config = ProxmoxConfig {
+            hosts: vec!["https://pve2:8006".to_string()],
+            ..proxmox_with(Some("a"), None)
+        };
+        assert_eq!(
+            config.endpoints().unwrap(),
+            ["https://pve:8006", "https://pve2:8006"]
+        );
+
+        let config = ProxmoxConfig {
+            host: String::new(),
+            ..proxmox_with(Some("a"), None)
+        };
+        assert_eq!(config.endpoints().unwrap_err(), "host or hosts is required");
+    }
+
+    #[test]
+    fn test_token_secret_needs_exactly_one_source() {
+        assert_eq!(
+            proxmox_with(Some("a"), Some("printf b"))
+                .resolve_token_secret()
+                .unwrap_err(),
+            "set either token_secret or token_secret_cmd, not both"
+        );
+        assert_eq!(
+            proxmox_with(None, None).resolve_token_secret().unwrap_err(),
+            "token_secret or token_secret_cmd is required"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_token_secret_from_command() {
+        let config = proxmox_with(None, Some("printf '  s3cret\\n'"));
+        assert_eq!(config.resolve_token_secret().unwrap(), "s3cret");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failing_secret_command_reports_stderr_only() {
+        let error = run_secret_command(
+            "printf leaked; printf 'entry not found' >&2; exit 3",
+            Duration::from_secs(5),
+        )
+        .unwrap_err();
+        assert!(error.contains("entry not found"), "{}", error);
+        assert!(error.contains("exit status: 3"), "{}", error);
+        assert!(!error.contains("leaked"), "{}", error);
+
+        assert_eq!(
+            run_secret_command("true", Duration::from_secs(5)).unwrap_err(),
+            "token_secret_cmd printed nothing"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hung_secret_command_times_out() {
+        let started = Instant::now();
+        let error = run_secret_command("sleep 10", Duration::from_millis(200)).unwrap_err();
+        assert_eq!(error, "token_secret_cmd timed out after 200ms");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
     #[test]
     fn test_parse_missing_field_fails() {
         let toml_str = r#"
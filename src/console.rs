@@ -0,0 +1,88 @@
+//! Console command templates such as `ssh root@{node} -t pct enter {vmid}`.
+//! A template is split into words first and the placeholders filled in per
+//! word, so no shell is involved and odd guest names stay one argument.
+
+use crate::models::Container;
+
+const PLACEHOLDERS: [&str; 3] = ["node", "vmid", "name"];
+
+/// Check that a template is non-empty and only uses known placeholders.
+pub fn validate(template: &str) -> Result<(), String> {
+    if template.split_whitespace().next().is_none() {
+        return Err("console command is empty".to_string());
+    }
+    for word in template.split_whitespace() {
+        placeholders(word)?;
+    }
+    Ok(())
+}
+
+/// The command line for a guest: program first, then its arguments.
+pub fn render(template: &str, container: &Container) -> Result<Vec<String>, String> {
+    validate(template)?;
+    Ok(template
+        .split_whitespace()
+        .map(|word| {
+            word.replace("{node}", &container.node)
+                .replace("{vmid}", &container.vmid.to_string())
+                .replace("{name}", &container.name)
+        })
+        .collect())
+}
+
+/// The placeholders in one word, or why it is malformed.
+fn placeholders(word: &str) -> Result<Vec<&str>, String> {
+    let mut found = Vec::new();
+    let mut rest = word;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err(format!("unclosed '{{' in console command word '{}'", word));
+        };
+        let name = &rest[open + 1..open + close];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder {{{}}} in console command (use {{node}}, {{vmid}} or {{name}})",
+                name
+            ));
+        }
+        found.push(name);
+        rest = &rest[open + close + 1..];
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest() -> Container {
+        Container::test("homelab", 105, "media server", "pve2")
+    }
+
+    #[test]
+    fn test_render_fills_placeholders_per_word() {
+        assert_eq!(
+            render("ssh root@{node} -t pct enter {vmid}", &guest()).unwrap(),
+            ["ssh", "root@pve2", "-t", "pct", "enter", "105"]
+        );
+        // The name's space doesn't split it into two arguments
+        assert_eq!(
+            render("echo {name}", &guest()).unwrap(),
+            ["echo", "media server"]
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_templates() {
+        assert_eq!(
+            validate("ssh root@{host} pct enter {vmid}").unwrap_err(),
+            "unknown placeholder {host} in console command (use {node}, {vmid} or {name})"
+        );
+        assert_eq!(
+            validate("qm terminal {vmid").unwrap_err(),
+            "unclosed '{' in console command word '{vmid'"
+        );
+        assert_eq!(validate("  ").unwrap_err(), "console command is empty");
+        assert!(validate("ssh root@{node} -t qm terminal {vmid}").is_ok());
+    }
+}
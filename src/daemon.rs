@@ -0,0 +1,785 @@
+//! `pulse daemon`: refreshes the configured providers on its own and serves
+//! what it fetched over a unix socket (see `protocol`), so any number of
+//! `pulse attach` UIs can show it without each polling the API. Actions an
+//! attached UI takes are run here, on the daemon's providers.
+//!
+//! The socket is taken from systemd when the daemon is socket-activated,
+//! and bound at `--socket` otherwise. One thread accepts connections and
+//! one per client reads its lines; everything else, providers included,
+//! runs on the main loop, so a slow provider call holds up the others the
+//! same way it does in a single UI.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use crate::app::CLUSTER_LOG_REFRESH_INTERVAL;
+use crate::backoff::Backoff;
+use crate::config::{self, Config};
+use crate::events::CLUSTER_LOG_ENTRIES;
+use crate::models::ClusterLogEntry;
+use crate::protocol::{
+    self, ClientMessage, PROTOCOL_VERSION, ProviderInfo, ProviderSnapshot, Reply, Request,
+    ServerMessage,
+};
+use crate::providers::Provider;
+use crate::reload::{ProviderSet, Selection};
+
+/// How long a write to a client may block before it is dropped: one that
+/// stops reading must not stall the others.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The first descriptor systemd passes a socket-activated service.
+const LISTEN_FDS_START: i32 = 3;
+
+/// `$XDG_RUNTIME_DIR/pulse.sock`, or one per user in the temp directory
+/// where there is no runtime directory.
+pub fn default_socket() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("pulse.sock"),
+        _ => {
+            let user = std::env::var("USER").unwrap_or_else(|_| "pulse".to_string());
+            std::env::temp_dir().join(format!("pulse-{}.sock", user))
+        }
+    }
+}
+
+/// The socket systemd passed us, if we were socket-activated.
+fn activated_listener() -> Option<UnixListener> {
+    use std::os::fd::FromRawFd;
+
+    let pid = std::env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    let fds = std::env::var("LISTEN_FDS").ok()?.parse::<u32>().ok()?;
+    if pid != std::process::id() || fds == 0 {
+        return None;
+    }
+    // SAFETY: systemd passes the listening socket as descriptor 3 when
+    // LISTEN_PID names this process, and nothing else in pulse has opened
+    // or taken ownership of it
+    Some(unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) })
+}
+
+/// Bind `path`, replacing a socket left behind by a daemon that is gone but
+/// never one still answering on it. Only the owner may connect: whoever
+/// can, can act with the daemon's credentials.
+fn bind(path: &Path) -> Result<UnixListener, String> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!(
+                "another daemon is already listening on {}",
+                path.display()
+            ));
+        }
+        std::fs::remove_file(path)
+            .map_err(|e| format!("cannot remove stale {}: {}", path.display(), e))?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| format!("cannot listen on {}: {}", path.display(), e))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("cannot restrict {}: {}", path.display(), e))?;
+    Ok(listener)
+}
+
+/// What the reading threads hand the main loop.
+enum Incoming {
+    Connected(u64, UnixStream),
+    Line(u64, String),
+    Closed(u64),
+}
+
+fn accept(listener: UnixListener, incoming: Sender<Incoming>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("daemon: cannot accept a client: {}", e);
+                continue;
+            }
+        };
+        let id = id as u64;
+        let Ok(reader) = stream.try_clone() else {
+            continue;
+        };
+        if incoming.send(Incoming::Connected(id, stream)).is_err() {
+            return;
+        }
+        let incoming = incoming.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else { break };
+                if incoming.send(Incoming::Line(id, line)).is_err() {
+                    return;
+                }
+            }
+            let _ = incoming.send(Incoming::Closed(id));
+        });
+    }
+}
+
+/// Where a client's conversation stands.
+#[derive(Debug, Default)]
+struct Session {
+    /// Said hello in our version; only then is it sent snapshots.
+    greeted: bool,
+    /// Hang up once the answer is sent.
+    closing: bool,
+}
+
+struct Client {
+    stream: UnixStream,
+    session: Session,
+}
+
+impl Client {
+    fn send(&mut self, message: &ServerMessage) -> bool {
+        self.stream
+            .write_all(protocol::encode(message).as_bytes())
+            .is_ok()
+    }
+}
+
+/// The providers and what was last fetched from them.
+struct Daemon {
+    providers: ProviderSet,
+    read_only: bool,
+    dry_run: bool,
+    backoff: HashMap<String, Backoff>,
+    snapshot: Vec<ProviderSnapshot>,
+    /// Each provider's log entries sent so far, newest first.
+    logs: HashMap<String, Vec<ClusterLogEntry>>,
+}
+
+impl Daemon {
+    fn new(providers: ProviderSet, read_only: bool, dry_run: bool) -> Self {
+        Daemon {
+            providers,
+            read_only,
+            dry_run,
+            backoff: HashMap::new(),
+            snapshot: Vec::new(),
+            logs: HashMap::new(),
+        }
+    }
+
+    fn hello(&self) -> ServerMessage {
+        ServerMessage::Hello {
+            version: PROTOCOL_VERSION,
+            providers: self
+                .providers
+                .iter()
+                .map(|p| ProviderInfo {
+                    name: p.name().to_string(),
+                    user: p.user().map(str::to_string),
+                })
+                .collect(),
+            read_only: self.read_only,
+            dry_run: self.dry_run,
+        }
+    }
+
+    /// Everything a client is sent once it said hello: what it would have
+    /// seen had it been attached all along.
+    fn greeting(&self) -> Vec<ServerMessage> {
+        let mut messages = vec![
+            self.hello(),
+            ServerMessage::Snapshot {
+                providers: self.snapshot.clone(),
+            },
+        ];
+        for provider in self.providers.iter() {
+            if let Some(entries) = self.logs.get(provider.name()) {
+                messages.push(ServerMessage::Events {
+                    provider: provider.name().to_string(),
+                    entries: entries.clone(),
+                });
+            }
+        }
+        messages
+    }
+
+    /// Fetch from every provider not backing off; the others keep what
+    /// they returned last.
+    fn refresh(&mut self, now: Instant) {
+        self.snapshot
+            .retain(|s| self.providers.iter().any(|p| p.name() == s.name));
+        for provider in self.providers.iter() {
+            let name = provider.name();
+            let backoff = self.backoff.entry(name.to_string()).or_default();
+            if !backoff.is_due(now) {
+                continue;
+            }
+            provider.begin_refresh();
+            let fetched = ProviderSnapshot {
+                name: name.to_string(),
+                nodes: provider.fetch_nodes().map_err(|e| e.to_string()),
+                containers: provider.fetch_containers().map_err(|e| e.to_string()),
+                cluster: provider.fetch_cluster_status().map_err(|e| e.to_string()),
+            };
+            let errors: Vec<String> = [
+                fetched.nodes.as_ref().err(),
+                fetched.containers.as_ref().err(),
+                fetched.cluster.as_ref().err(),
+            ]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+            if errors.is_empty() {
+                backoff.record_success();
+            } else {
+                // Logged once when a provider goes down, not on every retry
+                if backoff.failures() == 0 {
+                    log::warn!("daemon: {}: {}", name, errors.join("; "));
+                }
+                backoff.record_failure(now);
+            }
+            match self.snapshot.iter_mut().find(|s| s.name == name) {
+                Some(previous) => *previous = fetched,
+                None => self.snapshot.push(fetched),
+            }
+        }
+        // In provider order, as an attached UI lists them
+        let order: Vec<&str> = self.providers.iter().map(|p| p.name()).collect();
+        self.snapshot
+            .sort_by_key(|s| order.iter().position(|name| *name == s.name));
+    }
+
+    /// Fetch the providers' logs and return the entries not sent before.
+    fn fetch_logs(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        for provider in self.providers.iter() {
+            let name = provider.name();
+            if self.backoff.get(name).is_some_and(|b| b.failures() > 0) {
+                continue;
+            }
+            let entries = match provider.fetch_cluster_log(CLUSTER_LOG_ENTRIES) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("daemon: {}: error fetching cluster log: {}", name, e);
+                    continue;
+                }
+            };
+            let sent = self.logs.entry(name.to_string()).or_default();
+            let new: Vec<ClusterLogEntry> = entries
+                .into_iter()
+                .filter(|e| !sent.iter().any(|s| s.uid == e.uid && s.time == e.time))
+                .collect();
+            if new.is_empty() {
+                continue;
+            }
+            sent.splice(0..0, new.iter().cloned());
+            sent.truncate(CLUSTER_LOG_ENTRIES);
+            messages.push(ServerMessage::Events {
+                provider: name.to_string(),
+                entries: new,
+            });
+        }
+        self.logs
+            .retain(|name, _| self.providers.iter().any(|p| p.name() == name));
+        messages
+    }
+
+    /// What to send back for one line from a client.
+    fn answer(&self, line: &str, session: &mut Session) -> Vec<ServerMessage> {
+        let message = match protocol::decode::<ClientMessage>(line) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                // Answered if it has an id, so the client isn't left waiting
+                let id = serde_json::from_str::<serde_json::Value>(line)
+                    .ok()
+                    .and_then(|v| v.get("id")?.as_u64());
+                return id
+                    .map(|id| ServerMessage::Reply {
+                        id,
+                        result: Err(format!("cannot read request: {}", e)),
+                    })
+                    .into_iter()
+                    .collect();
+            }
+        };
+        match message {
+            ClientMessage::Hello { version } if version != PROTOCOL_VERSION => {
+                session.closing = true;
+                vec![ServerMessage::Refused {
+                    reason: format!(
+                        "the client speaks protocol v{}, this daemon v{}",
+                        version, PROTOCOL_VERSION
+                    ),
+                }]
+            }
+            ClientMessage::Hello { .. } => {
+                session.greeted = true;
+                self.greeting()
+            }
+            ClientMessage::Request { id, .. } if !session.greeted => vec![ServerMessage::Reply {
+                id,
+                result: Err("hello first".to_string()),
+            }],
+            ClientMessage::Request {
+                id,
+                provider,
+                request,
+            } => {
+                let result = match self.providers.iter().find(|p| p.name() == provider) {
+                    Some(provider) => call(provider.as_ref(), request).map_err(|e| e.to_string()),
+                    None => Err(format!("the daemon has no provider named {}", provider)),
+                };
+                vec![ServerMessage::Reply { id, result }]
+            }
+        }
+    }
+}
+
+/// Run `request` on `provider`.
+fn call(provider: &dyn Provider, request: Request) -> Result<Reply, Box<dyn std::error::Error>> {
+    Ok(match request {
+        Request::ProbePermissions => Reply::Issues(provider.probe_permissions()?),
+        Request::ProbeActions => Reply::Issues(provider.probe_actions()?),
+        Request::FetchReplication => Reply::Replication(provider.fetch_replication()?),
+        Request::FetchHaResources => Reply::HaResources(provider.fetch_ha_resources()?),
+        Request::FetchGuestDetails { guest } => {
+            Reply::GuestDetails(provider.fetch_guest_details(&guest)?)
+        }
+        Request::FetchGuestResources { guest } => {
+            Reply::GuestResources(provider.fetch_guest_resources(&guest)?)
+        }
+        Request::NextVmid => Reply::Vmid(provider.next_vmid()?),
+        Request::MigrateGuest { guest, target } => {
+            Reply::Text(provider.migrate_guest(&guest, &target)?)
+        }
+        Request::StartGuest { guest } => Reply::Text(provider.start_guest(&guest)?),
+        Request::ShutdownGuest { guest } => Reply::Text(provider.shutdown_guest(&guest)?),
+        Request::CloneGuest { guest, request } => {
+            Reply::Text(provider.clone_guest(&guest, &request)?)
+        }
+        Request::SetGuestResources { guest, request } => {
+            Reply::Text(provider.set_guest_resources(&guest, &request)?)
+        }
+        Request::NodePower { node, command } => Reply::Text(provider.node_power(&node, command)?),
+        Request::WebConsoleUrl { guest } => Reply::Text(provider.web_console_url(&guest)?),
+        Request::TaskStatus { node, task } => {
+            Reply::TaskStatus(provider.task_status(&node, &task)?)
+        }
+    })
+}
+
+/// Send `message` to every client that said hello, dropping those that
+/// can't take it.
+fn broadcast(clients: &mut HashMap<u64, Client>, message: &ServerMessage) {
+    clients.retain(|_, client| !client.session.greeted || client.send(message));
+}
+
+/// Serve the providers of `config` on `socket` until killed. SIGHUP reloads
+/// the config, as in the UI.
+pub fn run(
+    path: &Path,
+    config: &Config,
+    local: bool,
+    read_only_flag: bool,
+    dry_run_flag: bool,
+    socket: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let read_only = read_only_flag || config.general.read_only;
+    let dry_run = dry_run_flag || config.general.dry_run;
+    let (providers, errors) =
+        ProviderSet::build(config, local, read_only, dry_run, &Selection::All);
+    for error in errors {
+        eprintln!("{}", error);
+    }
+    if providers.is_empty() {
+        return Err("no providers configured".into());
+    }
+    let mut interval = config.general.refresh_interval()?;
+
+    let listener = match activated_listener() {
+        Some(listener) => {
+            println!("pulse daemon started by socket activation");
+            listener
+        }
+        None => {
+            let socket = socket.map_or_else(default_socket, Path::to_path_buf);
+            let listener = bind(&socket)?;
+            println!("pulse daemon listening on {}", socket.display());
+            listener
+        }
+    };
+    let hangup = crate::reload::watch_hangup()?;
+    let (sender, incoming): (Sender<Incoming>, Receiver<Incoming>) = mpsc::channel();
+    std::thread::spawn(move || accept(listener, sender));
+
+    let mut daemon = Daemon::new(providers, read_only, dry_run);
+    let mut clients: HashMap<u64, Client> = HashMap::new();
+    log::info!(
+        "pulse daemon started with {} provider(s)",
+        daemon.providers.len()
+    );
+    daemon.refresh(Instant::now());
+    daemon.fetch_logs();
+    let mut next_refresh = Instant::now() + interval;
+    let mut next_logs = Instant::now() + CLUSTER_LOG_REFRESH_INTERVAL;
+
+    loop {
+        let wait = next_refresh.saturating_duration_since(Instant::now());
+        match incoming.recv_timeout(wait) {
+            Ok(Incoming::Connected(id, stream)) => {
+                let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                log::info!("daemon: client {} attached", id);
+                clients.insert(
+                    id,
+                    Client {
+                        stream,
+                        session: Session::default(),
+                    },
+                );
+            }
+            Ok(Incoming::Line(id, line)) => {
+                if let Some(client) = clients.get_mut(&id) {
+                    let answers = daemon.answer(&line, &mut client.session);
+                    let sent = answers.iter().all(|answer| client.send(answer));
+                    if !sent || client.session.closing {
+                        let _ = client.stream.shutdown(std::net::Shutdown::Both);
+                        clients.remove(&id);
+                    }
+                }
+            }
+            Ok(Incoming::Closed(id)) => {
+                if clients.remove(&id).is_some() {
+                    log::info!("daemon: client {} detached", id);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Err("the socket was closed".into()),
+        }
+
+        if hangup.swap(false, Ordering::Relaxed) {
+            match reload(path, local, read_only_flag, dry_run_flag, &mut daemon) {
+                Ok((notice, reloaded)) => {
+                    log::info!("daemon: {}", notice);
+                    interval = reloaded;
+                    broadcast(&mut clients, &daemon.hello());
+                    next_refresh = Instant::now();
+                }
+                Err(e) => log::warn!("daemon: config not reloaded: {}", e),
+            }
+        }
+
+        let now = Instant::now();
+        if now >= next_refresh {
+            daemon.refresh(now);
+            broadcast(
+                &mut clients,
+                &ServerMessage::Snapshot {
+                    providers: daemon.snapshot.clone(),
+                },
+            );
+            next_refresh = now + interval;
+        }
+        if now >= next_logs {
+            for message in daemon.fetch_logs() {
+                broadcast(&mut clients, &message);
+            }
+            next_logs = now + CLUSTER_LOG_REFRESH_INTERVAL;
+        }
+    }
+}
+
+/// Switch the daemon to the config now at `path`; returns the notice and
+/// the new refresh interval.
+fn reload(
+    path: &Path,
+    local: bool,
+    read_only_flag: bool,
+    dry_run_flag: bool,
+    daemon: &mut Daemon,
+) -> Result<(String, Duration), String> {
+    let config = config::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let interval = config
+        .general
+        .refresh_interval()
+        .map_err(|e| format!("general.refresh_rate: {}", e))?;
+    let read_only = read_only_flag || config.general.read_only;
+    let dry_run = dry_run_flag || config.general.dry_run;
+    let changes = daemon
+        .providers
+        .rebuild(&config, local, read_only, dry_run)?;
+    daemon.read_only = read_only;
+    daemon.dry_run = dry_run;
+    Ok((changes.summary(), interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU32};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::models::{Container, ContainerStatus, ContainerType, EventLevel, Node};
+
+    /// Clones share their state, so a test keeps one to steer the other.
+    #[derive(Clone, Default)]
+    struct FakeProvider {
+        fetches: Arc<AtomicU32>,
+        failing: Arc<AtomicBool>,
+        log: Arc<Mutex<Vec<ClusterLogEntry>>>,
+    }
+
+    impl Provider for FakeProvider {
+        fn name(&self) -> &str {
+            "homelab"
+        }
+
+        fn user(&self) -> Option<&str> {
+            Some("pulse@pve!daemon")
+        }
+
+        fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![node()])
+        }
+
+        fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err("HTTP 596: connection timed out".into());
+            }
+            Ok(vec![guest()])
+        }
+
+        fn fetch_cluster_log(
+            &self,
+            max: usize,
+        ) -> Result<Vec<ClusterLogEntry>, Box<dyn std::error::Error>> {
+            Ok(self.log.lock().unwrap().iter().take(max).cloned().collect())
+        }
+
+        fn start_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+            match container.lock {
+                Some(ref lock) => Err(format!("VM is locked ({})", lock).into()),
+                None => Ok(format!("UPID:pve1:start:{}", container.vmid)),
+            }
+        }
+    }
+
+    fn node() -> Node {
+        Node {
+            cpu_usage: 10.0,
+            cpu_cores: 8,
+            memory_used: 1024,
+            memory_total: 4096,
+            uptime: 600,
+            ..Node::test("homelab", "pve1")
+        }
+    }
+
+    fn guest() -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            status: ContainerStatus::Stopped,
+            max_cpu: 2,
+            memory_max: 2048,
+            ..Container::test("homelab", 100, "web", "pve1")
+        }
+    }
+
+    fn entry(uid: &str, time: u64) -> ClusterLogEntry {
+        ClusterLogEntry {
+            uid: uid.to_string(),
+            time,
+            node: "pve1".to_string(),
+            tag: "pvedaemon".to_string(),
+            user: Some("root@pam".to_string()),
+            level: EventLevel::Info,
+            message: format!("entry {}", uid),
+        }
+    }
+
+    fn daemon(provider: &FakeProvider) -> Daemon {
+        let providers = ProviderSet::fixed(vec![Arc::new(provider.clone())]);
+        Daemon::new(providers, false, true)
+    }
+
+    fn request(id: u64, provider: &str, request: Request) -> String {
+        protocol::encode(&ClientMessage::Request {
+            id,
+            provider: provider.to_string(),
+            request,
+        })
+    }
+
+    #[test]
+    fn test_clients_say_hello_in_our_version_first() {
+        let mut daemon = daemon(&FakeProvider::default());
+        daemon.refresh(Instant::now());
+
+        let mut session = Session::default();
+        let start = request(
+            1,
+            "homelab",
+            Request::StartGuest {
+                guest: Box::new(guest()),
+            },
+        );
+        match &daemon.answer(&start, &mut session)[..] {
+            [ServerMessage::Reply { id: 1, result }] => {
+                assert_eq!(result.as_ref().unwrap_err(), "hello first")
+            }
+            other => panic!("unexpected answer: {:?}", other),
+        }
+
+        let old = protocol::encode(&ClientMessage::Hello { version: 0 });
+        match &daemon.answer(&old, &mut session)[..] {
+            [ServerMessage::Refused { reason }] => {
+                assert_eq!(reason, "the client speaks protocol v0, this daemon v1")
+            }
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        assert!(session.closing);
+
+        let mut session = Session::default();
+        let hello = protocol::encode(&ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+        });
+        let greeting = daemon.answer(&hello, &mut session);
+        assert!(session.greeted && !session.closing);
+        match &greeting[..] {
+            [
+                ServerMessage::Hello {
+                    providers,
+                    read_only: false,
+                    dry_run: true,
+                    ..
+                },
+                ServerMessage::Snapshot {
+                    providers: snapshot,
+                },
+            ] => {
+                assert_eq!(
+                    providers,
+                    &[ProviderInfo {
+                        name: "homelab".to_string(),
+                        user: Some("pulse@pve!daemon".to_string()),
+                    }]
+                );
+                assert_eq!(snapshot[0].nodes.as_ref().unwrap()[0].name, "pve1");
+            }
+            other => panic!("unexpected greeting: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_requests_run_on_the_named_provider() {
+        let daemon = daemon(&FakeProvider::default());
+        let mut session = Session {
+            greeted: true,
+            closing: false,
+        };
+        let reply = |line: &str, session: &mut Session| match daemon.answer(line, session).pop() {
+            Some(ServerMessage::Reply { id, result }) => (id, result),
+            other => panic!("unexpected answer: {:?}", other),
+        };
+
+        let start = |provider: &str, lock: Option<&str>| {
+            let guest = Container {
+                lock: lock.map(str::to_string),
+                ..guest()
+            };
+            request(
+                7,
+                provider,
+                Request::StartGuest {
+                    guest: Box::new(guest),
+                },
+            )
+        };
+        match reply(&start("homelab", None), &mut session) {
+            (7, Ok(Reply::Text(task))) => assert_eq!(task, "UPID:pve1:start:100"),
+            other => panic!("unexpected reply: {:?}", other),
+        }
+        // Provider errors are passed on as they are
+        let (_, result) = reply(&start("homelab", Some("backup")), &mut session);
+        assert_eq!(result.unwrap_err(), "VM is locked (backup)");
+        let (_, result) = reply(&start("office", None), &mut session);
+        assert_eq!(
+            result.unwrap_err(),
+            "the daemon has no provider named office"
+        );
+        // Defaults of the trait answer too
+        let (_, result) = reply(&request(8, "homelab", Request::NextVmid), &mut session);
+        assert_eq!(
+            result.unwrap_err(),
+            "VMIDs are not supported by this provider"
+        );
+
+        // A request it can't read is still answered, if it has an id
+        let garbled = r#"{"type":"request","id":9,"provider":"homelab","request":{"call":"reboot_everything"}}"#;
+        let (id, result) = reply(garbled, &mut session);
+        assert_eq!(id, 9);
+        assert!(result.unwrap_err().starts_with("cannot read request: "));
+        assert!(
+            daemon
+                .answer("{\"type\":\"ping\"}", &mut session)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_refresh_reports_each_fetch_and_backs_off() {
+        let fake = FakeProvider::default();
+        let mut daemon = daemon(&fake);
+        let now = Instant::now();
+        fake.failing.store(true, Ordering::Relaxed);
+        daemon.refresh(now);
+        let snapshot = &daemon.snapshot[0];
+        assert!(snapshot.nodes.is_ok());
+        assert_eq!(
+            snapshot.containers.as_ref().unwrap_err(),
+            "HTTP 596: connection timed out"
+        );
+        assert_eq!(snapshot.cluster, Ok(None));
+
+        // Not retried until its backoff has passed; the failure stays
+        fake.failing.store(false, Ordering::Relaxed);
+        daemon.refresh(now + Duration::from_secs(1));
+        assert_eq!(fake.fetches.load(Ordering::Relaxed), 1);
+        assert!(daemon.snapshot[0].containers.is_err());
+        daemon.refresh(now + Duration::from_secs(6));
+        assert_eq!(fake.fetches.load(Ordering::Relaxed), 2);
+        assert_eq!(daemon.snapshot[0].containers.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_log_entries_are_sent_once() {
+        let fake = FakeProvider::default();
+        let mut daemon = daemon(&fake);
+        *fake.log.lock().unwrap() = vec![entry("2", 200), entry("1", 100)];
+        match &daemon.fetch_logs()[..] {
+            [ServerMessage::Events { provider, entries }] => {
+                assert_eq!(provider, "homelab");
+                assert_eq!(entries.len(), 2);
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+        assert!(daemon.fetch_logs().is_empty());
+
+        fake.log.lock().unwrap().insert(0, entry("3", 300));
+        match &daemon.fetch_logs()[..] {
+            [ServerMessage::Events { entries, .. }] => assert_eq!(entries, &[entry("3", 300)]),
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        // A client attaching now is sent all of them
+        let greeting = daemon.greeting();
+        match greeting.last() {
+            Some(ServerMessage::Events { entries, .. }) => {
+                let uids: Vec<&str> = entries.iter().map(|e| e.uid.as_str()).collect();
+                assert_eq!(uids, ["3", "2", "1"]);
+            }
+            other => panic!("unexpected greeting: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Counters about pulse itself, for the debug panel (`F12`): how long each
+//! provider's part of a refresh takes and how often it fails, how long
+//! whole refreshes and frames take. They are updated on every refresh and
+//! every frame, so recording is a little arithmetic and nothing more.
+
+use std::time::Duration;
+
+/// Weight of a new frame time in the running average.
+const FRAME_SMOOTHING: f64 = 1.0 / 8.0;
+
+/// One provider's share of the refreshes so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderTimings {
+    pub name: String,
+    pub refreshes: u64,
+    pub errors: u64,
+    /// API requests over all refreshes, for providers that count them.
+    pub requests: u64,
+    pub last_duration: Duration,
+    pub slowest: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    providers: Vec<ProviderTimings>,
+    pub refreshes: u64,
+    pub last_refresh: Option<Duration>,
+    pub slowest_refresh: Duration,
+    pub frames: u64,
+    pub last_frame: Option<Duration>,
+    /// Running average of the frame time, new frames weighted by
+    /// `FRAME_SMOOTHING`.
+    pub average_frame: Option<Duration>,
+    pub slowest_frame: Duration,
+}
+
+impl Diagnostics {
+    pub fn providers(&self) -> &[ProviderTimings] {
+        &self.providers
+    }
+
+    /// A provider was fetched from (successfully or not) in `duration`.
+    pub fn record_provider(
+        &mut self,
+        name: &str,
+        duration: Duration,
+        requests: Option<u32>,
+        failed: bool,
+    ) {
+        let index = match self.providers.iter().position(|p| p.name == name) {
+            Some(index) => index,
+            None => {
+                self.providers.push(ProviderTimings {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                self.providers.len() - 1
+            }
+        };
+        let timings = &mut self.providers[index];
+        timings.refreshes += 1;
+        timings.errors += u64::from(failed);
+        timings.requests += u64::from(requests.unwrap_or(0));
+        timings.last_duration = duration;
+        timings.slowest = timings.slowest.max(duration);
+    }
+
+    /// Drop the providers that are no longer configured.
+    pub fn retain_providers(&mut self, keep: impl Fn(&str) -> bool) {
+        self.providers.retain(|p| keep(&p.name));
+    }
+
+    /// A whole refresh, every provider and the detail fetches after them.
+    pub fn record_refresh(&mut self, duration: Duration) {
+        self.refreshes += 1;
+        self.last_refresh = Some(duration);
+        self.slowest_refresh = self.slowest_refresh.max(duration);
+    }
+
+    pub fn record_frame(&mut self, duration: Duration) {
+        self.frames += 1;
+        self.last_frame = Some(duration);
+        self.slowest_frame = self.slowest_frame.max(duration);
+        self.average_frame = Some(match self.average_frame {
+            Some(average) => {
+                let average = average.as_secs_f64();
+                Duration::from_secs_f64(
+                    average + (duration.as_secs_f64() - average) * FRAME_SMOOTHING,
+                )
+            }
+            None => duration,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_provider_counters_accumulate() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.record_provider("homelab", ms(120), Some(7), false);
+        diagnostics.record_provider("nas", ms(30), None, true);
+        diagnostics.record_provider("homelab", ms(80), Some(5), true);
+
+        assert_eq!(
+            diagnostics.providers(),
+            [
+                ProviderTimings {
+                    name: "homelab".to_string(),
+                    refreshes: 2,
+                    errors: 1,
+                    requests: 12,
+                    last_duration: ms(80),
+                    slowest: ms(120),
+                },
+                ProviderTimings {
+                    name: "nas".to_string(),
+                    refreshes: 1,
+                    errors: 1,
+                    requests: 0,
+                    last_duration: ms(30),
+                    slowest: ms(30),
+                },
+            ]
+        );
+
+        diagnostics.retain_providers(|name| name != "nas");
+        assert_eq!(diagnostics.providers().len(), 1);
+    }
+
+    #[test]
+    fn test_refresh_keeps_last_and_slowest() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.record_refresh(ms(400));
+        diagnostics.record_refresh(ms(150));
+        assert_eq!(diagnostics.refreshes, 2);
+        assert_eq!(diagnostics.last_refresh, Some(ms(150)));
+        assert_eq!(diagnostics.slowest_refresh, ms(400));
+    }
+
+    #[test]
+    fn test_frame_average_is_smoothed() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.record_frame(ms(8));
+        assert_eq!(diagnostics.average_frame, Some(ms(8)));
+
+        // One slow frame moves the average by an eighth of the difference
+        diagnostics.record_frame(ms(88));
+        assert_eq!(diagnostics.average_frame, Some(ms(18)));
+        assert_eq!(diagnostics.last_frame, Some(ms(88)));
+        assert_eq!(diagnostics.slowest_frame, ms(88));
+        assert_eq!(diagnostics.frames, 2);
+    }
+}
@@ -0,0 +1,356 @@
+//! Node maintenance: move every running guest off a node before a reboot.
+//!
+//! `plan()` decides what happens to each guest; `Drain` tracks the plan while
+//! `App::advance_drain` executes it one guest at a time through the provider's
+//! action methods.
+
+use std::fmt::Write;
+use std::time::Instant;
+
+use crate::format::{Units, format_bytes};
+use crate::models::{Container, ContainerStatus, GuestId, Node, NodeId, NodeStatus};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrainAction {
+    Migrate {
+        target: String,
+    },
+    /// The guest can't move and will be shut down instead.
+    Shutdown {
+        reason: String,
+    },
+    /// The guest is locked: listed in the plan but never sent to the provider.
+    Skip {
+        reason: String,
+    },
+}
+
+impl DrainAction {
+    /// Short form for the audit log, e.g. "migrate to pve2".
+    pub fn describe(&self) -> String {
+        match self {
+            DrainAction::Migrate { target } => format!("migrate to {}", target),
+            DrainAction::Shutdown { .. } => "shutdown".to_string(),
+            DrainAction::Skip { .. } => "skip".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepState {
+    Pending,
+    /// Task started on the provider, polled until it finishes.
+    Running {
+        task: String,
+    },
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrainStep {
+    pub guest: GuestId,
+    pub label: String,
+    pub action: DrainAction,
+    pub state: StepState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrainPhase {
+    /// Plan shown, waiting for the user to confirm.
+    Confirm,
+    Running,
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct Drain {
+    pub node: NodeId,
+    pub steps: Vec<DrainStep>,
+    pub phase: DrainPhase,
+    /// When the running task was last polled.
+    pub last_poll: Option<Instant>,
+}
+
+/// Plan a drain of `node`: each running guest on it migrates to the online
+/// node of the same provider with the most free memory that can hold it, or
+/// is shut down when nothing can take it. Locked guests are skipped: the
+/// provider would refuse any action on them.
+pub fn plan(node: &Node, nodes: &[Node], containers: &[Container], units: Units) -> Drain {
+    // Free memory per candidate target, reduced as guests are assigned
+    let mut free: Vec<(&str, u64)> = nodes
+        .iter()
+        .filter(|n| {
+            n.provider() == node.provider() && n.id != node.id && n.status == NodeStatus::Online
+        })
+        .map(|n| {
+            (
+                n.name.as_str(),
+                n.memory_total.saturating_sub(n.memory_used),
+            )
+        })
+        .collect();
+
+    let mut guests: Vec<&Container> = containers
+        .iter()
+        .filter(|c| {
+            c.provider() == node.provider()
+                && c.node == node.name
+                && c.status == ContainerStatus::Running
+        })
+        .collect();
+    // Place the biggest guests first so they get the roomiest targets
+    guests.sort_by(|a, b| b.memory_max.cmp(&a.memory_max).then(a.vmid.cmp(&b.vmid)));
+
+    let steps = guests
+        .into_iter()
+        .map(|guest| {
+            let action = if let Some(message) = guest.lock_message() {
+                DrainAction::Skip { reason: message }
+            } else if free.is_empty() {
+                DrainAction::Shutdown {
+                    reason: "no other online node".to_string(),
+                }
+            } else {
+                let best = free
+                    .iter_mut()
+                    .filter(|(_, available)| *available >= guest.memory_max)
+                    .max_by_key(|(_, available)| *available);
+                match best {
+                    Some((target, available)) => {
+                        *available -= guest.memory_max;
+                        DrainAction::Migrate {
+                            target: target.to_string(),
+                        }
+                    }
+                    None => DrainAction::Shutdown {
+                        reason: format!(
+                            "no node has {} free",
+                            format_bytes(guest.memory_max, units)
+                        ),
+                    },
+                }
+            };
+            DrainStep {
+                guest: guest.id.clone(),
+                label: format!("{} {}", guest.vmid, guest.name),
+                action,
+                state: StepState::Pending,
+            }
+        })
+        .collect();
+
+    Drain {
+        node: node.id.clone(),
+        steps,
+        phase: DrainPhase::Confirm,
+        last_poll: None,
+    }
+}
+
+impl Drain {
+    /// Index of the step being executed or next to run. Skipped steps are
+    /// never current.
+    pub fn current(&self) -> Option<usize> {
+        self.steps.iter().position(|s| {
+            s.state != StepState::Done && !matches!(s.action, DrainAction::Skip { .. })
+        })
+    }
+
+    /// Mark a step failed, which ends the drain.
+    pub fn fail(&mut self, index: usize, error: String) {
+        self.steps[index].state = StepState::Failed(error);
+        self.phase = DrainPhase::Finished;
+    }
+
+    /// Final report: what was done, what failed and what was never attempted.
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        let migrated = self.count_done(|a| matches!(a, DrainAction::Migrate { .. }));
+        let shut_down = self.count_done(|a| matches!(a, DrainAction::Shutdown { .. }));
+
+        let failure = self.steps.iter().find_map(|s| match &s.state {
+            StepState::Failed(error) => Some((s, error)),
+            _ => None,
+        });
+        match failure {
+            Some((failed, error)) => {
+                let _ = write!(
+                    out,
+                    "Drain of {} stopped at {}: {}. ",
+                    self.node.name, failed.label, error
+                );
+            }
+            None => {
+                let _ = write!(out, "Drained {}: ", self.node.name);
+            }
+        }
+        let _ = write!(out, "{} migrated, {} shut down", migrated, shut_down);
+
+        let not_done: Vec<String> = self
+            .steps
+            .iter()
+            .filter(|s| s.state == StepState::Pending)
+            .map(|s| match &s.action {
+                DrainAction::Skip { reason } => format!("{}: {}", s.label, reason),
+                _ => s.label.clone(),
+            })
+            .collect();
+        if !not_done.is_empty() {
+            let _ = write!(out, "; not done: {}", not_done.join(", "));
+        }
+        out
+    }
+
+    fn count_done(&self, matches: impl Fn(&DrainAction) -> bool) -> usize {
+        self.steps
+            .iter()
+            .filter(|s| s.state == StepState::Done && matches(&s.action))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ContainerType;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn node(name: &str, used_gb: u64, total_gb: u64) -> Node {
+        Node {
+            cpu_cores: 8,
+            memory_used: used_gb * GB,
+            memory_total: total_gb * GB,
+            uptime: 1,
+            ..Node::test("homelab", name)
+        }
+    }
+
+    fn guest(vmid: u32, node: &str, memory_gb: u64) -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            max_cpu: 2,
+            memory_max: memory_gb * GB,
+            uptime: 1,
+            ..Container::test("homelab", vmid, &format!("guest{}", vmid), node)
+        }
+    }
+
+    fn targets(drain: &Drain) -> Vec<(u32, String)> {
+        drain
+            .steps
+            .iter()
+            .map(|s| {
+                let vmid = s.guest.id.parse().unwrap();
+                match &s.action {
+                    DrainAction::Migrate { target } => (vmid, target.clone()),
+                    DrainAction::Shutdown { reason } => (vmid, format!("shutdown: {}", reason)),
+                    DrainAction::Skip { reason } => (vmid, format!("skip: {}", reason)),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_spreads_by_free_memory() {
+        let nodes = vec![
+            node("pve1", 0, 64),
+            node("pve2", 24, 32),
+            node("pve3", 16, 32),
+        ];
+        let containers = vec![
+            guest(101, "pve1", 8),
+            guest(102, "pve1", 12),
+            guest(103, "pve1", 4),
+            guest(201, "pve2", 4),
+        ];
+
+        let drain = plan(&nodes[0], &nodes, &containers, Units::Short);
+        assert_eq!(drain.phase, DrainPhase::Confirm);
+        // Biggest first: 102 (12G) -> pve3 (16G free), then 101 (8G) -> pve2
+        // (8G free vs 4G left on pve3), then 103 (4G) -> pve3
+        assert_eq!(
+            targets(&drain),
+            [
+                (102, "pve3".to_string()),
+                (101, "pve2".to_string()),
+                (103, "pve3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_shuts_down_what_cannot_move() {
+        let nodes = vec![node("pve1", 0, 64), node("pve2", 28, 32)];
+        let mut locked = guest(102, "pve1", 1);
+        locked.lock = Some("backup".to_string());
+        let mut stopped = guest(103, "pve1", 1);
+        stopped.status = ContainerStatus::Stopped;
+        let containers = vec![guest(101, "pve1", 8), locked, stopped];
+
+        let drain = plan(&nodes[0], &nodes, &containers, Units::Short);
+        assert_eq!(
+            targets(&drain),
+            [
+                (101, "shutdown: no node has 8.0 GB free".to_string()),
+                (102, "skip: guest is locked (backup)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_ignores_offline_and_foreign_nodes() {
+        let mut offline = node("pve2", 0, 64);
+        offline.status = NodeStatus::Offline;
+        let mut foreign = node("pve3", 0, 64);
+        foreign.id = NodeId::new("remote", "pve3");
+        let nodes = vec![node("pve1", 0, 64), offline, foreign];
+
+        let drain = plan(&nodes[0], &nodes, &[guest(101, "pve1", 1)], Units::Short);
+        assert_eq!(
+            targets(&drain),
+            [(101, "shutdown: no other online node".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_summary_reports_done_failed_and_pending() {
+        let nodes = vec![node("pve1", 0, 64), node("pve2", 0, 64)];
+        let containers = vec![
+            guest(101, "pve1", 3),
+            guest(102, "pve1", 2),
+            guest(103, "pve1", 1),
+        ];
+        let mut drain = plan(&nodes[0], &nodes, &containers, Units::Short);
+        drain.steps[0].state = StepState::Done;
+        drain.steps[1].state = StepState::Failed("migration aborted".to_string());
+
+        assert_eq!(
+            drain.summary(),
+            "Drain of pve1 stopped at 102 guest102: migration aborted. 1 migrated, 0 shut down; not done: 103 guest103"
+        );
+
+        drain.steps[1].state = StepState::Done;
+        drain.steps[2].state = StepState::Done;
+        assert_eq!(drain.summary(), "Drained pve1: 3 migrated, 0 shut down");
+    }
+
+    #[test]
+    fn test_locked_guests_are_skipped_not_run() {
+        let nodes = vec![node("pve1", 0, 64), node("pve2", 0, 64)];
+        let mut locked = guest(101, "pve1", 2);
+        locked.lock = Some("backup".to_string());
+        let containers = vec![locked, guest(102, "pve1", 1)];
+        let mut drain = plan(&nodes[0], &nodes, &containers, Units::Short);
+
+        // The locked guest sorts first but the migration runs first
+        assert_eq!(drain.current(), Some(1));
+        drain.steps[1].state = StepState::Done;
+        assert_eq!(drain.current(), None);
+        assert_eq!(
+            drain.summary(),
+            "Drained pve1: 1 migrated, 0 shut down; not done: 101 guest101: guest is locked (backup)"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Two providers reporting the same machines, as when one cluster is
+//! configured twice under different names. Matching is deliberately strict,
+//! since clusters built from the same defaults look alike: node `pve1` with
+//! guest 100 exists in many homelabs. A node only counts as the same machine
+//! when its hardware matches and it booted at about the same time, and a
+//! pair of providers only as duplicates when most of the guests on those
+//! nodes match too, by type, VMID and name.
+
+use std::collections::HashSet;
+
+use crate::models::{Container, ContainerType, Node, NodeStatus};
+
+/// How far apart two uptimes of the same machine may be. The providers
+/// read them at different moments, up to a refresh interval apart when
+/// refreshes are staggered.
+const UPTIME_TOLERANCE: u64 = 600;
+
+/// Share of the guests on shared nodes that must match on both sides.
+const GUEST_MATCH_SHARE: f64 = 0.8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    /// The provider listed first; when duplicates are hidden, it is kept.
+    pub first: String,
+    pub second: String,
+    /// Nodes both report, by name.
+    pub nodes: Vec<String>,
+    /// Guests both report on those nodes.
+    pub guests: usize,
+    /// Every node of either provider is shared.
+    pub whole: bool,
+}
+
+impl Duplicate {
+    /// "homelab and homelab-old appear to report the same cluster"
+    pub fn describe(&self) -> String {
+        if self.whole {
+            format!(
+                "{} and {} appear to report the same cluster",
+                self.first, self.second
+            )
+        } else {
+            format!(
+                "{} and {} appear to report the same nodes ({})",
+                self.first,
+                self.second,
+                self.nodes.join(", ")
+            )
+        }
+    }
+
+    pub fn involves(&self, provider: &str) -> bool {
+        self.first == provider || self.second == provider
+    }
+}
+
+fn same_machine(a: &Node, b: &Node) -> bool {
+    a.name == b.name
+        && a.status == NodeStatus::Online
+        && b.status == NodeStatus::Online
+        && a.cpu_cores == b.cpu_cores
+        && a.memory_total == b.memory_total
+        && a.uptime.abs_diff(b.uptime) <= UPTIME_TOLERANCE
+}
+
+fn guest_key(guest: &Container) -> (&ContainerType, u32, &str, &str) {
+    (&guest.container_type, guest.vmid, &guest.node, &guest.name)
+}
+
+/// Whether provider `second` repeats what `first` reports.
+fn compare(
+    first: &str,
+    second: &str,
+    nodes: &[Node],
+    containers: &[Container],
+) -> Option<Duplicate> {
+    let of = |provider: &str| -> Vec<&Node> {
+        nodes.iter().filter(|n| n.provider() == provider).collect()
+    };
+    let (first_nodes, second_nodes) = (of(first), of(second));
+    let shared: Vec<String> = first_nodes
+        .iter()
+        .filter(|a| second_nodes.iter().any(|b| same_machine(a, b)))
+        .map(|n| n.name.clone())
+        .collect();
+    if shared.is_empty() {
+        return None;
+    }
+
+    let guests = |provider: &str| -> Vec<&Container> {
+        containers
+            .iter()
+            .filter(|c| c.provider() == provider && !c.missing && shared.contains(&c.node))
+            .collect()
+    };
+    let (first_guests, second_guests) = (guests(first), guests(second));
+    let keys: HashSet<_> = second_guests.iter().map(|c| guest_key(c)).collect();
+    let matched = first_guests
+        .iter()
+        .filter(|c| keys.contains(&guest_key(c)))
+        .count();
+    // Nodes alone never decide it: the same machine under two providers
+    // (--local next to a systemd provider) is fine as long as what they
+    // list on it differs
+    let larger = first_guests.len().max(second_guests.len());
+    if matched == 0 || (matched as f64) < larger as f64 * GUEST_MATCH_SHARE {
+        return None;
+    }
+    Some(Duplicate {
+        first: first.to_string(),
+        second: second.to_string(),
+        whole: shared.len() == first_nodes.len() && shared.len() == second_nodes.len(),
+        nodes: shared,
+        guests: matched,
+    })
+}
+
+/// Every pair of `providers`, in the order given, that report the same
+/// machines in `nodes` and `containers`.
+pub fn find(providers: &[&str], nodes: &[Node], containers: &[Container]) -> Vec<Duplicate> {
+    let mut found = Vec::new();
+    for (i, first) in providers.iter().enumerate() {
+        for second in &providers[i + 1..] {
+            found.extend(compare(first, second, nodes, containers));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn node(provider: &str, name: &str, uptime: u64) -> Node {
+        Node {
+            cpu_usage: 10.0,
+            cpu_cores: 8,
+            memory_used: 8 * GB,
+            memory_total: 32 * GB,
+            uptime,
+            ..Node::test(provider, name)
+        }
+    }
+
+    fn guest(provider: &str, vmid: u32, name: &str, node: &str) -> Container {
+        Container {
+            cpu_usage: 1.0,
+            max_cpu: 2,
+            memory_used: GB,
+            memory_max: 2 * GB,
+            uptime: 60,
+            ..Container::test(provider, vmid, name, node)
+        }
+    }
+
+    /// The same two-node cluster as `provider` sees it, a refresh apart.
+    fn cluster(provider: &str, drift: u64) -> (Vec<Node>, Vec<Container>) {
+        (
+            vec![
+                node(provider, "pve1", 86_400 + drift),
+                node(provider, "pve2", 3_600 + drift),
+            ],
+            vec![
+                guest(provider, 100, "web", "pve1"),
+                guest(provider, 101, "db", "pve1"),
+                guest(provider, 102, "dns", "pve2"),
+            ],
+        )
+    }
+
+    fn merged(
+        parts: impl IntoIterator<Item = (Vec<Node>, Vec<Container>)>,
+    ) -> (Vec<Node>, Vec<Container>) {
+        parts.into_iter().fold(
+            (Vec::new(), Vec::new()),
+            |(mut nodes, mut guests), (n, g)| {
+                nodes.extend(n);
+                guests.extend(g);
+                (nodes, guests)
+            },
+        )
+    }
+
+    #[test]
+    fn test_the_same_cluster_twice_is_found() {
+        let (nodes, guests) = merged([cluster("homelab", 0), cluster("homelab-old", 5)]);
+        let found = find(&["homelab", "homelab-old"], &nodes, &guests);
+        assert_eq!(
+            found,
+            [Duplicate {
+                first: "homelab".to_string(),
+                second: "homelab-old".to_string(),
+                nodes: vec!["pve1".to_string(), "pve2".to_string()],
+                guests: 3,
+                whole: true,
+            }]
+        );
+        assert_eq!(
+            found[0].describe(),
+            "homelab and homelab-old appear to report the same cluster"
+        );
+
+        // Provider order decides which is first
+        let found = find(&["homelab-old", "homelab"], &nodes, &guests);
+        assert_eq!(found[0].first, "homelab-old");
+    }
+
+    #[test]
+    fn test_one_node_of_a_cluster_configured_on_its_own() {
+        let (mut nodes, mut guests) = cluster("homelab", 0);
+        nodes.push(node("pve1-direct", "pve1", 86_400));
+        guests.push(guest("pve1-direct", 100, "web", "pve1"));
+        guests.push(guest("pve1-direct", 101, "db", "pve1"));
+        let found = find(&["homelab", "pve1-direct"], &nodes, &guests);
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].whole);
+        assert_eq!(
+            found[0].describe(),
+            "homelab and pve1-direct appear to report the same nodes (pve1)"
+        );
+    }
+
+    #[test]
+    fn test_lookalike_clusters_are_left_alone() {
+        // Default names and VMIDs, different machines
+        let (mut nodes, guests) = merged([cluster("home", 0), cluster("office", 0)]);
+        for node in nodes.iter_mut().filter(|n| n.provider() == "office") {
+            node.memory_total = 64 * GB;
+        }
+        assert!(find(&["home", "office"], &nodes, &guests).is_empty());
+
+        // Identical hardware booted at different times
+        let (nodes, guests) = merged([cluster("home", 0), cluster("office", 7_200)]);
+        assert!(find(&["home", "office"], &nodes, &guests).is_empty());
+
+        // Identical hardware that came back from the same power cut, running
+        // guests that share VMIDs but not names
+        let (nodes, mut guests) = merged([cluster("home", 0), cluster("office", 30)]);
+        for guest in guests.iter_mut().filter(|c| c.provider() == "office") {
+            guest.name = format!("office-{}", guest.name);
+        }
+        assert!(find(&["home", "office"], &nodes, &guests).is_empty());
+
+        // One template cloned into both is not enough
+        guests[0].name = "office-web".to_string();
+        assert!(find(&["home", "office"], &nodes, &guests).is_empty());
+    }
+
+    #[test]
+    fn test_same_machine_listing_different_things_is_fine() {
+        // --local next to a systemd provider: one host, processes vs units
+        let mut nodes = vec![node("local", "nas", 500), node("units", "nas", 500)];
+        let mut process = guest("local", 812, "postgres", "nas");
+        process.container_type = ContainerType::Process;
+        let mut unit = guest("units", 0, "nginx", "nas");
+        unit.container_type = ContainerType::Service;
+        assert!(find(&["units", "local"], &nodes, &[process, unit]).is_empty());
+
+        // Nor does an offline node match anything
+        nodes
+            .iter_mut()
+            .for_each(|n| n.status = NodeStatus::Offline);
+        let (_, guests) = cluster("x", 0);
+        assert!(find(&["units", "local"], &nodes, &guests).is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+use crate::models::PermissionIssue;
+
+#[derive(Debug)]
+pub enum ProviderError {
+    /// The request never produced a response (connection refused, timeout, TLS).
+    Request(reqwest::Error),
+    /// The API answered with a non-success HTTP status.
+    Status { url: String, status: u16 },
+    /// A 403 on an endpoint whose required privilege is known.
+    Permission(PermissionIssue),
+    /// The response body was not the JSON shape we expected.
+    Parse {
+        url: String,
+        source: serde_json::Error,
+    },
+    /// A local command could not be run or exited unsuccessfully.
+    Command { command: String, message: String },
+    /// The request failed at (or beyond) a proxy or SSH jump host.
+    Hop { hop: String, message: String },
+    /// An action was refused because pulse runs read-only.
+    ReadOnly { action: String },
+    /// A pulse agent speaks another snapshot schema (None: it sent none).
+    Version {
+        url: String,
+        found: Option<u64>,
+        expected: u32,
+    },
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Request(e) => write!(f, "request failed: {}", e),
+            ProviderError::Status { url, status } => match status {
+                401 => write!(f, "authentication failed (401) for {}", url),
+                403 => write!(f, "permission denied (403) for {}", url),
+                _ => write!(f, "HTTP {} from {}", status, url),
+            },
+            ProviderError::Permission(issue) => write!(f, "{}", issue),
+            ProviderError::Parse { url, source } => {
+                write!(f, "invalid response from {}: {}", url, source)
+            }
+            ProviderError::Command { command, message } => {
+                write!(f, "`{}` failed: {}", command, message)
+            }
+            ProviderError::Hop { hop, message } => write!(f, "{}: {}", hop, message),
+            ProviderError::ReadOnly { action } => {
+                write!(f, "{} refused: pulse is in read-only mode", action)
+            }
+            ProviderError::Version {
+                url,
+                found: Some(found),
+                expected,
+            } => write!(
+                f,
+                "agent at {} speaks schema v{}, this pulse expects v{}; run the same pulse version on both ends",
+                url, found, expected
+            ),
+            ProviderError::Version {
+                url, found: None, ..
+            } => write!(f, "{} sent no schema version; is it a pulse agent?", url),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProviderError::Request(e) => Some(e),
+            ProviderError::Status { .. }
+            | ProviderError::Permission(_)
+            | ProviderError::Command { .. }
+            | ProviderError::Hop { .. }
+            | ProviderError::ReadOnly { .. }
+            | ProviderError::Version { .. } => None,
+            ProviderError::Parse { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        ProviderError::Request(e)
+    }
+}
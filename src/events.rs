@@ -0,0 +1,223 @@
+//! Recent events for the `L` popup: the providers' own logs (the Proxmox
+//! cluster log) next to the transitions pulse notices between refreshes,
+//! such as a node going offline or a guest restarting.
+//!
+//! Cluster logs are fetched whole every time, so their entries are only
+//! added once, by uid and time.
+
+use std::collections::VecDeque;
+
+use crate::models::{ClusterLogEntry, EventLevel};
+
+/// Events kept, across all providers.
+pub const EVENT_CAPACITY: usize = 200;
+
+/// Cluster log entries asked for per fetch.
+pub const CLUSTER_LOG_ENTRIES: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventSource {
+    /// From the provider's log: the service and user that logged it.
+    Cluster {
+        uid: String,
+        tag: String,
+        user: Option<String>,
+    },
+    /// Noticed by pulse itself.
+    Pulse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub provider: String,
+    /// Unix seconds.
+    pub time: u64,
+    pub node: Option<String>,
+    pub source: EventSource,
+    pub level: EventLevel,
+    pub message: String,
+}
+
+impl Event {
+    /// One noticed by pulse.
+    pub fn detected(
+        provider: &str,
+        time: u64,
+        node: Option<&str>,
+        level: EventLevel,
+        message: String,
+    ) -> Self {
+        Event {
+            provider: provider.to_string(),
+            time,
+            node: node.map(str::to_string),
+            source: EventSource::Pulse,
+            level,
+            message,
+        }
+    }
+
+    fn from_log(provider: &str, entry: ClusterLogEntry) -> Self {
+        Event {
+            provider: provider.to_string(),
+            time: entry.time,
+            node: Some(entry.node),
+            source: EventSource::Cluster {
+                uid: entry.uid,
+                tag: entry.tag,
+                user: entry.user,
+            },
+            level: entry.level,
+            message: entry.message,
+        }
+    }
+
+    /// "pvedaemon root@pam" for log entries, "pulse" for detected ones.
+    pub fn origin(&self) -> String {
+        match &self.source {
+            EventSource::Cluster {
+                tag,
+                user: Some(user),
+                ..
+            } => format!("{} {}", tag, user),
+            EventSource::Cluster { tag, .. } => tag.clone(),
+            EventSource::Pulse => "pulse".to_string(),
+        }
+    }
+
+    fn is_entry(&self, provider: &str, entry: &ClusterLogEntry) -> bool {
+        matches!(&self.source, EventSource::Cluster { uid, .. } if *uid == entry.uid)
+            && self.time == entry.time
+            && self.provider == provider
+    }
+}
+
+/// Newest first, at most `EVENT_CAPACITY`.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, event: Event) {
+        self.insert(event);
+        self.events.truncate(EVENT_CAPACITY);
+    }
+
+    /// Add the entries of `provider`'s log not already kept; returns how
+    /// many were new.
+    pub fn add_cluster_log(&mut self, provider: &str, entries: Vec<ClusterLogEntry>) -> usize {
+        let mut added = 0;
+        for entry in entries {
+            // Entries older than everything in a full buffer already fell out
+            let too_old = self.events.len() >= EVENT_CAPACITY
+                && self.events.back().is_some_and(|e| e.time >= entry.time);
+            if too_old || self.events.iter().any(|e| e.is_entry(provider, &entry)) {
+                continue;
+            }
+            self.insert(Event::from_log(provider, entry));
+            self.events.truncate(EVENT_CAPACITY);
+            added += 1;
+        }
+        added
+    }
+
+    /// Keep the order by time, newest first; among equal times the later
+    /// arrival goes first.
+    fn insert(&mut self, event: Event) {
+        let at = self.events.partition_point(|e| e.time > event.time);
+        self.events.insert(at, event);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(uid: &str, time: u64, pri: u8, message: &str) -> ClusterLogEntry {
+        ClusterLogEntry {
+            uid: uid.to_string(),
+            time,
+            node: "pve1".to_string(),
+            tag: "pvedaemon".to_string(),
+            user: Some("root@pam".to_string()),
+            level: EventLevel::from_priority(pri),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cluster_log_entries_are_added_once() {
+        let mut log = EventLog::default();
+        let first = vec![
+            entry("2", 200, 3, "auth failure"),
+            entry("1", 100, 6, "login"),
+        ];
+        assert_eq!(log.add_cluster_log("homelab", first.clone()), 2);
+        assert_eq!(log.add_cluster_log("homelab", first), 0);
+        // The same uid at another time is another entry, as is another provider's
+        assert_eq!(
+            log.add_cluster_log(
+                "homelab",
+                vec![entry("3", 300, 4, "fence"), entry("1", 150, 6, "login")]
+            ),
+            2
+        );
+        assert_eq!(
+            log.add_cluster_log("office", vec![entry("1", 100, 6, "login")]),
+            1
+        );
+
+        let times: Vec<u64> = log.iter().map(|e| e.time).collect();
+        assert_eq!(times, [300, 200, 150, 100, 100]);
+        let levels: Vec<EventLevel> = log.iter().take(2).map(|e| e.level).collect();
+        assert_eq!(levels, [EventLevel::Warning, EventLevel::Error]);
+        assert_eq!(log.iter().next().unwrap().origin(), "pvedaemon root@pam");
+    }
+
+    #[test]
+    fn test_detected_events_interleave_and_the_buffer_is_bounded() {
+        let mut log = EventLog::default();
+        log.add_cluster_log("homelab", vec![entry("1", 100, 6, "login")]);
+        log.push(Event::detected(
+            "homelab",
+            150,
+            Some("pve2"),
+            EventLevel::Error,
+            "went offline".to_string(),
+        ));
+        assert_eq!(log.iter().next().unwrap().origin(), "pulse");
+
+        for time in 1000..1000 + EVENT_CAPACITY as u64 {
+            log.add_cluster_log("homelab", vec![entry(&time.to_string(), time, 6, "task")]);
+        }
+        assert_eq!(log.len(), EVENT_CAPACITY);
+        // Older entries fell out, and don't come back when listed again
+        assert_eq!(
+            log.add_cluster_log("homelab", vec![entry("1", 100, 6, "login")]),
+            0
+        );
+        assert_eq!(log.len(), EVENT_CAPACITY);
+        assert!(log.iter().all(|e| e.time >= 1000));
+    }
+
+    #[test]
+    fn test_priority_levels() {
+        assert_eq!(EventLevel::from_priority(0), EventLevel::Error);
+        assert_eq!(EventLevel::from_priority(3), EventLevel::Error);
+        assert_eq!(EventLevel::from_priority(4), EventLevel::Warning);
+        assert_eq!(EventLevel::from_priority(6), EventLevel::Info);
+    }
+}
@@ -0,0 +1,155 @@
+//! Memoized container filtering. Matching the search against every container
+//! is most of the work in a frame on large inventories, and the list is asked
+//! for several times per frame, so the result is kept as indices into
+//! `App::containers` until something it depends on changes.
+
+use crate::config::CpuMode;
+use crate::groups::{GroupBy, NamePrefix};
+use crate::query;
+use crate::restarts::RecentFilter;
+
+/// Everything a filter result depends on besides the containers themselves,
+/// which are covered by `generation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterKey {
+    /// Bumped by `App` whenever nodes or containers change.
+    pub generation: u64,
+    /// Guards against the list being replaced without a bump: cached
+    /// indices must never point past its end.
+    pub containers: usize,
+    pub query: String,
+    pub options: query::MatchOptions,
+    /// The selected pool's key; None for "All".
+    pub pool: Option<Option<String>>,
+    /// The provider picked in the providers popup; None for all.
+    pub provider: Option<String>,
+    /// `cpu>50` compares the displayed CPU, which depends on the mode.
+    pub cpu_mode: CpuMode,
+    pub recent: RecentFilter,
+    pub hide_templates: bool,
+    pub group_by: GroupBy,
+    pub name_prefix: NamePrefix,
+}
+
+impl FilterKey {
+    /// Whether results for `self` are a subset of those for `broader`: the
+    /// same data and view, and a query that only narrows `broader`'s.
+    fn narrows(&self, broader: &FilterKey) -> bool {
+        let same_view = self.generation == broader.generation
+            && self.containers == broader.containers
+            && self.pool == broader.pool
+            && self.provider == broader.provider
+            && self.cpu_mode == broader.cpu_mode
+            && self.recent == broader.recent
+            && self.hide_templates == broader.hide_templates;
+        same_view
+            && match (
+                query::parse_with(&self.query, self.options),
+                query::parse_with(&broader.query, broader.options),
+            ) {
+                (Ok(new), Ok(old)) => new.narrows(&old),
+                // A mistyped query falls back to a plain substring match
+                _ => false,
+            }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilterCache {
+    key: Option<FilterKey>,
+    /// Matching containers, in the order of `App::containers`.
+    pub matching: Vec<usize>,
+    /// The same, in display order (grouped when a grouping is on).
+    pub display: Vec<usize>,
+    /// Containers checked against the search so far; lets tests see how much
+    /// work a keystroke caused.
+    pub evaluated: usize,
+}
+
+impl FilterCache {
+    pub fn is_for(&self, key: &FilterKey) -> bool {
+        self.key.as_ref() == Some(key)
+    }
+
+    /// The previous matches, if every result for `key` is among them.
+    pub fn narrowed_from(&self, key: &FilterKey) -> Option<&[usize]> {
+        self.key
+            .as_ref()
+            .filter(|cached| key.narrows(cached))
+            .map(|_| self.matching.as_slice())
+    }
+
+    pub fn store(&mut self, key: FilterKey, matching: Vec<usize>, display: Vec<usize>) {
+        self.key = Some(key);
+        self.matching = matching;
+        self.display = display;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(query: &str) -> FilterKey {
+        FilterKey {
+            generation: 0,
+            containers: 5,
+            query: query.to_string(),
+            options: query::MatchOptions::default(),
+            pool: None,
+            provider: None,
+            cpu_mode: CpuMode::Guest,
+            recent: RecentFilter::Off,
+            hide_templates: false,
+            group_by: GroupBy::Off,
+            name_prefix: NamePrefix::default(),
+        }
+    }
+
+    #[test]
+    fn test_narrowing_needs_the_same_data_and_view() {
+        let mut cache = FilterCache::default();
+        cache.store(key("we"), vec![1, 4], vec![1, 4]);
+
+        assert!(cache.is_for(&key("we")));
+        assert_eq!(cache.narrowed_from(&key("web")), Some(&[1, 4][..]));
+        assert_eq!(cache.narrowed_from(&key("w")), None);
+        assert_eq!(cache.narrowed_from(&key("cpu>")), None);
+
+        let changed = FilterKey {
+            generation: 1,
+            ..key("web")
+        };
+        assert_eq!(cache.narrowed_from(&changed), None);
+        let other_pool = FilterKey {
+            pool: Some(Some("prod".to_string())),
+            ..key("web")
+        };
+        assert_eq!(cache.narrowed_from(&other_pool), None);
+        let one_provider = FilterKey {
+            provider: Some("homelab".to_string()),
+            ..key("web")
+        };
+        assert_eq!(cache.narrowed_from(&one_provider), None);
+        let recent = FilterKey {
+            recent: RecentFilter::Hour,
+            ..key("web")
+        };
+        assert_eq!(cache.narrowed_from(&recent), None);
+        let case_sensitive = FilterKey {
+            options: query::MatchOptions {
+                case_sensitive: true,
+                whole_word: false,
+            },
+            ..key("web")
+        };
+        assert_eq!(cache.narrowed_from(&case_sensitive), None);
+
+        // Regrouping keeps the matches, only their order changes
+        let grouped = FilterKey {
+            group_by: GroupBy::Node,
+            ..key("web")
+        };
+        assert_eq!(cache.narrowed_from(&grouped), Some(&[1, 4][..]));
+    }
+}
@@ -0,0 +1,415 @@
+//! How pulse writes and reads durations and sizes, so uptimes, ages, config
+//! values and byte counts look the same wherever they appear.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// A hundred years, in seconds.
+const MAX_PLAUSIBLE_UPTIME: u64 = 100 * 365 * 86400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimeUnit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Year,
+}
+
+impl TimeUnit {
+    const ALL: [TimeUnit; 6] = [
+        TimeUnit::Year,
+        TimeUnit::Week,
+        TimeUnit::Day,
+        TimeUnit::Hour,
+        TimeUnit::Minute,
+        TimeUnit::Second,
+    ];
+
+    pub fn secs(self) -> u64 {
+        match self {
+            TimeUnit::Second => 1,
+            TimeUnit::Minute => 60,
+            TimeUnit::Hour => 60 * 60,
+            TimeUnit::Day => 24 * 60 * 60,
+            TimeUnit::Week => 7 * 24 * 60 * 60,
+            TimeUnit::Year => 365 * 24 * 60 * 60,
+        }
+    }
+
+    pub fn suffix(self) -> &'static str {
+        match self {
+            TimeUnit::Second => "s",
+            TimeUnit::Minute => "m",
+            TimeUnit::Hour => "h",
+            TimeUnit::Day => "d",
+            TimeUnit::Week => "w",
+            TimeUnit::Year => "y",
+        }
+    }
+
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        TimeUnit::ALL.into_iter().find(|u| u.suffix() == suffix)
+    }
+}
+
+/// Which units `format_duration` writes a span in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurationStyle {
+    /// Spans longer than this unit are still counted in it ("36500d").
+    pub largest: TimeUnit,
+    /// Anything shorter is dropped, not rounded.
+    pub smallest: TimeUnit,
+    /// Only the largest unit that fits ("3h") instead of every unit from
+    /// there down to `smallest` ("3h 0m").
+    pub compact: bool,
+}
+
+impl DurationStyle {
+    /// "2d 5h 30m", as uptimes are shown.
+    pub const UPTIME: Self = Self {
+        largest: TimeUnit::Day,
+        smallest: TimeUnit::Minute,
+        compact: false,
+    };
+    /// "12s" or "75m", for how long ago something happened or how long until
+    /// a retry.
+    pub const AGE: Self = Self {
+        largest: TimeUnit::Minute,
+        smallest: TimeUnit::Second,
+        compact: true,
+    };
+    /// "45s", "3h", "2w": one unit, as short as it gets.
+    pub const COMPACT: Self = Self {
+        largest: TimeUnit::Year,
+        smallest: TimeUnit::Second,
+        compact: true,
+    };
+}
+
+/// Write `secs` in the units of `style`. A span shorter than the smallest
+/// unit is "0" of it.
+pub fn format_duration(secs: u64, style: DurationStyle) -> String {
+    let units: Vec<TimeUnit> = TimeUnit::ALL
+        .into_iter()
+        .filter(|&u| u <= style.largest && u >= style.smallest)
+        .collect();
+    let first = units
+        .iter()
+        .position(|u| secs >= u.secs())
+        .unwrap_or(units.len() - 1);
+    let units = if style.compact {
+        &units[first..=first]
+    } else {
+        &units[first..]
+    };
+
+    let mut rest = secs;
+    let mut parts = Vec::with_capacity(units.len());
+    for unit in units {
+        parts.push(format!("{}{}", rest / unit.secs(), unit.suffix()));
+        rest %= unit.secs();
+    }
+    parts.join(" ")
+}
+
+/// "2d 5h 30m"; "-" for nothing reported and "?" for a counter no machine
+/// could have reached.
+pub fn format_uptime(seconds: u64) -> String {
+    if seconds == 0 {
+        return "-".to_string();
+    }
+    // Nothing stays up for a century; this is a wrapped or bogus counter
+    if seconds > MAX_PLAUSIBLE_UPTIME {
+        return "?".to_string();
+    }
+    format_duration(seconds, DurationStyle::UPTIME)
+}
+
+/// Parse a duration like "30s", "15m", "6h", "7d", "2w" or "1y", as used in
+/// the config and on the command line.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+    let unit = TimeUnit::from_suffix(unit).ok_or_else(|| {
+        format!(
+            "invalid duration '{}' (expected a number followed by s, m, h, d, w or y)",
+            value
+        )
+    })?;
+    number
+        .checked_mul(unit.secs())
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{}' is too long", value))
+}
+
+/// How byte counts are written (`[ui] units`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Powers of 1024 with the short "KB", "MB", "GB" labels pulse has
+    /// always shown.
+    #[default]
+    Short,
+    /// Powers of 1024 labelled "KiB", "MiB", "GiB" (IEC).
+    Binary,
+    /// Powers of 1000 labelled "kB", "MB", "GB" (SI).
+    Decimal,
+}
+
+impl Units {
+    fn base(self) -> u64 {
+        match self {
+            Units::Short | Units::Binary => 1024,
+            Units::Decimal => 1000,
+        }
+    }
+
+    /// Labels for kilo, mega, giga and tera.
+    fn labels(self) -> [&'static str; 4] {
+        match self {
+            Units::Short => ["KB", "MB", "GB", "TB"],
+            Units::Binary => ["KiB", "MiB", "GiB", "TiB"],
+            Units::Decimal => ["kB", "MB", "GB", "TB"],
+        }
+    }
+}
+
+/// "512 B", "12 MB", "8.0 GB": whole numbers up to mega, one decimal above.
+pub fn format_bytes(bytes: u64, units: Units) -> String {
+    let base = units.base();
+    let [kilo, mega, giga, tera] = units.labels();
+    let scaled = |power: u32| bytes as f64 / base.pow(power) as f64;
+
+    if bytes >= base.pow(4) {
+        format!("{:.1} {}", scaled(4), tera)
+    } else if bytes >= base.pow(3) {
+        format!("{:.1} {}", scaled(3), giga)
+    } else if bytes >= base.pow(2) {
+        format!("{:.0} {}", scaled(2), mega)
+    } else if bytes >= base {
+        format!("{:.0} {}", scaled(1), kilo)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// How long a step took: "340ms" under a second, "1.2s" under a minute,
+/// then as an age ("3m").
+pub fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{}ms", elapsed.as_millis())
+    } else if elapsed < Duration::from_secs(60) {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format_duration(elapsed.as_secs(), DurationStyle::AGE)
+    }
+}
+
+/// "12 MB/s".
+pub fn format_rate(bytes_per_sec: f64, units: Units) -> String {
+    format!("{}/s", format_bytes(bytes_per_sec.round() as u64, units))
+}
+
+/// Signed difference `to - from`, e.g. "+2.0 GB" or "-512 MB".
+pub fn format_bytes_delta(from: u64, to: u64, units: Units) -> String {
+    if to >= from {
+        format!("+{}", format_bytes(to - from, units))
+    } else {
+        format!("-{}", format_bytes(from - to, units))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86400;
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_micros(900)), "0ms");
+        assert_eq!(format_elapsed(Duration::from_millis(340)), "340ms");
+        assert_eq!(format_elapsed(Duration::from_millis(1250)), "1.2s");
+        assert_eq!(format_elapsed(Duration::from_secs(185)), "3m");
+    }
+
+    #[test]
+    fn test_format_uptime_zero() {
+        assert_eq!(format_uptime(0), "-");
+    }
+
+    #[test]
+    fn test_format_uptime_rejects_absurd_values() {
+        assert_eq!(format_uptime(MAX_PLAUSIBLE_UPTIME), "36500d 0h 0m");
+        assert_eq!(format_uptime(MAX_PLAUSIBLE_UPTIME + 1), "?");
+        assert_eq!(format_uptime(u64::MAX), "?");
+    }
+
+    #[test]
+    fn test_format_uptime_minutes_only() {
+        assert_eq!(format_uptime(30), "0m");
+        assert_eq!(format_uptime(60), "1m");
+        assert_eq!(format_uptime(300), "5m");
+        assert_eq!(format_uptime(3540), "59m");
+    }
+
+    #[test]
+    fn test_format_uptime_hours_and_minutes() {
+        assert_eq!(format_uptime(3600), "1h 0m");
+        assert_eq!(format_uptime(3660), "1h 1m");
+        assert_eq!(format_uptime(7200), "2h 0m");
+        assert_eq!(format_uptime(86399), "23h 59m");
+    }
+
+    #[test]
+    fn test_format_uptime_days() {
+        assert_eq!(format_uptime(86400), "1d 0h 0m");
+        assert_eq!(format_uptime(90000), "1d 1h 0m");
+        assert_eq!(format_uptime(172800), "2d 0h 0m");
+        assert_eq!(format_uptime(192600), "2d 5h 30m");
+        assert_eq!(format_uptime(30 * DAY), "30d 0h 0m");
+    }
+
+    #[test]
+    fn test_format_duration_ages() {
+        let age = |secs| format_duration(secs, DurationStyle::AGE);
+        assert_eq!(age(0), "0s");
+        assert_eq!(age(59), "59s");
+        assert_eq!(age(60), "1m");
+        assert_eq!(age(119), "1m");
+        assert_eq!(age(4500), "75m");
+    }
+
+    #[test]
+    fn test_format_duration_compact_picks_the_largest_unit() {
+        let compact = |secs| format_duration(secs, DurationStyle::COMPACT);
+        assert_eq!(compact(45), "45s");
+        assert_eq!(compact(3 * 3600 + 59), "3h");
+        assert_eq!(compact(6 * DAY), "6d");
+        assert_eq!(compact(15 * DAY), "2w");
+        assert_eq!(compact(800 * DAY), "2y");
+    }
+
+    #[test]
+    fn test_format_duration_precise_keeps_zero_units() {
+        let style = DurationStyle {
+            compact: false,
+            ..DurationStyle::COMPACT
+        };
+        let precise = |secs| format_duration(secs, style);
+        assert_eq!(precise(0), "0s");
+        assert_eq!(precise(61), "1m 1s");
+        assert_eq!(precise(3600), "1h 0m 0s");
+        assert_eq!(precise(8 * DAY + 5), "1w 1d 0h 0m 5s");
+        assert_eq!(precise(365 * DAY + 2 * DAY + 300), "1y 0w 2d 0h 5m 0s");
+    }
+
+    #[test]
+    fn test_format_duration_custom_range() {
+        let style = DurationStyle {
+            largest: TimeUnit::Hour,
+            smallest: TimeUnit::Hour,
+            compact: true,
+        };
+        assert_eq!(format_duration(59 * 60, style), "0h");
+        assert_eq!(format_duration(3 * DAY, style), "72h");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration(" 6h "), Ok(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * DAY)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * DAY)));
+        assert_eq!(parse_duration("1y"), Ok(Duration::from_secs(365 * DAY)));
+        assert_eq!(parse_duration("0s"), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_malformed_values() {
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("5 m").is_err());
+        assert!(parse_duration("5M").is_err());
+        assert!(parse_duration("3x").is_err());
+        assert!(parse_duration("99999999999999999999y").is_err());
+        assert!(parse_duration("18446744073709551615y").is_err());
+    }
+
+    #[test]
+    fn test_parsed_durations_format_back() {
+        for value in ["45s", "15m", "6h", "3d", "2w", "1y"] {
+            let secs = parse_duration(value).unwrap().as_secs();
+            assert_eq!(format_duration(secs, DurationStyle::COMPACT), value);
+        }
+    }
+
+    #[test]
+    fn test_format_bytes_short() {
+        let short = |bytes| format_bytes(bytes, Units::Short);
+        assert_eq!(short(0), "0 B");
+        assert_eq!(short(512), "512 B");
+        assert_eq!(short(1023), "1023 B");
+        assert_eq!(short(1024), "1 KB");
+        assert_eq!(short(2048), "2 KB");
+        assert_eq!(short(1048575), "1024 KB");
+        assert_eq!(short(1048576), "1 MB");
+        assert_eq!(short(536870912), "512 MB");
+        assert_eq!(short(1073741824), "1.0 GB");
+        assert_eq!(short(8589934592), "8.0 GB");
+        assert_eq!(short(1099511627776), "1.0 TB");
+        assert_eq!(short(2199023255552), "2.0 TB");
+        assert_eq!(short(u64::MAX), "16777216.0 TB");
+    }
+
+    #[test]
+    fn test_format_bytes_binary() {
+        let binary = |bytes| format_bytes(bytes, Units::Binary);
+        assert_eq!(binary(1000), "1000 B");
+        assert_eq!(binary(1024), "1 KiB");
+        assert_eq!(binary(1048576), "1 MiB");
+        assert_eq!(binary(1073741824), "1.0 GiB");
+        assert_eq!(binary(1099511627776), "1.0 TiB");
+    }
+
+    #[test]
+    fn test_format_bytes_decimal() {
+        let decimal = |bytes| format_bytes(bytes, Units::Decimal);
+        assert_eq!(decimal(999), "999 B");
+        assert_eq!(decimal(1000), "1 kB");
+        assert_eq!(decimal(1_500_000), "2 MB");
+        assert_eq!(decimal(1_000_000_000), "1.0 GB");
+        assert_eq!(decimal(1073741824), "1.1 GB");
+        assert_eq!(decimal(2_000_000_000_000), "2.0 TB");
+    }
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(format_rate(0.0, Units::Short), "0 B/s");
+        assert_eq!(format_rate(1536.4, Units::Short), "2 KB/s");
+        assert_eq!(format_rate(12.9 * 1048576.0, Units::Binary), "13 MiB/s");
+        assert_eq!(format_rate(2.5e9, Units::Decimal), "2.5 GB/s");
+    }
+
+    #[test]
+    fn test_format_bytes_delta() {
+        let gb = 1024 * 1024 * 1024;
+        assert_eq!(format_bytes_delta(2 * gb, 4 * gb, Units::Short), "+2.0 GB");
+        assert_eq!(format_bytes_delta(4 * gb, 2 * gb, Units::Short), "-2.0 GB");
+        assert_eq!(format_bytes_delta(gb, gb, Units::Short), "+0 B");
+        assert_eq!(format_bytes_delta(0, 1000, Units::Decimal), "+1 kB");
+    }
+}
@@ -0,0 +1,239 @@
+//! Grouped container display. Each way of grouping is a key function;
+//! `group()` and the totals work the same for all of them.
+
+use crate::models::{Container, ContainerStatus};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GroupBy {
+    #[default]
+    Off,
+    Node,
+    /// Naming-convention prefix, e.g. "prod" of "prod-web-01".
+    Prefix,
+}
+
+impl GroupBy {
+    pub fn next(self) -> Self {
+        match self {
+            GroupBy::Off => GroupBy::Node,
+            GroupBy::Node => GroupBy::Prefix,
+            GroupBy::Prefix => GroupBy::Off,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GroupBy::Off => "off",
+            GroupBy::Node => "node",
+            GroupBy::Prefix => "prefix",
+        }
+    }
+}
+
+/// How names like `<env>-<service>-<nn>` are split into a group prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamePrefix {
+    pub separator: String,
+    /// How many leading parts make up the prefix.
+    pub depth: usize,
+}
+
+impl Default for NamePrefix {
+    fn default() -> Self {
+        Self {
+            separator: "-".to_string(),
+            depth: 1,
+        }
+    }
+}
+
+impl NamePrefix {
+    /// The first `depth` parts of the name: "prod-web-01" is "prod" at depth
+    /// 1 and "prod-web" at depth 2. A name needs at least one part after the
+    /// prefix, so "nas" and (at depth 2) "prod-web" have none.
+    pub fn key(&self, name: &str) -> Option<String> {
+        if self.separator.is_empty() || self.depth == 0 {
+            return None;
+        }
+        let parts: Vec<&str> = name.splitn(self.depth + 1, &*self.separator).collect();
+        if parts.len() <= self.depth || parts.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+        Some(parts[..self.depth].join(&self.separator))
+    }
+}
+
+pub struct Group<'a> {
+    /// None for the items the key function has no group for.
+    pub key: Option<String>,
+    pub members: Vec<&'a Container>,
+}
+
+impl Group<'_> {
+    pub fn label(&self) -> &str {
+        self.key.as_deref().unwrap_or("(other)")
+    }
+
+    pub fn running(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|c| c.status == ContainerStatus::Running)
+            .count()
+    }
+
+    pub fn memory_used(&self) -> u64 {
+        self.members.iter().map(|c| c.memory_used).sum()
+    }
+
+    pub fn memory_max(&self) -> u64 {
+        self.members.iter().map(|c| c.memory_max).sum()
+    }
+
+    /// CPU as a percent of all the cores allocated to the group.
+    pub fn cpu_of_allocation(&self) -> f64 {
+        let cores: u32 = self.members.iter().map(|c| c.max_cpu).sum();
+        if cores == 0 {
+            return 0.0;
+        }
+        let used: f64 = self
+            .members
+            .iter()
+            .map(|c| c.cpu_usage * c.max_cpu as f64)
+            .sum();
+        used / cores as f64
+    }
+
+    /// The group's totals, detached from the containers they came from.
+    pub fn summary(&self, cpu: f64) -> GroupSummary {
+        GroupSummary {
+            key: self.key.clone(),
+            running: self.running(),
+            total: self.members.len(),
+            cpu,
+            memory_used: self.memory_used(),
+            memory_max: self.memory_max(),
+        }
+    }
+}
+
+/// Totals of one group, kept between refreshes for the groups panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupSummary {
+    pub key: Option<String>,
+    pub running: usize,
+    pub total: usize,
+    pub cpu: f64,
+    pub memory_used: u64,
+    pub memory_max: u64,
+}
+
+impl GroupSummary {
+    pub fn label(&self) -> &str {
+        self.key.as_deref().unwrap_or("(no pool)")
+    }
+}
+
+/// Split containers into groups by `key`: groups in key order, the ungrouped
+/// last, and each group's members in their original order.
+pub fn group<'a>(
+    containers: Vec<&'a Container>,
+    key: impl Fn(&Container) -> Option<String>,
+) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group> = Vec::new();
+    for container in containers {
+        let k = key(container);
+        match groups.iter_mut().find(|g| g.key == k) {
+            Some(group) => group.members.push(container),
+            None => groups.push(Group {
+                key: k,
+                members: vec![container],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| (a.key.is_none(), &a.key).cmp(&(b.key.is_none(), &b.key)));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GuestId;
+
+    fn container(name: &str, node: &str, cpu: f64, max_cpu: u32) -> Container {
+        Container {
+            id: GuestId::new("test", name),
+            cpu_usage: cpu,
+            max_cpu,
+            memory_used: 256,
+            memory_max: 1024,
+            uptime: 3600,
+            ..Container::test("test", 100, name, node)
+        }
+    }
+
+    #[test]
+    fn test_prefix_keys() {
+        let prefix = NamePrefix::default();
+        assert_eq!(prefix.key("prod-web-01").as_deref(), Some("prod"));
+        assert_eq!(prefix.key("lab-db").as_deref(), Some("lab"));
+        assert_eq!(prefix.key("nas"), None);
+        assert_eq!(prefix.key("-leading"), None);
+        assert_eq!(prefix.key("trailing-"), None);
+        assert_eq!(prefix.key(""), None);
+
+        let deep = NamePrefix {
+            separator: "-".to_string(),
+            depth: 2,
+        };
+        assert_eq!(deep.key("prod-web-01").as_deref(), Some("prod-web"));
+        assert_eq!(deep.key("prod-web"), None);
+
+        let dotted = NamePrefix {
+            separator: ".".to_string(),
+            depth: 1,
+        };
+        assert_eq!(dotted.key("prod.media.jellyfin").as_deref(), Some("prod"));
+        assert_eq!(dotted.key("prod-web-01"), None);
+    }
+
+    #[test]
+    fn test_group_orders_keys_and_keeps_member_order() {
+        let containers = [
+            container("prod-web-02", "pve1", 10.0, 1),
+            container("nas", "pve1", 10.0, 1),
+            container("lab-db-01", "pve2", 10.0, 1),
+            container("prod-web-01", "pve2", 10.0, 1),
+        ];
+        let prefix = NamePrefix::default();
+
+        let groups = group(containers.iter().collect(), |c| prefix.key(&c.name));
+        let labels: Vec<&str> = groups.iter().map(|g| g.label()).collect();
+        assert_eq!(labels, ["lab", "prod", "(other)"]);
+        let prod: Vec<&str> = groups[1].members.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(prod, ["prod-web-02", "prod-web-01"]);
+
+        // Same infrastructure, different key
+        let groups = group(containers.iter().collect(), |c| Some(c.node.clone()));
+        let labels: Vec<&str> = groups.iter().map(|g| g.label()).collect();
+        assert_eq!(labels, ["pve1", "pve2"]);
+    }
+
+    #[test]
+    fn test_group_totals() {
+        let containers = [
+            container("prod-a", "pve1", 100.0, 1),
+            container("prod-b", "pve1", 25.0, 3),
+        ];
+        let groups = group(containers.iter().collect(), |_| None);
+        assert_eq!(groups[0].running(), 2);
+        assert_eq!(groups[0].memory_used(), 512);
+        assert_eq!(groups[0].memory_max(), 2048);
+        // 1 + 0.75 of 4 allocated cores
+        assert_eq!(groups[0].cpu_of_allocation(), 43.75);
+
+        let summary = groups[0].summary(43.75);
+        assert_eq!(summary.label(), "(no pool)");
+        assert_eq!((summary.running, summary.total), (2, 2));
+        assert_eq!(summary.memory_max, 2048);
+    }
+}
@@ -0,0 +1,557 @@
+//! Time-series history of every refresh, stored in SQLite.
+//!
+//! Writes happen on a background thread so a slow disk never stalls the UI;
+//! failures come back as warnings for the status bar instead of errors.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, params};
+
+use crate::app::App;
+use crate::format::{Units, format_bytes};
+use crate::models::{ContainerStatus, NodeStatus, format_timestamp};
+use crate::report::{Usage, UsageByItem};
+
+/// How often old samples are pruned while recording.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Unix timestamp in seconds.
+    pub ts: i64,
+    pub provider: String,
+    /// "node" or "guest".
+    pub kind: String,
+    /// Node name or VMID.
+    pub item_id: String,
+    pub name: String,
+    pub cpu: f64,
+    pub mem_used: u64,
+    pub mem_total: u64,
+    pub status: String,
+    /// Seconds; None in samples recorded before uptimes were.
+    pub uptime: Option<u64>,
+}
+
+/// One sample per node and guest of every provider that is currently up.
+pub fn samples(app: &App, ts: i64) -> Vec<Sample> {
+    let mut samples = Vec::new();
+
+    for node in app
+        .nodes
+        .iter()
+        .filter(|n| !app.provider_is_down(n.provider()))
+    {
+        samples.push(Sample {
+            ts,
+            provider: node.provider().to_string(),
+            kind: "node".to_string(),
+            item_id: node.id.name.clone(),
+            name: node.name.clone(),
+            cpu: node.cpu_usage,
+            mem_used: node.memory_used,
+            mem_total: node.memory_total,
+            status: match node.status {
+                NodeStatus::Online => "online",
+                NodeStatus::Offline => "offline",
+            }
+            .to_string(),
+            uptime: Some(node.uptime),
+        });
+    }
+
+    for container in app
+        .containers
+        .iter()
+        .filter(|c| !app.provider_is_down(c.provider()))
+    {
+        samples.push(Sample {
+            ts,
+            provider: container.provider().to_string(),
+            kind: "guest".to_string(),
+            item_id: container.vmid.to_string(),
+            name: container.name.clone(),
+            cpu: container.cpu_usage,
+            mem_used: container.memory_used,
+            mem_total: container.memory_max,
+            status: match container.status {
+                ContainerStatus::Running => "running",
+                ContainerStatus::Stopped => "stopped",
+            }
+            .to_string(),
+            uptime: Some(container.uptime),
+        });
+    }
+
+    samples
+}
+
+/// The start of a `since` window ending at `now`; None if it reaches back
+/// further than a timestamp can.
+pub fn cutoff(now: i64, since: Duration) -> Option<i64> {
+    i64::try_from(since.as_secs())
+        .ok()
+        .and_then(|secs| now.checked_sub(secs))
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub struct HistoryStore {
+    conn: Connection,
+}
+
+impl HistoryStore {
+    /// Open the database, creating it and its parent directory if needed.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS samples (
+                ts INTEGER NOT NULL,
+                provider TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                cpu REAL NOT NULL,
+                mem_used INTEGER NOT NULL,
+                mem_total INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                uptime INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS samples_name_ts ON samples (name, ts);
+            CREATE INDEX IF NOT EXISTS samples_ts ON samples (ts);",
+        )?;
+        // Databases from before uptimes were recorded
+        if conn.prepare("SELECT uptime FROM samples LIMIT 0").is_err() {
+            conn.execute_batch("ALTER TABLE samples ADD COLUMN uptime INTEGER")?;
+        }
+        Ok(Self { conn })
+    }
+
+    pub fn insert(&mut self, samples: &[Sample]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO samples
+                 (ts, provider, kind, item_id, name, cpu, mem_used, mem_total, status, uptime)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for sample in samples {
+                stmt.execute(params![
+                    sample.ts,
+                    sample.provider,
+                    sample.kind,
+                    sample.item_id,
+                    sample.name,
+                    sample.cpu,
+                    sample.mem_used as i64,
+                    sample.mem_total as i64,
+                    sample.status,
+                    sample.uptime.map(|uptime| uptime as i64),
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Delete samples older than `before`; returns how many were removed.
+    pub fn prune(&self, before: i64) -> rusqlite::Result<usize> {
+        self.conn
+            .execute("DELETE FROM samples WHERE ts < ?1", params![before])
+    }
+
+    /// Samples for an item matched by name or id, oldest first.
+    pub fn query(&self, name: &str, since: i64) -> rusqlite::Result<Vec<Sample>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ts, provider, kind, item_id, name, cpu, mem_used, mem_total, status, uptime
+             FROM samples
+             WHERE (name = ?1 OR item_id = ?1) AND ts >= ?2
+             ORDER BY ts, provider",
+        )?;
+        let rows = stmt.query_map(params![name, since], read_sample)?;
+        rows.collect()
+    }
+
+    /// Every sample since `since`, oldest first, handed to `each` one at a
+    /// time so a long period never has to fit in memory.
+    pub fn each_since(&self, since: i64, mut each: impl FnMut(Sample)) -> rusqlite::Result<()> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ts, provider, kind, item_id, name, cpu, mem_used, mem_total, status, uptime
+             FROM samples
+             WHERE ts >= ?1
+             ORDER BY ts",
+        )?;
+        for sample in stmt.query_map(params![since], read_sample)? {
+            each(sample?);
+        }
+        Ok(())
+    }
+
+    /// Average and peak CPU and memory of every item since `since`.
+    pub fn usage(&self, since: i64) -> rusqlite::Result<UsageByItem> {
+        let mut stmt = self.conn.prepare(
+            "SELECT provider, kind, item_id, AVG(cpu), MAX(cpu), AVG(mem_used), MAX(mem_used)
+             FROM samples
+             WHERE ts >= ?1
+             GROUP BY provider, kind, item_id",
+        )?;
+        let rows = stmt.query_map(params![since], |row| {
+            Ok((
+                (row.get(0)?, row.get(1)?, row.get(2)?),
+                Usage {
+                    cpu_avg: row.get(3)?,
+                    cpu_peak: row.get(4)?,
+                    mem_avg: row.get(5)?,
+                    mem_peak: row.get::<_, i64>(6)? as u64,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+}
+
+/// A row selected with the columns in table order.
+fn read_sample(row: &rusqlite::Row) -> rusqlite::Result<Sample> {
+    Ok(Sample {
+        ts: row.get(0)?,
+        provider: row.get(1)?,
+        kind: row.get(2)?,
+        item_id: row.get(3)?,
+        name: row.get(4)?,
+        cpu: row.get(5)?,
+        mem_used: row.get::<_, i64>(6)? as u64,
+        mem_total: row.get::<_, i64>(7)? as u64,
+        status: row.get(8)?,
+        uptime: row.get::<_, Option<i64>>(9)?.map(|uptime| uptime as u64),
+    })
+}
+
+/// Records samples on a background thread.
+pub struct HistoryWriter {
+    sender: Option<Sender<Vec<Sample>>>,
+    warnings: Receiver<String>,
+    handle: Option<JoinHandle<()>>,
+    recorded: Option<Instant>,
+}
+
+impl HistoryWriter {
+    pub fn spawn(path: PathBuf, retention: Duration) -> Self {
+        let (sender, batches) = mpsc::channel::<Vec<Sample>>();
+        let (warn, warnings) = mpsc::channel();
+
+        let handle = std::thread::spawn(move || {
+            let mut store = match HistoryStore::open(&path) {
+                Ok(store) => store,
+                Err(e) => {
+                    let _ = warn.send(format!("cannot open {}: {}", path.display(), e));
+                    return;
+                }
+            };
+            let retention = retention.as_secs() as i64;
+            let mut last_prune: Option<Instant> = None;
+
+            for batch in batches {
+                if let Err(e) = store.insert(&batch) {
+                    let _ = warn.send(format!("write failed: {}", e));
+                }
+                if last_prune.is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL) {
+                    if let Err(e) = store.prune(unix_now() - retention) {
+                        let _ = warn.send(format!("prune failed: {}", e));
+                    }
+                    last_prune = Some(Instant::now());
+                }
+            }
+        });
+
+        Self {
+            sender: Some(sender),
+            warnings,
+            handle: Some(handle),
+            recorded: None,
+        }
+    }
+
+    /// Queue the current snapshot, once per completed refresh.
+    pub fn record(&mut self, app: &App) {
+        if app.last_refresh.is_none() || app.last_refresh == self.recorded {
+            return;
+        }
+        self.recorded = app.last_refresh;
+
+        let batch = samples(app, unix_now());
+        if let Some(sender) = &self.sender
+            && !batch.is_empty()
+        {
+            // The thread only stops early if the database could not be
+            // opened, which it has already reported
+            let _ = sender.send(batch);
+        }
+    }
+
+    pub fn take_warning(&self) -> Option<String> {
+        self.warnings.try_recv().ok()
+    }
+}
+
+impl Drop for HistoryWriter {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread finish the queued writes
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Render query results as a plain-text table.
+pub fn render_table(name: &str, samples: &[Sample], units: Units) -> String {
+    let mut out = String::new();
+
+    if samples.is_empty() {
+        let _ = writeln!(out, "No history for '{}' in that period.", name);
+        return out;
+    }
+
+    let _ = writeln!(
+        out,
+        "{:<19} {:<12} {:<8} {:>6} {:>22}",
+        "TIME (UTC)", "PROVIDER", "STATUS", "CPU", "MEMORY"
+    );
+    for sample in samples {
+        let _ = writeln!(
+            out,
+            "{:<19} {:<12} {:<8} {:>5.1}% {:>22}",
+            format_timestamp(sample.ts),
+            sample.provider,
+            sample.status,
+            sample.cpu,
+            format!(
+                "{} / {}",
+                format_bytes(sample.mem_used, units),
+                format_bytes(sample.mem_total, units)
+            ),
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ProviderStatus;
+    use crate::models::{Container, Node};
+
+    fn sample(ts: i64, name: &str, cpu: f64) -> Sample {
+        Sample {
+            ts,
+            provider: "homelab".to_string(),
+            kind: "guest".to_string(),
+            item_id: "101".to_string(),
+            name: name.to_string(),
+            cpu,
+            mem_used: 512 * 1024 * 1024,
+            mem_total: 1024 * 1024 * 1024,
+            status: "running".to_string(),
+            uptime: Some(3600),
+        }
+    }
+
+    #[test]
+    fn test_cutoff_refuses_windows_past_any_timestamp() {
+        assert_eq!(cutoff(1000, Duration::from_secs(400)), Some(600));
+        // Before 1970 is fine, it just finds everything
+        assert_eq!(cutoff(1000, Duration::from_secs(5000)), Some(-4000));
+        assert_eq!(cutoff(1000, Duration::from_secs(u64::MAX)), None);
+        assert_eq!(cutoff(-10, Duration::from_secs(i64::MAX as u64)), None);
+    }
+
+    #[test]
+    fn test_store_insert_query_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = HistoryStore::open(&dir.path().join("nested/history.db")).unwrap();
+
+        store
+            .insert(&[
+                sample(100, "nginx", 1.0),
+                sample(200, "nginx", 2.0),
+                sample(300, "nginx", 3.0),
+            ])
+            .unwrap();
+
+        let rows = store.query("nginx", 150).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], sample(200, "nginx", 2.0));
+
+        // Guests can also be looked up by VMID
+        assert_eq!(store.query("101", 0).unwrap().len(), 3);
+        assert!(store.query("postgres", 0).unwrap().is_empty());
+
+        assert_eq!(store.prune(250).unwrap(), 2);
+        assert_eq!(store.query("nginx", 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_old_databases_gain_the_uptime_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE samples (
+                    ts INTEGER NOT NULL, provider TEXT NOT NULL, kind TEXT NOT NULL,
+                    item_id TEXT NOT NULL, name TEXT NOT NULL, cpu REAL NOT NULL,
+                    mem_used INTEGER NOT NULL, mem_total INTEGER NOT NULL,
+                    status TEXT NOT NULL
+                );
+                INSERT INTO samples VALUES (100, 'homelab', 'guest', '101', 'nginx',
+                    1.0, 512, 1024, 'running');",
+            )
+            .unwrap();
+
+        let mut store = HistoryStore::open(&path).unwrap();
+        store.insert(&[sample(200, "nginx", 2.0)]).unwrap();
+        let mut seen = Vec::new();
+        store
+            .each_since(0, |sample| seen.push((sample.ts, sample.uptime)))
+            .unwrap();
+        assert_eq!(seen, [(100, None), (200, Some(3600))]);
+
+        // Opening it again doesn't add the column twice
+        assert!(HistoryStore::open(&path).is_ok());
+    }
+
+    #[test]
+    fn test_usage_averages_and_peaks_per_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = HistoryStore::open(&dir.path().join("history.db")).unwrap();
+        let mut busy = sample(300, "nginx", 90.0);
+        busy.mem_used = 1024 * 1024 * 1024;
+        let mut other = sample(300, "postgres", 5.0);
+        other.item_id = "102".to_string();
+        store
+            .insert(&[
+                sample(100, "nginx", 50.0),
+                sample(200, "nginx", 10.0),
+                busy,
+                other,
+            ])
+            .unwrap();
+
+        let usage = store.usage(150).unwrap();
+        assert_eq!(usage.len(), 2);
+        let nginx = usage[&(
+            "homelab".to_string(),
+            "guest".to_string(),
+            "101".to_string(),
+        )];
+        assert_eq!(nginx.cpu_avg, 50.0);
+        assert_eq!(nginx.cpu_peak, 90.0);
+        assert_eq!(nginx.mem_avg, 768.0 * 1024.0 * 1024.0);
+        assert_eq!(nginx.mem_peak, 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_samples_skip_down_providers() {
+        let mut app = App::new();
+        app.nodes.push(Node {
+            cpu_usage: 12.5,
+            cpu_cores: 8,
+            memory_used: 1024,
+            memory_total: 2048,
+            uptime: 60,
+            ..Node::test("homelab", "pve1")
+        });
+        app.containers.push(Container {
+            status: ContainerStatus::Stopped,
+            max_cpu: 2,
+            memory_max: 512,
+            ..Container::test("remote", 101, "nginx", "pve2")
+        });
+
+        let all = samples(&app, 42);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].kind, "node");
+        assert_eq!(all[0].item_id, "pve1");
+        assert_eq!(all[1].item_id, "101");
+        assert_eq!(all[1].status, "stopped");
+
+        let mut down = ProviderStatus::new("remote");
+        down.backoff.record_failure(Instant::now());
+        app.provider_status.push(down);
+        let up = samples(&app, 42);
+        assert_eq!(up.len(), 1);
+        assert_eq!(up[0].provider, "homelab");
+    }
+
+    #[test]
+    fn test_writer_records_once_per_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.db");
+        let mut app = App::new();
+        app.nodes.push(Node {
+            cpu_usage: 5.0,
+            cpu_cores: 4,
+            memory_used: 1,
+            memory_total: 2,
+            uptime: 1,
+            ..Node::test("homelab", "pve1")
+        });
+
+        {
+            let mut writer = HistoryWriter::spawn(path.clone(), Duration::from_secs(3600));
+            // Nothing to record before the first refresh
+            writer.record(&app);
+            app.last_refresh = Some(Instant::now());
+            writer.record(&app);
+            writer.record(&app);
+        }
+
+        let store = HistoryStore::open(&path).unwrap();
+        assert_eq!(store.query("pve1", 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_writer_reports_open_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory cannot be opened as a database
+        let writer = HistoryWriter::spawn(dir.path().to_path_buf(), Duration::from_secs(3600));
+        let warning = (0..100).find_map(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            writer.take_warning()
+        });
+        assert!(warning.unwrap().starts_with("cannot open"));
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render_table(
+            "nginx",
+            &[sample(1_700_000_000, "nginx", 3.25)],
+            Units::Short,
+        );
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("TIME (UTC)"));
+        assert!(lines[1].starts_with("2023-11-14 22:13:20 homelab"));
+        assert!(lines[1].contains("3.2%"));
+        assert!(lines[1].contains("512 MB / 1.0 GB"));
+
+        assert_eq!(
+            render_table("nginx", &[], Units::Short),
+            "No history for 'nginx' in that period.\n"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! The first load. Each provider's permission probe and first fetch run on
+//! a worker thread, which sends the results back one provider at a time;
+//! the main loop hands them to `App::load` between frames, so a slow
+//! provider holds up neither the first frame nor the keys.
+//!
+//! `Probe` and `Fetched` are also what the UI thread collects for a regular
+//! refresh, so both paths apply the same answers the same way.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::models::{ClusterStatus, Container, Node, PermissionIssue};
+use crate::providers::{Provider, SharedProvider};
+
+/// What a provider's permission probes found. Errors are kept as text so
+/// the results can cross threads.
+pub struct Probe {
+    pub permissions: Result<Vec<PermissionIssue>, String>,
+    /// Only asked once the permission probe got an answer.
+    pub actions: Option<Result<Vec<PermissionIssue>, String>>,
+}
+
+impl Probe {
+    pub fn run(provider: &dyn Provider) -> Self {
+        let permissions = provider.probe_permissions().map_err(|e| e.to_string());
+        let actions = permissions
+            .is_ok()
+            .then(|| provider.probe_actions().map_err(|e| e.to_string()));
+        Probe {
+            permissions,
+            actions,
+        }
+    }
+}
+
+/// One provider's answers to a refresh.
+pub struct Fetched {
+    pub name: String,
+    pub nodes: Result<Vec<Node>, String>,
+    pub containers: Result<Vec<Container>, String>,
+    pub cluster: Result<Option<ClusterStatus>, String>,
+    pub elapsed: Duration,
+}
+
+impl Fetched {
+    pub fn fetch(provider: &dyn Provider) -> Self {
+        let started = Instant::now();
+        provider.begin_refresh();
+        let nodes = provider.fetch_nodes().map_err(|e| e.to_string());
+        let containers = provider.fetch_containers().map_err(|e| e.to_string());
+        let cluster = provider.fetch_cluster_status().map_err(|e| e.to_string());
+        Fetched {
+            name: provider.name().to_string(),
+            nodes,
+            containers,
+            cluster,
+            elapsed: started.elapsed(),
+        }
+    }
+}
+
+/// A provider's first load: probed just before its first fetch, as the
+/// probe waits on the provider as long as a fetch does.
+pub struct Loaded {
+    pub probe: Probe,
+    pub fetched: Fetched,
+}
+
+impl Loaded {
+    pub fn fetch(provider: &dyn Provider) -> Self {
+        Loaded {
+            probe: Probe::run(provider),
+            fetched: Fetched::fetch(provider),
+        }
+    }
+}
+
+/// Loads providers on a background thread, in order.
+pub struct Loader {
+    results: Receiver<Loaded>,
+    done: bool,
+}
+
+impl Loader {
+    /// Start loading `providers`. The thread only holds on to the one it is
+    /// fetching, so a provider dropped by a reload before its turn is
+    /// skipped and closed as usual.
+    pub fn spawn(providers: Vec<SharedProvider>) -> Self {
+        let (sender, results) = mpsc::channel();
+        let providers: Vec<_> = providers.iter().map(Arc::downgrade).collect();
+        std::thread::spawn(move || {
+            for provider in providers {
+                let Some(provider) = provider.upgrade() else {
+                    continue;
+                };
+                if sender.send(Loaded::fetch(provider.as_ref())).is_err() {
+                    return;
+                }
+            }
+        });
+        Loader {
+            results,
+            done: false,
+        }
+    }
+
+    /// The providers loaded since the last call.
+    pub fn take(&mut self) -> Vec<Loaded> {
+        let mut loaded = Vec::new();
+        loop {
+            match self.results.try_recv() {
+                Ok(result) => loaded.push(result),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        loaded
+    }
+
+    /// Whether the thread is gone and every result taken: loaded all, or a
+    /// provider panicked and took the thread with it.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
@@ -0,0 +1,352 @@
+//! pulse's own log (`--log-file`). Records go to the file and to a ring
+//! buffer of the last `LOG_CAPACITY` lines, which the `l` popup reads so the
+//! log can be followed without leaving the TUI. The buffer starts with the
+//! tail of the file, so earlier runs show too.
+//!
+//! Other crates log through the same `log` facade; only their warnings and
+//! errors are kept.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Lines the popup can scroll back through.
+pub const LOG_CAPACITY: usize = 500;
+
+/// Columns `h`/`l` move long lines by.
+const SIDE_STEP: u16 = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    /// Increases by one per line, so a position survives old lines
+    /// dropping off the front.
+    pub seq: u64,
+    pub level: Level,
+    /// The line as written to the file.
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+struct Ring {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+    capacity: usize,
+}
+
+/// The last lines logged, shared between the logger and the UI.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    ring: Arc<Mutex<Ring>>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ring: Arc::new(Mutex::new(Ring {
+                capacity,
+                ..Ring::default()
+            })),
+        }
+    }
+
+    pub fn push(&self, level: Level, text: String) {
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        let seq = ring.next_seq;
+        ring.next_seq += 1;
+        ring.lines.push_back(LogLine { seq, level, text });
+        while ring.lines.len() > ring.capacity {
+            ring.lines.pop_front();
+        }
+    }
+
+    /// The buffered lines at `min` or more severe, oldest first.
+    pub fn lines(&self, min: LevelFilter) -> Vec<LogLine> {
+        let ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        ring.lines
+            .iter()
+            .filter(|line| line.level <= min)
+            .cloned()
+            .collect()
+    }
+}
+
+/// "2026-10-17 14:03:12 WARN  homelab: connection refused"
+pub fn format_line(timestamp: &str, level: Level, message: &str) -> String {
+    format!("{} {:<5} {}", timestamp, level, message)
+}
+
+/// The level of a line read back from the file; lines that aren't ours
+/// (a wrapped message, say) count as info.
+pub fn parse_level(line: &str) -> Level {
+    line.split_whitespace()
+        .nth(2)
+        .and_then(|word| word.parse().ok())
+        .unwrap_or(Level::Info)
+}
+
+struct FileLogger {
+    file: Mutex<File>,
+    buffer: LogBuffer,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("pulse") || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let line = format_line(&timestamp, record.level(), &record.args().to_string());
+        if let Ok(mut file) = self.file.lock() {
+            // Nowhere to report a failed write to; the popup still has it
+            let _ = writeln!(file, "{}", line);
+        }
+        self.buffer.push(record.level(), line);
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Fill a buffer with the tail of the log at `path`.
+fn read_tail(path: &Path, buffer: &LogBuffer) -> io::Result<()> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut tail = VecDeque::with_capacity(LOG_CAPACITY);
+    for line in BufReader::new(file).lines() {
+        tail.push_back(line?);
+        if tail.len() > LOG_CAPACITY {
+            tail.pop_front();
+        }
+    }
+    for line in tail {
+        buffer.push(parse_level(&line), line);
+    }
+    Ok(())
+}
+
+/// Log to `path` (appending) from here on, and return the buffer the popup
+/// reads.
+pub fn init(path: &Path) -> io::Result<LogBuffer> {
+    let buffer = LogBuffer::new(LOG_CAPACITY);
+    read_tail(path, &buffer)?;
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let logger = FileLogger {
+        file: Mutex::new(file),
+        buffer: buffer.clone(),
+    };
+    log::set_boxed_logger(Box::new(logger)).map_err(io::Error::other)?;
+    log::set_max_level(LevelFilter::Debug);
+    Ok(buffer)
+}
+
+/// Where the `l` popup is: which level it shows down to, the line at its
+/// bottom (None follows new lines) and how far long lines are scrolled
+/// sideways.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogView {
+    pub min_level: LevelFilter,
+    pub anchor: Option<u64>,
+    pub column: u16,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            min_level: LevelFilter::Debug,
+            anchor: None,
+            column: 0,
+        }
+    }
+}
+
+impl LogView {
+    /// Index range of `lines` to show in `height` rows, ending at the
+    /// anchor, or at the line before it when the level filter hides it.
+    pub fn window(&self, lines: &[LogLine], height: usize) -> std::ops::Range<usize> {
+        let end = match self.anchor {
+            None => lines.len(),
+            Some(anchor) => lines.partition_point(|l| l.seq <= anchor),
+        };
+        // Keep a full page when the anchor is near the top
+        let end = end.max(height.min(lines.len()));
+        end.saturating_sub(height)..end
+    }
+
+    /// Move the bottom line `by` lines up; scrolling stops following.
+    pub fn scroll_up(&mut self, lines: &[LogLine], height: usize, by: usize) {
+        let window = self.window(lines, height);
+        let bottom = window.end.saturating_sub(by).max(height.min(lines.len()));
+        self.anchor = bottom.checked_sub(1).map(|i| lines[i].seq);
+    }
+
+    /// Move the bottom line `by` lines down; reaching the newest follows
+    /// again.
+    pub fn scroll_down(&mut self, lines: &[LogLine], height: usize, by: usize) {
+        let bottom = self.window(lines, height).end.saturating_add(by);
+        self.anchor = if bottom >= lines.len() {
+            None
+        } else {
+            Some(lines[bottom - 1].seq)
+        };
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.column = self.column.saturating_sub(SIDE_STEP);
+    }
+
+    pub fn scroll_right(&mut self) {
+        self.column = self.column.saturating_add(SIDE_STEP);
+    }
+
+    /// Show everything, then info and up, warnings and up, errors only.
+    pub fn cycle_level(&mut self) {
+        self.min_level = match self.min_level {
+            LevelFilter::Debug => LevelFilter::Info,
+            LevelFilter::Info => LevelFilter::Warn,
+            LevelFilter::Warn => LevelFilter::Error,
+            _ => LevelFilter::Debug,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(levels: &[Level]) -> LogBuffer {
+        let buffer = LogBuffer::new(LOG_CAPACITY);
+        for (i, level) in levels.iter().enumerate() {
+            buffer.push(*level, format!("line {}", i));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_ring_keeps_the_newest_lines() {
+        let buffer = LogBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(Level::Info, format!("line {}", i));
+        }
+        let lines = buffer.lines(LevelFilter::Trace);
+        assert_eq!(
+            lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(),
+            ["line 2", "line 3", "line 4"]
+        );
+        // Sequence numbers keep counting past the dropped lines
+        assert_eq!(lines.iter().map(|l| l.seq).collect::<Vec<_>>(), [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_lines_filter_by_level() {
+        let buffer = buffer(&[Level::Debug, Level::Warn, Level::Info, Level::Error]);
+        let texts = |min| {
+            buffer
+                .lines(min)
+                .into_iter()
+                .map(|l| l.text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(LevelFilter::Warn), ["line 1", "line 3"]);
+        assert_eq!(texts(LevelFilter::Error), ["line 3"]);
+        assert_eq!(texts(LevelFilter::Debug).len(), 4);
+    }
+
+    #[test]
+    fn test_clones_share_the_buffer() {
+        let buffer = LogBuffer::new(LOG_CAPACITY);
+        let ui = buffer.clone();
+        buffer.push(Level::Warn, "from the logger".to_string());
+        assert_eq!(ui.lines(LevelFilter::Debug).len(), 1);
+    }
+
+    #[test]
+    fn test_tail_of_an_existing_file_is_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pulse.log");
+        let mut text = String::new();
+        for i in 0..LOG_CAPACITY + 20 {
+            text.push_str(&format_line(
+                "2026-10-17 14:03:12",
+                Level::Info,
+                &i.to_string(),
+            ));
+            text.push('\n');
+        }
+        text.push_str(&format_line("2026-10-17 14:03:13", Level::Error, "boom"));
+        text.push_str("\n  continued without a prefix\n");
+        std::fs::write(&path, text).unwrap();
+
+        let buffer = LogBuffer::new(LOG_CAPACITY);
+        read_tail(&path, &buffer).unwrap();
+        let lines = buffer.lines(LevelFilter::Trace);
+        assert_eq!(lines.len(), LOG_CAPACITY);
+        assert_eq!(lines[0].text, "2026-10-17 14:03:12 INFO  22");
+        assert_eq!(lines[LOG_CAPACITY - 2].level, Level::Error);
+        assert_eq!(lines[LOG_CAPACITY - 1].level, Level::Info);
+
+        // No file yet is an empty log
+        read_tail(&dir.path().join("new.log"), &buffer).unwrap();
+    }
+
+    #[test]
+    fn test_view_follows_until_scrolled() {
+        let buffer = buffer(&[Level::Info; 10]);
+        let lines = buffer.lines(LevelFilter::Debug);
+        let mut view = LogView::default();
+        assert_eq!(view.window(&lines, 4), 6..10);
+
+        view.scroll_up(&lines, 4, 2);
+        assert_eq!(view.anchor, Some(7));
+        assert_eq!(view.window(&lines, 4), 4..8);
+        // Lines logged meanwhile don't move a scrolled view
+        buffer.push(Level::Info, "new".to_string());
+        let lines = buffer.lines(LevelFilter::Debug);
+        assert_eq!(view.window(&lines, 4), 4..8);
+
+        // The top stops at a full page
+        view.scroll_up(&lines, 4, 100);
+        assert_eq!(view.window(&lines, 4), 0..4);
+
+        view.scroll_down(&lines, 4, 100);
+        assert_eq!(view.anchor, None);
+        assert_eq!(view.window(&lines, 4), 7..11);
+    }
+
+    #[test]
+    fn test_view_anchor_survives_filtering_and_eviction() {
+        let buffer = buffer(&[Level::Debug, Level::Warn, Level::Debug, Level::Warn]);
+        let view = LogView {
+            anchor: Some(2),
+            ..LogView::default()
+        };
+        // The anchored debug line is hidden: the view ends at the warning before it
+        let warnings = buffer.lines(LevelFilter::Warn);
+        assert_eq!(view.window(&warnings, 1), 0..1);
+        assert_eq!(warnings[0].seq, 1);
+
+        let mut short = LogView::default();
+        short.cycle_level();
+        assert_eq!(short.min_level, LevelFilter::Info);
+        for _ in 0..3 {
+            short.cycle_level();
+        }
+        assert_eq!(short.min_level, LevelFilter::Debug);
+        assert_eq!(parse_level("2026-10-17 14:03:12 WARN  x"), Level::Warn);
+        assert_eq!(parse_level("garbage"), Level::Info);
+    }
+}
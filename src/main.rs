@@ -1,37 +1,266 @@
+mod agent;
+mod alarm;
 mod app;
+mod audit;
+mod backoff;
+mod changes;
+mod check;
 mod cli;
+mod clipboard;
+mod clone;
+mod commands;
 mod config;
+mod console;
+#[cfg(unix)]
+mod daemon;
+mod diagnostics;
+mod drain;
+mod duplicates;
+mod error;
+mod events;
+mod filter_cache;
+mod format;
+mod groups;
+#[cfg(feature = "history")]
+mod history;
+mod loader;
+mod logging;
+mod metrics;
 mod models;
+mod node_power;
+mod notes;
+mod output;
+#[cfg(feature = "history")]
+mod peaks;
+mod picker;
+mod presets;
+mod process;
+mod protocol;
 mod providers;
+mod query;
+mod queue;
+mod reload;
+mod report;
+mod resolve;
+mod resources;
+mod restarts;
+mod scheduler;
+mod severity;
+mod snooze;
+mod start;
+mod state;
+mod stats;
+mod text;
+mod theme;
+mod title;
+mod totals;
+mod trend;
 mod ui;
+mod view;
+mod watch;
 
-use std::time::{Duration, Instant};
+use std::io::{self, IsTerminal, Write};
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
 
 use clap::Parser;
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::DefaultTerminal;
 
-use crate::app::InputMode;
-use crate::providers::{Provider, ProxmoxProvider};
+use crate::app::{App, InputMode};
+use crate::clipboard::Handoff;
+use crate::clone::ClonePhase;
+use crate::drain::DrainPhase;
+use crate::node_power::NodePowerPhase;
+use crate::providers::Provider;
+use crate::queue::{ItemState, QueuePhase, QueuedAction};
+use crate::resources::ResourcePhase;
+use crate::start::StartPhase;
+
+/// How often `pulse guest` moves its action along while the task runs.
+const GUEST_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long `pulse attach` waits for the daemon's first snapshot.
+#[cfg(unix)]
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(10);
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = cli::Args::parse();
 
-    let path = std::path::Path::new(&args.config);
-    let config = config::load(path)?;
+    // First, so that whatever goes wrong below is logged too
+    let log = match &args.log_file {
+        Some(path) => match logging::init(path) {
+            Ok(buffer) => Some(buffer),
+            Err(e) => {
+                eprintln!("Cannot open log file {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-    let mut providers: Vec<Box<dyn Provider>> = Vec::new();
+    // The agent serves this machine's stats and needs no configuration
+    if let Some(cli::Command::Agent { listen }) = &args.command {
+        return agent::serve(listen);
+    }
 
-    if let Some(proxmox_configs) = &config.providers.proxmox {
-        for proxmox_config in proxmox_configs {
-            match ProxmoxProvider::new(proxmox_config) {
-                Ok(provider) => {
-                    providers.push(Box::new(provider));
+    let path = std::path::Path::new(&args.config);
+    let attaching = matches!(args.command, Some(cli::Command::Attach { .. }));
+    // `--local` needs no configuration at all, nor does a UI attached to a
+    // daemon, which has the providers
+    let config = if (args.local || attaching) && !path.exists() {
+        config::Config::default()
+    } else {
+        config::load(path)?
+    };
+
+    if let Some(cli::Command::History {
+        command,
+        name,
+        since,
+    }) = &args.command
+    {
+        #[cfg(feature = "history")]
+        {
+            let Some(database) = config.history.database_path() else {
+                eprintln!("History is not configured; set [history] database in the config.");
+                std::process::exit(1);
+            };
+            let store = history::HistoryStore::open(&database)?;
+            let now = history::unix_now();
+            if let Some(cli::HistoryCommand::Report {
+                since,
+                sort,
+                format,
+            }) = command
+            {
+                let since = since_cutoff(now, *since);
+                let mut report =
+                    peaks::PeakReport::new(peaks::max_gap(config.general.refresh_interval()?));
+                store.each_since(since, |sample| report.add(sample))?;
+                let items = report.finish(*sort);
+                match format {
+                    cli::HistoryFormat::Table => {
+                        print!("{}", peaks::render_table(&items, config.ui.units))
+                    }
+                    cli::HistoryFormat::Json => {
+                        print!("{}", peaks::render_json(&items, since, now))
+                    }
                 }
+                return Ok(());
+            }
+            // Required by clap unless `report` was given
+            let name = name.as_deref().unwrap_or_default();
+            let samples = store.query(name, since_cutoff(now, *since))?;
+            print!("{}", history::render_table(name, &samples, config.ui.units));
+            return Ok(());
+        }
+        #[cfg(not(feature = "history"))]
+        {
+            let _ = (command, name, since);
+            eprintln!("pulse was built without the \"history\" feature.");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(cli::Command::Audit {
+        command: cli::AuditCommand::Tail { lines },
+    }) = &args.command
+    {
+        let Some(path) = config.audit.log_path() else {
+            eprintln!("The audit log is not configured; set [audit] path in the config.");
+            std::process::exit(1);
+        };
+        match audit::tail(&path, *lines) {
+            Ok(entries) => print!("{}", entries),
+            Err(e) => {
+                eprintln!("Cannot read audit log {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(cli::Command::Daemon { socket }) = &args.command {
+        #[cfg(unix)]
+        return daemon::run(
+            path,
+            &config,
+            args.local,
+            args.read_only,
+            args.dry_run,
+            socket.as_deref(),
+        );
+        #[cfg(not(unix))]
+        {
+            let _ = socket;
+            eprintln!("pulse daemon needs unix sockets, which this platform lacks.");
+            std::process::exit(1);
+        }
+    }
+
+    let read_only = args.read_only || config.general.read_only;
+    let dry_run = args.dry_run || config.general.dry_run;
+    #[cfg(unix)]
+    let attached = match &args.command {
+        Some(cli::Command::Attach { socket }) => {
+            let socket = socket.clone().unwrap_or_else(daemon::default_socket);
+            match providers::Connection::open(&socket, ATTACH_TIMEOUT) {
+                Ok(connection) => Some(connection),
                 Err(e) => {
-                    eprintln!("Failed to create provider '{}': {}", proxmox_config.name, e);
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
             }
         }
+        _ => None,
+    };
+    #[cfg(not(unix))]
+    if attaching {
+        eprintln!("pulse attach needs unix sockets, which this platform lacks.");
+        std::process::exit(1);
+    }
+    // Attached, the daemon's modes apply on top of our own
+    #[cfg(unix)]
+    let info = attached.as_ref().map(|c| c.daemon()).unwrap_or_default();
+    #[cfg(unix)]
+    let (read_only, dry_run) = (read_only || info.read_only, dry_run || info.dry_run);
+
+    let names = reload::provider_names(&config, args.local);
+    let mut selection =
+        reload::Selection::choose(&args.provider, config.general.default_providers.as_deref());
+    // Attached, the daemon has its own providers
+    if !attaching && let Err(e) = selection.check(&names) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    // Only the UI asks; the other modes poll every provider unless told
+    if selection == reload::Selection::All
+        && names.len() > picker::PICKER_THRESHOLD
+        && args.command.is_none()
+        && !args.once
+        && io::stdout().is_terminal()
+    {
+        match picker::run(names)? {
+            Some(picked) => selection = reload::Selection::Only(picked),
+            None => return Ok(()),
+        }
+    }
+
+    #[cfg(unix)]
+    let (mut providers, errors) = match &attached {
+        Some(connection) => (
+            reload::ProviderSet::fixed(connection.providers()),
+            Vec::new(),
+        ),
+        None => reload::ProviderSet::build(&config, args.local, read_only, dry_run, &selection),
+    };
+    #[cfg(not(unix))]
+    let (mut providers, errors) =
+        reload::ProviderSet::build(&config, args.local, read_only, dry_run, &selection);
+    for error in errors {
+        eprintln!("{}", error);
     }
 
     if providers.is_empty() {
@@ -39,70 +268,650 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         std::process::exit(1);
     }
 
-    let mut terminal = ratatui::init();
-
     let mut app = app::App::new();
+    if let Err(e) = app.apply_config(&config) {
+        eprintln!("Invalid config: {}", e);
+        drop(providers);
+        std::process::exit(1);
+    }
+    // A reload keeps the active panel; startup begins on the first
+    app.active_panel = app.panels[0];
+    app.cpu_mode = config.ui.cpu_mode;
+    app.group_sort_by_node = config.ui.group_sort_by_node;
+    app.hide_templates = config.ui.hide_templates;
+    app.read_only = read_only;
+    app.dry_run = dry_run;
+    app.apply_startup_options(args.filter.as_deref(), args.sort, args.desc);
+    app.mark_dormant(&providers, providers.dormant());
+    app.log = log;
 
-    app.refresh(&providers);
+    if args.command == Some(cli::Command::Check) {
+        app.probe_permissions(&providers);
+        app.refresh(&providers);
+        let (report, healthy) = check::report(&app);
+        print!("{}", report);
+        // exit() skips destructors, and providers may own an ssh tunnel
+        drop(providers);
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
 
-    let mut last_refresh = Instant::now();
-    let refresh_interval = Duration::from_secs(5);
+    if let Some(cli::Command::Guest { command }) = &args.command {
+        let (action, target) = match command {
+            cli::GuestCommand::Start { target } => (QueuedAction::Start, target),
+            cli::GuestCommand::Shutdown { target } => (QueuedAction::Shutdown, target),
+        };
+        let done = run_guest_action(&mut app, &providers, action, target);
+        drop(providers);
+        std::process::exit(if done { 0 } else { 1 });
+    }
+
+    if let Some(cli::Command::Watch {
+        interval,
+        full,
+        color,
+    }) = &args.command
+    {
+        let interval = interval.unwrap_or(app.refresh_interval);
+        let watched = watch::run(&mut app, &providers, interval, *full, *color);
+        drop(providers);
+        return Ok(watched?);
+    }
+
+    if let Some(cli::Command::Report { output, since }) = &args.command {
+        app.refresh(&providers);
+        if let Some(ref error) = app.error_message {
+            eprintln!("{}", error);
+        }
+        let now = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        #[cfg(feature = "history")]
+        let usage = match config.history.database_path() {
+            Some(database) => history::HistoryStore::open(&database)
+                .and_then(|store| Ok(store.usage(since_cutoff(now, *since))?))
+                .unwrap_or_else(|e| {
+                    eprintln!("Report without history averages: {}", e);
+                    report::UsageByItem::new()
+                }),
+            None => report::UsageByItem::new(),
+        };
+        #[cfg(not(feature = "history"))]
+        let usage = {
+            let _ = since;
+            report::UsageByItem::new()
+        };
+        let written = std::fs::write(output, report::render(&app, now, &usage));
+        drop(providers);
+        match written {
+            Ok(()) => {
+                println!("Wrote {}", output);
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Cannot write {}: {}", output, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(feature = "history")]
+    let mut history = match config.history.database_path() {
+        Some(path) => {
+            let retention = format::parse_duration(&config.history.retention)?;
+            Some(history::HistoryWriter::spawn(path, retention))
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "history"))]
+    if config.history.database_path().is_some() {
+        eprintln!("Ignoring [history]: pulse was built without the \"history\" feature.");
+    }
+
+    if args.once {
+        app.refresh(&providers);
+        #[cfg(feature = "history")]
+        if let Some(history) = &mut history {
+            history.record(&app);
+        }
+        if let Some(ref error) = app.error_message {
+            eprintln!("{}", error);
+        }
+        match args.format {
+            cli::OutputFormat::Table => print!("{}", output::render_table(&app)),
+            cli::OutputFormat::Json => print!("{}", output::render_json(&app)),
+            cli::OutputFormat::Prometheus => print!("{}", metrics::render(&app)),
+        }
+        return Ok(());
+    }
+
+    app.load_state(config.general.state_path());
+    log::info!("pulse started with {} provider(s)", providers.len());
+    let hangup = reload::watch_hangup()?;
+    let mut terminal = ratatui::init();
+    let mut terminal_title = title::TerminalTitle::default();
+    // The first frame goes up before any provider is asked for anything;
+    // the first fetches run on the loader's thread meanwhile
+    app.start_loading(&providers);
+    let mut loader = loader::Loader::spawn(
+        providers
+            .shared()
+            .into_iter()
+            .filter(|p| app.is_provider_loading(p.name()))
+            .collect(),
+    );
 
     while app.running {
+        let frame_started = Instant::now();
         terminal.draw(|frame| ui::draw(frame, &app))?;
+        app.diagnostics.record_frame(frame_started.elapsed());
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                // Handle help popup first - any key closes it
-                if app.show_help {
-                    app.toggle_help();
-                    continue;
+        // Handle every key typed meanwhile (e.g. during a refresh) before
+        // refreshing again, so repeated `r` presses add up to one refresh
+        let mut timeout = Duration::from_millis(100);
+        while event::poll(timeout)? {
+            timeout = Duration::ZERO;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            app.schedule.interacted();
+            // Handle help popup first - j/k scroll, any other key closes it
+            if app.show_help {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.scroll_help_down(),
+                    KeyCode::Up | KeyCode::Char('k') => app.scroll_help_up(),
+                    _ => app.toggle_help(),
                 }
+                continue;
+            }
 
-                match app.input_mode {
-                    InputMode::Search => match key.code {
-                        KeyCode::Esc => {
-                            app.exit_search_mode();
-                            app.clear_search();
+            if app.show_errors {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.scroll_errors_down(),
+                    KeyCode::Up | KeyCode::Char('k') => app.scroll_errors_up(),
+                    KeyCode::Esc | KeyCode::Char('E') | KeyCode::Char('q') => {
+                        app.toggle_error_log()
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if app.log_view.is_some() {
+                let height = ui::logs_height(terminal.get_frame().area());
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.scroll_logs(1, height),
+                    KeyCode::Up | KeyCode::Char('k') => app.scroll_logs(-1, height),
+                    KeyCode::PageDown => app.scroll_logs(height as isize, height),
+                    KeyCode::PageUp => app.scroll_logs(-(height as isize), height),
+                    KeyCode::Char('g') => app.scroll_logs(isize::MIN, height),
+                    KeyCode::Char('G') => app.scroll_logs(isize::MAX, height),
+                    KeyCode::Esc | KeyCode::Char('q') => app.log_view = None,
+                    code => {
+                        if let Some(view) = &mut app.log_view {
+                            match code {
+                                KeyCode::Left | KeyCode::Char('h') => view.scroll_left(),
+                                KeyCode::Right | KeyCode::Char('l') => view.scroll_right(),
+                                KeyCode::Char('f') => view.cycle_level(),
+                                _ => {}
+                            }
                         }
-                        KeyCode::Enter => {
-                            app.exit_search_mode();
+                    }
+                }
+                continue;
+            }
+
+            if app.show_debug {
+                if matches!(key.code, KeyCode::Esc | KeyCode::F(12) | KeyCode::Char('q')) {
+                    app.toggle_debug();
+                }
+                continue;
+            }
+
+            if app.show_events {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.scroll_events_down(),
+                    KeyCode::Up | KeyCode::Char('k') => app.scroll_events_up(),
+                    KeyCode::Esc | KeyCode::Char('L') | KeyCode::Char('q') => app.toggle_events(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(phase) = app.drain.as_ref().map(|d| d.phase) {
+                match (phase, key.code) {
+                    (DrainPhase::Confirm, KeyCode::Enter | KeyCode::Char('y')) => {
+                        app.confirm_drain()
+                    }
+                    (DrainPhase::Running, _) => {}
+                    (_, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('n' | 'q')) => {
+                        app.close_drain()
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(phase) = app.queue.as_ref().map(|q| q.phase) {
+                match (phase, key.code) {
+                    (QueuePhase::Confirm, KeyCode::Enter | KeyCode::Char('y')) => {
+                        app.confirm_queue()
+                    }
+                    (QueuePhase::Finished, KeyCode::Enter) => {
+                        if let Some(queue) = &mut app.queue {
+                            queue.show_details = !queue.show_details;
                         }
-                        KeyCode::Backspace => {
-                            app.pop_search_char();
+                    }
+                    (_, KeyCode::Esc | KeyCode::Char('n' | 'q' | 'c')) => app.cancel_queue(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(phase) = app.start.as_ref().map(|s| s.phase.clone()) {
+                match (phase, key.code) {
+                    (StartPhase::Confirm, KeyCode::Enter | KeyCode::Char('y')) => {
+                        app.confirm_start(&providers, Instant::now())
+                    }
+                    (StartPhase::Running { .. }, _) => {}
+                    (_, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('n' | 'q')) => {
+                        app.close_start()
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(confirming) = app
+                .node_power
+                .as_ref()
+                .map(|r| r.phase == NodePowerPhase::Confirm)
+            {
+                match key.code {
+                    KeyCode::Esc => app.close_node_power(),
+                    KeyCode::Enter if confirming => {
+                        app.confirm_node_power(&providers, Instant::now())
+                    }
+                    // Typing the node's name is the confirmation
+                    KeyCode::Backspace if confirming => {
+                        if let Some(request) = &mut app.node_power {
+                            request.typed.pop();
                         }
-                        KeyCode::Char(c) => {
-                            app.push_search_char(c);
+                    }
+                    KeyCode::Char(c) if confirming => {
+                        if let Some(request) = &mut app.node_power {
+                            request.typed.push(c);
                         }
-                        _ => {}
-                    },
-                    InputMode::Normal => match key.code {
-                        KeyCode::Char('q') => app.quit(),
-                        KeyCode::Tab => app.next_panel(),
-                        KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
-                        KeyCode::Down | KeyCode::Char('j') => app.select_next(),
-                        KeyCode::Char('r') => app.refresh(&providers),
-                        KeyCode::Char('s') => app.cycle_sort(),
-                        KeyCode::Char('S') => app.toggle_sort_order(),
-                        KeyCode::Char('/') => app.enter_search_mode(),
-                        KeyCode::Char('?') => app.toggle_help(),
-                        KeyCode::Esc => {
-                            if !app.search_query.is_empty() {
-                                app.clear_search();
+                    }
+                    KeyCode::Enter | KeyCode::Char('q') => app.close_node_power(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(editing) = app.clone.as_ref().map(|c| c.phase == ClonePhase::Editing) {
+                match key.code {
+                    KeyCode::Esc if editing => app.close_clone(),
+                    KeyCode::Enter if editing => app.submit_clone(&providers, Instant::now()),
+                    code if editing => {
+                        if let Some(form) = &mut app.clone {
+                            match code {
+                                KeyCode::Tab | KeyCode::Down => form.next_field(),
+                                KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+                                KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right => form.cycle(),
+                                KeyCode::Backspace => form.pop_char(),
+                                KeyCode::Char(c) => form.push_char(c),
+                                _ => {}
+                            }
+                        }
+                    }
+                    // A running clone ignores keys; close_clone() refuses it
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => app.close_clone(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(editing) = app
+                .resources
+                .as_ref()
+                .map(|r| r.phase == ResourcePhase::Editing)
+            {
+                match key.code {
+                    KeyCode::Enter if editing => app.submit_resources(&providers, Instant::now()),
+                    KeyCode::Esc => app.close_resources(),
+                    code if editing => {
+                        if let Some(form) = &mut app.resources {
+                            match code {
+                                KeyCode::Tab | KeyCode::Down => form.next_field(),
+                                KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+                                KeyCode::Backspace => form.pop_char(),
+                                KeyCode::Char(c) => form.push_char(c),
+                                _ => {}
                             }
                         }
+                    }
+                    KeyCode::Enter | KeyCode::Char('q') => app.close_resources(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(index) = app.presets_popup {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next_preset(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous_preset(),
+                    KeyCode::Enter => app.apply_preset_at(index),
+                    KeyCode::Char(c @ '1'..='9') => app.apply_preset_at(c as usize - '1' as usize),
+                    KeyCode::Char('d') => app.delete_selected_preset(),
+                    KeyCode::Esc | KeyCode::Char('q' | 'F') => app.toggle_presets_popup(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if app.providers_popup.is_some() {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next_provider(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous_provider(),
+                    KeyCode::Enter => app.filter_selected_provider(),
+                    KeyCode::Char('d') => app.toggle_selected_provider(),
+                    KeyCode::Char('p') => app.probe_permissions(&providers),
+                    KeyCode::Esc | KeyCode::Char('q' | 'P') => app.toggle_providers_popup(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(selected) = app.snooze_menu.as_ref().map(|m| m.selected) {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next_snooze(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous_snooze(),
+                    KeyCode::Enter => app.choose_snooze(selected),
+                    KeyCode::Char(c @ '1'..='4') => app.choose_snooze(c as usize - '1' as usize),
+                    KeyCode::Esc | KeyCode::Char('q' | 'z') => app.close_snooze_menu(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(prompt) = &mut app.note_prompt {
+                match key.code {
+                    KeyCode::Esc => app.close_note_prompt(),
+                    KeyCode::Enter => app.submit_note(),
+                    KeyCode::Backspace => {
+                        prompt.text.pop();
+                    }
+                    KeyCode::Char(c) => prompt.text.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(prompt) = &mut app.preset_prompt {
+                match key.code {
+                    KeyCode::Esc => app.close_preset_prompt(),
+                    KeyCode::Enter => app.submit_preset_name(),
+                    KeyCode::Backspace => {
+                        prompt.name.pop();
+                        prompt.confirm_overwrite = false;
+                    }
+                    KeyCode::Char(c) => {
+                        prompt.name.push(c);
+                        prompt.confirm_overwrite = false;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(prompt) = &app.goto_prompt {
+                if prompt.confirmed && key.code != KeyCode::Enter {
+                    // After Enter, other keys leave the prompt and act as usual
+                    app.goto_prompt = None;
+                    if key.code == KeyCode::Esc {
+                        continue;
+                    }
+                } else {
+                    match key.code {
+                        KeyCode::Esc => app.cancel_goto(),
+                        KeyCode::Enter => app.confirm_goto(),
+                        KeyCode::Backspace => app.pop_goto_char(),
+                        KeyCode::Char(c) => app.push_goto_char(c),
                         _ => {}
-                    },
+                    }
+                    continue;
+                }
+            }
+
+            if app.visual_anchor.is_some() {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
+                    KeyCode::Enter | KeyCode::Char(' ') => app.confirm_visual(),
+                    KeyCode::Esc | KeyCode::Char('V') => app.cancel_visual(),
+                    _ => {}
                 }
+                continue;
+            }
+
+            match app.input_mode {
+                InputMode::Search => match key.code {
+                    KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.open_preset_prompt()
+                    }
+                    // Ctrl+I reaches most terminals as Tab
+                    KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.toggle_search_case()
+                    }
+                    KeyCode::Tab => app.toggle_search_case(),
+                    KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.toggle_search_whole_word()
+                    }
+                    KeyCode::Esc => {
+                        app.exit_search_mode();
+                        app.clear_search();
+                    }
+                    KeyCode::Enter => app.submit_search(),
+                    KeyCode::Backspace => {
+                        app.pop_search_char();
+                    }
+                    KeyCode::Char(c) => {
+                        app.push_search_char(c);
+                    }
+                    _ => {}
+                },
+                InputMode::Command => match key.code {
+                    KeyCode::Esc => app.exit_command_mode(),
+                    KeyCode::Enter => app.submit_command(&providers),
+                    KeyCode::Tab => app.complete_command(),
+                    KeyCode::Backspace if app.command_input.is_empty() => app.exit_command_mode(),
+                    KeyCode::Backspace => app.pop_command_char(),
+                    KeyCode::Char(c) => app.push_command_char(c),
+                    _ => {}
+                },
+                InputMode::Normal => match key.code {
+                    KeyCode::Char(':') => app.enter_command_mode(),
+                    // Keys are bound to the same commands the palette runs
+                    code => {
+                        if let Some(action) = commands::for_key(code, key.modifiers)
+                            && let Err(e) = app.run(action, &providers)
+                        {
+                            app.error_message = Some(e);
+                        }
+                    }
+                },
             }
         }
 
-        if last_refresh.elapsed() >= refresh_interval {
+        if let Some(command) = app.console_request.take() {
+            run_console(&mut terminal, &mut app, &command)?;
+        }
+        if let Some((handoff, url)) = app.url_request.take() {
+            let result = match handoff {
+                Handoff::Copy => clipboard::copy(&mut io::stdout(), &url),
+                Handoff::Open => clipboard::open(&url),
+            };
+            app.url_handed_off(handoff, result);
+        }
+
+        #[cfg(unix)]
+        if let Some(connection) = &attached
+            && connection.take_changed()
+        {
+            let info = connection.daemon();
+            providers = reload::ProviderSet::fixed(connection.providers());
+            app.read_only = args.read_only || config.general.read_only || info.read_only;
+            app.dry_run = args.dry_run || config.general.dry_run || info.dry_run;
+            app.probe_permissions(&providers);
             app.refresh(&providers);
-            last_refresh = Instant::now();
+            app.notice = Some("The daemon's providers changed".to_string());
+        }
+
+        if std::mem::take(&mut app.reload_requested) || hangup.swap(false, Ordering::Relaxed) {
+            let reloaded = if attaching {
+                #[cfg(unix)]
+                let info = attached.as_ref().map(|c| c.daemon()).unwrap_or_default();
+                #[cfg(unix)]
+                let modes = (info.read_only, info.dry_run);
+                #[cfg(not(unix))]
+                let modes = (false, false);
+                reload::reload_settings(
+                    path,
+                    args.read_only || modes.0,
+                    args.dry_run || modes.1,
+                    &mut app,
+                )
+            } else {
+                reload::reload(
+                    path,
+                    args.local,
+                    args.read_only,
+                    args.dry_run,
+                    &mut app,
+                    &mut providers,
+                )
+            };
+            match reloaded {
+                Ok(notice) => {
+                    log::info!("{}", notice);
+                    // New or changed providers may come with other tokens
+                    app.probe_permissions(&providers);
+                    app.notice = Some(notice);
+                }
+                Err(e) => app.record_error("config", format!("not reloaded: {}", e)),
+            }
+        }
+
+        for loaded in loader.take() {
+            app.load(&providers, loaded, Instant::now());
+        }
+        if loader.is_done() {
+            app.stop_loading();
+        }
+        reload::start_enabled(&mut app, &mut providers);
+        app.ensure_selected_details(&providers);
+        app.advance_drain(&providers, Instant::now());
+        app.advance_clone(&providers, Instant::now());
+        app.advance_start(&providers, Instant::now());
+        app.advance_queue(&providers, Instant::now());
+        app.refresh_if_due(&providers, Instant::now(), SystemTime::now());
+        if app.alarm.take_bell() {
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
+        // Only written when the summary changes
+        if app.terminal_title {
+            terminal_title.set(&mut io::stdout(), &app.health_title())?;
+        } else {
+            terminal_title.restore(&mut io::stdout())?;
+        }
+
+        #[cfg(feature = "history")]
+        if let Some(history) = &mut history {
+            history.record(&app);
+            while let Some(warning) = history.take_warning() {
+                app.record_error("history", warning);
+            }
         }
     }
 
+    terminal_title.restore(&mut io::stdout())?;
     ratatui::restore();
     Ok(())
 }
+
+/// Where a `--since` window starting `since` before `now` begins; a window
+/// longer than a timestamp can reach is refused.
+#[cfg(feature = "history")]
+fn since_cutoff(now: i64, since: Duration) -> i64 {
+    history::cutoff(now, since).unwrap_or_else(|| {
+        eprintln!("--since reaches back further than pulse can count.");
+        std::process::exit(1);
+    })
+}
+
+/// Hand the terminal to a console command and take it back once it exits,
+/// or straight away if it could not be started.
+fn run_console(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    command: &[String],
+) -> io::Result<()> {
+    ratatui::restore();
+    let result = Command::new(&command[0]).args(&command[1..]).status();
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    terminal.clear()?;
+    app.console_closed(&command[0], result);
+    Ok(())
+}
+
+/// `pulse guest`: queue one guest's action the way the UI would and wait
+/// for its task. Returns whether it succeeded.
+fn run_guest_action(
+    app: &mut App,
+    providers: &[Box<dyn Provider>],
+    action: QueuedAction,
+    target: &str,
+) -> bool {
+    app.probe_permissions(providers);
+    app.refresh(providers);
+    if let Some(ref error) = app.error_message {
+        eprintln!("{}", error);
+    }
+    if let Err(e) = app.queue_guest(action, target) {
+        eprintln!("{}", e);
+        return false;
+    }
+    app.confirm_queue();
+    loop {
+        app.advance_queue(providers, Instant::now());
+        if app
+            .queue
+            .as_ref()
+            .is_none_or(|q| q.phase == QueuePhase::Finished)
+        {
+            break;
+        }
+        std::thread::sleep(GUEST_POLL_INTERVAL);
+    }
+    if let Some(notice) = app.notice.take() {
+        println!("{}", notice);
+    }
+    let Some(item) = app.queue.as_ref().and_then(|q| q.items.first()) else {
+        return false;
+    };
+    match &item.state {
+        ItemState::Done => {
+            println!("{} {}: done", action.describe(), item.label);
+            true
+        }
+        ItemState::Failed(e) => {
+            eprintln!("{} {}: {}", action.describe(), item.label, e);
+            false
+        }
+        // Nothing cancels it, and a finished queue has nothing running
+        _ => false,
+    }
+}
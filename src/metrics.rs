@@ -0,0 +1,245 @@
+//! Prometheus text exposition of the current snapshot.
+//!
+//! This is the single place metric names, labels and HELP/TYPE lines are
+//! defined, so every way of exporting them stays identical.
+
+use std::fmt::Write;
+
+use crate::app::App;
+use crate::models::{ContainerStatus, NodeStatus};
+
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    samples: Vec<(String, f64)>,
+}
+
+impl Family {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            samples: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, labels: &[(&str, &str)], value: f64) {
+        self.samples.push((format_labels(labels), value));
+    }
+
+    fn write(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        for (labels, value) in &self.samples {
+            let _ = writeln!(out, "{}{} {}", self.name, labels, value);
+        }
+    }
+}
+
+/// Escape a label value per the exposition format: backslash, double quote
+/// and newline are the only characters that need it.
+pub fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+fn bool_value(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+/// Render gauges for the filtered view, ending with one
+/// `pulse_scrape_success` sample per provider.
+pub fn render(app: &App) -> String {
+    let mut node_up = Family::new("pulse_node_up", "Whether the node is online.");
+    let mut node_cpu = Family::new(
+        "pulse_node_cpu_percent",
+        "Node CPU usage in percent of all its cores.",
+    );
+    let mut node_mem_used = Family::new(
+        "pulse_node_memory_used_bytes",
+        "Memory used on the node in bytes.",
+    );
+    let mut node_mem_total = Family::new(
+        "pulse_node_memory_total_bytes",
+        "Memory installed on the node in bytes.",
+    );
+    let mut node_uptime = Family::new("pulse_node_uptime_seconds", "Node uptime in seconds.");
+
+    for node in app.filtered_nodes() {
+        let labels = [("provider", node.provider()), ("node", node.name.as_str())];
+        node_up.push(&labels, bool_value(node.status == NodeStatus::Online));
+        node_cpu.push(&labels, node.cpu_usage);
+        node_mem_used.push(&labels, node.memory_used as f64);
+        node_mem_total.push(&labels, node.memory_total as f64);
+        node_uptime.push(&labels, node.uptime as f64);
+    }
+
+    let mut guest_up = Family::new("pulse_guest_up", "Whether the guest is running.");
+    let mut guest_cpu = Family::new(
+        "pulse_guest_cpu_percent",
+        "Guest CPU usage in percent of the cores allocated to it.",
+    );
+    let mut guest_mem_used = Family::new(
+        "pulse_guest_memory_used_bytes",
+        "Memory used by the guest in bytes.",
+    );
+    let mut guest_mem_max = Family::new(
+        "pulse_guest_memory_max_bytes",
+        "Memory allocated to the guest in bytes.",
+    );
+    let mut guest_uptime = Family::new("pulse_guest_uptime_seconds", "Guest uptime in seconds.");
+
+    for container in app.filtered_containers() {
+        let vmid = container.vmid.to_string();
+        let labels = [
+            ("provider", container.provider()),
+            ("node", container.node.as_str()),
+            ("vmid", vmid.as_str()),
+            ("name", container.name.as_str()),
+            ("type", container.type_label()),
+        ];
+        guest_up.push(
+            &labels,
+            bool_value(container.status == ContainerStatus::Running),
+        );
+        guest_cpu.push(&labels, container.cpu_usage);
+        guest_mem_used.push(&labels, container.memory_used as f64);
+        guest_mem_max.push(&labels, container.memory_max as f64);
+        guest_uptime.push(&labels, container.uptime as f64);
+    }
+
+    let mut quorate = Family::new(
+        "pulse_cluster_quorate",
+        "Whether the Proxmox cluster has quorum.",
+    );
+    for status in &app.provider_status {
+        if let Some(cluster) = &status.cluster {
+            quorate.push(
+                &[("provider", &status.name), ("cluster", &cluster.name)],
+                bool_value(cluster.quorate),
+            );
+        }
+    }
+
+    let mut scrape_success = Family::new(
+        "pulse_scrape_success",
+        "Whether the last refresh of the provider succeeded.",
+    );
+    for status in app.provider_status.iter().filter(|s| !s.dormant) {
+        let success = status.last_success.is_some() && status.last_error.is_none();
+        scrape_success.push(&[("provider", &status.name)], bool_value(success));
+    }
+
+    let mut out = String::new();
+    for family in [
+        node_up,
+        node_cpu,
+        node_mem_used,
+        node_mem_total,
+        node_uptime,
+        guest_up,
+        guest_cpu,
+        guest_mem_used,
+        guest_mem_max,
+        guest_uptime,
+        quorate,
+        scrape_success,
+    ] {
+        family.write(&mut out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    use crate::app::ProviderStatus;
+    use crate::models::{Container, Node};
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.nodes = vec![Node {
+            cpu_usage: 12.5,
+            cpu_cores: 8,
+            memory_used: 8 * 1024 * 1024 * 1024,
+            memory_total: 16 * 1024 * 1024 * 1024,
+            uptime: 90061,
+            ..Node::test("homelab", "pve1")
+        }];
+        app.containers = vec![Container {
+            cpu_usage: 25.0,
+            max_cpu: 2,
+            memory_used: 512 * 1024 * 1024,
+            memory_max: 1024 * 1024 * 1024,
+            uptime: 3600,
+            ..Container::test("homelab", 100, "jellyfin", "pve1")
+        }];
+
+        let mut ok = ProviderStatus::new("homelab");
+        ok.last_success = Some(Instant::now());
+        let mut down = ProviderStatus::new("remote");
+        down.last_error = Some("connection refused".to_string());
+        app.provider_status = vec![ok, down];
+        app
+    }
+
+    #[test]
+    fn test_escape_label_value() {
+        assert_eq!(escape_label_value("plain"), "plain");
+        assert_eq!(escape_label_value(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(escape_label_value(r"C:\vms"), r"C:\\vms");
+        assert_eq!(escape_label_value("two\nlines"), r"two\nlines");
+    }
+
+    #[test]
+    fn test_render_gauges() {
+        let text = render(&test_app());
+
+        assert!(text.contains("# HELP pulse_node_up Whether the node is online.\n"));
+        assert!(text.contains("# TYPE pulse_node_up gauge\n"));
+        assert!(text.contains("pulse_node_up{provider=\"homelab\",node=\"pve1\"} 1\n"));
+        assert!(text.contains(
+            "pulse_node_memory_total_bytes{provider=\"homelab\",node=\"pve1\"} 17179869184\n"
+        ));
+        assert!(text.contains(
+            "pulse_guest_cpu_percent{provider=\"homelab\",node=\"pve1\",vmid=\"100\",name=\"jellyfin\",type=\"LXC\"} 25\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_ends_with_scrape_success_per_provider() {
+        let text = render(&test_app());
+
+        assert!(text.ends_with(
+            "pulse_scrape_success{provider=\"homelab\"} 1\npulse_scrape_success{provider=\"remote\"} 0\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_escapes_guest_names() {
+        let mut app = test_app();
+        app.containers[0].name = r#"odd "name" \ here"#.to_string();
+
+        let text = render(&app);
+
+        assert!(text.contains(r#"name="odd \"name\" \\ here""#));
+    }
+}
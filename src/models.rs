@@ -1,14 +1,105 @@
-#[derive(Debug, Clone)]
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Stable identity of a node: the provider that reported it plus the node's
+/// name within that provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId {
+    pub provider: String,
+    pub name: String,
+}
+
+impl NodeId {
+    pub fn new(provider: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            name: name.into(),
+        }
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.provider, self.name)
+    }
+}
+
+/// Stable identity of a guest: the provider that reported it plus an id that
+/// is unique within that provider (the vmid for Proxmox, the container id for
+/// Docker). Two providers may both report vmid 100; their ids still differ.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GuestId {
+    pub provider: String,
+    pub id: String,
+}
+
+impl GuestId {
+    pub fn new(provider: impl Into<String>, id: impl ToString) -> Self {
+        Self {
+            provider: provider.into(),
+            id: id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GuestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.provider, self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
+    /// Left out by command providers, which only know the name.
+    #[serde(default)]
+    pub id: NodeId,
     pub name: String,
     pub status: NodeStatus,
     pub cpu_usage: f64,
+    /// Logical CPUs on the host (0 if unknown).
+    #[serde(default)]
+    pub cpu_cores: u32,
     pub memory_used: u64,
     pub memory_total: u64,
+    /// Root filesystem usage; both 0 if the provider can't tell.
+    #[serde(default)]
+    pub disk_used: u64,
+    #[serde(default)]
+    pub disk_total: u64,
+    #[serde(default)]
     pub uptime: u64,
+    /// Network interfaces with traffic counters (empty if the provider has none).
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceStat>,
+    #[serde(default)]
+    pub disks: NodeDisks,
+    /// Packages with an update available; None if the provider can't tell.
+    #[serde(default)]
+    pub pending_updates: Option<u32>,
+    #[serde(default)]
+    pub package_updates: Vec<PackageUpdate>,
+    /// Linux pressure stall figures; None where the provider can't read them.
+    #[serde(default)]
+    pub pressure: Option<Pressure>,
+}
+
+/// Pressure stall information (`/proc/pressure`): the share of the last ten
+/// seconds (avg10) in which tasks stalled waiting, in percent. "some" counts
+/// time at least one task waited, "full" time all of them did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pressure {
+    pub cpu_some: f64,
+    pub memory_some: f64,
+    pub memory_full: f64,
 }
 
 impl Node {
+    pub fn provider(&self) -> &str {
+        &self.id.provider
+    }
+
     pub fn memory_percent(&self) -> f64 {
         if self.memory_total > 0 {
             (self.memory_used as f64 / self.memory_total as f64) * 100.0
@@ -16,34 +107,208 @@ impl Node {
             0.0
         }
     }
+
+    pub fn disk_percent(&self) -> Option<f64> {
+        (self.disk_total > 0).then(|| self.disk_used as f64 / self.disk_total as f64 * 100.0)
+    }
+}
+
+#[cfg(test)]
+impl Node {
+    /// An online node for tests, with everything but its identity zeroed;
+    /// tests override the fields they care about.
+    pub fn test(provider: &str, name: &str) -> Self {
+        Node {
+            id: NodeId::new(provider, name),
+            name: name.to_string(),
+            status: NodeStatus::Online,
+            cpu_usage: 0.0,
+            cpu_cores: 0,
+            memory_used: 0,
+            memory_total: 0,
+            disk_used: 0,
+            disk_total: 0,
+            uptime: 0,
+            interfaces: Vec::new(),
+            disks: NodeDisks::NotReported,
+            pending_updates: None,
+            package_updates: Vec::new(),
+            pressure: None,
+        }
+    }
+}
+
+/// Physical disks of a node, as far as the provider can tell.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum NodeDisks {
+    /// The provider doesn't report disks.
+    #[default]
+    NotReported,
+    /// Disks couldn't be listed; the reason is shown instead of an error.
+    Unavailable(String),
+    Listed(Vec<Disk>),
+}
+
+impl NodeDisks {
+    /// Disks that are failing or worn out, with what is wrong with each.
+    pub fn problems(&self) -> Vec<(&Disk, String)> {
+        match self {
+            NodeDisks::Listed(disks) => disks
+                .iter()
+                .filter_map(|d| d.problem().map(|p| (d, p)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A package the node's package manager can upgrade.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageUpdate {
+    pub package: String,
+    pub old_version: String,
+    pub new_version: String,
+}
+
+/// Remaining SSD life (percent) below which a disk is flagged.
+pub const DISK_WEAROUT_WARN: u8 = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Disk {
+    /// Device path, e.g. "/dev/sda".
+    pub dev_path: String,
+    pub model: String,
+    pub serial: String,
+    pub size: u64,
+    /// SMART health as reported ("PASSED", "OK", "FAILED", "UNKNOWN", ...).
+    pub health: String,
+    /// Remaining life of an SSD in percent; None for disks that don't report it.
+    pub wearout: Option<u8>,
+}
+
+impl Disk {
+    /// What is wrong with the disk, if anything. "UNKNOWN" health (common
+    /// for USB and RAID-backed disks) is not treated as a failure.
+    pub fn problem(&self) -> Option<String> {
+        let health = self.health.to_uppercase();
+        if !matches!(health.as_str(), "PASSED" | "OK" | "UNKNOWN" | "") {
+            return Some(format!("SMART {}", self.health));
+        }
+        match self.wearout {
+            Some(left) if left < DISK_WEAROUT_WARN => Some(format!("{}% life left", left)),
+            _ => None,
+        }
+    }
+}
+
+/// Traffic through one network interface of a node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceStat {
+    pub name: String,
+    /// Cumulative byte counters as reported by the provider.
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Bytes per second since the previous sample (0 on the first one).
+    pub rx_rate: f64,
+    pub tx_rate: f64,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl InterfaceStat {
+    pub fn total_rate(&self) -> f64 {
+        self.rx_rate + self.tx_rate
+    }
+}
+
+/// Fill in rates for `current` from the counters in `previous`, taken
+/// `elapsed` earlier. A counter that went backwards (wrapped, or the
+/// interface was reset) gives a rate of zero rather than a bogus spike.
+pub fn interface_rates(
+    previous: &[InterfaceStat],
+    mut current: Vec<InterfaceStat>,
+    elapsed: Duration,
+) -> Vec<InterfaceStat> {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return current;
+    }
+    for interface in &mut current {
+        if let Some(before) = previous.iter().find(|p| p.name == interface.name) {
+            interface.rx_rate = interface.rx_bytes.saturating_sub(before.rx_bytes) as f64 / secs;
+            interface.tx_rate = interface.tx_bytes.saturating_sub(before.tx_bytes) as f64 / secs;
+        }
+    }
+    current
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NodeStatus {
     Online,
     Offline,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ContainerType {
     VM,
     LXC,
+    /// A systemd unit on the local machine.
+    Service,
+    /// A process on the local machine, shown by the local provider.
+    Process,
+    /// A Docker container, reported by a pulse agent.
+    Docker,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Container {
+    /// Left out by command providers: the name stands in for it.
+    #[serde(default)]
+    pub id: GuestId,
+    #[serde(default)]
     pub vmid: u32,
     pub name: String,
     pub node: String,
     pub container_type: ContainerType,
     pub status: ContainerStatus,
+    /// Percent of the guest's own allocation, as Proxmox reports it.
     pub cpu_usage: f64,
+    /// Cores allocated to the guest (0 if unknown).
+    #[serde(default)]
+    pub max_cpu: u32,
     pub memory_used: u64,
     pub memory_max: u64,
+    #[serde(default)]
     pub uptime: u64,
+    /// Operation holding the guest lock (backup, snapshot, migrate, clone, ...).
+    #[serde(default)]
+    pub lock: Option<String>,
+    /// Configured to be watched but not present on the provider (always Stopped).
+    #[serde(default)]
+    pub missing: bool,
+    /// Proxmox pool (or Kubernetes namespace) the guest belongs to.
+    #[serde(default)]
+    pub pool: Option<String>,
+    /// A template: never started, only cloned from, so not "stopped" either.
+    #[serde(default)]
+    pub is_template: bool,
+    /// Bytes used on the guest's volume; None where the provider can't tell
+    /// (Proxmox only reports it for containers, a VM's reads 0).
+    #[serde(default)]
+    pub disk_used: Option<u64>,
+    /// Size of the guest's volume (0 if unknown).
+    #[serde(default)]
+    pub disk_max: u64,
+    /// What the guest runs ("debian", "Windows 10/2016/2019"), once its
+    /// details have been fetched; providers carry it over from then on.
+    #[serde(default)]
+    pub os_info: Option<String>,
 }
 
 impl Container {
+    pub fn provider(&self) -> &str {
+        &self.id.provider
+    }
+
     pub fn memory_percent(&self) -> f64 {
         if self.memory_max > 0 {
             (self.memory_used as f64 / self.memory_max as f64) * 100.0
@@ -52,133 +317,421 @@ impl Container {
         }
     }
 
+    /// Share of the volume in use; None when either figure is unknown.
+    pub fn disk_percent(&self) -> Option<f64> {
+        match self.disk_used {
+            Some(used) if self.disk_max > 0 => Some(used as f64 / self.disk_max as f64 * 100.0),
+            _ => None,
+        }
+    }
+
+    /// Why actions against this guest would fail right now, if it is locked.
+    pub fn lock_message(&self) -> Option<String> {
+        self.lock
+            .as_ref()
+            .map(|reason| format!("guest is locked ({})", reason))
+    }
+
     pub fn type_label(&self) -> &'static str {
         match self.container_type {
             ContainerType::VM => "VM",
             ContainerType::LXC => "LXC",
+            ContainerType::Service => "SVC",
+            ContainerType::Process => "PRC",
+            ContainerType::Docker => "DKR",
+        }
+    }
+}
+
+#[cfg(test)]
+impl Container {
+    /// A running LXC guest for tests, with everything but its identity
+    /// zeroed; tests override the fields they care about.
+    pub fn test(provider: &str, vmid: u32, name: &str, node: &str) -> Self {
+        Container {
+            id: GuestId::new(provider, vmid),
+            vmid,
+            name: name.to_string(),
+            node: node.to_string(),
+            container_type: ContainerType::LXC,
+            status: ContainerStatus::Running,
+            cpu_usage: 0.0,
+            max_cpu: 1,
+            memory_used: 0,
+            memory_max: 0,
+            uptime: 0,
+            lock: None,
+            missing: false,
+            pool: None,
+            is_template: false,
+            disk_used: None,
+            disk_max: 0,
+            os_info: None,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ContainerStatus {
     Running,
     Stopped,
 }
 
-pub fn format_uptime(seconds: u64) -> String {
-    if seconds == 0 {
-        return "-".to_string();
+/// Memory as seen from inside a VM, reported by the balloon driver.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuestMemory {
+    pub allocated: u64,
+    pub guest_used: u64,
+    pub guest_total: u64,
+}
+
+impl GuestMemory {
+    pub fn guest_percent(&self) -> f64 {
+        if self.guest_total > 0 {
+            (self.guest_used as f64 / self.guest_total as f64) * 100.0
+        } else {
+            0.0
+        }
     }
+}
+
+/// Per-guest data fetched on demand for the selected container only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuestDetails {
+    pub memory: Option<GuestMemory>,
+    /// QEMU guest agent state; None when it isn't enabled or couldn't be checked.
+    pub agent: Option<AgentStatus>,
+    /// The guest's own firewall; None where the provider has none.
+    pub firewall: Option<FirewallStatus>,
+    /// The guest's OS, as `Container::os_info` keeps it.
+    pub os_info: Option<String>,
+    /// Set when the provider's request budget was exhausted and nothing was fetched.
+    pub deferred: bool,
+}
 
-    let days = seconds / 86400;
-    let hours = (seconds % 86400) / 3600;
-    let minutes = (seconds % 3600) / 60;
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AgentStatus {
+    Responding,
+    /// Enabled in the VM config, but the VM is off or the agent doesn't answer.
+    NotResponding,
+}
+
+/// Whether a firewall filters traffic, as far as the token may see.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FirewallStatus {
+    /// Enabled, with this many rules when they could be counted. Enabled
+    /// without rules usually drops everything but the defaults allow.
+    On {
+        rules: Option<usize>,
+    },
+    Off,
+    /// The options could not be read, usually for lack of permission.
+    Unknown,
+}
 
-    if days > 0 {
-        format!("{}d {}h {}m", days, hours, minutes)
-    } else if hours > 0 {
-        format!("{}h {}m", hours, minutes)
-    } else {
-        format!("{}m", minutes)
+impl FirewallStatus {
+    /// e.g. "on (3 rules)", "off" or "unknown".
+    pub fn label(&self) -> String {
+        match self {
+            FirewallStatus::On { rules: Some(1) } => "on (1 rule)".to_string(),
+            FirewallStatus::On { rules: Some(n) } => format!("on ({} rules)", n),
+            FirewallStatus::On { rules: None } => "on".to_string(),
+            FirewallStatus::Off => "off".to_string(),
+            FirewallStatus::Unknown => "unknown".to_string(),
+        }
     }
 }
 
-pub fn format_bytes(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-    const TB: u64 = GB * 1024;
+/// How serious a log entry or detected event is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EventLevel {
+    Info,
+    Warning,
+    Error,
+}
 
-    if bytes >= TB {
-        format!("{:.1} TB", bytes as f64 / TB as f64)
-    } else if bytes >= GB {
-        format!("{:.1} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.0} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.0} KB", bytes as f64 / KB as f64)
-    } else {
-        format!("{} B", bytes)
+impl EventLevel {
+    /// From a syslog priority (0 emergency to 7 debug), as in the `pri` of
+    /// the Proxmox cluster log.
+    pub fn from_priority(priority: u8) -> Self {
+        match priority {
+            0..=3 => EventLevel::Error,
+            4 => EventLevel::Warning,
+            _ => EventLevel::Info,
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// An entry of a provider's own log, such as the Proxmox cluster log:
+/// logins, task starts, fencing, service restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterLogEntry {
+    /// Tells entries apart across fetches, together with `time`.
+    pub uid: String,
+    /// Unix seconds.
+    pub time: u64,
+    pub node: String,
+    /// The service that logged it, e.g. "pvedaemon".
+    pub tag: String,
+    pub user: Option<String>,
+    pub level: EventLevel,
+    pub message: String,
+}
 
-    // format_uptime tests
-    #[test]
-    fn test_format_uptime_zero() {
-        assert_eq!(format_uptime(0), "-");
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationJob {
+    pub guest: GuestId,
+    pub source: String,
+    pub target: String,
+    pub last_sync: u64,
+    pub fail_count: u32,
+    pub error: Option<String>,
+}
+
+impl ReplicationJob {
+    pub fn is_failing(&self) -> bool {
+        self.fail_count > 0 || self.error.is_some()
     }
 
-    #[test]
-    fn test_format_uptime_minutes_only() {
-        assert_eq!(format_uptime(60), "1m");
-        assert_eq!(format_uptime(300), "5m");
-        assert_eq!(format_uptime(3540), "59m");
+    /// One-line description of a failing job, e.g.
+    /// "Replication to pve2 failing since 3h 0m ago (2 failures): error text".
+    pub fn failure_summary(&self, now: u64) -> String {
+        let since = if self.last_sync == 0 {
+            "never synced".to_string()
+        } else {
+            format!(
+                "failing since {} ago",
+                crate::format::format_uptime(now.saturating_sub(self.last_sync))
+            )
+        };
+
+        let mut summary = format!(
+            "Replication to {} {} ({} failures)",
+            self.target, since, self.fail_count
+        );
+        if let Some(ref error) = self.error {
+            summary.push_str(": ");
+            summary.push_str(error);
+        }
+        summary
     }
+}
 
-    #[test]
-    fn test_format_uptime_hours_and_minutes() {
-        assert_eq!(format_uptime(3600), "1h 0m");
-        assert_eq!(format_uptime(3660), "1h 1m");
-        assert_eq!(format_uptime(7200), "2h 0m");
-        assert_eq!(format_uptime(86399), "23h 59m");
+/// A guest the cluster's HA manager looks after.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HaResource {
+    pub guest: GuestId,
+    /// Requested state: "started", "stopped", "disabled", "ignored"...
+    pub state: String,
+}
+
+impl HaResource {
+    /// Whether the HA manager acts on the guest; "ignored" leaves it alone.
+    pub fn is_managed(&self) -> bool {
+        self.state != "ignored"
     }
+}
 
-    #[test]
-    fn test_format_uptime_days() {
-        assert_eq!(format_uptime(86400), "1d 0h 0m");
-        assert_eq!(format_uptime(90000), "1d 1h 0m");
-        assert_eq!(format_uptime(172800), "2d 0h 0m");
-        assert_eq!(format_uptime(192600), "2d 5h 30m");
+/// A privilege the API token is missing, and what pulse can't show because of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionIssue {
+    pub privilege: String,
+    pub path: String,
+    pub consequence: String,
+}
+
+impl PermissionIssue {
+    pub fn new(privilege: &str, path: &str, consequence: &str) -> Self {
+        Self {
+            privilege: privilege.to_string(),
+            path: path.to_string(),
+            consequence: consequence.to_string(),
+        }
     }
+}
 
-    // format_bytes tests
-    #[test]
-    fn test_format_bytes_bytes() {
-        assert_eq!(format_bytes(0), "0 B");
-        assert_eq!(format_bytes(512), "512 B");
-        assert_eq!(format_bytes(1023), "1023 B");
+impl fmt::Display for PermissionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "token lacks {} on {} — {}",
+            self.privilege, self.path, self.consequence
+        )
+    }
+}
+
+/// Corosync state of a Proxmox cluster, as reported by one provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterStatus {
+    pub name: String,
+    pub quorate: bool,
+    pub nodes_online: u32,
+    pub nodes_total: u32,
+    /// The cluster-wide firewall; None when it wasn't checked.
+    pub firewall: Option<FirewallStatus>,
+    /// Problems the HA manager reports; empty without HA or the privilege.
+    #[serde(default)]
+    pub ha: HaStatus,
+}
+
+/// What the HA manager says is wrong right now.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HaStatus {
+    /// Guests whose HA service is in the error state.
+    pub errors: Vec<GuestId>,
+    /// Nodes being fenced.
+    pub fenced: Vec<String>,
+    /// Nodes the manager has lost track of.
+    pub unknown: Vec<String>,
+}
+
+impl ClusterStatus {
+    /// e.g. "cluster homelab: quorate (3/3)" or "cluster homelab: NO QUORUM (1/3)".
+    pub fn summary(&self) -> String {
+        format!(
+            "cluster {}: {} ({}/{})",
+            self.name,
+            if self.quorate { "quorate" } else { "NO QUORUM" },
+            self.nodes_online,
+            self.nodes_total
+        )
+    }
+}
+
+/// State of a long-running provider task (migration, shutdown).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TaskStatus {
+    Running,
+    Succeeded,
+    Failed(String),
+}
+
+/// Convert guest CPU usage from percent of its own allocation to percent of
+/// the host: a 1-core guest at 100% on a 16-core host uses 6.25% of the host.
+/// Unknown core counts leave the value unchanged.
+pub fn host_cpu_percent(guest_percent: f64, guest_cores: u32, host_cores: u32) -> f64 {
+    if guest_cores == 0 || host_cores == 0 {
+        return guest_percent;
+    }
+    guest_percent * guest_cores as f64 / host_cores as f64
+}
+
+/// Format a Unix timestamp as "YYYY-MM-DD HH:MM:SS" in UTC.
+pub fn format_timestamp(ts: i64) -> String {
+    let days = ts.div_euclid(86400);
+    let secs = ts.rem_euclid(86400);
+
+    // Civil-from-days (Howard Hinnant), valid for the proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Interface rate tests
+    fn interface(name: &str, rx_bytes: u64, tx_bytes: u64) -> InterfaceStat {
+        InterfaceStat {
+            name: name.to_string(),
+            rx_bytes,
+            tx_bytes,
+            rx_rate: 0.0,
+            tx_rate: 0.0,
+        }
     }
 
     #[test]
-    fn test_format_bytes_kilobytes() {
-        assert_eq!(format_bytes(1024), "1 KB");
-        assert_eq!(format_bytes(2048), "2 KB");
-        assert_eq!(format_bytes(1048575), "1024 KB");
+    fn test_interface_rates_per_second() {
+        let previous = [interface("vmbr0", 1000, 500), interface("eno1", 0, 0)];
+        let current = vec![
+            interface("vmbr0", 11000, 2500),
+            interface("tap100i0", 4000, 4000),
+        ];
+
+        let rates = interface_rates(&previous, current, Duration::from_secs(5));
+        assert_eq!((rates[0].rx_rate, rates[0].tx_rate), (2000.0, 400.0));
+        // New interface: no baseline yet
+        assert_eq!((rates[1].rx_rate, rates[1].tx_rate), (0.0, 0.0));
     }
 
     #[test]
-    fn test_format_bytes_megabytes() {
-        assert_eq!(format_bytes(1048576), "1 MB");
-        assert_eq!(format_bytes(536870912), "512 MB");
+    fn test_interface_rates_clamp_counter_reset() {
+        let previous = [interface("vmbr0", u64::MAX - 10, 5000)];
+        let current = vec![interface("vmbr0", 20, 6000)];
+
+        let rates = interface_rates(&previous, current, Duration::from_secs(2));
+        assert_eq!((rates[0].rx_rate, rates[0].tx_rate), (0.0, 500.0));
+    }
+
+    fn disk(health: &str, wearout: Option<u8>) -> Disk {
+        Disk {
+            dev_path: "/dev/sda".to_string(),
+            model: "SSD".to_string(),
+            serial: "S1".to_string(),
+            size: 0,
+            health: health.to_string(),
+            wearout,
+        }
     }
 
     #[test]
-    fn test_format_bytes_gigabytes() {
-        assert_eq!(format_bytes(1073741824), "1.0 GB");
-        assert_eq!(format_bytes(8589934592), "8.0 GB");
+    fn test_disk_problem() {
+        assert_eq!(disk("PASSED", Some(80)).problem(), None);
+        assert_eq!(disk("OK", None).problem(), None);
+        // USB and RAID-backed disks often can't report SMART
+        assert_eq!(disk("UNKNOWN", None).problem(), None);
+        assert_eq!(
+            disk("FAILED", Some(80)).problem().as_deref(),
+            Some("SMART FAILED")
+        );
+        assert_eq!(
+            disk("PASSED", Some(DISK_WEAROUT_WARN - 1))
+                .problem()
+                .as_deref(),
+            Some("9% life left")
+        );
+        assert_eq!(disk("PASSED", Some(DISK_WEAROUT_WARN)).problem(), None);
     }
 
     #[test]
-    fn test_format_bytes_terabytes() {
-        assert_eq!(format_bytes(1099511627776), "1.0 TB");
-        assert_eq!(format_bytes(2199023255552), "2.0 TB");
+    fn test_node_disks_problems() {
+        let listed = NodeDisks::Listed(vec![disk("PASSED", None), disk("FAILED", None)]);
+        let problems = listed.problems();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].1, "SMART FAILED");
+        assert!(
+            NodeDisks::Unavailable("forbidden".to_string())
+                .problems()
+                .is_empty()
+        );
     }
 
     // Node tests
     #[test]
     fn test_node_memory_percent() {
         let node = Node {
-            name: "test".to_string(),
-            status: NodeStatus::Online,
-            cpu_usage: 0.0,
+            cpu_cores: 4,
             memory_used: 512,
             memory_total: 1024,
-            uptime: 0,
+            ..Node::test("test", "test")
         };
         assert_eq!(node.memory_percent(), 50.0);
     }
@@ -186,29 +739,137 @@ mod tests {
     #[test]
     fn test_node_memory_percent_zero_total() {
         let node = Node {
-            name: "test".to_string(),
-            status: NodeStatus::Online,
-            cpu_usage: 0.0,
+            cpu_cores: 4,
             memory_used: 512,
-            memory_total: 0,
-            uptime: 0,
+            ..Node::test("test", "test")
         };
         assert_eq!(node.memory_percent(), 0.0);
     }
 
+    // GuestMemory tests
+    #[test]
+    fn test_guest_memory_percent() {
+        let memory = GuestMemory {
+            allocated: 8192,
+            guest_used: 1024,
+            guest_total: 4096,
+        };
+        assert_eq!(memory.guest_percent(), 25.0);
+
+        let empty = GuestMemory {
+            allocated: 8192,
+            guest_used: 1024,
+            guest_total: 0,
+        };
+        assert_eq!(empty.guest_percent(), 0.0);
+    }
+
+    // ReplicationJob tests
+    #[test]
+    fn test_replication_job_is_failing() {
+        let mut job = ReplicationJob {
+            guest: GuestId::new("test", 100),
+            source: "pve1".to_string(),
+            target: "pve2".to_string(),
+            last_sync: 1_700_000_000,
+            fail_count: 0,
+            error: None,
+        };
+        assert!(!job.is_failing());
+
+        job.fail_count = 2;
+        assert!(job.is_failing());
+
+        job.fail_count = 0;
+        job.error = Some("zfs error".to_string());
+        assert!(job.is_failing());
+    }
+
+    #[test]
+    fn test_replication_failure_summary() {
+        let mut job = ReplicationJob {
+            guest: GuestId::new("test", 100),
+            source: "pve1".to_string(),
+            target: "pve2".to_string(),
+            last_sync: 1_700_000_000,
+            fail_count: 2,
+            error: Some("zfs error".to_string()),
+        };
+        assert_eq!(
+            job.failure_summary(1_700_010_800),
+            "Replication to pve2 failing since 3h 0m ago (2 failures): zfs error"
+        );
+
+        job.last_sync = 0;
+        job.error = None;
+        assert_eq!(
+            job.failure_summary(1_700_010_800),
+            "Replication to pve2 never synced (2 failures)"
+        );
+    }
+
+    #[test]
+    fn test_permission_issue_message() {
+        let issue = PermissionIssue::new("VM.Audit", "/vms", "containers will not be shown");
+        assert_eq!(
+            issue.to_string(),
+            "token lacks VM.Audit on /vms — containers will not be shown"
+        );
+    }
+
+    #[test]
+    fn test_firewall_labels() {
+        assert_eq!(
+            FirewallStatus::On { rules: Some(3) }.label(),
+            "on (3 rules)"
+        );
+        assert_eq!(FirewallStatus::On { rules: Some(1) }.label(), "on (1 rule)");
+        assert_eq!(FirewallStatus::On { rules: None }.label(), "on");
+        assert_eq!(FirewallStatus::Off.label(), "off");
+        assert_eq!(FirewallStatus::Unknown.label(), "unknown");
+    }
+
+    #[test]
+    fn test_cluster_status_summary() {
+        let mut cluster = ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: true,
+            nodes_online: 3,
+            nodes_total: 3,
+            firewall: None,
+            ha: Default::default(),
+        };
+        assert_eq!(cluster.summary(), "cluster homelab: quorate (3/3)");
+
+        cluster.quorate = false;
+        cluster.nodes_online = 1;
+        assert_eq!(cluster.summary(), "cluster homelab: NO QUORUM (1/3)");
+    }
+
+    #[test]
+    fn test_host_cpu_percent() {
+        // A saturated 1-core guest on a 16-core host
+        assert_eq!(host_cpu_percent(100.0, 1, 16), 6.25);
+        // An 8-core guest at 12% uses about as much as that
+        assert_eq!(host_cpu_percent(12.5, 8, 16), 6.25);
+        // A guest with every host core allocated is already host-relative
+        assert_eq!(host_cpu_percent(40.0, 16, 16), 40.0);
+        assert_eq!(host_cpu_percent(0.0, 4, 16), 0.0);
+    }
+
+    #[test]
+    fn test_host_cpu_percent_unknown_cores() {
+        assert_eq!(host_cpu_percent(50.0, 0, 16), 50.0);
+        assert_eq!(host_cpu_percent(50.0, 2, 0), 50.0);
+    }
+
     // Container tests
     #[test]
     fn test_container_memory_percent() {
         let container = Container {
-            vmid: 100,
-            name: "test".to_string(),
-            node: "node1".to_string(),
-            container_type: ContainerType::LXC,
-            status: ContainerStatus::Running,
-            cpu_usage: 0.0,
             memory_used: 256,
             memory_max: 1024,
-            uptime: 0,
+            ..Container::test("test", 100, "test", "node1")
         };
         assert_eq!(container.memory_percent(), 25.0);
     }
@@ -216,45 +877,59 @@ mod tests {
     #[test]
     fn test_container_memory_percent_zero_max() {
         let container = Container {
-            vmid: 100,
-            name: "test".to_string(),
-            node: "node1".to_string(),
             container_type: ContainerType::VM,
             status: ContainerStatus::Stopped,
-            cpu_usage: 0.0,
             memory_used: 256,
-            memory_max: 0,
-            uptime: 0,
+            ..Container::test("test", 100, "test", "node1")
         };
         assert_eq!(container.memory_percent(), 0.0);
     }
 
+    #[test]
+    fn test_container_disk_percent() {
+        let container = |disk_used: Option<u64>, disk_max: u64| Container {
+            disk_used,
+            disk_max,
+            ..Container::test("test", 100, "test", "node1")
+        };
+        assert_eq!(container(Some(768), 1024).disk_percent(), Some(75.0));
+        assert_eq!(container(Some(0), 1024).disk_percent(), Some(0.0));
+        // No size, or no usage (a VM), is unknown rather than 0%
+        assert_eq!(container(Some(768), 0).disk_percent(), None);
+        assert_eq!(container(None, 1024).disk_percent(), None);
+    }
+
     #[test]
     fn test_container_type_label() {
         let vm = Container {
-            vmid: 100,
-            name: "test".to_string(),
-            node: "node1".to_string(),
             container_type: ContainerType::VM,
-            status: ContainerStatus::Running,
-            cpu_usage: 0.0,
-            memory_used: 0,
-            memory_max: 0,
-            uptime: 0,
+            ..Container::test("test", 100, "test", "node1")
         };
         assert_eq!(vm.type_label(), "VM");
 
-        let lxc = Container {
-            vmid: 101,
-            name: "test".to_string(),
-            node: "node1".to_string(),
-            container_type: ContainerType::LXC,
-            status: ContainerStatus::Running,
-            cpu_usage: 0.0,
-            memory_used: 0,
-            memory_max: 0,
-            uptime: 0,
-        };
+        let lxc = Container::test("test", 101, "test", "node1");
         assert_eq!(lxc.type_label(), "LXC");
     }
+
+    #[test]
+    fn test_container_lock_message() {
+        let mut container = Container {
+            container_type: ContainerType::VM,
+            ..Container::test("test", 100, "test", "node1")
+        };
+        assert!(container.lock_message().is_none());
+
+        container.lock = Some("backup".to_string());
+        assert_eq!(
+            container.lock_message().as_deref(),
+            Some("guest is locked (backup)")
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20");
+    }
 }
@@ -0,0 +1,224 @@
+//! Rebooting or shutting down a whole node (`B`, `X`). The popup first shows
+//! what happens to the guests running there: those the HA manager looks
+//! after are moved to another node, the rest go down with it. Nothing is
+//! sent until the node's name has been typed out.
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::{Units, format_bytes};
+use crate::models::{Container, HaResource, NodeId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodePower {
+    Reboot,
+    Shutdown,
+}
+
+impl NodePower {
+    /// The `command` Proxmox takes.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NodePower::Reboot => "reboot",
+            NodePower::Shutdown => "shutdown",
+        }
+    }
+
+    /// For titles and the audit log.
+    pub fn describe(self) -> &'static str {
+        match self {
+            NodePower::Reboot => "node reboot",
+            NodePower::Shutdown => "node shutdown",
+        }
+    }
+}
+
+/// What a node going down does to the guests running on it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GuestImpact {
+    /// HA-managed guests, which the HA manager moves elsewhere ("105 web").
+    pub migrated: Vec<String>,
+    /// Guests that are shut down with the node.
+    pub stopped: Vec<String>,
+    /// Memory the running guests are configured with.
+    pub memory: u64,
+    /// Why HA membership couldn't be read; every guest is then counted as
+    /// stopped, the worst case.
+    pub ha_error: Option<String>,
+}
+
+impl GuestImpact {
+    /// Split the running `guests` of a node by whether `ha` manages them.
+    /// `memory` is the node's allocation for running guests.
+    pub fn new(guests: &[&Container], ha: Result<&[HaResource], String>, memory: u64) -> Self {
+        let (migrated, stopped) = match &ha {
+            Ok(resources) => guests.iter().partition(|guest| {
+                resources
+                    .iter()
+                    .any(|r| r.guest == guest.id && r.is_managed())
+            }),
+            Err(_) => (Vec::new(), guests.to_vec()),
+        };
+        let label = |guest: &&Container| format!("{} {}", guest.vmid, guest.name);
+        Self {
+            migrated: migrated.iter().map(label).collect(),
+            stopped: stopped.iter().map(label).collect(),
+            memory,
+            ha_error: ha.err(),
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.migrated.len() + self.stopped.len()
+    }
+
+    /// The preview, one line each: "Running guests: 3 (12.0 GB configured)",
+    /// then which move and which stop.
+    pub fn summary(&self, units: Units) -> Vec<String> {
+        if self.running() == 0 {
+            return vec!["No guests running".to_string()];
+        }
+        let mut lines = vec![format!(
+            "Running guests: {} ({} configured)",
+            self.running(),
+            format_bytes(self.memory, units)
+        )];
+        if let Some(error) = &self.ha_error {
+            lines.push(format!(
+                "HA state unknown ({}), all may be stopped: {}",
+                error,
+                self.stopped.join(", ")
+            ));
+            return lines;
+        }
+        if !self.migrated.is_empty() {
+            lines.push(format!(
+                "Migrated by HA ({}): {}",
+                self.migrated.len(),
+                self.migrated.join(", ")
+            ));
+        }
+        if !self.stopped.is_empty() {
+            lines.push(format!(
+                "Stopped, not HA-managed ({}): {}",
+                self.stopped.len(),
+                self.stopped.join(", ")
+            ));
+        }
+        lines
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodePowerPhase {
+    /// Waiting for the node's name to be typed.
+    Confirm,
+    /// The provider accepted the command; the node drops out of the
+    /// list as it goes down.
+    Sent,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct NodePowerRequest {
+    pub node: NodeId,
+    pub command: NodePower,
+    /// Checked when the popup opened, from the last refresh.
+    pub impact: GuestImpact,
+    /// What has been typed so far.
+    pub typed: String,
+    pub phase: NodePowerPhase,
+}
+
+impl NodePowerRequest {
+    pub fn new(node: NodeId, command: NodePower, impact: GuestImpact) -> Self {
+        Self {
+            node,
+            command,
+            impact,
+            typed: String::new(),
+            phase: NodePowerPhase::Confirm,
+        }
+    }
+
+    /// Whether the node's name has been typed exactly.
+    pub fn confirmed(&self) -> bool {
+        self.typed == self.node.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ContainerType, GuestId};
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn guest(vmid: u32, name: &str) -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            max_cpu: 2,
+            memory_max: 4 * GB,
+            uptime: 1,
+            ..Container::test("homelab", vmid, name, "pve1")
+        }
+    }
+
+    fn ha(vmid: u32, state: &str) -> HaResource {
+        HaResource {
+            guest: GuestId::new("homelab", vmid),
+            state: state.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_impact_splits_guests_by_ha() {
+        let guests = [guest(101, "web"), guest(102, "db"), guest(103, "cache")];
+        let refs: Vec<&Container> = guests.iter().collect();
+        // An ignored resource is left alone, and 200 isn't on this node
+        let resources = [ha(102, "started"), ha(103, "ignored"), ha(200, "started")];
+
+        let impact = GuestImpact::new(&refs, Ok(&resources), 12 * GB);
+        assert_eq!(impact.migrated, ["102 db"]);
+        assert_eq!(impact.stopped, ["101 web", "103 cache"]);
+        assert_eq!(
+            impact.summary(Units::Short),
+            [
+                "Running guests: 3 (12.0 GB configured)",
+                "Migrated by HA (1): 102 db",
+                "Stopped, not HA-managed (2): 101 web, 103 cache",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_ha_counts_every_guest_as_stopped() {
+        let guests = [guest(101, "web")];
+        let refs: Vec<&Container> = guests.iter().collect();
+        let impact = GuestImpact::new(&refs, Err("HTTP 403".to_string()), 4 * GB);
+        assert_eq!(impact.running(), 1);
+        assert_eq!(
+            impact.summary(Units::Short),
+            [
+                "Running guests: 1 (4.0 GB configured)",
+                "HA state unknown (HTTP 403), all may be stopped: 101 web",
+            ]
+        );
+
+        let idle = GuestImpact::new(&[], Ok(&[]), 0);
+        assert_eq!(idle.summary(Units::Short), ["No guests running"]);
+    }
+
+    #[test]
+    fn test_only_the_exact_name_confirms() {
+        let mut request = NodePowerRequest::new(
+            NodeId::new("homelab", "pve1"),
+            NodePower::Reboot,
+            GuestImpact::default(),
+        );
+        assert!(!request.confirmed());
+        request.typed = "PVE1".to_string();
+        assert!(!request.confirmed());
+        request.typed = "pve1".to_string();
+        assert!(request.confirmed());
+    }
+}
@@ -0,0 +1,132 @@
+//! Guest notes: a line of free text pinned to a guest (`A`), such as
+//! "left stopped on purpose", shown as a marker on its row and in full in
+//! the details.
+//!
+//! Notes are kept under `[notes]` in the state file, keyed by the guest's
+//! identity (`provider/id`), so they survive restarts and follow the guest
+//! across renames. A note whose guest hasn't been seen for
+//! `[general] note_retention` is dropped.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::GuestId;
+use crate::state;
+
+/// How often `seen` is moved forward, so the state file isn't rewritten on
+/// every refresh.
+const SEEN_GRANULARITY: u64 = 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    /// Unix seconds of the last edit.
+    pub updated: u64,
+    /// Unix seconds the guest was last listed, to the hour.
+    pub seen: u64,
+}
+
+/// Notes by guest identity (`GuestId`'s `provider/id` form).
+pub type Notes = BTreeMap<String, Note>;
+
+/// The note being typed for a guest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotePrompt {
+    pub guest: GuestId,
+    pub text: String,
+}
+
+pub fn key(guest: &GuestId) -> String {
+    guest.to_string()
+}
+
+pub fn load(path: &Path) -> Result<Notes, String> {
+    state::load(path, "notes")
+}
+
+/// Write `notes` to `path`, keeping the rest of the state file.
+pub fn save(path: &Path, notes: &Notes) -> Result<(), String> {
+    state::save(path, "notes", notes)
+}
+
+/// Mark the notes of `present` guests seen at `now` and drop those unseen for
+/// longer than `retention`. True if anything worth saving changed.
+pub fn sweep<'a>(
+    notes: &mut Notes,
+    present: impl IntoIterator<Item = &'a GuestId>,
+    now: u64,
+    retention: Duration,
+) -> bool {
+    let mut changed = false;
+    for guest in present {
+        if let Some(note) = notes.get_mut(&key(guest))
+            && now.saturating_sub(note.seen) >= SEEN_GRANULARITY
+        {
+            note.seen = now;
+            changed = true;
+        }
+    }
+    let before = notes.len();
+    notes.retain(|_, note| now.saturating_sub(note.seen) <= retention.as_secs());
+    changed || notes.len() != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(seen: u64) -> Note {
+        Note {
+            text: "left stopped on purpose".to_string(),
+            updated: seen,
+            seen,
+        }
+    }
+
+    #[test]
+    fn test_sweep_keeps_listed_guests_and_drops_stale_ones() {
+        let day = 24 * 60 * 60;
+        let web = GuestId::new("pve", 100);
+        let gone = GuestId::new("pve", 101);
+        let mut notes = Notes::from([(key(&web), note(0)), (key(&gone), note(0))]);
+
+        // Seen recently: nothing to save yet
+        assert!(!sweep(&mut notes, [&web], 60, Duration::from_secs(7 * day)));
+        assert_eq!(notes[&key(&web)].seen, 0);
+
+        assert!(sweep(
+            &mut notes,
+            [&web],
+            2 * day,
+            Duration::from_secs(7 * day)
+        ));
+        assert_eq!(notes[&key(&web)].seen, 2 * day);
+        assert_eq!(notes.len(), 2);
+
+        assert!(sweep(
+            &mut notes,
+            [&web],
+            8 * day,
+            Duration::from_secs(7 * day)
+        ));
+        assert_eq!(notes.keys().collect::<Vec<_>>(), ["pve/100"]);
+    }
+
+    #[test]
+    fn test_notes_share_the_state_file_with_presets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        std::fs::write(&path, "[presets.media]\nquery = \"node:media\"\n").unwrap();
+
+        let notes = Notes::from([("pve/100".to_string(), note(5))]);
+        save(&path, &notes).unwrap();
+        assert_eq!(load(&path), Ok(notes));
+        assert_eq!(
+            crate::presets::load(&path).unwrap()["media"].query,
+            "node:media"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt::Write;
+
+use serde_json::json;
+
+use crate::app::App;
+use crate::check;
+use crate::format::{format_bytes, format_uptime};
+use crate::models::{Container, ContainerStatus, ContainerType, NodeStatus};
+use crate::severity::Severity;
+
+/// SGR colors for `render_summary`, only used when asked for.
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Render the filtered and sorted view as a plain-text table.
+pub fn render_table(app: &App) -> String {
+    let mut out = String::new();
+
+    let mut clusters = app.clusters().peekable();
+    if clusters.peek().is_some() {
+        for cluster in clusters {
+            let _ = writeln!(out, "{}", cluster.summary());
+        }
+        out.push('\n');
+    }
+
+    let _ = writeln!(
+        out,
+        "{:<16} {:<8} {:>6} {:>22} {:>12}",
+        "NODE", "STATUS", "CPU", "MEMORY", "UPTIME"
+    );
+    for node in app.filtered_nodes() {
+        let status = match node.status {
+            NodeStatus::Online => "online",
+            NodeStatus::Offline => "offline",
+        };
+        let _ = writeln!(
+            out,
+            "{:<16} {:<8} {:>5.1}% {:>22} {:>12}",
+            node.name,
+            status,
+            node.cpu_usage,
+            format!(
+                "{} / {}",
+                format_bytes(node.memory_used, app.units),
+                format_bytes(node.memory_total, app.units)
+            ),
+            format_uptime(node.uptime),
+        );
+    }
+
+    out.push('\n');
+
+    let _ = writeln!(
+        out,
+        "{:>6} {:<20} {:<4} {:<12} {:<8} {:>6} {:>22} {:>12}",
+        "VMID", "NAME", "TYPE", "NODE", "STATUS", "CPU", "MEMORY", "UPTIME"
+    );
+    for container in app.filtered_containers() {
+        let status = guest_status(container);
+        let vmid = match container.container_type {
+            ContainerType::Service | ContainerType::Docker => "-".to_string(),
+            _ => container.vmid.to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{:>6} {:<20} {:<4} {:<12} {:<8} {:>5.1}% {:>22} {:>12}",
+            vmid,
+            container.name,
+            container.type_label(),
+            container.node,
+            status,
+            app.container_cpu(container),
+            format!(
+                "{} / {}",
+                format_bytes(container.memory_used, app.units),
+                format_bytes(container.memory_max, app.units)
+            ),
+            format_uptime(container.uptime),
+        );
+    }
+
+    out
+}
+
+/// The filtered view as plain lines for `pulse watch`: each provider's
+/// health as `pulse check` reports it, node and guest counts, and then
+/// only the nodes and guests in HA error, down or over a critical threshold.
+/// Nothing but text and newlines unless `color` is set.
+pub fn render_summary(app: &App, color: bool) -> String {
+    let mut out = String::new();
+    let (providers, _) = check::report(app);
+    for line in providers.lines() {
+        let code = (!line.ends_with(": ok")).then_some(RED);
+        let _ = writeln!(out, "{}", paint(line, code, color));
+    }
+
+    let nodes = app.filtered_node_totals();
+    let _ = writeln!(out, "Nodes: {} of {} online", nodes.online, nodes.total);
+    for node in app.filtered_nodes() {
+        let (line, code) = match app.node_severity(node) {
+            Severity::HaError => (
+                format!(
+                    "{} {}",
+                    node.name,
+                    app.node_ha_problem(node).unwrap_or("HA error")
+                ),
+                RED,
+            ),
+            Severity::Down => (format!("{} offline", node.name), RED),
+            Severity::Critical => (
+                format!(
+                    "{} critical: CPU {:.1}%, memory {:.1}%",
+                    node.name,
+                    node.cpu_usage,
+                    node.memory_percent()
+                ),
+                YELLOW,
+            ),
+            Severity::Normal => continue,
+        };
+        let _ = writeln!(out, "  {}", paint(&line, Some(code), color));
+    }
+
+    let guests = app.filtered_container_totals();
+    let _ = writeln!(
+        out,
+        "Guests: {} of {} running",
+        guests.running, guests.total
+    );
+    for container in app.filtered_containers() {
+        let name = match container.container_type {
+            ContainerType::Service | ContainerType::Docker => container.name.clone(),
+            _ => format!("{} {}", container.vmid, container.name),
+        };
+        let (line, code) = match app.container_severity(container) {
+            Severity::HaError => (format!("{} on {}: HA error", name, container.node), RED),
+            Severity::Down => (
+                format!(
+                    "{} on {}: {}",
+                    name,
+                    container.node,
+                    guest_status(container)
+                ),
+                RED,
+            ),
+            Severity::Critical => (
+                format!(
+                    "{} on {} critical: CPU {:.1}%, memory {:.1}%",
+                    name,
+                    container.node,
+                    app.container_cpu(container),
+                    container.memory_percent()
+                ),
+                YELLOW,
+            ),
+            Severity::Normal => continue,
+        };
+        let _ = writeln!(out, "  {}", paint(&line, Some(code), color));
+    }
+
+    out
+}
+
+fn guest_status(container: &Container) -> &'static str {
+    match container.status {
+        _ if container.missing => "missing",
+        _ if container.is_template => "template",
+        ContainerStatus::Running => "running",
+        ContainerStatus::Stopped => "stopped",
+    }
+}
+
+fn paint(text: &str, code: Option<&str>, color: bool) -> String {
+    match code {
+        Some(code) if color => format!("{}{}{}", code, text, RESET),
+        _ => text.to_string(),
+    }
+}
+
+/// Render the filtered and sorted view as a JSON document, CPU in the
+/// current CPU mode.
+pub fn render_json(app: &App) -> String {
+    let nodes: Vec<serde_json::Value> = app
+        .filtered_nodes()
+        .iter()
+        .map(|node| {
+            json!({
+                "provider": node.provider(),
+                "name": node.name,
+                "status": match node.status {
+                    NodeStatus::Online => "online",
+                    NodeStatus::Offline => "offline",
+                },
+                "cpu_percent": node.cpu_usage,
+                "memory_used": node.memory_used,
+                "memory_total": node.memory_total,
+                "uptime": node.uptime,
+                "pending_updates": node.pending_updates,
+            })
+        })
+        .collect();
+    let containers: Vec<serde_json::Value> = app
+        .filtered_containers()
+        .iter()
+        .map(|container| {
+            json!({
+                "provider": container.provider(),
+                "vmid": match container.container_type {
+                    ContainerType::Service | ContainerType::Docker => None,
+                    _ => Some(container.vmid),
+                },
+                "name": container.name,
+                "type": container.type_label(),
+                "node": container.node,
+                "status": guest_status(container),
+                "cpu_percent": app.container_cpu(container),
+                "memory_used": container.memory_used,
+                "memory_max": container.memory_max,
+                "uptime": container.uptime,
+                "lock": container.lock,
+            })
+        })
+        .collect();
+
+    let mut out = serde_json::to_string_pretty(&json!({
+        "nodes": nodes,
+        "containers": containers,
+    }))
+    .unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{ProviderStatus, SortField};
+    use crate::models::{ClusterStatus, Container, GuestId, HaStatus, Node};
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.nodes = vec![Node {
+            cpu_usage: 12.5,
+            cpu_cores: 4,
+            memory_used: 8 * 1024 * 1024 * 1024,
+            memory_total: 16 * 1024 * 1024 * 1024,
+            uptime: 90061,
+            ..Node::test("homelab", "pve1")
+        }];
+        app.containers = ["jellyfin", "media-sonarr", "media-radarr"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Container {
+                cpu_usage: 10.0 * (i + 1) as f64,
+                memory_used: 512 * 1024 * 1024,
+                memory_max: 1024 * 1024 * 1024,
+                uptime: 3600,
+                ..Container::test("homelab", 100 + i as u32, name, "pve1")
+            })
+            .collect();
+        app
+    }
+
+    #[test]
+    fn test_render_table_lists_everything() {
+        let app = test_app();
+        let table = render_table(&app);
+
+        assert!(table.contains("pve1"));
+        assert!(table.contains("8.0 GB / 16.0 GB"));
+        assert!(table.contains("1d 1h 1m"));
+        assert!(table.contains("jellyfin"));
+        assert!(table.contains("media-sonarr"));
+        assert!(table.contains("media-radarr"));
+    }
+
+    #[test]
+    fn test_render_table_applies_filter_and_sort() {
+        let mut app = test_app();
+        app.apply_startup_options(Some("media"), Some(SortField::Cpu), true);
+
+        let table = render_table(&app);
+
+        assert!(!table.contains("jellyfin"));
+        let radarr = table.find("media-radarr").unwrap();
+        let sonarr = table.find("media-sonarr").unwrap();
+        assert!(radarr < sonarr, "higher CPU should come first:\n{}", table);
+    }
+
+    #[test]
+    fn test_render_json() {
+        let mut app = test_app();
+        let value: serde_json::Value = serde_json::from_str(&render_json(&app)).unwrap();
+        assert_eq!(value["nodes"][0]["name"], "pve1");
+        assert_eq!(
+            value["nodes"][0]["memory_total"],
+            16u64 * 1024 * 1024 * 1024
+        );
+        assert_eq!(value["containers"].as_array().unwrap().len(), 3);
+
+        app.apply_startup_options(Some("jellyfin"), None, false);
+        let value: serde_json::Value = serde_json::from_str(&render_json(&app)).unwrap();
+        let containers = value["containers"].as_array().unwrap();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0]["vmid"], 100);
+        assert_eq!(containers[0]["status"], "running");
+        assert_eq!(containers[0]["lock"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_render_summary_lists_only_problems() {
+        let mut app = test_app();
+        app.provider_status.push(ProviderStatus::new("homelab"));
+        assert_eq!(
+            render_summary(&app, false),
+            "homelab: ok\nNodes: 1 of 1 online\nGuests: 3 of 3 running\n"
+        );
+
+        app.containers[2].cpu_usage = 99.0;
+        app.provider_status[0].last_error = Some("timed out".to_string());
+        let summary = render_summary(&app, false);
+        assert!(summary.starts_with("homelab: error: timed out\n"));
+        assert!(
+            summary.ends_with("  102 media-radarr on pve1 critical: CPU 99.0%, memory 50.0%\n")
+        );
+
+        let colored = render_summary(&app, true);
+        assert!(colored.starts_with("\x1b[31mhomelab: error: timed out\x1b[0m\n"));
+        assert!(colored.contains("  \x1b[33m102 media-radarr"));
+    }
+
+    #[test]
+    fn test_render_summary_names_ha_problems() {
+        let mut app = test_app();
+        let mut status = ProviderStatus::new("homelab");
+        status.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: true,
+            nodes_online: 1,
+            nodes_total: 1,
+            firewall: None,
+            ha: HaStatus {
+                errors: vec![GuestId::new("homelab", 101)],
+                fenced: vec!["pve1".to_string()],
+                unknown: Vec::new(),
+            },
+        });
+        app.provider_status.push(status);
+
+        assert_eq!(
+            render_summary(&app, false),
+            "homelab: ok\n\
+             Nodes: 1 of 1 online\n\
+             \x20 pve1 fenced\n\
+             Guests: 3 of 3 running\n\
+             \x20 101 media-sonarr on pve1: HA error\n"
+        );
+    }
+
+    #[test]
+    fn test_render_table_reports_quorum() {
+        let mut app = test_app();
+        assert!(!render_table(&app).contains("cluster"));
+
+        let mut status = ProviderStatus::new("homelab");
+        status.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: false,
+            nodes_online: 1,
+            nodes_total: 3,
+            firewall: None,
+            ha: Default::default(),
+        });
+        app.provider_status.push(status);
+
+        let table = render_table(&app);
+        assert!(table.starts_with("cluster homelab: NO QUORUM (1/3)\n"));
+    }
+}
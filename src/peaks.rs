@@ -0,0 +1,424 @@
+//! `pulse history report`: peak and average use, downtime and restarts of
+//! every node and guest over a period, folded from the history one sample at
+//! a time so a week of refreshes never has to fit in memory.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::cli::PeakSort;
+use crate::format::{DurationStyle, TimeUnit, Units, format_bytes, format_duration};
+use crate::history::Sample;
+use crate::text::{display_width, pad_right};
+
+/// Samples further apart than this many refresh intervals mean pulse wasn't
+/// recording in between, or the provider was unreachable. Adaptive refresh
+/// stretches the interval 4x at most, and a slow refresh adds a little.
+const GAP_INTERVALS: u32 = 10;
+const MIN_GAP: Duration = Duration::from_secs(60);
+
+/// "2d 3h 0m 12s": downtimes are often short, so seconds still count.
+const DOWNTIME: DurationStyle = DurationStyle {
+    largest: TimeUnit::Day,
+    smallest: TimeUnit::Second,
+    compact: false,
+};
+
+/// The longest step between two samples that still counts as watched, for
+/// the configured refresh interval.
+pub fn max_gap(refresh_interval: Duration) -> Duration {
+    (refresh_interval * GAP_INTERVALS).max(MIN_GAP)
+}
+
+/// One node or guest over the period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemPeaks {
+    pub provider: String,
+    /// "node" or "guest".
+    pub kind: String,
+    /// Node name or VMID.
+    pub id: String,
+    /// As last recorded.
+    pub name: String,
+    pub cpu_avg: f64,
+    pub cpu_max: f64,
+    /// Bytes.
+    pub memory_avg: f64,
+    pub memory_max: u64,
+    /// Seconds spent stopped or offline, counted only between samples close
+    /// enough together (`max_gap()`) that pulse was watching.
+    pub downtime_secs: u64,
+    /// Times it came back up with a lower uptime than the sample before:
+    /// restarts of a guest, reboots of a node. Stopping and starting again
+    /// isn't one.
+    pub restarts: u32,
+    pub samples: u64,
+}
+
+/// Folds samples, oldest first, into one `ItemPeaks` per item.
+pub struct PeakReport {
+    max_gap: i64,
+    items: HashMap<(String, String, String), Tally>,
+}
+
+struct Tally {
+    peaks: ItemPeaks,
+    cpu_sum: f64,
+    memory_sum: f64,
+    /// The previous sample's time, whether it was up, and its uptime.
+    last: (i64, bool, Option<u64>),
+}
+
+impl PeakReport {
+    pub fn new(max_gap: Duration) -> Self {
+        PeakReport {
+            max_gap: max_gap.as_secs() as i64,
+            items: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, sample: Sample) {
+        let up = matches!(sample.status.as_str(), "running" | "online");
+        let key = (
+            sample.provider.clone(),
+            sample.kind.clone(),
+            sample.item_id.clone(),
+        );
+        let Some(tally) = self.items.get_mut(&key) else {
+            let tally = Tally {
+                cpu_sum: sample.cpu,
+                memory_sum: sample.mem_used as f64,
+                last: (sample.ts, up, sample.uptime),
+                peaks: ItemPeaks {
+                    provider: sample.provider,
+                    kind: sample.kind,
+                    id: sample.item_id,
+                    name: sample.name,
+                    cpu_avg: 0.0,
+                    cpu_max: sample.cpu,
+                    memory_avg: 0.0,
+                    memory_max: sample.mem_used,
+                    downtime_secs: 0,
+                    restarts: 0,
+                    samples: 1,
+                },
+            };
+            self.items.insert(key, tally);
+            return;
+        };
+
+        let (then, was_up, uptime) = tally.last;
+        let step = sample.ts - then;
+        // Down until this sample said otherwise, unless nobody was looking
+        if !was_up && (0..=self.max_gap).contains(&step) {
+            tally.peaks.downtime_secs += step as u64;
+        }
+        // Across a gap too: the uptime says it restarted meanwhile
+        if up
+            && uptime
+                .zip(sample.uptime)
+                .is_some_and(|(before, after)| after < before)
+        {
+            tally.peaks.restarts += 1;
+        }
+
+        tally.cpu_sum += sample.cpu;
+        tally.memory_sum += sample.mem_used as f64;
+        tally.last = (sample.ts, up, sample.uptime);
+        let peaks = &mut tally.peaks;
+        peaks.name = sample.name;
+        peaks.cpu_max = peaks.cpu_max.max(sample.cpu);
+        peaks.memory_max = peaks.memory_max.max(sample.mem_used);
+        peaks.samples += 1;
+    }
+
+    /// Every item, in `sort` order: highest first, names A to Z.
+    pub fn finish(self, sort: PeakSort) -> Vec<ItemPeaks> {
+        let mut items: Vec<ItemPeaks> = self
+            .items
+            .into_values()
+            .map(|tally| {
+                let count = tally.peaks.samples as f64;
+                ItemPeaks {
+                    cpu_avg: tally.cpu_sum / count,
+                    memory_avg: tally.memory_sum / count,
+                    ..tally.peaks
+                }
+            })
+            .collect();
+        sort_items(&mut items, sort);
+        items
+    }
+}
+
+/// Highest first, ties and `PeakSort::Name` by name.
+fn sort_items(items: &mut [ItemPeaks], sort: PeakSort) {
+    items.sort_by(|a, b| {
+        let by_name =
+            (&a.name, &a.provider, &a.kind, &a.id).cmp(&(&b.name, &b.provider, &b.kind, &b.id));
+        match sort {
+            PeakSort::Cpu => b.cpu_max.total_cmp(&a.cpu_max),
+            PeakSort::Memory => b.memory_max.cmp(&a.memory_max),
+            PeakSort::Downtime => b.downtime_secs.cmp(&a.downtime_secs),
+            PeakSort::Restarts => b.restarts.cmp(&a.restarts),
+            PeakSort::Name => std::cmp::Ordering::Equal,
+        }
+        .then(by_name)
+    });
+}
+
+/// "101 nginx" for guests, the name alone for nodes.
+fn label(item: &ItemPeaks) -> String {
+    if item.kind == "guest" {
+        format!("{} {}", item.id, item.name)
+    } else {
+        item.name.clone()
+    }
+}
+
+pub fn render_table(items: &[ItemPeaks], units: Units) -> String {
+    let mut out = String::new();
+    if items.is_empty() {
+        out.push_str("No history in that period.\n");
+        return out;
+    }
+
+    let labels: Vec<String> = items.iter().map(label).collect();
+    let width = labels.iter().map(|l| display_width(l)).max().unwrap_or(0);
+    let width = width.max("NAME".len());
+    let _ = writeln!(
+        out,
+        "{:<12} {:<5} {} {:>7} {:>7} {:>9} {:>9} {:>15} {:>8}",
+        "PROVIDER",
+        "KIND",
+        pad_right("NAME", width),
+        "CPU AVG",
+        "CPU MAX",
+        "MEM AVG",
+        "MEM MAX",
+        "DOWNTIME",
+        "RESTARTS"
+    );
+    for (item, label) in items.iter().zip(&labels) {
+        let downtime = match item.downtime_secs {
+            0 => "-".to_string(),
+            secs => format_duration(secs, DOWNTIME),
+        };
+        let _ = writeln!(
+            out,
+            "{:<12} {:<5} {} {:>6.1}% {:>6.1}% {:>9} {:>9} {:>15} {:>8}",
+            item.provider,
+            item.kind,
+            pad_right(label, width),
+            item.cpu_avg,
+            item.cpu_max,
+            format_bytes(item.memory_avg.round() as u64, units),
+            format_bytes(item.memory_max, units),
+            downtime,
+            item.restarts,
+        );
+    }
+    out
+}
+
+/// The period as Unix seconds, and the items in the order given.
+pub fn render_json(items: &[ItemPeaks], since: i64, until: i64) -> String {
+    let report = json!({
+        "since": since,
+        "until": until,
+        "items": items,
+    });
+    format!("{:#}\n", report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAP: Duration = Duration::from_secs(60);
+
+    fn sample(ts: i64, status: &str, uptime: Option<u64>) -> Sample {
+        Sample {
+            ts,
+            provider: "homelab".to_string(),
+            kind: "guest".to_string(),
+            item_id: "101".to_string(),
+            name: "nginx".to_string(),
+            cpu: 10.0,
+            mem_used: 1024,
+            mem_total: 4096,
+            status: status.to_string(),
+            uptime,
+        }
+    }
+
+    /// The one item's peaks after `states`: (time, status) in order, with
+    /// the uptime counting from each start.
+    fn fold(states: &[(i64, &str)]) -> ItemPeaks {
+        let mut report = PeakReport::new(GAP);
+        let mut started = None;
+        for &(ts, status) in states {
+            let uptime = if status == "running" {
+                ts - *started.get_or_insert(ts)
+            } else {
+                started = None;
+                0
+            };
+            report.add(sample(ts, status, Some(uptime as u64)));
+        }
+        report.finish(PeakSort::Cpu).remove(0)
+    }
+
+    #[test]
+    fn test_downtime_sums_stopped_stretches() {
+        let peaks = fold(&[
+            (0, "running"),
+            (10, "stopped"),
+            (20, "stopped"),
+            (30, "running"),
+            (40, "stopped"),
+            (50, "running"),
+        ]);
+        // Down from 10 until the sample at 30, and from 40 until 50
+        assert_eq!(peaks.downtime_secs, 30);
+        assert_eq!(peaks.samples, 6);
+        // Starting after a stop isn't a restart
+        assert_eq!(peaks.restarts, 0);
+    }
+
+    #[test]
+    fn test_downtime_skips_gaps_in_the_history() {
+        // Pulse wasn't running between 20 and 500: that time isn't downtime,
+        // even with the guest stopped on both sides of it
+        let peaks = fold(&[
+            (0, "running"),
+            (10, "stopped"),
+            (20, "stopped"),
+            (500, "stopped"),
+            (510, "running"),
+        ]);
+        assert_eq!(peaks.downtime_secs, 20);
+
+        // A gap of exactly `max_gap` still counts
+        assert_eq!(fold(&[(0, "stopped"), (60, "running")]).downtime_secs, 60);
+        assert_eq!(fold(&[(0, "stopped"), (61, "running")]).downtime_secs, 0);
+    }
+
+    #[test]
+    fn test_downtime_ends_with_the_last_sample() {
+        // Still down at the end: nothing is known past the last sample
+        assert_eq!(
+            fold(&[(0, "running"), (10, "stopped"), (20, "stopped")]).downtime_secs,
+            10
+        );
+        assert_eq!(fold(&[(0, "stopped")]).downtime_secs, 0);
+        assert_eq!(fold(&[(0, "running"), (30, "running")]).downtime_secs, 0);
+    }
+
+    #[test]
+    fn test_nodes_are_down_while_offline() {
+        let mut report = PeakReport::new(GAP);
+        for (ts, status) in [(0, "online"), (5, "offline"), (25, "online")] {
+            let mut node = sample(ts, status, None);
+            node.kind = "node".to_string();
+            node.item_id = "pve1".to_string();
+            node.name = "pve1".to_string();
+            report.add(node);
+        }
+        let peaks = report.finish(PeakSort::Cpu);
+        assert_eq!(peaks[0].downtime_secs, 20);
+        assert_eq!(label(&peaks[0]), "pve1");
+    }
+
+    #[test]
+    fn test_restarts_come_from_uptime_drops() {
+        let mut report = PeakReport::new(GAP);
+        for (ts, uptime) in [
+            (0, Some(100)),
+            (10, Some(110)),
+            // Restarted between samples without ever being seen stopped
+            (20, Some(3)),
+            (30, Some(13)),
+            // Across a gap the uptime still tells
+            (5000, Some(5)),
+            // Old samples without an uptime say nothing
+            (5010, None),
+            (5020, Some(1)),
+        ] {
+            report.add(sample(ts, "running", uptime));
+        }
+        let peaks = report.finish(PeakSort::Cpu).remove(0);
+        assert_eq!(peaks.restarts, 2);
+        assert_eq!(peaks.downtime_secs, 0);
+    }
+
+    #[test]
+    fn test_averages_peaks_and_order() {
+        let mut report = PeakReport::new(GAP);
+        for (ts, cpu, mem_used) in [(0, 20.0, 1000), (10, 80.0, 3000)] {
+            let mut busy = sample(ts, "running", None);
+            busy.cpu = cpu;
+            busy.mem_used = mem_used;
+            report.add(busy);
+        }
+        let mut quiet = sample(0, "stopped", None);
+        quiet.item_id = "102".to_string();
+        quiet.name = "backup".to_string();
+        quiet.cpu = 1.0;
+        report.add(quiet.clone());
+        quiet.ts = 30;
+        report.add(quiet);
+
+        let items = report.finish(PeakSort::Cpu);
+        assert_eq!(items[0].name, "nginx");
+        assert_eq!(items[0].cpu_avg, 50.0);
+        assert_eq!(items[0].cpu_max, 80.0);
+        assert_eq!(items[0].memory_avg, 2000.0);
+        assert_eq!(items[0].memory_max, 3000);
+
+        assert_eq!(items[1].downtime_secs, 30);
+        let sorted = |sort| {
+            let mut items = items.clone();
+            sort_items(&mut items, sort);
+            items.into_iter().map(|item| item.name).collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(PeakSort::Cpu), ["nginx", "backup"]);
+        assert_eq!(sorted(PeakSort::Memory), ["nginx", "backup"]);
+        assert_eq!(sorted(PeakSort::Downtime), ["backup", "nginx"]);
+        // Ties go by name
+        assert_eq!(sorted(PeakSort::Restarts), ["backup", "nginx"]);
+        assert_eq!(sorted(PeakSort::Name), ["backup", "nginx"]);
+    }
+
+    #[test]
+    fn test_max_gap_follows_the_refresh_interval() {
+        assert_eq!(max_gap(Duration::from_secs(30)), Duration::from_secs(300));
+        assert_eq!(max_gap(Duration::from_secs(2)), MIN_GAP);
+    }
+
+    #[test]
+    fn test_render_table_and_json() {
+        let items = fold(&[(0, "running"), (10, "stopped"), (95, "running")]);
+        let items = vec![items];
+        let table = render_table(&items, Units::Short);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("PROVIDER     KIND  NAME      CPU AVG CPU MAX"));
+        assert!(lines[0].ends_with("DOWNTIME RESTARTS"));
+        assert!(lines[1].starts_with("homelab      guest 101 nginx   10.0%   10.0%"));
+        // More than a minute apart: pulse wasn't watching
+        assert!(lines[1].ends_with("       -        0"), "{}", lines[1]);
+        assert_eq!(
+            render_table(&[], Units::Short),
+            "No history in that period.\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&render_json(&items, 100, 200)).unwrap();
+        assert_eq!(json["since"], 100);
+        assert_eq!(json["items"][0]["id"], "101");
+        assert_eq!(json["items"][0]["downtime_secs"], 0);
+        assert_eq!(json["items"][0]["restarts"], 0);
+    }
+}
@@ -0,0 +1,234 @@
+//! The provider picker shown at startup when a config has more than
+//! `PICKER_THRESHOLD` providers and neither `--provider` nor
+//! `[general] default_providers` chose among them. Those left unticked
+//! are not connected to until turned on in the providers popup.
+
+use std::io;
+
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+/// More providers than this and pulse asks which to start.
+pub const PICKER_THRESHOLD: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Picker {
+    names: Vec<String>,
+    checked: Vec<bool>,
+    selected: usize,
+    /// Why Enter did nothing, until the next key.
+    hint: Option<&'static str>,
+}
+
+/// What a key did to the picker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Picking,
+    /// Start these, in config order.
+    Start(Vec<String>),
+    Quit,
+}
+
+impl Picker {
+    /// Nothing ticked: with dozens of clusters, starting a few is the point.
+    pub fn new(names: Vec<String>) -> Self {
+        Picker {
+            checked: vec![false; names.len()],
+            names,
+            selected: 0,
+            hint: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> Outcome {
+        self.hint = None;
+        match code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.names.len().saturating_sub(1));
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char(' ') => {
+                if let Some(checked) = self.checked.get_mut(self.selected) {
+                    *checked = !*checked;
+                }
+            }
+            KeyCode::Char('a') => {
+                let all = self.checked.iter().all(|c| *c);
+                self.checked.iter_mut().for_each(|c| *c = !all);
+            }
+            KeyCode::Enter => {
+                let picked: Vec<String> = self
+                    .names
+                    .iter()
+                    .zip(&self.checked)
+                    .filter(|(_, checked)| **checked)
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                if !picked.is_empty() {
+                    return Outcome::Start(picked);
+                }
+                self.hint = Some("Tick at least one provider with space");
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Outcome::Quit,
+            _ => {}
+        }
+        Outcome::Picking
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let height = (self.names.len() as u16 + 5).min(area.height);
+        let width = (self.names.iter().map(|n| n.len()).max().unwrap_or(0) as u16 + 12)
+            .max(48)
+            .min(area.width);
+        let area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        // Keep the highlighted row in view when the list is taller
+        let rows = height.saturating_sub(5) as usize;
+        let first = self.selected.saturating_sub(rows.saturating_sub(1));
+        let mut lines: Vec<Line> = self
+            .names
+            .iter()
+            .zip(&self.checked)
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(i, (name, checked))| {
+                let text = format!(" [{}] {}", if *checked { "x" } else { " " }, name);
+                if i == self.selected {
+                    Line::styled(text, Style::default().fg(Color::Black).bg(Color::Cyan))
+                } else {
+                    Line::raw(text)
+                }
+            })
+            .collect();
+        lines.push(Line::raw(""));
+        lines.push(match self.hint {
+            Some(hint) => Line::styled(format!(" {}", hint), Style::default().fg(Color::Yellow)),
+            None => Line::styled(
+                " j/k move, space ticks, a all, Enter starts, q quits",
+                Style::default().fg(Color::DarkGray),
+            ),
+        });
+
+        let picked = self.checked.iter().filter(|c| **c).count();
+        let popup = Paragraph::new(lines).block(
+            Block::default()
+                .title(format!(
+                    " Start which providers? {} of {} ",
+                    picked,
+                    self.names.len()
+                ))
+                .title_style(Style::default().add_modifier(Modifier::BOLD))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan)),
+        );
+        frame.render_widget(popup, area);
+    }
+}
+
+/// Ask which of `names` to start; None when the user quit instead.
+pub fn run(names: Vec<String>) -> io::Result<Option<Vec<String>>> {
+    let mut picker = Picker::new(names);
+    let mut terminal = ratatui::init();
+    let outcome = loop {
+        if let Err(e) = terminal.draw(|frame| picker.draw(frame)) {
+            break Err(e);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) => match picker.handle_key(key.code) {
+                Outcome::Picking => {}
+                Outcome::Start(picked) => break Ok(Some(picked)),
+                Outcome::Quit => break Ok(None),
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    ratatui::restore();
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn picker() -> Picker {
+        Picker::new(["dc1", "dc2", "dc3"].map(String::from).to_vec())
+    }
+
+    #[test]
+    fn test_space_ticks_and_enter_starts_in_config_order() {
+        let mut picker = picker();
+        for code in [
+            KeyCode::Char('j'),
+            KeyCode::Char('j'),
+            KeyCode::Char(' '),
+            KeyCode::Char('k'),
+            KeyCode::Char('k'),
+            KeyCode::Char('k'),
+            KeyCode::Char(' '),
+        ] {
+            assert_eq!(picker.handle_key(code), Outcome::Picking);
+        }
+        assert_eq!(
+            picker.handle_key(KeyCode::Enter),
+            Outcome::Start(vec!["dc1".to_string(), "dc3".to_string()])
+        );
+
+        // Moving stops at the last row
+        for _ in 0..5 {
+            picker.handle_key(KeyCode::Char('j'));
+        }
+        assert_eq!(picker.selected, 2);
+    }
+
+    #[test]
+    fn test_enter_needs_a_tick() {
+        let mut picker = picker();
+        assert_eq!(picker.handle_key(KeyCode::Enter), Outcome::Picking);
+        assert!(picker.hint.is_some());
+        picker.handle_key(KeyCode::Char('a'));
+        assert!(picker.hint.is_none());
+        assert_eq!(
+            picker.handle_key(KeyCode::Enter),
+            Outcome::Start(["dc1", "dc2", "dc3"].map(String::from).to_vec())
+        );
+        // `a` again clears them all
+        picker.handle_key(KeyCode::Char('a'));
+        assert!(picker.checked.iter().all(|c| !c));
+        assert_eq!(picker.handle_key(KeyCode::Char('q')), Outcome::Quit);
+    }
+
+    #[test]
+    fn test_draw_shows_ticks() {
+        let mut picker = picker();
+        picker.handle_key(KeyCode::Char(' '));
+        let mut terminal = Terminal::new(TestBackend::new(60, 10)).unwrap();
+        terminal.draw(|frame| picker.draw(frame)).unwrap();
+        let rows: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(60)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Start which providers? 1 of 3"))
+        );
+        assert!(rows.iter().any(|r| r.contains("[x] dc1")));
+        assert!(rows.iter().any(|r| r.contains("[ ] dc2")));
+    }
+}
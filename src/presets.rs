@@ -0,0 +1,95 @@
+//! Named filter presets: a search query and the filter toggles that go with
+//! it, saved from search mode (`Ctrl+s`) and applied from the `F` popup.
+//!
+//! Presets are kept in pulse's state file (`[general] state_file`) under
+//! `[presets]`, one table per name, rather than in the config, which pulse
+//! never writes.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::query::MatchOptions;
+use crate::restarts::RecentFilter;
+use crate::state;
+
+/// Everything a preset replaces when applied.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default)]
+    pub whole_word: bool,
+    #[serde(default)]
+    pub recent: RecentFilter,
+    #[serde(default)]
+    pub hide_templates: bool,
+}
+
+impl Preset {
+    pub fn options(&self) -> MatchOptions {
+        MatchOptions {
+            case_sensitive: self.case_sensitive,
+            whole_word: self.whole_word,
+        }
+    }
+}
+
+/// The name prompt `Ctrl+s` opens in search mode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresetPrompt {
+    pub name: String,
+    /// The name is taken and Enter was pressed once; again overwrites.
+    pub confirm_overwrite: bool,
+}
+
+/// Presets by name, in name order (the order of the popup and its number keys).
+pub type Presets = BTreeMap<String, Preset>;
+
+/// Presets saved at `path`; none if the file doesn't exist yet.
+pub fn load(path: &Path) -> Result<Presets, String> {
+    state::load(path, "presets")
+}
+
+/// Write `presets` to `path`, keeping the rest of the state file.
+pub fn save(path: &Path, presets: &Presets) -> Result<(), String> {
+    state::save(path, "presets", presets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/pulse.toml");
+        assert_eq!(load(&path), Ok(Presets::new()));
+
+        let mut presets = Presets::new();
+        presets.insert(
+            "prod running".to_string(),
+            Preset {
+                query: "node:prod status:running".to_string(),
+                recent: RecentFilter::Hour,
+                hide_templates: true,
+                ..Preset::default()
+            },
+        );
+        save(&path, &presets).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("[presets.\"prod running\"]"), "{}", text);
+        assert!(text.contains("recent = \"1h\""), "{}", text);
+        assert_eq!(load(&path), Ok(presets));
+
+        // Only the query is needed by hand
+        std::fs::write(&path, "[presets.media]\nquery = \"node:media\"\n").unwrap();
+        assert_eq!(load(&path).unwrap()["media"].query, "node:media");
+
+        std::fs::write(&path, "[presets.media]\nquery = 5\n").unwrap();
+        assert!(load(&path).unwrap_err().starts_with("cannot parse"));
+    }
+}
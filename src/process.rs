@@ -0,0 +1,137 @@
+//! Running a shell command with a time limit, for `token_secret_cmd` and
+//! the command provider. Both pipes are drained while it runs, and the child
+//! is always waited for, killed first when it overruns, so none is left
+//! behind as a zombie however often it is run.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often a running child is checked on.
+const POLL: Duration = Duration::from_millis(20);
+
+/// What a command printed, and how it exited.
+#[derive(Debug)]
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Spawn(io::Error),
+    /// Killed after running this long.
+    TimedOut(Duration),
+    Wait(io::Error),
+}
+
+/// Reads like "`token_secret_cmd` timed out after 30s" after the command's
+/// name.
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Spawn(e) => write!(f, "could not be started: {}", e),
+            RunError::TimedOut(timeout) => write!(f, "timed out after {:?}", timeout),
+            RunError::Wait(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+fn shell() -> Command {
+    #[cfg(windows)]
+    {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    }
+    #[cfg(not(windows))]
+    {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    }
+}
+
+fn read_all(mut pipe: impl Read + Send + 'static) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut buffer = String::new();
+        let _ = pipe.read_to_string(&mut buffer);
+        buffer
+    })
+}
+
+/// Run `command` through the shell, killing it after `timeout`.
+pub fn run(command: &str, timeout: Duration) -> Result<Output, RunError> {
+    let mut child = shell()
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(RunError::Spawn)?;
+
+    // Drain both pipes while waiting, so a chatty command can't fill one and stall
+    let stdout = read_all(child.stdout.take().expect("stdout is piped"));
+    let stderr = read_all(child.stderr.take().expect("stderr is piped"));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(POLL),
+            waited => {
+                let _ = child.kill();
+                let _ = child.wait();
+                // The readers are left to finish on their own: anything
+                // the command started may still hold the pipes open
+                return Err(match waited {
+                    Err(e) => RunError::Wait(e),
+                    Ok(_) => RunError::TimedOut(timeout),
+                });
+            }
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_and_status_are_kept_apart() {
+        let output = run("printf out; printf err >&2; exit 3", Duration::from_secs(5)).unwrap();
+        assert_eq!(output.stdout, "out");
+        assert_eq!(output.stderr, "err");
+        assert_eq!(output.status.code(), Some(3));
+    }
+
+    #[test]
+    fn test_chatty_command_does_not_stall() {
+        // Well past a pipe buffer on both streams
+        let output = run(
+            "head -c 1000000 /dev/zero | tr '\\0' a; head -c 1000000 /dev/zero | tr '\\0' b >&2",
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(output.stdout.len(), 1_000_000);
+        assert_eq!(output.stderr.len(), 1_000_000);
+    }
+
+    #[test]
+    fn test_overrunning_command_is_killed() {
+        let started = Instant::now();
+        let error = run("sleep 10", Duration::from_millis(200)).unwrap_err();
+        assert!(matches!(error, RunError::TimedOut(_)));
+        assert_eq!(error.to_string(), "timed out after 200ms");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
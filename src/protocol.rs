@@ -0,0 +1,274 @@
+//! What `pulse daemon` and `pulse attach` say to each other over the unix
+//! socket: one JSON object per line, tagged by `type`.
+//!
+//! The client opens with `hello` and its protocol version; the daemon
+//! answers with its own `hello` listing its providers, or `refused` and
+//! closes when the versions differ. From then on the daemon pushes a
+//! `snapshot` after every refresh and `events` as its providers' logs grow,
+//! and answers each `request` (an action or an on-demand fetch) with a
+//! `reply` carrying the same id. A second `hello` means the daemon reloaded
+//! its config and the provider list may have changed.
+//!
+//! Lines whose `type` is unknown are skipped, so either side may add
+//! messages without a version bump; changing existing ones needs one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clone::CloneRequest;
+use crate::models::{
+    ClusterLogEntry, ClusterStatus, Container, GuestDetails, HaResource, Node, PermissionIssue,
+    ReplicationJob, TaskStatus,
+};
+use crate::node_power::NodePower;
+use crate::resources::{GuestResources, ResourceRequest};
+
+/// Bumped whenever a message changes incompatibly; each side refuses the
+/// other speaking another version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello {
+        version: u32,
+    },
+    Request {
+        id: u64,
+        provider: String,
+        request: Request,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Hello {
+        version: u32,
+        providers: Vec<ProviderInfo>,
+        /// The daemon's providers refuse actions, or only pretend to take
+        /// them; an attached UI shows as much.
+        read_only: bool,
+        dry_run: bool,
+    },
+    /// Sent instead of `hello`, before the daemon closes the connection.
+    Refused {
+        reason: String,
+    },
+    Snapshot {
+        providers: Vec<ProviderSnapshot>,
+    },
+    /// Entries of a provider's log not sent before, newest first.
+    Events {
+        provider: String,
+        entries: Vec<ClusterLogEntry>,
+    },
+    Reply {
+        id: u64,
+        result: Result<Reply, String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderInfo {
+    pub name: String,
+    /// Recorded in an attached UI's audit log.
+    pub user: Option<String>,
+}
+
+/// What one provider returned in the daemon's last refresh, fetch by
+/// fetch, so an attached UI sees the same errors it would on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderSnapshot {
+    pub name: String,
+    pub nodes: Result<Vec<Node>, String>,
+    pub containers: Result<Vec<Container>, String>,
+    pub cluster: Result<Option<ClusterStatus>, String>,
+}
+
+/// A call forwarded to one of the daemon's providers, named like the
+/// `Provider` method it ends up in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub enum Request {
+    ProbePermissions,
+    ProbeActions,
+    FetchReplication,
+    FetchHaResources,
+    FetchGuestDetails {
+        guest: Box<Container>,
+    },
+    FetchGuestResources {
+        guest: Box<Container>,
+    },
+    NextVmid,
+    MigrateGuest {
+        guest: Box<Container>,
+        target: String,
+    },
+    StartGuest {
+        guest: Box<Container>,
+    },
+    ShutdownGuest {
+        guest: Box<Container>,
+    },
+    CloneGuest {
+        guest: Box<Container>,
+        request: CloneRequest,
+    },
+    SetGuestResources {
+        guest: Box<Container>,
+        request: ResourceRequest,
+    },
+    NodePower {
+        node: String,
+        command: NodePower,
+    },
+    WebConsoleUrl {
+        guest: Box<Container>,
+    },
+    TaskStatus {
+        node: String,
+        task: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Reply {
+    Issues(Vec<PermissionIssue>),
+    Replication(Vec<ReplicationJob>),
+    HaResources(Vec<HaResource>),
+    GuestDetails(GuestDetails),
+    GuestResources(GuestResources),
+    Vmid(u32),
+    /// A task id, URL or other text an action returns.
+    Text(String),
+    TaskStatus(TaskStatus),
+}
+
+/// A message as one line, newline included.
+pub fn encode(message: &impl Serialize) -> String {
+    let mut line = serde_json::to_string(message).expect("protocol messages serialize");
+    line.push('\n');
+    line
+}
+
+/// A line read from the other side; `Ok(None)` for a message type this
+/// side doesn't know.
+pub fn decode<T: for<'de> Deserialize<'de>>(line: &str) -> Result<Option<T>, String> {
+    match serde_json::from_str(line) {
+        Ok(message) => Ok(Some(message)),
+        Err(e) => {
+            let value: serde_json::Value =
+                serde_json::from_str(line).map_err(|_| format!("invalid JSON: {}", e))?;
+            // A whole message of a type added later in this version; an
+            // unknown variant further in is an error like any other
+            let unknown = value
+                .get("type")
+                .and_then(|t| t.as_str())
+                .is_some_and(|kind| {
+                    e.to_string()
+                        .starts_with(&format!("unknown variant `{}`", kind))
+                });
+            if unknown {
+                Ok(None)
+            } else {
+                Err(format!("invalid message: {}", e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GuestId;
+
+    fn guest() -> Container {
+        Container {
+            cpu_usage: 3.5,
+            max_cpu: 2,
+            memory_used: 512,
+            memory_max: 1024,
+            uptime: 90,
+            pool: Some("prod".to_string()),
+            disk_used: Some(10),
+            disk_max: 100,
+            ..Container::test("homelab", 101, "db", "pve2")
+        }
+    }
+
+    #[test]
+    fn test_messages_are_single_tagged_lines() {
+        let line = encode(&ClientMessage::Request {
+            id: 3,
+            provider: "homelab".to_string(),
+            request: Request::MigrateGuest {
+                guest: Box::new(guest()),
+                target: "pve1".to_string(),
+            },
+        });
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.matches('\n').count(), 1);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["type"], "request");
+        assert_eq!(value["request"]["call"], "migrate_guest");
+
+        match decode::<ClientMessage>(&line).unwrap() {
+            Some(ClientMessage::Request {
+                id: 3,
+                provider,
+                request: Request::MigrateGuest { guest, target },
+            }) => {
+                assert_eq!(provider, "homelab");
+                assert_eq!(target, "pve1");
+                assert_eq!(guest.id, GuestId::new("homelab", 101));
+                assert_eq!(guest.pool.as_deref(), Some("prod"));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        let line = encode(&ServerMessage::Reply {
+            id: 3,
+            result: Ok(Reply::TaskStatus(TaskStatus::Failed(
+                "no space".to_string(),
+            ))),
+        });
+        match decode::<ServerMessage>(&line).unwrap() {
+            Some(ServerMessage::Reply {
+                id: 3,
+                result: Ok(Reply::TaskStatus(status)),
+            }) => assert_eq!(status, TaskStatus::Failed("no space".to_string())),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_only_unknown_message_types_are_skipped() {
+        // Added later within the version: skipped
+        let later = decode::<ServerMessage>(r#"{"type":"progress","percent":40}"#);
+        assert!(matches!(later, Ok(None)));
+
+        // Known, but wrong: an error
+        let error = decode::<ServerMessage>(r#"{"type":"reply","id":1}"#).unwrap_err();
+        assert!(
+            error.starts_with("invalid message: missing field `result`"),
+            "{}",
+            error
+        );
+        let error = decode::<ClientMessage>(
+            r#"{"type":"request","id":1,"provider":"homelab","request":{"call":"format_disks"}}"#,
+        )
+        .unwrap_err();
+        assert!(
+            error.starts_with("invalid message: unknown variant `format_disks`"),
+            "{}",
+            error
+        );
+        assert!(
+            decode::<ServerMessage>("hello")
+                .unwrap_err()
+                .starts_with("invalid JSON: ")
+        );
+    }
+}
@@ -1,7 +1,381 @@
-use crate::models::{Container, Node};
+use std::sync::Arc;
+
+use super::failover::EndpointInUse;
+use crate::clone::CloneRequest;
+use crate::models::{
+    ClusterLogEntry, ClusterStatus, Container, GuestDetails, HaResource, Node, PermissionIssue,
+    ReplicationJob, TaskStatus,
+};
+use crate::node_power::NodePower;
+use crate::resources::{GuestResources, ResourceRequest};
+
+/// Starts the task id an action returns in dry-run mode, followed by the
+/// call it would have made.
+const DRY_RUN_TASK: &str = "dry-run: ";
+
+/// The task id standing in for `call` ("POST https://... target=pve2").
+pub fn dry_run_task(call: &str) -> String {
+    format!("{}{}", DRY_RUN_TASK, call)
+}
+
+/// The call behind a dry-run task id; None for a real task.
+pub fn dry_run_call(task: &str) -> Option<&str> {
+    task.strip_prefix(DRY_RUN_TASK)
+}
 
 pub trait Provider {
     fn name(&self) -> &str;
     fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>>;
     fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>>;
+
+    /// Re-fetch one node, bypassing any caches (`R`). `Ok(None)` if the
+    /// node is no longer listed. The default filters a full `fetch_nodes`.
+    fn fetch_node(&self, name: &str) -> Result<Option<Node>, Box<dyn std::error::Error>> {
+        Ok(self.fetch_nodes()?.into_iter().find(|n| n.name == name))
+    }
+
+    /// Re-fetch one guest from its node's list (`R`). `Ok(None)` if the
+    /// guest is gone. The default filters a full `fetch_containers`.
+    fn fetch_guest(
+        &self,
+        node: &str,
+        vmid: u32,
+    ) -> Result<Option<Container>, Box<dyn std::error::Error>> {
+        Ok(self
+            .fetch_containers()?
+            .into_iter()
+            .find(|c| c.node == node && c.vmid == vmid))
+    }
+
+    /// Account the provider acts as, recorded in the audit log.
+    fn user(&self) -> Option<&str> {
+        None
+    }
+
+    /// Command line template for a guest's console (see `console::render`),
+    /// if one is configured for its type.
+    fn console_template(&self, _container: &Container) -> Option<&str> {
+        None
+    }
+
+    /// Called at the start of every refresh cycle.
+    fn begin_refresh(&self) {}
+
+    /// API requests made since the last `begin_refresh`, if the provider counts them.
+    fn request_count(&self) -> Option<u32> {
+        None
+    }
+
+    /// Response bytes received since the last `begin_refresh`, as sent over
+    /// the wire (compressed), if the provider counts them.
+    fn bytes_received(&self) -> Option<u64> {
+        None
+    }
+
+    /// The API endpoint in use, for providers configured with several.
+    fn endpoint(&self) -> Option<EndpointInUse> {
+        None
+    }
+
+    /// Try each class of endpoint once and report the privileges the
+    /// credentials are missing. Other failures are returned as errors.
+    fn probe_permissions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Privileges the credentials lack for actions (migrating, cloning),
+    /// checked at startup and on reload so those keys can be refused up
+    /// front. Empty when actions can work or there is nothing to check.
+    fn probe_actions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Cluster membership and quorum; `None` for standalone hosts.
+    fn fetch_cluster_status(&self) -> Result<Option<ClusterStatus>, Box<dyn std::error::Error>> {
+        Ok(None)
+    }
+
+    fn fetch_replication(&self) -> Result<Vec<ReplicationJob>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Guests under the cluster's HA manager; empty without HA.
+    fn fetch_ha_resources(&self) -> Result<Vec<HaResource>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    /// The newest `max` entries of the provider's own log, newest first.
+    fn fetch_cluster_log(
+        &self,
+        _max: usize,
+    ) -> Result<Vec<ClusterLogEntry>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    fn fetch_guest_details(
+        &self,
+        _container: &Container,
+    ) -> Result<GuestDetails, Box<dyn std::error::Error>> {
+        Ok(GuestDetails::default())
+    }
+
+    /// A guest's configured cores and memory, read now for editing.
+    fn fetch_guest_resources(
+        &self,
+        _container: &Container,
+    ) -> Result<GuestResources, Box<dyn std::error::Error>> {
+        Err("changing resources is not supported by this provider".into())
+    }
+
+    /// A free VMID to suggest for a new guest.
+    fn next_vmid(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        Err("VMIDs are not supported by this provider".into())
+    }
+
+    // Actions. Each starts a task on the provider and returns a handle for
+    // `task_status`; providers without write access keep the defaults.
+
+    /// Start moving a guest to `target` (live for running VMs).
+    fn migrate_guest(
+        &self,
+        _container: &Container,
+        _target: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Err("migration is not supported by this provider".into())
+    }
+
+    /// Start a stopped guest.
+    fn start_guest(&self, _container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        Err("starting guests is not supported by this provider".into())
+    }
+
+    /// Start a clean shutdown of a guest.
+    fn shutdown_guest(&self, _container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        Err("shutdown is not supported by this provider".into())
+    }
+
+    /// Start cloning a guest; the task runs on the guest's node.
+    fn clone_guest(
+        &self,
+        _container: &Container,
+        _request: &CloneRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Err("cloning is not supported by this provider".into())
+    }
+
+    /// Change a guest's cores and memory in its config. Like `node_power`
+    /// there is no task: the result is a dry-run task id, or empty once the
+    /// config is changed.
+    fn set_guest_resources(
+        &self,
+        _container: &Container,
+        _request: &ResourceRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Err("changing resources is not supported by this provider".into())
+    }
+
+    /// Reboot or shut down a whole node. There is no task to poll: the
+    /// result is a dry-run task id, or empty once the node has the command.
+    fn node_power(
+        &self,
+        _node: &str,
+        _command: NodePower,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Err("node power actions are not supported by this provider".into())
+    }
+
+    /// A URL that opens the guest's console in the provider's web UI, with
+    /// a console ticket fetched just now (they are short-lived).
+    fn web_console_url(
+        &self,
+        _container: &Container,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Err("web consoles are not supported by this provider".into())
+    }
+
+    /// Poll a task started on `node` by one of the actions above.
+    fn task_status(
+        &self,
+        _node: &str,
+        _task: &str,
+    ) -> Result<TaskStatus, Box<dyn std::error::Error>> {
+        Err("tasks are not supported by this provider".into())
+    }
+}
+
+/// A provider the first load can fetch from on its own thread while the UI
+/// keeps using the same instance, caches and all (see `loader.rs`).
+pub type SharedProvider = Arc<dyn Provider + Send + Sync>;
+
+/// Forwards every method, overridden or not; `tests` fails when one is
+/// missing, as it would silently run the default instead.
+impl<P: Provider + ?Sized> Provider for Arc<P> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        (**self).fetch_nodes()
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        (**self).fetch_containers()
+    }
+
+    fn fetch_node(&self, name: &str) -> Result<Option<Node>, Box<dyn std::error::Error>> {
+        (**self).fetch_node(name)
+    }
+
+    fn fetch_guest(
+        &self,
+        node: &str,
+        vmid: u32,
+    ) -> Result<Option<Container>, Box<dyn std::error::Error>> {
+        (**self).fetch_guest(node, vmid)
+    }
+
+    fn user(&self) -> Option<&str> {
+        (**self).user()
+    }
+
+    fn console_template(&self, container: &Container) -> Option<&str> {
+        (**self).console_template(container)
+    }
+
+    fn begin_refresh(&self) {
+        (**self).begin_refresh()
+    }
+
+    fn request_count(&self) -> Option<u32> {
+        (**self).request_count()
+    }
+
+    fn bytes_received(&self) -> Option<u64> {
+        (**self).bytes_received()
+    }
+
+    fn endpoint(&self) -> Option<EndpointInUse> {
+        (**self).endpoint()
+    }
+
+    fn probe_permissions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        (**self).probe_permissions()
+    }
+
+    fn probe_actions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        (**self).probe_actions()
+    }
+
+    fn fetch_cluster_status(&self) -> Result<Option<ClusterStatus>, Box<dyn std::error::Error>> {
+        (**self).fetch_cluster_status()
+    }
+
+    fn fetch_replication(&self) -> Result<Vec<ReplicationJob>, Box<dyn std::error::Error>> {
+        (**self).fetch_replication()
+    }
+
+    fn fetch_ha_resources(&self) -> Result<Vec<HaResource>, Box<dyn std::error::Error>> {
+        (**self).fetch_ha_resources()
+    }
+
+    fn fetch_cluster_log(
+        &self,
+        max: usize,
+    ) -> Result<Vec<ClusterLogEntry>, Box<dyn std::error::Error>> {
+        (**self).fetch_cluster_log(max)
+    }
+
+    fn fetch_guest_details(
+        &self,
+        container: &Container,
+    ) -> Result<GuestDetails, Box<dyn std::error::Error>> {
+        (**self).fetch_guest_details(container)
+    }
+
+    fn fetch_guest_resources(
+        &self,
+        container: &Container,
+    ) -> Result<GuestResources, Box<dyn std::error::Error>> {
+        (**self).fetch_guest_resources(container)
+    }
+
+    fn next_vmid(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        (**self).next_vmid()
+    }
+
+    fn migrate_guest(
+        &self,
+        container: &Container,
+        target: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).migrate_guest(container, target)
+    }
+
+    fn start_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).start_guest(container)
+    }
+
+    fn shutdown_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).shutdown_guest(container)
+    }
+
+    fn clone_guest(
+        &self,
+        container: &Container,
+        request: &CloneRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).clone_guest(container, request)
+    }
+
+    fn set_guest_resources(
+        &self,
+        container: &Container,
+        request: &ResourceRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).set_guest_resources(container, request)
+    }
+
+    fn node_power(
+        &self,
+        node: &str,
+        command: NodePower,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).node_power(node, command)
+    }
+
+    fn web_console_url(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        (**self).web_console_url(container)
+    }
+
+    fn task_status(
+        &self,
+        node: &str,
+        task: &str,
+    ) -> Result<TaskStatus, Box<dyn std::error::Error>> {
+        (**self).task_status(node, task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// The methods declared between `start` and the closing brace at the
+    /// start of a line.
+    fn methods(source: &str, start: &str) -> Vec<String> {
+        let block = source.split_once(start).unwrap().1;
+        let block = block.split_once("\n}").unwrap().0;
+        block
+            .lines()
+            .filter_map(|line| line.trim_start().strip_prefix("fn "))
+            .map(|rest| rest.split(['(', '<']).next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_arc_forwards_every_method() {
+        let source = include_str!("base.rs");
+        let declared = methods(source, "pub trait Provider {");
+        let forwarded = methods(source, "Provider for Arc<P> {");
+        assert!(declared.len() > 20, "{:?}", declared);
+        assert_eq!(declared, forwarded);
+    }
 }
@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Counts API requests made during one refresh window and caps the optional
+/// ones (on-demand detail fetches) so they can't hammer the API.
+///
+/// Essential requests (node and guest lists) are always allowed but still
+/// count against the limit, so details queue behind them. Response bytes as
+/// received (before decompression) are counted alongside.
+pub struct RequestBudget {
+    limit: Option<u32>,
+    used: AtomicU32,
+    bytes: AtomicU64,
+}
+
+impl RequestBudget {
+    pub fn new(limit: Option<u32>) -> Self {
+        Self {
+            limit,
+            used: AtomicU32::new(0),
+            bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Start a new refresh window.
+    pub fn reset(&self) {
+        self.used.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+
+    pub fn record(&self) {
+        self.used.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn used(&self) -> u32 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn record_bytes(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Whether an optional request may run without exceeding the limit.
+    pub fn allows_optional(&self) -> bool {
+        self.limit.is_none_or(|limit| self.used() < limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_budget_always_allows() {
+        let budget = RequestBudget::new(None);
+        for _ in 0..1000 {
+            budget.record();
+        }
+        assert!(budget.allows_optional());
+        assert_eq!(budget.used(), 1000);
+    }
+
+    #[test]
+    fn test_limit_blocks_optional_requests() {
+        let budget = RequestBudget::new(Some(3));
+        budget.record();
+        budget.record();
+        assert!(budget.allows_optional());
+
+        budget.record();
+        assert!(!budget.allows_optional());
+
+        // Essential requests are still counted past the limit
+        budget.record();
+        assert_eq!(budget.used(), 4);
+    }
+
+    #[test]
+    fn test_reset_starts_new_window() {
+        let budget = RequestBudget::new(Some(1));
+        budget.record();
+        budget.record_bytes(4096);
+        assert!(!budget.allows_optional());
+        assert_eq!(budget.bytes(), 4096);
+
+        budget.reset();
+        assert_eq!(budget.used(), 0);
+        assert_eq!(budget.bytes(), 0);
+        assert!(budget.allows_optional());
+    }
+}
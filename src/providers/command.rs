@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::Provider;
+use crate::agent::{SCHEMA_VERSION, Snapshot};
+use crate::config::CommandConfig;
+use crate::error::ProviderError;
+use crate::models::{Container, GuestId, Node, NodeId};
+use crate::process;
+use crate::text::truncate_end;
+
+/// Lines of stderr quoted in an error, from the end, where the reason
+/// usually is.
+const STDERR_LINES: usize = 3;
+/// Columns of stderr quoted in an error.
+const STDERR_WIDTH: usize = 200;
+
+/// Anything that can print a snapshot: a script run every refresh, whose
+/// stdout is the same JSON `pulse agent` serves.
+pub struct CommandProvider {
+    name: String,
+    command: String,
+    timeout: Duration,
+    /// This cycle's snapshot, shared by `fetch_nodes` and `fetch_containers`.
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+impl CommandProvider {
+    pub fn new(config: &CommandConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let timeout = config.timeout().map_err(|e| format!("timeout: {}", e))?;
+        Ok(Self {
+            name: config.name.clone(),
+            command: config.command.clone(),
+            timeout,
+            snapshot: Mutex::new(None),
+        })
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, ProviderError> {
+        let mut cached = self.snapshot.lock().unwrap();
+        if let Some(snapshot) = cached.as_ref() {
+            return Ok(snapshot.clone());
+        }
+        let snapshot = self.run()?;
+        *cached = Some(snapshot.clone());
+        Ok(snapshot)
+    }
+
+    fn error(&self, message: String) -> ProviderError {
+        ProviderError::Command {
+            command: self.command.clone(),
+            message,
+        }
+    }
+
+    fn run(&self) -> Result<Snapshot, ProviderError> {
+        let output =
+            process::run(&self.command, self.timeout).map_err(|e| self.error(e.to_string()))?;
+        let with_stderr = |message: String| match stderr_snippet(&output.stderr) {
+            Some(stderr) => self.error(format!("{}; stderr: {}", message, stderr)),
+            None => self.error(message),
+        };
+        if !output.status.success() {
+            return Err(with_stderr(output.status.to_string()));
+        }
+        parse_snapshot(&self.name, &output.stdout).map_err(with_stderr)
+    }
+}
+
+/// The end of what a command wrote to stderr, on one line; None if it
+/// wrote nothing.
+fn stderr_snippet(stderr: &str) -> Option<String> {
+    let lines: Vec<&str> = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let tail = &lines[lines.len().saturating_sub(STDERR_LINES)..];
+    (!tail.is_empty()).then(|| truncate_end(&tail.join(" / "), STDERR_WIDTH))
+}
+
+/// Read a command's stdout as a snapshot for provider `provider`, and check
+/// it makes sense as one: every container on a listed node, no name or id
+/// used twice.
+fn parse_snapshot(provider: &str, stdout: &str) -> Result<Snapshot, String> {
+    if stdout.trim().is_empty() {
+        return Err("printed nothing".to_string());
+    }
+    // The version is checked on its own first, as from an agent: output
+    // for another version may not parse at all
+    let value: serde_json::Value =
+        serde_json::from_str(stdout).map_err(|e| format!("printed invalid JSON: {}", e))?;
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(version) if version == SCHEMA_VERSION as u64 => {}
+        Some(version) => {
+            return Err(format!(
+                "printed schema v{}, this pulse expects v{}",
+                version, SCHEMA_VERSION
+            ));
+        }
+        None => {
+            return Err(format!(
+                "printed no schema version; add \"version\": {}",
+                SCHEMA_VERSION
+            ));
+        }
+    }
+    let mut snapshot: Snapshot =
+        serde_json::from_value(value).map_err(|e| format!("printed an invalid snapshot: {}", e))?;
+
+    let mut nodes = HashSet::new();
+    for (i, node) in snapshot.nodes.iter_mut().enumerate() {
+        if node.name.is_empty() {
+            return Err(format!("nodes[{}] has no name", i));
+        }
+        if !nodes.insert(node.name.clone()) {
+            return Err(format!(
+                "nodes[{}]: node {:?} is listed twice",
+                i, node.name
+            ));
+        }
+        node.id = NodeId::new(provider, &node.name);
+    }
+    let mut ids = HashSet::new();
+    for (i, container) in snapshot.containers.iter_mut().enumerate() {
+        if container.name.is_empty() {
+            return Err(format!("containers[{}] has no name", i));
+        }
+        if !nodes.contains(&container.node) {
+            return Err(format!(
+                "containers[{}] ({}): node {:?} is not among the nodes",
+                i, container.name, container.node
+            ));
+        }
+        let id = match container.id.id.as_str() {
+            "" => container.name.clone(),
+            id => id.to_string(),
+        };
+        if !ids.insert(id.clone()) {
+            return Err(format!(
+                "containers[{}] ({}): id {:?} is used twice; give each container its own \"id\"",
+                i, container.name, id
+            ));
+        }
+        container.id = GuestId::new(provider, id);
+    }
+    Ok(snapshot)
+}
+
+impl Provider for CommandProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        Ok(self.snapshot()?.nodes)
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        Ok(self.snapshot()?.containers)
+    }
+
+    fn begin_refresh(&self) {
+        *self.snapshot.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ContainerStatus, ContainerType, NodeStatus};
+
+    /// Only what a script has to print: the rest defaults.
+    const MINIMAL_JSON: &str = r#"{"version": 1,
+        "nodes": [{"name": "ups", "status": "Online", "cpu_usage": 3.0,
+            "memory_used": 0, "memory_total": 0}],
+        "containers": [{"name": "load", "node": "ups", "container_type": "Service",
+            "status": "Running", "cpu_usage": 41.0, "memory_used": 0, "memory_max": 0}]}"#;
+
+    fn provider(command: &str) -> CommandProvider {
+        CommandProvider::new(&CommandConfig {
+            name: "nut".to_string(),
+            command: command.to_string(),
+            timeout: Some("5s".to_string()),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_minimal_snapshot_fills_in_ids_and_defaults() {
+        let snapshot = parse_snapshot("nut", MINIMAL_JSON).unwrap();
+        let node = &snapshot.nodes[0];
+        assert_eq!(node.id, NodeId::new("nut", "ups"));
+        assert_eq!(node.status, NodeStatus::Online);
+        assert_eq!((node.cpu_cores, node.uptime), (0, 0));
+        assert!(node.interfaces.is_empty());
+        let container = &snapshot.containers[0];
+        assert_eq!(container.id, GuestId::new("nut", "load"));
+        assert_eq!(container.container_type, ContainerType::Service);
+        assert_eq!(container.status, ContainerStatus::Running);
+        assert!(!container.missing);
+
+        // An agent's full snapshot reads the same way
+        let full = r#"{"version":1,
+            "nodes":[{"id":{"provider":"local","name":"nas"},"name":"nas","status":"Online",
+                "cpu_usage":12.5,"cpu_cores":4,"memory_used":1024,"memory_total":4096,"uptime":600,
+                "interfaces":[],"disks":"NotReported","pending_updates":null,"package_updates":[]}],
+            "containers":[{"id":{"provider":"local","id":"a1b2c3"},"vmid":0,"name":"web","node":"nas",
+                "container_type":"Docker","status":"Running","cpu_usage":5.0,"max_cpu":4,
+                "memory_used":512,"memory_max":4096,"uptime":60,"lock":null,"missing":false,"pool":null}]}"#;
+        let snapshot = parse_snapshot("nas", full).unwrap();
+        assert_eq!(snapshot.nodes[0].cpu_cores, 4);
+        assert_eq!(snapshot.containers[0].id, GuestId::new("nas", "a1b2c3"));
+    }
+
+    #[test]
+    fn test_schema_errors_say_what_is_wrong() {
+        let error = |json: &str| parse_snapshot("nut", json).unwrap_err();
+        assert_eq!(error("  \n"), "printed nothing");
+        assert!(error("ups: 3%").starts_with("printed invalid JSON: "));
+        assert_eq!(
+            error(r#"{"nodes": []}"#),
+            "printed no schema version; add \"version\": 1"
+        );
+        assert_eq!(
+            error(r#"{"version": 2}"#),
+            "printed schema v2, this pulse expects v1"
+        );
+        let missing = error(r#"{"version": 1, "nodes": [{"name": "ups"}], "containers": []}"#);
+        assert!(
+            missing.starts_with("printed an invalid snapshot: missing field `status`"),
+            "{}",
+            missing
+        );
+
+        let orphan = MINIMAL_JSON.replace(r#""node": "ups""#, r#""node": "pve9""#);
+        assert_eq!(
+            error(&orphan),
+            "containers[0] (load): node \"pve9\" is not among the nodes"
+        );
+        let twice = r#"{"version": 1, "nodes": [
+            {"name": "ups", "status": "Online", "cpu_usage": 0, "memory_used": 0, "memory_total": 0},
+            {"name": "ups", "status": "Online", "cpu_usage": 0, "memory_used": 0, "memory_total": 0}],
+            "containers": []}"#;
+        assert_eq!(error(twice), "nodes[1]: node \"ups\" is listed twice");
+    }
+
+    #[test]
+    fn test_stderr_snippet_keeps_the_last_lines() {
+        assert_eq!(stderr_snippet(" \n\n"), None);
+        assert_eq!(
+            stderr_snippet("starting\nconnecting to upsd\n\nfailed: refused\nexiting\n").unwrap(),
+            "connecting to upsd / failed: refused / exiting"
+        );
+        let long = "x".repeat(500);
+        assert_eq!(stderr_snippet(&long).unwrap().chars().count(), STDERR_WIDTH);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_runs_once_per_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let runs = dir.path().join("runs");
+        let json = dir.path().join("out.json");
+        std::fs::write(&json, MINIMAL_JSON).unwrap();
+        let provider = provider(&format!(
+            "echo run >> {}; cat {}",
+            runs.display(),
+            json.display()
+        ));
+
+        assert_eq!(provider.fetch_nodes().unwrap()[0].name, "ups");
+        assert_eq!(provider.fetch_containers().unwrap()[0].name, "load");
+        provider.begin_refresh();
+        provider.fetch_nodes().unwrap();
+        assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\nrun\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failures_are_provider_errors_with_stderr() {
+        let err = provider("echo 'upsc: connection refused' >&2; exit 2")
+            .fetch_nodes()
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ProviderError>(),
+            Some(ProviderError::Command { .. })
+        ));
+        assert_eq!(
+            err.to_string(),
+            "`echo 'upsc: connection refused' >&2; exit 2` failed: exit status: 2; stderr: upsc: connection refused"
+        );
+
+        let err = provider("echo 'warning: stale data' >&2; echo '{}'")
+            .fetch_nodes()
+            .unwrap_err();
+        assert!(
+            err.to_string().ends_with(
+                "failed: printed no schema version; add \"version\": 1; stderr: warning: stale data"
+            ),
+            "{}",
+            err
+        );
+
+        let slow = CommandProvider::new(&CommandConfig {
+            name: "slow".to_string(),
+            command: "sleep 10".to_string(),
+            timeout: Some("1s".to_string()),
+        })
+        .unwrap();
+        assert_eq!(
+            slow.fetch_nodes().unwrap_err().to_string(),
+            "`sleep 10` failed: timed out after 1s"
+        );
+    }
+
+    #[test]
+    fn test_timeout_must_be_positive() {
+        let config = CommandConfig {
+            name: "nut".to_string(),
+            command: "true".to_string(),
+            timeout: Some("0s".to_string()),
+        };
+        assert_eq!(
+            CommandProvider::new(&config).err().unwrap().to_string(),
+            "timeout: must be more than 0s"
+        );
+        let default = CommandConfig {
+            timeout: None,
+            ..config
+        };
+        assert_eq!(default.timeout(), Ok(Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a provider stays on a fallback endpoint before trying the
+/// preferred one again.
+pub const RETRY_PREFERRED_AFTER: Duration = Duration::from_secs(60);
+
+/// The API endpoint a provider is using, for the status display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointInUse {
+    pub url: String,
+    /// False while failed over from the first configured endpoint.
+    pub preferred: bool,
+}
+
+/// API endpoints of one provider in order of preference, e.g. every node of
+/// a cluster. Requests go to the endpoint that last answered and fail over
+/// to the next on connection errors; the preferred (first) endpoint is
+/// tried again every `RETRY_PREFERRED_AFTER`.
+pub struct Endpoints {
+    urls: Vec<String>,
+    state: Mutex<State>,
+}
+
+struct State {
+    current: usize,
+    /// When the preferred endpoint was last found unreachable.
+    preferred_failed: Option<Instant>,
+}
+
+impl Endpoints {
+    /// `urls` must not be empty.
+    pub fn new(urls: Vec<String>) -> Self {
+        assert!(!urls.is_empty(), "a provider needs an endpoint");
+        Self {
+            urls,
+            state: Mutex::new(State {
+                current: 0,
+                preferred_failed: None,
+            }),
+        }
+    }
+
+    pub fn url(&self, index: usize) -> &str {
+        &self.urls[index]
+    }
+
+    pub fn current(&self) -> &str {
+        self.url(self.state.lock().unwrap().current)
+    }
+
+    /// The endpoint in use, when there is more than one to choose from.
+    pub fn in_use(&self) -> Option<EndpointInUse> {
+        if self.urls.len() < 2 {
+            return None;
+        }
+        let current = self.state.lock().unwrap().current;
+        Some(EndpointInUse {
+            url: self.urls[current].clone(),
+            preferred: current == 0,
+        })
+    }
+
+    /// The endpoints to try for a request, in order: the one in use, then
+    /// the others as configured. Once the preferred endpoint has been down
+    /// for `RETRY_PREFERRED_AFTER`, it goes first again.
+    pub fn attempts(&self, now: Instant) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        let retry_preferred = state
+            .preferred_failed
+            .is_none_or(|at| now.duration_since(at) >= RETRY_PREFERRED_AFTER);
+        let first = if retry_preferred { 0 } else { state.current };
+        std::iter::once(first)
+            .chain((0..self.urls.len()).filter(|&i| i != first))
+            .collect()
+    }
+
+    /// The endpoint at `index` answered; requests stay there.
+    pub fn answered(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.current = index;
+        if index == 0 {
+            state.preferred_failed = None;
+        }
+    }
+
+    /// The endpoint at `index` could not be reached.
+    pub fn unreachable(&self, index: usize, now: Instant) {
+        if index == 0 {
+            self.state.lock().unwrap().preferred_failed = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> Endpoints {
+        Endpoints::new(vec![
+            "https://pve1:8006".to_string(),
+            "https://pve2:8006".to_string(),
+            "https://pve3:8006".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_single_endpoint_is_not_shown() {
+        let single = Endpoints::new(vec!["https://pve:8006".to_string()]);
+        assert_eq!(single.attempts(Instant::now()), [0]);
+        assert_eq!(single.in_use(), None);
+    }
+
+    #[test]
+    fn test_fails_over_and_stays_on_the_working_endpoint() {
+        let endpoints = endpoints();
+        let now = Instant::now();
+        assert_eq!(endpoints.attempts(now), [0, 1, 2]);
+
+        endpoints.unreachable(0, now);
+        endpoints.unreachable(1, now);
+        endpoints.answered(2);
+        assert_eq!(endpoints.current(), "https://pve3:8006");
+        assert_eq!(
+            endpoints.in_use(),
+            Some(EndpointInUse {
+                url: "https://pve3:8006".to_string(),
+                preferred: false,
+            })
+        );
+        // The next requests start where the last one succeeded
+        assert_eq!(endpoints.attempts(now + Duration::from_secs(5)), [2, 0, 1]);
+    }
+
+    #[test]
+    fn test_preferred_endpoint_is_retried_periodically() {
+        let endpoints = endpoints();
+        let now = Instant::now();
+        endpoints.unreachable(0, now);
+        endpoints.answered(1);
+
+        let later = now + RETRY_PREFERRED_AFTER;
+        assert_eq!(endpoints.attempts(later), [0, 1, 2]);
+        // Still down: back to the fallback for another interval
+        endpoints.unreachable(0, later);
+        endpoints.answered(1);
+        assert_eq!(
+            endpoints.attempts(later + Duration::from_secs(1)),
+            [1, 0, 2]
+        );
+
+        // Back up: it is used again
+        let recovered = later + RETRY_PREFERRED_AFTER;
+        assert_eq!(endpoints.attempts(recovered)[0], 0);
+        endpoints.answered(0);
+        assert!(endpoints.in_use().unwrap().preferred);
+        assert_eq!(endpoints.attempts(recovered), [0, 1, 2]);
+    }
+}
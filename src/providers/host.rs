@@ -0,0 +1,141 @@
+//! Turning a configured `host` into the base URL API paths are joined to.
+//!
+//! Hosts are written many ways: `pve1`, `pve1:8006`, `https://pve1:8006/`,
+//! or a reverse proxy sub-path such as `https://proxy.example.com/pve/`.
+//! A missing scheme means https, and the Proxmox port is only added when
+//! neither a port nor a sub-path says otherwise (a proxy listens on its own
+//! port).
+
+use reqwest::Url;
+
+/// Port the Proxmox API listens on.
+const DEFAULT_PORT: u16 = 8006;
+
+/// The base URL for `host`, without a trailing slash, e.g.
+/// "https://proxy.example.com/pve".
+pub fn normalize(host: &str) -> Result<String, String> {
+    let trimmed = host.trim();
+    let with_scheme = if trimmed.contains("://") {
+        trimmed.to_string()
+    } else {
+        format!("https://{}", trimmed)
+    };
+    let mut url =
+        Url::parse(&with_scheme).map_err(|e| format!("invalid host '{}': {}", host, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "invalid host '{}': unsupported scheme '{}' (expected http or https)",
+            host,
+            url.scheme()
+        ));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!(
+            "invalid host '{}': a query or fragment is not allowed",
+            host
+        ));
+    }
+    let path = url.path().trim_end_matches('/').to_string();
+    // `Url` drops a port that is the scheme's default, so look at what was written
+    if path.is_empty() && !has_port(&with_scheme) {
+        url.set_port(Some(DEFAULT_PORT))
+            .map_err(|()| format!("invalid host '{}': cannot have a port", host))?;
+    }
+    url.set_path(&path);
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// `base` and an absolute API `path` with exactly one slash between them.
+pub fn join(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Whether the authority of `url` (which has a scheme) spells out a port.
+fn has_port(url: &str) -> bool {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    match host_port.strip_prefix('[') {
+        // IPv6: a port follows the closing bracket
+        Some(v6) => v6
+            .split_once(']')
+            .is_some_and(|(_, port)| port.starts_with(':')),
+        None => host_port.contains(':'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_host_formats() {
+        let cases = [
+            ("pve1", "https://pve1:8006"),
+            ("pve1:8006", "https://pve1:8006"),
+            ("pve1:443", "https://pve1"),
+            ("192.168.1.100", "https://192.168.1.100:8006"),
+            ("https://pve1:8006", "https://pve1:8006"),
+            ("https://pve1:8006/", "https://pve1:8006"),
+            ("https://pve1", "https://pve1:8006"),
+            ("https://pve1/", "https://pve1:8006"),
+            ("http://pve1:8080", "http://pve1:8080"),
+            ("https://pve1:443/", "https://pve1"),
+            ("[fd00::1]", "https://[fd00::1]:8006"),
+            ("https://[fd00::1]:8443", "https://[fd00::1]:8443"),
+            // Reverse proxy sub-paths keep their prefix and get no port
+            (
+                "https://proxy.example.com/pve/",
+                "https://proxy.example.com/pve",
+            ),
+            (
+                "https://proxy.example.com/pve",
+                "https://proxy.example.com/pve",
+            ),
+            ("proxy.example.com/pve/", "https://proxy.example.com/pve"),
+            (
+                "https://proxy.example.com:8443/infra/pve//",
+                "https://proxy.example.com:8443/infra/pve",
+            ),
+            ("  https://pve1:8006  ", "https://pve1:8006"),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(normalize(host).as_deref(), Ok(expected), "host {:?}", host);
+        }
+    }
+
+    #[test]
+    fn test_normalize_rejects_what_is_not_a_host() {
+        assert!(
+            normalize("ftp://pve1")
+                .unwrap_err()
+                .contains("unsupported scheme")
+        );
+        assert!(normalize("https://").is_err());
+        assert!(
+            normalize("https://pve1/?a=1")
+                .unwrap_err()
+                .contains("query")
+        );
+    }
+
+    #[test]
+    fn test_join_uses_one_slash() {
+        assert_eq!(
+            join("https://proxy.example.com/pve", "/api2/json/nodes"),
+            "https://proxy.example.com/pve/api2/json/nodes"
+        );
+        assert_eq!(
+            join("https://pve1:8006/", "/api2/json/nodes"),
+            "https://pve1:8006/api2/json/nodes"
+        );
+        assert_eq!(
+            join("https://pve1:8006", "api2/json"),
+            "https://pve1:8006/api2/json"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use sysinfo::{Disks, MINIMUM_CPU_UPDATE_INTERVAL, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use super::Provider;
+use crate::config::LocalConfig;
+use crate::models::{
+    Container, ContainerStatus, ContainerType, GuestId, Node, NodeId, NodeStatus, Pressure,
+};
+
+/// The machine pulse runs on, via `sysinfo`, so it works on Linux, macOS and
+/// Windows alike. Optionally the busiest processes are shown as containers.
+pub struct LocalProvider {
+    name: String,
+    top_processes: usize,
+    state: Mutex<LocalState>,
+}
+
+struct LocalState {
+    system: System,
+    /// When CPU usage (and process usage) was last sampled. Usage is the
+    /// difference between two samples, so the first read is taken up front.
+    cpu_sampled: Instant,
+    processes_sampled: Instant,
+}
+
+/// One process, as the container mapping needs it.
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessSample {
+    pid: u32,
+    name: String,
+    /// Percent of one core; can exceed 100 for multi-threaded processes.
+    cpu: f32,
+    memory: u64,
+    run_time: u64,
+}
+
+impl LocalProvider {
+    pub fn new(config: &LocalConfig) -> Self {
+        let top_processes = config.top_processes.unwrap_or(0);
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        if top_processes > 0 {
+            refresh_processes(&mut system);
+        }
+        let now = Instant::now();
+        Self {
+            name: config.name.clone(),
+            top_processes,
+            state: Mutex::new(LocalState {
+                system,
+                cpu_sampled: now,
+                processes_sampled: now,
+            }),
+        }
+    }
+
+    fn host_name() -> String {
+        System::host_name().unwrap_or_else(|| "localhost".to_string())
+    }
+}
+
+impl Provider for LocalProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        let mut state = self.state.lock().unwrap();
+        wait_for_next_sample(state.cpu_sampled);
+        state.system.refresh_cpu_usage();
+        state.system.refresh_memory();
+        state.cpu_sampled = Instant::now();
+
+        let system = &state.system;
+        let name = Self::host_name();
+        let (root_used, root_total) = root_disk();
+        Ok(vec![Node {
+            id: NodeId::new(&self.name, &name),
+            name,
+            status: NodeStatus::Online,
+            cpu_usage: system.global_cpu_usage() as f64,
+            cpu_cores: system.cpus().len() as u32,
+            memory_used: system.used_memory(),
+            memory_total: system.total_memory(),
+            disk_used: root_used,
+            disk_total: root_total,
+            uptime: System::uptime(),
+            interfaces: Vec::new(),
+            disks: Default::default(),
+            pending_updates: None,
+            package_updates: Vec::new(),
+            pressure: read_pressure(),
+        }])
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        if self.top_processes == 0 {
+            return Ok(Vec::new());
+        }
+        let mut state = self.state.lock().unwrap();
+        wait_for_next_sample(state.processes_sampled);
+        refresh_processes(&mut state.system);
+        state.processes_sampled = Instant::now();
+
+        let system = &state.system;
+        let samples = system
+            .processes()
+            .values()
+            .map(|p| ProcessSample {
+                pid: p.pid().as_u32(),
+                name: p.name().to_string_lossy().into_owned(),
+                cpu: p.cpu_usage(),
+                memory: p.memory(),
+                run_time: p.run_time(),
+            })
+            .collect();
+        let host = Self::host_name();
+        let cores = system.cpus().len().max(1) as u32;
+        Ok(busiest(samples, self.top_processes)
+            .iter()
+            .map(|p| process_to_container(&self.name, &host, cores, system.total_memory(), p))
+            .collect())
+    }
+}
+
+/// Usage over less than sysinfo's minimum interval is meaningless, so a
+/// refresh right after the previous sample (the first one, or a manual
+/// refresh) waits out the rest of it.
+fn wait_for_next_sample(last: Instant) {
+    let elapsed = last.elapsed();
+    if elapsed < MINIMUM_CPU_UPDATE_INTERVAL {
+        std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL - elapsed);
+    }
+}
+
+fn refresh_processes(system: &mut System) {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+}
+
+/// Used and total bytes of the filesystem mounted at `/`; zeros where there
+/// is none (Windows).
+fn root_disk() -> (u64, u64) {
+    Disks::new_with_refreshed_list()
+        .iter()
+        .find(|disk| disk.mount_point() == Path::new("/"))
+        .map(|disk| {
+            let total = disk.total_space();
+            (total - disk.available_space().min(total), total)
+        })
+        .unwrap_or((0, 0))
+}
+
+/// CPU and memory pressure from `/proc/pressure`; None on other systems and
+/// on kernels without PSI.
+fn read_pressure() -> Option<Pressure> {
+    let cpu = std::fs::read_to_string("/proc/pressure/cpu").ok()?;
+    let memory = std::fs::read_to_string("/proc/pressure/memory").ok()?;
+    Some(Pressure {
+        cpu_some: psi_avg10(&cpu, "some")?,
+        memory_some: psi_avg10(&memory, "some")?,
+        memory_full: psi_avg10(&memory, "full")?,
+    })
+}
+
+/// The avg10 of one line of a PSI file, e.g. 12.5 from
+/// `some avg10=12.50 avg60=3.10 avg300=0.80 total=123456`.
+fn psi_avg10(text: &str, kind: &str) -> Option<f64> {
+    let line = text
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some(kind))?;
+    line.split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+/// The `count` processes using the most CPU, busiest first; ties go to the
+/// lower PID so the list doesn't shuffle between refreshes.
+fn busiest(mut samples: Vec<ProcessSample>, count: usize) -> Vec<ProcessSample> {
+    samples.sort_by(|a, b| b.cpu.total_cmp(&a.cpu).then(a.pid.cmp(&b.pid)));
+    samples.truncate(count);
+    samples
+}
+
+/// A process as a container on the local node. CPU is spread over all
+/// cores, so guest and host CPU modes agree.
+fn process_to_container(
+    provider: &str,
+    host: &str,
+    cores: u32,
+    memory_total: u64,
+    process: &ProcessSample,
+) -> Container {
+    Container {
+        id: GuestId::new(provider, process.pid),
+        vmid: process.pid,
+        name: process.name.clone(),
+        node: host.to_string(),
+        container_type: ContainerType::Process,
+        status: ContainerStatus::Running,
+        cpu_usage: process.cpu as f64 / cores as f64,
+        max_cpu: cores,
+        memory_used: process.memory,
+        memory_max: memory_total,
+        uptime: process.run_time,
+        lock: None,
+        missing: false,
+        pool: None,
+        is_template: false,
+        disk_used: None,
+        disk_max: 0,
+        os_info: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(pid: u32, cpu: f32) -> ProcessSample {
+        ProcessSample {
+            pid,
+            name: format!("proc{}", pid),
+            cpu,
+            memory: 1024,
+            run_time: 60,
+        }
+    }
+
+    #[test]
+    fn test_busiest_processes_first() {
+        let samples = vec![sample(30, 1.0), sample(20, 250.0), sample(10, 1.0)];
+        let pids: Vec<u32> = busiest(samples, 2).iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [20, 10]);
+    }
+
+    #[test]
+    fn test_process_to_container() {
+        let container = process_to_container("laptop", "mbp", 8, 16 << 30, &sample(4242, 200.0));
+        assert_eq!(container.id, GuestId::new("laptop", 4242));
+        assert_eq!(container.node, "mbp");
+        assert_eq!(container.type_label(), "PRC");
+        // Two busy cores out of eight
+        assert_eq!(container.cpu_usage, 25.0);
+        assert_eq!(container.max_cpu, 8);
+        assert_eq!(container.memory_max, 16 << 30);
+    }
+
+    #[test]
+    fn test_psi_avg10() {
+        let memory = "some avg10=12.50 avg60=3.10 avg300=0.80 total=123456\n\
+                      full avg10=4.00 avg60=1.00 avg300=0.20 total=45678\n";
+        assert_eq!(psi_avg10(memory, "some"), Some(12.5));
+        assert_eq!(psi_avg10(memory, "full"), Some(4.0));
+        // Older kernels have no "full" line for CPU
+        let cpu = "some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n";
+        assert_eq!(psi_avg10(cpu, "some"), Some(0.0));
+        assert_eq!(psi_avg10(cpu, "full"), None);
+        assert_eq!(psi_avg10("some avg10=lots", "some"), None);
+    }
+
+    #[test]
+    fn test_reports_this_machine() {
+        let provider = LocalProvider::new(&LocalConfig {
+            name: "local".to_string(),
+            top_processes: Some(3),
+        });
+
+        let nodes = provider.fetch_nodes().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].status, NodeStatus::Online);
+        assert!(nodes[0].memory_total > 0);
+        assert!(nodes[0].cpu_cores > 0);
+
+        let containers = provider.fetch_containers().unwrap();
+        assert!(!containers.is_empty() && containers.len() <= 3);
+        assert!(containers.iter().all(|c| c.node == nodes[0].name));
+    }
+}
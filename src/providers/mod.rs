@@ -1,5 +1,22 @@
 mod base;
+mod budget;
+mod command;
+mod failover;
+mod host;
+mod local;
 mod proxmox;
+mod proxy;
+mod pulse_agent;
+#[cfg(unix)]
+mod remote;
+mod systemd;
 
-pub use base::Provider;
+pub use base::{Provider, SharedProvider, dry_run_call, dry_run_task};
+pub use command::CommandProvider;
+pub use failover::EndpointInUse;
+pub use local::LocalProvider;
 pub use proxmox::ProxmoxProvider;
+pub use pulse_agent::PulseAgentProvider;
+#[cfg(unix)]
+pub use remote::Connection;
+pub use systemd::SystemdProvider;
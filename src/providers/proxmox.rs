@@ -1,204 +1,1245 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use flate2::read::{GzDecoder, ZlibDecoder};
+use reqwest::Method;
 use reqwest::blocking::Client;
-use serde::Deserialize;
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Deserializer};
 
-use super::Provider;
+use super::budget::RequestBudget;
+use super::failover::{EndpointInUse, Endpoints};
+use super::host;
+use super::proxy::Route;
+use super::{Provider, dry_run_call, dry_run_task};
+use crate::clone::CloneRequest;
 use crate::config::ProxmoxConfig;
-use crate::models::{Container, ContainerStatus, ContainerType, Node, NodeStatus};
+use crate::console;
+use crate::error::ProviderError;
+use crate::models::{
+    AgentStatus, ClusterLogEntry, ClusterStatus, Container, ContainerStatus, ContainerType, Disk,
+    EventLevel, FirewallStatus, GuestDetails, GuestId, GuestMemory, HaResource, HaStatus,
+    InterfaceStat, Node, NodeDisks, NodeId, NodeStatus, PackageUpdate, PermissionIssue,
+    ReplicationJob, TaskStatus, interface_rates,
+};
+use crate::node_power::NodePower;
+use crate::resources::{GuestResources, ResourceRequest};
+
+/// Listing disks runs smartctl on the node, so it is refreshed rarely.
+const DISK_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// The package list only changes when the node runs `apt update`.
+const UPDATES_REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Pool membership changes rarely and is one cluster-wide request.
+const POOLS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A guest agent check reaches into the VM, so its answer is kept a while.
+const AGENT_REFRESH_INTERVAL: Duration = Duration::from_secs(180);
+
+/// What a guest runs changes with an upgrade at most; re-read after a while
+/// so a VM whose agent came up later gets its full name.
+const OS_INFO_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Firewall options rarely change; the cluster's and the selected guest's
+/// are re-read this often.
+const FIREWALL_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Privileges pulse's actions need, the ACL path they are granted under,
+/// and what is lost without them.
+const ACTION_PRIVILEGES: &[(&str, &str, &str)] = &[
+    ("VM.Migrate", "/vms", "guests cannot be migrated"),
+    (
+        "VM.PowerMgmt",
+        "/vms",
+        "guests cannot be started or shut down",
+    ),
+    ("VM.Clone", "/vms", "guests cannot be cloned"),
+    ("VM.Console", "/vms", "web consoles cannot be opened"),
+    ("VM.Config.CPU", "/vms", "guest cores cannot be changed"),
+    ("VM.Config.Memory", "/vms", "guest memory cannot be changed"),
+    (
+        "Sys.PowerMgmt",
+        "/nodes",
+        "nodes cannot be rebooted or shut down",
+    ),
+];
+
+/// Effective privileges of the token by ACL path, as `/access/permissions`
+/// lists them (each privilege maps to its propagate flag).
+type Permissions = HashMap<String, HashMap<String, IgnoredAny>>;
+
+/// Per-node results of a slow endpoint and when they were fetched.
+type NodeCache<T> = Mutex<HashMap<String, (Instant, T)>>;
 
 pub struct ProxmoxProvider {
     name: String,
+    user: String,
     client: Client,
-    base_url: String,
+    /// `host` and any further `hosts`, failed over between.
+    endpoints: Endpoints,
     auth_header: String,
+    budget: RequestBudget,
+    /// Last status of each node and the list figures it was fetched with.
+    node_status: Mutex<HashMap<String, (NodeListSample, NodeStatusData)>>,
+    /// Last interface counters per node, to turn the next sample into rates.
+    net_samples: Mutex<HashMap<String, (Instant, Vec<InterfaceStat>)>>,
+    disks: NodeCache<NodeDisks>,
+    /// Pending package updates; None where the node can't be asked.
+    updates: NodeCache<Option<Vec<PackageUpdate>>>,
+    /// Pool of each pooled guest by VMID, under the key "cluster".
+    pools: NodeCache<HashMap<u32, String>>,
+    /// Guest agent state of each checked VM, keyed by its API path.
+    agents: NodeCache<Option<AgentStatus>>,
+    /// Firewall state of the cluster and of checked guests, keyed by the
+    /// API path the firewall is under.
+    firewalls: NodeCache<Option<FirewallStatus>>,
+    /// OS of each guest whose details were fetched, keyed by its API path;
+    /// listed guests carry it from then on.
+    os_infos: NodeCache<Option<String>>,
+    /// Direct, via a proxy, or via an SSH tunnel that lives as long as this.
+    route: Route,
+    console_lxc: Option<String>,
+    console_vm: Option<String>,
+    read_only: bool,
+    /// Actions return a synthetic task instead of calling the API.
+    dry_run: bool,
 }
 
 impl ProxmoxProvider {
     pub fn new(config: &ProxmoxConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .timeout(Duration::from_secs(10))
-            .connect_timeout(Duration::from_secs(5))
-            .build()?;
+        let hosts = config
+            .endpoints()?
+            .iter()
+            .map(|host| host::normalize(host))
+            .collect::<Result<_, _>>()?;
+        let endpoints = Endpoints::new(hosts);
+        let secret = config.resolve_token_secret()?;
+        let auth_header = format!("PVEAPIToken={}={}", config.token_id, secret);
 
-        let auth_header = format!("PVEAPIToken={}={}", config.token_id, config.token_secret);
+        for template in [&config.console.lxc, &config.console.vm]
+            .into_iter()
+            .flatten()
+        {
+            console::validate(template)?;
+        }
+        let route = Route::open(config)?;
+        let client = route
+            .configure(
+                Client::builder()
+                    // Compression is asked for and undone in `request`, so
+                    // the bytes on the wire can be counted
+                    .gzip(false)
+                    .deflate(false)
+                    .danger_accept_invalid_certs(true)
+                    .timeout(Duration::from_secs(10))
+                    .connect_timeout(Duration::from_secs(5)),
+            )?
+            .build()?;
 
         Ok(Self {
             name: config.name.clone(),
+            user: config.user.clone(),
             client,
-            base_url: config.host.clone(),
+            endpoints,
             auth_header,
+            budget: RequestBudget::new(config.max_requests),
+            node_status: Mutex::new(HashMap::new()),
+            net_samples: Mutex::new(HashMap::new()),
+            disks: Mutex::new(HashMap::new()),
+            updates: Mutex::new(HashMap::new()),
+            pools: Mutex::new(HashMap::new()),
+            agents: Mutex::new(HashMap::new()),
+            firewalls: Mutex::new(HashMap::new()),
+            os_infos: Mutex::new(HashMap::new()),
+            route,
+            console_lxc: config.console.lxc.clone(),
+            console_vm: config.console.vm.clone(),
+            read_only: config.read_only,
+            dry_run: config.dry_run,
         })
     }
 
-    fn fetch_node_status(&self, node: &str) -> NodeStatusData {
-        let url = format!("{}/api2/json/nodes/{}/status", self.base_url, node);
-
-        let result = self
-            .client
-            .get(&url)
-            .header("Authorization", &self.auth_header)
-            .send()
-            .and_then(|r| r.json::<ProxmoxResponse<ProxmoxNodeStatus>>());
-
-        match result {
-            Ok(response) => {
-                let cpu = response.data.cpu.unwrap_or(0.0) * 100.0;
-                let memory_used = response.data.memory.as_ref().map(|m| m.used).unwrap_or(0);
-                let memory_total = response.data.memory.as_ref().map(|m| m.total).unwrap_or(0);
-                let uptime = response.data.uptime.unwrap_or(0);
-                NodeStatusData {
-                    cpu,
-                    memory_used,
-                    memory_total,
-                    uptime,
+    /// Refuse `action` in read-only mode, before anything is sent.
+    fn check_writable(&self, action: &str) -> Result<(), ProviderError> {
+        if self.read_only {
+            return Err(ProviderError::ReadOnly {
+                action: action.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// POST an action and return its task id (empty for the few that start
+    /// none). Every action goes through here: read-only mode refuses it and
+    /// dry-run mode describes the call in a synthetic task id instead of
+    /// sending it.
+    fn act(
+        &self,
+        action: &str,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<String, ProviderError> {
+        self.act_with(Method::POST, action, path, params)
+    }
+
+    /// `act` for the actions that are not a POST, such as a config change.
+    fn act_with(
+        &self,
+        method: Method,
+        action: &str,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<String, ProviderError> {
+        self.check_writable(action)?;
+        if self.dry_run {
+            let url = host::join(self.endpoints.current(), &format!("/api2/json{}", path));
+            let mut call = format!("{} {}", method, url);
+            for (key, value) in params {
+                call.push_str(&format!(" {}={}", key, value));
+            }
+            return Ok(dry_run_task(&call));
+        }
+        let task: Option<String> = self.request(method, path, params)?;
+        Ok(task.unwrap_or_default())
+    }
+
+    /// GET an `/api2/json` path and unwrap the `data` envelope.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ProviderError> {
+        self.request(Method::GET, path, &[])
+    }
+
+    /// POST form parameters to an `/api2/json` path and unwrap the `data` envelope.
+    fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, ProviderError> {
+        self.request(Method::POST, path, params)
+    }
+
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, ProviderError> {
+        let (url, response) = self.send(method.clone(), path, params)?;
+
+        let status = response.status();
+        if status.as_u16() == 403
+            && let Some(issue) = required_permission(&method, path)
+        {
+            return Err(ProviderError::Permission(issue));
+        }
+        if !status.is_success() {
+            return Err(ProviderError::Status {
+                url,
+                status: status.as_u16(),
+            });
+        }
+
+        let encoding = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let raw = response.bytes()?;
+        self.budget.record_bytes(raw.len());
+        let body = decode_body(encoding.as_deref(), &raw).map_err(|e| ProviderError::Parse {
+            url: url.clone(),
+            source: serde_json::Error::io(e),
+        })?;
+        serde_json::from_slice::<ProxmoxResponse<T>>(&body)
+            .map(|r| r.data)
+            .map_err(|source| ProviderError::Parse { url, source })
+    }
+
+    /// Send a request to the endpoint in use, failing over to the next one
+    /// while they can't be reached. A POST only fails over when the
+    /// connection was refused, as a timed out one may have arrived.
+    fn send(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(String, reqwest::blocking::Response), ProviderError> {
+        let attempts = self.endpoints.attempts(Instant::now());
+        let mut last_error = None;
+        for index in attempts {
+            let base_url = self.endpoints.url(index);
+            let url = host::join(base_url, &format!("/api2/json{}", path));
+            self.budget.record();
+            let mut request = self
+                .client
+                .request(method.clone(), &url)
+                .header("Authorization", &self.auth_header)
+                .header(ACCEPT_ENCODING, "gzip, deflate");
+            if !params.is_empty() {
+                request = request.form(params);
+            }
+            match request.send() {
+                Ok(response) => {
+                    self.endpoints.answered(index);
+                    return Ok((url, response));
                 }
+                Err(e) if e.is_connect() || (method == Method::GET && e.is_timeout()) => {
+                    self.endpoints.unreachable(index, Instant::now());
+                    last_error = Some(self.route.explain(e, base_url));
+                }
+                Err(e) => return Err(self.route.explain(e, base_url)),
             }
-            Err(_) => NodeStatusData::default(),
         }
+        Err(last_error.expect("at least one endpoint"))
     }
 
-    fn fetch_node_vms(&self, node: &str) -> Vec<Container> {
-        let url = format!("{}/api2/json/nodes/{}/qemu", self.base_url, node);
+    fn fetch_node_list(&self) -> Result<Vec<ProxmoxNodeBasic>, ProviderError> {
+        self.get("/nodes")
+    }
 
-        let result = self
-            .client
-            .get(&url)
-            .header("Authorization", &self.auth_header)
-            .send()
-            .and_then(|r| r.json::<ProxmoxResponse<Vec<ProxmoxVm>>>());
+    fn fetch_node_status(&self, node: &str) -> Result<NodeStatusData, ProviderError> {
+        let status: ProxmoxNodeStatus = self.get(&format!("/nodes/{}/status", node))?;
 
-        match result {
-            Ok(response) => response
-                .data
-                .into_iter()
-                .map(|vm| Container {
-                    vmid: vm.vmid,
-                    name: vm.name.unwrap_or_else(|| format!("VM {}", vm.vmid)),
-                    node: node.to_string(),
-                    container_type: ContainerType::VM,
-                    status: if vm.status == "running" {
-                        ContainerStatus::Running
-                    } else {
-                        ContainerStatus::Stopped
-                    },
-                    cpu_usage: vm.cpu.unwrap_or(0.0) * 100.0,
-                    memory_used: vm.mem.unwrap_or(0),
-                    memory_max: vm.maxmem.unwrap_or(0),
-                    uptime: vm.uptime.unwrap_or(0),
-                })
-                .collect(),
-            Err(_) => Vec::new(),
+        Ok(NodeStatusData {
+            cpu: status.cpu.unwrap_or(0.0) * 100.0,
+            cpu_cores: status.cpuinfo.map(|c| c.cpus).unwrap_or(0),
+            memory_used: status.memory.as_ref().map(|m| m.used).unwrap_or(0),
+            memory_total: status.memory.as_ref().map(|m| m.total).unwrap_or(0),
+            disk_used: status.rootfs.as_ref().map(|r| r.used).unwrap_or(0),
+            disk_total: status.rootfs.as_ref().map(|r| r.total).unwrap_or(0),
+            uptime: status.uptime.unwrap_or(0),
+        })
+    }
+
+    /// Status of an online node, reused while its `/nodes` list figures are
+    /// unchanged (with the list's uptime, which always moves on).
+    fn node_status(&self, n: &ProxmoxNodeBasic) -> Result<NodeStatusData, ProviderError> {
+        if n.sample.cpu.is_some()
+            && let Some((sample, data)) = self.node_status.lock().unwrap().get(&n.node)
+            && *sample == n.sample
+        {
+            return Ok(NodeStatusData {
+                uptime: n.uptime.unwrap_or(data.uptime),
+                ..data.clone()
+            });
         }
+        let data = self.fetch_node_status(&n.node)?;
+        self.node_status
+            .lock()
+            .unwrap()
+            .insert(n.node.clone(), (n.sample.clone(), data.clone()));
+        Ok(data)
     }
 
-    fn fetch_node_lxc(&self, node: &str) -> Vec<Container> {
-        let url = format!("{}/api2/json/nodes/{}/lxc", self.base_url, node);
+    /// Interface counters for a node, with rates against the previous sample.
+    /// Throughput is optional, so it is skipped once the request budget is spent.
+    fn fetch_interfaces(&self, node: &str) -> Result<Vec<InterfaceStat>, ProviderError> {
+        if !self.budget.allows_optional() {
+            return Ok(Vec::new());
+        }
+        let netstat: Vec<ProxmoxNetstat> = self.get(&format!("/nodes/{}/netstat", node))?;
+        let now = Instant::now();
+        let current = netstat
+            .into_iter()
+            .map(|n| InterfaceStat {
+                name: n.dev,
+                rx_bytes: n.rx,
+                tx_bytes: n.tx,
+                rx_rate: 0.0,
+                tx_rate: 0.0,
+            })
+            .collect();
 
-        let result = self
-            .client
-            .get(&url)
-            .header("Authorization", &self.auth_header)
-            .send()
-            .and_then(|r| r.json::<ProxmoxResponse<Vec<ProxmoxLxc>>>());
+        let mut samples = self.net_samples.lock().unwrap();
+        let interfaces = match samples.get(node) {
+            Some((at, previous)) => interface_rates(previous, current, now - *at),
+            None => current,
+        };
+        samples.insert(node.to_string(), (now, interfaces.clone()));
+        Ok(interfaces)
+    }
 
-        match result {
-            Ok(response) => response
-                .data
-                .into_iter()
-                .map(|lxc| Container {
-                    vmid: lxc.vmid,
-                    name: lxc.name.unwrap_or_else(|| format!("CT {}", lxc.vmid)),
-                    node: node.to_string(),
-                    container_type: ContainerType::LXC,
-                    status: if lxc.status == "running" {
-                        ContainerStatus::Running
-                    } else {
-                        ContainerStatus::Stopped
-                    },
-                    cpu_usage: lxc.cpu.unwrap_or(0.0) * 100.0,
-                    memory_used: lxc.mem.unwrap_or(0),
-                    memory_max: lxc.maxmem.unwrap_or(0),
-                    uptime: lxc.uptime.unwrap_or(0),
+    /// Disks of a node, re-listed every `DISK_REFRESH_INTERVAL`. Failures
+    /// (usually a token without Sys.Audit) are kept as "unavailable" rather
+    /// than failing the refresh.
+    fn node_disks(&self, node: &str) -> NodeDisks {
+        self.cached(&self.disks, node, DISK_REFRESH_INTERVAL, || {
+            match self.get::<Vec<ProxmoxDisk>>(&format!("/nodes/{}/disks/list", node)) {
+                Ok(disks) => NodeDisks::Listed(
+                    disks
+                        .into_iter()
+                        .map(|d| Disk {
+                            dev_path: d.devpath,
+                            model: d.model.unwrap_or_default(),
+                            serial: d.serial.unwrap_or_default(),
+                            size: d.size.unwrap_or(0),
+                            health: d.health.unwrap_or_default(),
+                            wearout: d.wearout.and_then(|w| w.as_u64()).map(|w| w.min(100) as u8),
+                        })
+                        .collect(),
+                ),
+                Err(e) => NodeDisks::Unavailable(e.to_string()),
+            }
+        })
+    }
+
+    /// Packages the node could upgrade, re-read every
+    /// `UPDATES_REFRESH_INTERVAL`. Nodes that refuse or time out show nothing.
+    fn node_updates(&self, node: &str) -> Option<Vec<PackageUpdate>> {
+        self.cached(&self.updates, node, UPDATES_REFRESH_INTERVAL, || {
+            let updates: Vec<ProxmoxAptUpdate> =
+                self.get(&format!("/nodes/{}/apt/update", node)).ok()?;
+            Some(
+                updates
+                    .into_iter()
+                    .map(|u| PackageUpdate {
+                        package: u.package,
+                        old_version: u.old_version.unwrap_or_default(),
+                        new_version: u.version,
+                    })
+                    .collect(),
+            )
+        })
+    }
+
+    /// Pool of each guest that is in one, re-read every
+    /// `POOLS_REFRESH_INTERVAL`. Without access, guests just have no pool.
+    fn guest_pools(&self) -> HashMap<u32, String> {
+        self.cached(&self.pools, "cluster", POOLS_REFRESH_INTERVAL, || {
+            self.get::<Vec<ProxmoxResource>>("/cluster/resources?type=vm")
+                .map(|resources| {
+                    resources
+                        .into_iter()
+                        .filter_map(|r| Some((r.vmid?, r.pool?)))
+                        .collect()
                 })
+                .unwrap_or_default()
+        })
+    }
+
+    fn assign_pools(&self, containers: &mut [Container]) {
+        let pools = self.guest_pools();
+        for container in containers {
+            container.pool = pools.get(&container.vmid).cloned();
+        }
+    }
+
+    /// Whether a VM's guest agent answers, re-checked every
+    /// `AGENT_REFRESH_INTERVAL`: None unless the agent is enabled in the VM
+    /// config, and not responding while the VM is off or the ping fails.
+    fn guest_agent(&self, node: &str, vmid: u32, running: bool) -> Option<AgentStatus> {
+        let path = format!("/nodes/{}/qemu/{}", node, vmid);
+        self.cached(&self.agents, &path, AGENT_REFRESH_INTERVAL, || {
+            let config: ProxmoxVmConfig = self.get(&format!("{}/config", path)).ok()?;
+            if !config.agent.as_ref().is_some_and(agent_enabled) {
+                return None;
+            }
+            if !running {
+                return Some(AgentStatus::NotResponding);
+            }
+            match self.post::<IgnoredAny>(&format!("{}/agent/ping", path), &[]) {
+                Ok(_) => Some(AgentStatus::Responding),
+                // Proxmox answers 500 when the agent is not running in the guest
+                Err(ProviderError::Status { .. }) => Some(AgentStatus::NotResponding),
+                Err(_) => None,
+            }
+        })
+    }
+
+    /// The firewall under `path` (`/cluster` or a guest's path), re-read
+    /// every `FIREWALL_REFRESH_INTERVAL`. Its rules are counted with one more
+    /// request while it is on and the budget allows. Options that can't be
+    /// read, usually for lack of permission, leave it unknown.
+    fn firewall(&self, path: &str) -> Option<FirewallStatus> {
+        self.cached(&self.firewalls, path, FIREWALL_REFRESH_INTERVAL, || {
+            let Ok(options) =
+                self.get::<ProxmoxFirewallOptions>(&format!("{}/firewall/options", path))
+            else {
+                return Some(FirewallStatus::Unknown);
+            };
+            if options.enable == 0 {
+                return Some(FirewallStatus::Off);
+            }
+            let rules = self
+                .budget
+                .allows_optional()
+                .then(|| self.get::<Vec<IgnoredAny>>(&format!("{}/firewall/rules", path)))
+                .and_then(|rules| rules.ok())
+                .map(|rules| rules.len());
+            Some(FirewallStatus::On { rules })
+        })
+    }
+
+    /// The guest an HA resource id names: "vm:105" or "ct:105"; other
+    /// kinds of resource aren't guests.
+    fn ha_guest(&self, sid: &str) -> Option<GuestId> {
+        let (kind, vmid) = sid.split_once(':')?;
+        if kind != "vm" && kind != "ct" {
+            return None;
+        }
+        Some(GuestId::new(&self.name, vmid.parse::<u32>().ok()?))
+    }
+
+    /// Guests in error and nodes fenced or unknown, from the HA manager's
+    /// own status. Empty when the cluster has no HA or the token can't
+    /// read it: nothing to flag then.
+    fn ha_status(&self) -> HaStatus {
+        let Ok(status) = self.get::<ProxmoxHaManager>("/cluster/ha/status/manager_status") else {
+            return HaStatus::default();
+        };
+        let manager = status.manager_status;
+        let mut ha = HaStatus {
+            errors: manager
+                .service_status
+                .iter()
+                .filter(|(_, service)| service.state == "error")
+                .filter_map(|(sid, _)| self.ha_guest(sid))
                 .collect(),
-            Err(_) => Vec::new(),
+            ..HaStatus::default()
+        };
+        for (node, state) in manager.node_status {
+            match state.as_str() {
+                "fence" => ha.fenced.push(node),
+                "unknown" => ha.unknown.push(node),
+                _ => {}
+            }
+        }
+        ha.errors.sort_by_key(|guest| guest.id.parse::<u32>().ok());
+        ha.fenced.sort();
+        ha.unknown.sort();
+        ha
+    }
+
+    /// What a guest runs: for a VM whose agent answers, the OS it reports;
+    /// otherwise the config's `ostype`, as a label for VMs ("l26" is Linux).
+    fn os_info(
+        &self,
+        path: &str,
+        container: &Container,
+        agent: Option<AgentStatus>,
+    ) -> Option<String> {
+        self.cached(&self.os_infos, path, OS_INFO_REFRESH_INTERVAL, || {
+            if agent == Some(AgentStatus::Responding)
+                && let Ok(info) = self
+                    .get::<ProxmoxAgentResult<ProxmoxOsInfo>>(&format!("{}/agent/get-osinfo", path))
+                && let Some(described) = info.result.describe()
+            {
+                return Some(described);
+            }
+            let config: ProxmoxOsConfig = self.get(&format!("{}/config", path)).ok()?;
+            let ostype = config.ostype?;
+            match container.container_type {
+                ContainerType::VM => {
+                    Some(os_type_label(&ostype).map(str::to_string).unwrap_or(ostype))
+                }
+                _ => Some(ostype),
+            }
+        })
+    }
+
+    /// The OS last found for the guest under `path`, without asking.
+    fn known_os_info(&self, path: &str) -> Option<String> {
+        self.os_infos
+            .lock()
+            .unwrap()
+            .get(path)
+            .and_then(|(_, os)| os.clone())
+    }
+
+    /// The cached value for a node while it is younger than `interval`,
+    /// otherwise a fresh `fetch`. Past the request budget the last value
+    /// (or nothing) is kept.
+    fn cached<T: Clone + Default>(
+        &self,
+        cache: &NodeCache<T>,
+        node: &str,
+        interval: Duration,
+        fetch: impl FnOnce() -> T,
+    ) -> T {
+        let now = Instant::now();
+        let mut cache = cache.lock().unwrap();
+        if let Some((at, value)) = cache.get(node)
+            && now.saturating_duration_since(*at) < interval
+        {
+            return value.clone();
+        }
+        if !self.budget.allows_optional() {
+            return cache
+                .get(node)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default();
         }
+
+        let value = fetch();
+        cache.insert(node.to_string(), (now, value.clone()));
+        value
+    }
+
+    /// A node from the `/nodes` list, with status, throughput and disks for
+    /// online nodes.
+    fn build_node(&self, n: ProxmoxNodeBasic) -> Result<Node, ProviderError> {
+        let (status_data, interfaces, disks, updates) = if n.status == "online" {
+            (
+                self.node_status(&n)?,
+                self.fetch_interfaces(&n.node)?,
+                self.node_disks(&n.node),
+                self.node_updates(&n.node),
+            )
+        } else {
+            (
+                NodeStatusData::default(),
+                Vec::new(),
+                NodeDisks::default(),
+                None,
+            )
+        };
+
+        Ok(Node {
+            id: NodeId::new(&self.name, &n.node),
+            name: n.node,
+            status: if n.status == "online" {
+                NodeStatus::Online
+            } else {
+                NodeStatus::Offline
+            },
+            cpu_usage: status_data.cpu,
+            cpu_cores: status_data.cpu_cores,
+            memory_used: status_data.memory_used,
+            memory_total: status_data.memory_total,
+            disk_used: status_data.disk_used,
+            disk_total: status_data.disk_total,
+            uptime: status_data.uptime,
+            interfaces,
+            disks,
+            pending_updates: updates.as_ref().map(|u| u.len() as u32),
+            package_updates: updates.unwrap_or_default(),
+            pressure: None,
+        })
+    }
+
+    fn fetch_node_vms(&self, node: &str) -> Result<Vec<Container>, ProviderError> {
+        let vms: Vec<ProxmoxVm> = self.get(&format!("/nodes/{}/qemu", node))?;
+
+        Ok(vms
+            .into_iter()
+            .map(|vm| Container {
+                id: GuestId::new(&self.name, vm.vmid),
+                vmid: vm.vmid,
+                name: vm.name.unwrap_or_else(|| format!("VM {}", vm.vmid)),
+                node: node.to_string(),
+                container_type: ContainerType::VM,
+                status: if vm.status == "running" {
+                    ContainerStatus::Running
+                } else {
+                    ContainerStatus::Stopped
+                },
+                cpu_usage: vm.cpu.unwrap_or(0.0) * 100.0,
+                max_cpu: vm.cpus.unwrap_or(0.0).ceil() as u32,
+                memory_used: vm.mem.unwrap_or(0),
+                memory_max: vm.maxmem.unwrap_or(0),
+                uptime: vm.uptime.unwrap_or(0),
+                lock: vm.lock,
+                missing: false,
+                pool: None,
+                is_template: vm.template == 1,
+                disk_used: vm.disk.filter(|&used| used > 0),
+                disk_max: vm.maxdisk.unwrap_or(0),
+                os_info: self.known_os_info(&format!("/nodes/{}/qemu/{}", node, vm.vmid)),
+            })
+            .collect())
+    }
+
+    fn fetch_node_lxc(&self, node: &str) -> Result<Vec<Container>, ProviderError> {
+        let containers: Vec<ProxmoxLxc> = self.get(&format!("/nodes/{}/lxc", node))?;
+
+        Ok(containers
+            .into_iter()
+            .map(|lxc| Container {
+                id: GuestId::new(&self.name, lxc.vmid),
+                vmid: lxc.vmid,
+                name: lxc.name.unwrap_or_else(|| format!("CT {}", lxc.vmid)),
+                node: node.to_string(),
+                container_type: ContainerType::LXC,
+                status: if lxc.status == "running" {
+                    ContainerStatus::Running
+                } else {
+                    ContainerStatus::Stopped
+                },
+                cpu_usage: lxc.cpu.unwrap_or(0.0) * 100.0,
+                max_cpu: lxc.cpus.unwrap_or(0.0).ceil() as u32,
+                memory_used: lxc.mem.unwrap_or(0),
+                memory_max: lxc.maxmem.unwrap_or(0),
+                uptime: lxc.uptime.unwrap_or(0),
+                lock: lxc.lock,
+                missing: false,
+                pool: None,
+                is_template: lxc.template == 1,
+                disk_used: lxc.disk,
+                disk_max: lxc.maxdisk.unwrap_or(0),
+                os_info: self.known_os_info(&format!("/nodes/{}/lxc/{}", node, lxc.vmid)),
+            })
+            .collect())
+    }
+
+    /// In-guest memory from the balloon driver, if the VM reports it.
+    fn fetch_vm_memory(&self, node: &str, vmid: u32) -> Result<Option<GuestMemory>, ProviderError> {
+        let status: ProxmoxVmStatus =
+            self.get(&format!("/nodes/{}/qemu/{}/status/current", node, vmid))?;
+
+        Ok(status.ballooninfo.and_then(|info| {
+            let total = info.total_mem?;
+            let free = info.free_mem?;
+            Some(GuestMemory {
+                allocated: info.actual.or(status.balloon).unwrap_or(total),
+                guest_used: total.saturating_sub(free),
+                guest_total: total,
+            })
+        }))
+    }
+
+    fn fetch_node_replication(&self, node: &str) -> Result<Vec<ReplicationJob>, ProviderError> {
+        let jobs: Vec<ProxmoxReplication> = self.get(&format!("/nodes/{}/replication", node))?;
+
+        Ok(jobs
+            .into_iter()
+            .map(|job| ReplicationJob {
+                guest: GuestId::new(&self.name, job.guest),
+                source: node.to_string(),
+                target: job.target,
+                last_sync: job.last_sync.unwrap_or(0),
+                fail_count: job.fail_count.unwrap_or(0),
+                error: job.error,
+            })
+            .collect())
     }
 }
 
+/// The action privileges held on no path their objects are under: `/`,
+/// the privilege's own tree (`/vms`, `/nodes`) or anything in it, and for
+/// guests also a pool.
+fn missing_action_privileges(permissions: &Permissions) -> Vec<PermissionIssue> {
+    let under = |tree: &str, path: &str| {
+        path == "/"
+            || path == tree
+            || path.starts_with(&format!("{}/", tree))
+            || (tree == "/vms" && path.starts_with("/pool/"))
+    };
+    ACTION_PRIVILEGES
+        .iter()
+        .filter(|(privilege, tree, _)| {
+            !permissions
+                .iter()
+                .any(|(path, privileges)| under(tree, path) && privileges.contains_key(*privilege))
+        })
+        .map(|(privilege, tree, consequence)| PermissionIssue::new(privilege, tree, consequence))
+        .collect()
+}
+
+/// The privilege an API path needs, phrased for a user who has to fix the
+/// token, together with what is lost without it.
+fn required_permission(method: &Method, path: &str) -> Option<PermissionIssue> {
+    let (privilege, acl_path, consequence) = if path == "/nodes" {
+        ("Sys.Audit", "/nodes", "nodes will not be shown")
+    } else if path == "/cluster/status" {
+        ("Sys.Audit", "/", "cluster quorum will not be shown")
+    } else if path.starts_with("/cluster/log") {
+        ("Sys.Syslog", "/", "the cluster log will not be shown")
+    } else if path.starts_with("/cluster/firewall/") {
+        (
+            "Sys.Audit",
+            "/",
+            "the cluster firewall will show as unknown",
+        )
+    } else if !path.starts_with("/nodes/") {
+        return None;
+    } else if path.contains("/firewall/") {
+        ("VM.Audit", "/vms", "guest firewalls will show as unknown")
+    } else if path.ends_with("/migrate") {
+        ("VM.Migrate", "/vms", "guests cannot be migrated")
+    } else if path.ends_with("/clone") {
+        ("VM.Clone", "/vms", "guests cannot be cloned")
+    } else if path.ends_with("/vncproxy") || path.ends_with("/termproxy") {
+        ("VM.Console", "/vms", "web consoles cannot be opened")
+    } else if path.ends_with("/config") && *method == Method::PUT {
+        (
+            "VM.Config.CPU or VM.Config.Memory",
+            "/vms",
+            "guest cores and memory cannot be changed",
+        )
+    } else if path.ends_with("/status/shutdown") {
+        (
+            "VM.PowerMgmt",
+            "/vms",
+            "guests cannot be started or shut down",
+        )
+    } else if path.contains("/tasks/") {
+        ("Sys.Audit", "/nodes", "task progress will not be shown")
+    } else if path.ends_with("/disks/list") {
+        ("Sys.Audit", "/nodes", "disk health will not be shown")
+    } else if path.ends_with("/netstat") {
+        (
+            "Sys.Audit",
+            "/nodes",
+            "interface throughput will not be shown",
+        )
+    } else if path.ends_with("/status/current") {
+        ("VM.Audit", "/vms", "in-guest memory will not be shown")
+    } else if path.ends_with("/qemu") || path.ends_with("/lxc") {
+        ("VM.Audit", "/vms", "containers will not be shown")
+    } else if path.ends_with("/replication") {
+        ("VM.Audit", "/vms", "replication status will not be shown")
+    } else if path.ends_with("/status") && *method == Method::POST {
+        (
+            "Sys.PowerMgmt",
+            "/nodes",
+            "nodes cannot be rebooted or shut down",
+        )
+    } else if path.ends_with("/status") {
+        ("Sys.Audit", "/nodes", "node metrics will not be shown")
+    } else {
+        return None;
+    };
+    Some(PermissionIssue::new(privilege, acl_path, consequence))
+}
+
 impl Provider for ProxmoxProvider {
     fn name(&self) -> &str {
         &self.name
     }
 
-    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
-        let url = format!("{}/api2/json/nodes", self.base_url);
+    fn user(&self) -> Option<&str> {
+        Some(&self.user)
+    }
 
-        let response: ProxmoxResponse<Vec<ProxmoxNodeBasic>> = self
-            .client
-            .get(&url)
-            .header("Authorization", &self.auth_header)
-            .send()?
-            .json()?;
+    fn console_template(&self, container: &Container) -> Option<&str> {
+        match container.container_type {
+            ContainerType::LXC => self.console_lxc.as_deref(),
+            ContainerType::VM => self.console_vm.as_deref(),
+            ContainerType::Service | ContainerType::Process | ContainerType::Docker => None,
+        }
+    }
 
-        let mut nodes = Vec::new();
+    fn begin_refresh(&self) {
+        self.budget.reset();
+    }
 
-        for n in response.data {
-            let status_data = if n.status == "online" {
-                self.fetch_node_status(&n.node)
-            } else {
-                NodeStatusData::default()
-            };
+    fn request_count(&self) -> Option<u32> {
+        Some(self.budget.used())
+    }
 
-            nodes.push(Node {
-                name: n.node,
-                status: if n.status == "online" {
-                    NodeStatus::Online
-                } else {
-                    NodeStatus::Offline
-                },
-                cpu_usage: status_data.cpu,
-                memory_used: status_data.memory_used,
-                memory_total: status_data.memory_total,
-                uptime: status_data.uptime,
-            });
+    fn bytes_received(&self) -> Option<u64> {
+        Some(self.budget.bytes())
+    }
+
+    fn endpoint(&self) -> Option<EndpointInUse> {
+        self.endpoints.in_use()
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        let mut nodes = Vec::new();
+
+        for n in self.fetch_node_list()? {
+            nodes.push(self.build_node(n)?);
         }
 
         Ok(nodes)
     }
 
-    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
-        let url = format!("{}/api2/json/nodes", self.base_url);
+    fn fetch_node(&self, name: &str) -> Result<Option<Node>, Box<dyn std::error::Error>> {
+        let Some(n) = self.fetch_node_list()?.into_iter().find(|n| n.node == name) else {
+            return Ok(None);
+        };
+        // Re-read status, disks and updates now instead of waiting for the cache
+        self.node_status.lock().unwrap().remove(name);
+        self.disks.lock().unwrap().remove(name);
+        self.updates.lock().unwrap().remove(name);
+        Ok(Some(self.build_node(n)?))
+    }
 
-        let response: ProxmoxResponse<Vec<ProxmoxNodeBasic>> = self
-            .client
-            .get(&url)
-            .header("Authorization", &self.auth_header)
-            .send()?
-            .json()?;
+    fn fetch_guest(
+        &self,
+        node: &str,
+        vmid: u32,
+    ) -> Result<Option<Container>, Box<dyn std::error::Error>> {
+        let guest = match self
+            .fetch_node_vms(node)?
+            .into_iter()
+            .find(|c| c.vmid == vmid)
+        {
+            Some(vm) => Some(vm),
+            None => self
+                .fetch_node_lxc(node)?
+                .into_iter()
+                .find(|c| c.vmid == vmid),
+        };
+        // Check the guest agent, firewalls and OS again instead of waiting
+        // for the cache; the guest keeps the OS it was listed with meanwhile
+        self.agents
+            .lock()
+            .unwrap()
+            .remove(&format!("/nodes/{}/qemu/{}", node, vmid));
+        for kind in ["qemu", "lxc"] {
+            let path = format!("/nodes/{}/{}/{}", node, kind, vmid);
+            self.firewalls.lock().unwrap().remove(&path);
+            self.os_infos.lock().unwrap().remove(&path);
+        }
+        Ok(guest.map(|mut guest| {
+            self.assign_pools(std::slice::from_mut(&mut guest));
+            guest
+        }))
+    }
 
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
         let mut all_containers = Vec::new();
 
-        for n in response.data {
+        for n in self.fetch_node_list()? {
             if n.status == "online" {
-                all_containers.extend(self.fetch_node_vms(&n.node));
-                all_containers.extend(self.fetch_node_lxc(&n.node));
+                all_containers.extend(self.fetch_node_vms(&n.node)?);
+                all_containers.extend(self.fetch_node_lxc(&n.node)?);
             }
         }
 
+        self.assign_pools(&mut all_containers);
         Ok(all_containers)
     }
+
+    fn probe_permissions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        let nodes = match self.fetch_node_list() {
+            Ok(nodes) => nodes,
+            Err(ProviderError::Permission(issue)) => return Ok(vec![issue]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut paths = vec![
+            "/cluster/status".to_string(),
+            "/cluster/log?max=1".to_string(),
+        ];
+        if let Some(node) = nodes.iter().find(|n| n.status == "online") {
+            for endpoint in [
+                "status",
+                "netstat",
+                "disks/list",
+                "qemu",
+                "lxc",
+                "replication",
+            ] {
+                paths.push(format!("/nodes/{}/{}", node.node, endpoint));
+            }
+        }
+
+        let mut issues = Vec::new();
+        for path in paths {
+            match self.get::<serde_json::Value>(&path) {
+                Ok(_) => {}
+                Err(ProviderError::Permission(issue)) => {
+                    if !issues.contains(&issue) {
+                        issues.push(issue);
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(issues)
+    }
+
+    fn probe_actions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        // Refused anyway, so there is nothing to tell
+        if self.read_only {
+            return Ok(Vec::new());
+        }
+        let permissions: Permissions = self.get("/access/permissions")?;
+        Ok(missing_action_privileges(&permissions))
+    }
+
+    fn fetch_cluster_status(&self) -> Result<Option<ClusterStatus>, Box<dyn std::error::Error>> {
+        let entries: Vec<ProxmoxClusterEntry> = self.get("/cluster/status")?;
+
+        // Standalone hosts only list themselves as a "node" entry
+        let Some(cluster) = entries.iter().find(|e| e.kind == "cluster") else {
+            return Ok(None);
+        };
+        let members = entries.iter().filter(|e| e.kind == "node");
+
+        Ok(Some(ClusterStatus {
+            name: cluster.name.clone(),
+            quorate: cluster.quorate == Some(1),
+            nodes_online: members.clone().filter(|e| e.online == Some(1)).count() as u32,
+            nodes_total: cluster.nodes.unwrap_or(members.count() as u32),
+            firewall: self.firewall("/cluster"),
+            ha: self.ha_status(),
+        }))
+    }
+
+    fn fetch_replication(&self) -> Result<Vec<ReplicationJob>, Box<dyn std::error::Error>> {
+        let mut jobs = Vec::new();
+
+        for n in self.fetch_node_list()? {
+            if n.status == "online" {
+                jobs.extend(self.fetch_node_replication(&n.node)?);
+            }
+        }
+
+        Ok(jobs)
+    }
+
+    fn fetch_ha_resources(&self) -> Result<Vec<HaResource>, Box<dyn std::error::Error>> {
+        let resources: Vec<ProxmoxHaResource> = self.get("/cluster/ha/resources")?;
+        Ok(resources
+            .into_iter()
+            .filter_map(|r| {
+                Some(HaResource {
+                    guest: self.ha_guest(&r.sid)?,
+                    state: r.state.unwrap_or_else(|| "started".to_string()),
+                })
+            })
+            .collect())
+    }
+
+    fn fetch_cluster_log(
+        &self,
+        max: usize,
+    ) -> Result<Vec<ClusterLogEntry>, Box<dyn std::error::Error>> {
+        if !self.budget.allows_optional() {
+            return Ok(Vec::new());
+        }
+        let entries: Vec<ProxmoxLogEntry> = self.get(&format!("/cluster/log?max={}", max))?;
+        Ok(entries
+            .into_iter()
+            .map(|e| ClusterLogEntry {
+                uid: match e.uid {
+                    serde_json::Value::String(uid) => uid,
+                    uid => uid.to_string(),
+                },
+                time: e.time,
+                node: e.node,
+                tag: e.tag,
+                user: e.user.filter(|u| !u.is_empty()),
+                level: EventLevel::from_priority(e.pri),
+                message: e.msg,
+            })
+            .collect())
+    }
+
+    fn fetch_guest_details(
+        &self,
+        container: &Container,
+    ) -> Result<GuestDetails, Box<dyn std::error::Error>> {
+        let mut details = GuestDetails::default();
+
+        if !self.budget.allows_optional() {
+            details.deferred = true;
+            return Ok(details);
+        }
+
+        if container.container_type == ContainerType::VM
+            && container.status == ContainerStatus::Running
+        {
+            details.memory = self.fetch_vm_memory(&container.node, container.vmid)?;
+        }
+        if container.container_type == ContainerType::VM {
+            let running = container.status == ContainerStatus::Running;
+            details.agent = self.guest_agent(&container.node, container.vmid, running);
+        }
+        let path = guest_path(container)?;
+        details.firewall = self.firewall(&path);
+        details.os_info = self.os_info(&path, container, details.agent);
+
+        Ok(details)
+    }
+
+    fn migrate_guest(
+        &self,
+        container: &Container,
+        target: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let running = container.status == ContainerStatus::Running;
+        let mut params = vec![("target", target)];
+        match container.container_type {
+            // Running containers can't move live; Proxmox restarts them on the target
+            ContainerType::LXC if running => params.push(("restart", "1")),
+            ContainerType::VM if running => params.push(("online", "1")),
+            _ => {}
+        }
+        let path = format!("{}/migrate", guest_path(container)?);
+        Ok(self.act("migration", &path, &params)?)
+    }
+
+    fn start_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        let path = format!("{}/status/start", guest_path(container)?);
+        Ok(self.act("start", &path, &[])?)
+    }
+
+    fn shutdown_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        let path = format!("{}/status/shutdown", guest_path(container)?);
+        Ok(self.act("shutdown", &path, &[])?)
+    }
+
+    fn fetch_guest_resources(
+        &self,
+        container: &Container,
+    ) -> Result<GuestResources, Box<dyn std::error::Error>> {
+        let path = format!("{}/config", guest_path(container)?);
+        let config: ProxmoxResourceConfig = self.get(&path)?;
+        Ok(config.resources())
+    }
+
+    fn next_vmid(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        let next: NextId = self.get("/cluster/nextid")?;
+        Ok(u32::try_from(next.0)?)
+    }
+
+    fn clone_guest(
+        &self,
+        container: &Container,
+        request: &CloneRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let newid = request.vmid.to_string();
+        // Containers call their name the hostname
+        let name_param = match container.container_type {
+            ContainerType::LXC => "hostname",
+            _ => "name",
+        };
+        let mut params = vec![
+            ("newid", newid.as_str()),
+            (name_param, request.name.as_str()),
+            ("full", if request.full { "1" } else { "0" }),
+        ];
+        if let Some(target) = &request.target {
+            params.push(("target", target));
+        }
+        let path = format!("{}/clone", guest_path(container)?);
+        Ok(self.act("clone", &path, &params)?)
+    }
+
+    fn set_guest_resources(
+        &self,
+        container: &Container,
+        request: &ResourceRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let values = resource_params(request);
+        let params: Vec<(&str, &str)> = values.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let path = format!("{}/config", guest_path(container)?);
+        // A PUT applies what it can right away and leaves the rest pending,
+        // where POST would start a task
+        Ok(self.act_with(Method::PUT, "resource change", &path, &params)?)
+    }
+
+    fn node_power(
+        &self,
+        node: &str,
+        command: NodePower,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let path = format!("/nodes/{}/status", node);
+        Ok(self.act(command.describe(), &path, &[("command", command.as_str())])?)
+    }
+
+    fn web_console_url(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        self.check_writable("console")?;
+        if container.status != ContainerStatus::Running {
+            return Err(format!("{} is not running", container.name).into());
+        }
+        // VMs get a VNC proxy for noVNC, containers a terminal proxy for xterm.js
+        let (endpoint, console, viewer) = match container.container_type {
+            ContainerType::LXC => ("termproxy", "lxc", "xtermjs"),
+            _ => ("vncproxy", "kvm", "novnc"),
+        };
+        let path = format!("{}/{}", guest_path(container)?, endpoint);
+        let ticket: ProxmoxConsoleTicket = self.post(&path, &[("websocket", "1")])?;
+        let vmid = container.vmid.to_string();
+        let port = ticket.port.to_string();
+        let url = reqwest::Url::parse_with_params(
+            &host::join(self.endpoints.current(), "/"),
+            [
+                ("console", console),
+                (viewer, "1"),
+                ("node", container.node.as_str()),
+                ("vmid", vmid.as_str()),
+                ("vmname", container.name.as_str()),
+                ("resize", "scale"),
+                ("port", port.as_str()),
+                ("vncticket", ticket.ticket.as_str()),
+            ],
+        )?;
+        Ok(url.into())
+    }
+
+    fn task_status(
+        &self,
+        node: &str,
+        task: &str,
+    ) -> Result<TaskStatus, Box<dyn std::error::Error>> {
+        if dry_run_call(task).is_some() {
+            return Ok(TaskStatus::Succeeded);
+        }
+        let status: ProxmoxTaskStatus =
+            self.get(&format!("/nodes/{}/tasks/{}/status", node, task))?;
+        Ok(match (status.status.as_str(), status.exitstatus) {
+            ("running", _) => TaskStatus::Running,
+            (_, Some(exit)) if exit == "OK" => TaskStatus::Succeeded,
+            (_, Some(exit)) => TaskStatus::Failed(exit),
+            (_, None) => TaskStatus::Failed("task ended without an exit status".to_string()),
+        })
+    }
+}
+
+/// API path of a guest, e.g. `/nodes/pve1/qemu/101`.
+fn guest_path(container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+    let kind = match container.container_type {
+        ContainerType::VM => "qemu",
+        ContainerType::LXC => "lxc",
+        ContainerType::Service | ContainerType::Process | ContainerType::Docker => {
+            return Err("not a Proxmox guest".into());
+        }
+    };
+    Ok(format!(
+        "/nodes/{}/{}/{}",
+        container.node, kind, container.vmid
+    ))
+}
+
+/// Undo the `Content-Encoding` of a response body. HTTP "deflate" is zlib
+/// framed.
+fn decode_body(encoding: Option<&str>, raw: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    match encoding {
+        Some("gzip") => GzDecoder::new(raw).read_to_end(&mut body)?,
+        Some("deflate") => ZlibDecoder::new(raw).read_to_end(&mut body)?,
+        _ => return Ok(raw.to_vec()),
+    };
+    Ok(body)
 }
 
 // --- Helper Structs ---
 
-#[derive(Default)]
+/// `/cluster/nextid` answers with the VMID as a string.
+#[derive(Debug, Deserialize)]
+struct NextId(#[serde(deserialize_with = "number_or_string")] u64);
+
+/// What `vncproxy` and `termproxy` hand out for one console connection.
+#[derive(Debug, Deserialize)]
+struct ProxmoxConsoleTicket {
+    ticket: String,
+    #[serde(deserialize_with = "number_or_string")]
+    port: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxTaskStatus {
+    status: String,
+    exitstatus: Option<String>,
+}
+
+#[derive(Clone, Default)]
 struct NodeStatusData {
     cpu: f64,
+    cpu_cores: u32,
     memory_used: u64,
     memory_total: u64,
+    disk_used: u64,
+    disk_total: u64,
     uptime: u64,
 }
 
@@ -213,30 +1254,105 @@ struct ProxmoxResponse<T> {
 struct ProxmoxNodeBasic {
     node: String,
     status: String,
+    uptime: Option<u64>,
+    #[serde(flatten)]
+    sample: NodeListSample,
+}
+
+/// The figures the `/nodes` list carries for a node. pvestatd updates them
+/// together with the node status, so while they stay the same the status
+/// has nothing new either.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct NodeListSample {
+    cpu: Option<f64>,
+    mem: Option<u64>,
+    maxmem: Option<u64>,
+    disk: Option<u64>,
+    maxdisk: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ProxmoxNodeStatus {
     cpu: Option<f64>,
+    cpuinfo: Option<ProxmoxCpuInfo>,
     memory: Option<ProxmoxMemory>,
+    rootfs: Option<ProxmoxMemory>,
     uptime: Option<u64>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ProxmoxCpuInfo {
+    cpus: u32,
+}
+
+/// Memory, and also the root filesystem, which reports the same fields.
 #[derive(Debug, Deserialize)]
 struct ProxmoxMemory {
     total: u64,
     used: u64,
 }
 
+#[derive(Debug, Deserialize)]
+struct ProxmoxDisk {
+    devpath: String,
+    model: Option<String>,
+    serial: Option<String>,
+    size: Option<u64>,
+    health: Option<String>,
+    /// Remaining life in percent, or "N/A" for disks without the attribute
+    wearout: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProxmoxAptUpdate {
+    package: String,
+    old_version: Option<String>,
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxNetstat {
+    dev: String,
+    #[serde(rename = "in", deserialize_with = "number_or_string")]
+    rx: u64,
+    #[serde(rename = "out", deserialize_with = "number_or_string")]
+    tx: u64,
+}
+
+/// Counters in `netstat` come back as strings on some PVE versions.
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Counter {
+        Number(u64),
+        Text(String),
+    }
+    match Counter::deserialize(deserializer)? {
+        Counter::Number(n) => Ok(n),
+        Counter::Text(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ProxmoxVm {
     vmid: u32,
     name: Option<String>,
     status: String,
     cpu: Option<f64>,
+    /// Allocated cores; reported as `maxcpu` by some endpoints
+    #[serde(alias = "maxcpu")]
+    cpus: Option<f64>,
     mem: Option<u64>,
     maxmem: Option<u64>,
     uptime: Option<u64>,
+    /// Bytes used and size of the root volume
+    disk: Option<u64>,
+    maxdisk: Option<u64>,
+    lock: Option<String>,
+    /// 1 for templates, absent otherwise
+    #[serde(default, deserialize_with = "number_or_string")]
+    template: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -245,7 +1361,2332 @@ struct ProxmoxLxc {
     name: Option<String>,
     status: String,
     cpu: Option<f64>,
+    /// Allocated cores; reported as `maxcpu` by some endpoints
+    #[serde(alias = "maxcpu")]
+    cpus: Option<f64>,
     mem: Option<u64>,
     maxmem: Option<u64>,
     uptime: Option<u64>,
+    /// Bytes used and size of the root volume
+    disk: Option<u64>,
+    maxdisk: Option<u64>,
+    lock: Option<String>,
+    /// 1 for templates, absent otherwise
+    #[serde(default, deserialize_with = "number_or_string")]
+    template: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxResource {
+    vmid: Option<u32>,
+    pool: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxVmStatus {
+    balloon: Option<u64>,
+    ballooninfo: Option<ProxmoxBalloonInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxFirewallOptions {
+    /// Missing while the firewall has never been turned on.
+    #[serde(default, deserialize_with = "number_or_string")]
+    enable: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxVmConfig {
+    /// `1`, or a property string such as "enabled=1,fstrim_cloned_disks=1".
+    agent: Option<serde_json::Value>,
+}
+
+/// The OS type a guest's config declares, VM or container.
+#[derive(Debug, Deserialize)]
+struct ProxmoxOsConfig {
+    ostype: Option<String>,
+}
+
+/// Guest agent commands wrap their answer in `result`.
+#[derive(Debug, Deserialize)]
+struct ProxmoxAgentResult<T> {
+    result: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxOsInfo {
+    #[serde(rename = "pretty-name")]
+    pretty_name: Option<String>,
+    name: Option<String>,
+    version: Option<String>,
+    #[serde(rename = "kernel-release")]
+    kernel_release: Option<String>,
+}
+
+impl ProxmoxOsInfo {
+    /// "Debian GNU/Linux 12 (bookworm), kernel 6.1.0-18-amd64"
+    fn describe(&self) -> Option<String> {
+        let name = self.pretty_name.clone().or_else(|| {
+            let name = self.name.as_ref()?;
+            Some(match &self.version {
+                Some(version) => format!("{} {}", name, version),
+                None => name.clone(),
+            })
+        })?;
+        Some(match &self.kernel_release {
+            Some(kernel) => format!("{}, kernel {}", name, kernel),
+            None => name,
+        })
+    }
+}
+
+/// What a VM config's `ostype` stands for, as the Proxmox web UI words it.
+fn os_type_label(ostype: &str) -> Option<&'static str> {
+    Some(match ostype {
+        "l24" => "Linux 2.4",
+        "l26" => "Linux",
+        "win11" => "Windows 11/2022/2025",
+        "win10" => "Windows 10/2016/2019",
+        "win8" => "Windows 8/2012/2012r2",
+        "win7" => "Windows 7/2008r2",
+        "w2k8" => "Windows Vista/2008",
+        "wvista" => "Windows Vista",
+        "w2k3" => "Windows 2003",
+        "w2k" => "Windows 2000",
+        "wxp" => "Windows XP",
+        "solaris" => "Solaris",
+        "other" => "Other",
+        _ => return None,
+    })
+}
+
+/// The cores and memory of a guest's config, VM or container; the rest
+/// only VMs have.
+#[derive(Debug, Deserialize)]
+struct ProxmoxResourceConfig {
+    cores: Option<u32>,
+    sockets: Option<u32>,
+    /// MiB, as a number, or a string such as "4096" or "current=4096"
+    /// (PVE 8.1 on).
+    memory: Option<serde_json::Value>,
+    /// MiB; 0 turns the balloon off.
+    balloon: Option<u64>,
+    numa: Option<u64>,
+    /// "network,disk,usb" when unset; "1" is that too, "0" nothing.
+    hotplug: Option<String>,
+}
+
+/// Memory Proxmox gives a guest whose config doesn't say.
+const DEFAULT_MEMORY_MB: u64 = 512;
+
+impl ProxmoxResourceConfig {
+    fn resources(&self) -> GuestResources {
+        let memory_hotplug = match self.hotplug.as_deref() {
+            None | Some("0" | "1") => false,
+            Some(features) => features.split(',').any(|f| f.trim() == "memory"),
+        };
+        GuestResources {
+            cores: self.cores,
+            sockets: self.sockets.unwrap_or(1),
+            memory_mb: self
+                .memory
+                .as_ref()
+                .and_then(memory_mb)
+                .unwrap_or(DEFAULT_MEMORY_MB),
+            balloon_mb: self.balloon.filter(|&b| b > 0),
+            // Memory hot-plug needs NUMA on as well
+            memory_hotplug: memory_hotplug && self.numa == Some(1),
+        }
+    }
+}
+
+fn memory_mb(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => s
+            .split(',')
+            .find_map(|part| match part.split_once('=') {
+                None => Some(part),
+                Some(("current", current)) => Some(current),
+                Some(_) => None,
+            })?
+            .trim()
+            .parse()
+            .ok(),
+        _ => None,
+    }
+}
+
+/// The config parameters for `request`, only those that change.
+fn resource_params(request: &ResourceRequest) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if let Some(cores) = request.cores {
+        params.push(("cores", cores.to_string()));
+    }
+    if let Some(memory) = request.memory_mb {
+        params.push(("memory", memory.to_string()));
+    }
+    params
+}
+
+/// Whether a VM config's `agent` value turns the agent on.
+fn agent_enabled(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64() != Some(0),
+        serde_json::Value::String(s) => s
+            .split(',')
+            .find_map(|part| match part.split_once('=') {
+                None => Some(part),
+                Some(("enabled", enabled)) => Some(enabled),
+                Some(_) => None,
+            })
+            .is_some_and(|enabled| matches!(enabled, "1" | "yes" | "on" | "true")),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxBalloonInfo {
+    actual: Option<u64>,
+    total_mem: Option<u64>,
+    free_mem: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxClusterEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    quorate: Option<u8>,
+    nodes: Option<u32>,
+    online: Option<u8>,
+}
+
+/// An entry of `/cluster/log`.
+#[derive(Debug, Deserialize)]
+struct ProxmoxLogEntry {
+    /// A number on current versions; compared as text either way.
+    uid: serde_json::Value,
+    time: u64,
+    /// Syslog priority.
+    pri: u8,
+    #[serde(default)]
+    node: String,
+    #[serde(default)]
+    tag: String,
+    user: Option<String>,
+    #[serde(default)]
+    msg: String,
+}
+
+/// One entry of `/cluster/ha/resources`. A resource added without a state
+/// is "started".
+#[derive(Debug, Deserialize)]
+struct ProxmoxHaResource {
+    sid: String,
+    state: Option<String>,
+}
+
+/// `/cluster/ha/status/manager_status`: the HA manager's state, empty
+/// until HA is set up.
+#[derive(Debug, Deserialize)]
+struct ProxmoxHaManager {
+    #[serde(default)]
+    manager_status: ProxmoxHaManagerStatus,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProxmoxHaManagerStatus {
+    /// Node name to "online", "maintenance", "unknown", "fence" or "gone".
+    #[serde(default)]
+    node_status: HashMap<String, String>,
+    /// Resource id ("vm:105") to its current state.
+    #[serde(default)]
+    service_status: HashMap<String, ProxmoxHaService>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxHaService {
+    state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxmoxReplication {
+    guest: u32,
+    target: String,
+    last_sync: Option<u64>,
+    fail_count: Option<u32>,
+    error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConsoleConfig;
+    use mockito::{Matcher, Server, ServerGuard};
+
+    const NODES_JSON: &str = r#"{"data":[
+        {"node":"pve1","status":"online","cpu":0.05,"maxcpu":8,"mem":4294967296,"maxmem":17179869184,"uptime":86400,"type":"node","id":"node/pve1"},
+        {"node":"pve2","status":"offline","type":"node","id":"node/pve2"}
+    ]}"#;
+
+    const NODE_STATUS_JSON: &str = r#"{"data":{
+        "cpu":0.1234,
+        "memory":{"total":17179869184,"used":8589934592,"free":8589934592},
+        "rootfs":{"total":100861726720,"used":25215431680,"avail":70460203008,"free":75646294940},
+        "uptime":90061,
+        "kversion":"Linux 6.8.12-4-pve",
+        "cpuinfo":{"cpus":8,"model":"Intel(R) Core(TM) i7"}
+    }}"#;
+
+    const NETSTAT_JSON: &str = r#"{"data":[
+        {"dev":"tap100i0","vmid":"100","in":"1000","out":"2000"},
+        {"dev":"tap200i0","vmid":"200","in":5000,"out":9000}
+    ]}"#;
+
+    const DISKS_JSON: &str = r#"{"data":[
+        {"devpath":"/dev/nvme0n1","model":"Samsung SSD 980","serial":"S64D","size":1000204886016,"health":"PASSED","wearout":97,"type":"nvme"},
+        {"devpath":"/dev/sda","model":"WDC WD40EFRX","serial":"WD-1","size":4000787030016,"health":"FAILED","wearout":"N/A","type":"hdd"}
+    ]}"#;
+
+    const APT_UPDATE_JSON: &str = r#"{"data":[
+        {"Package":"pve-manager","OldVersion":"8.2.2","Version":"8.2.4","Title":"Proxmox Virtual Environment Management Tools","Priority":"important","Section":"admin","Origin":"Proxmox"},
+        {"Package":"libc6","Version":"2.36-9+deb12u8","Priority":"required"}
+    ]}"#;
+
+    const QEMU_JSON: &str = r#"{"data":[
+        {"vmid":100,"name":"homeassistant","status":"running","lock":"backup","cpu":0.25,"mem":2147483648,"maxmem":4294967296,"uptime":3600,"cpus":2,"disk":0,"maxdisk":34359738368},
+        {"vmid":101,"status":"stopped","cpu":null,"mem":0,"maxmem":2147483648,"uptime":0,"template":1}
+    ]}"#;
+
+    const LXC_JSON: &str = r#"{"data":[
+        {"vmid":200,"name":"jellyfin","status":"running","cpu":0.5,"mem":1073741824,"maxmem":2147483648,"uptime":7200,"maxcpu":1,"type":"lxc","disk":2147483648,"maxdisk":8589934592},
+        {"vmid":201,"status":"stopped","type":"lxc"}
+    ]}"#;
+
+    const RESOURCES_JSON: &str = r#"{"data":[
+        {"id":"qemu/100","type":"qemu","vmid":100,"node":"pve1","pool":"home","status":"running"},
+        {"id":"lxc/200","type":"lxc","vmid":200,"node":"pve1","pool":"media","status":"running"},
+        {"id":"lxc/201","type":"lxc","vmid":201,"node":"pve1","status":"stopped"}
+    ]}"#;
+
+    fn provider_for(server: &ServerGuard) -> ProxmoxProvider {
+        ProxmoxProvider::new(&provider_config(server)).unwrap()
+    }
+
+    fn provider_config(server: &ServerGuard) -> ProxmoxConfig {
+        ProxmoxConfig {
+            name: "test".to_string(),
+            host: server.url(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: None,
+            read_only: false,
+            dry_run: false,
+        }
+    }
+
+    fn mock_json(server: &mut ServerGuard, path: &str, body: &str) -> mockito::Mock {
+        server
+            .mock("GET", path)
+            .match_header("authorization", "PVEAPIToken=root@pam!pulse=secret")
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create()
+    }
+
+    fn provider_error<'a>(e: &'a (dyn std::error::Error + 'static)) -> &'a ProviderError {
+        e.downcast_ref::<ProviderError>()
+            .expect("expected a ProviderError")
+    }
+
+    #[test]
+    fn test_fetch_nodes_maps_status_and_metrics() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        let status = mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let offline_status = server
+            .mock("GET", "/api2/json/nodes/pve2/status")
+            .expect(0)
+            .create();
+
+        let nodes = provider_for(&server).fetch_nodes().unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].name, "pve1");
+        assert_eq!(nodes[0].provider(), "test");
+        assert_eq!(nodes[0].status, NodeStatus::Online);
+        assert!((nodes[0].cpu_usage - 12.34).abs() < 1e-9);
+        assert_eq!(nodes[0].cpu_cores, 8);
+        assert_eq!(nodes[0].memory_used, 8589934592);
+        assert_eq!(nodes[0].memory_total, 17179869184);
+        assert_eq!(nodes[0].disk_used, 25215431680);
+        assert_eq!(nodes[0].disk_total, 100861726720);
+        assert_eq!(nodes[0].uptime, 90061);
+
+        // Offline nodes are reported with zeroed metrics and no status call
+        assert_eq!(nodes[1].name, "pve2");
+        assert_eq!(nodes[1].status, NodeStatus::Offline);
+        assert_eq!(nodes[1].cpu_usage, 0.0);
+        assert_eq!(nodes[1].memory_total, 0);
+        assert!(nodes[1].interfaces.is_empty());
+
+        status.assert();
+        offline_status.assert();
+    }
+
+    #[test]
+    fn test_node_status_skipped_while_list_figures_are_unchanged() {
+        let mut server = Server::new();
+        let list = mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        let status = mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        )
+        .expect(2);
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let provider = provider_for(&server);
+
+        provider.fetch_nodes().unwrap();
+        let nodes = provider.fetch_nodes().unwrap();
+        // Reused status, but the uptime comes from the list
+        assert_eq!(nodes[0].memory_used, 8589934592);
+        assert_eq!(nodes[0].uptime, 86400);
+
+        // New figures in the list: the status is fetched again
+        list.remove();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes",
+            &NODES_JSON.replace(r#""cpu":0.05"#, r#""cpu":0.07"#),
+        );
+        provider.fetch_nodes().unwrap();
+        status.assert();
+    }
+
+    #[test]
+    fn test_responses_are_compressed_and_counted() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(NODES_JSON.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut server = Server::new();
+        let list = server
+            .mock("GET", "/api2/json/nodes")
+            .match_header("accept-encoding", Matcher::Regex("gzip".to_string()))
+            .with_header("content-encoding", "gzip")
+            .with_body(&gzipped)
+            .create();
+        let status = mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let provider = provider_for(&server);
+
+        provider.begin_refresh();
+        let nodes = provider.fetch_nodes().unwrap();
+        assert_eq!(nodes[0].name, "pve1");
+        list.assert();
+        status.assert();
+
+        // Bytes are counted as they arrived, compressed
+        let received = provider.bytes_received().unwrap();
+        let uncompressed = NODES_JSON.len() + NODE_STATUS_JSON.len() + NETSTAT_JSON.len();
+        assert!(received >= (gzipped.len() + NODE_STATUS_JSON.len()) as u64);
+        assert!(received < uncompressed as u64, "{}", received);
+
+        provider.begin_refresh();
+        assert_eq!(provider.bytes_received(), Some(0));
+    }
+
+    #[test]
+    fn test_netstat_counters_become_rates() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        let first = mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let provider = provider_for(&server);
+
+        let nodes = provider.fetch_nodes().unwrap();
+        let interfaces = &nodes[0].interfaces;
+        assert_eq!(interfaces.len(), 2);
+        // String and numeric counters both parse
+        assert_eq!(
+            (interfaces[0].name.as_str(), interfaces[0].rx_bytes),
+            ("tap100i0", 1000)
+        );
+        assert_eq!(interfaces[1].tx_bytes, 9000);
+        assert!(interfaces.iter().all(|i| i.rx_rate == 0.0));
+
+        first.remove();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/netstat",
+            r#"{"data":[{"dev":"tap100i0","vmid":"100","in":"2000000","out":"2000"},{"dev":"tap200i0","vmid":"200","in":0,"out":0}]}"#,
+        );
+        let nodes = provider.fetch_nodes().unwrap();
+        let interfaces = &nodes[0].interfaces;
+        assert!(interfaces[0].rx_rate > 0.0);
+        // Counter reset clamps to zero
+        assert_eq!((interfaces[1].rx_rate, interfaces[1].tx_rate), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_disks_listed_and_cached() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let disks = server
+            .mock("GET", "/api2/json/nodes/pve1/disks/list")
+            .with_header("content-type", "application/json")
+            .with_body(DISKS_JSON)
+            .expect(1)
+            .create();
+        let provider = provider_for(&server);
+
+        let nodes = provider.fetch_nodes().unwrap();
+        let NodeDisks::Listed(listed) = &nodes[0].disks else {
+            panic!("expected listed disks, got {:?}", nodes[0].disks);
+        };
+        assert_eq!(listed.len(), 2);
+        assert_eq!(
+            (listed[0].dev_path.as_str(), listed[0].wearout),
+            ("/dev/nvme0n1", Some(97))
+        );
+        // HDDs report wearout as "N/A"
+        assert_eq!(listed[1].wearout, None);
+        assert_eq!(nodes[0].disks.problems().len(), 1);
+        // Offline nodes aren't asked
+        assert_eq!(nodes[1].disks, NodeDisks::NotReported);
+
+        // The second refresh is served from the cache
+        provider.fetch_nodes().unwrap();
+        disks.assert();
+    }
+
+    #[test]
+    fn test_pending_updates_listed_and_cached() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let updates = server
+            .mock("GET", "/api2/json/nodes/pve1/apt/update")
+            .with_header("content-type", "application/json")
+            .with_body(APT_UPDATE_JSON)
+            .expect(1)
+            .create();
+        let provider = provider_for(&server);
+
+        let nodes = provider.fetch_nodes().unwrap();
+        assert_eq!(nodes[0].pending_updates, Some(2));
+        assert_eq!(
+            nodes[0].package_updates[0],
+            PackageUpdate {
+                package: "pve-manager".to_string(),
+                old_version: "8.2.2".to_string(),
+                new_version: "8.2.4".to_string(),
+            }
+        );
+        assert_eq!(nodes[1].pending_updates, None);
+
+        provider.fetch_nodes().unwrap();
+        updates.assert();
+    }
+
+    #[test]
+    fn test_forbidden_updates_show_nothing() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        server
+            .mock("GET", "/api2/json/nodes/pve1/apt/update")
+            .with_status(403)
+            .create();
+
+        let nodes = provider_for(&server).fetch_nodes().unwrap();
+        assert_eq!(nodes[0].pending_updates, None);
+        assert!(nodes[0].package_updates.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_guest_lists_only_its_node() {
+        let mut server = Server::new();
+        let qemu = mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        let lxc = mock_json(&mut server, "/api2/json/nodes/pve1/lxc", LXC_JSON);
+        let provider = provider_for(&server);
+
+        let vm = provider.fetch_guest("pve1", 100).unwrap().unwrap();
+        assert_eq!(vm.name, "homeassistant");
+        qemu.assert();
+        // Found among the VMs, so the LXC list wasn't needed
+        assert!(!lxc.matched());
+
+        assert!(provider.fetch_guest("pve1", 999).unwrap().is_none());
+        lxc.assert();
+    }
+
+    #[test]
+    fn test_fetch_node_bypasses_disk_cache() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        let disks = server
+            .mock("GET", "/api2/json/nodes/pve1/disks/list")
+            .with_header("content-type", "application/json")
+            .with_body(DISKS_JSON)
+            .expect(2)
+            .create();
+        let provider = provider_for(&server);
+
+        provider.fetch_nodes().unwrap();
+        let node = provider.fetch_node("pve1").unwrap().unwrap();
+        assert_eq!(node.name, "pve1");
+        disks.assert();
+
+        assert!(provider.fetch_node("pve7").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_forbidden_disks_are_unavailable() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        server
+            .mock("GET", "/api2/json/nodes/pve1/disks/list")
+            .with_status(403)
+            .create();
+
+        let nodes = provider_for(&server).fetch_nodes().unwrap();
+        assert_eq!(
+            nodes[0].disks,
+            NodeDisks::Unavailable(
+                "token lacks Sys.Audit on /nodes — disk health will not be shown".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_fetch_containers_maps_vms_and_lxc() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/lxc", LXC_JSON);
+        let offline_guests = server
+            .mock("GET", Matcher::Regex("^/api2/json/nodes/pve2/".to_string()))
+            .expect(0)
+            .create();
+
+        let containers = provider_for(&server).fetch_containers().unwrap();
+
+        assert_eq!(containers.len(), 4);
+
+        let vm = &containers[0];
+        assert_eq!(vm.vmid, 100);
+        assert_eq!(vm.name, "homeassistant");
+        assert_eq!(vm.node, "pve1");
+        assert_eq!(vm.id, GuestId::new("test", 100));
+        assert_eq!(vm.container_type, ContainerType::VM);
+        assert_eq!(vm.status, ContainerStatus::Running);
+        assert_eq!(vm.cpu_usage, 25.0);
+        assert_eq!(vm.max_cpu, 2);
+        assert_eq!(vm.memory_used, 2147483648);
+        assert_eq!(vm.memory_max, 4294967296);
+        assert_eq!(vm.uptime, 3600);
+        assert_eq!(vm.lock.as_deref(), Some("backup"));
+        assert!(!vm.is_template);
+        // Without the guest agent a VM reports 0 bytes used
+        assert_eq!(vm.disk_used, None);
+        assert_eq!(vm.disk_max, 34359738368);
+        assert!(containers[1].is_template);
+
+        let lxc = &containers[2];
+        assert_eq!(lxc.vmid, 200);
+        assert_eq!(lxc.name, "jellyfin");
+        assert_eq!(lxc.container_type, ContainerType::LXC);
+        assert_eq!(lxc.max_cpu, 1);
+        assert_eq!(lxc.cpu_usage, 50.0);
+        assert!(lxc.lock.is_none());
+        assert_eq!(lxc.disk_used, Some(2147483648));
+        assert_eq!(lxc.disk_percent(), Some(25.0));
+
+        offline_guests.assert();
+    }
+
+    #[test]
+    fn test_guests_get_their_pools() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/lxc", LXC_JSON);
+        let resources = server
+            .mock("GET", "/api2/json/cluster/resources")
+            .match_query(Matcher::UrlEncoded("type".into(), "vm".into()))
+            .with_body(RESOURCES_JSON)
+            .expect(1)
+            .create();
+        let provider = provider_for(&server);
+
+        let containers = provider.fetch_containers().unwrap();
+        let pools: Vec<Option<&str>> = containers.iter().map(|c| c.pool.as_deref()).collect();
+        assert_eq!(pools, [Some("home"), None, Some("media"), None]);
+
+        // Served from the cache the second time
+        let jellyfin = provider.fetch_guest("pve1", 200).unwrap().unwrap();
+        assert_eq!(jellyfin.pool.as_deref(), Some("media"));
+        resources.assert();
+    }
+
+    #[test]
+    fn test_forbidden_pools_leave_guests_unpooled() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/lxc", LXC_JSON);
+        server
+            .mock("GET", "/api2/json/cluster/resources")
+            .match_query(Matcher::Any)
+            .with_status(403)
+            .create();
+
+        let containers = provider_for(&server).fetch_containers().unwrap();
+        assert_eq!(containers.len(), 4);
+        assert!(containers.iter().all(|c| c.pool.is_none()));
+    }
+
+    #[test]
+    fn test_missing_name_and_null_cpu_fall_back() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/lxc",
+            r#"{"data":[{"vmid":201,"status":"stopped","type":"lxc"}]}"#,
+        );
+
+        let containers = provider_for(&server).fetch_containers().unwrap();
+
+        let vm = containers.iter().find(|c| c.vmid == 101).unwrap();
+        assert_eq!(vm.name, "VM 101");
+        assert_eq!(vm.status, ContainerStatus::Stopped);
+        assert_eq!(vm.cpu_usage, 0.0);
+
+        let lxc = containers.iter().find(|c| c.vmid == 201).unwrap();
+        assert_eq!(lxc.name, "CT 201");
+        assert_eq!(lxc.memory_used, 0);
+        assert_eq!(lxc.memory_max, 0);
+        assert_eq!(lxc.uptime, 0);
+    }
+
+    #[test]
+    fn test_fetch_replication_maps_jobs() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/replication",
+            r#"{"data":[
+                {"id":"100-0","guest":100,"jobnum":0,"target":"pve2","type":"local","schedule":"*/15","last_sync":1700000000,"last_try":1700003600,"fail_count":3,"error":"command 'zfs snapshot' failed: exit code 1","duration":1.2},
+                {"id":"101-0","guest":101,"jobnum":0,"target":"pve2","type":"local","last_sync":1700003600,"fail_count":0}
+            ]}"#,
+        );
+
+        let jobs = provider_for(&server).fetch_replication().unwrap();
+
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].guest, GuestId::new("test", 100));
+        assert_eq!(jobs[0].source, "pve1");
+        assert_eq!(jobs[0].target, "pve2");
+        assert_eq!(jobs[0].last_sync, 1700000000);
+        assert_eq!(jobs[0].fail_count, 3);
+        assert!(jobs[0].is_failing());
+        assert!(!jobs[1].is_failing());
+    }
+
+    #[test]
+    fn test_fetch_cluster_log() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("GET", "/api2/json/cluster/log")
+            .match_query(Matcher::UrlEncoded("max".into(), "50".into()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"data":[
+                {"uid":1203,"time":1700000300,"pri":3,"tag":"pvedaemon","pid":811,"node":"pve1","user":"root@pam","msg":"authentication failure; rhost=10.0.0.9 user=root@pam msg=no such user"},
+                {"uid":"1202","time":1700000200,"pri":6,"tag":"pve-ha-crm","pid":900,"node":"pve2","user":"","msg":"service 'vm:105' started"}
+            ]}"#,
+            )
+            .create();
+
+        let entries = provider_for(&server).fetch_cluster_log(50).unwrap();
+        mock.assert();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].uid, "1203");
+        assert_eq!(entries[0].level, EventLevel::Error);
+        assert_eq!(entries[0].user.as_deref(), Some("root@pam"));
+        assert!(entries[0].message.starts_with("authentication failure"));
+        assert_eq!(entries[1].uid, "1202");
+        assert_eq!(
+            (entries[1].node.as_str(), entries[1].tag.as_str()),
+            ("pve2", "pve-ha-crm")
+        );
+        assert_eq!(entries[1].level, EventLevel::Info);
+        assert!(entries[1].user.is_none());
+    }
+
+    #[test]
+    fn test_fetch_cluster_status_quorate() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[
+                {"type":"cluster","id":"cluster","name":"homelab","nodes":3,"quorate":1,"version":5},
+                {"type":"node","id":"node/pve1","name":"pve1","nodeid":1,"online":1,"local":1,"ip":"10.0.0.1"},
+                {"type":"node","id":"node/pve2","name":"pve2","nodeid":2,"online":1,"local":0,"ip":"10.0.0.2"},
+                {"type":"node","id":"node/pve3","name":"pve3","nodeid":3,"online":1,"local":0,"ip":"10.0.0.3"}
+            ]}"#,
+        );
+
+        let cluster = provider_for(&server)
+            .fetch_cluster_status()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(cluster.name, "homelab");
+        assert!(cluster.quorate);
+        assert_eq!(cluster.summary(), "cluster homelab: quorate (3/3)");
+    }
+
+    #[test]
+    fn test_fetch_cluster_status_lost_quorum() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[
+                {"type":"cluster","id":"cluster","name":"homelab","nodes":3,"quorate":0,"version":5},
+                {"type":"node","id":"node/pve1","name":"pve1","nodeid":1,"online":1,"local":1},
+                {"type":"node","id":"node/pve2","name":"pve2","nodeid":2,"online":0,"local":0},
+                {"type":"node","id":"node/pve3","name":"pve3","nodeid":3,"online":0,"local":0}
+            ]}"#,
+        );
+
+        let cluster = provider_for(&server)
+            .fetch_cluster_status()
+            .unwrap()
+            .unwrap();
+
+        assert!(!cluster.quorate);
+        assert_eq!(cluster.nodes_online, 1);
+        assert_eq!(cluster.nodes_total, 3);
+    }
+
+    #[test]
+    fn test_fetch_cluster_status_standalone_node() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[
+                {"type":"node","id":"node/pve","name":"pve","nodeid":0,"online":1,"local":1,"ip":"10.0.0.1","level":""}
+            ]}"#,
+        );
+
+        let cluster = provider_for(&server).fetch_cluster_status().unwrap();
+
+        assert_eq!(cluster, None);
+    }
+
+    fn running_vm(vmid: u32) -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            max_cpu: 2,
+            ..Container::test("test", vmid, "windows", "pve1")
+        }
+    }
+
+    #[test]
+    fn test_guest_details_reads_balloon_info() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/status/current",
+            r#"{"data":{
+                "status":"running","vmid":100,"agent":1,
+                "mem":8321499136,"maxmem":8589934592,"balloon":8589934592,
+                "ballooninfo":{"actual":8589934592,"max_mem":8589934592,"total_mem":8387338240,"free_mem":5368709120,"last_update":1700000000}
+            }}"#,
+        );
+
+        let details = provider_for(&server)
+            .fetch_guest_details(&running_vm(100))
+            .unwrap();
+
+        let memory = details.memory.unwrap();
+        assert_eq!(memory.allocated, 8589934592);
+        assert_eq!(memory.guest_total, 8387338240);
+        assert_eq!(memory.guest_used, 8387338240 - 5368709120);
+    }
+
+    #[test]
+    fn test_guest_details_without_balloon_stats() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/status/current",
+            r#"{"data":{"status":"running","vmid":100,"mem":1073741824,"maxmem":2147483648,"ballooninfo":{"actual":2147483648}}}"#,
+        );
+
+        let details = provider_for(&server)
+            .fetch_guest_details(&running_vm(100))
+            .unwrap();
+
+        assert!(details.memory.is_none());
+    }
+
+    #[test]
+    fn test_guest_details_skips_lxc() {
+        let mut server = Server::new();
+        // The config is read for the OS type, but nothing only VMs have
+        let vm_only = server
+            .mock("GET", Matcher::Regex("status|agent".to_string()))
+            .expect(0)
+            .create();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/lxc/200/firewall/options",
+            r#"{"data":{}}"#,
+        );
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+
+        let details = provider_for(&server).fetch_guest_details(&lxc).unwrap();
+
+        assert!(details.memory.is_none());
+        assert_eq!(details.firewall, Some(FirewallStatus::Off));
+        vm_only.assert();
+    }
+
+    #[test]
+    fn test_guest_firewall_counts_rules_and_is_cached() {
+        let mut server = Server::new();
+        let options = server
+            .mock("GET", "/api2/json/nodes/pve1/lxc/100/firewall/options")
+            .with_body(r#"{"data":{"enable":1,"policy_in":"DROP"}}"#)
+            .expect(2)
+            .create();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/lxc/100/firewall/rules",
+            r#"{"data":[{"pos":0,"type":"in","action":"ACCEPT"},{"pos":1,"type":"in","action":"ACCEPT"},{"pos":2,"type":"out","action":"DROP"}]}"#,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        let provider = provider_for(&server);
+        let mut lxc = running_vm(100);
+        lxc.container_type = ContainerType::LXC;
+
+        let details = provider.fetch_guest_details(&lxc).unwrap();
+        assert_eq!(
+            details.firewall,
+            Some(FirewallStatus::On { rules: Some(3) })
+        );
+        // The next refresh reuses it; `R` reads it again
+        provider.fetch_guest_details(&lxc).unwrap();
+        provider.fetch_guest("pve1", 100).unwrap();
+        provider.fetch_guest_details(&lxc).unwrap();
+        options.assert();
+    }
+
+    #[test]
+    fn test_firewall_unknown_without_permission() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/api2/json/nodes/pve1/lxc/100/firewall/options")
+            .with_status(403)
+            .create();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[{"type":"cluster","name":"homelab","quorate":1,"nodes":1},{"type":"node","name":"pve1","online":1}]}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/firewall/options",
+            r#"{"data":{"enable":0}}"#,
+        );
+        let provider = provider_for(&server);
+        let mut lxc = running_vm(100);
+        lxc.container_type = ContainerType::LXC;
+
+        let details = provider.fetch_guest_details(&lxc).unwrap();
+        assert_eq!(details.firewall, Some(FirewallStatus::Unknown));
+        let cluster = provider.fetch_cluster_status().unwrap().unwrap();
+        assert_eq!(cluster.firewall, Some(FirewallStatus::Off));
+    }
+
+    #[test]
+    fn test_guest_agent_is_pinged_and_cached() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/status/current",
+            r#"{"data":{"status":"running"}}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/config",
+            r#"{"data":{"name":"homeassistant","agent":"enabled=1,fstrim_cloned_disks=1"}}"#,
+        );
+        let ping = server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/agent/ping")
+            .with_body(r#"{"data":null}"#)
+            .expect(2)
+            .create();
+        let provider = provider_for(&server);
+
+        let details = provider.fetch_guest_details(&running_vm(100)).unwrap();
+        assert_eq!(details.agent, Some(AgentStatus::Responding));
+        // The next refresh reuses the answer; `R` asks again
+        provider.fetch_guest_details(&running_vm(100)).unwrap();
+        provider.fetch_guest("pve1", 100).unwrap();
+        provider.fetch_guest_details(&running_vm(100)).unwrap();
+        ping.assert();
+    }
+
+    #[test]
+    fn test_guest_agent_not_responding() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/status/current",
+            r#"{"data":{"status":"running"}}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/config",
+            r#"{"data":{"agent":"1"}}"#,
+        );
+        server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/agent/ping")
+            .with_status(500)
+            .with_body(r#"{"data":null,"message":"QEMU guest agent is not running\n"}"#)
+            .create();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/101/config",
+            r#"{"data":{"agent":1}}"#,
+        );
+        let stopped_ping = server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/101/agent/ping")
+            .expect(0)
+            .create();
+        let provider = provider_for(&server);
+
+        let details = provider.fetch_guest_details(&running_vm(100)).unwrap();
+        assert_eq!(details.agent, Some(AgentStatus::NotResponding));
+
+        let mut stopped = running_vm(101);
+        stopped.status = ContainerStatus::Stopped;
+        let details = provider.fetch_guest_details(&stopped).unwrap();
+        assert_eq!(details.agent, Some(AgentStatus::NotResponding));
+        stopped_ping.assert();
+    }
+
+    #[test]
+    fn test_os_info_from_the_agent_or_the_config() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes/pve1/lxc", LXC_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/status/current",
+            r#"{"data":{"status":"running"}}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/config",
+            r#"{"data":{"agent":"1","ostype":"l26"}}"#,
+        );
+        server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/agent/ping")
+            .with_body(r#"{"data":null}"#)
+            .create();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/agent/get-osinfo",
+            r#"{"data":{"result":{"id":"debian","name":"Debian GNU/Linux","pretty-name":"Debian GNU/Linux 12 (bookworm)","kernel-release":"6.1.0-18-amd64","machine":"x86_64"}}}"#,
+        );
+        // No agent: the config's ostype, as a label
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/101/config",
+            r#"{"data":{"ostype":"win10"}}"#,
+        );
+        let lxc_config = server
+            .mock("GET", "/api2/json/nodes/pve1/lxc/200/config")
+            .with_body(r#"{"data":{"hostname":"jellyfin","ostype":"debian"}}"#)
+            .expect(1)
+            .create();
+        let provider = provider_for(&server);
+
+        let details = provider.fetch_guest_details(&running_vm(100)).unwrap();
+        assert_eq!(
+            details.os_info.as_deref(),
+            Some("Debian GNU/Linux 12 (bookworm), kernel 6.1.0-18-amd64")
+        );
+
+        let mut stopped = running_vm(101);
+        stopped.status = ContainerStatus::Stopped;
+        let details = provider.fetch_guest_details(&stopped).unwrap();
+        assert_eq!(details.os_info.as_deref(), Some("Windows 10/2016/2019"));
+
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+        let details = provider.fetch_guest_details(&lxc).unwrap();
+        assert_eq!(details.os_info.as_deref(), Some("debian"));
+        // Cached, and carried by the listed guest from now on
+        provider.fetch_guest_details(&lxc).unwrap();
+        lxc_config.assert();
+        let listed = provider.fetch_node_lxc("pve1").unwrap();
+        assert_eq!(listed[0].os_info.as_deref(), Some("debian"));
+        assert_eq!(listed[1].os_info, None);
+    }
+
+    #[test]
+    fn test_os_type_labels() {
+        assert_eq!(os_type_label("l26"), Some("Linux"));
+        assert_eq!(os_type_label("win11"), Some("Windows 11/2022/2025"));
+        assert_eq!(os_type_label("w2k8"), Some("Windows Vista/2008"));
+        assert_eq!(os_type_label("solaris"), Some("Solaris"));
+        // Containers' types aren't VM types
+        assert_eq!(os_type_label("debian"), None);
+        assert_eq!(os_type_label(""), None);
+
+        let info = |pretty: Option<&str>, kernel: Option<&str>| ProxmoxOsInfo {
+            pretty_name: pretty.map(str::to_string),
+            name: Some("Microsoft Windows".to_string()),
+            version: Some("10".to_string()),
+            kernel_release: kernel.map(str::to_string),
+        };
+        assert_eq!(
+            info(None, Some("19045")).describe().as_deref(),
+            Some("Microsoft Windows 10, kernel 19045")
+        );
+        assert_eq!(
+            info(Some("Alpine Linux v3.19"), None).describe().as_deref(),
+            Some("Alpine Linux v3.19")
+        );
+        let nameless = ProxmoxOsInfo {
+            pretty_name: None,
+            name: None,
+            version: Some("10".to_string()),
+            kernel_release: None,
+        };
+        assert_eq!(nameless.describe(), None);
+    }
+
+    #[test]
+    fn test_agent_enabled_values() {
+        let enabled = |value: serde_json::Value| agent_enabled(&value);
+        assert!(enabled(serde_json::json!(1)));
+        assert!(enabled(serde_json::json!("1")));
+        assert!(enabled(serde_json::json!("enabled=1,type=virtio")));
+        assert!(enabled(serde_json::json!(
+            "fstrim_cloned_disks=1,enabled=yes"
+        )));
+        assert!(!enabled(serde_json::json!(0)));
+        assert!(!enabled(serde_json::json!("0,fstrim_cloned_disks=1")));
+        assert!(!enabled(serde_json::json!("fstrim_cloned_disks=1")));
+    }
+
+    #[test]
+    fn test_details_deferred_when_budget_exhausted() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        let status_current = server
+            .mock("GET", "/api2/json/nodes/pve1/qemu/100/status/current")
+            .expect(0)
+            .create();
+        let config = ProxmoxConfig {
+            name: "test".to_string(),
+            host: server.url(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: Some(2),
+            read_only: false,
+            dry_run: false,
+        };
+        let provider = ProxmoxProvider::new(&config).unwrap();
+
+        provider.begin_refresh();
+        provider.fetch_nodes().unwrap();
+        assert_eq!(provider.request_count(), Some(2));
+
+        let details = provider.fetch_guest_details(&running_vm(100)).unwrap();
+        assert!(details.deferred);
+        assert!(details.memory.is_none());
+        status_current.assert();
+
+        provider.begin_refresh();
+        assert_eq!(provider.request_count(), Some(0));
+    }
+
+    #[test]
+    fn test_unauthorized_is_reported() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/api2/json/nodes")
+            .with_status(401)
+            .with_body("authentication failure")
+            .create();
+
+        let err = provider_for(&server).fetch_nodes().unwrap_err();
+
+        match provider_error(err.as_ref()) {
+            ProviderError::Status { status, url } => {
+                assert_eq!(*status, 401);
+                assert!(url.ends_with("/api2/json/nodes"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(err.to_string().contains("authentication failed"));
+    }
+
+    #[test]
+    fn test_forbidden_guest_list_is_not_an_empty_list() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        server
+            .mock("GET", "/api2/json/nodes/pve1/qemu")
+            .with_status(403)
+            .create();
+
+        let err = provider_for(&server).fetch_containers().unwrap_err();
+
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Permission(_)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "token lacks VM.Audit on /vms — containers will not be shown"
+        );
+    }
+
+    fn missing_privileges(json: &str) -> Vec<String> {
+        let permissions: Permissions = serde_json::from_str(json).unwrap();
+        missing_action_privileges(&permissions)
+            .into_iter()
+            .map(|issue| issue.privilege)
+            .collect()
+    }
+
+    #[test]
+    fn test_action_privileges_from_permission_tree() {
+        // A privilege-separated token with audit rights only
+        let audit = r#"{
+            "/": {"Sys.Audit": 1},
+            "/vms": {"VM.Audit": 1},
+            "/storage": {"Datastore.Audit": 1}
+        }"#;
+        assert_eq!(
+            missing_privileges(audit),
+            [
+                "VM.Migrate",
+                "VM.PowerMgmt",
+                "VM.Clone",
+                "VM.Console",
+                "VM.Config.CPU",
+                "VM.Config.Memory",
+                "Sys.PowerMgmt"
+            ]
+        );
+
+        // PVEVMAdmin on /vms, as on most admin tokens, covers the guests
+        // but not their nodes
+        let admin = r#"{
+            "/vms": {"VM.Audit": 1, "VM.Migrate": 1, "VM.PowerMgmt": 1, "VM.Clone": 1, "VM.Console": 1,
+                "VM.Config.CPU": 1, "VM.Config.Memory": 1, "VM.Config.Disk": 1}
+        }"#;
+        assert_eq!(missing_privileges(admin), ["Sys.PowerMgmt"]);
+        let with_nodes = r#"{
+            "/vms": {"VM.Migrate": 1, "VM.PowerMgmt": 1, "VM.Clone": 1, "VM.Console": 1,
+                "VM.Config.CPU": 1, "VM.Config.Memory": 1},
+            "/nodes/pve1": {"Sys.PowerMgmt": 1}
+        }"#;
+        assert!(missing_privileges(with_nodes).is_empty());
+
+        // Granted per pool or guest, without propagation: still held there
+        let scattered = r#"{
+            "/pool/lab": {"VM.Migrate": 1},
+            "/vms/100": {"VM.PowerMgmt": 0, "VM.Console": 1, "VM.Config.CPU": 1, "VM.Config.Memory": 0},
+            "/storage/local": {"VM.Clone": 1}
+        }"#;
+        assert_eq!(missing_privileges(scattered), ["VM.Clone", "Sys.PowerMgmt"]);
+    }
+
+    #[test]
+    fn test_probe_actions_reports_missing_privileges() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/access/permissions",
+            r#"{"data":{"/":{"Sys.Audit":1},"/vms":{"VM.Audit":1,"VM.Migrate":1}}}"#,
+        );
+
+        let issues = provider_for(&server).probe_actions().unwrap();
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "token lacks VM.PowerMgmt on /vms — guests cannot be started or shut down",
+                "token lacks VM.Clone on /vms — guests cannot be cloned",
+                "token lacks VM.Console on /vms — web consoles cannot be opened",
+                "token lacks VM.Config.CPU on /vms — guest cores cannot be changed",
+                "token lacks VM.Config.Memory on /vms — guest memory cannot be changed",
+                "token lacks Sys.PowerMgmt on /nodes — nodes cannot be rebooted or shut down",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_required_permission_by_endpoint() {
+        let privilege = |path: &str| required_permission(&Method::GET, path).map(|i| i.to_string());
+
+        assert_eq!(
+            privilege("/nodes/pve1/qemu").as_deref(),
+            Some("token lacks VM.Audit on /vms — containers will not be shown")
+        );
+        assert_eq!(privilege("/nodes/pve1/lxc"), privilege("/nodes/pve1/qemu"));
+        assert_eq!(
+            privilege("/nodes/pve1/status").as_deref(),
+            Some("token lacks Sys.Audit on /nodes — node metrics will not be shown")
+        );
+        // Posting to the same path reboots or shuts the node down
+        assert_eq!(
+            required_permission(&Method::POST, "/nodes/pve1/status")
+                .map(|i| i.to_string())
+                .as_deref(),
+            Some("token lacks Sys.PowerMgmt on /nodes — nodes cannot be rebooted or shut down")
+        );
+        assert_eq!(
+            privilege("/nodes/pve1/qemu/100/status/current").as_deref(),
+            Some("token lacks VM.Audit on /vms — in-guest memory will not be shown")
+        );
+        assert_eq!(
+            privilege("/nodes/pve1/lxc/200/migrate").as_deref(),
+            Some("token lacks VM.Migrate on /vms — guests cannot be migrated")
+        );
+        assert_eq!(
+            privilege("/nodes/pve1/qemu/100/status/shutdown").as_deref(),
+            Some("token lacks VM.PowerMgmt on /vms — guests cannot be started or shut down")
+        );
+        assert_eq!(
+            privilege("/nodes/pve1/qemu/100/firewall/options").as_deref(),
+            Some("token lacks VM.Audit on /vms — guest firewalls will show as unknown")
+        );
+        assert_eq!(
+            privilege("/cluster/firewall/options").as_deref(),
+            Some("token lacks Sys.Audit on / — the cluster firewall will show as unknown")
+        );
+        assert_eq!(
+            privilege("/nodes/pve1/netstat").as_deref(),
+            Some("token lacks Sys.Audit on /nodes — interface throughput will not be shown")
+        );
+        assert_eq!(
+            privilege("/nodes/pve1/disks/list").as_deref(),
+            Some("token lacks Sys.Audit on /nodes — disk health will not be shown")
+        );
+        // Reading a guest's config is not an action; changing it is
+        assert!(privilege("/nodes/pve1/lxc/200/config").is_none());
+        assert_eq!(
+            required_permission(&Method::PUT, "/nodes/pve1/lxc/200/config")
+                .map(|i| i.to_string())
+                .as_deref(),
+            Some(
+                "token lacks VM.Config.CPU or VM.Config.Memory on /vms — guest cores and memory cannot be changed"
+            )
+        );
+        assert!(privilege("/version").is_none());
+    }
+
+    const UPID: &str = "UPID:pve1:0000A1B2:00C3D4E5:6530A1B2:qmigrate:100:root@pam:";
+
+    #[test]
+    fn test_migrate_running_vm_is_live() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/migrate")
+            .match_header("authorization", "PVEAPIToken=root@pam!pulse=secret")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("target".into(), "pve2".into()),
+                Matcher::UrlEncoded("online".into(), "1".into()),
+            ]))
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .create();
+
+        let task = provider_for(&server)
+            .migrate_guest(&running_vm(100), "pve2")
+            .unwrap();
+
+        assert_eq!(task, UPID);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_migrate_running_lxc_restarts_it() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve1/lxc/200/migrate")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("target".into(), "pve3".into()),
+                Matcher::UrlEncoded("restart".into(), "1".into()),
+            ]))
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .create();
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+
+        provider_for(&server).migrate_guest(&lxc, "pve3").unwrap();
+        mock.assert();
+    }
+
+    #[test]
+    fn test_start_guest() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve1/lxc/101/status/start")
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .create();
+        let mut lxc = running_vm(101);
+        lxc.container_type = ContainerType::LXC;
+        lxc.status = ContainerStatus::Stopped;
+
+        let task = provider_for(&server).start_guest(&lxc).unwrap();
+
+        assert_eq!(task, UPID);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_shutdown_guest() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/status/shutdown")
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .create();
+
+        let task = provider_for(&server)
+            .shutdown_guest(&running_vm(100))
+            .unwrap();
+
+        assert_eq!(task, UPID);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_node_power_posts_the_command() {
+        let mut server = Server::new();
+        // Proxmox answers without a task
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve2/status")
+            .match_body("command=reboot")
+            .with_body(r#"{"data":null}"#)
+            .create();
+        let provider = provider_for(&server);
+
+        assert_eq!(provider.node_power("pve2", NodePower::Reboot).unwrap(), "");
+        mock.assert();
+
+        server
+            .mock("POST", "/api2/json/nodes/pve3/status")
+            .with_status(403)
+            .create();
+        assert_eq!(
+            provider
+                .node_power("pve3", NodePower::Shutdown)
+                .unwrap_err()
+                .to_string(),
+            "token lacks Sys.PowerMgmt on /nodes — nodes cannot be rebooted or shut down"
+        );
+    }
+
+    #[test]
+    fn test_ha_resources_by_guest() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/ha/resources",
+            r#"{"data":[
+                {"sid":"vm:100","state":"started","type":"vm"},
+                {"sid":"ct:200","type":"ct"},
+                {"sid":"vm:300","state":"ignored","type":"vm"}
+            ]}"#,
+        );
+
+        let resources = provider_for(&server).fetch_ha_resources().unwrap();
+        assert_eq!(
+            resources
+                .iter()
+                .map(|r| (r.guest.id.as_str(), r.state.as_str(), r.is_managed()))
+                .collect::<Vec<_>>(),
+            [
+                ("100", "started", true),
+                ("200", "started", true),
+                ("300", "ignored", false)
+            ]
+        );
+        assert!(resources.iter().all(|r| r.guest.provider == "test"));
+    }
+
+    #[test]
+    fn test_cluster_status_carries_ha_problems() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[{"type":"cluster","name":"homelab","quorate":1,"nodes":3},{"type":"node","name":"pve1","online":1}]}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/ha/status/manager_status",
+            r#"{"data":{"manager_status":{
+                "master_node":"pve1",
+                "node_status":{"pve1":"online","pve2":"fence","pve3":"unknown","pve4":"maintenance"},
+                "service_status":{
+                    "vm:100":{"state":"started","node":"pve1"},
+                    "vm:101":{"state":"error","node":"pve2"},
+                    "ct:20":{"state":"error","node":"pve1"},
+                    "fa:1":{"state":"error","node":"pve1"}
+                }
+            },"quorum":{"node":"pve1","quorate":"1"}}}"#,
+        );
+
+        let cluster = provider_for(&server)
+            .fetch_cluster_status()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            cluster.ha,
+            HaStatus {
+                errors: vec![GuestId::new("test", 20), GuestId::new("test", 101)],
+                fenced: vec!["pve2".to_string()],
+                unknown: vec!["pve3".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_cluster_status_without_ha() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[{"type":"cluster","name":"homelab","quorate":1,"nodes":1},{"type":"node","name":"pve1","online":1}]}"#,
+        );
+        // No HA set up: an empty manager status
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/ha/status/manager_status",
+            r#"{"data":{"manager_status":{},"quorum":{"node":"pve1","quorate":"1"}}}"#,
+        );
+        let provider = provider_for(&server);
+        assert_eq!(
+            provider.fetch_cluster_status().unwrap().unwrap().ha,
+            HaStatus::default()
+        );
+
+        // Nor without the privilege to read it
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/status",
+            r#"{"data":[{"type":"cluster","name":"homelab","quorate":1,"nodes":1},{"type":"node","name":"pve1","online":1}]}"#,
+        );
+        server
+            .mock("GET", "/api2/json/cluster/ha/status/manager_status")
+            .with_status(403)
+            .create();
+        let cluster = provider_for(&server).fetch_cluster_status().unwrap();
+        assert_eq!(cluster.unwrap().ha, HaStatus::default());
+    }
+
+    #[test]
+    fn test_web_console_url_carries_a_fresh_ticket() {
+        let mut server = Server::new();
+        let vnc = server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/vncproxy")
+            .match_body("websocket=1")
+            .with_body(r#"{"data":{"ticket":"PVEVNC:6500+abc/d=","port":"5900","upid":"x"}}"#)
+            .create();
+        let provider = provider_for(&server);
+
+        let url = provider.web_console_url(&running_vm(100)).unwrap();
+        assert_eq!(
+            url,
+            format!(
+                "{}/?console=kvm&novnc=1&node=pve1&vmid=100&vmname=windows&resize=scale\
+                 &port=5900&vncticket=PVEVNC%3A6500%2Babc%2Fd%3D",
+                server.url()
+            )
+        );
+        vnc.assert();
+
+        // Containers get a terminal instead
+        let term = server
+            .mock("POST", "/api2/json/nodes/pve1/lxc/200/termproxy")
+            .with_body(r#"{"data":{"ticket":"t","port":5901}}"#)
+            .create();
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+        lxc.name = "web".to_string();
+        let url = provider.web_console_url(&lxc).unwrap();
+        assert!(url.contains("/?console=lxc&xtermjs=1&node=pve1&vmid=200&vmname=web"));
+        term.assert();
+
+        // Stopped guests have no console to connect to
+        let mut stopped = running_vm(100);
+        stopped.status = ContainerStatus::Stopped;
+        assert_eq!(
+            provider.web_console_url(&stopped).unwrap_err().to_string(),
+            "windows is not running"
+        );
+    }
+
+    #[test]
+    fn test_forbidden_web_console_names_privilege() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/vncproxy")
+            .with_status(403)
+            .create();
+
+        let err = provider_for(&server)
+            .web_console_url(&running_vm(100))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "token lacks VM.Console on /vms — web consoles cannot be opened"
+        );
+    }
+
+    #[test]
+    fn test_read_only_refuses_actions_without_a_request() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", Matcher::Any)
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .expect(0)
+            .create();
+        let config = ProxmoxConfig {
+            name: "test".to_string(),
+            host: server.url(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: None,
+            read_only: true,
+            dry_run: false,
+        };
+        let provider = ProxmoxProvider::new(&config).unwrap();
+
+        let err = provider
+            .migrate_guest(&running_vm(100), "pve2")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "migration refused: pulse is in read-only mode"
+        );
+        assert!(provider.shutdown_guest(&running_vm(100)).is_err());
+        let request = CloneRequest {
+            vmid: 101,
+            name: "copy".to_string(),
+            target: None,
+            full: true,
+        };
+        assert!(provider.clone_guest(&running_vm(100), &request).is_err());
+        let request = ResourceRequest {
+            cores: Some(4),
+            memory_mb: None,
+        };
+        assert_eq!(
+            provider
+                .set_guest_resources(&running_vm(100), &request)
+                .unwrap_err()
+                .to_string(),
+            "resource change refused: pulse is in read-only mode"
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn test_requests_keep_a_reverse_proxy_sub_path() {
+        let mut server = Server::new();
+        let nextid = server
+            .mock("GET", "/pve/api2/json/cluster/nextid")
+            .with_body(r#"{"data":"104"}"#)
+            .create();
+        let mut config = provider_config(&server);
+        config.host = format!("{}/pve/", server.url());
+        let provider = ProxmoxProvider::new(&config).unwrap();
+
+        assert_eq!(provider.next_vmid().unwrap(), 104);
+        nextid.assert();
+    }
+
+    #[test]
+    fn test_dry_run_describes_actions_without_a_request() {
+        let mut server = Server::new();
+        let posts = server.mock("POST", Matcher::Any).expect(0).create();
+        let gets = server.mock("GET", Matcher::Any).expect(0).create();
+        let config = ProxmoxConfig {
+            name: "test".to_string(),
+            host: server.url(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: None,
+            read_only: false,
+            dry_run: true,
+        };
+        let provider = ProxmoxProvider::new(&config).unwrap();
+
+        let task = provider.migrate_guest(&running_vm(100), "pve2").unwrap();
+        assert_eq!(
+            dry_run_call(&task),
+            Some(
+                format!(
+                    "POST {}/api2/json/nodes/pve1/qemu/100/migrate target=pve2 online=1",
+                    server.url()
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(
+            provider.task_status("pve1", &task).unwrap(),
+            TaskStatus::Succeeded
+        );
+        let task = provider.shutdown_guest(&running_vm(100)).unwrap();
+        assert!(task.ends_with("/nodes/pve1/qemu/100/status/shutdown"));
+        let task = provider.node_power("pve1", NodePower::Shutdown).unwrap();
+        assert!(task.ends_with("/nodes/pve1/status command=shutdown"));
+        let request = ResourceRequest {
+            cores: None,
+            memory_mb: Some(4096),
+        };
+        let task = provider
+            .set_guest_resources(&running_vm(100), &request)
+            .unwrap();
+        assert_eq!(
+            dry_run_call(&task),
+            Some(
+                format!(
+                    "PUT {}/api2/json/nodes/pve1/qemu/100/config memory=4096",
+                    server.url()
+                )
+                .as_str()
+            )
+        );
+        posts.assert();
+        gets.assert();
+    }
+
+    #[test]
+    fn test_clone_lxc_to_another_node() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve1/lxc/200/clone")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("newid".into(), "201".into()),
+                Matcher::UrlEncoded("hostname".into(), "web-test".into()),
+                Matcher::UrlEncoded("full".into(), "1".into()),
+                Matcher::UrlEncoded("target".into(), "pve2".into()),
+            ]))
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .create();
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+        let request = CloneRequest {
+            vmid: 201,
+            name: "web-test".to_string(),
+            target: Some("pve2".to_string()),
+            full: true,
+        };
+
+        let task = provider_for(&server).clone_guest(&lxc, &request).unwrap();
+        assert_eq!(task, UPID);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_linked_vm_clone_stays_on_its_node() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/9000/clone")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("newid".into(), "105".into()),
+                Matcher::UrlEncoded("name".into(), "debian-1".into()),
+                Matcher::UrlEncoded("full".into(), "0".into()),
+            ]))
+            .with_body(format!(r#"{{"data":"{}"}}"#, UPID))
+            .create();
+        let request = CloneRequest {
+            vmid: 105,
+            name: "debian-1".to_string(),
+            target: None,
+            full: false,
+        };
+
+        provider_for(&server)
+            .clone_guest(&running_vm(9000), &request)
+            .unwrap();
+        mock.assert();
+    }
+
+    #[test]
+    fn test_guest_resources_read_from_the_config() {
+        let mut server = Server::new();
+        // PVE 8.1 on writes memory as a property string
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/100/config",
+            r#"{"data":{"cores":2,"sockets":2,"memory":"current=4096","balloon":1024,
+                "numa":1,"hotplug":"disk,network,usb,memory","agent":"1"}}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu/101/config",
+            r#"{"data":{"memory":2048,"balloon":0,"hotplug":"memory"}}"#,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/lxc/200/config",
+            r#"{"data":{"hostname":"web","memory":512,"swap":512}}"#,
+        );
+        let provider = provider_for(&server);
+
+        assert_eq!(
+            provider.fetch_guest_resources(&running_vm(100)).unwrap(),
+            GuestResources {
+                cores: Some(2),
+                sockets: 2,
+                memory_mb: 4096,
+                balloon_mb: Some(1024),
+                memory_hotplug: true,
+            }
+        );
+        // Memory hot-plug without NUMA does nothing
+        assert_eq!(
+            provider.fetch_guest_resources(&running_vm(101)).unwrap(),
+            GuestResources {
+                cores: None,
+                sockets: 1,
+                memory_mb: 2048,
+                balloon_mb: None,
+                memory_hotplug: false,
+            }
+        );
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+        let resources = provider.fetch_guest_resources(&lxc).unwrap();
+        assert_eq!((resources.cores, resources.memory_mb), (None, 512));
+    }
+
+    #[test]
+    fn test_set_guest_resources_puts_only_what_changes() {
+        let mut server = Server::new();
+        let memory_only = server
+            .mock("PUT", "/api2/json/nodes/pve1/lxc/200/config")
+            .match_body("memory=4096")
+            .with_body(r#"{"data":null}"#)
+            .create();
+        let both = server
+            .mock("PUT", "/api2/json/nodes/pve1/qemu/100/config")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("cores".into(), "4".into()),
+                Matcher::UrlEncoded("memory".into(), "8192".into()),
+            ]))
+            .with_body(r#"{"data":null}"#)
+            .create();
+        let provider = provider_for(&server);
+        let mut lxc = running_vm(200);
+        lxc.container_type = ContainerType::LXC;
+
+        let request = ResourceRequest {
+            cores: None,
+            memory_mb: Some(4096),
+        };
+        assert_eq!(provider.set_guest_resources(&lxc, &request).unwrap(), "");
+        let request = ResourceRequest {
+            cores: Some(4),
+            memory_mb: Some(8192),
+        };
+        provider
+            .set_guest_resources(&running_vm(100), &request)
+            .unwrap();
+        memory_only.assert();
+        both.assert();
+
+        server
+            .mock("PUT", "/api2/json/nodes/pve1/qemu/101/config")
+            .with_status(403)
+            .create();
+        assert!(
+            provider
+                .set_guest_resources(&running_vm(101), &request)
+                .unwrap_err()
+                .to_string()
+                .starts_with("token lacks VM.Config.CPU or VM.Config.Memory on /vms")
+        );
+    }
+
+    #[test]
+    fn test_memory_values() {
+        assert_eq!(memory_mb(&serde_json::json!(2048)), Some(2048));
+        assert_eq!(memory_mb(&serde_json::json!("2048")), Some(2048));
+        assert_eq!(memory_mb(&serde_json::json!("current=4096")), Some(4096));
+        assert_eq!(memory_mb(&serde_json::json!("lots")), None);
+    }
+
+    #[test]
+    fn test_next_vmid() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/cluster/nextid",
+            r#"{"data":"105"}"#,
+        );
+        assert_eq!(provider_for(&server).next_vmid().unwrap(), 105);
+    }
+
+    #[test]
+    fn test_forbidden_migration_names_privilege() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/api2/json/nodes/pve1/qemu/100/migrate")
+            .with_status(403)
+            .create();
+
+        let err = provider_for(&server)
+            .migrate_guest(&running_vm(100), "pve2")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "token lacks VM.Migrate on /vms — guests cannot be migrated"
+        );
+    }
+
+    #[test]
+    fn test_task_status() {
+        let mut server = Server::new();
+        let path = format!("/api2/json/nodes/pve1/tasks/{}/status", UPID);
+        let provider = provider_for(&server);
+
+        let cases = [
+            (
+                r#"{"data":{"status":"running","upid":"x"}}"#,
+                TaskStatus::Running,
+            ),
+            (
+                r#"{"data":{"status":"stopped","exitstatus":"OK"}}"#,
+                TaskStatus::Succeeded,
+            ),
+            (
+                r#"{"data":{"status":"stopped","exitstatus":"migration aborted"}}"#,
+                TaskStatus::Failed("migration aborted".to_string()),
+            ),
+        ];
+        for (body, expected) in cases {
+            let mock = mock_json(&mut server, &path, body);
+            assert_eq!(provider.task_status("pve1", UPID).unwrap(), expected);
+            mock.remove();
+        }
+    }
+
+    #[test]
+    fn test_probe_collects_forbidden_endpoints() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/replication",
+            r#"{"data":[]}"#,
+        );
+        mock_json(&mut server, "/api2/json/cluster/status", r#"{"data":[]}"#);
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/disks/list", DISKS_JSON);
+        for path in ["nodes/pve1/qemu", "nodes/pve1/lxc", "cluster/log"] {
+            server
+                .mock("GET", format!("/api2/json/{}", path).as_str())
+                .match_query(Matcher::Any)
+                .with_status(403)
+                .create();
+        }
+
+        let issues = provider_for(&server).probe_permissions().unwrap();
+
+        // qemu and lxc need the same privilege, so it is reported once
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            issues[0].to_string(),
+            "token lacks Sys.Syslog on / — the cluster log will not be shown"
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "token lacks VM.Audit on /vms — containers will not be shown"
+        );
+    }
+
+    #[test]
+    fn test_probe_with_full_permissions() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/status",
+            NODE_STATUS_JSON,
+        );
+        mock_json(&mut server, "/api2/json/nodes/pve1/qemu", QEMU_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/lxc", LXC_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/replication",
+            r#"{"data":[]}"#,
+        );
+        mock_json(&mut server, "/api2/json/cluster/status", r#"{"data":[]}"#);
+        server
+            .mock("GET", "/api2/json/cluster/log")
+            .match_query(Matcher::Any)
+            .with_body(r#"{"data":[]}"#)
+            .create();
+        mock_json(&mut server, "/api2/json/nodes/pve1/netstat", NETSTAT_JSON);
+        mock_json(&mut server, "/api2/json/nodes/pve1/disks/list", DISKS_JSON);
+
+        assert!(
+            provider_for(&server)
+                .probe_permissions()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_probe_fails_on_other_errors() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/api2/json/nodes")
+            .with_status(401)
+            .create();
+
+        let err = provider_for(&server).probe_permissions().unwrap_err();
+
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Status { status: 401, .. }
+        ));
+    }
+
+    #[test]
+    fn test_malformed_json_is_reported() {
+        let mut server = Server::new();
+        mock_json(
+            &mut server,
+            "/api2/json/nodes",
+            r#"{"data":[{"node":"pve1""#,
+        );
+
+        let err = provider_for(&server).fetch_nodes().unwrap_err();
+
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Parse { .. }
+        ));
+    }
+
+    #[test]
+    fn test_unexpected_shape_is_reported() {
+        let mut server = Server::new();
+        mock_json(&mut server, "/api2/json/nodes", NODES_JSON);
+        mock_json(
+            &mut server,
+            "/api2/json/nodes/pve1/qemu",
+            r#"{"data":{"vmid":100}}"#,
+        );
+
+        let err = provider_for(&server).fetch_containers().unwrap_err();
+
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Parse { .. }
+        ));
+    }
+
+    #[test]
+    fn test_connection_refused_is_request_error() {
+        // Bind and immediately release a port so nothing is listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = ProxmoxConfig {
+            name: "test".to_string(),
+            host: format!("http://127.0.0.1:{}", port),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: None,
+            read_only: false,
+            dry_run: false,
+        };
+
+        let err = ProxmoxProvider::new(&config)
+            .unwrap()
+            .fetch_nodes()
+            .unwrap_err();
+
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Request(_)
+        ));
+    }
+
+    #[test]
+    fn test_fails_over_to_the_next_endpoint() {
+        let closed_port = || {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port()
+        };
+        let down = format!("http://127.0.0.1:{}", closed_port());
+        let also_down = format!("http://127.0.0.1:{}", closed_port());
+        let mut server = Server::new();
+        let nodes = server
+            .mock("GET", "/api2/json/nodes")
+            .with_body(r#"{"data":[]}"#)
+            .expect(2)
+            .create();
+        let mut config = ProxmoxConfig {
+            host: down.clone(),
+            hosts: vec![also_down, server.url()],
+            ..provider_config(&server)
+        };
+        let provider = ProxmoxProvider::new(&config).unwrap();
+
+        assert!(provider.fetch_nodes().unwrap().is_empty());
+        assert_eq!(
+            provider.endpoint(),
+            Some(EndpointInUse {
+                url: server.url(),
+                preferred: false,
+            })
+        );
+        // Later requests go straight to the endpoint that answered
+        provider.begin_refresh();
+        provider.fetch_nodes().unwrap();
+        assert_eq!(provider.request_count(), Some(1));
+        nodes.assert();
+
+        // Nothing answers: the last connection error is reported
+        config.hosts.pop();
+        let err = ProxmoxProvider::new(&config)
+            .unwrap()
+            .fetch_nodes()
+            .unwrap_err();
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Request(_)
+        ));
+    }
+
+    #[test]
+    fn test_console_templates_by_guest_type() {
+        let config = ProxmoxConfig {
+            name: "test".to_string(),
+            host: "https://pve:8006".to_string(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: None,
+            ssh_jump: None,
+            console: ConsoleConfig {
+                lxc: Some("ssh root@{node} -t pct enter {vmid}".to_string()),
+                vm: None,
+            },
+            max_requests: None,
+            read_only: false,
+            dry_run: false,
+        };
+        let provider = ProxmoxProvider::new(&config).unwrap();
+        let mut guest = Container::test("test", 105, "web", "pve");
+        assert_eq!(
+            provider.console_template(&guest),
+            Some("ssh root@{node} -t pct enter {vmid}")
+        );
+        guest.container_type = ContainerType::VM;
+        assert_eq!(provider.console_template(&guest), None);
+
+        // A typo in a placeholder stops the provider from starting
+        let config = ProxmoxConfig {
+            console: ConsoleConfig {
+                lxc: None,
+                vm: Some("ssh root@{host} qm terminal {vmid}".to_string()),
+            },
+            ..config
+        };
+        let err = ProxmoxProvider::new(&config).err().unwrap();
+        assert!(err.to_string().starts_with("unknown placeholder {host}"));
+    }
+
+    fn provider_via(server: &ServerGuard, proxy: String) -> ProxmoxProvider {
+        ProxmoxProvider::new(&ProxmoxConfig {
+            name: "test".to_string(),
+            host: server.url(),
+            hosts: Vec::new(),
+            user: "root@pam".to_string(),
+            token_id: "root@pam!pulse".to_string(),
+            token_secret: Some("secret".to_string()),
+            token_secret_cmd: None,
+            proxy: Some(proxy),
+            ssh_jump: None,
+            console: Default::default(),
+            max_requests: None,
+            read_only: false,
+            dry_run: false,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unreachable_proxy_is_named() {
+        let server = Server::new();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let provider = provider_via(&server, format!("socks5h://127.0.0.1:{}", port));
+
+        let err = provider.fetch_nodes().unwrap_err();
+        match provider_error(err.as_ref()) {
+            ProviderError::Hop { hop, message } => {
+                assert_eq!(hop, &format!("proxy socks5h://127.0.0.1:{}", port));
+                assert!(message.starts_with("unreachable: "), "{}", message);
+            }
+            other => panic!("expected a Hop error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_failing_proxy_blames_the_next_hop() {
+        let server = Server::new();
+        // A "proxy" that accepts connections and hangs up at once
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                drop(stream);
+            }
+        });
+        let provider = provider_via(&server, format!("socks5://127.0.0.1:{}", port));
+
+        let err = provider.fetch_nodes().unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with(&format!(
+                "proxy socks5://127.0.0.1:{}: reached, but it could not connect to {}",
+                port,
+                server.url()
+            )),
+            "{}",
+            message
+        );
+    }
 }
@@ -0,0 +1,187 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+
+use super::Provider;
+use crate::agent::{SCHEMA_VERSION, Snapshot};
+use crate::config::PulseAgentConfig;
+use crate::error::ProviderError;
+use crate::models::{Container, GuestId, Node, NodeId};
+
+/// A machine running `pulse agent`, read over HTTP. One request per refresh
+/// returns both its node and its containers.
+pub struct PulseAgentProvider {
+    name: String,
+    url: String,
+    client: Client,
+    /// This cycle's snapshot, shared by `fetch_nodes` and `fetch_containers`.
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+impl PulseAgentProvider {
+    pub fn new(config: &PulseAgentConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .connect_timeout(Duration::from_secs(5))
+            .build()?;
+        Ok(Self {
+            name: config.name.clone(),
+            url: format!("{}/snapshot", config.url.trim_end_matches('/')),
+            client,
+            snapshot: Mutex::new(None),
+        })
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, ProviderError> {
+        let mut cached = self.snapshot.lock().unwrap();
+        if let Some(snapshot) = cached.as_ref() {
+            return Ok(snapshot.clone());
+        }
+        let snapshot = self.fetch_snapshot()?;
+        *cached = Some(snapshot.clone());
+        Ok(snapshot)
+    }
+
+    fn fetch_snapshot(&self) -> Result<Snapshot, ProviderError> {
+        let url = self.url.clone();
+        let response = self.client.get(&url).send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ProviderError::Status {
+                url,
+                status: status.as_u16(),
+            });
+        }
+        let body = response.text()?;
+        // The version is checked on its own first: an agent of another
+        // version may not parse at all, and "wrong version" is the better error
+        let value: serde_json::Value =
+            serde_json::from_str(&body).map_err(|source| ProviderError::Parse {
+                url: url.clone(),
+                source,
+            })?;
+        let found = value.get("version").and_then(|v| v.as_u64());
+        if found != Some(SCHEMA_VERSION as u64) {
+            return Err(ProviderError::Version {
+                url,
+                found,
+                expected: SCHEMA_VERSION,
+            });
+        }
+        let mut snapshot: Snapshot =
+            serde_json::from_value(value).map_err(|source| ProviderError::Parse { url, source })?;
+        // The agent names itself "local"; here it is whatever it's configured as
+        for node in &mut snapshot.nodes {
+            node.id = NodeId::new(&self.name, &node.name);
+        }
+        for container in &mut snapshot.containers {
+            container.id = GuestId::new(&self.name, &container.id.id);
+        }
+        Ok(snapshot)
+    }
+}
+
+impl Provider for PulseAgentProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        Ok(self.snapshot()?.nodes)
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        Ok(self.snapshot()?.containers)
+    }
+
+    fn begin_refresh(&self) {
+        *self.snapshot.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ContainerType;
+    use mockito::{Server, ServerGuard};
+
+    const SNAPSHOT_JSON: &str = r#"{"version":1,
+        "nodes":[{"id":{"provider":"local","name":"nas"},"name":"nas","status":"Online",
+            "cpu_usage":12.5,"cpu_cores":4,"memory_used":1024,"memory_total":4096,"uptime":600,
+            "interfaces":[],"disks":"NotReported","pending_updates":null,"package_updates":[]}],
+        "containers":[{"id":{"provider":"local","id":"a1b2c3"},"vmid":0,"name":"web","node":"nas",
+            "container_type":"Docker","status":"Running","cpu_usage":5.0,"max_cpu":4,
+            "memory_used":512,"memory_max":4096,"uptime":60,"lock":null,"missing":false,"pool":null}]}"#;
+
+    fn provider(server: &ServerGuard) -> PulseAgentProvider {
+        PulseAgentProvider::new(&PulseAgentConfig {
+            name: "nas".to_string(),
+            url: format!("{}/", server.url()),
+        })
+        .unwrap()
+    }
+
+    fn provider_error<'a>(e: &'a (dyn std::error::Error + 'static)) -> &'a ProviderError {
+        e.downcast_ref::<ProviderError>()
+            .expect("expected a ProviderError")
+    }
+
+    #[test]
+    fn test_one_request_per_refresh() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("GET", "/snapshot")
+            .with_body(SNAPSHOT_JSON)
+            .expect(2)
+            .create();
+        let provider = provider(&server);
+
+        let nodes = provider.fetch_nodes().unwrap();
+        assert_eq!(nodes[0].id, NodeId::new("nas", "nas"));
+        assert_eq!(nodes[0].cpu_cores, 4);
+        let containers = provider.fetch_containers().unwrap();
+        assert_eq!(containers[0].id, GuestId::new("nas", "a1b2c3"));
+        assert_eq!(containers[0].container_type, ContainerType::Docker);
+
+        provider.begin_refresh();
+        provider.fetch_nodes().unwrap();
+        mock.assert();
+    }
+
+    #[test]
+    fn test_version_mismatch_is_a_clear_error() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/snapshot")
+            .with_body(r#"{"version":2,"nodes":"changed shape"}"#)
+            .create();
+        let err = provider(&server).fetch_nodes().unwrap_err();
+        assert!(matches!(
+            provider_error(err.as_ref()),
+            ProviderError::Version {
+                found: Some(2),
+                expected: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "agent at {}/snapshot speaks schema v2, this pulse expects v1; run the same pulse version on both ends",
+                server.url()
+            )
+        );
+
+        let mut server = Server::new();
+        server.mock("GET", "/snapshot").with_body("{}").create();
+        let err = provider(&server).fetch_nodes().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}/snapshot sent no schema version; is it a pulse agent?",
+                server.url()
+            )
+        );
+    }
+}
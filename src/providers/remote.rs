@@ -0,0 +1,706 @@
+//! The providers of a `pulse daemon`, as `pulse attach` sees them. One
+//! connection is shared by all of them: a thread reads what the daemon
+//! pushes (snapshots, log entries, replies) and keeps the latest, so fetches
+//! answer from memory and only actions and on-demand fetches go over the
+//! socket.
+//!
+//! When the daemon goes away the thread keeps trying to reconnect, and
+//! meanwhile every fetch fails with the reason; the UI treats that like a
+//! provider that is down, keeping the last items as stale.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::Duration;
+
+use super::{Provider, SharedProvider};
+use crate::clone::CloneRequest;
+use crate::events::CLUSTER_LOG_ENTRIES;
+use crate::models::{
+    ClusterLogEntry, ClusterStatus, Container, GuestDetails, HaResource, Node, PermissionIssue,
+    ReplicationJob, TaskStatus,
+};
+use crate::node_power::NodePower;
+use crate::protocol::{
+    self, ClientMessage, PROTOCOL_VERSION, ProviderInfo, ProviderSnapshot, Reply, Request,
+    ServerMessage,
+};
+use crate::resources::{GuestResources, ResourceRequest};
+
+/// Wait between attempts to reach a daemon that is gone.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// How long a forwarded call may take. The daemon answers once its
+/// provider has, so this is about a slow API rather than the socket.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the daemon said about itself in its last hello.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaemonInfo {
+    pub providers: Vec<ProviderInfo>,
+    pub read_only: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Default)]
+struct State {
+    /// Where requests are written while connected.
+    writer: Option<UnixStream>,
+    /// Why there is no connection; None while connected and greeted.
+    down: Option<String>,
+    /// A connection attempt has finished one way or another.
+    tried: bool,
+    daemon: Option<DaemonInfo>,
+    /// The daemon's hello changed since `take_changed`.
+    changed: bool,
+    snapshot: HashMap<String, ProviderSnapshot>,
+    /// Each provider's log entries, newest first.
+    logs: HashMap<String, Vec<ClusterLogEntry>>,
+    pending: HashMap<u64, Sender<Result<Reply, String>>>,
+}
+
+/// A connection to a daemon, re-established whenever it drops.
+pub struct Connection {
+    path: PathBuf,
+    state: Mutex<State>,
+    /// Signalled whenever a connection attempt settles, either way.
+    settled: Condvar,
+    next_id: AtomicU64,
+}
+
+impl Connection {
+    /// Connect to the daemon at `path` and wait up to `timeout` for its
+    /// hello and first snapshot. Fails if it can't be reached or refuses.
+    pub fn open(path: &Path, timeout: Duration) -> Result<Arc<Connection>, String> {
+        let connection = Arc::new(Connection {
+            path: path.to_path_buf(),
+            state: Mutex::new(State::default()),
+            settled: Condvar::new(),
+            next_id: AtomicU64::new(0),
+        });
+        let weak = Arc::downgrade(&connection);
+        std::thread::spawn(move || keep_connected(weak));
+
+        let state = connection.state.lock().unwrap();
+        let (state, _) = connection
+            .settled
+            .wait_timeout_while(state, timeout, |s| !s.tried)
+            .unwrap();
+        if !state.tried {
+            return Err(format!(
+                "no answer from the daemon at {} within {:?}",
+                path.display(),
+                timeout
+            ));
+        }
+        if let Some(reason) = &state.down {
+            return Err(format!("cannot attach to {}: {}", path.display(), reason));
+        }
+        drop(state);
+        Ok(connection)
+    }
+
+    /// What the daemon said in its last hello.
+    pub fn daemon(&self) -> DaemonInfo {
+        self.state
+            .lock()
+            .unwrap()
+            .daemon
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// One provider per provider of the daemon.
+    pub fn providers(self: &Arc<Self>) -> Vec<SharedProvider> {
+        self.daemon()
+            .providers
+            .into_iter()
+            .map(|info| {
+                Arc::new(RemoteProvider {
+                    name: info.name,
+                    user: info.user,
+                    connection: self.clone(),
+                }) as SharedProvider
+            })
+            .collect()
+    }
+
+    /// Whether the daemon's providers or modes changed (it reloaded its
+    /// config, or was restarted with another) since last asked.
+    pub fn take_changed(&self) -> bool {
+        std::mem::take(&mut self.state.lock().unwrap().changed)
+    }
+
+    /// Read the daemon's messages from `stream` until it closes; returns
+    /// why it ended.
+    fn serve(&self, stream: UnixStream) -> String {
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(e) => return e.to_string(),
+        };
+        let hello = protocol::encode(&ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+        });
+        if let Err(e) = (&writer).write_all(hello.as_bytes()) {
+            return e.to_string();
+        }
+        self.state.lock().unwrap().writer = Some(writer);
+
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return e.to_string(),
+            };
+            let message = match protocol::decode::<ServerMessage>(&line) {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!("attach: skipped a message from the daemon: {}", e);
+                    continue;
+                }
+            };
+            if let Err(reason) = self.receive(message) {
+                return reason;
+            }
+        }
+        "the daemon closed the connection".to_string()
+    }
+
+    /// Take in one message; an error ends the connection.
+    fn receive(&self, message: ServerMessage) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        match message {
+            ServerMessage::Hello { version, .. } if version != PROTOCOL_VERSION => {
+                return Err(format!(
+                    "the daemon speaks protocol v{}, this pulse v{}",
+                    version, PROTOCOL_VERSION
+                ));
+            }
+            ServerMessage::Hello {
+                providers,
+                read_only,
+                dry_run,
+                ..
+            } => {
+                let daemon = DaemonInfo {
+                    providers,
+                    read_only,
+                    dry_run,
+                };
+                if state.daemon.as_ref().is_some_and(|d| *d != daemon) {
+                    state.changed = true;
+                }
+                state.daemon = Some(daemon);
+            }
+            ServerMessage::Refused { reason } => return Err(reason),
+            ServerMessage::Snapshot { providers } => {
+                state.snapshot = providers.into_iter().map(|p| (p.name.clone(), p)).collect();
+                // Attached once the first snapshot is in
+                if state.down.take().is_some() || !state.tried {
+                    state.tried = true;
+                    self.settled.notify_all();
+                }
+            }
+            ServerMessage::Events { provider, entries } => {
+                let log = state.logs.entry(provider).or_default();
+                let new: Vec<ClusterLogEntry> = entries
+                    .into_iter()
+                    .filter(|e| !log.iter().any(|l| l.uid == e.uid && l.time == e.time))
+                    .collect();
+                log.splice(0..0, new);
+                log.sort_by_key(|e| std::cmp::Reverse(e.time));
+                log.truncate(CLUSTER_LOG_ENTRIES);
+            }
+            ServerMessage::Reply { id, result } => {
+                if let Some(reply) = state.pending.remove(&id) {
+                    let _ = reply.send(result);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The connection ended (or never started) for `reason`.
+    fn disconnected(&self, reason: String) {
+        let mut state = self.state.lock().unwrap();
+        if state.down.is_none() {
+            log::warn!("attach: {}: {}", self.path.display(), reason);
+        }
+        state.writer = None;
+        state.down = Some(reason);
+        state.snapshot.clear();
+        // Dropping the senders fails every call still waiting
+        state.pending.clear();
+        state.tried = true;
+        self.settled.notify_all();
+    }
+
+    /// What provider `name` returned for `pick` in the daemon's last refresh.
+    fn fetched<T: Clone>(
+        &self,
+        name: &str,
+        pick: impl Fn(&ProviderSnapshot) -> &Result<T, String>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let state = self.state.lock().unwrap();
+        if let Some(reason) = &state.down {
+            return Err(format!("daemon unreachable: {}", reason).into());
+        }
+        match state.snapshot.get(name) {
+            Some(snapshot) => pick(snapshot).clone().map_err(Into::into),
+            None => Err("the daemon no longer has this provider".into()),
+        }
+    }
+
+    /// Send `request` for provider `name` and wait for the daemon's reply.
+    fn call(&self, name: &str, request: Request) -> Result<Reply, Box<dyn std::error::Error>> {
+        let (sender, reply) = mpsc::channel();
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            let mut writer: &UnixStream = match (&state.down, &state.writer) {
+                (None, Some(writer)) => writer,
+                (down, _) => {
+                    let reason = down.as_deref().unwrap_or("not connected");
+                    return Err(format!("daemon unreachable: {}", reason).into());
+                }
+            };
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let line = protocol::encode(&ClientMessage::Request {
+                id,
+                provider: name.to_string(),
+                request,
+            });
+            writer
+                .write_all(line.as_bytes())
+                .map_err(|e| format!("cannot reach the daemon: {}", e))?;
+            state.pending.insert(id, sender);
+            id
+        };
+        match reply.recv_timeout(REPLY_TIMEOUT) {
+            Ok(result) => result.map_err(Into::into),
+            Err(RecvTimeoutError::Timeout) => {
+                self.state.lock().unwrap().pending.remove(&id);
+                Err(format!("the daemon did not answer within {:?}", REPLY_TIMEOUT).into())
+            }
+            Err(RecvTimeoutError::Disconnected) => Err("lost the connection to the daemon".into()),
+        }
+    }
+}
+
+/// Connect, read until the connection drops, wait, and again, for as long
+/// as anyone holds the connection.
+fn keep_connected(connection: Weak<Connection>) {
+    loop {
+        let Some(strong) = connection.upgrade() else {
+            return;
+        };
+        let reason = match UnixStream::connect(&strong.path) {
+            Ok(stream) => strong.serve(stream),
+            Err(e) => e.to_string(),
+        };
+        strong.disconnected(reason);
+        drop(strong);
+        std::thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+fn unexpected(reply: Reply) -> Box<dyn std::error::Error> {
+    format!("unexpected reply from the daemon: {:?}", reply).into()
+}
+
+/// One of the daemon's providers.
+pub struct RemoteProvider {
+    name: String,
+    user: Option<String>,
+    connection: Arc<Connection>,
+}
+
+impl RemoteProvider {
+    fn text(&self, request: Request) -> Result<String, Box<dyn std::error::Error>> {
+        match self.connection.call(&self.name, request)? {
+            Reply::Text(text) => Ok(text),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn issues(&self, request: Request) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        match self.connection.call(&self.name, request)? {
+            Reply::Issues(issues) => Ok(issues),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+fn guest(container: &Container) -> Box<Container> {
+    Box::new(container.clone())
+}
+
+impl Provider for RemoteProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        self.connection.fetched(&self.name, |s| &s.nodes)
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        self.connection.fetched(&self.name, |s| &s.containers)
+    }
+
+    fn fetch_cluster_status(&self) -> Result<Option<ClusterStatus>, Box<dyn std::error::Error>> {
+        self.connection.fetched(&self.name, |s| &s.cluster)
+    }
+
+    fn fetch_cluster_log(
+        &self,
+        max: usize,
+    ) -> Result<Vec<ClusterLogEntry>, Box<dyn std::error::Error>> {
+        let state = self.connection.state.lock().unwrap();
+        let log = state.logs.get(&self.name).map_or(&[][..], Vec::as_slice);
+        Ok(log.iter().take(max).cloned().collect())
+    }
+
+    fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    fn probe_permissions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        self.issues(Request::ProbePermissions)
+    }
+
+    fn probe_actions(&self) -> Result<Vec<PermissionIssue>, Box<dyn std::error::Error>> {
+        self.issues(Request::ProbeActions)
+    }
+
+    fn fetch_replication(&self) -> Result<Vec<ReplicationJob>, Box<dyn std::error::Error>> {
+        match self
+            .connection
+            .call(&self.name, Request::FetchReplication)?
+        {
+            Reply::Replication(jobs) => Ok(jobs),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn fetch_ha_resources(&self) -> Result<Vec<HaResource>, Box<dyn std::error::Error>> {
+        match self
+            .connection
+            .call(&self.name, Request::FetchHaResources)?
+        {
+            Reply::HaResources(resources) => Ok(resources),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn fetch_guest_details(
+        &self,
+        container: &Container,
+    ) -> Result<GuestDetails, Box<dyn std::error::Error>> {
+        let request = Request::FetchGuestDetails {
+            guest: guest(container),
+        };
+        match self.connection.call(&self.name, request)? {
+            Reply::GuestDetails(details) => Ok(details),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn fetch_guest_resources(
+        &self,
+        container: &Container,
+    ) -> Result<GuestResources, Box<dyn std::error::Error>> {
+        let request = Request::FetchGuestResources {
+            guest: guest(container),
+        };
+        match self.connection.call(&self.name, request)? {
+            Reply::GuestResources(resources) => Ok(resources),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn next_vmid(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        match self.connection.call(&self.name, Request::NextVmid)? {
+            Reply::Vmid(vmid) => Ok(vmid),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn migrate_guest(
+        &self,
+        container: &Container,
+        target: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::MigrateGuest {
+            guest: guest(container),
+            target: target.to_string(),
+        })
+    }
+
+    fn start_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::StartGuest {
+            guest: guest(container),
+        })
+    }
+
+    fn shutdown_guest(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::ShutdownGuest {
+            guest: guest(container),
+        })
+    }
+
+    fn clone_guest(
+        &self,
+        container: &Container,
+        request: &CloneRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::CloneGuest {
+            guest: guest(container),
+            request: request.clone(),
+        })
+    }
+
+    fn set_guest_resources(
+        &self,
+        container: &Container,
+        request: &ResourceRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::SetGuestResources {
+            guest: guest(container),
+            request: request.clone(),
+        })
+    }
+
+    fn node_power(
+        &self,
+        node: &str,
+        command: NodePower,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::NodePower {
+            node: node.to_string(),
+            command,
+        })
+    }
+
+    fn web_console_url(&self, container: &Container) -> Result<String, Box<dyn std::error::Error>> {
+        self.text(Request::WebConsoleUrl {
+            guest: guest(container),
+        })
+    }
+
+    fn task_status(
+        &self,
+        node: &str,
+        task: &str,
+    ) -> Result<TaskStatus, Box<dyn std::error::Error>> {
+        let request = Request::TaskStatus {
+            node: node.to_string(),
+            task: task.to_string(),
+        };
+        match self.connection.call(&self.name, request)? {
+            Reply::TaskStatus(status) => Ok(status),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::thread::JoinHandle;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::models::{ContainerStatus, ContainerType, EventLevel};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn info(name: &str) -> ProviderInfo {
+        ProviderInfo {
+            name: name.to_string(),
+            user: Some("pulse@pve!daemon".to_string()),
+        }
+    }
+
+    fn hello(names: &[&str]) -> ServerMessage {
+        ServerMessage::Hello {
+            version: PROTOCOL_VERSION,
+            providers: names.iter().map(|n| info(n)).collect(),
+            read_only: false,
+            dry_run: false,
+        }
+    }
+
+    fn snapshot(names: &[&str]) -> ServerMessage {
+        ServerMessage::Snapshot {
+            providers: names
+                .iter()
+                .map(|name| ProviderSnapshot {
+                    name: name.to_string(),
+                    nodes: Ok(Vec::new()),
+                    containers: Err("HTTP 596: connection timed out".to_string()),
+                    cluster: Ok(None),
+                })
+                .collect(),
+        }
+    }
+
+    fn guest() -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            status: ContainerStatus::Stopped,
+            max_cpu: 2,
+            memory_max: 2048,
+            ..Container::test("homelab", 100, "web", "pve1")
+        }
+    }
+
+    /// A daemon for one client: reads its hello, sends `greeting`, answers
+    /// `requests` requests with a task id and hangs up.
+    fn fake_daemon(path: &Path, greeting: Vec<ServerMessage>, requests: usize) -> JoinHandle<()> {
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path).unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut lines = BufReader::new(stream).lines();
+            let first = lines.next().unwrap().unwrap();
+            assert!(matches!(
+                protocol::decode::<ClientMessage>(&first),
+                Ok(Some(ClientMessage::Hello { version: 1 }))
+            ));
+            for message in &greeting {
+                writer
+                    .write_all(protocol::encode(message).as_bytes())
+                    .unwrap();
+            }
+            for line in lines.take(requests) {
+                let Ok(Some(ClientMessage::Request { id, request, .. })) =
+                    protocol::decode::<ClientMessage>(&line.unwrap())
+                else {
+                    panic!("expected a request");
+                };
+                assert!(matches!(request, Request::StartGuest { .. }));
+                let reply = ServerMessage::Reply {
+                    id,
+                    result: Ok(Reply::Text(format!("UPID:pve1:start:{}", id))),
+                };
+                writer
+                    .write_all(protocol::encode(&reply).as_bytes())
+                    .unwrap();
+            }
+        })
+    }
+
+    /// Wait for `done`, failing the test after `TIMEOUT`.
+    fn eventually(mut done: impl FnMut() -> bool) {
+        let started = Instant::now();
+        while !done() {
+            assert!(started.elapsed() < TIMEOUT, "timed out");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_attached_providers_follow_the_daemon_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pulse.sock");
+        let events = ServerMessage::Events {
+            provider: "homelab".to_string(),
+            entries: vec![ClusterLogEntry {
+                uid: "1".to_string(),
+                time: 100,
+                node: "pve1".to_string(),
+                tag: "pvedaemon".to_string(),
+                user: None,
+                level: EventLevel::Warning,
+                message: "starting VM 100".to_string(),
+            }],
+        };
+        let daemon = fake_daemon(
+            &path,
+            vec![hello(&["homelab"]), snapshot(&["homelab"]), events],
+            1,
+        );
+
+        let connection = Connection::open(&path, TIMEOUT).unwrap();
+        let providers = connection.providers();
+        assert_eq!(providers.len(), 1);
+        let homelab = &providers[0];
+        assert_eq!(homelab.name(), "homelab");
+        assert_eq!(homelab.user(), Some("pulse@pve!daemon"));
+        assert!(homelab.fetch_nodes().unwrap().is_empty());
+        // The daemon's errors are the provider's
+        assert_eq!(
+            homelab.fetch_containers().unwrap_err().to_string(),
+            "HTTP 596: connection timed out"
+        );
+        eventually(|| homelab.fetch_cluster_log(50).unwrap().len() == 1);
+        assert_eq!(homelab.start_guest(&guest()).unwrap(), "UPID:pve1:start:0");
+
+        // The daemon goes away: fetches and calls fail until it is back
+        daemon.join().unwrap();
+        eventually(|| homelab.fetch_nodes().is_err());
+        assert!(
+            homelab
+                .fetch_nodes()
+                .unwrap_err()
+                .to_string()
+                .starts_with("daemon unreachable: ")
+        );
+        assert!(homelab.start_guest(&guest()).is_err());
+        assert!(!connection.take_changed());
+
+        // Restarted with another provider
+        let names = ["homelab", "office"];
+        let daemon = fake_daemon(&path, vec![hello(&names), snapshot(&names)], 1);
+        eventually(|| connection.take_changed());
+        let providers = connection.providers();
+        assert_eq!(providers.len(), 2);
+        assert!(providers[1].fetch_nodes().unwrap().is_empty());
+        // Request ids keep counting across connections
+        assert_eq!(
+            providers[0].start_guest(&guest()).unwrap(),
+            "UPID:pve1:start:1"
+        );
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn test_attaching_fails_with_the_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pulse.sock");
+        let error = Connection::open(&path, TIMEOUT).err().unwrap();
+        assert!(error.starts_with("cannot attach to "), "{}", error);
+
+        let refused = ServerMessage::Refused {
+            reason: "the client speaks protocol v1, this daemon v2".to_string(),
+        };
+        let daemon = fake_daemon(&path, vec![refused], 0);
+        let error = Connection::open(&path, TIMEOUT).err().unwrap();
+        assert!(
+            error.ends_with(": the client speaks protocol v1, this daemon v2"),
+            "{}",
+            error
+        );
+        daemon.join().unwrap();
+
+        let newer = ServerMessage::Hello {
+            version: PROTOCOL_VERSION + 1,
+            providers: vec![info("homelab")],
+            read_only: false,
+            dry_run: false,
+        };
+        let daemon = fake_daemon(&path, vec![newer], 0);
+        let error = Connection::open(&path, TIMEOUT).err().unwrap();
+        assert!(
+            error.ends_with(": the daemon speaks protocol v2, this pulse v1"),
+            "{}",
+            error
+        );
+        daemon.join().unwrap();
+    }
+}
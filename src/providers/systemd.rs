@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::Provider;
+use crate::config::SystemdConfig;
+use crate::error::ProviderError;
+use crate::models::{Container, ContainerStatus, ContainerType, GuestId, Node, NodeId, NodeStatus};
+
+const PROPERTIES: &str =
+    "Id,LoadState,ActiveState,MemoryCurrent,MemoryMax,CPUUsageNSec,ActiveEnterTimestampMonotonic";
+
+/// Systemd units on the machine pulse runs on, via `systemctl show`. The
+/// machine itself is reported as the only node, read from /proc.
+pub struct SystemdProvider {
+    name: String,
+    units: Vec<String>,
+    samples: Mutex<CpuSamples>,
+}
+
+/// Previous CPU counters, for turning cumulative totals into percentages.
+#[derive(Default)]
+struct CpuSamples {
+    host: Option<CpuTimes>,
+    units: HashMap<String, (u64, Instant)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+/// What the unit mapping needs to know about the local machine.
+struct Host {
+    name: String,
+    cores: u32,
+    memory_total: u64,
+    uptime: u64,
+}
+
+impl SystemdProvider {
+    pub fn new(config: &SystemdConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            units: config.units.clone(),
+            samples: Mutex::new(CpuSamples::default()),
+        }
+    }
+
+    fn host(&self) -> Result<Host, Box<dyn std::error::Error>> {
+        let stat = read_proc("/proc/stat")?;
+        let meminfo = read_proc("/proc/meminfo")?;
+        Ok(Host {
+            name: hostname()?,
+            cores: count_cores(&stat),
+            memory_total: parse_meminfo(&meminfo).1,
+            uptime: parse_uptime(&read_proc("/proc/uptime")?),
+        })
+    }
+
+    /// Configured names with globs expanded. Globs matching nothing are kept
+    /// as-is so they show up as missing rather than silently vanishing.
+    fn expand_units(&self) -> Result<Vec<(String, bool)>, ProviderError> {
+        let mut units = Vec::new();
+        for pattern in &self.units {
+            if !is_glob(pattern) {
+                units.push((pattern.clone(), true));
+                continue;
+            }
+            let listed = systemctl(&[
+                "list-units",
+                "--all",
+                "--plain",
+                "--no-legend",
+                "--no-pager",
+                pattern,
+            ])?;
+            let matched = parse_unit_list(&listed);
+            if matched.is_empty() {
+                units.push((pattern.clone(), false));
+            }
+            units.extend(matched.into_iter().map(|unit| (unit, true)));
+        }
+        Ok(units)
+    }
+}
+
+impl Provider for SystemdProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        let name = hostname()?;
+        let stat = read_proc("/proc/stat")?;
+        let (memory_used, memory_total) = parse_meminfo(&read_proc("/proc/meminfo")?);
+
+        let now = parse_cpu_times(&stat).ok_or("unexpected /proc/stat format")?;
+        let mut samples = self.samples.lock().unwrap();
+        let cpu_usage = cpu_percent(samples.host, now);
+        samples.host = Some(now);
+
+        Ok(vec![Node {
+            id: NodeId::new(&self.name, &name),
+            name,
+            status: NodeStatus::Online,
+            cpu_usage,
+            cpu_cores: count_cores(&stat),
+            memory_used,
+            memory_total,
+            disk_used: 0,
+            disk_total: 0,
+            uptime: parse_uptime(&read_proc("/proc/uptime")?),
+            interfaces: Vec::new(),
+            disks: Default::default(),
+            pending_updates: None,
+            package_updates: Vec::new(),
+            pressure: None,
+        }])
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        let host = self.host()?;
+        let units = self.expand_units()?;
+
+        let existing: Vec<&str> = units
+            .iter()
+            .filter(|(_, exists)| *exists)
+            .map(|(unit, _)| unit.as_str())
+            .collect();
+        let blocks = if existing.is_empty() {
+            Vec::new()
+        } else {
+            let mut args = vec!["show", "--no-pager", "--property", PROPERTIES, "--"];
+            args.extend(&existing);
+            parse_properties(&systemctl(&args)?)
+        };
+        if blocks.len() != existing.len() {
+            return Err(format!(
+                "systemctl show returned {} units, expected {}",
+                blocks.len(),
+                existing.len()
+            )
+            .into());
+        }
+
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        let mut blocks = blocks.into_iter();
+        let mut containers = Vec::new();
+        for (unit, exists) in units {
+            if !exists {
+                containers.push(missing_unit(&self.name, &host, &unit));
+                continue;
+            }
+            let props = blocks.next().unwrap_or_default();
+            let id = props.get("Id").cloned().unwrap_or(unit);
+
+            let cpu_nsec = props.get("CPUUsageNSec").and_then(|v| parse_number(v));
+            let cpu_usage = match (cpu_nsec, samples.units.get(&id)) {
+                (Some(nsec), Some(&(last, at))) => unit_cpu_percent(
+                    nsec.saturating_sub(last),
+                    now.duration_since(at),
+                    host.cores,
+                ),
+                _ => 0.0,
+            };
+            match cpu_nsec {
+                Some(nsec) => {
+                    samples.units.insert(id.clone(), (nsec, now));
+                }
+                None => {
+                    samples.units.remove(&id);
+                }
+            }
+
+            containers.push(unit_to_container(&self.name, &host, &id, &props, cpu_usage));
+        }
+
+        Ok(containers)
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<String, ProviderError> {
+    let command = format!("systemctl {}", args.join(" "));
+    let output =
+        Command::new("systemctl")
+            .args(args)
+            .output()
+            .map_err(|e| ProviderError::Command {
+                command: command.clone(),
+                message: e.to_string(),
+            })?;
+    if !output.status.success() {
+        return Err(ProviderError::Command {
+            command,
+            message: String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .replace('\n', "; "),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_proc(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e).into())
+}
+
+fn hostname() -> Result<String, Box<dyn std::error::Error>> {
+    Ok(read_proc("/proc/sys/kernel/hostname")?.trim().to_string())
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Unit names from `systemctl list-units --plain --no-legend`.
+fn parse_unit_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `systemctl show` output: one block of `Key=Value` lines per unit,
+/// separated by blank lines, in the order the units were given.
+fn parse_properties(output: &str) -> Vec<HashMap<String, String>> {
+    let mut blocks = Vec::new();
+    let mut current = HashMap::new();
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            current.insert(key.to_string(), value.to_string());
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+/// Numeric property; systemd reports unset values as "[not set]", "infinity"
+/// or u64::MAX.
+fn parse_number(value: &str) -> Option<u64> {
+    value.parse().ok().filter(|&n| n != u64::MAX)
+}
+
+/// Short name for display: "nginx.service" becomes "nginx".
+fn display_name(unit: &str) -> &str {
+    unit.strip_suffix(".service").unwrap_or(unit)
+}
+
+fn unit_to_container(
+    provider: &str,
+    host: &Host,
+    id: &str,
+    props: &HashMap<String, String>,
+    cpu_usage: f64,
+) -> Container {
+    let get = |key: &str| props.get(key).map(String::as_str).unwrap_or("");
+
+    if get("LoadState") == "not-found" {
+        return missing_unit(provider, host, id);
+    }
+
+    let running = matches!(get("ActiveState"), "active" | "reloading");
+    let uptime = match parse_number(get("ActiveEnterTimestampMonotonic")) {
+        Some(entered) if running && entered > 0 => host.uptime.saturating_sub(entered / 1_000_000),
+        _ => 0,
+    };
+
+    Container {
+        id: GuestId::new(provider, id),
+        vmid: 0,
+        name: display_name(id).to_string(),
+        node: host.name.clone(),
+        container_type: ContainerType::Service,
+        status: if running {
+            ContainerStatus::Running
+        } else {
+            ContainerStatus::Stopped
+        },
+        cpu_usage,
+        max_cpu: host.cores,
+        memory_used: parse_number(get("MemoryCurrent")).unwrap_or(0),
+        memory_max: parse_number(get("MemoryMax")).unwrap_or(host.memory_total),
+        uptime,
+        lock: None,
+        missing: false,
+        pool: None,
+        is_template: false,
+        disk_used: None,
+        disk_max: 0,
+        os_info: None,
+    }
+}
+
+fn missing_unit(provider: &str, host: &Host, unit: &str) -> Container {
+    Container {
+        id: GuestId::new(provider, unit),
+        vmid: 0,
+        name: display_name(unit).to_string(),
+        node: host.name.clone(),
+        container_type: ContainerType::Service,
+        status: ContainerStatus::Stopped,
+        cpu_usage: 0.0,
+        max_cpu: host.cores,
+        memory_used: 0,
+        memory_max: host.memory_total,
+        uptime: 0,
+        lock: None,
+        missing: true,
+        pool: None,
+        is_template: false,
+        disk_used: None,
+        disk_max: 0,
+        os_info: None,
+    }
+}
+
+/// Aggregate busy and total jiffies from the first line of /proc/stat.
+fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let values: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    if values.len() < 4 {
+        return None;
+    }
+    // Guest time is already counted in user/nice
+    let total: u64 = values.iter().take(8).sum();
+    let idle = values[3] + values.get(4).copied().unwrap_or(0);
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+    })
+}
+
+/// Busy percentage since the previous sample, or since boot on the first one.
+fn cpu_percent(previous: Option<CpuTimes>, now: CpuTimes) -> f64 {
+    let (busy, total) = match previous {
+        Some(prev) => (
+            now.busy.saturating_sub(prev.busy),
+            now.total.saturating_sub(prev.total),
+        ),
+        None => (now.busy, now.total),
+    };
+    if total == 0 {
+        0.0
+    } else {
+        busy as f64 / total as f64 * 100.0
+    }
+}
+
+/// CPU time used over a wall-clock interval, as a percentage of all host cores.
+fn unit_cpu_percent(cpu_nsec: u64, elapsed: Duration, cores: u32) -> f64 {
+    let available = elapsed.as_nanos() as f64 * cores.max(1) as f64;
+    if available > 0.0 {
+        cpu_nsec as f64 / available * 100.0
+    } else {
+        0.0
+    }
+}
+
+fn count_cores(stat: &str) -> u32 {
+    stat.lines()
+        .filter(|l| l.starts_with("cpu") && l.as_bytes().get(3).is_some_and(u8::is_ascii_digit))
+        .count() as u32
+}
+
+/// (used, total) bytes, where used excludes reclaimable cache.
+fn parse_meminfo(meminfo: &str) -> (u64, u64) {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|rest| rest.split_whitespace().next()?.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    };
+    let total = field("MemTotal").unwrap_or(0);
+    let available = field("MemAvailable")
+        .or_else(|| field("MemFree"))
+        .unwrap_or(0);
+    (total.saturating_sub(available), total)
+}
+
+fn parse_uptime(uptime: &str) -> u64 {
+    uptime
+        .split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .map(|secs| secs as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host() -> Host {
+        Host {
+            name: "nas".to_string(),
+            cores: 4,
+            memory_total: 16 * 1024 * 1024 * 1024,
+            uptime: 10_000,
+        }
+    }
+
+    const SHOW_OUTPUT: &str = "\
+Id=nginx.service
+LoadState=loaded
+ActiveState=active
+MemoryCurrent=52428800
+MemoryMax=infinity
+CPUUsageNSec=1500000000
+ActiveEnterTimestampMonotonic=4000000000
+
+Id=smbd.service
+LoadState=loaded
+ActiveState=inactive
+MemoryCurrent=[not set]
+MemoryMax=1073741824
+CPUUsageNSec=[not set]
+ActiveEnterTimestampMonotonic=0
+
+Id=wireguard.service
+LoadState=not-found
+ActiveState=inactive
+MemoryCurrent=[not set]
+MemoryMax=infinity
+CPUUsageNSec=[not set]
+ActiveEnterTimestampMonotonic=0
+";
+
+    #[test]
+    fn test_parse_properties_blocks() {
+        let blocks = parse_properties(SHOW_OUTPUT);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0]["Id"], "nginx.service");
+        assert_eq!(blocks[1]["ActiveState"], "inactive");
+        assert_eq!(blocks[2]["LoadState"], "not-found");
+    }
+
+    #[test]
+    fn test_unit_mapping() {
+        let blocks = parse_properties(SHOW_OUTPUT);
+
+        let nginx = unit_to_container("local", &host(), "nginx.service", &blocks[0], 2.5);
+        assert_eq!(nginx.id, GuestId::new("local", "nginx.service"));
+        assert_eq!(nginx.name, "nginx");
+        assert_eq!(nginx.node, "nas");
+        assert_eq!(nginx.container_type, ContainerType::Service);
+        assert_eq!(nginx.status, ContainerStatus::Running);
+        assert_eq!(nginx.cpu_usage, 2.5);
+        assert_eq!(nginx.max_cpu, 4);
+        assert_eq!(nginx.memory_used, 52_428_800);
+        // No MemoryMax: bounded by the host
+        assert_eq!(nginx.memory_max, 16 * 1024 * 1024 * 1024);
+        // Active since 4000s after boot, host up 10000s
+        assert_eq!(nginx.uptime, 6000);
+        assert!(!nginx.missing);
+
+        let smbd = unit_to_container("local", &host(), "smbd.service", &blocks[1], 0.0);
+        assert_eq!(smbd.status, ContainerStatus::Stopped);
+        assert_eq!(smbd.memory_used, 0);
+        assert_eq!(smbd.memory_max, 1_073_741_824);
+        assert_eq!(smbd.uptime, 0);
+        assert!(!smbd.missing);
+    }
+
+    #[test]
+    fn test_unknown_unit_is_missing_not_an_error() {
+        let blocks = parse_properties(SHOW_OUTPUT);
+        let wg = unit_to_container("local", &host(), "wireguard.service", &blocks[2], 0.0);
+        assert_eq!(wg.status, ContainerStatus::Stopped);
+        assert!(wg.missing);
+        assert_eq!(wg.name, "wireguard");
+    }
+
+    #[test]
+    fn test_parse_unit_list() {
+        let output = "\
+docker-web.service  loaded active running Web
+docker-db.service   loaded failed failed  DB
+";
+        assert_eq!(
+            parse_unit_list(output),
+            ["docker-web.service", "docker-db.service"]
+        );
+        assert!(parse_unit_list("").is_empty());
+        assert!(is_glob("docker-*"));
+        assert!(!is_glob("wg-quick@wg0.service"));
+    }
+
+    #[test]
+    fn test_parse_number_unset_values() {
+        assert_eq!(parse_number("1024"), Some(1024));
+        assert_eq!(parse_number("[not set]"), None);
+        assert_eq!(parse_number("infinity"), None);
+        assert_eq!(parse_number("18446744073709551615"), None);
+    }
+
+    #[test]
+    fn test_host_cpu_from_proc_stat() {
+        let first = "cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 50 0 50 350 50 0 0 0 0 0\ncpu1 50 0 50 350 50 0 0 0 0 0\nintr 1\n";
+        let second = "cpu  200 0 200 1300 100 0 0 0 0 0\n";
+
+        let a = parse_cpu_times(first).unwrap();
+        assert_eq!(
+            a,
+            CpuTimes {
+                busy: 200,
+                total: 1000
+            }
+        );
+        assert_eq!(count_cores(first), 2);
+
+        // Since boot on the first sample, then the delta
+        assert_eq!(cpu_percent(None, a), 20.0);
+        let b = parse_cpu_times(second).unwrap();
+        assert_eq!(cpu_percent(Some(a), b), 25.0);
+
+        assert!(parse_cpu_times("intr 1\n").is_none());
+    }
+
+    #[test]
+    fn test_unit_cpu_percent() {
+        // One full core for a second on a 4-core host
+        assert_eq!(
+            unit_cpu_percent(1_000_000_000, Duration::from_secs(1), 4),
+            25.0
+        );
+        assert_eq!(unit_cpu_percent(500, Duration::ZERO, 4), 0.0);
+    }
+
+    #[test]
+    fn test_parse_meminfo_and_uptime() {
+        let meminfo =
+            "MemTotal:       16000 kB\nMemFree:         1000 kB\nMemAvailable:    6000 kB\n";
+        assert_eq!(parse_meminfo(meminfo), (10_000 * 1024, 16_000 * 1024));
+        // Older kernels without MemAvailable
+        assert_eq!(
+            parse_meminfo("MemTotal: 2000 kB\nMemFree: 500 kB\n"),
+            (1500 * 1024, 2000 * 1024)
+        );
+        assert_eq!(parse_uptime("12345.67 54321.00\n"), 12345);
+        assert_eq!(parse_uptime(""), 0);
+    }
+}
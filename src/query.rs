@@ -0,0 +1,578 @@
+//! Search query language for the `/` filter.
+//!
+//! A query is a list of whitespace-separated terms that must all match:
+//!
+//! - `field:value` - substring match on `name`, `node`, `status`, `type`,
+//!   `provider`, `lock` (`lock:` alone matches any locked guest) or `os`
+//!   (only guests whose details have been fetched know theirs)
+//! - `cpu>50`, `mem<=20` - numeric comparison on CPU or memory percent, with
+//!   `>`, `>=`, `<`, `<=` or `=`
+//! - anything else matches the name
+//!
+//! Matching is case-insensitive unless `MatchOptions::case_sensitive` is
+//! set (`Ctrl+I` while searching), and with `whole_word` (`Ctrl+O`) names
+//! only match at word boundaries. A term that does not apply to an item
+//! (such as `type:` for a node) never matches it.
+
+use std::fmt;
+
+use crate::models::{Container, ContainerStatus, Node, NodeStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Name,
+    Node,
+    Status,
+    Type,
+    Provider,
+    Lock,
+    Os,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Cpu,
+    Mem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// A bare word, matched against the name.
+    Text(String),
+    Field(Field, String),
+    Compare(Metric, Op, f64),
+}
+
+/// How text terms compare against names, nodes, providers and locks;
+/// `status:` and `type:` values are always case-insensitive substrings.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MatchOptions {
+    pub case_sensitive: bool,
+    /// Match only whole words: runs of letters or of digits, split at `-`,
+    /// `_` and `.` ("DB01" is "DB" and "01").
+    pub whole_word: bool,
+}
+
+impl MatchOptions {
+    /// Whether `text` is in `haystack`. `text` is already lowercased when
+    /// matching is case-insensitive.
+    pub fn matches(self, haystack: &str, text: &str) -> bool {
+        let lowered;
+        let haystack = if self.case_sensitive {
+            haystack
+        } else {
+            lowered = haystack.to_lowercase();
+            &lowered
+        };
+        if !self.whole_word {
+            return haystack.contains(text);
+        }
+        if text.is_empty() {
+            return true;
+        }
+        haystack
+            .match_indices(text)
+            .any(|(at, _)| is_boundary(haystack, at) && is_boundary(haystack, at + text.len()))
+    }
+}
+
+/// Whether a word starts or ends at byte `at` of `s`.
+fn is_boundary(s: &str, at: usize) -> bool {
+    let (Some(before), Some(after)) = (s[..at].chars().next_back(), s[at..].chars().next()) else {
+        return true;
+    };
+    let separator = |c: char| matches!(c, '-' | '_' | '.');
+    separator(before)
+        || separator(after)
+        || before.is_alphabetic() && after.is_numeric()
+        || before.is_numeric() && after.is_alphabetic()
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+    pub options: MatchOptions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a search query. Unless `options` is case-sensitive, values are
+/// lowercased so matching can compare against lowercased item fields.
+pub fn parse_with(input: &str, options: MatchOptions) -> Result<Query, ParseError> {
+    let terms = input
+        .split_whitespace()
+        .map(|word| parse_term(word, options))
+        .collect::<Result<_, _>>()?;
+    Ok(Query { terms, options })
+}
+
+fn parse_term(original: &str, options: MatchOptions) -> Result<Term, ParseError> {
+    let word = original.to_lowercase();
+    // Field names are case-insensitive either way
+    let value = |at: usize| {
+        let value = if options.case_sensitive {
+            original
+        } else {
+            &word
+        };
+        value[at..].to_string()
+    };
+
+    // Comparisons first, so `cpu>5:00` reads as a bad number rather than a field
+    if let Some(at) = word.find(['>', '<', '=']) {
+        let (name, rest) = word.split_at(at);
+        let metric = match name {
+            "cpu" => Metric::Cpu,
+            "mem" | "memory" => Metric::Mem,
+            "" => return Err(ParseError(format!("missing field before '{}'", rest))),
+            _ if parse_field(name).is_some() => {
+                return Err(ParseError(format!(
+                    "'{}' cannot be compared; use {}:value",
+                    name, name
+                )));
+            }
+            _ => return Err(unknown_field(name)),
+        };
+        let (op, value) = if let Some(v) = rest.strip_prefix(">=") {
+            (Op::Ge, v)
+        } else if let Some(v) = rest.strip_prefix("<=") {
+            (Op::Le, v)
+        } else if let Some(v) = rest.strip_prefix('>') {
+            (Op::Gt, v)
+        } else if let Some(v) = rest.strip_prefix('<') {
+            (Op::Lt, v)
+        } else {
+            (Op::Eq, &rest[1..])
+        };
+        let number = value.strip_suffix('%').unwrap_or(value);
+        return match number.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Term::Compare(metric, op, n)),
+            _ if number.is_empty() => {
+                Err(ParseError(format!("expected a number after '{}'", word)))
+            }
+            _ => Err(ParseError(format!("'{}' is not a number", value))),
+        };
+    }
+
+    if let Some((name, rest)) = word.split_once(':') {
+        if name.is_empty() {
+            return Err(ParseError(format!("missing field before ':{}'", rest)));
+        }
+        let field = parse_field(name).ok_or_else(|| unknown_field(name))?;
+        if rest.is_empty() && field != Field::Lock {
+            return Err(ParseError(format!("missing value after '{}:'", name)));
+        }
+        let value = match field {
+            Field::Status | Field::Type => rest.to_string(),
+            _ => value(name.len() + 1),
+        };
+        return Ok(Term::Field(field, value));
+    }
+
+    Ok(Term::Text(value(0)))
+}
+
+fn parse_field(name: &str) -> Option<Field> {
+    match name {
+        "name" => Some(Field::Name),
+        "node" => Some(Field::Node),
+        "status" => Some(Field::Status),
+        "type" => Some(Field::Type),
+        "provider" => Some(Field::Provider),
+        "lock" => Some(Field::Lock),
+        "os" => Some(Field::Os),
+        _ => None,
+    }
+}
+
+fn unknown_field(name: &str) -> ParseError {
+    ParseError(format!(
+        "unknown field '{}' (name, node, status, type, provider, lock, os, cpu, mem)",
+        name
+    ))
+}
+
+impl Op {
+    fn test(self, actual: f64, wanted: f64) -> bool {
+        match self {
+            Op::Gt => actual > wanted,
+            Op::Ge => actual >= wanted,
+            Op::Lt => actual < wanted,
+            Op::Le => actual <= wanted,
+            // Percentages are shown to one decimal, so compare at that precision
+            Op::Eq => (actual - wanted).abs() < 0.05,
+        }
+    }
+}
+
+impl Query {
+    pub fn matches_node(&self, node: &Node) -> bool {
+        let text_matches = |haystack: &str, text: &str| self.options.matches(haystack, text);
+        self.terms.iter().all(|term| match term {
+            Term::Text(text) => text_matches(&node.name, text),
+            Term::Field(Field::Name | Field::Node, value) => text_matches(&node.name, value),
+            Term::Field(Field::Status, value) => node_status(node).contains(value.as_str()),
+            Term::Field(Field::Provider, value) => text_matches(node.provider(), value),
+            Term::Field(Field::Type | Field::Lock | Field::Os, _) => false,
+            Term::Compare(Metric::Cpu, op, n) => op.test(node.cpu_usage, *n),
+            Term::Compare(Metric::Mem, op, n) => op.test(node.memory_percent(), *n),
+        })
+    }
+
+    /// `cpu` is the guest's CPU percent as currently displayed (guest or host
+    /// relative), so `cpu>50` agrees with the column.
+    pub fn matches_container(&self, container: &Container, cpu: f64) -> bool {
+        let text_matches = |haystack: &str, text: &str| self.options.matches(haystack, text);
+        self.terms.iter().all(|term| match term {
+            Term::Text(text) => text_matches(&container.name, text),
+            Term::Field(Field::Name, value) => text_matches(&container.name, value),
+            Term::Field(Field::Node, value) => text_matches(&container.node, value),
+            Term::Field(Field::Status, value) => {
+                container_status(container).contains(value.as_str())
+            }
+            Term::Field(Field::Type, value) => {
+                container.type_label().to_lowercase().contains(value)
+            }
+            Term::Field(Field::Provider, value) => text_matches(container.provider(), value),
+            Term::Field(Field::Lock, value) => container
+                .lock
+                .as_ref()
+                .is_some_and(|lock| text_matches(lock, value)),
+            Term::Field(Field::Os, value) => container
+                .os_info
+                .as_ref()
+                .is_some_and(|os| text_matches(os, value)),
+            Term::Compare(Metric::Cpu, op, n) => op.test(cpu, *n),
+            Term::Compare(Metric::Mem, op, n) => op.test(container.memory_percent(), *n),
+        })
+    }
+
+    /// Whether everything this query matches is also matched by `broader`,
+    /// so filtering can start from `broader`'s results. True when each of
+    /// `broader`'s terms is kept or lengthened in place (`med` → `medi`)
+    /// and any extra terms come after them. Lengthening a whole-word term
+    /// doesn't narrow it, so only unchanged terms count then.
+    pub fn narrows(&self, broader: &Query) -> bool {
+        let lengthened = |old: &str, new: &str| {
+            if self.options.whole_word {
+                new == old
+            } else {
+                new.contains(old)
+            }
+        };
+        self.options == broader.options
+            && self.terms.len() >= broader.terms.len()
+            && broader
+                .terms
+                .iter()
+                .zip(&self.terms)
+                .all(|pair| match pair {
+                    (Term::Text(old), Term::Text(new)) => lengthened(old, new),
+                    (Term::Field(old_field, old), Term::Field(new_field, new)) => {
+                        old_field == new_field && lengthened(old, new)
+                    }
+                    (old, new) => old == new,
+                })
+    }
+}
+
+fn node_status(node: &Node) -> &'static str {
+    match node.status {
+        NodeStatus::Online => "online",
+        NodeStatus::Offline => "offline",
+    }
+}
+
+fn container_status(container: &Container) -> &'static str {
+    match container.status {
+        _ if container.missing => "missing",
+        _ if container.is_template => "template",
+        ContainerStatus::Running => "running",
+        ContainerStatus::Stopped => "stopped",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Query, ParseError> {
+        parse_with(input, MatchOptions::default())
+    }
+
+    fn node(name: &str, cpu: f64) -> Node {
+        Node {
+            cpu_usage: cpu,
+            cpu_cores: 8,
+            memory_used: 25,
+            memory_total: 100,
+            uptime: 1,
+            ..Node::test("homelab", name)
+        }
+    }
+
+    fn container(name: &str, node: &str) -> Container {
+        Container {
+            max_cpu: 2,
+            memory_used: 60,
+            memory_max: 100,
+            uptime: 1,
+            ..Container::test("homelab", 101, name, node)
+        }
+    }
+
+    fn matches(query: &str, container: &Container, cpu: f64) -> bool {
+        parse(query).unwrap().matches_container(container, cpu)
+    }
+
+    #[test]
+    fn test_parse_terms() {
+        let query = parse("node:PVE2 status:running cpu>50 Media").unwrap();
+        assert_eq!(
+            query.terms,
+            [
+                Term::Field(Field::Node, "pve2".to_string()),
+                Term::Field(Field::Status, "running".to_string()),
+                Term::Compare(Metric::Cpu, Op::Gt, 50.0),
+                Term::Text("media".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_comparisons() {
+        let cases = [
+            ("cpu>10", Metric::Cpu, Op::Gt, 10.0),
+            ("cpu>=10", Metric::Cpu, Op::Ge, 10.0),
+            ("mem<2.5", Metric::Mem, Op::Lt, 2.5),
+            ("memory<=90%", Metric::Mem, Op::Le, 90.0),
+            ("CPU=0", Metric::Cpu, Op::Eq, 0.0),
+        ];
+        for (input, metric, op, n) in cases {
+            assert_eq!(
+                parse(input).unwrap().terms,
+                [Term::Compare(metric, op, n)],
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(parse("").unwrap(), Query::default());
+        assert_eq!(parse("   ").unwrap(), Query::default());
+    }
+
+    #[test]
+    fn test_parse_lock_without_value() {
+        assert_eq!(
+            parse("lock:").unwrap().terms,
+            [Term::Field(Field::Lock, String::new())]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            (
+                "colour:red",
+                "unknown field 'colour' (name, node, status, type, provider, lock, os, cpu, mem)",
+            ),
+            (
+                "disk>5",
+                "unknown field 'disk' (name, node, status, type, provider, lock, os, cpu, mem)",
+            ),
+            ("node:", "missing value after 'node:'"),
+            (":web", "missing field before ':web'"),
+            (">50", "missing field before '>50'"),
+            ("cpu>", "expected a number after 'cpu>'"),
+            ("cpu>=", "expected a number after 'cpu>='"),
+            ("mem<lots", "'lots' is not a number"),
+            ("cpu>inf", "'inf' is not a number"),
+            ("name>3", "'name' cannot be compared; use name:value"),
+        ];
+        for (input, message) in cases {
+            assert_eq!(parse(input).unwrap_err().to_string(), message, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_first_bad_term_is_reported() {
+        let err = parse("web node: cpu>x").unwrap_err();
+        assert_eq!(err.to_string(), "missing value after 'node:'");
+    }
+
+    #[test]
+    fn test_all_terms_must_match() {
+        let media = container("media-server", "pve2");
+        assert!(matches(
+            "node:pve2 status:running cpu>50 media",
+            &media,
+            75.0
+        ));
+        assert!(!matches(
+            "node:pve2 status:running cpu>50 media",
+            &media,
+            25.0
+        ));
+        assert!(!matches("node:pve1 media", &media, 75.0));
+        assert!(!matches("media plex", &media, 75.0));
+    }
+
+    #[test]
+    fn test_container_fields() {
+        let mut ct = container("Web", "pve1");
+        assert!(matches("name:we", &ct, 0.0));
+        assert!(matches("type:lxc", &ct, 0.0));
+        assert!(!matches("type:vm", &ct, 0.0));
+        assert!(matches("provider:home", &ct, 0.0));
+        assert!(matches("status:run", &ct, 0.0));
+        assert!(!matches("status:stopped", &ct, 0.0));
+
+        ct.status = ContainerStatus::Stopped;
+        ct.missing = true;
+        assert!(matches("status:missing", &ct, 0.0));
+        assert!(!matches("status:stopped", &ct, 0.0));
+
+        ct.missing = false;
+        ct.is_template = true;
+        assert!(matches("status:template", &ct, 0.0));
+        assert!(!matches("status:stopped", &ct, 0.0));
+    }
+
+    #[test]
+    fn test_bare_word_matches_name_only() {
+        let ct = container("web", "production");
+        assert!(matches("WEB", &ct, 0.0));
+        assert!(!matches("prod", &ct, 0.0));
+        assert!(matches("node:prod", &ct, 0.0));
+    }
+
+    #[test]
+    fn test_case_sensitive_and_whole_word() {
+        let names = ["mariadb", "db-test", "DB01"];
+        let matching = |query: &str, options: MatchOptions| -> Vec<&str> {
+            let query = parse_with(query, options).unwrap();
+            names
+                .into_iter()
+                .filter(|name| query.matches_container(&container(name, "pve1"), 0.0))
+                .collect()
+        };
+        let options = |case_sensitive, whole_word| MatchOptions {
+            case_sensitive,
+            whole_word,
+        };
+
+        assert_eq!(matching("db", options(false, false)), names);
+        assert_eq!(matching("db", options(true, false)), ["mariadb", "db-test"]);
+        assert_eq!(matching("db", options(false, true)), ["db-test", "DB01"]);
+        assert_eq!(matching("db", options(true, true)), ["db-test"]);
+        assert_eq!(matching("DB", options(true, true)), ["DB01"]);
+        assert_eq!(matching("01", options(false, true)), ["DB01"]);
+        assert_eq!(matching("b-t", options(false, true)), [] as [&str; 0]);
+        assert_eq!(matching("db-test", options(false, true)), ["db-test"]);
+        // Field names stay case-insensitive; status values too
+        assert_eq!(
+            matching("NAME:DB STATUS:run", options(true, false)),
+            ["DB01"]
+        );
+    }
+
+    #[test]
+    fn test_lock_field() {
+        let mut ct = container("web", "pve1");
+        assert!(!matches("lock:", &ct, 0.0));
+        ct.lock = Some("backup".to_string());
+        assert!(matches("lock:", &ct, 0.0));
+        assert!(matches("lock:back", &ct, 0.0));
+        assert!(!matches("lock:migrate", &ct, 0.0));
+    }
+
+    #[test]
+    fn test_os_field() {
+        let mut ct = container("web", "pve1");
+        // Not known until the guest's details are fetched
+        assert!(!matches("os:debian", &ct, 0.0));
+        ct.os_info = Some("Debian GNU/Linux 12 (bookworm), kernel 6.1.0-18-amd64".to_string());
+        assert!(matches("os:debian", &ct, 0.0));
+        assert!(matches("os:bookworm", &ct, 0.0));
+        assert!(!matches("os:alpine", &ct, 0.0));
+        assert!(matches!(parse("os:"), Err(ParseError(_))));
+    }
+
+    #[test]
+    fn test_numeric_comparisons() {
+        let ct = container("web", "pve1");
+        // memory is 60%
+        assert!(matches("mem>50", &ct, 0.0));
+        assert!(matches("mem>=60", &ct, 0.0));
+        assert!(matches("mem=60", &ct, 0.0));
+        assert!(!matches("mem<60", &ct, 0.0));
+        assert!(matches("mem<=60", &ct, 0.0));
+        assert!(matches("cpu=12.5", &ct, 12.52));
+        assert!(!matches("cpu=12.5", &ct, 12.6));
+    }
+
+    #[test]
+    fn test_node_matching() {
+        let pve2 = node("pve2", 80.0);
+        let query = |q: &str| parse(q).unwrap().matches_node(&pve2);
+        assert!(query("pve"));
+        assert!(query("node:pve2"));
+        assert!(query("name:PVE2"));
+        assert!(query("status:online cpu>50 mem<50"));
+        assert!(!query("status:offline"));
+        assert!(!query("cpu<50"));
+        // Guest-only fields never match nodes
+        assert!(!query("type:vm"));
+        assert!(!query("lock:"));
+        assert!(!query("os:linux"));
+    }
+
+    #[test]
+    fn test_whole_word_lengthening_does_not_narrow() {
+        let whole_word = MatchOptions {
+            whole_word: true,
+            ..MatchOptions::default()
+        };
+        let med = parse_with("med", whole_word).unwrap();
+        assert!(!parse_with("medi", whole_word).unwrap().narrows(&med));
+        assert!(parse_with("med web", whole_word).unwrap().narrows(&med));
+        // Nor does changing the options
+        assert!(!parse("med").unwrap().narrows(&med));
+    }
+
+    #[test]
+    fn test_narrowing() {
+        let narrows = |new: &str, old: &str| parse(new).unwrap().narrows(&parse(old).unwrap());
+        assert!(narrows("med", ""));
+        assert!(narrows("medi", "med"));
+        assert!(narrows("node:pve2", "node:pve"));
+        assert!(narrows("med status:r", "med"));
+        assert!(narrows("lock:backup", "lock:"));
+        // A longer number is not a narrower comparison
+        assert!(!narrows("cpu<50", "cpu<5"));
+        assert!(!narrows("med", "medi"));
+        assert!(!narrows("name:med", "med"));
+        assert!(!narrows("", "med"));
+    }
+}
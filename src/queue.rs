@@ -0,0 +1,252 @@
+//! Bulk actions on the marked guests. The guests are queued, confirmed, and
+//! then worked through a few at a time (`[general] action_concurrency`, one
+//! by default) so a node that is already struggling doesn't get every API
+//! call at once. `App::advance_queue` starts and polls the tasks; the queue
+//! only holds ids, so it carries on across refreshes.
+
+use std::time::Instant;
+
+use crate::models::{Container, ContainerStatus, ContainerType, GuestId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuedAction {
+    Start,
+    Shutdown,
+}
+
+impl QueuedAction {
+    /// For titles and the audit log.
+    pub fn describe(self) -> &'static str {
+        match self {
+            QueuedAction::Start => "start",
+            QueuedAction::Shutdown => "shutdown",
+        }
+    }
+
+    /// Whether the action makes sense for `guest` as it was last listed.
+    /// Locked guests are left out: the provider would refuse the call.
+    pub fn applies_to(self, guest: &Container) -> bool {
+        let actionable = matches!(guest.container_type, ContainerType::VM | ContainerType::LXC)
+            && !guest.is_template
+            && !guest.missing
+            && guest.lock_message().is_none();
+        let wanted = match self {
+            QueuedAction::Start => ContainerStatus::Stopped,
+            QueuedAction::Shutdown => ContainerStatus::Running,
+        };
+        actionable && guest.status == wanted
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemState {
+    Pending,
+    /// Task started on the provider, polled until it finishes.
+    Running {
+        task: String,
+    },
+    Done,
+    Failed(String),
+    /// Taken off the queue before it started.
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueItem {
+    pub guest: GuestId,
+    /// Node the guest is on, which runs its task.
+    pub node: String,
+    /// "105 web"
+    pub label: String,
+    pub state: ItemState,
+    /// When the running task was last polled.
+    pub last_poll: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueuePhase {
+    /// Listed, waiting for the user to confirm.
+    Confirm,
+    Running,
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActionQueue {
+    pub action: QueuedAction,
+    pub items: Vec<QueueItem>,
+    pub phase: QueuePhase,
+    /// Tasks allowed to run at once.
+    pub concurrency: usize,
+    /// Failed items show their errors (`Enter` on the summary).
+    pub show_details: bool,
+}
+
+impl ActionQueue {
+    pub fn new(action: QueuedAction, guests: &[&Container], concurrency: usize) -> Self {
+        Self {
+            action,
+            items: guests
+                .iter()
+                .map(|g| QueueItem {
+                    guest: g.id.clone(),
+                    node: g.node.clone(),
+                    label: format!("{} {}", g.vmid, g.name),
+                    state: ItemState::Pending,
+                    last_poll: None,
+                })
+                .collect(),
+            phase: QueuePhase::Confirm,
+            concurrency: concurrency.max(1),
+            show_details: false,
+        }
+    }
+
+    /// Pending items to start now, in order, as far as free slots allow.
+    pub fn startable(&self) -> Vec<usize> {
+        let running = self
+            .items
+            .iter()
+            .filter(|i| matches!(i.state, ItemState::Running { .. }))
+            .count();
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.state == ItemState::Pending)
+            .map(|(index, _)| index)
+            .take(self.concurrency.saturating_sub(running))
+            .collect()
+    }
+
+    /// Cancel what hasn't started; running tasks finish. Returns how many
+    /// items were taken off.
+    pub fn cancel_pending(&mut self) -> usize {
+        let mut cancelled = 0;
+        for item in &mut self.items {
+            if item.state == ItemState::Pending {
+                item.state = ItemState::Cancelled;
+                cancelled += 1;
+            }
+        }
+        self.finish_if_done();
+        cancelled
+    }
+
+    /// Move to `Finished` once nothing is pending or running.
+    pub fn finish_if_done(&mut self) {
+        let busy = self
+            .items
+            .iter()
+            .any(|i| matches!(i.state, ItemState::Pending | ItemState::Running { .. }));
+        if self.phase == QueuePhase::Running && !busy {
+            self.phase = QueuePhase::Finished;
+        }
+    }
+
+    /// "6 ok, 2 failed", with any cancelled items after.
+    pub fn summary(&self) -> String {
+        let count = |f: fn(&ItemState) -> bool| self.items.iter().filter(|i| f(&i.state)).count();
+        let mut summary = format!(
+            "{} ok, {} failed",
+            count(|s| *s == ItemState::Done),
+            count(|s| matches!(s, ItemState::Failed(_)))
+        );
+        let cancelled = count(|s| *s == ItemState::Cancelled);
+        if cancelled > 0 {
+            summary.push_str(&format!(", {} cancelled", cancelled));
+        }
+        summary
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.items
+            .iter()
+            .any(|i| matches!(i.state, ItemState::Failed(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest(vmid: u32) -> Container {
+        Container {
+            uptime: 1,
+            ..Container::test("homelab", vmid, &format!("ct{}", vmid), "pve1")
+        }
+    }
+
+    /// A confirmed shutdown of `n` guests.
+    fn queue(n: u32, concurrency: usize) -> ActionQueue {
+        let guests: Vec<Container> = (100..100 + n).map(guest).collect();
+        let refs: Vec<&Container> = guests.iter().collect();
+        let mut queue = ActionQueue::new(QueuedAction::Shutdown, &refs, concurrency);
+        queue.phase = QueuePhase::Running;
+        queue
+    }
+
+    fn running(task: &str) -> ItemState {
+        ItemState::Running {
+            task: task.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_startable_respects_concurrency() {
+        let mut queue = queue(4, 2);
+        assert_eq!(queue.startable(), [0, 1]);
+        queue.items[0].state = running("a");
+        assert_eq!(queue.startable(), [1]);
+        queue.items[1].state = running("b");
+        assert!(queue.startable().is_empty());
+        queue.items[0].state = ItemState::Failed("x".to_string());
+        assert_eq!(queue.startable(), [2]);
+
+        // Zero is taken as one at a time
+        assert_eq!(self::queue(3, 0).startable(), [0]);
+    }
+
+    #[test]
+    fn test_cancel_leaves_running_items_alone() {
+        let mut queue = queue(3, 1);
+        queue.items[0].state = running("a");
+        assert_eq!(queue.cancel_pending(), 2);
+        assert_eq!(queue.phase, QueuePhase::Running);
+        assert!(queue.startable().is_empty());
+
+        queue.items[0].state = ItemState::Done;
+        queue.finish_if_done();
+        assert_eq!(queue.phase, QueuePhase::Finished);
+        assert_eq!(queue.summary(), "1 ok, 0 failed, 2 cancelled");
+    }
+
+    #[test]
+    fn test_summary_counts_outcomes() {
+        let mut queue = queue(3, 1);
+        queue.items[0].state = ItemState::Done;
+        queue.items[1].state = ItemState::Failed("locked".to_string());
+        queue.items[2].state = ItemState::Done;
+        assert_eq!(queue.summary(), "2 ok, 1 failed");
+        assert!(queue.has_failures());
+    }
+
+    #[test]
+    fn test_action_applies_to_guests_in_the_right_state() {
+        let mut guest = guest(100);
+        assert!(QueuedAction::Shutdown.applies_to(&guest));
+        assert!(!QueuedAction::Start.applies_to(&guest));
+        guest.status = ContainerStatus::Stopped;
+        assert!(QueuedAction::Start.applies_to(&guest));
+        guest.missing = true;
+        assert!(!QueuedAction::Start.applies_to(&guest));
+        guest.missing = false;
+        guest.is_template = true;
+        assert!(!QueuedAction::Start.applies_to(&guest));
+        guest.is_template = false;
+        guest.lock = Some("backup".to_string());
+        assert!(!QueuedAction::Start.applies_to(&guest));
+        guest.lock = None;
+        guest.container_type = ContainerType::Service;
+        assert!(!QueuedAction::Start.applies_to(&guest));
+    }
+}
@@ -0,0 +1,812 @@
+//! The provider list built from the config, and live reloads of both (on
+//! SIGHUP or `Ctrl+e`). A reload runs on the main loop between refreshes, so
+//! no refresh is ever in flight against a provider being replaced.
+//!
+//! Providers left out of the startup selection (`--provider`,
+//! `[general] default_providers` or the picker) are not built at all: a
+//! stand-in keeps their place until they are turned on in the providers
+//! popup, so a config with dozens of clusters starts as fast as one.
+
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use crate::app::{self, App};
+use crate::config::{
+    self, CommandConfig, Config, LocalConfig, ProxmoxConfig, PulseAgentConfig, SystemdConfig,
+};
+use crate::drain::DrainPhase;
+use crate::models::{Container, Node};
+use crate::providers::{
+    CommandProvider, LocalProvider, Provider, ProxmoxProvider, PulseAgentProvider, SharedProvider,
+    SystemdProvider,
+};
+
+/// Which configured providers to build at startup; the rest stay dormant.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Selection {
+    #[default]
+    All,
+    Only(Vec<String>),
+}
+
+impl Selection {
+    /// `--provider` wins over `[general] default_providers`; with neither,
+    /// every provider starts.
+    pub fn choose(flag: &[String], default: Option<&[String]>) -> Self {
+        let names: Vec<String> = if flag.is_empty() {
+            default.unwrap_or_default().to_vec()
+        } else {
+            flag.to_vec()
+        };
+        let names: Vec<String> = names
+            .into_iter()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+        if names.is_empty() {
+            Selection::All
+        } else {
+            Selection::Only(names)
+        }
+    }
+
+    pub fn includes(&self, name: &str) -> bool {
+        match self {
+            Selection::All => true,
+            Selection::Only(names) => names.iter().any(|n| n == name),
+        }
+    }
+
+    /// An error naming the first selected provider that isn't configured.
+    pub fn check(&self, configured: &[String]) -> Result<(), String> {
+        let Selection::Only(names) = self else {
+            return Ok(());
+        };
+        match names.iter().find(|name| !configured.contains(name)) {
+            Some(name) => Err(format!(
+                "no provider named '{}'; configured: {}",
+                name,
+                configured.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Stands in for a provider left out at startup, keeping its place in the
+/// list. Never polled: the app starts it disabled and asks for it to be
+/// built once it is turned on.
+struct Dormant {
+    name: String,
+}
+
+impl Provider for Dormant {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        Err("not started".into())
+    }
+
+    fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+        Err("not started".into())
+    }
+}
+
+/// The provider the UI calls, or a stand-in named `name` for a dormant one.
+fn boxed(provider: Option<SharedProvider>, name: &str) -> Box<dyn Provider> {
+    match provider {
+        Some(provider) => Box::new(provider),
+        None => Box::new(Dormant {
+            name: name.to_string(),
+        }),
+    }
+}
+
+/// The config section a provider was built from.
+#[derive(Debug, Clone, PartialEq)]
+enum Section {
+    Proxmox(Box<ProxmoxConfig>),
+    Systemd(SystemdConfig),
+    Local(LocalConfig),
+    PulseAgent(PulseAgentConfig),
+    Command(CommandConfig),
+}
+
+impl Section {
+    fn name(&self) -> &str {
+        match self {
+            Section::Proxmox(c) => &c.name,
+            Section::Systemd(c) => &c.name,
+            Section::Local(c) => &c.name,
+            Section::PulseAgent(c) => &c.name,
+            Section::Command(c) => &c.name,
+        }
+    }
+
+    fn build(&self) -> Result<SharedProvider, String> {
+        let provider: SharedProvider = match self {
+            Section::Proxmox(c) => Arc::new(ProxmoxProvider::new(c).map_err(|e| e.to_string())?),
+            Section::Systemd(c) => Arc::new(SystemdProvider::new(c)),
+            Section::Local(c) => Arc::new(LocalProvider::new(c)),
+            Section::PulseAgent(c) => {
+                Arc::new(PulseAgentProvider::new(c).map_err(|e| e.to_string())?)
+            }
+            Section::Command(c) => Arc::new(CommandProvider::new(c).map_err(|e| e.to_string())?),
+        };
+        Ok(provider)
+    }
+}
+
+/// Every provider section of a config, in the order providers are shown.
+/// `local` adds this machine unless the config already covers it; read-only
+/// and dry-run mode are passed down so providers refuse or fake actions
+/// themselves too.
+fn sections(config: &Config, local: bool, read_only: bool, dry_run: bool) -> Vec<Section> {
+    let providers = &config.providers;
+    let mut sections: Vec<Section> = Vec::new();
+    sections.extend(providers.proxmox.iter().flatten().map(|c| {
+        Section::Proxmox(Box::new(ProxmoxConfig {
+            read_only,
+            dry_run,
+            ..c.clone()
+        }))
+    }));
+    sections.extend(
+        providers
+            .systemd
+            .iter()
+            .flatten()
+            .cloned()
+            .map(Section::Systemd),
+    );
+    sections.extend(
+        providers
+            .pulse_agent
+            .iter()
+            .flatten()
+            .cloned()
+            .map(Section::PulseAgent),
+    );
+    sections.extend(
+        providers
+            .command
+            .iter()
+            .flatten()
+            .cloned()
+            .map(Section::Command),
+    );
+    sections.extend(
+        providers
+            .local
+            .iter()
+            .flatten()
+            .cloned()
+            .map(Section::Local),
+    );
+    if local && providers.local.is_none() {
+        sections.push(Section::Local(LocalConfig::default()));
+    }
+    sections
+}
+
+/// The name of every provider a config has, in the order they are shown.
+pub fn provider_names(config: &Config, local: bool) -> Vec<String> {
+    sections(config, local, false, false)
+        .iter()
+        .map(|section| section.name().to_string())
+        .collect()
+}
+
+/// The running providers, each with the section it was built from.
+pub struct ProviderSet {
+    providers: Vec<Box<dyn Provider>>,
+    /// The same providers, for the first load's thread; `None` for the
+    /// stand-ins of dormant ones.
+    shared: Vec<Option<SharedProvider>>,
+    sections: Vec<Section>,
+    /// Providers not built yet; a `Dormant` holds each one's place.
+    dormant: Vec<String>,
+}
+
+impl Deref for ProviderSet {
+    type Target = [Box<dyn Provider>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.providers
+    }
+}
+
+/// What a reload did to the provider list.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProviderChanges {
+    pub added: usize,
+    /// Same name, different settings: rebuilt.
+    pub changed: usize,
+    pub removed: usize,
+}
+
+impl ProviderChanges {
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = [
+            (self.added, "added"),
+            (self.changed, "changed"),
+            (self.removed, "removed"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+        if parts.is_empty() {
+            "Config reloaded".to_string()
+        } else {
+            format!("Config reloaded; providers {}", parts.join(", "))
+        }
+    }
+}
+
+impl ProviderSet {
+    /// Every selected provider that could be built, and why the others
+    /// couldn't. Those not selected are left dormant.
+    pub fn build(
+        config: &Config,
+        local: bool,
+        read_only: bool,
+        dry_run: bool,
+        selection: &Selection,
+    ) -> (Self, Vec<String>) {
+        let mut set = ProviderSet {
+            providers: Vec::new(),
+            shared: Vec::new(),
+            sections: Vec::new(),
+            dormant: Vec::new(),
+        };
+        let mut errors = Vec::new();
+        for section in sections(config, local, read_only, dry_run) {
+            if !selection.includes(section.name()) {
+                set.push(None, section.name());
+                set.dormant.push(section.name().to_string());
+                set.sections.push(section);
+                continue;
+            }
+            match section.build() {
+                Ok(provider) => {
+                    set.push(Some(provider), section.name());
+                    set.sections.push(section);
+                }
+                Err(e) => errors.push(format!(
+                    "Failed to create provider '{}': {}",
+                    section.name(),
+                    e
+                )),
+            }
+        }
+        (set, errors)
+    }
+
+    /// Providers that don't come from the config, as when attached to a
+    /// daemon: `rebuild` is never called on them.
+    pub fn fixed(providers: Vec<SharedProvider>) -> Self {
+        let mut set = ProviderSet {
+            providers: Vec::new(),
+            shared: Vec::new(),
+            sections: Vec::new(),
+            dormant: Vec::new(),
+        };
+        for provider in providers {
+            let name = provider.name().to_string();
+            set.push(Some(provider), &name);
+        }
+        set
+    }
+
+    /// Add `provider`, or a stand-in named `name` for a dormant one.
+    fn push(&mut self, provider: Option<SharedProvider>, name: &str) {
+        self.providers.push(boxed(provider.clone(), name));
+        self.shared.push(provider);
+    }
+
+    /// Every provider that has been built, for the first load.
+    pub fn shared(&self) -> Vec<SharedProvider> {
+        self.shared.iter().flatten().cloned().collect()
+    }
+
+    /// Providers left out at startup and not turned on since.
+    pub fn dormant(&self) -> &[String] {
+        &self.dormant
+    }
+
+    /// Build a dormant provider in its place.
+    pub fn start(&mut self, name: &str) -> Result<(), String> {
+        let index = self
+            .sections
+            .iter()
+            .position(|s| s.name() == name)
+            .filter(|_| self.dormant.iter().any(|d| d == name))
+            .ok_or_else(|| format!("no dormant provider named '{}'", name))?;
+        let provider = self.sections[index].build()?;
+        self.providers[index] = boxed(Some(provider.clone()), name);
+        self.shared[index] = Some(provider);
+        self.dormant.retain(|d| d != name);
+        Ok(())
+    }
+
+    /// Switch to the providers of a new config. Unchanged sections keep
+    /// their provider, with its caches, tunnel and CPU samples; the rest are
+    /// built new and the old ones dropped. Dormant providers stay dormant,
+    /// even when their settings change. If any fails to build, nothing
+    /// changes.
+    pub fn rebuild(
+        &mut self,
+        config: &Config,
+        local: bool,
+        read_only: bool,
+        dry_run: bool,
+    ) -> Result<ProviderChanges, String> {
+        let sections = sections(config, local, read_only, dry_run);
+        if sections.is_empty() {
+            return Err("no providers configured".to_string());
+        }
+
+        let mut kept: Vec<Option<usize>> = Vec::new();
+        for section in &sections {
+            let previous = (0..self.sections.len())
+                .find(|&i| self.sections[i] == *section && !kept.contains(&Some(i)));
+            kept.push(previous);
+        }
+        let mut built: Vec<Option<SharedProvider>> = Vec::new();
+        for (section, previous) in sections.iter().zip(&kept) {
+            if previous.is_some() {
+                continue;
+            }
+            if self.dormant.iter().any(|d| d == section.name()) {
+                built.push(None);
+                continue;
+            }
+            let provider = section
+                .build()
+                .map_err(|e| format!("provider '{}': {}", section.name(), e))?;
+            built.push(Some(provider));
+        }
+
+        let changed = sections
+            .iter()
+            .zip(&kept)
+            .filter(|(section, previous)| {
+                previous.is_none() && self.sections.iter().any(|s| s.name() == section.name())
+            })
+            .count();
+        let reused = kept.iter().flatten().count();
+        let changes = ProviderChanges {
+            added: built.len() - changed,
+            changed,
+            removed: self.sections.len() - reused - changed,
+        };
+
+        self.providers.clear();
+        let mut old: Vec<Option<Option<SharedProvider>>> =
+            self.shared.drain(..).map(Some).collect();
+        let mut built = built.into_iter();
+        for (section, previous) in sections.iter().zip(&kept) {
+            let provider = match previous {
+                Some(i) => old[*i].take().expect("each provider is kept once"),
+                None => built.next().expect("one provider built per new section"),
+            };
+            self.push(provider, section.name());
+        }
+        self.dormant
+            .retain(|name| sections.iter().any(|s| s.name() == name));
+        self.sections = sections;
+        Ok(changes)
+    }
+}
+
+/// Build the dormant providers that were turned on in the providers popup
+/// since the last call. One that fails to build is turned off again.
+pub fn start_enabled(app: &mut App, providers: &mut ProviderSet) {
+    for name in app.providers_to_start() {
+        match providers.start(&name) {
+            Ok(()) => {
+                log::info!("{}: started", name);
+                app.provider_started(&name);
+                if let Some(index) = providers.iter().position(|p| p.name() == name) {
+                    app.probe_permissions(&providers[index..=index]);
+                }
+            }
+            Err(e) => app.provider_failed_to_start(&name, e),
+        }
+    }
+}
+
+/// Re-read the config at `path` and apply it: providers, thresholds, theme,
+/// refresh rate and the other settings `App::apply_config` covers. Runtime
+/// UI state (selection, filters, sorting) stays. On any error the old config
+/// stays in effect. Returns the notice to show.
+pub fn reload(
+    path: &Path,
+    local: bool,
+    read_only_flag: bool,
+    dry_run_flag: bool,
+    app: &mut App,
+    providers: &mut ProviderSet,
+) -> Result<String, String> {
+    if app
+        .drain
+        .as_ref()
+        .is_some_and(|d| d.phase == DrainPhase::Running)
+    {
+        return Err("a drain is running; reload once it has finished".to_string());
+    }
+    let config = config::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    app::check_config(&config)?;
+    let read_only = read_only_flag || config.general.read_only;
+    let dry_run = dry_run_flag || config.general.dry_run;
+    let changes = providers.rebuild(&config, local, read_only, dry_run)?;
+    app.apply_config(&config)?;
+    app.read_only = read_only;
+    app.dry_run = dry_run;
+    Ok(changes.summary())
+}
+
+/// Reload for a UI attached to a daemon: its providers are the daemon's,
+/// which reloads its own config, so only the other settings are re-read.
+pub fn reload_settings(
+    path: &Path,
+    read_only_flag: bool,
+    dry_run_flag: bool,
+    app: &mut App,
+) -> Result<String, String> {
+    let config = config::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    app::check_config(&config)?;
+    app.apply_config(&config)?;
+    app.read_only = read_only_flag || config.general.read_only;
+    app.dry_run = dry_run_flag || config.general.dry_run;
+    Ok("Config reloaded; providers come from the daemon".to_string())
+}
+
+/// A flag raised whenever the process receives SIGHUP; the main loop swaps
+/// it back and reloads. Never raised on other platforms.
+#[cfg(unix)]
+pub fn watch_hangup() -> std::io::Result<Arc<AtomicBool>> {
+    use std::sync::atomic::Ordering;
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let flag = Arc::new(AtomicBool::new(false));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+    // Registered before returning, so an early SIGHUP no longer terminates pulse
+    let mut hangup = {
+        let _context = runtime.enter();
+        signal(SignalKind::hangup())?
+    };
+    let raised = flag.clone();
+    std::thread::spawn(move || {
+        runtime.block_on(async {
+            while hangup.recv().await.is_some() {
+                raised.store(true, Ordering::Relaxed);
+            }
+        })
+    });
+    Ok(flag)
+}
+
+#[cfg(not(unix))]
+pub fn watch_hangup() -> std::io::Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(&format!("[general]\nrefresh_rate = \"5s\"\n{}", toml)).unwrap()
+    }
+
+    const TWO_LOCALS: &str = r#"
+[[providers.local]]
+name = "laptop"
+
+[[providers.systemd]]
+name = "units"
+units = ["nginx"]
+"#;
+
+    fn names(set: &ProviderSet) -> Vec<&str> {
+        set.iter().map(|p| p.name()).collect()
+    }
+
+    #[test]
+    fn test_build_orders_sections() {
+        let (set, errors) =
+            ProviderSet::build(&config(TWO_LOCALS), true, false, false, &Selection::All);
+        assert!(errors.is_empty());
+        // --local adds nothing when the config has a local provider
+        assert_eq!(names(&set), ["units", "laptop"]);
+
+        let (set, _) = ProviderSet::build(
+            &config("[providers]\n"),
+            true,
+            false,
+            false,
+            &Selection::All,
+        );
+        assert_eq!(names(&set), ["local"]);
+    }
+
+    #[test]
+    fn test_command_providers_come_after_agents() {
+        let toml = r#"
+[[providers.command]]
+name = "ups"
+command = "/usr/local/bin/nut-to-pulse"
+
+[[providers.pulse_agent]]
+name = "nas"
+url = "http://nas:9898"
+
+[[providers.command]]
+name = "printer"
+command = "printer-stats --json"
+timeout = "forever"
+"#;
+        let (set, errors) = ProviderSet::build(&config(toml), false, false, false, &Selection::All);
+        assert_eq!(names(&set), ["nas", "ups"]);
+        assert_eq!(
+            errors,
+            ["Failed to create provider 'printer': timeout: invalid duration 'forever'"]
+        );
+    }
+
+    #[test]
+    fn test_rebuild_keeps_unchanged_providers() {
+        let (mut set, _) =
+            ProviderSet::build(&config(TWO_LOCALS), false, false, false, &Selection::All);
+        let laptop = Arc::as_ptr(set.shared[1].as_ref().unwrap()) as *const ();
+
+        let edited = r#"
+[[providers.local]]
+name = "laptop"
+
+[[providers.systemd]]
+name = "units"
+units = ["nginx", "smbd"]
+
+[[providers.pulse_agent]]
+name = "nas"
+url = "http://nas:9898"
+"#;
+        let changes = set.rebuild(&config(edited), false, false, false).unwrap();
+        assert_eq!(
+            changes,
+            ProviderChanges {
+                added: 1,
+                changed: 1,
+                removed: 0,
+            }
+        );
+        assert_eq!(names(&set), ["units", "nas", "laptop"]);
+        // The same provider object, not a new one
+        assert_eq!(
+            Arc::as_ptr(set.shared[2].as_ref().unwrap()) as *const (),
+            laptop
+        );
+        assert_eq!(
+            changes.summary(),
+            "Config reloaded; providers 1 added, 1 changed"
+        );
+
+        let changes = set
+            .rebuild(
+                &config("[[providers.local]]\nname = \"laptop\"\n"),
+                false,
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(changes.removed, 2);
+        assert_eq!(names(&set), ["laptop"]);
+    }
+
+    #[test]
+    fn test_failed_rebuild_changes_nothing() {
+        let (mut set, _) =
+            ProviderSet::build(&config(TWO_LOCALS), false, false, false, &Selection::All);
+        let broken = r#"
+[[providers.proxmox]]
+name = "pve"
+host = "https://pve:8006"
+user = "root@pam"
+token_id = "pulse"
+"#;
+        let error = set
+            .rebuild(&config(broken), false, false, false)
+            .unwrap_err();
+        assert!(error.starts_with("provider 'pve': "), "{}", error);
+        assert_eq!(names(&set), ["units", "laptop"]);
+
+        assert_eq!(
+            set.rebuild(&config("[providers]\n"), false, false, false)
+                .unwrap_err(),
+            "no providers configured"
+        );
+        assert_eq!(names(&set), ["units", "laptop"]);
+    }
+
+    fn only(names: &[&str]) -> Selection {
+        Selection::Only(names.iter().map(|n| n.to_string()).collect())
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn is_dormant(provider: &dyn Provider) -> bool {
+        matches!(provider.fetch_nodes(), Err(e) if e.to_string() == "not started")
+    }
+
+    #[test]
+    fn test_selection_prefers_the_flag() {
+        let default = strings(&["office"]);
+        assert_eq!(
+            Selection::choose(&strings(&["homelab"]), Some(&default)),
+            only(&["homelab"])
+        );
+        assert_eq!(Selection::choose(&[], Some(&default)), only(&["office"]));
+        assert_eq!(Selection::choose(&[], None), Selection::All);
+        // `--provider ""` or an empty list select nothing in particular
+        assert_eq!(Selection::choose(&strings(&[""]), None), Selection::All);
+        assert_eq!(Selection::choose(&[], Some(&[])), Selection::All);
+
+        assert!(only(&["homelab"]).includes("homelab"));
+        assert!(!only(&["homelab"]).includes("office"));
+        assert!(Selection::All.includes("office"));
+    }
+
+    #[test]
+    fn test_selection_must_name_configured_providers() {
+        let configured = provider_names(&config(TWO_LOCALS), false);
+        assert_eq!(configured, ["units", "laptop"]);
+        assert_eq!(only(&["laptop"]).check(&configured), Ok(()));
+        assert_eq!(Selection::All.check(&configured), Ok(()));
+        assert_eq!(
+            only(&["laptop", "nas"]).check(&configured),
+            Err("no provider named 'nas'; configured: units, laptop".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unselected_providers_start_when_turned_on() {
+        let (mut set, errors) =
+            ProviderSet::build(&config(TWO_LOCALS), false, false, false, &only(&["laptop"]));
+        assert!(errors.is_empty());
+        // Still listed, in order, but not built
+        assert_eq!(names(&set), ["units", "laptop"]);
+        assert_eq!(set.dormant(), ["units"]);
+        assert!(is_dormant(&*set[0]));
+        assert!(!is_dormant(&*set[1]));
+
+        let mut app = App::new();
+        app.mark_dormant(&set, set.dormant());
+        let statuses: Vec<(&str, bool, bool)> = app
+            .provider_status
+            .iter()
+            .map(|s| (s.name.as_str(), s.dormant, s.disabled))
+            .collect();
+        assert_eq!(statuses, [("units", true, true), ("laptop", false, false)]);
+        // Never polled while dormant
+        app.refresh(&set);
+        assert!(app.provider_status[0].last_error.is_none());
+
+        // Nothing starts until it is turned on
+        start_enabled(&mut app, &mut set);
+        assert_eq!(set.dormant(), ["units"]);
+
+        app.providers_popup = Some(0);
+        app.toggle_selected_provider();
+        assert_eq!(app.providers_to_start(), ["units"]);
+        start_enabled(&mut app, &mut set);
+        assert!(set.dormant().is_empty());
+        assert!(!is_dormant(&*set[0]));
+        assert!(!app.provider_status[0].dormant);
+        assert!(!app.provider_status[0].disabled);
+        assert!(app.providers_to_start().is_empty());
+        assert_eq!(app.notice.as_deref(), Some("units started, refreshing"));
+    }
+
+    #[test]
+    fn test_dormant_providers_stay_dormant_across_reloads() {
+        let (mut set, _) =
+            ProviderSet::build(&config(TWO_LOCALS), false, false, false, &only(&["laptop"]));
+        // Changed settings, and now impossible to build: nothing is built
+        let edited = r#"
+[[providers.proxmox]]
+name = "units"
+host = "https://pve:8006"
+user = "root@pam"
+token_id = "pulse"
+
+[[providers.local]]
+name = "laptop"
+"#;
+        let changes = set.rebuild(&config(edited), false, false, false).unwrap();
+        assert_eq!(changes.changed, 1);
+        assert_eq!(set.dormant(), ["units"]);
+        assert!(is_dormant(&*set[0]));
+
+        let mut app = App::new();
+        app.mark_dormant(&set, set.dormant());
+        app.providers_popup = Some(0);
+        app.toggle_selected_provider();
+        start_enabled(&mut app, &mut set);
+        // Turned off again, with the reason in the error log
+        assert_eq!(set.dormant(), ["units"]);
+        assert!(app.provider_status[0].dormant);
+        assert!(app.provider_status[0].disabled);
+        assert!(
+            app.error_log[0].message.starts_with("failed to start: "),
+            "{}",
+            app.error_log[0].message
+        );
+
+        // Gone from the config, gone from the dormant list
+        set.rebuild(
+            &config("[[providers.local]]\nname = \"laptop\"\n"),
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(set.dormant().is_empty());
+        assert_eq!(
+            set.start("units"),
+            Err("no dormant provider named 'units'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reload_keeps_old_config_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!("[general]\nrefresh_rate = \"5s\"\n{}", TWO_LOCALS),
+        )
+        .unwrap();
+        let (mut set, _) = ProviderSet::build(
+            &config::load(&path).unwrap(),
+            false,
+            false,
+            false,
+            &Selection::All,
+        );
+        let mut app = App::new();
+        app.cpu_mode = config::CpuMode::Host;
+
+        let edited = format!(
+            "[general]\nrefresh_rate = \"30s\"\n[thresholds]\ncpu_critical = 50.0\n{}",
+            TWO_LOCALS
+        );
+        std::fs::write(&path, edited).unwrap();
+        assert_eq!(
+            reload(&path, false, false, false, &mut app, &mut set),
+            Ok("Config reloaded".to_string())
+        );
+        assert_eq!(app.refresh_interval, std::time::Duration::from_secs(30));
+        assert_eq!(app.thresholds.cpu_critical, 50.0);
+        // Runtime toggles survive
+        assert_eq!(app.cpu_mode, config::CpuMode::Host);
+
+        std::fs::write(&path, "[general]\nrefresh_rate = \"soon\"\n[providers]\n").unwrap();
+        assert_eq!(
+            reload(&path, false, false, false, &mut app, &mut set),
+            Err("general.refresh_rate: invalid duration 'soon'".to_string())
+        );
+        assert_eq!(app.refresh_interval, std::time::Duration::from_secs(30));
+        assert_eq!(names(&set), ["units", "laptop"]);
+    }
+}
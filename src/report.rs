@@ -0,0 +1,390 @@
+//! Standalone HTML report (`pulse report`): node and guest tables with usage
+//! bars and totals, in one file with inline styles and no scripts, so it can
+//! be mailed or archived as is.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::app::App;
+use crate::format::{Units, format_bytes, format_uptime};
+use crate::models::{
+    Container, ContainerStatus, ContainerType, Node, NodeStatus, format_timestamp,
+};
+
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{title}}</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 4px 10px; border-bottom: 1px solid #ddd; text-align: left; white-space: nowrap; }
+th { background: #f4f4f4; }
+td.num { text-align: right; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<p>Generated {{generated}} UTC</p>
+{{body}}
+</body>
+</html>
+"#;
+
+/// Average and peak use of an item over the report period, from the history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(feature = "history"), allow(dead_code))]
+pub struct Usage {
+    pub cpu_avg: f64,
+    pub cpu_peak: f64,
+    pub mem_avg: f64,
+    pub mem_peak: u64,
+}
+
+/// Usage keyed the way the history stores items: provider, kind ("node" or
+/// "guest") and id (node name or VMID).
+pub type UsageByItem = HashMap<(String, String, String), Usage>;
+
+/// The report for the current view. `usage` adds average and peak columns;
+/// without it they are left out.
+pub fn render(app: &App, generated: i64, usage: &UsageByItem) -> String {
+    let mut body = String::new();
+    body.push_str(&totals(app));
+    body.push_str(&node_table(app, usage));
+    body.push_str(&guest_table(app, usage));
+    fill(
+        TEMPLATE,
+        &[
+            ("title", escape("Pulse report")),
+            ("generated", escape(&format_timestamp(generated))),
+            ("body", body),
+        ],
+    )
+}
+
+/// Replace each `{{key}}` in `template` with its value, which must already
+/// be escaped.
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |out, (key, value)| {
+            out.replace(&format!("{{{{{}}}}}", key), value)
+        })
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn totals(app: &App) -> String {
+    let nodes = app.filtered_nodes();
+    let guests = app.filtered_containers();
+    let online = nodes
+        .iter()
+        .filter(|n| n.status == NodeStatus::Online)
+        .count();
+    let cores: u32 = nodes.iter().map(|n| n.cpu_cores).sum();
+    let memory_used: u64 = nodes.iter().map(|n| n.memory_used).sum();
+    let memory_total: u64 = nodes.iter().map(|n| n.memory_total).sum();
+    let running = guests
+        .iter()
+        .filter(|c| c.status == ContainerStatus::Running)
+        .count();
+    let allocated_cores: u32 = guests.iter().map(|c| c.max_cpu).sum();
+    let allocated_memory: u64 = guests.iter().map(|c| c.memory_max).sum();
+
+    let mut out = String::from("<h2>Totals</h2>\n<table class=\"totals\">\n");
+    let mut row = |label: &str, value: String| {
+        let _ = writeln!(
+            out,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            label,
+            escape(&value)
+        );
+    };
+    row("Nodes online", format!("{} / {}", online, nodes.len()));
+    row("Node cores", cores.to_string());
+    row(
+        "Node memory",
+        format!(
+            "{} / {}",
+            format_bytes(memory_used, app.units),
+            format_bytes(memory_total, app.units)
+        ),
+    );
+    row("Guests running", format!("{} / {}", running, guests.len()));
+    row("Allocated cores", allocated_cores.to_string());
+    row(
+        "Allocated memory",
+        format_bytes(allocated_memory, app.units),
+    );
+    out.push_str("</table>\n");
+    out
+}
+
+fn node_table(app: &App, usage: &UsageByItem) -> String {
+    let with_usage = !usage.is_empty();
+    let mut out = String::from("<h2>Nodes</h2>\n<table class=\"nodes\">\n<tr>");
+    for heading in ["Name", "Status", "CPU", "Memory", "Uptime"] {
+        let _ = write!(out, "<th>{}</th>", heading);
+    }
+    if with_usage {
+        out.push_str(USAGE_HEADINGS);
+    }
+    out.push_str("</tr>\n");
+
+    for node in app.filtered_nodes() {
+        let status = match node.status {
+            NodeStatus::Online => "online",
+            NodeStatus::Offline => "offline",
+        };
+        let _ = write!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td>",
+            escape(&node.name),
+            status,
+            bar(node.cpu_usage, &format!("{:.1}%", node.cpu_usage)),
+            bar(
+                node.memory_percent(),
+                &format!(
+                    "{} / {}",
+                    format_bytes(node.memory_used, app.units),
+                    format_bytes(node.memory_total, app.units)
+                )
+            ),
+            escape(&format_uptime(node.uptime)),
+        );
+        if with_usage {
+            out.push_str(&usage_cells(usage.get(&node_key(node)), app.units));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+/// Guests, largest memory first.
+fn guest_table(app: &App, usage: &UsageByItem) -> String {
+    let with_usage = !usage.is_empty();
+    let mut out = String::from("<h2>Guests</h2>\n<table class=\"guests\">\n<tr>");
+    for heading in [
+        "VMID", "Name", "Type", "Node", "Status", "CPU", "Memory", "Uptime",
+    ] {
+        let _ = write!(out, "<th>{}</th>", heading);
+    }
+    if with_usage {
+        out.push_str(USAGE_HEADINGS);
+    }
+    out.push_str("</tr>\n");
+
+    let mut guests = app.filtered_containers();
+    guests.sort_by_key(|c| std::cmp::Reverse(c.memory_used));
+    for guest in guests {
+        let status = match guest.status {
+            _ if guest.missing => "missing",
+            _ if guest.is_template => "template",
+            ContainerStatus::Running => "running",
+            ContainerStatus::Stopped => "stopped",
+        };
+        let vmid = match guest.container_type {
+            ContainerType::Service | ContainerType::Docker => "-".to_string(),
+            _ => guest.vmid.to_string(),
+        };
+        let cpu = app.container_cpu(guest);
+        let _ = write!(
+            out,
+            "<tr><td class=\"num\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td>",
+            vmid,
+            escape(&guest.name),
+            guest.type_label(),
+            escape(&guest.node),
+            status,
+            bar(cpu, &format!("{:.1}%", cpu)),
+            bar(
+                guest.memory_percent(),
+                &format!(
+                    "{} / {}",
+                    format_bytes(guest.memory_used, app.units),
+                    format_bytes(guest.memory_max, app.units)
+                )
+            ),
+            escape(&format_uptime(guest.uptime)),
+        );
+        if with_usage {
+            out.push_str(&usage_cells(usage.get(&guest_key(guest)), app.units));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+const USAGE_HEADINGS: &str =
+    "<th>CPU avg</th><th>CPU peak</th><th>Memory avg</th><th>Memory peak</th>";
+
+fn usage_cells(usage: Option<&Usage>, units: Units) -> String {
+    match usage {
+        Some(u) => format!(
+            "<td class=\"num\">{:.1}%</td><td class=\"num\">{:.1}%</td><td class=\"num\">{}</td><td class=\"num\">{}</td>",
+            u.cpu_avg,
+            u.cpu_peak,
+            format_bytes(u.mem_avg as u64, units),
+            format_bytes(u.mem_peak, units)
+        ),
+        None => "<td></td><td></td><td></td><td></td>".to_string(),
+    }
+}
+
+fn node_key(node: &Node) -> (String, String, String) {
+    (
+        node.provider().to_string(),
+        "node".to_string(),
+        node.id.name.clone(),
+    )
+}
+
+fn guest_key(guest: &Container) -> (String, String, String) {
+    (
+        guest.provider().to_string(),
+        "guest".to_string(),
+        guest.vmid.to_string(),
+    )
+}
+
+/// A usage bar drawn with inline styles, colored like the UI's gauges, with
+/// `label` beside it.
+fn bar(percent: f64, label: &str) -> String {
+    let color = if percent >= 90.0 {
+        "#d9534f"
+    } else if percent >= 70.0 {
+        "#f0ad4e"
+    } else {
+        "#5cb85c"
+    };
+    format!(
+        "<div style=\"display:inline-block;width:120px;height:10px;background:#eee;margin-right:6px\"><div style=\"width:{:.1}%;height:100%;background:{}\"></div></div>{}",
+        percent.clamp(0.0, 100.0),
+        color,
+        escape(label)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str) -> Node {
+        Node {
+            cpu_usage: 95.0,
+            cpu_cores: 8,
+            memory_used: 4 * 1024 * 1024 * 1024,
+            memory_total: 8 * 1024 * 1024 * 1024,
+            uptime: 3600,
+            ..Node::test("homelab", name)
+        }
+    }
+
+    fn guest(vmid: u32, name: &str, memory_used: u64) -> Container {
+        Container {
+            cpu_usage: 10.0,
+            max_cpu: 2,
+            memory_used,
+            memory_max: 2048,
+            uptime: 60,
+            ..Container::test("homelab", vmid, name, "pve1")
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.nodes = vec![node("pve1"), node("pve2")];
+        app.containers = vec![
+            guest(101, "small", 100),
+            guest(102, "<script>alert('x')</script>", 1500),
+            guest(103, "medium & co", 700),
+        ];
+        app
+    }
+
+    /// The text of each `<tr>` in the table with this class.
+    fn rows<'a>(html: &'a str, class: &str) -> Vec<&'a str> {
+        let start = html
+            .find(&format!("<table class=\"{}\">", class))
+            .expect("table present");
+        let table = &html[start..start + html[start..].find("</table>").unwrap()];
+        table.split("<tr>").skip(1).collect()
+    }
+
+    #[test]
+    fn test_report_tables_and_escaping() {
+        let html = render(&app(), 1_700_000_000, &UsageByItem::new());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Generated 2023-11-14 22:13:20 UTC"));
+        assert!(!html.contains("{{"));
+        assert!(!html.contains("<script"));
+        // Self-contained: no external assets
+        assert!(!html.contains("src=") && !html.contains("href="));
+
+        assert_eq!(rows(&html, "nodes").len(), 3);
+        let guests = rows(&html, "guests");
+        assert_eq!(guests.len(), 4);
+        // Largest memory first, names escaped
+        assert!(guests[1].contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"));
+        assert!(guests[2].contains("medium &amp; co"));
+        assert!(guests[3].contains("small"));
+        assert!(!guests[0].contains("CPU avg"));
+
+        let totals = rows(&html, "totals");
+        assert!(totals[0].contains("2 / 2"));
+        assert!(totals[3].contains("3 / 3"));
+    }
+
+    #[test]
+    fn test_usage_columns_from_history() {
+        let mut usage = UsageByItem::new();
+        usage.insert(
+            (
+                "homelab".to_string(),
+                "guest".to_string(),
+                "103".to_string(),
+            ),
+            Usage {
+                cpu_avg: 12.5,
+                cpu_peak: 80.0,
+                mem_avg: 512.0,
+                mem_peak: 1024,
+            },
+        );
+        let html = render(&app(), 0, &usage);
+
+        let guests = rows(&html, "guests");
+        assert!(guests[0].contains("<th>CPU avg</th>"));
+        assert!(guests[2].contains("12.5%</td><td class=\"num\">80.0%"));
+        // Items without history get empty cells, not a shorter row
+        assert_eq!(
+            guests[1].matches("<td").count(),
+            guests[2].matches("<td").count()
+        );
+    }
+
+    #[test]
+    fn test_bar_is_clamped_and_colored() {
+        assert!(bar(150.0, "x").contains("width:100.0%;height:100%;background:#d9534f"));
+        assert!(bar(75.0, "x").contains("background:#f0ad4e"));
+        assert!(bar(-5.0, "a<b").contains("width:0.0%"));
+        assert!(bar(10.0, "a<b").ends_with("a&lt;b"));
+    }
+}
@@ -0,0 +1,339 @@
+//! Turning what someone typed into one guest, for `pulse guest` and goto.
+//!
+//! Within the guests a target may name, an exact VMID wins, then an exact
+//! name (ignoring case), then a name the target is the only prefix of. The
+//! first rule with any match decides: a VMID never loses to a name that
+//! happens to be all digits, and a full name never to a longer one it is a
+//! prefix of. More than one match under the deciding rule is an error
+//! listing them, never a guess.
+//!
+//! A target may be narrowed to a provider, a node, or both:
+//! `homelab/105`, `pve2:web-01`, `homelab/pve2:web-01`. A qualifier only
+//! counts when it names a provider or node some guest is on, so process
+//! names like `kworker/0:1` still resolve as they are.
+
+use std::fmt;
+
+use crate::models::Container;
+
+/// Candidates an ambiguity error lists before it only counts the rest.
+const LISTED_CANDIDATES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// No guest matches under any rule.
+    NotFound { target: String },
+    /// Several guests match under the deciding rule; labelled "web-01 (pve1)".
+    Ambiguous {
+        target: String,
+        candidates: Vec<String>,
+    },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::NotFound { target } => write!(f, "nothing named '{}'", target),
+            ResolveError::Ambiguous { target, candidates } => {
+                write!(f, "'{}' matches ", target)?;
+                let listed = candidates.len().min(LISTED_CANDIDATES);
+                write!(f, "{}", candidates[..listed].join(", "))?;
+                if candidates.len() > listed {
+                    write!(f, " and {} more", candidates.len() - listed)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// A target split into its qualifiers and what is left to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target<'a> {
+    pub provider: Option<&'a str>,
+    pub node: Option<&'a str>,
+    pub name: &'a str,
+}
+
+impl<'a> Target<'a> {
+    /// Split off the qualifiers of `target` that name a provider or node
+    /// of `guests`; anything else is left in the name.
+    pub fn parse(target: &'a str, guests: &[Container]) -> Self {
+        let mut parsed = Target {
+            provider: None,
+            node: None,
+            name: target,
+        };
+        if let Some((provider, rest)) = target.split_once('/')
+            && guests.iter().any(|g| g.provider() == provider)
+        {
+            parsed.provider = Some(provider);
+            parsed.name = rest;
+        }
+        if let Some((node, rest)) = parsed.name.split_once(':')
+            && guests.iter().any(|g| parsed.allows(g) && g.node == node)
+        {
+            parsed.node = Some(node);
+            parsed.name = rest;
+        }
+        parsed
+    }
+
+    /// Whether `guest` is within the qualifiers.
+    fn allows(&self, guest: &Container) -> bool {
+        self.provider.is_none_or(|p| guest.provider() == p)
+            && self.node.is_none_or(|n| guest.node == n)
+    }
+}
+
+/// The one guest of `guests` that `target` names.
+pub fn guest<'a>(guests: &'a [Container], target: &str) -> Result<&'a Container, ResolveError> {
+    resolve(guests, target, true)
+}
+
+/// Like `guest`, without prefixes: for goto, where a node's full name
+/// comes before a guest's prefix.
+pub fn exact_guest<'a>(
+    guests: &'a [Container],
+    target: &str,
+) -> Result<&'a Container, ResolveError> {
+    resolve(guests, target, false)
+}
+
+fn resolve<'a>(
+    guests: &'a [Container],
+    target: &str,
+    prefixes: bool,
+) -> Result<&'a Container, ResolveError> {
+    let parsed = Target::parse(target, guests);
+    let scope: Vec<&Container> = guests.iter().filter(|g| parsed.allows(g)).collect();
+    let name = parsed.name.to_lowercase();
+    let vmid = parsed.name.parse::<u32>().ok();
+    let rules: [&dyn Fn(&Container) -> bool; 3] = [
+        &|g| Some(g.vmid) == vmid,
+        &|g| g.name.to_lowercase() == name,
+        &|g| prefixes && !name.is_empty() && g.name.to_lowercase().starts_with(&name),
+    ];
+    for rule in rules {
+        let matches: Vec<&Container> = scope.iter().copied().filter(|g| rule(g)).collect();
+        match matches.as_slice() {
+            [] => continue,
+            [guest] => return Ok(guest),
+            _ => {
+                return Err(ResolveError::Ambiguous {
+                    target: target.to_string(),
+                    candidates: labels(&matches),
+                });
+            }
+        }
+    }
+    Err(ResolveError::NotFound {
+        target: target.to_string(),
+    })
+}
+
+/// "web-01 (pve1)", with the provider too when the matches span several,
+/// written the way a qualified target would be.
+fn labels(matches: &[&Container]) -> Vec<String> {
+    let providers = matches
+        .iter()
+        .any(|g| g.provider() != matches[0].provider());
+    matches
+        .iter()
+        .map(|g| {
+            if providers {
+                format!("{} ({}/{})", g.name, g.provider(), g.node)
+            } else {
+                format!("{} ({})", g.name, g.node)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ContainerType;
+
+    fn guest_on(provider: &str, node: &str, vmid: u32, name: &str) -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            cpu_usage: 1.0,
+            max_cpu: 2,
+            memory_used: 512,
+            memory_max: 1024,
+            uptime: 60,
+            ..Container::test(provider, vmid, name, node)
+        }
+    }
+
+    /// Two providers; `homelab` has two nodes.
+    fn guests() -> Vec<Container> {
+        vec![
+            guest_on("homelab", "pve1", 101, "web-01"),
+            guest_on("homelab", "pve2", 102, "web-02"),
+            guest_on("homelab", "pve2", 105, "db"),
+            guest_on("homelab", "pve1", 110, "105"),
+            guest_on("office", "pve1", 105, "mail"),
+            guest_on("office", "pve1", 200, "dbbackup"),
+        ]
+    }
+
+    fn resolve(target: &str) -> Result<String, ResolveError> {
+        let guests = guests();
+        guest(&guests, target).map(|g| g.id.to_string())
+    }
+
+    fn candidates(target: &str) -> Vec<String> {
+        match resolve(target) {
+            Err(ResolveError::Ambiguous { candidates, .. }) => candidates,
+            other => panic!("{} not ambiguous: {:?}", target, other),
+        }
+    }
+
+    #[test]
+    fn test_vmid_beats_name_beats_prefix() {
+        assert_eq!(resolve("101").unwrap(), "homelab/101");
+        // Guest 110 is named "105", but VMID 105 exists, twice
+        assert_eq!(
+            candidates("105"),
+            ["db (homelab/pve2)", "mail (office/pve1)"]
+        );
+        assert_eq!(resolve("homelab/105").unwrap(), "homelab/105");
+        assert_eq!(resolve("110").unwrap(), "homelab/110");
+
+        // "db" is a whole name, though also a prefix of "dbbackup"
+        assert_eq!(resolve("db").unwrap(), "homelab/105");
+        assert_eq!(resolve("DB").unwrap(), "homelab/105");
+        assert_eq!(resolve("dbb").unwrap(), "office/200");
+        assert_eq!(resolve("mai").unwrap(), "office/105");
+        assert!(matches!(
+            exact_guest(&guests(), "mai"),
+            Err(ResolveError::NotFound { .. })
+        ));
+        assert_eq!(exact_guest(&guests(), "db").unwrap().vmid, 105);
+    }
+
+    #[test]
+    fn test_digit_names_match_once_no_vmid_does() {
+        // "105" as a name, once VMIDs are out of the way
+        let mut guests = guests();
+        guests.retain(|g| g.vmid != 105);
+        assert_eq!(guest(&guests, "105").unwrap().vmid, 110);
+        // Nor is a VMID matched by prefix
+        assert!(matches!(
+            guest(&guests, "20"),
+            Err(ResolveError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_ambiguous_prefix_lists_candidates() {
+        let error = guest(&guests(), "web").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'web' matches web-01 (pve1), web-02 (pve2)"
+        );
+
+        let many: Vec<Container> = (1..=8)
+            .map(|i| guest_on("homelab", "pve1", 100 + i, &format!("web-{:02}", i)))
+            .collect();
+        assert_eq!(
+            guest(&many, "web").unwrap_err().to_string(),
+            "'web' matches web-01 (pve1), web-02 (pve1), web-03 (pve1), web-04 (pve1), \
+             web-05 (pve1) and 3 more"
+        );
+    }
+
+    #[test]
+    fn test_nothing_matching_is_not_found() {
+        assert_eq!(
+            resolve("cache").unwrap_err().to_string(),
+            "nothing named 'cache'"
+        );
+        assert!(matches!(resolve(""), Err(ResolveError::NotFound { .. })));
+        // The qualifiers hold even when the name exists elsewhere
+        assert!(matches!(
+            resolve("office/web"),
+            Err(ResolveError::NotFound { .. })
+        ));
+        assert!(matches!(
+            resolve("pve2:mail"),
+            Err(ResolveError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_qualifiers_narrow_the_search() {
+        assert_eq!(resolve("office/105").unwrap(), "office/105");
+        assert_eq!(resolve("pve2:web").unwrap(), "homelab/102");
+        assert_eq!(resolve("pve2:db").unwrap(), "homelab/105");
+        assert_eq!(resolve("homelab/pve1:web").unwrap(), "homelab/101");
+        assert_eq!(resolve("pve1:db").unwrap(), "office/200");
+        // pve1 is in both providers: office's VMID 105 wins over homelab's
+        // guest named "105" there, unless homelab is named too
+        assert_eq!(resolve("pve1:105").unwrap(), "office/105");
+        assert_eq!(resolve("homelab/pve1:105").unwrap(), "homelab/110");
+    }
+
+    #[test]
+    fn test_qualifier_parsing() {
+        let guests = guests();
+        let parse = |target| Target::parse(target, &guests);
+        assert_eq!(
+            parse("homelab/105"),
+            Target {
+                provider: Some("homelab"),
+                node: None,
+                name: "105",
+            }
+        );
+        assert_eq!(
+            parse("pve2:web-01"),
+            Target {
+                provider: None,
+                node: Some("pve2"),
+                name: "web-01",
+            }
+        );
+        assert_eq!(
+            parse("homelab/pve2:db"),
+            Target {
+                provider: Some("homelab"),
+                node: Some("pve2"),
+                name: "db",
+            }
+        );
+        // office has no pve2, so that part stays in the name
+        assert_eq!(
+            parse("office/pve2:db"),
+            Target {
+                provider: Some("office"),
+                node: None,
+                name: "pve2:db",
+            }
+        );
+        // Neither an unknown provider nor an unknown node is a qualifier
+        for target in ["kworker/0:1", "nas:backup", "web-01"] {
+            assert_eq!(
+                parse(target),
+                Target {
+                    provider: None,
+                    node: None,
+                    name: target,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_names_that_look_qualified_still_resolve() {
+        let mut guests = guests();
+        guests.push(guest_on("local", "nas", 42, "kworker/0:1"));
+        assert_eq!(guest(&guests, "kworker/0:1").unwrap().vmid, 42);
+        assert_eq!(guest(&guests, "kworker/0").unwrap().vmid, 42);
+        assert_eq!(guest(&guests, "local/nas:kworker/0:1").unwrap().vmid, 42);
+    }
+}
@@ -0,0 +1,455 @@
+//! Changing a guest's cores and memory: the form the `M` popup edits,
+//! prefilled from the guest's config, and what a change will take to apply.
+//!
+//! Containers take new limits at once. A running VM only takes memory live
+//! when it has memory hot-plug (and NUMA) enabled, and never takes cores:
+//! Proxmox keeps such changes pending until the VM restarts, which the form
+//! warns about before anything is sent.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Container, ContainerStatus, ContainerType, GuestId, Node};
+
+/// Proxmox refuses less memory than this for any guest.
+const MIN_MEMORY_MB: u64 = 16;
+/// Cores allowed when the node's count is unknown.
+const MAX_CORES: u32 = 8192;
+
+/// A guest's cores and memory as configured, read by
+/// `Provider::fetch_guest_resources`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuestResources {
+    /// Cores per socket; `None` for a container without a limit, which may
+    /// use every core of its node.
+    pub cores: Option<u32>,
+    /// A VM gets `cores × sockets` vCPUs; 1 for containers.
+    pub sockets: u32,
+    pub memory_mb: u64,
+    /// The least a VM's balloon may shrink it to, which memory can't go
+    /// below.
+    pub balloon_mb: Option<u64>,
+    /// A running VM can take new memory: hot-plug is on for memory and NUMA
+    /// is enabled.
+    pub memory_hotplug: bool,
+}
+
+/// What `Provider::set_guest_resources` is asked to change; unchanged
+/// values are `None` and left out of the call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceRequest {
+    pub cores: Option<u32>,
+    pub memory_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceField {
+    Cores,
+    Memory,
+}
+
+impl ResourceField {
+    const ALL: [ResourceField; 2] = [ResourceField::Cores, ResourceField::Memory];
+
+    fn index(self) -> usize {
+        ResourceField::ALL
+            .iter()
+            .position(|&f| f == self)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourcePhase {
+    /// The form is open for input.
+    Editing,
+    /// The config was changed; with the reason when it waits for a restart.
+    Saved {
+        pending: Option<String>,
+    },
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ResourceForm {
+    pub guest: GuestId,
+    /// "105 web", for the title and the audit log.
+    pub label: String,
+    pub container_type: ContainerType,
+    pub running: bool,
+    /// As read when the form opened.
+    pub current: GuestResources,
+    /// Cores and MiB of memory of the guest's node, the most it can be
+    /// given; 0 when unknown.
+    pub node_cores: u32,
+    pub node_memory_mb: u64,
+    pub cores: String,
+    pub memory: String,
+    pub field: ResourceField,
+    /// Why the form can't be submitted as it is.
+    pub error: Option<String>,
+    pub phase: ResourcePhase,
+}
+
+impl ResourceForm {
+    /// A form for `guest` on `node`, prefilled with `current`. Memory
+    /// is focused, as the value most often changed in a hurry.
+    pub fn new(guest: &Container, node: Option<&Node>, current: GuestResources) -> Self {
+        ResourceForm {
+            guest: guest.id.clone(),
+            label: format!("{} {}", guest.vmid, guest.name),
+            container_type: guest.container_type.clone(),
+            running: guest.status == ContainerStatus::Running,
+            node_cores: node.map_or(0, |n| n.cpu_cores),
+            node_memory_mb: node.map_or(0, |n| n.memory_total >> 20),
+            cores: current.cores.map(|c| c.to_string()).unwrap_or_default(),
+            memory: current.memory_mb.to_string(),
+            current,
+            field: ResourceField::Memory,
+            error: None,
+            phase: ResourcePhase::Editing,
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        self.field = ResourceField::ALL[(self.field.index() + 1) % ResourceField::ALL.len()];
+    }
+
+    pub fn previous_field(&mut self) {
+        let count = ResourceField::ALL.len();
+        self.field = ResourceField::ALL[(self.field.index() + count - 1) % count];
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        match self.field {
+            ResourceField::Cores => self.cores.push(c),
+            ResourceField::Memory => self.memory.push(c),
+        }
+        self.error = None;
+    }
+
+    pub fn pop_char(&mut self) {
+        match self.field {
+            ResourceField::Cores => self.cores.pop(),
+            ResourceField::Memory => self.memory.pop(),
+        };
+        self.error = None;
+    }
+
+    /// Cores per socket the node has room for.
+    fn max_cores(&self) -> u32 {
+        match self.node_cores {
+            0 => MAX_CORES,
+            cores => (cores / self.current.sockets.max(1)).max(1),
+        }
+    }
+
+    /// The change the form describes, or why it can't be sent. An empty
+    /// cores field keeps a container without a limit.
+    pub fn validate(&self) -> Result<ResourceRequest, String> {
+        let cores = match self.cores.trim() {
+            "" if self.current.cores.is_none() => None,
+            text => {
+                let max = self.max_cores();
+                Some(
+                    text.parse::<u32>()
+                        .ok()
+                        .filter(|c| (1..=max).contains(c))
+                        .ok_or_else(|| format!("cores must be a number from 1 to {}", max))?,
+                )
+            }
+        };
+        let memory = parse_memory(&self.memory)
+            .ok_or("memory must be in MiB, or GiB with a G (4096 or 4G)")?;
+        if memory < MIN_MEMORY_MB || (self.node_memory_mb > 0 && memory > self.node_memory_mb) {
+            return Err(match self.node_memory_mb {
+                0 => format!("memory must be at least {} MiB", MIN_MEMORY_MB),
+                max => format!("memory must be from {} to {} MiB", MIN_MEMORY_MB, max),
+            });
+        }
+        if let Some(balloon) = self.current.balloon_mb.filter(|&b| memory < b) {
+            return Err(format!(
+                "memory can't go below the balloon minimum of {} MiB",
+                balloon
+            ));
+        }
+        let request = ResourceRequest {
+            cores: cores.filter(|&c| Some(c) != self.current.cores),
+            memory_mb: Some(memory).filter(|&m| m != self.current.memory_mb),
+        };
+        if request.cores.is_none() && request.memory_mb.is_none() {
+            return Err("nothing changed".to_string());
+        }
+        Ok(request)
+    }
+
+    /// Why `request` won't apply until the guest restarts, if it won't.
+    pub fn pending(&self, request: &ResourceRequest) -> Option<String> {
+        if !self.running || self.container_type != ContainerType::VM {
+            return None;
+        }
+        // Proxmox hot-plugs vCPUs, never cores
+        let mut waiting = Vec::new();
+        if request.cores.is_some() {
+            waiting.push("cores");
+        }
+        if request.memory_mb.is_some() && !self.current.memory_hotplug {
+            waiting.push("memory");
+        }
+        (!waiting.is_empty()).then(|| {
+            format!(
+                "{} will only apply once the VM restarts",
+                waiting.join(" and ")
+            )
+        })
+    }
+
+    /// The restart warning for what is typed so far, shown while editing.
+    pub fn warning(&self) -> Option<String> {
+        self.validate()
+            .ok()
+            .and_then(|request| self.pending(&request))
+    }
+
+    /// "cores 2 → 4, memory 2048 → 4096 MiB", for the audit log.
+    pub fn describe(&self, request: &ResourceRequest) -> String {
+        let mut changes = Vec::new();
+        if let Some(cores) = request.cores {
+            let before = self
+                .current
+                .cores
+                .map_or("all".to_string(), |c| c.to_string());
+            changes.push(format!("cores {} → {}", before, cores));
+        }
+        if let Some(memory) = request.memory_mb {
+            changes.push(format!(
+                "memory {} → {} MiB",
+                self.current.memory_mb, memory
+            ));
+        }
+        changes.join(", ")
+    }
+}
+
+/// MiB from "4096", "4096M" or "4G" (any case, with an optional "iB").
+fn parse_memory(text: &str) -> Option<u64> {
+    let text = text.trim().to_ascii_lowercase();
+    let text = text.strip_suffix("ib").unwrap_or(&text);
+    let (number, scale) = match text.strip_suffix('g') {
+        Some(number) => (number, 1024),
+        None => (text.strip_suffix('m').unwrap_or(text), 1),
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest(container_type: ContainerType, status: ContainerStatus) -> Container {
+        Container {
+            container_type,
+            status,
+            max_cpu: 2,
+            memory_max: 2048 << 20,
+            uptime: 60,
+            ..Container::test("homelab", 105, "web", "pve1")
+        }
+    }
+
+    fn node() -> Node {
+        Node {
+            cpu_cores: 8,
+            memory_total: 32 << 30,
+            uptime: 3600,
+            ..Node::test("homelab", "pve1")
+        }
+    }
+
+    fn resources(cores: Option<u32>) -> GuestResources {
+        GuestResources {
+            cores,
+            sockets: 1,
+            memory_mb: 2048,
+            balloon_mb: None,
+            memory_hotplug: false,
+        }
+    }
+
+    fn form(container_type: ContainerType, current: GuestResources) -> ResourceForm {
+        let guest = guest(container_type, ContainerStatus::Running);
+        ResourceForm::new(&guest, Some(&node()), current)
+    }
+
+    #[test]
+    fn test_new_form_is_prefilled() {
+        let form = form(ContainerType::LXC, resources(Some(2)));
+        assert_eq!(form.label, "105 web");
+        assert_eq!((form.cores.as_str(), form.memory.as_str()), ("2", "2048"));
+        assert_eq!((form.node_cores, form.node_memory_mb), (8, 32768));
+        assert_eq!(form.field, ResourceField::Memory);
+        assert!(form.running);
+
+        // A container without a limit starts with an empty cores field
+        assert_eq!(form_without_limit().cores, "");
+    }
+
+    fn form_without_limit() -> ResourceForm {
+        form(ContainerType::LXC, resources(None))
+    }
+
+    #[test]
+    fn test_editing_follows_the_focused_field() {
+        let mut form = form(ContainerType::LXC, resources(Some(2)));
+        form.error = Some("nothing changed".to_string());
+        form.pop_char();
+        form.pop_char();
+        form.push_char('9');
+        form.push_char('6');
+        assert_eq!(form.memory, "2096");
+        assert_eq!(form.error, None);
+
+        form.next_field();
+        assert_eq!(form.field, ResourceField::Cores);
+        form.pop_char();
+        form.push_char('4');
+        assert_eq!(form.cores, "4");
+        form.next_field();
+        assert_eq!(form.field, ResourceField::Memory);
+        form.previous_field();
+        assert_eq!(form.field, ResourceField::Cores);
+    }
+
+    #[test]
+    fn test_validate_sends_only_what_changed() {
+        let mut form = form(ContainerType::LXC, resources(Some(2)));
+        assert_eq!(form.validate().unwrap_err(), "nothing changed");
+
+        form.memory = "4G".to_string();
+        let request = form.validate().unwrap();
+        assert_eq!(
+            request,
+            ResourceRequest {
+                cores: None,
+                memory_mb: Some(4096),
+            }
+        );
+        assert_eq!(form.describe(&request), "memory 2048 → 4096 MiB");
+
+        form.cores = "4".to_string();
+        let request = form.validate().unwrap();
+        assert_eq!(request.cores, Some(4));
+        assert_eq!(
+            form.describe(&request),
+            "cores 2 → 4, memory 2048 → 4096 MiB"
+        );
+
+        // Setting a limit on a container without one
+        let mut form = form_without_limit();
+        assert_eq!(form.validate().unwrap_err(), "nothing changed");
+        form.cores = "2".to_string();
+        let request = form.validate().unwrap();
+        assert_eq!(form.describe(&request), "cores all → 2");
+    }
+
+    #[test]
+    fn test_validate_refuses_what_the_node_cannot_give() {
+        let mut form = form(ContainerType::VM, resources(Some(2)));
+        for cores in ["0", "9", "two", ""] {
+            form.cores = cores.to_string();
+            assert_eq!(
+                form.validate().unwrap_err(),
+                "cores must be a number from 1 to 8"
+            );
+        }
+        // Two sockets halve the cores per socket
+        form.current.sockets = 2;
+        form.cores = "5".to_string();
+        assert_eq!(
+            form.validate().unwrap_err(),
+            "cores must be a number from 1 to 4"
+        );
+
+        form.cores = "2".to_string();
+        form.memory = "8".to_string();
+        assert_eq!(
+            form.validate().unwrap_err(),
+            "memory must be from 16 to 32768 MiB"
+        );
+        form.memory = "64G".to_string();
+        assert!(form.validate().is_err());
+        form.memory = "lots".to_string();
+        assert_eq!(
+            form.validate().unwrap_err(),
+            "memory must be in MiB, or GiB with a G (4096 or 4G)"
+        );
+
+        form.current.balloon_mb = Some(1024);
+        form.memory = "512".to_string();
+        assert_eq!(
+            form.validate().unwrap_err(),
+            "memory can't go below the balloon minimum of 1024 MiB"
+        );
+        form.memory = "1024".to_string();
+        assert!(form.validate().is_ok());
+
+        // Without the node's figures only the floor applies
+        form.node_cores = 0;
+        form.node_memory_mb = 0;
+        form.cores = "64".to_string();
+        form.memory = "1T".to_string();
+        assert!(form.validate().is_err());
+        form.memory = "128G".to_string();
+        assert_eq!(
+            form.validate().unwrap(),
+            ResourceRequest {
+                cores: Some(64),
+                memory_mb: Some(131_072),
+            }
+        );
+    }
+
+    #[test]
+    fn test_running_vms_warn_about_what_waits_for_a_restart() {
+        let mut vm = form(ContainerType::VM, resources(Some(2)));
+        vm.memory = "4096".to_string();
+        assert_eq!(
+            vm.warning().as_deref(),
+            Some("memory will only apply once the VM restarts")
+        );
+        vm.cores = "4".to_string();
+        assert_eq!(
+            vm.warning().as_deref(),
+            Some("cores and memory will only apply once the VM restarts")
+        );
+        vm.current.memory_hotplug = true;
+        assert_eq!(
+            vm.warning().as_deref(),
+            Some("cores will only apply once the VM restarts")
+        );
+        vm.cores = "2".to_string();
+        assert_eq!(vm.warning(), None);
+
+        // Stopped VMs pick everything up when started
+        vm.current.memory_hotplug = false;
+        vm.running = false;
+        vm.cores = "4".to_string();
+        assert_eq!(vm.warning(), None);
+
+        // Containers apply both at once
+        let mut lxc = form(ContainerType::LXC, resources(Some(2)));
+        lxc.cores = "4".to_string();
+        lxc.memory = "4096".to_string();
+        assert_eq!(lxc.warning(), None);
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("4096"), Some(4096));
+        assert_eq!(parse_memory(" 4096M "), Some(4096));
+        assert_eq!(parse_memory("4g"), Some(4096));
+        assert_eq!(parse_memory("4 GiB"), Some(4096));
+        assert_eq!(parse_memory("512MiB"), Some(512));
+        assert_eq!(parse_memory("1.5G"), None);
+        assert_eq!(parse_memory(""), None);
+    }
+}
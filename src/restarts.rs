@@ -0,0 +1,238 @@
+//! Restart detection: an item whose uptime went down between two refreshes
+//! was restarted (or, for a node, rebooted) in between.
+//!
+//! Counts live only as long as pulse runs.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::{DurationStyle, TimeUnit, format_duration};
+use crate::models::{Container, ContainerStatus, Node, NodeStatus};
+
+/// Restarts this recent are highlighted.
+pub const RECENT_WINDOW_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestartCount {
+    pub count: u32,
+    /// Unix seconds of the first and latest restart seen.
+    pub first: u64,
+    pub last: u64,
+}
+
+impl RestartCount {
+    pub fn new(now: u64) -> Self {
+        Self {
+            count: 1,
+            first: now,
+            last: now,
+        }
+    }
+
+    pub fn record(&mut self, now: u64) {
+        self.count += 1;
+        self.last = now;
+    }
+
+    pub fn is_recent(&self, now: u64) -> bool {
+        now.saturating_sub(self.last) < RECENT_WINDOW_SECS
+    }
+
+    /// e.g. "restarted 3× in the last 2h, last at 13:41 UTC".
+    pub fn describe(&self, now: u64) -> String {
+        format!(
+            "restarted {}× in the last {}, last at {} UTC",
+            self.count,
+            format_window(now.saturating_sub(self.first)),
+            format_clock(self.last)
+        )
+    }
+}
+
+/// The `u` filter: only running guests up for less than a window, to see
+/// what came back after a power blip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RecentFilter {
+    #[default]
+    #[serde(rename = "off")]
+    Off,
+    #[serde(rename = "15m")]
+    Minutes15,
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "24h")]
+    Day,
+}
+
+impl RecentFilter {
+    pub fn next(self) -> Self {
+        match self {
+            RecentFilter::Off => RecentFilter::Minutes15,
+            RecentFilter::Minutes15 => RecentFilter::Hour,
+            RecentFilter::Hour => RecentFilter::Day,
+            RecentFilter::Day => RecentFilter::Off,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecentFilter::Off => "off",
+            RecentFilter::Minutes15 => "<15m",
+            RecentFilter::Hour => "<1h",
+            RecentFilter::Day => "<24h",
+        }
+    }
+
+    fn window_secs(&self) -> Option<u64> {
+        match self {
+            RecentFilter::Off => None,
+            RecentFilter::Minutes15 => Some(15 * 60),
+            RecentFilter::Hour => Some(3600),
+            RecentFilter::Day => Some(86400),
+        }
+    }
+
+    /// Everything passes when off. Otherwise stopped guests are left out:
+    /// their uptime of 0 says nothing about a restart.
+    pub fn matches(&self, container: &Container) -> bool {
+        let Some(window) = self.window_secs() else {
+            return true;
+        };
+        container.status == ContainerStatus::Running
+            && !container.missing
+            && container.uptime > 0
+            && container.uptime < window
+    }
+}
+
+/// Pair each current item with its previous snapshot by identity; items new
+/// in `current` are skipped.
+pub fn matched<'a, T, K: Eq + Hash>(
+    previous: &'a [T],
+    current: &'a [T],
+    key: impl Fn(&T) -> K,
+) -> Vec<(&'a T, &'a T)> {
+    let by_key: HashMap<K, &T> = previous.iter().map(|item| (key(item), item)).collect();
+    current
+        .iter()
+        .filter_map(|item| by_key.get(&key(item)).map(|before| (*before, item)))
+        .collect()
+}
+
+/// Running now with a lower uptime than last time. A guest that was merely
+/// stopped drops to zero but isn't running, so it doesn't count.
+pub fn container_restarted(before: &Container, after: &Container) -> bool {
+    after.status == ContainerStatus::Running && after.uptime < before.uptime
+}
+
+pub fn node_rebooted(before: &Node, after: &Node) -> bool {
+    after.status == NodeStatus::Online && after.uptime < before.uptime
+}
+
+/// Round a span up to whole hours (or minutes under an hour).
+fn format_window(secs: u64) -> String {
+    let (unit, rounded) = if secs >= 3600 {
+        (TimeUnit::Hour, secs.div_ceil(3600) * 3600)
+    } else {
+        (TimeUnit::Minute, secs.div_ceil(60).max(1) * 60)
+    };
+    let style = DurationStyle {
+        largest: unit,
+        smallest: unit,
+        compact: true,
+    };
+    format_duration(rounded, style)
+}
+
+fn format_clock(unix: u64) -> String {
+    let secs = unix % 86400;
+    format!("{:02}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(vmid: u32, status: ContainerStatus, uptime: u64) -> Container {
+        Container {
+            status,
+            uptime,
+            ..Container::test("homelab", vmid, &format!("ct{}", vmid), "pve1")
+        }
+    }
+
+    #[test]
+    fn test_matched_pairs_by_key() {
+        let previous = [container(101, ContainerStatus::Running, 10)];
+        let current = [
+            container(102, ContainerStatus::Running, 5),
+            container(101, ContainerStatus::Running, 20),
+        ];
+        let pairs = matched(&previous, &current, |c| c.id.clone());
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0.uptime, pairs[0].1.uptime), (10, 20));
+    }
+
+    #[test]
+    fn test_container_restarted() {
+        let running = container(101, ContainerStatus::Running, 5000);
+        assert!(container_restarted(
+            &running,
+            &container(101, ContainerStatus::Running, 12)
+        ));
+        assert!(!container_restarted(
+            &running,
+            &container(101, ContainerStatus::Running, 5005)
+        ));
+        // Stopping is not a restart, and neither is starting from stopped
+        let stopped = container(101, ContainerStatus::Stopped, 0);
+        assert!(!container_restarted(&running, &stopped));
+        assert!(!container_restarted(
+            &stopped,
+            &container(101, ContainerStatus::Running, 3)
+        ));
+    }
+
+    #[test]
+    fn test_recent_filter() {
+        let fresh = container(101, ContainerStatus::Running, 600);
+        let hours = container(102, ContainerStatus::Running, 3 * 3600);
+        let stopped = container(103, ContainerStatus::Stopped, 0);
+
+        assert!(RecentFilter::Minutes15.matches(&fresh));
+        assert!(!RecentFilter::Minutes15.matches(&hours));
+        assert!(!RecentFilter::Hour.matches(&hours));
+        assert!(RecentFilter::Day.matches(&hours));
+        assert!(!RecentFilter::Day.matches(&stopped));
+        assert!(RecentFilter::Off.matches(&stopped));
+
+        assert_eq!(RecentFilter::Day.next(), RecentFilter::Off);
+        assert_eq!(RecentFilter::Off.next().label(), "<15m");
+    }
+
+    #[test]
+    fn test_describe() {
+        // 2024-01-01 13:41:00 UTC
+        let last = 1_704_116_460;
+        let mut restarts = RestartCount::new(last - 5400);
+        restarts.record(last - 600);
+        restarts.record(last);
+        assert_eq!(
+            restarts.describe(last + 60),
+            "restarted 3× in the last 2h, last at 13:41 UTC"
+        );
+        assert!(restarts.is_recent(last + 60));
+        assert!(!restarts.is_recent(last + RECENT_WINDOW_SECS));
+    }
+
+    #[test]
+    fn test_format_window() {
+        assert_eq!(format_window(0), "1m");
+        assert_eq!(format_window(61), "2m");
+        assert_eq!(format_window(3599), "60m");
+        assert_eq!(format_window(3600), "1h");
+        assert_eq!(format_window(3601), "2h");
+    }
+}
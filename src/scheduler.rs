@@ -0,0 +1,498 @@
+use std::time::{Duration, Instant, SystemTime};
+
+/// How far the wall clock may run ahead of the monotonic one between two
+/// loop iterations before pulse assumes the machine was asleep.
+const RESUME_GAP: Duration = Duration::from_secs(30);
+
+/// Extra refreshes after an action completes, so its effect shows up quickly.
+const FOLLOW_UP_DELAYS: [Duration; 2] = [Duration::from_secs(1), Duration::from_secs(3)];
+
+/// Refreshes in a row that changed nothing before adaptive mode slows down.
+const QUIET_STREAK: u32 = 3;
+
+/// Adaptive mode never waits longer than this many configured intervals.
+const MAX_STRETCH: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefreshKind {
+    /// Asked for with `r`: every provider, even those backing off.
+    Manual,
+    /// The regular cycle or a follow-up: providers backing off are skipped.
+    Scheduled,
+    /// The regular cycle while staggering: only the providers whose slot
+    /// came up (`provider_due()`), those backing off still skipped.
+    Staggered,
+    /// The first load after startup: the next provider not fetched yet.
+    Loading,
+}
+
+/// When the next refresh runs. Refreshes block the UI loop, so requests made
+/// before one completes are coalesced into a single pending refresh.
+#[derive(Debug, Clone, Default)]
+pub struct RefreshScheduler {
+    last_refresh: Option<Instant>,
+    manual_pending: bool,
+    follow_ups: Vec<Instant>,
+    /// Both clocks at the previous loop iteration, to notice a suspend.
+    last_tick: Option<(Instant, SystemTime)>,
+    /// Stretch the interval while refreshes keep changing nothing
+    /// (`[general] adaptive_refresh`).
+    adaptive: bool,
+    /// Refreshes in a row that changed nothing.
+    quiet: u32,
+    /// Spread the providers' regular refreshes over the interval
+    /// (`[general] stagger_refresh`).
+    stagger: bool,
+    /// While staggering, each provider's last refresh, or the time its slot
+    /// is counted from; in provider order.
+    slots: Vec<(String, Instant)>,
+}
+
+impl RefreshScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_adaptive(&mut self, adaptive: bool) {
+        self.adaptive = adaptive;
+        self.quiet = 0;
+    }
+
+    /// Turning staggering on or off (or a reload) starts over from the next
+    /// refresh of everything.
+    pub fn set_stagger(&mut self, stagger: bool) {
+        self.stagger = stagger;
+        self.slots.clear();
+    }
+
+    /// Ask for a manual refresh; asking again before it runs changes nothing.
+    pub fn request(&mut self) {
+        self.manual_pending = true;
+        self.quiet = 0;
+    }
+
+    /// The user did something: back to the configured interval, so what
+    /// they are looking at is current.
+    pub fn interacted(&mut self) {
+        self.quiet = 0;
+    }
+
+    /// Whether the refresh that just completed changed anything worth
+    /// looking at; a change snaps back to the configured interval.
+    pub fn record_outcome(&mut self, changed: bool) {
+        self.quiet = if changed { 0 } else { self.quiet + 1 };
+    }
+
+    /// The interval actually waited for, given the configured one: doubled
+    /// after `QUIET_STREAK` quiet refreshes, and again after one more, up to
+    /// `MAX_STRETCH` times.
+    pub fn interval(&self, configured: Duration) -> Duration {
+        if !self.adaptive || self.quiet < QUIET_STREAK {
+            return configured;
+        }
+        let doublings = (self.quiet - QUIET_STREAK + 1).min(MAX_STRETCH.ilog2());
+        configured * (1 << doublings)
+    }
+
+    /// An action finished at `now`: refresh shortly after, then once more.
+    /// A later action replaces the burst of an earlier one.
+    pub fn action_completed(&mut self, now: Instant) {
+        self.follow_ups = FOLLOW_UP_DELAYS.iter().map(|delay| now + *delay).collect();
+    }
+
+    /// Called once per loop iteration with both clocks. The monotonic clock
+    /// stops while the machine sleeps and the wall clock doesn't, so when the
+    /// wall clock got well ahead, one refresh of everything is requested and
+    /// the time asleep returned. A slow refresh moves both clocks alike.
+    pub fn tick(&mut self, now: Instant, wall: SystemTime) -> Option<Duration> {
+        let previous = self.last_tick.replace((now, wall));
+        let (then, then_wall) = previous?;
+        let ran = now.saturating_duration_since(then);
+        let passed = wall.duration_since(then_wall).ok()?;
+        let asleep = passed.saturating_sub(ran);
+        if asleep < RESUME_GAP {
+            return None;
+        }
+        self.request();
+        Some(asleep)
+    }
+
+    /// The refresh to run at `now`, if any, for the configured `interval`.
+    pub fn due(&self, now: Instant, interval: Duration) -> Option<RefreshKind> {
+        if self.manual_pending {
+            return Some(RefreshKind::Manual);
+        }
+        let interval = self.interval(interval);
+        let follow_up_due = self.follow_ups.iter().any(|&at| at <= now);
+        if self.staggering() && !follow_up_due {
+            let slot_due = self
+                .slots
+                .iter()
+                .any(|&(_, at)| now.saturating_duration_since(at) >= interval);
+            return slot_due.then_some(RefreshKind::Staggered);
+        }
+        let cycle_due = self
+            .last_refresh
+            .is_none_or(|at| now.saturating_duration_since(at) >= interval);
+        (cycle_due || follow_up_due).then_some(RefreshKind::Scheduled)
+    }
+
+    /// Whether refreshes go by provider slots: staggering is on and a
+    /// refresh of everything has handed the slots out.
+    fn staggering(&self) -> bool {
+        self.stagger && !self.slots.is_empty()
+    }
+
+    /// Whether `provider`'s slot has come up in a staggered refresh. A
+    /// provider without one (added by a reload) is due.
+    pub fn provider_due(&self, provider: &str, now: Instant, interval: Duration) -> bool {
+        let interval = self.interval(interval);
+        self.slots
+            .iter()
+            .find(|(name, _)| name == provider)
+            .is_none_or(|&(_, at)| now.saturating_duration_since(at) >= interval)
+    }
+
+    /// A staggered refresh got to `provider` at `now`: its next one is an
+    /// interval away.
+    pub fn provider_refreshed(&mut self, provider: &str, now: Instant) {
+        match self.slots.iter_mut().find(|(name, _)| name == provider) {
+            Some(slot) => slot.1 = now,
+            None => self.slots.push((provider.to_string(), now)),
+        }
+    }
+
+    /// Everything was refreshed at `now`: when staggering, give the
+    /// `providers` evenly spaced slots in the interval to come, the first
+    /// `interval / n` away and the last a whole interval, so none waits
+    /// longer than before.
+    pub fn spread(&mut self, providers: &[&str], now: Instant, interval: Duration) {
+        if !self.stagger {
+            return;
+        }
+        let interval = self.interval(interval);
+        let step = interval / providers.len().max(1) as u32;
+        self.slots = providers
+            .iter()
+            .zip(1..)
+            .map(|(name, slot)| {
+                let wait = step * slot;
+                let from = now.checked_sub(interval - wait).unwrap_or(now);
+                (name.to_string(), from)
+            })
+            .collect();
+    }
+
+    /// Time until the next refresh falls due: zero when one is due now.
+    pub fn next_in(&self, now: Instant, interval: Duration) -> Duration {
+        if self.manual_pending {
+            return Duration::ZERO;
+        }
+        let interval = self.interval(interval);
+        let cycle = if self.staggering() {
+            self.slots
+                .iter()
+                .map(|&(_, at)| at + interval)
+                .min()
+                .unwrap_or(now)
+        } else {
+            self.last_refresh.map_or(now, |at| at + interval)
+        };
+        let next = self.follow_ups.iter().copied().fold(cycle, Instant::min);
+        next.saturating_duration_since(now)
+    }
+
+    /// Follow-up refreshes still to come after an action.
+    pub fn pending_follow_ups(&self) -> usize {
+        self.follow_ups.len()
+    }
+
+    /// A refresh of any kind completed at `now`; it satisfies every request
+    /// and follow-up that was due by then.
+    pub fn refreshed(&mut self, now: Instant) {
+        self.last_refresh = Some(now);
+        self.manual_pending = false;
+        self.follow_ups.retain(|&at| at > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(5);
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn test_next_in_counts_down_to_the_earliest_refresh() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        assert_eq!(scheduler.next_in(start, INTERVAL), Duration::ZERO);
+
+        scheduler.refreshed(start);
+        assert_eq!(scheduler.next_in(start + secs(2), INTERVAL), secs(3));
+        assert_eq!(scheduler.next_in(start + secs(9), INTERVAL), Duration::ZERO);
+
+        // A follow-up comes before the cycle
+        scheduler.action_completed(start);
+        assert_eq!(scheduler.pending_follow_ups(), 2);
+        assert_eq!(scheduler.next_in(start, INTERVAL), secs(1));
+
+        scheduler.request();
+        assert_eq!(scheduler.next_in(start, INTERVAL), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_first_refresh_is_due_then_waits_for_interval() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        assert_eq!(scheduler.due(start, INTERVAL), Some(RefreshKind::Scheduled));
+
+        scheduler.refreshed(start);
+        assert_eq!(scheduler.due(start + secs(4), INTERVAL), None);
+        assert_eq!(
+            scheduler.due(start + secs(5), INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+    }
+
+    #[test]
+    fn test_manual_requests_are_coalesced() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        scheduler.refreshed(start);
+
+        // Three presses while a refresh was in flight
+        scheduler.request();
+        scheduler.request();
+        scheduler.request();
+        assert_eq!(scheduler.due(start, INTERVAL), Some(RefreshKind::Manual));
+
+        scheduler.refreshed(start + secs(1));
+        assert_eq!(scheduler.due(start + secs(1), INTERVAL), None);
+    }
+
+    #[test]
+    fn test_action_schedules_follow_up_burst() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        scheduler.refreshed(start);
+        scheduler.action_completed(start);
+
+        assert_eq!(scheduler.due(start, INTERVAL), None);
+        let first = start + secs(1);
+        assert_eq!(scheduler.due(first, INTERVAL), Some(RefreshKind::Scheduled));
+        scheduler.refreshed(first);
+
+        assert_eq!(scheduler.due(start + secs(2), INTERVAL), None);
+        let second = start + secs(3);
+        assert_eq!(
+            scheduler.due(second, INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+        scheduler.refreshed(second);
+
+        // Back to the regular cycle, counted from the last refresh
+        assert_eq!(scheduler.due(start + secs(7), INTERVAL), None);
+        assert_eq!(
+            scheduler.due(start + secs(8), INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+    }
+
+    #[test]
+    fn test_resume_requests_one_refresh() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        let wall = SystemTime::UNIX_EPOCH + secs(1_700_000_000);
+        assert_eq!(scheduler.tick(start, wall), None);
+        scheduler.refreshed(start);
+
+        // Two hours asleep: the monotonic clock barely moved
+        let woke = start + Duration::from_millis(100);
+        assert_eq!(
+            scheduler.tick(woke, wall + secs(7200)),
+            Some(secs(7200) - Duration::from_millis(100))
+        );
+        assert_eq!(scheduler.due(woke, INTERVAL), Some(RefreshKind::Manual));
+        scheduler.refreshed(woke);
+        assert_eq!(scheduler.tick(woke + secs(1), wall + secs(7201)), None);
+        assert_eq!(scheduler.due(woke + secs(1), INTERVAL), None);
+    }
+
+    #[test]
+    fn test_slow_iterations_and_clock_changes_are_not_a_resume() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        let wall = SystemTime::UNIX_EPOCH + secs(1_700_000_000);
+        scheduler.tick(start, wall);
+        // A refresh that hung on timeouts moves both clocks
+        assert_eq!(scheduler.tick(start + secs(40), wall + secs(40)), None);
+        // The wall clock set back an hour
+        assert_eq!(scheduler.tick(start + secs(41), wall - secs(3600)), None);
+        assert_eq!(scheduler.tick(start + secs(42), wall - secs(3599)), None);
+    }
+
+    #[test]
+    fn test_adaptive_interval_stretches_while_quiet() {
+        let mut scheduler = RefreshScheduler::new();
+        scheduler.set_adaptive(true);
+        let intervals: Vec<u64> = [false, false, false, false, false, false]
+            .into_iter()
+            .map(|changed| {
+                scheduler.record_outcome(changed);
+                scheduler.interval(INTERVAL).as_secs()
+            })
+            .collect();
+        assert_eq!(intervals, [5, 5, 10, 20, 20, 20]);
+
+        // A change snaps back at once, then the streak starts over
+        let intervals: Vec<u64> = [true, false, false, false, true]
+            .into_iter()
+            .map(|changed| {
+                scheduler.record_outcome(changed);
+                scheduler.interval(INTERVAL).as_secs()
+            })
+            .collect();
+        assert_eq!(intervals, [5, 5, 5, 10, 5]);
+    }
+
+    #[test]
+    fn test_adaptive_interval_resets_on_interaction() {
+        let mut scheduler = RefreshScheduler::new();
+        scheduler.set_adaptive(true);
+        let start = Instant::now();
+        for _ in 0..4 {
+            scheduler.record_outcome(false);
+        }
+        scheduler.refreshed(start);
+        assert_eq!(scheduler.due(start + secs(10), INTERVAL), None);
+
+        // A keypress long enough after the last refresh refreshes right away
+        scheduler.interacted();
+        assert_eq!(
+            scheduler.due(start + secs(10), INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+
+        for _ in 0..4 {
+            scheduler.record_outcome(false);
+        }
+        scheduler.request();
+        assert_eq!(scheduler.interval(INTERVAL), INTERVAL);
+    }
+
+    #[test]
+    fn test_interval_is_fixed_without_adaptive_mode() {
+        let mut scheduler = RefreshScheduler::new();
+        for _ in 0..10 {
+            scheduler.record_outcome(false);
+        }
+        assert_eq!(scheduler.interval(INTERVAL), INTERVAL);
+
+        // Turning it on starts counting afresh
+        scheduler.set_adaptive(true);
+        assert_eq!(scheduler.interval(INTERVAL), INTERVAL);
+    }
+
+    /// A scheduler staggering `PROVIDERS`, all refreshed at `start`.
+    fn staggered(start: Instant) -> RefreshScheduler {
+        let mut scheduler = RefreshScheduler::new();
+        scheduler.set_stagger(true);
+        assert_eq!(scheduler.due(start, INTERVAL), Some(RefreshKind::Scheduled));
+        scheduler.refreshed(start);
+        scheduler.spread(&PROVIDERS, start, INTERVAL);
+        scheduler
+    }
+
+    const PROVIDERS: [&str; 3] = ["a", "b", "c"];
+
+    /// Which providers a staggered refresh at `now` would fetch.
+    fn due_providers(scheduler: &RefreshScheduler, now: Instant) -> Vec<&'static str> {
+        PROVIDERS
+            .into_iter()
+            .filter(|name| scheduler.provider_due(name, now, INTERVAL))
+            .collect()
+    }
+
+    #[test]
+    fn test_stagger_offsets_providers_across_the_interval() {
+        let start = Instant::now();
+        let mut scheduler = staggered(start);
+        assert_eq!(scheduler.due(start + secs(1), INTERVAL), None);
+        assert_eq!(scheduler.next_in(start, INTERVAL), INTERVAL / 3);
+
+        // A little past each third, as the loop gets to it
+        let third = INTERVAL / 3 + Duration::from_millis(1);
+        let mut fetched = Vec::new();
+        for slot in 1..=6 {
+            let now = start + third * slot;
+            assert_eq!(scheduler.due(now, INTERVAL), Some(RefreshKind::Staggered));
+            let due = due_providers(&scheduler, now);
+            for name in &due {
+                scheduler.provider_refreshed(name, now);
+            }
+            scheduler.refreshed(now);
+            fetched.push(due);
+        }
+        // One provider per third of the interval, each every interval
+        assert_eq!(fetched, [["a"], ["b"], ["c"], ["a"], ["b"], ["c"]]);
+    }
+
+    #[test]
+    fn test_manual_refresh_fetches_every_staggered_provider() {
+        let start = Instant::now();
+        let mut scheduler = staggered(start);
+        let now = start + secs(1);
+        scheduler.request();
+        assert_eq!(scheduler.due(now, INTERVAL), Some(RefreshKind::Manual));
+
+        // The refresh of everything hands out fresh slots from now
+        scheduler.refreshed(now);
+        scheduler.spread(&PROVIDERS, now, INTERVAL);
+        assert!(due_providers(&scheduler, now).is_empty());
+        assert_eq!(due_providers(&scheduler, now + INTERVAL / 3), ["a"]);
+
+        // Follow-ups after an action refresh everything too
+        scheduler.action_completed(now);
+        assert_eq!(
+            scheduler.due(now + secs(1), INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+    }
+
+    #[test]
+    fn test_stagger_off_or_reloaded_refreshes_everything() {
+        let start = Instant::now();
+        let mut scheduler = staggered(start);
+        scheduler.set_stagger(false);
+        assert_eq!(scheduler.due(start + secs(2), INTERVAL), None);
+        assert_eq!(
+            scheduler.due(start + INTERVAL, INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+
+        // Back on, it waits for a refresh of everything to hand out slots
+        scheduler.set_stagger(true);
+        assert_eq!(
+            scheduler.due(start + INTERVAL, INTERVAL),
+            Some(RefreshKind::Scheduled)
+        );
+        assert!(scheduler.provider_due("new", start, INTERVAL));
+    }
+
+    #[test]
+    fn test_refresh_covers_overdue_follow_ups() {
+        let mut scheduler = RefreshScheduler::new();
+        let start = Instant::now();
+        scheduler.action_completed(start);
+        scheduler.action_completed(start + secs(2));
+
+        // A slow refresh finishing after both follow-ups were due
+        scheduler.refreshed(start + secs(6));
+        assert_eq!(scheduler.due(start + secs(6), INTERVAL), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! How urgently a node or guest needs attention. Problems-first sorting,
+//! the bell and flash, the terminal title and `pulse watch` all classify
+//! items here, so they never disagree about what counts as a problem.
+
+use crate::config::ThresholdsConfig;
+use crate::models::{Container, ContainerStatus, Node, NodeStatus};
+
+/// Most urgent first, so sorting by it puts problems on top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// A guest in HA error state, or a node the HA manager is fencing or
+    /// has lost track of: nothing will recover it without a person.
+    HaError,
+    Down,
+    Critical,
+    Normal,
+}
+
+/// What the classification looks at, for either kind of item.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ItemState {
+    pub ha_error: bool,
+    /// An offline node, or a stopped guest that isn't a template.
+    pub down: bool,
+    pub cpu: f64,
+    pub memory: f64,
+}
+
+impl Severity {
+    pub fn classify(state: ItemState, thresholds: &ThresholdsConfig) -> Self {
+        if state.ha_error {
+            Severity::HaError
+        } else if state.down {
+            Severity::Down
+        } else if state.cpu >= thresholds.cpu_critical || state.memory >= thresholds.memory_critical
+        {
+            Severity::Critical
+        } else {
+            Severity::Normal
+        }
+    }
+
+    /// `ha_error` when the HA manager reports the node fenced or unknown.
+    pub fn of_node(node: &Node, ha_error: bool, thresholds: &ThresholdsConfig) -> Self {
+        Self::classify(
+            ItemState {
+                ha_error,
+                down: node.status == NodeStatus::Offline,
+                cpu: node.cpu_usage,
+                memory: node.memory_percent(),
+            },
+            thresholds,
+        )
+    }
+
+    /// `cpu` is the guest's CPU in the current display mode.
+    pub fn of_container(
+        container: &Container,
+        cpu: f64,
+        ha_error: bool,
+        thresholds: &ThresholdsConfig,
+    ) -> Self {
+        Self::classify(
+            ItemState {
+                ha_error,
+                down: container.status == ContainerStatus::Stopped && !container.is_template,
+                cpu,
+                memory: container.memory_percent(),
+            },
+            thresholds,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ContainerType;
+
+    fn thresholds() -> ThresholdsConfig {
+        ThresholdsConfig {
+            cpu_critical: 90.0,
+            memory_critical: 80.0,
+            ..Default::default()
+        }
+    }
+
+    fn state(ha_error: bool, down: bool, cpu: f64) -> ItemState {
+        ItemState {
+            ha_error,
+            down,
+            cpu,
+            memory: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_classify_takes_the_most_urgent_problem() {
+        let thresholds = thresholds();
+        let classify = |s| Severity::classify(s, &thresholds);
+        assert_eq!(classify(state(true, true, 99.0)), Severity::HaError);
+        assert_eq!(classify(state(true, false, 0.0)), Severity::HaError);
+        assert_eq!(classify(state(false, true, 99.0)), Severity::Down);
+        assert_eq!(classify(state(false, false, 99.0)), Severity::Critical);
+        assert_eq!(classify(state(false, false, 10.0)), Severity::Normal);
+        assert_eq!(classify(ItemState::default()), Severity::Normal);
+    }
+
+    #[test]
+    fn test_thresholds_are_inclusive() {
+        let thresholds = thresholds();
+        let classify = |cpu, memory| {
+            Severity::classify(
+                ItemState {
+                    cpu,
+                    memory,
+                    ..Default::default()
+                },
+                &thresholds,
+            )
+        };
+        assert_eq!(classify(90.0, 0.0), Severity::Critical);
+        assert_eq!(classify(89.9, 79.9), Severity::Normal);
+        assert_eq!(classify(0.0, 80.0), Severity::Critical);
+    }
+
+    #[test]
+    fn test_sorting_puts_ha_errors_first() {
+        let mut severities = vec![
+            Severity::Normal,
+            Severity::Critical,
+            Severity::HaError,
+            Severity::Down,
+        ];
+        severities.sort();
+        assert_eq!(
+            severities,
+            [
+                Severity::HaError,
+                Severity::Down,
+                Severity::Critical,
+                Severity::Normal
+            ]
+        );
+    }
+
+    #[test]
+    fn test_items_map_to_states() {
+        let thresholds = thresholds();
+        let mut guest = Container {
+            container_type: ContainerType::VM,
+            status: ContainerStatus::Stopped,
+            max_cpu: 2,
+            memory_used: 900,
+            memory_max: 1000,
+            is_template: true,
+            ..Container::test("homelab", 100, "template", "pve1")
+        };
+        // A stopped template isn't down, but its memory still counts
+        assert_eq!(
+            Severity::of_container(&guest, 0.0, false, &thresholds),
+            Severity::Critical
+        );
+        guest.is_template = false;
+        assert_eq!(
+            Severity::of_container(&guest, 0.0, false, &thresholds),
+            Severity::Down
+        );
+        assert_eq!(
+            Severity::of_container(&guest, 0.0, true, &thresholds),
+            Severity::HaError
+        );
+
+        let node = Node {
+            status: NodeStatus::Offline,
+            cpu_cores: 4,
+            memory_total: 4096,
+            ..Node::test("homelab", "pve1")
+        };
+        assert_eq!(Severity::of_node(&node, false, &thresholds), Severity::Down);
+        assert_eq!(
+            Severity::of_node(&node, true, &thresholds),
+            Severity::HaError
+        );
+    }
+}
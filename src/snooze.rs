@@ -0,0 +1,263 @@
+//! Alert snoozes: `z` on an alerting item silences one of its alerts for a
+//! while (15 minutes, an hour, or until the item restarts), shown as `zzz`
+//! on its row instead of the alert badge.
+//!
+//! Snoozes are kept under `[snoozes]` in the state file, keyed by the item's
+//! identity and the rule (`pve/100 replication`, `pve/pve1 disk`), so
+//! restarting pulse mid-incident doesn't bring every alert back. Expired
+//! snoozes are dropped after each refresh.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::{DurationStyle, format_duration};
+use crate::state;
+
+/// An uptime this much shorter than expected still counts as the same boot;
+/// providers report uptime with some jitter.
+const BOOT_SLACK: u64 = 60;
+
+/// What an item alerts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rule {
+    /// A guest's storage replication job is failing.
+    Replication,
+    /// A node has a failing or worn out disk.
+    Disk,
+}
+
+impl Rule {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rule::Replication => "replication",
+            Rule::Disk => "disk",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// The choices in the `z` popup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnoozeLength {
+    Minutes15,
+    Hour,
+    UntilRestart,
+}
+
+impl SnoozeLength {
+    pub const ALL: [SnoozeLength; 3] = [
+        SnoozeLength::Minutes15,
+        SnoozeLength::Hour,
+        SnoozeLength::UntilRestart,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SnoozeLength::Minutes15 => "15 minutes",
+            SnoozeLength::Hour => "1 hour",
+            SnoozeLength::UntilRestart => "until restart",
+        }
+    }
+
+    /// The `snooze` command's argument.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "15m" => Some(SnoozeLength::Minutes15),
+            "1h" => Some(SnoozeLength::Hour),
+            "restart" => Some(SnoozeLength::UntilRestart),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Snooze {
+    /// Unix seconds the snooze ends; None while it lasts until a restart.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    /// Unix seconds the item booted, for snoozes until it restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot: Option<u64>,
+}
+
+impl Snooze {
+    /// A snooze taken at `now` on an item up for `uptime` seconds.
+    pub fn new(length: SnoozeLength, now: u64, uptime: u64) -> Self {
+        match length {
+            SnoozeLength::Minutes15 => Self::until(now + 15 * 60),
+            SnoozeLength::Hour => Self::until(now + 60 * 60),
+            SnoozeLength::UntilRestart => Self {
+                until: None,
+                boot: Some(now.saturating_sub(uptime)),
+            },
+        }
+    }
+
+    fn until(until: u64) -> Self {
+        Self {
+            until: Some(until),
+            boot: None,
+        }
+    }
+
+    /// Whether the snooze still holds at `now`, given the item's uptime
+    /// (None while it isn't listed).
+    pub fn is_active(&self, now: u64, uptime: Option<u64>) -> bool {
+        if self.until.is_some_and(|until| now >= until) {
+            return false;
+        }
+        match (self.boot, uptime) {
+            (Some(boot), Some(uptime)) => now.saturating_sub(uptime) <= boot + BOOT_SLACK,
+            _ => true,
+        }
+    }
+
+    /// e.g. "for 12m", "for 1h" or "until restart".
+    pub fn describe(&self, now: u64) -> String {
+        match self.until {
+            Some(until) => {
+                let minutes = until.saturating_sub(now).div_ceil(60);
+                format!(
+                    "for {}",
+                    format_duration(minutes * 60, DurationStyle::COMPACT)
+                )
+            }
+            None => "until restart".to_string(),
+        }
+    }
+}
+
+/// The `z` popup for the selected item's alerts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnoozeMenu {
+    /// The item's identity, and its name for display.
+    pub item: String,
+    pub label: String,
+    /// The rules the item is alerting on.
+    pub rules: Vec<Rule>,
+    pub uptime: u64,
+    /// Whether any of them is snoozed already, which offers waking them.
+    pub snoozed: bool,
+    pub selected: usize,
+}
+
+impl SnoozeMenu {
+    /// The lengths on offer; None wakes the alerts up again.
+    pub fn choices(&self) -> Vec<Option<SnoozeLength>> {
+        let mut choices: Vec<_> = SnoozeLength::ALL.into_iter().map(Some).collect();
+        if self.snoozed {
+            choices.push(None);
+        }
+        choices
+    }
+}
+
+/// Snoozes by `key()`.
+pub type Snoozes = BTreeMap<String, Snooze>;
+
+/// `item` is the item's identity (`GuestId` or `NodeId` form).
+pub fn key(item: &str, rule: Rule) -> String {
+    format!("{} {}", item, rule)
+}
+
+pub fn load(path: &Path) -> Result<Snoozes, String> {
+    state::load(path, "snoozes")
+}
+
+/// Write `snoozes` to `path`, keeping the rest of the state file.
+pub fn save(path: &Path, snoozes: &Snoozes) -> Result<(), String> {
+    state::save(path, "snoozes", snoozes)
+}
+
+/// Drop the snoozes that no longer hold; `uptime` gives an item's uptime by
+/// identity. True if any were dropped.
+pub fn sweep(snoozes: &mut Snoozes, now: u64, uptime: impl Fn(&str) -> Option<u64>) -> bool {
+    let before = snoozes.len();
+    snoozes.retain(|key, snooze| {
+        let item = key.rsplit_once(' ').map_or(key.as_str(), |(item, _)| item);
+        snooze.is_active(now, uptime(item))
+    });
+    snoozes.len() != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_snoozes_expire() {
+        let snooze = Snooze::new(SnoozeLength::Minutes15, 1000, 50);
+        assert!(snooze.is_active(1000 + 14 * 60, Some(900)));
+        assert_eq!(snooze.describe(1000 + 14 * 60), "for 1m");
+        assert!(!snooze.is_active(1000 + 15 * 60, Some(900)));
+
+        let hour = Snooze::new(SnoozeLength::Hour, 1000, 50);
+        assert!(hour.is_active(1000 + 59 * 60, None));
+        assert!(!hour.is_active(1000 + 60 * 60, None));
+    }
+
+    #[test]
+    fn test_restart_snooze_ends_when_uptime_resets() {
+        // Up since 400 when snoozed at 1000
+        let snooze = Snooze::new(SnoozeLength::UntilRestart, 1000, 600);
+        assert_eq!(snooze.describe(5000), "until restart");
+        assert!(snooze.is_active(5000, Some(4600)));
+        // Reported uptime lagging a little is still the same boot
+        assert!(snooze.is_active(5000, Some(4570)));
+        // Gone from the list: kept until it comes back
+        assert!(snooze.is_active(5000, None));
+        // Rebooted at 4900
+        assert!(!snooze.is_active(5000, Some(100)));
+    }
+
+    #[test]
+    fn test_sweep_drops_expired_snoozes() {
+        let mut snoozes = Snoozes::from([
+            (
+                key("pve/100", Rule::Replication),
+                Snooze::new(SnoozeLength::Minutes15, 0, 0),
+            ),
+            (
+                key("pve/pve1", Rule::Disk),
+                Snooze::new(SnoozeLength::UntilRestart, 0, 0),
+            ),
+        ]);
+        assert!(!sweep(&mut snoozes, 60, |_| Some(60)));
+        assert!(sweep(&mut snoozes, 15 * 60, |_| Some(15 * 60)));
+        assert_eq!(snoozes.keys().collect::<Vec<_>>(), ["pve/pve1 disk"]);
+        assert!(sweep(&mut snoozes, 20 * 60, |item| {
+            assert_eq!(item, "pve/pve1");
+            Some(10)
+        }));
+        assert!(snoozes.is_empty());
+    }
+
+    #[test]
+    fn test_snoozes_survive_a_restart_next_to_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        std::fs::write(
+            &path,
+            "[notes.\"pve/100\"]\ntext = \"x\"\nupdated = 1\nseen = 1\n",
+        )
+        .unwrap();
+
+        let snoozes = Snoozes::from([(
+            key("pve/100", Rule::Replication),
+            Snooze::new(SnoozeLength::Hour, 0, 0),
+        )]);
+        save(&path, &snoozes).unwrap();
+
+        assert_eq!(load(&path).unwrap(), snoozes);
+        assert_eq!(crate::notes::load(&path).unwrap().len(), 1);
+    }
+}
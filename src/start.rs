@@ -0,0 +1,149 @@
+//! Starting a stopped guest: the confirmation the `b` popup shows, with a
+//! warning when the guest's memory doesn't fit on its node right now, and
+//! the task it starts.
+//!
+//! The warning only informs. Proxmox may still manage (ballooning, KSM,
+//! swap), and the user knows what else is about to stop.
+
+use std::time::Instant;
+
+use crate::format::{Units, format_bytes};
+use crate::models::{Container, GuestId, Node};
+
+/// Memory a guest is configured with that its node can't currently spare.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryShortfall {
+    pub node: String,
+    /// Node total minus used.
+    pub free: u64,
+    /// The guest's configured memory.
+    pub wanted: u64,
+}
+
+impl MemoryShortfall {
+    /// "pve1 has 3.1 GB free, guest wants 8.0 GB"
+    pub fn describe(&self, units: Units) -> String {
+        format!(
+            "{} has {} free, guest wants {}",
+            self.node,
+            format_bytes(self.free, units),
+            format_bytes(self.wanted, units)
+        )
+    }
+}
+
+/// Whether `guest` would start on `node` with less memory free than it is
+/// configured with. None when it fits, or when either figure is unknown.
+pub fn memory_shortfall(guest: &Container, node: &Node) -> Option<MemoryShortfall> {
+    if guest.memory_max == 0 || node.memory_total == 0 {
+        return None;
+    }
+    let free = node.memory_total.saturating_sub(node.memory_used);
+    (guest.memory_max > free).then(|| MemoryShortfall {
+        node: node.name.clone(),
+        free,
+        wanted: guest.memory_max,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartPhase {
+    /// Waiting for the user to confirm.
+    Confirm,
+    /// Task started on the provider, polled until it finishes.
+    Running {
+        task: String,
+    },
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct GuestStart {
+    pub guest: GuestId,
+    /// Node the guest is on, which runs the start task.
+    pub node: String,
+    /// "105 web", for the title and the audit log.
+    pub label: String,
+    /// Checked when the popup opened, from the last refresh.
+    pub shortfall: Option<MemoryShortfall>,
+    pub phase: StartPhase,
+    /// When the running task was last polled.
+    pub last_poll: Option<Instant>,
+}
+
+impl GuestStart {
+    /// Confirmation for starting `guest`, warning if `node` (its node, when
+    /// listed) looks short of memory.
+    pub fn new(guest: &Container, node: Option<&Node>) -> Self {
+        Self {
+            guest: guest.id.clone(),
+            node: guest.node.clone(),
+            label: format!("{} {}", guest.vmid, guest.name),
+            shortfall: node.and_then(|n| memory_shortfall(guest, n)),
+            phase: StartPhase::Confirm,
+            last_poll: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ContainerStatus, ContainerType};
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn node(used_gb: u64, total_gb: u64) -> Node {
+        Node {
+            cpu_cores: 8,
+            memory_used: used_gb * GB,
+            memory_total: total_gb * GB,
+            uptime: 1,
+            ..Node::test("homelab", "pve1")
+        }
+    }
+
+    fn guest(memory_gb: u64) -> Container {
+        Container {
+            container_type: ContainerType::VM,
+            status: ContainerStatus::Stopped,
+            max_cpu: 2,
+            memory_max: memory_gb * GB,
+            ..Container::test("homelab", 105, "web", "pve1")
+        }
+    }
+
+    #[test]
+    fn test_shortfall_when_guest_wants_more_than_is_free() {
+        let shortfall = memory_shortfall(&guest(8), &node(29, 32)).unwrap();
+        assert_eq!(
+            shortfall,
+            MemoryShortfall {
+                node: "pve1".to_string(),
+                free: 3 * GB,
+                wanted: 8 * GB,
+            }
+        );
+        assert_eq!(
+            shortfall.describe(Units::Binary),
+            "pve1 has 3.0 GiB free, guest wants 8.0 GiB"
+        );
+    }
+
+    #[test]
+    fn test_no_shortfall_when_it_fits_exactly() {
+        assert_eq!(memory_shortfall(&guest(8), &node(24, 32)), None);
+    }
+
+    #[test]
+    fn test_unknown_figures_never_warn() {
+        assert_eq!(memory_shortfall(&guest(0), &node(32, 32)), None);
+        assert_eq!(memory_shortfall(&guest(8), &node(0, 0)), None);
+        // Used above total (stale figures) reads as nothing free
+        assert_eq!(
+            memory_shortfall(&guest(1), &node(33, 32)).map(|s| s.free),
+            Some(0)
+        );
+    }
+}
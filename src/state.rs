@@ -0,0 +1,108 @@
+//! pulse's state file (`[general] state_file`): what pulse saves itself,
+//! one top-level table per feature (`[presets]`, `[notes]`). Each feature
+//! reads and writes only its own table, so saving one keeps the others.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// The `section` table of the file at `path`; the default if the file or
+/// the table doesn't exist yet.
+pub fn load<T: DeserializeOwned + Default>(path: &Path, section: &str) -> Result<T, String> {
+    let Some(mut table) = read_table(path)? else {
+        return Ok(T::default());
+    };
+    match table.remove(section) {
+        Some(value) => value
+            .try_into()
+            .map_err(|e| format!("cannot parse {}: {}", path.display(), e)),
+        None => Ok(T::default()),
+    }
+}
+
+/// Replace the `section` table of the file at `path` with `value`, creating
+/// the file and its directory if needed. A file that doesn't parse is left
+/// alone rather than overwritten. The new file is written and synced next
+/// to the old one, then renamed over it, so a crash or power cut mid-write
+/// never truncates it.
+pub fn save<T: Serialize>(path: &Path, section: &str, value: &T) -> Result<(), String> {
+    let mut table = read_table(path)?.unwrap_or_default();
+    let value = toml::Value::try_from(value).map_err(|e| e.to_string())?;
+    table.insert(section.to_string(), value);
+    let text = toml::to_string(&table).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(text.as_bytes())?;
+            file.sync_all()
+        })
+        .map_err(|e| format!("cannot write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+fn read_table(path: &Path) -> Result<Option<toml::Table>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    text.parse::<toml::Table>()
+        .map(Some)
+        .map_err(|e| format!("cannot parse {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_sections_are_saved_independently() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        let empty: BTreeMap<String, u32> = load(&path, "a").unwrap();
+        assert!(empty.is_empty());
+
+        save(&path, "a", &BTreeMap::from([("x".to_string(), 1u32)])).unwrap();
+        save(&path, "b", &BTreeMap::from([("y".to_string(), 2u32)])).unwrap();
+        let a: BTreeMap<String, u32> = load(&path, "a").unwrap();
+        let b: BTreeMap<String, u32> = load(&path, "b").unwrap();
+        assert_eq!(a["x"], 1);
+        assert_eq!(b["y"], 2);
+
+        // A broken file is reported, and not replaced by the next save
+        std::fs::write(&path, "[a\n").unwrap();
+        assert!(
+            load::<BTreeMap<String, u32>>(&path, "a")
+                .unwrap_err()
+                .starts_with("cannot parse")
+        );
+        assert!(save(&path, "b", &b).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[a\n");
+    }
+
+    #[test]
+    fn test_leftover_temp_file_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.toml");
+        save(&path, "a", &BTreeMap::from([("x".to_string(), 1u32)])).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        // A save killed before its rename leaves a half-written temp file
+        std::fs::write(path.with_extension("tmp"), "[a\nx = ").unwrap();
+        let a: BTreeMap<String, u32> = load(&path, "a").unwrap();
+        assert_eq!(a["x"], 1);
+
+        save(&path, "a", &BTreeMap::from([("x".to_string(), 2u32)])).unwrap();
+        let a: BTreeMap<String, u32> = load(&path, "a").unwrap();
+        assert_eq!(a["x"], 2);
+        assert!(!path.with_extension("tmp").exists());
+    }
+}
@@ -0,0 +1,141 @@
+//! Rolling average and session peak of the CPU and memory figures of each
+//! node and guest, for the line under the detail gauges ("now 43% · avg 38%
+//! · peak 91% at 13:22:05").
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::trend::Trend;
+
+/// How far back the rolling average reaches.
+pub const AVERAGE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary<T> {
+    pub average: f64,
+    pub peak: f64,
+    /// When the peak was first reached.
+    pub peak_at: T,
+}
+
+/// Average and highest value of a series of `(time, value)`; None when it
+/// is empty.
+pub fn summarize<T: Copy>(series: impl IntoIterator<Item = (T, f64)>) -> Option<Summary<T>> {
+    let mut series = series.into_iter();
+    let (first_at, first) = series.next()?;
+    let mut summary = Summary {
+        average: first,
+        peak: first,
+        peak_at: first_at,
+    };
+    let mut count = 1;
+    for (at, value) in series {
+        summary.average += value;
+        count += 1;
+        if value > summary.peak {
+            summary.peak = value;
+            summary.peak_at = at;
+        }
+    }
+    summary.average /= count as f64;
+    Some(summary)
+}
+
+/// One figure of one item: the samples of the last `AVERAGE_WINDOW` and the
+/// highest value since pulse started.
+#[derive(Debug, Clone)]
+pub struct UsageSeries {
+    recent: Trend,
+    peak: Option<(f64, SystemTime)>,
+}
+
+impl Default for UsageSeries {
+    fn default() -> Self {
+        Self {
+            recent: Trend::new(AVERAGE_WINDOW),
+            peak: None,
+        }
+    }
+}
+
+impl UsageSeries {
+    pub fn push(&mut self, at: Instant, wall: SystemTime, value: f64) {
+        self.recent.push(at, value);
+        if self.peak.is_none_or(|(peak, _)| value > peak) {
+            self.peak = Some((value, wall));
+        }
+    }
+
+    /// Average over the window, or over what there is of it so far.
+    pub fn average(&self) -> Option<f64> {
+        summarize(self.recent.samples()).map(|s| s.average)
+    }
+
+    pub fn peak(&self) -> Option<(f64, SystemTime)> {
+        self.peak
+    }
+}
+
+/// The figures shown as gauges in the detail panel.
+#[derive(Debug, Clone, Default)]
+pub struct UsageStats {
+    pub cpu: UsageSeries,
+    pub memory: UsageSeries,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_empty_and_single() {
+        assert_eq!(summarize(Vec::<(u32, f64)>::new()), None);
+        assert_eq!(
+            summarize([(7, 40.0)]),
+            Some(Summary {
+                average: 40.0,
+                peak: 40.0,
+                peak_at: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_summarize_keeps_the_first_peak() {
+        let summary = summarize([(1, 10.0), (2, 90.0), (3, 20.0), (4, 90.0)]).unwrap();
+        assert_eq!(summary.average, 52.5);
+        assert_eq!(summary.peak, 90.0);
+        assert_eq!(summary.peak_at, 2);
+    }
+
+    #[test]
+    fn test_series_averages_a_partial_window() {
+        let start = Instant::now();
+        let wall = SystemTime::UNIX_EPOCH;
+        let mut series = UsageSeries::default();
+        assert_eq!(series.average(), None);
+        assert_eq!(series.peak(), None);
+
+        // Ten seconds of a minute's window
+        series.push(start, wall, 30.0);
+        series.push(start + Duration::from_secs(10), wall, 50.0);
+        assert_eq!(series.average(), Some(40.0));
+    }
+
+    #[test]
+    fn test_series_peak_outlives_the_window() {
+        let start = Instant::now();
+        let wall = SystemTime::UNIX_EPOCH;
+        let mut series = UsageSeries::default();
+        series.push(start, wall, 95.0);
+        series.push(start + Duration::from_secs(5), wall, 10.0);
+        let later = wall + Duration::from_secs(120);
+        series.push(start + Duration::from_secs(120), later, 20.0);
+
+        // The spike is out of the average but still the peak
+        assert_eq!(series.average(), Some(20.0));
+        assert_eq!(series.peak(), Some((95.0, wall)));
+
+        series.push(start + Duration::from_secs(125), later, 96.0);
+        assert_eq!(series.peak(), Some((96.0, later)));
+    }
+}
@@ -0,0 +1,194 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Number of terminal columns `s` occupies.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+fn char_width(c: char) -> usize {
+    UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+/// Longest prefix of `s` that fits in `width` columns.
+fn take_width(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in s.char_indices() {
+        let w = char_width(c);
+        if used + w > width {
+            return &s[..i];
+        }
+        used += w;
+    }
+    s
+}
+
+/// Longest suffix of `s` that fits in `width` columns.
+fn take_width_from_end(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in s.char_indices().rev() {
+        let w = char_width(c);
+        if used + w > width {
+            return &s[i + c.len_utf8()..];
+        }
+        used += w;
+    }
+    s
+}
+
+/// Cut `s` to at most `width` columns, marking the cut with `~` at the end.
+pub fn truncate_end(s: &str, width: usize) -> String {
+    truncate_end_with(s, width, "~")
+}
+
+/// Cut `s` to at most `width` columns, ending with `marker` when cut.
+pub fn truncate_end_with(s: &str, width: usize, marker: &str) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    let marker_width = display_width(marker);
+    if width < marker_width {
+        return take_width(s, width).to_string();
+    }
+    format!("{}{}", take_width(s, width - marker_width), marker)
+}
+
+/// Cut `s` to at most `width` columns by removing its middle, so both the
+/// prefix and the distinguishing suffix stay visible ("svc-…-web-01").
+pub fn truncate_middle(s: &str, width: usize) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    if width < 3 {
+        return truncate_end(s, width);
+    }
+    let budget = width - 1;
+    let tail = take_width_from_end(s, budget - budget / 2);
+    let head = take_width(s, budget - display_width(tail));
+    format!("{}…{}", head, tail)
+}
+
+/// Pad `s` with spaces on the right to exactly `width` columns (if shorter).
+pub fn pad_right(s: &str, width: usize) -> String {
+    let pad = width.saturating_sub(display_width(s));
+    format!("{}{}", s, " ".repeat(pad))
+}
+
+/// Pad `s` with spaces on the left to exactly `width` columns (if shorter).
+pub fn pad_left(s: &str, width: usize) -> String {
+    let pad = width.saturating_sub(display_width(s));
+    format!("{}{}", " ".repeat(pad), s)
+}
+
+/// Exactly `width` columns: cut with `truncate_end()`, then padded. A wide
+/// character that would straddle the edge is replaced by padding.
+pub fn fit(s: &str, width: usize) -> String {
+    pad_right(&truncate_end(s, width), width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_end_ascii() {
+        assert_eq!(truncate_end("jellyfin", 12), "jellyfin");
+        assert_eq!(truncate_end("svc-prod-web-01", 12), "svc-prod-we~");
+        assert_eq!(truncate_end("abc", 0), "");
+    }
+
+    #[test]
+    fn test_truncate_end_with_marker() {
+        let error = "homelab: error fetching nodes: request failed";
+        let cut = truncate_end_with(error, 30, "…(press E)");
+        assert_eq!(cut, "homelab: error fetch…(press E)");
+        assert_eq!(display_width(&cut), 30);
+        assert_eq!(truncate_end_with(error, 60, "…(press E)"), error);
+        // Too narrow for the marker: plain cut
+        assert_eq!(truncate_end_with(error, 4, "…(press E)"), "home");
+    }
+
+    #[test]
+    fn test_truncate_middle_keeps_suffix() {
+        assert_eq!(truncate_middle("svc-prod-web-01", 15), "svc-prod-web-01");
+        assert_eq!(truncate_middle("svc-prod-web-01", 12), "svc-p…web-01");
+        assert_eq!(truncate_middle("svc-prod-web-02", 12), "svc-p…web-02");
+        assert_eq!(display_width(&truncate_middle("svc-prod-web-01", 12)), 12);
+    }
+
+    #[test]
+    fn test_truncate_middle_tiny_widths() {
+        assert_eq!(truncate_middle("abcdef", 2), "a~");
+        assert_eq!(truncate_middle("abcdef", 3), "a…f");
+    }
+
+    #[test]
+    fn test_truncation_is_width_aware() {
+        // Each CJK character takes two columns; never split one in half
+        let name = "数据库服务器";
+        assert_eq!(display_width(name), 12);
+        assert_eq!(truncate_end(name, 6), "数据~");
+        assert!(display_width(&truncate_end(name, 6)) <= 6);
+        assert!(display_width(&truncate_middle(name, 7)) <= 7);
+    }
+
+    #[test]
+    fn test_pad_right() {
+        assert_eq!(pad_right("ab", 4), "ab  ");
+        assert_eq!(pad_right("数据", 6), "数据  ");
+        assert_eq!(pad_right("toolong", 3), "toolong");
+    }
+
+    #[test]
+    fn test_pad_left() {
+        assert_eq!(pad_left("ab", 4), "  ab");
+        assert_eq!(pad_left("数据", 6), "  数据");
+        assert_eq!(pad_left("toolong", 3), "toolong");
+    }
+
+    #[test]
+    fn test_fit_mixed_scripts_at_several_widths() {
+        let names = [
+            "web-01",
+            "数据库服务器",
+            "ウェブサーバー",
+            "서버-메인",
+            "db🐘primary",
+            "mix-数据-01",
+        ];
+        for name in names {
+            for width in [0, 1, 2, 3, 5, 8, 10, 16] {
+                let fitted = fit(name, width);
+                assert_eq!(
+                    display_width(&fitted),
+                    width,
+                    "fit({:?}, {}) = {:?}",
+                    name,
+                    width,
+                    fitted
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fit_never_splits_wide_characters() {
+        // "数据~" is 5 columns; the 6th is padding, not half a character
+        assert_eq!(fit("数据库服务器", 6), "数据~ ");
+        assert_eq!(fit("서버-메인", 9), "서버-메인");
+        assert_eq!(fit("서버-메인", 8), "서버-메~");
+        assert_eq!(fit("서버-메인", 7), "서버-~ ");
+        assert_eq!(fit("db🐘primary", 4), "db~ ");
+        assert_eq!(fit("db🐘primary", 5), "db🐘~");
+        assert_eq!(fit("ウェブ", 1), "~");
+    }
+
+    #[test]
+    fn test_truncate_middle_mixed_scripts() {
+        for width in [3, 4, 7, 10] {
+            let cut = truncate_middle("mix-数据-ウェブ-01", width);
+            assert!(display_width(&cut) <= width, "{:?} at {}", cut, width);
+            assert!(cut.contains('…'));
+        }
+        assert_eq!(truncate_middle("数据库-web-01", 10), "数据…eb-01");
+    }
+}
@@ -0,0 +1,145 @@
+//! Accessibility modes: status glyphs that don't rely on color, and a
+//! final pass over the rendered frame that swaps or strips colors.
+//!
+//! The UI keeps drawing with its usual green/yellow/red; `recolor` maps
+//! them afterwards so every widget follows the mode without knowing about it.
+
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+use crate::config::Accessibility;
+
+/// Orange in the 256-color palette, the colorblind stand-in for red.
+const ORANGE: Color = Color::Indexed(208);
+
+/// Background tints for changes at the last refresh: usage that rose,
+/// usage that fell, and a guest that appeared.
+pub const TINT_RISE: Color = Color::Indexed(52);
+pub const TINT_FALL: Color = Color::Indexed(22);
+pub const TINT_NEW: Color = Color::Indexed(24);
+/// Background of the rows a visual-mode range would mark.
+pub const TINT_VISUAL: Color = Color::Indexed(60);
+
+/// What a status dot reports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    /// Running guest or online node.
+    Up,
+    /// Stopped guest or offline node.
+    Down,
+    /// Configured but not found.
+    Missing,
+    /// Guest whose HA service is in the error state.
+    HaError,
+    /// Node the HA manager is fencing or has lost track of.
+    Fenced,
+}
+
+/// The glyph for a state. Outside standard mode every state has its own
+/// shape, so nothing is told by color alone.
+pub fn state_glyph(mode: Accessibility, state: State) -> &'static str {
+    match (mode, state) {
+        (_, State::Up) => "●",
+        (Accessibility::Standard, State::Down) => "○",
+        (_, State::Down) => "✖",
+        (_, State::Missing) => "?",
+        (_, State::HaError) => "‼",
+        (_, State::Fenced) => "⊘",
+    }
+}
+
+/// Adapt a rendered frame to the mode. Colorblind swaps green for blue and
+/// red for orange; mono drops all colors and shows highlighted cells (the
+/// selection, filled gauges under their label) in reverse video.
+pub fn recolor(mode: Accessibility, buffer: &mut Buffer) {
+    if mode == Accessibility::Standard {
+        return;
+    }
+    for cell in buffer.content.iter_mut() {
+        match mode {
+            Accessibility::Standard => {}
+            Accessibility::Colorblind => {
+                cell.fg = colorblind(cell.fg);
+                cell.bg = colorblind(cell.bg);
+            }
+            Accessibility::Mono => {
+                if cell.bg != Color::Reset {
+                    cell.modifier.insert(Modifier::REVERSED);
+                }
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            }
+        }
+    }
+}
+
+fn colorblind(color: Color) -> Color {
+    match color {
+        Color::Green | Color::LightGreen => Color::LightBlue,
+        Color::Red | Color::LightRed => ORANGE,
+        TINT_FALL => Color::Indexed(17),
+        TINT_RISE => Color::Indexed(94),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    #[test]
+    fn test_every_state_has_its_own_glyph_outside_standard() {
+        for mode in [Accessibility::Colorblind, Accessibility::Mono] {
+            let glyphs = [
+                State::Up,
+                State::Down,
+                State::Missing,
+                State::HaError,
+                State::Fenced,
+            ]
+            .map(|s| state_glyph(mode, s));
+            assert_eq!(glyphs, ["●", "✖", "?", "‼", "⊘"]);
+        }
+        assert_eq!(state_glyph(Accessibility::Standard, State::Down), "○");
+        // HA problems look apart from plain down in every mode
+        assert_eq!(state_glyph(Accessibility::Standard, State::HaError), "‼");
+        assert_eq!(state_glyph(Accessibility::Standard, State::Fenced), "⊘");
+    }
+
+    #[test]
+    fn test_recolor() {
+        let area = Rect::new(0, 0, 3, 1);
+        let mut buffer = Buffer::empty(area);
+        buffer[(0, 0)].set_style(Style::default().fg(Color::Green));
+        buffer[(1, 0)].set_style(Style::default().fg(Color::Red).bg(Color::DarkGray));
+        buffer[(2, 0)].set_style(Style::default().fg(Color::Magenta));
+
+        let mut colorblind = buffer.clone();
+        recolor(Accessibility::Colorblind, &mut colorblind);
+        let colors: Vec<(Color, Color)> = colorblind.content.iter().map(|c| (c.fg, c.bg)).collect();
+        assert_eq!(
+            colors,
+            [
+                (Color::LightBlue, Color::Reset),
+                (ORANGE, Color::DarkGray),
+                (Color::Magenta, Color::Reset),
+            ]
+        );
+
+        recolor(Accessibility::Mono, &mut buffer);
+        assert!(
+            buffer
+                .content
+                .iter()
+                .all(|c| c.fg == Color::Reset && c.bg == Color::Reset)
+        );
+        let reversed: Vec<bool> = buffer
+            .content
+            .iter()
+            .map(|c| c.modifier.contains(Modifier::REVERSED))
+            .collect();
+        assert_eq!(reversed, [false, true, false]);
+    }
+}
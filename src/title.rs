@@ -0,0 +1,69 @@
+//! The terminal window (or tmux pane) title, kept to a short health summary
+//! such as "pulse: 2 down, 1 crit" so problems show while pulse is in a
+//! background tab.
+//!
+//! The title is set with the OSC 0 escape. The one pulse replaced is saved
+//! with the xterm title stack (CSI 22 t) and put back on exit (CSI 23 t);
+//! terminals without the stack ignore those and are left with an empty title.
+
+use std::io::{self, Write};
+
+#[derive(Debug, Default)]
+pub struct TerminalTitle {
+    /// What the terminal was last told, so unchanged titles aren't resent.
+    shown: Option<String>,
+}
+
+impl TerminalTitle {
+    /// Show `title`, unless it is already shown. The first call saves the
+    /// terminal's own title for `restore`.
+    pub fn set(&mut self, out: &mut impl Write, title: &str) -> io::Result<()> {
+        // Control characters would end the escape early
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        if self.shown.as_ref() == Some(&title) {
+            return Ok(());
+        }
+        if self.shown.is_none() {
+            write!(out, "\x1b[22;0t")?;
+        }
+        write!(out, "\x1b]0;{}\x07", title)?;
+        out.flush()?;
+        self.shown = Some(title);
+        Ok(())
+    }
+
+    /// Put back the title from before the first `set`; nothing if none was.
+    pub fn restore(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.shown.take().is_none() {
+            return Ok(());
+        }
+        write!(out, "\x1b]0;\x07\x1b[23;0t")?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_restore() {
+        let mut title = TerminalTitle::default();
+        let mut out = Vec::new();
+        title.restore(&mut out).unwrap();
+        assert!(out.is_empty());
+
+        title.set(&mut out, "pulse: ok").unwrap();
+        title.set(&mut out, "pulse: ok").unwrap();
+        assert_eq!(out, b"\x1b[22;0t\x1b]0;pulse: ok\x07");
+
+        out.clear();
+        title.set(&mut out, "pulse: 1 down\x07").unwrap();
+        assert_eq!(out, b"\x1b]0;pulse: 1 down\x07");
+
+        out.clear();
+        title.restore(&mut out).unwrap();
+        title.restore(&mut out).unwrap();
+        assert_eq!(out, b"\x1b]0;\x07\x1b[23;0t");
+    }
+}
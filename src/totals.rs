@@ -0,0 +1,180 @@
+//! Totals over a set of nodes or guests. The header counts, the providers
+//! popup and the summary rows under the panels all add up the same way;
+//! the summary rows just pass the filtered lists.
+
+use crate::format::{Units, format_bytes};
+use crate::models::{Container, ContainerStatus, Node, NodeStatus};
+use crate::trend;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeTotals {
+    pub online: usize,
+    pub total: usize,
+    /// Core-weighted CPU of the online nodes; None with none online.
+    pub cpu: Option<f64>,
+    /// Memory of the online nodes.
+    pub memory_used: u64,
+    pub memory_total: u64,
+}
+
+impl NodeTotals {
+    pub fn memory_percent(&self) -> Option<f64> {
+        (self.memory_total > 0).then(|| self.memory_used as f64 / self.memory_total as f64 * 100.0)
+    }
+
+    /// "Σ 3 nodes · CPU avg 34% · Mem 96.0 GB/192.0 GB"
+    pub fn summary(&self, units: Units) -> String {
+        let mut parts = vec![format!("Σ {} nodes", self.total)];
+        if self.online < self.total {
+            parts.push(format!("{} offline", self.total - self.online));
+        }
+        if let Some(cpu) = self.cpu {
+            parts.push(format!("CPU avg {:.0}%", cpu));
+        }
+        if self.memory_total > 0 {
+            parts.push(format!(
+                "Mem {}/{}",
+                format_bytes(self.memory_used, units),
+                format_bytes(self.memory_total, units)
+            ));
+        }
+        parts.join(" · ")
+    }
+}
+
+pub fn nodes<'a>(nodes: impl IntoIterator<Item = &'a Node>) -> NodeTotals {
+    let nodes: Vec<&Node> = nodes.into_iter().collect();
+    let online = || nodes.iter().filter(|n| n.status == NodeStatus::Online);
+    NodeTotals {
+        online: online().count(),
+        total: nodes.len(),
+        cpu: trend::cluster_cpu(online().copied()),
+        memory_used: online().map(|n| n.memory_used).sum(),
+        memory_total: online().map(|n| n.memory_total).sum(),
+    }
+}
+
+/// Templates never run, so they count as neither running nor stopped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GuestTotals {
+    pub running: usize,
+    pub total: usize,
+    /// CPU of the running guests added up, as displayed.
+    pub cpu: f64,
+    /// Memory in use by the running guests.
+    pub memory_used: u64,
+}
+
+impl GuestTotals {
+    /// "Σ 47 running · CPU 212% · Mem 81.0 GB"
+    pub fn summary(&self, units: Units) -> String {
+        let mut parts = vec![format!("Σ {} running", self.running)];
+        if self.running < self.total {
+            parts.push(format!("{} stopped", self.total - self.running));
+        }
+        parts.push(format!("CPU {:.0}%", self.cpu));
+        parts.push(format!("Mem {}", format_bytes(self.memory_used, units)));
+        parts.join(" · ")
+    }
+}
+
+/// `cpu` gives a guest's CPU as displayed (it depends on the CPU mode).
+pub fn guests<'a>(
+    guests: impl IntoIterator<Item = &'a Container>,
+    cpu: impl Fn(&Container) -> f64,
+) -> GuestTotals {
+    let mut totals = GuestTotals::default();
+    for guest in guests.into_iter().filter(|c| !c.is_template) {
+        totals.total += 1;
+        if guest.status == ContainerStatus::Running {
+            totals.running += 1;
+            totals.cpu += cpu(guest);
+            totals.memory_used += guest.memory_used;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn node(name: &str, online: bool, cpu: f64, cores: u32) -> Node {
+        Node {
+            status: if online {
+                NodeStatus::Online
+            } else {
+                NodeStatus::Offline
+            },
+            cpu_usage: cpu,
+            cpu_cores: cores,
+            memory_used: 32 * GB,
+            memory_total: 64 * GB,
+            uptime: 1,
+            ..Node::test("homelab", name)
+        }
+    }
+
+    fn guest(vmid: u32, status: ContainerStatus, cpu: f64) -> Container {
+        Container {
+            status,
+            cpu_usage: cpu,
+            max_cpu: 2,
+            memory_used: 2 * GB,
+            memory_max: 4 * GB,
+            uptime: 1,
+            ..Container::test("homelab", vmid, &format!("ct{}", vmid), "pve1")
+        }
+    }
+
+    #[test]
+    fn test_node_totals_leave_offline_nodes_out_of_usage() {
+        let all = [
+            node("pve1", true, 10.0, 8),
+            node("pve2", true, 40.0, 24),
+            node("pve3", false, 99.0, 8),
+        ];
+        let totals = nodes(&all);
+        assert_eq!((totals.online, totals.total), (2, 3));
+        assert_eq!(totals.cpu, Some(32.5));
+        assert_eq!(totals.memory_total, 128 * GB);
+        assert_eq!(totals.memory_percent(), Some(50.0));
+        assert_eq!(
+            totals.summary(Units::Short),
+            "Σ 3 nodes · 1 offline · CPU avg 32% · Mem 64.0 GB/128.0 GB"
+        );
+
+        // A filtered subset adds up only what is listed
+        let totals = nodes(all.iter().filter(|n| n.name == "pve3"));
+        assert_eq!(totals.cpu, None);
+        assert_eq!(totals.summary(Units::Short), "Σ 1 nodes · 1 offline");
+    }
+
+    #[test]
+    fn test_guest_totals_count_running_usage_only() {
+        let mut template = guest(900, ContainerStatus::Stopped, 0.0);
+        template.is_template = true;
+        let all = [
+            guest(101, ContainerStatus::Running, 12.5),
+            guest(102, ContainerStatus::Running, 30.0),
+            guest(103, ContainerStatus::Stopped, 0.0),
+            template,
+        ];
+        let totals = guests(&all, |c| c.cpu_usage * 2.0);
+        assert_eq!((totals.running, totals.total), (2, 3));
+        assert_eq!(totals.cpu, 85.0);
+        assert_eq!(totals.memory_used, 4 * GB);
+        assert_eq!(
+            totals.summary(Units::Short),
+            "Σ 2 running · 1 stopped · CPU 85% · Mem 4.0 GB"
+        );
+
+        let totals = guests(all.iter().filter(|c| c.vmid == 102), |c| c.cpu_usage);
+        assert_eq!(
+            totals.summary(Units::Short),
+            "Σ 1 running · CPU 30% · Mem 2.0 GB"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::models::{Node, NodeStatus};
+
+/// How far back the header sparkline reaches.
+pub const TREND_WINDOW: Duration = Duration::from_secs(600);
+
+/// CPU of all online nodes together. Each node counts by its cores, so a busy
+/// 4-core box weighs less than a busy 32-core one; nodes that don't report
+/// cores count as one.
+pub fn cluster_cpu<'a>(nodes: impl IntoIterator<Item = &'a Node>) -> Option<f64> {
+    let (used, cores) = nodes
+        .into_iter()
+        .filter(|n| n.status == NodeStatus::Online)
+        .map(|n| (n.cpu_usage, n.cpu_cores.max(1) as f64))
+        .fold((0.0, 0.0), |(used, cores), (cpu, weight)| {
+            (used + cpu * weight, cores + weight)
+        });
+    (cores > 0.0).then(|| used / cores)
+}
+
+/// One value per refresh over the last `window`, oldest first.
+#[derive(Debug, Clone)]
+pub struct Trend {
+    window: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl Trend {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Add a sample and forget those that fell out of the window.
+    pub fn push(&mut self, at: Instant, value: f64) {
+        self.samples.push_back((at, value));
+        while let Some(&(oldest, _)) = self.samples.front()
+            && at.saturating_duration_since(oldest) > self.window
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// The samples in the window, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = (Instant, f64)> + '_ {
+        self.samples.iter().copied()
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.samples.back().map(|&(_, value)| value)
+    }
+
+    /// The window up to `now` in `count` equal slices, each the average of
+    /// its samples (None for slices without any), oldest first.
+    pub fn buckets(&self, now: Instant, count: usize) -> Vec<Option<f64>> {
+        if count == 0 {
+            return Vec::new();
+        }
+        let mut sums = vec![(0.0, 0); count];
+        let slice = self.window.as_secs_f64() / count as f64;
+        for &(at, value) in &self.samples {
+            let age = now.saturating_duration_since(at).as_secs_f64();
+            if age > self.window.as_secs_f64() {
+                continue;
+            }
+            let back = ((age / slice) as usize).min(count - 1);
+            let (sum, n) = &mut sums[count - 1 - back];
+            *sum += value;
+            *n += 1;
+        }
+        sums.into_iter()
+            .map(|(sum, n)| (n > 0).then(|| sum / n as f64))
+            .collect()
+    }
+}
+
+impl Default for Trend {
+    fn default() -> Self {
+        Self::new(TREND_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(cpu: f64, cores: u32, status: NodeStatus) -> Node {
+        Node {
+            status,
+            cpu_usage: cpu,
+            cpu_cores: cores,
+            ..Node::test("test", "pve")
+        }
+    }
+
+    #[test]
+    fn test_cluster_cpu_is_weighted_by_cores() {
+        let nodes = [
+            node(100.0, 4, NodeStatus::Online),
+            node(0.0, 12, NodeStatus::Online),
+            node(90.0, 64, NodeStatus::Offline),
+        ];
+        assert_eq!(cluster_cpu(&nodes), Some(25.0));
+
+        // Without core counts every node weighs the same
+        let nodes = [
+            node(30.0, 0, NodeStatus::Online),
+            node(10.0, 0, NodeStatus::Online),
+        ];
+        assert_eq!(cluster_cpu(&nodes), Some(20.0));
+
+        assert_eq!(cluster_cpu(&[node(50.0, 8, NodeStatus::Offline)]), None);
+    }
+
+    #[test]
+    fn test_trend_forgets_old_samples() {
+        let start = Instant::now();
+        let mut trend = Trend::new(Duration::from_secs(60));
+        trend.push(start, 10.0);
+        trend.push(start + Duration::from_secs(30), 20.0);
+        trend.push(start + Duration::from_secs(90), 30.0);
+
+        assert_eq!(trend.samples.len(), 2);
+        assert_eq!(trend.latest(), Some(30.0));
+    }
+
+    #[test]
+    fn test_buckets_average_and_leave_gaps() {
+        let start = Instant::now();
+        let mut trend = Trend::new(Duration::from_secs(60));
+        for (secs, value) in [(0, 10.0), (5, 30.0), (50, 40.0), (59, 60.0)] {
+            trend.push(start + Duration::from_secs(secs), value);
+        }
+
+        let now = start + Duration::from_secs(60);
+        assert_eq!(trend.buckets(now, 3), [Some(20.0), None, Some(50.0)]);
+        assert!(trend.buckets(now, 0).is_empty());
+    }
+}
@@ -1,47 +1,183 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::Level;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Gauge, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, Gauge, List, ListItem, Padding, Paragraph, Sparkline, Wrap},
+};
+
+use crate::app::{
+    Allocation, App, InputMode, ItemId, Panel, SortField, clock_time, overcommit_ratio,
+};
+use crate::changes::{Change, Delta};
+use crate::clone::{CloneField, CloneForm, ClonePhase};
+use crate::commands::{self, COMMANDS, CommandSpec};
+use crate::config::{Accessibility, Clock, ThresholdsConfig, Truncation};
+use crate::drain::{Drain, DrainAction, DrainPhase, StepState};
+use crate::format::{
+    DurationStyle, Units, format_bytes, format_bytes_delta, format_duration, format_elapsed,
+    format_rate, format_uptime,
 };
+use crate::groups::{Group, GroupBy, GroupSummary};
+use crate::models::{
+    AgentStatus, Container, ContainerStatus, Disk, EventLevel, FirewallStatus, InterfaceStat,
+    NodeDisks, NodeStatus,
+};
+use crate::node_power::{NodePower, NodePowerPhase, NodePowerRequest};
+use crate::queue::{ActionQueue, ItemState, QueuePhase};
+use crate::resources::{ResourceField, ResourceForm, ResourcePhase};
+use crate::restarts::{RecentFilter, RestartCount};
+use crate::snooze::{Rule, SnoozeMenu};
+use crate::start::{GuestStart, StartPhase};
+use crate::stats::UsageSeries;
+use crate::text::{display_width, fit, pad_left, pad_right, truncate_end, truncate_middle};
+use crate::theme::{self, State, state_glyph};
+
+// Container row layout, in terminal columns
+const NAME_WIDTH_MIN: usize = 8;
+const NAME_WIDTH_CAP: usize = 24;
+const ROW_FIXED_WIDTH: usize = 8; // selection marker, status icon, type label, name padding
+const NODE_COLUMN_WIDTH: usize = 8;
+const CPU_COLUMN_WIDTH: usize = 8;
+const MEMORY_COLUMN_WIDTH: usize = 8;
+const RESTART_COLUMN_WIDTH: usize = 4;
+const DISK_COLUMN_WIDTH: usize = 7;
+const NODE_NAME_WIDTH: usize = 10;
+/// Cursor, status icon and the spaces around the name in a node row.
+const NODE_ROW_FIXED_WIDTH: usize = 3 + NODE_NAME_WIDTH + 1;
+const GAUGE_BAR_MIN: usize = 4;
+const GAUGE_BAR_MAX: usize = 10;
+const COMPARE_NAME_WIDTH: usize = 20;
+const HELP_KEY_WIDTH: usize = 6;
+const STARTUP_PANEL_WIDTH: u16 = 72;
+const DRAIN_LABEL_WIDTH: usize = 24;
+const SPARKLINE_MIN_WIDTH: u16 = 10;
+const SPARKLINE_MAX_WIDTH: u16 = 30;
+
+// Node network line
+const TOP_INTERFACES: usize = 3;
+const NEGLIGIBLE_RATE: f64 = 1024.0; // bytes per second
+
+// Node disk lines
+const MAX_DISK_LINES: usize = 4;
+const DISK_MODEL_WIDTH: usize = 24;
 
-use crate::app::{App, InputMode, Panel};
-use crate::models::{ContainerStatus, NodeStatus, format_bytes, format_uptime};
+// Node package update lines
+const MAX_UPDATE_LINES: usize = 4;
 
 pub fn draw(frame: &mut Frame, app: &App) {
+    let cluster_rows = if app.clusters().next().is_some() {
+        1
+    } else {
+        0
+    };
+    // The node details grow to list its disks and updates, a guest's for
+    // its disk gauge, and either for the figures under their gauges
+    let node_rows = match (app.selected_node(), app.selected_container()) {
+        (Some(node), _) if app.active_panel == Panel::Nodes && app.comparison.is_none() => {
+            node_extra_lines(app, node).len() as u16
+                + if node.disk_total > 0 { 2 } else { 0 }
+                + 2 * usage_rows(app, &ItemId::Node(node.id.clone()))
+        }
+        (_, Some(container))
+            if app.active_panel == Panel::Containers && app.comparison.is_none() =>
+        {
+            (if container.disk_max > 0 { 2 } else { 0 })
+                + 2 * usage_rows(app, &ItemId::Guest(container.id.clone()))
+        }
+        _ => 0,
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3), // Header
-            Constraint::Min(8),    // Main panels
-            Constraint::Length(7), // Detail panel
-            Constraint::Length(1), // Status bar
+            Constraint::Length(3),             // Header
+            Constraint::Length(cluster_rows),  // Cluster strip
+            Constraint::Min(8),                // Main panels
+            Constraint::Length(8 + node_rows), // Detail panel
+            Constraint::Length(1),             // Status bar
         ])
         .split(frame.area());
 
     draw_header(frame, app, chunks[0]);
+    draw_cluster_strip(frame, app, chunks[1]);
 
-    let main_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
-        .split(chunks[1]);
+    if app.last_refresh.is_some() && !app.has_ever_loaded && !app.is_loading() {
+        // Nothing to show yet: explain why instead of drawing empty panels
+        draw_startup_failure(frame, app, chunks[2].union(chunks[3]));
+    } else {
+        let main_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(app.panels.iter().map(|panel| match panel {
+                Panel::Nodes => Constraint::Fill(35),
+                Panel::Groups => Constraint::Fill(25),
+                Panel::Containers => Constraint::Fill(65),
+            }))
+            .split(chunks[2]);
 
-    draw_nodes(frame, app, main_chunks[0]);
-    draw_containers(frame, app, main_chunks[1]);
-    draw_detail_panel(frame, app, chunks[2]);
-    draw_status_bar(frame, app, chunks[3]);
+        for (panel, &area) in app.panels.iter().zip(main_chunks.iter()) {
+            match panel {
+                Panel::Nodes => draw_nodes(frame, app, area),
+                Panel::Groups => draw_groups(frame, app, area),
+                Panel::Containers => draw_containers(frame, app, area),
+            }
+        }
+        draw_detail_panel(frame, app, chunks[3]);
+    }
+    draw_status_bar(frame, app, chunks[4]);
 
     if app.show_help {
-        draw_help_popup(frame);
+        draw_help_popup(frame, app.help_scroll, app.read_only);
+    }
+    if app.show_errors {
+        draw_error_popup(frame, app);
+    }
+    if app.show_events {
+        draw_events_popup(frame, app);
+    }
+    if app.log_view.is_some() {
+        draw_logs_popup(frame, app);
+    }
+    if app.show_debug {
+        draw_debug_popup(frame, app, Instant::now());
+    }
+    if let Some(ref drain) = app.drain {
+        draw_drain_popup(frame, drain);
+    }
+    if let Some(ref form) = app.clone {
+        draw_clone_popup(frame, form);
     }
+    if let Some(ref form) = app.resources {
+        draw_resources_popup(frame, form);
+    }
+    if let Some(ref start) = app.start {
+        draw_start_popup(frame, start, app.units);
+    }
+    if let Some(ref queue) = app.queue {
+        draw_queue_popup(frame, queue);
+    }
+    if let Some(ref request) = app.node_power {
+        draw_node_power_popup(frame, request, app.units);
+    }
+    if let Some(selected) = app.presets_popup {
+        draw_presets_popup(frame, app, selected);
+    }
+    if let Some(selected) = app.providers_popup {
+        draw_providers_popup(frame, app, selected, Instant::now());
+    }
+    if let Some(ref menu) = app.snooze_menu {
+        draw_snooze_popup(frame, menu);
+    }
+    theme::recolor(app.accessibility, frame.buffer_mut());
 }
 
 fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
     let (nodes_online, nodes_total) = app.nodes_summary();
     let (containers_running, containers_total) = app.containers_summary();
 
-    let title = vec![
+    let mut title = vec![
         Span::styled(
             " PULSE ",
             Style::default()
@@ -49,6 +185,26 @@ fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw("| "),
+    ];
+    if app.read_only {
+        title.push(Span::styled(
+            "🔒 read-only ",
+            Style::default().fg(Color::Yellow),
+        ));
+        title.push(Span::raw("| "));
+    }
+    if app.dry_run && !app.read_only {
+        title.push(Span::styled("dry-run ", Style::default().fg(Color::Yellow)));
+        title.push(Span::raw("| "));
+    }
+    if app.alarm.muted && (app.alarm.bell || app.alarm.flash) {
+        title.push(Span::styled(
+            "🔕 muted ",
+            Style::default().fg(Color::Yellow),
+        ));
+        title.push(Span::raw("| "));
+    }
+    title.extend([
         Span::styled(
             format!("Nodes: {}/{}", nodes_online, nodes_total),
             Style::default().fg(if nodes_online == nodes_total {
@@ -66,12 +222,169 @@ fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
                 Color::Yellow
             }),
         ),
+    ]);
+
+    let now = Instant::now();
+    // Left out at startup: counted, or a long list of names would push
+    // the rest off the line
+    let dormant = app
+        .provider_status
+        .iter()
+        .filter(|s| s.dormant && s.disabled)
+        .count();
+    for status in app
+        .provider_status
+        .iter()
+        .filter(|s| !(s.dormant && s.disabled))
+    {
+        title.push(Span::raw(" | "));
+        if status.disabled {
+            title.push(Span::styled(
+                format!("{} off", status.name),
+                Style::default().fg(Color::DarkGray),
+            ));
+            continue;
+        }
+        if status.loading {
+            title.push(Span::styled(
+                format!("{} loading…", status.name),
+                Style::default().fg(Color::DarkGray),
+            ));
+            continue;
+        }
+        let color = if status.last_error.is_some() {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        title.push(Span::styled(
+            status.name.clone(),
+            Style::default().fg(color),
+        ));
+        if !status.permission_issues.is_empty() {
+            title.push(Span::styled(
+                format!(
+                    " ⚠ {} missing permission(s)",
+                    status.permission_issues.len()
+                ),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        if let Some(duplicate) = app.duplicates.iter().find(|d| d.second == status.name) {
+            let hidden = if app.hide_duplicates { ", hidden" } else { "" };
+            title.push(Span::styled(
+                format!(" ⚠ same as {}{}", duplicate.first, hidden),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        if let Some(endpoint) = &status.endpoint {
+            let host = endpoint
+                .url
+                .split_once("://")
+                .map_or(endpoint.url.as_str(), |(_, host)| host);
+            let color = if endpoint.preferred {
+                Color::DarkGray
+            } else {
+                Color::Yellow
+            };
+            title.push(Span::styled(
+                format!(" via {}", host),
+                Style::default().fg(color),
+            ));
+        }
+        if let Some(wait) = status.backoff.retry_in(now) {
+            title.push(Span::styled(
+                format!(
+                    ": down, retrying in {}",
+                    format_duration(wait.as_secs(), DurationStyle::AGE)
+                ),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        if let Some(requests) = status.requests {
+            let mut text = format!(" {} req", requests);
+            if let Some(bytes) = status.bytes {
+                text.push_str(&format!(", {}", format_bytes(bytes, app.units)));
+            }
+            title.push(Span::styled(text, Style::default().fg(Color::DarkGray)));
+        }
+        if let Some(age) = status.staleness(now, app.refresh_interval) {
+            title.push(Span::styled(
+                format!(
+                    " (stale {})",
+                    format_duration(age.as_secs(), DurationStyle::AGE)
+                ),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+    }
+    if dormant > 0 {
+        title.push(Span::raw(" | "));
+        title.push(Span::styled(
+            format!("{} not started", dormant),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    if let Some(provider) = &app.provider_filter {
+        title.push(Span::raw(" | "));
+        title.push(Span::styled(
+            format!("Only: {}", provider),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+
+    if let Some(recent) = app.recent_restarts() {
+        title.push(Span::raw(" | "));
+        title.push(Span::styled(
+            format!("recent restarts: {}", recent),
+            Style::default().fg(if recent > 0 {
+                Color::Yellow
+            } else {
+                Color::Gray
+            }),
+        ));
+    }
+
+    let alerts = app.alert_count();
+    let ha_errors = app.ha_error_count();
+    if ha_errors > 0 {
+        title.push(Span::raw(" | "));
+        title.push(Span::styled(
+            format!("HA errors: {}", ha_errors),
+            ha_error_style(),
+        ));
+    }
+    if alerts > 0 {
+        title.push(Span::raw(" | "));
+        title.push(Span::styled(
+            format!("Alerts: {}", alerts),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    title.extend([
+        Span::raw(" | "),
+        Span::styled(
+            format!("CPU: {}", app.cpu_mode.label()),
+            Style::default().fg(Color::Gray),
+        ),
         Span::raw(" | "),
         Span::styled(
             format!(
-                "Sort: {} {}",
+                "Sort: {}{} {}{}",
+                if app.group_sort_by_node {
+                    "Node → "
+                } else {
+                    ""
+                },
                 app.sort_field.label(),
-                if app.sort_ascending { "^" } else { "v" }
+                if app.sort_ascending { "^" } else { "v" },
+                if app.problems_first {
+                    " [problems first]"
+                } else {
+                    ""
+                }
             ),
             Style::default().fg(Color::Gray),
         ),
@@ -80,46 +393,233 @@ fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
             format!("Refresh: {}", app.time_since_refresh()),
             Style::default().fg(Color::Gray),
         ),
+    ]);
+
+    let title = Line::from(title);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    let text_width = title.width() as u16;
+    // A new critical condition inverts the whole header for a moment
+    let style = if app.alarm.flashing(Instant::now()) {
+        Style::default().add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new(title).style(style).block(block), area);
+
+    // The trend and then the clock only take room the text doesn't need;
+    // the clock goes first when there isn't enough for both
+    let spare = inner.width.saturating_sub(text_width + 1);
+    let trend = app.cpu_trend.latest().and_then(|cpu| {
+        let label = format!(" Cluster CPU {:>3.0}% ", cpu);
+        let width = spare
+            .saturating_sub(label.len() as u16)
+            .min(SPARKLINE_MAX_WIDTH);
+        (width >= SPARKLINE_MIN_WIDTH).then_some((cpu, label, width))
+    });
+    let trend_width = trend
+        .as_ref()
+        .map_or(0, |(_, label, width)| label.len() as u16 + width);
+    let clock = clock_time(SystemTime::now(), app.clock)
+        .map(|time| format!(" {} ", time))
+        .filter(|clock| spare >= trend_width + clock.len() as u16);
+
+    let mut right = inner.right();
+    if let Some(clock) = clock {
+        let width = clock.len() as u16;
+        right -= width;
+        frame.render_widget(
+            Paragraph::new(clock).style(Style::default().fg(Color::White)),
+            Rect::new(right, inner.y, width, 1),
+        );
+    }
+    if let Some((cpu, label, width)) = trend {
+        let x = right - trend_width;
+        let label_area = Rect::new(x, inner.y, label.len() as u16, 1);
+        let chart_area = Rect::new(x + label.len() as u16, inner.y, width, 1);
+        frame.render_widget(
+            Paragraph::new(label).style(Style::default().fg(Color::Gray)),
+            label_area,
+        );
+        let data: Vec<Option<u64>> = app
+            .cpu_trend
+            .buckets(Instant::now(), width as usize)
+            .into_iter()
+            .map(|v| v.map(|cpu| cpu.round().clamp(0.0, 100.0) as u64))
+            .collect();
+        frame.render_widget(
+            Sparkline::default()
+                .data(data)
+                .max(100)
+                .style(Style::default().fg(cpu_color(cpu))),
+            chart_area,
+        );
+    }
+}
+
+fn draw_cluster_strip(frame: &mut Frame, app: &App, area: Rect) {
+    if area.height == 0 {
+        return;
+    }
+
+    let mut spans = Vec::new();
+    for cluster in app.clusters() {
+        if !spans.is_empty() {
+            spans.push(Span::raw(" | "));
+        }
+        let style = if cluster.quorate {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD | Modifier::SLOW_BLINK)
+        };
+        spans.push(Span::styled(format!(" {} ", cluster.summary()), style));
+        if let Some(firewall) = cluster.firewall {
+            spans.push(firewall_span(firewall));
+            spans.push(Span::raw(" "));
+        }
+    }
+
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Every provider failed and none has ever loaded: list each one with its
+/// last error and retry time, centered in `area`.
+fn draw_startup_failure(frame: &mut Frame, app: &App, area: Rect) {
+    let now = Instant::now();
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "Could not load data from any provider",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
     ];
 
-    let header = Paragraph::new(Line::from(title)).block(
+    for status in &app.provider_status {
+        let retry = match status.backoff.retry_in(now) {
+            Some(wait) => format!(
+                "retrying in {}",
+                format_duration(wait.as_secs(), DurationStyle::AGE)
+            ),
+            None => "retrying now".to_string(),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                status.name.clone(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!("  {}", retry), Style::default().fg(Color::DarkGray)),
+        ]));
+        if let Some(ref error) = status.last_error {
+            lines.push(Line::from(Span::styled(
+                format!("  {}", error),
+                Style::default().fg(Color::Red),
+            )));
+        }
+        lines.push(Line::from(""));
+    }
+
+    lines.push(Line::from(Span::styled(
+        "Check the network or VPN and the hosts in your config.",
+        Style::default().fg(Color::Gray),
+    )));
+    lines.push(Line::from(vec![
+        Span::raw("Press "),
+        Span::styled("r", Style::default().fg(Color::Cyan)),
+        Span::raw(" to retry now, "),
+        Span::styled("E", Style::default().fg(Color::Cyan)),
+        Span::raw(" for the error log, "),
+        Span::styled("q", Style::default().fg(Color::Cyan)),
+        Span::raw(" to quit."),
+    ]));
+
+    let width = area.width.min(STARTUP_PANEL_WIDTH);
+    // Long errors wrap; borders and padding take 4 columns and 2 rows
+    let inner_width = width.saturating_sub(4).max(1) as usize;
+    let wrapped: usize = lines
+        .iter()
+        .map(|line| line.width().div_ceil(inner_width).max(1))
+        .sum();
+    let height = (wrapped as u16 + 2).min(area.height);
+    let panel = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
         Block::default()
+            .title(" Waiting for providers ")
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan)),
+            .padding(Padding::horizontal(1))
+            .border_style(Style::default().fg(Color::Red)),
     );
-
-    frame.render_widget(header, area);
+    frame.render_widget(paragraph, panel);
 }
 
 fn draw_nodes(frame: &mut Frame, app: &App, area: Rect) {
     let is_active = app.active_panel == Panel::Nodes;
     let nodes = app.filtered_nodes();
+    let now = Instant::now();
+    let wall_now = unix_now();
+    // Flags after the gauges get room first, so the gauges stay aligned
+    let flags: Vec<Vec<Span>> = nodes
+        .iter()
+        .map(|node| node_flags(app, node, wall_now))
+        .collect();
+    let flags_width = flags
+        .iter()
+        .map(|spans| spans.iter().map(Span::width).sum::<usize>())
+        .max()
+        .unwrap_or(0);
+    let gauges = node_gauges(
+        (area.width as usize).saturating_sub(2 + NODE_ROW_FIXED_WIDTH + flags_width),
+        nodes.iter().any(|node| node.disk_total > 0),
+    );
 
-    let items: Vec<ListItem> = nodes
+    let mut items: Vec<ListItem> = nodes
         .iter()
+        .zip(flags)
         .enumerate()
-        .map(|(i, node)| {
-            let (status_icon, status_color) = match node.status {
-                NodeStatus::Online => ("●", Color::Green),
-                NodeStatus::Offline => ("○", Color::Red),
+        .map(|(i, (node, flags))| {
+            let ha_problem = app.node_ha_problem(node);
+            let (state, status_style) = match node.status {
+                _ if ha_problem.is_some() => (State::Fenced, ha_error_style()),
+                NodeStatus::Online => (State::Up, Style::default().fg(Color::Green)),
+                NodeStatus::Offline => (State::Down, Style::default().fg(Color::Red)),
             };
-
-            let cpu_bar = create_mini_bar(node.cpu_usage, 8);
-            let mem_bar = create_mini_bar(node.memory_percent(), 8);
+            let status_icon = state_glyph(app.accessibility, state);
 
             let selected = i == app.node_index && is_active;
             let prefix = if selected { ">" } else { " " };
 
-            let content = Line::from(vec![
+            let mut spans = vec![
                 Span::raw(prefix),
-                Span::styled(status_icon, Style::default().fg(status_color)),
-                Span::raw(format!(" {:<10} ", truncate(&node.name, 10))),
-                Span::styled("CPU", Style::default().fg(Color::Gray)),
-                Span::raw(cpu_bar),
-                Span::raw(" "),
-                Span::styled("MEM", Style::default().fg(Color::Gray)),
-                Span::raw(mem_bar),
-            ]);
+                Span::styled(status_icon, status_style),
+                Span::raw(format!(" {} ", fit(&node.name, NODE_NAME_WIDTH))),
+            ];
+            let mut metrics = vec![
+                ("C", Some(node.cpu_usage)),
+                ("M", Some(node.memory_percent())),
+            ];
+            if gauges.disk {
+                metrics.push(("D", node.disk_percent()));
+            }
+            for (j, (label, percent)) in metrics.into_iter().enumerate() {
+                if j > 0 {
+                    spans.push(Span::raw(" "));
+                }
+                spans.extend(gauges.spans(label, percent));
+            }
+            spans.extend(flags);
+
+            let stale = app.provider_staleness(node.provider(), now).is_some();
+            let content = Line::from(dim_if_stale(spans, stale));
 
             if selected {
                 ListItem::new(content).style(Style::default().bg(Color::DarkGray))
@@ -135,61 +635,165 @@ fn draw_nodes(frame: &mut Frame, app: &App, area: Rect) {
         Style::default().fg(Color::Gray)
     };
 
-    let title = format!(
-        " Nodes ({}/{}) ",
-        app.nodes_summary().0,
-        app.nodes_summary().1
-    );
+    let (online, total) = app.nodes_summary();
+    let title = if app.detailed_counts {
+        format!(
+            " Nodes ({}) ",
+            status_counts(&[(online, "online"), (total - online, "offline")])
+        )
+    } else {
+        format!(" Nodes ({}/{}) ", online, total)
+    };
 
-    let list = List::new(items).block(
-        Block::default()
-            .title(title)
-            .borders(Borders::ALL)
-            .border_style(border_style),
-    );
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if items.is_empty() && app.is_loading() {
+        items.push(loading_row());
+    }
+    let (list_area, summary_area) = split_summary_row(app, inner);
+    frame.render_widget(List::new(items), list_area);
+    if let Some(summary_area) = summary_area {
+        let summary = app.filtered_node_totals().summary(app.units);
+        frame.render_widget(summary_row(summary, summary_area.width), summary_area);
+    }
+}
 
-    frame.render_widget(list, area);
+/// Stands in for an empty list until the first load has fetched every
+/// provider.
+fn loading_row() -> ListItem<'static> {
+    ListItem::new(Line::styled(
+        " loading…",
+        Style::default().fg(Color::DarkGray),
+    ))
 }
 
-fn draw_containers(frame: &mut Frame, app: &App, area: Rect) {
-    let is_active = app.active_panel == Panel::Containers;
-    let containers = app.filtered_containers();
+/// The inside of a list panel, less its bottom line for the summary row
+/// when that is on and leaves room for a row of the list.
+fn split_summary_row(app: &App, inner: Rect) -> (Rect, Option<Rect>) {
+    if !app.summary_row || inner.height < 2 {
+        return (inner, None);
+    }
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    (rows[0], Some(rows[1]))
+}
+
+/// Totals of what a panel lists, pinned under its rows and never selected.
+/// Parts that don't fit in `width` are left off from the end rather than
+/// cut in half.
+fn summary_row(text: String, width: u16) -> Paragraph<'static> {
+    let mut parts: Vec<&str> = text.split(" · ").collect();
+    while parts.len() > 1 && display_width(&parts.join(" · ")) + 1 > width as usize {
+        parts.pop();
+    }
+    Paragraph::new(Line::from(Span::styled(
+        format!(" {}", parts.join(" · ")),
+        Style::default().fg(Color::DarkGray),
+    )))
+}
+
+/// HA trouble, restarts, disk trouble and pending updates, after a
+/// node's gauges.
+fn node_flags(app: &App, node: &crate::models::Node, wall_now: u64) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    if let Some(problem) = app.node_ha_problem(node) {
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!(" {} ", problem), ha_error_style()));
+    }
+    if let Some(restarts) = app.node_restarts(node) {
+        spans.push(restart_span(Some(restarts), wall_now, 0));
+    }
+    if app.disk_alert(node) {
+        spans.push(Span::styled(
+            " ⚠disk",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    } else if !node.disks.problems().is_empty() {
+        spans.push(snoozed_span());
+    }
+    if let Some(count) = node.pending_updates.filter(|&n| n > 0) {
+        spans.push(Span::styled(
+            format!(" ⬆ {}", count),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    spans
+}
+
+/// " 1 Status  2 Name ▲  3 CPU  4 MemGB " under the containers panel: the
+/// keys that sort by each column, and the direction of the one sorted by.
+fn sort_legend(fields: &[SortField], sorted_by: SortField, ascending: bool) -> Line<'static> {
+    let mut spans = vec![Span::raw(" ")];
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        let mut text = format!("{} {}", i + 1, field.label());
+        if *field == sorted_by {
+            text.push_str(if ascending { " ▲" } else { " ▼" });
+            spans.push(Span::styled(
+                text,
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        } else {
+            spans.push(Span::styled(text, Style::default().fg(Color::DarkGray)));
+        }
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
+}
 
-    let items: Vec<ListItem> = containers
+/// "47 run / 3 stop" for `[ui] detailed_counts`: the first count always,
+/// the others only when not zero.
+fn status_counts(counts: &[(usize, &str)]) -> String {
+    counts
         .iter()
         .enumerate()
-        .map(|(i, container)| {
-            let (status_icon, status_color) = match container.status {
-                ContainerStatus::Running => ("●", Color::Green),
-                ContainerStatus::Stopped => ("○", Color::Red),
-            };
-
-            let type_color = match container.container_type {
-                crate::models::ContainerType::VM => Color::Magenta,
-                crate::models::ContainerType::LXC => Color::Blue,
-            };
+        .filter(|&(i, &(count, _))| i == 0 || count > 0)
+        .map(|(_, (count, label))| format!("{} {}", count, label))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
 
-            let selected = i == app.container_index && is_active;
-            let prefix = if selected { ">" } else { " " };
+/// "All" and then one row per pool, with running guests and CPU.
+fn draw_groups(frame: &mut Frame, app: &App, area: Rect) {
+    let is_active = app.active_panel == Panel::Groups;
+    // Selection marker, the running/total column and the CPU column
+    let label_width = (area.width as usize).saturating_sub(2 + 1 + 8 + 7);
 
-            let content = Line::from(vec![
-                Span::raw(prefix),
-                Span::styled(status_icon, Style::default().fg(status_color)),
-                Span::raw(" "),
-                Span::styled(
-                    format!("{:<3}", container.type_label()),
-                    Style::default().fg(type_color),
+    let all = app.pools_total.as_ref();
+    let rows = std::iter::once(("All", all)).chain(app.pools.iter().map(|p| (p.label(), Some(p))));
+    let items: Vec<ListItem> = rows
+        .enumerate()
+        .map(|(i, (label, summary))| {
+            let selected = i == app.pool_index;
+            let prefix = if selected && is_active { ">" } else { " " };
+            let (counts, cpu) = match summary {
+                Some(s) => (
+                    format!("{}/{}", s.running, s.total),
+                    format!("{:>5.1}%", s.cpu),
                 ),
-                Span::raw(format!(" {:<12} ", truncate(&container.name, 12))),
+                None => (String::new(), String::new()),
+            };
+            let mut style = Style::default();
+            if selected {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            let content = Line::from(vec![
+                Span::styled(format!("{}{}", prefix, fit(label, label_width)), style),
+                Span::styled(format!(" {:>7}", counts), Style::default().fg(Color::Gray)),
                 Span::styled(
-                    format!("{:<8}", truncate(&container.node, 8)),
-                    Style::default().fg(Color::DarkGray),
+                    format!(" {}", cpu),
+                    Style::default().fg(summary.map_or(Color::Gray, |s| cpu_color(s.cpu))),
                 ),
-                Span::raw(format!(" {:>5.1}% ", container.cpu_usage)),
-                Span::raw(format!("{:>8}", format_bytes(container.memory_used))),
             ]);
-
-            if selected {
+            if selected && is_active {
                 ListItem::new(content).style(Style::default().bg(Color::DarkGray))
             } else {
                 ListItem::new(content)
@@ -203,15 +807,9 @@ fn draw_containers(frame: &mut Frame, app: &App, area: Rect) {
         Style::default().fg(Color::Gray)
     };
 
-    let title = format!(
-        " Containers ({}/{}) ",
-        app.containers_summary().0,
-        app.containers_summary().1
-    );
-
     let list = List::new(items).block(
         Block::default()
-            .title(title)
+            .title(format!(" Pools ({}) ", app.pools.len()))
             .borders(Borders::ALL)
             .border_style(border_style),
     );
@@ -219,93 +817,711 @@ fn draw_containers(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(list, area);
 }
 
-fn draw_detail_panel(frame: &mut Frame, app: &App, area: Rect) {
-    let block = Block::default()
-        .title(" Details ")
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Gray));
+fn draw_containers(frame: &mut Frame, app: &App, area: Rect) {
+    let is_active = app.active_panel == Panel::Containers;
+    let containers = app.filtered_containers();
+    let now = Instant::now();
+    let wall_now = unix_now();
 
-    let inner = block.inner(area);
-    frame.render_widget(block, area);
+    let longest_name = containers
+        .iter()
+        .map(|c| display_width(&c.name))
+        .max()
+        .unwrap_or(0);
+    // The disk column sits at the end of the row, so it just narrows the rest
+    let show_disk = app.disk_column || app.sort_field == SortField::Disk;
+    let disk_width = if show_disk { DISK_COLUMN_WIDTH } else { 0 };
+    let mut columns = container_columns(
+        longest_name,
+        app.name_width,
+        app.full_names,
+        (area.width.saturating_sub(2) as usize).saturating_sub(disk_width),
+    );
+    columns.show_disk = show_disk;
+    let sort_fields = columns.sort_fields(app.sort_field);
+    app.sort_columns.replace(sort_fields.clone());
 
-    match app.active_panel {
-        Panel::Nodes => {
-            if let Some(node) = app.selected_node() {
-                draw_node_details(frame, node, inner);
-            } else {
-                let msg =
-                    Paragraph::new("No node selected").style(Style::default().fg(Color::DarkGray));
-                frame.render_widget(msg, inner);
+    let visual = app.visual_range();
+    let mut items: Vec<ListItem> = Vec::new();
+    let mut index = 0;
+    for group in app.container_groups() {
+        let first = index;
+        index += group.members.len();
+        let holds_selection = (first..index).contains(&app.container_index) && is_active;
+        if app.group_by != GroupBy::Off {
+            let selected = holds_selection && app.groups_collapsed;
+            items.push(group_item(app, &group, &columns, selected));
+            if app.groups_collapsed {
+                continue;
             }
         }
-        Panel::Containers => {
-            if let Some(container) = app.selected_container() {
-                draw_container_details(frame, container, inner);
+        for (i, container) in group.members.iter().enumerate() {
+            let selected = first + i == app.container_index && is_active;
+            let mark = if visual.as_ref().is_some_and(|r| r.contains(&(first + i))) {
+                Mark::Provisional
+            } else if app.marked.contains(&container.id) {
+                Mark::Marked
             } else {
-                let msg = Paragraph::new("No container selected")
-                    .style(Style::default().fg(Color::DarkGray));
-                frame.render_widget(msg, inner);
-            }
+                Mark::None
+            };
+            items.push(container_item(
+                app, container, &columns, selected, mark, now, wall_now,
+            ));
         }
     }
-}
 
-fn draw_node_details(frame: &mut Frame, node: &crate::models::Node, area: Rect) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(1),
-            Constraint::Length(2),
-            Constraint::Length(2),
-        ])
-        .split(area);
+    let border_style = if is_active {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default().fg(Color::Gray)
+    };
 
-    // Title line
-    let status_text = match node.status {
-        NodeStatus::Online => Span::styled("Online", Style::default().fg(Color::Green)),
-        NodeStatus::Offline => Span::styled("Offline", Style::default().fg(Color::Red)),
+    let (running, total) = app.containers_summary();
+    let mut title = if app.detailed_counts {
+        let templates = app.containers.iter().filter(|c| c.is_template).count();
+        format!(
+            " Containers ({}) ",
+            status_counts(&[
+                (running, "run"),
+                (total - running, "stop"),
+                (templates, "tpl"),
+            ])
+        )
+    } else {
+        format!(" Containers ({}/{}) ", running, total)
     };
+    if let Some(pool) = app.selected_pool() {
+        title.push_str(&format!("in {} ", pool.label()));
+    }
+    if app.group_by != GroupBy::Off {
+        title.push_str(&format!("by {} ", app.group_by.label()));
+    }
+    if app.recent_filter != RecentFilter::Off {
+        title.push_str(&format!("up {} ", app.recent_filter.label()));
+    }
+    if app.hide_templates {
+        title.push_str("no templates ");
+    }
+    if let Some(range) = &visual {
+        title.push_str(&format!("visual {} ", range.clone().count()));
+    } else if !app.marked.is_empty() {
+        title.push_str(&format!("{} marked ", app.marked.len()));
+    }
 
-    let title_line = Line::from(vec![
-        Span::styled(&node.name, Style::default().add_modifier(Modifier::BOLD)),
-        Span::raw(" | Status: "),
-        status_text,
-        Span::raw(" | Uptime: "),
-        Span::raw(format_uptime(node.uptime)),
-    ]);
-    frame.render_widget(Paragraph::new(title_line), chunks[0]);
+    let mut block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    if is_active {
+        block = block.title_bottom(sort_legend(
+            &sort_fields,
+            app.sort_field,
+            app.sort_ascending,
+        ));
+    }
 
-    // CPU gauge
-    let cpu_gauge = Gauge::default()
-        .block(Block::default().title("CPU"))
-        .gauge_style(Style::default().fg(cpu_color(node.cpu_usage)))
-        .percent(node.cpu_usage.min(100.0) as u16)
-        .label(format!("{:.1}%", node.cpu_usage));
-    frame.render_widget(cpu_gauge, chunks[1]);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if items.is_empty() && app.is_loading() {
+        items.push(loading_row());
+    }
+    let (list_area, summary_area) = split_summary_row(app, inner);
+    frame.render_widget(List::new(items), list_area);
+    if let Some(summary_area) = summary_area {
+        let summary = app.filtered_container_totals().summary(app.units);
+        frame.render_widget(summary_row(summary, summary_area.width), summary_area);
+    }
+}
 
-    // Memory gauge
-    let mem_pct = node.memory_percent();
-    let mem_label = format!(
-        "{:.1}% ({} / {})",
-        mem_pct,
-        format_bytes(node.memory_used),
-        format_bytes(node.memory_total)
-    );
-    let mem_gauge = Gauge::default()
-        .block(Block::default().title("Memory"))
-        .gauge_style(Style::default().fg(cpu_color(mem_pct)))
-        .percent(mem_pct.min(100.0) as u16)
-        .label(mem_label);
-    frame.render_widget(mem_gauge, chunks[2]);
+/// Whether a guest row is marked, or in the visual range about to be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mark {
+    None,
+    Marked,
+    Provisional,
 }
 
-fn draw_container_details(frame: &mut Frame, container: &crate::models::Container, area: Rect) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
+/// One container row; `columns` is shared by every row of the panel.
+fn container_item(
+    app: &App,
+    container: &Container,
+    columns: &ContainerColumns,
+    selected: bool,
+    mark: Mark,
+    now: Instant,
+    wall_now: u64,
+) -> ListItem<'static> {
+    let ha_error = app.guest_ha_error(container);
+    let (state, status_style) = match container.status {
+        _ if container.missing => (State::Missing, Style::default().fg(Color::DarkGray)),
+        _ if ha_error => (State::HaError, ha_error_style()),
+        ContainerStatus::Running => (State::Up, Style::default().fg(Color::Green)),
+        ContainerStatus::Stopped => (State::Down, Style::default().fg(Color::Red)),
+    };
+    // Templates never run; "TPL" in the type column says so instead of a stopped icon
+    let status_icon = if container.is_template {
+        " "
+    } else {
+        state_glyph(app.accessibility, state)
+    };
+    let type_label = if container.is_template {
+        "TPL"
+    } else {
+        container.type_label()
+    };
+
+    let type_color = match container.container_type {
+        crate::models::ContainerType::VM => Color::Magenta,
+        crate::models::ContainerType::LXC => Color::Blue,
+        crate::models::ContainerType::Service => Color::Cyan,
+        crate::models::ContainerType::Process => Color::Gray,
+        crate::models::ContainerType::Docker => Color::LightBlue,
+    };
+
+    // The cursor wins; marks are told apart by glyph, not just color
+    let prefix = match mark {
+        _ if selected => Span::raw(">"),
+        Mark::Marked => Span::styled(
+            "*",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Mark::Provisional => Span::styled("+", Style::default().fg(Color::Cyan)),
+        Mark::None => Span::raw(" "),
+    };
+    let change = app.container_change(container, now);
+    let (cpu_tint, memory_tint) = match change {
+        Some(Change::Usage { cpu, memory }) => (usage_tint(cpu), usage_tint(memory)),
+        _ => (Style::default(), Style::default()),
+    };
+    let name_style = if change == Some(Change::Removed) {
+        Style::default().add_modifier(Modifier::CROSSED_OUT)
+    } else {
+        Style::default()
+    };
+
+    let mut spans = vec![
+        prefix,
+        Span::styled(status_icon, status_style),
+        Span::raw(" "),
+        Span::styled(
+            format!("{:<3}", type_label),
+            Style::default().fg(type_color),
+        ),
+        Span::raw(" "),
+        Span::styled(
+            pad_right(
+                &truncate_name(&container.name, columns.name_width, app.truncation),
+                columns.name_width,
+            ),
+            name_style,
+        ),
+        Span::raw(" "),
+    ];
+    if columns.show_node {
+        spans.push(Span::styled(
+            fit(&container.node, NODE_COLUMN_WIDTH),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    spans.push(Span::raw(" "));
+    spans.push(Span::styled(
+        format!("{:>5.1}%", app.container_cpu(container)),
+        cpu_tint,
+    ));
+    spans.push(Span::raw(" "));
+    if columns.show_memory {
+        // Percent of the limit while sorting by it, otherwise bytes used
+        let memory = if app.sort_field == SortField::Memory {
+            format!("{:.1}%", container.memory_percent())
+        } else {
+            format_bytes(container.memory_used, app.units)
+        };
+        spans.push(Span::styled(
+            pad_left(&memory, MEMORY_COLUMN_WIDTH),
+            memory_tint,
+        ));
+    }
+    if columns.show_disk {
+        let (text, color) = match container.disk_percent() {
+            Some(pct) => (
+                format!("{:.0}%", pct),
+                threshold_color(pct, app.thresholds.disk_critical),
+            ),
+            None => ("n/a".to_string(), Color::DarkGray),
+        };
+        spans.push(Span::styled(
+            pad_left(&text, DISK_COLUMN_WIDTH),
+            Style::default().fg(color),
+        ));
+    }
+    spans.push(restart_span(
+        app.container_restarts(container),
+        wall_now,
+        RESTART_COLUMN_WIDTH,
+    ));
+
+    if let Some(ref lock) = container.lock {
+        spans.push(Span::styled(
+            format!(" 🔒{}", lock),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    if ha_error {
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(" HA error ", ha_error_style()));
+    }
+
+    if container.missing {
+        spans.push(Span::styled(
+            " not found",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    if app.replication_alert(container).is_some() {
+        spans.push(Span::styled(
+            " REPL",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    } else if app.failing_replication(container).is_some() {
+        spans.push(snoozed_span());
+    }
+
+    if app.note_for(container).is_some() {
+        spans.push(Span::styled(" ✎", Style::default().fg(Color::Cyan)));
+    }
+
+    let stale = app.provider_staleness(container.provider(), now).is_some();
+    let mut spans = dim_if_stale(spans, stale);
+    if container.is_template {
+        spans = spans
+            .into_iter()
+            .map(|span| span.patch_style(Style::default().fg(Color::DarkGray)))
+            .collect();
+    }
+    let content = Line::from(spans);
+
+    if selected {
+        ListItem::new(content).style(Style::default().bg(Color::DarkGray))
+    } else if mark == Mark::Provisional {
+        ListItem::new(content).style(Style::default().bg(theme::TINT_VISUAL))
+    } else if change == Some(Change::Added) {
+        ListItem::new(content).style(Style::default().bg(theme::TINT_NEW))
+    } else {
+        ListItem::new(content)
+    }
+}
+
+/// Inverse red, for what the HA manager can't recover on its own: a guest
+/// in error, a node fenced or unknown. Kept in mono mode, which keeps
+/// modifiers.
+fn ha_error_style() -> Style {
+    Style::default()
+        .fg(Color::Red)
+        .add_modifier(Modifier::REVERSED | Modifier::BOLD)
+}
+
+/// Background for a usage value that moved at the last refresh.
+fn usage_tint(delta: Option<Delta>) -> Style {
+    match delta {
+        Some(Delta::Up) => Style::default().bg(theme::TINT_RISE),
+        Some(Delta::Down) => Style::default().bg(theme::TINT_FALL),
+        None => Style::default(),
+    }
+}
+
+/// Summary row of a group, aligned with the container rows' CPU and
+/// memory columns.
+fn group_item(
+    app: &App,
+    group: &Group,
+    columns: &ContainerColumns,
+    selected: bool,
+) -> ListItem<'static> {
+    let arrow = if app.groups_collapsed { "▸" } else { "▾" };
+    let prefix = if selected { ">" } else { " " };
+    let label = format!(
+        "{} ({}/{})",
+        group.label(),
+        group.running(),
+        group.members.len()
+    );
+    let mut text = format!(
+        "{}{} {} ",
+        prefix,
+        arrow,
+        fit(&label, columns.name_width + 4)
+    );
+    if columns.show_node {
+        text.push_str(&" ".repeat(NODE_COLUMN_WIDTH));
+    }
+    text.push_str(&format!(" {:>5.1}% ", app.group_cpu(group)));
+    if columns.show_memory {
+        text.push_str(&format!(
+            "{} / {}",
+            pad_left(
+                &format_bytes(group.memory_used(), app.units),
+                MEMORY_COLUMN_WIDTH
+            ),
+            format_bytes(group.memory_max(), app.units)
+        ));
+    }
+
+    let content = Line::styled(text, Style::default().add_modifier(Modifier::BOLD));
+    if selected {
+        ListItem::new(content).style(Style::default().bg(Color::DarkGray))
+    } else {
+        ListItem::new(content)
+    }
+}
+
+fn draw_detail_panel(frame: &mut Frame, app: &App, area: Rect) {
+    if let Some((a, b)) = &app.comparison
+        && draw_comparison(frame, app, a, b, area)
+    {
+        return;
+    }
+
+    let title = match &app.pinned {
+        Some(ItemId::Node(id)) => {
+            format!(" Details (pinned {}, p on another to compare) ", id.name)
+        }
+        Some(ItemId::Guest(id)) => format!(" Details (pinned {}, p on another to compare) ", id),
+        None => " Details ".to_string(),
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Gray));
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    match app.active_panel {
+        Panel::Nodes => {
+            if let Some(node) = app.selected_node() {
+                draw_node_details(frame, app, node, inner);
+            } else {
+                let msg =
+                    Paragraph::new("No node selected").style(Style::default().fg(Color::DarkGray));
+                frame.render_widget(msg, inner);
+            }
+        }
+        Panel::Groups => match app.selected_pool().or(app.pools_total.as_ref()) {
+            Some(summary) => {
+                let label = match app.selected_pool() {
+                    Some(pool) => pool.label(),
+                    None => "All pools",
+                };
+                draw_group_details(frame, label, summary, app.units, inner);
+            }
+            None => {
+                let msg =
+                    Paragraph::new("No containers").style(Style::default().fg(Color::DarkGray));
+                frame.render_widget(msg, inner);
+            }
+        },
+        Panel::Containers => {
+            if let Some(container) = app.selected_container() {
+                draw_container_details(frame, app, container, inner);
+            } else {
+                let msg = Paragraph::new("No container selected")
+                    .style(Style::default().fg(Color::DarkGray));
+                frame.render_widget(msg, inner);
+            }
+        }
+    }
+}
+
+/// Two items side by side with the differences in the title. Returns false
+/// if either item is gone, so the normal detail panel is shown instead.
+fn draw_comparison(frame: &mut Frame, app: &App, a: &ItemId, b: &ItemId, area: Rect) -> bool {
+    // (name, cpu %, free memory) for the delta line
+    let (left, right) = match (a, b) {
+        (ItemId::Node(a), ItemId::Node(b)) => {
+            let (Some(a), Some(b)) = (app.node_by_id(a), app.node_by_id(b)) else {
+                return false;
+            };
+            (
+                (
+                    a.name.as_str(),
+                    a.cpu_usage,
+                    a.memory_total.saturating_sub(a.memory_used),
+                ),
+                (
+                    b.name.as_str(),
+                    b.cpu_usage,
+                    b.memory_total.saturating_sub(b.memory_used),
+                ),
+            )
+        }
+        (ItemId::Guest(a), ItemId::Guest(b)) => {
+            let (Some(a), Some(b)) = (app.container_by_id(a), app.container_by_id(b)) else {
+                return false;
+            };
+            (
+                (
+                    a.name.as_str(),
+                    app.container_cpu(a),
+                    a.memory_max.saturating_sub(a.memory_used),
+                ),
+                (
+                    b.name.as_str(),
+                    app.container_cpu(b),
+                    b.memory_max.saturating_sub(b.memory_used),
+                ),
+            )
+        }
+        _ => return false,
+    };
+
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let title = Line::from(vec![
+        Span::raw(format!(
+            " Compare {} vs {} | ",
+            truncate_end(left.0, COMPARE_NAME_WIDTH),
+            truncate_end(right.0, COMPARE_NAME_WIDTH)
+        )),
+        Span::styled(format!("Δ CPU {:+.1}%", right.1 - left.1), highlight),
+        Span::raw(" | "),
+        Span::styled(
+            format!(
+                "Δ mem free {}",
+                format_bytes_delta(left.2, right.2, app.units)
+            ),
+            highlight,
+        ),
+        Span::raw(" | Esc: exit "),
+    ]);
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .spacing(2)
+        .split(inner);
+
+    for (id, column) in [(a, columns[0]), (b, columns[1])] {
+        match id {
+            ItemId::Node(id) => {
+                if let Some(node) = app.node_by_id(id) {
+                    draw_node_details(frame, app, node, column);
+                }
+            }
+            ItemId::Guest(id) => {
+                if let Some(container) = app.container_by_id(id) {
+                    draw_container_details(frame, app, container, column);
+                }
+            }
+        }
+    }
+    true
+}
+
+fn draw_node_details(frame: &mut Frame, app: &App, node: &crate::models::Node, area: Rect) {
+    let usage = app.usage.get(&ItemId::Node(node.id.clone()));
+    let gauge_rows = 2 + usage_rows(app, &ItemId::Node(node.id.clone()));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(gauge_rows),
+            Constraint::Length(gauge_rows),
+            Constraint::Length(if node.disk_total > 0 { 2 } else { 0 }),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    // Title line
+    let status_text = match node.status {
+        NodeStatus::Online => Span::styled("Online", Style::default().fg(Color::Green)),
+        NodeStatus::Offline => Span::styled("Offline", Style::default().fg(Color::Red)),
+    };
+
+    let mut title_spans = vec![
+        Span::styled(&node.name, Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(" | Status: "),
+        status_text,
+        Span::raw(" | Uptime: "),
+        Span::raw(format_uptime(node.uptime)),
+    ];
+    if let Some(restarts) = app.node_restarts(node) {
+        title_spans.extend(restart_detail(restarts, unix_now()));
+    }
+    frame.render_widget(Paragraph::new(Line::from(title_spans)), chunks[0]);
+
+    // CPU gauge
+    let cpu_gauge = Gauge::default()
+        .block(Block::default().title("CPU"))
+        .gauge_style(Style::default().fg(cpu_color(node.cpu_usage)))
+        .percent(node.cpu_usage.min(100.0) as u16)
+        .label(format!("{:.1}%", node.cpu_usage));
+    let cpu_usage = usage.and_then(|u| usage_line(&u.cpu, node.cpu_usage, |v| v, app.clock));
+    draw_gauge(frame, cpu_gauge, cpu_usage, chunks[1]);
+
+    // Memory gauge
+    let mem_pct = node.memory_percent();
+    let mem_label = format!(
+        "{:.1}% ({} / {})",
+        mem_pct,
+        format_bytes(node.memory_used, app.units),
+        format_bytes(node.memory_total, app.units)
+    );
+    let mem_gauge = Gauge::default()
+        .block(Block::default().title("Memory"))
+        .gauge_style(Style::default().fg(cpu_color(mem_pct)))
+        .percent(mem_pct.min(100.0) as u16)
+        .label(mem_label);
+    let mem_usage = usage.and_then(|u| usage_line(&u.memory, mem_pct, |v| v, app.clock));
+    draw_gauge(frame, mem_gauge, mem_usage, chunks[2]);
+
+    if let Some(disk_pct) = node.disk_percent() {
+        let disk_gauge = Gauge::default()
+            .block(Block::default().title("Root disk"))
+            .gauge_style(Style::default().fg(cpu_color(disk_pct)))
+            .percent(disk_pct.min(100.0) as u16)
+            .label(format!(
+                "{:.1}% ({} / {})",
+                disk_pct,
+                format_bytes(node.disk_used, app.units),
+                format_bytes(node.disk_total, app.units)
+            ));
+        frame.render_widget(disk_gauge, chunks[3]);
+    }
+
+    if let Some(summary) = network_summary(&node.interfaces, app.units) {
+        frame.render_widget(Paragraph::new(summary), chunks[4]);
+    }
+    frame.render_widget(Paragraph::new(node_extra_lines(app, node)), chunks[5]);
+}
+
+/// Rows under each detail gauge for the item's rolling figures: one once
+/// it has been sampled.
+fn usage_rows(app: &App, id: &ItemId) -> u16 {
+    u16::from(app.usage.contains_key(id))
+}
+
+/// A gauge (title row and bar) with the rolling figures, if any, below.
+fn draw_gauge(frame: &mut Frame, gauge: Gauge, usage: Option<Line<'static>>, area: Rect) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(area);
+    frame.render_widget(gauge, rows[0]);
+    if let Some(usage) = usage {
+        frame.render_widget(
+            Paragraph::new(usage).style(Style::default().fg(Color::DarkGray)),
+            rows[1],
+        );
+    }
+}
+
+/// "now 43.0% · avg 38.2% (1m) · peak 91.0% at 13:22:05", with the
+/// recorded values passed through `scale` into the gauge's terms. The peak
+/// reads "4m ago" with the clock off.
+fn usage_line(
+    series: &UsageSeries,
+    now: f64,
+    scale: impl Fn(f64) -> f64,
+    clock: Clock,
+) -> Option<Line<'static>> {
+    let average = series.average()?;
+    let mut text = format!("now {:.1}% · avg {:.1}% (1m)", now, scale(average));
+    if let Some((peak, at)) = series.peak() {
+        let when = match clock_time(at, clock) {
+            Some(time) => format!("at {}", time),
+            None => {
+                let age = SystemTime::now().duration_since(at).unwrap_or_default();
+                format!("{} ago", format_duration(age.as_secs(), DurationStyle::AGE))
+            }
+        };
+        text.push_str(&format!(" · peak {:.1}% {}", scale(peak), when));
+    }
+    Some(Line::raw(text))
+}
+
+fn draw_group_details(
+    frame: &mut Frame,
+    label: &str,
+    summary: &GroupSummary,
+    units: Units,
+    area: Rect,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
             Constraint::Length(1),
             Constraint::Length(2),
             Constraint::Length(2),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let title = Line::from(vec![
+        Span::styled(
+            label.to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!(
+            " | Guests: {} running / {}",
+            summary.running, summary.total
+        )),
+    ]);
+    frame.render_widget(Paragraph::new(title), chunks[0]);
+
+    let cpu_gauge = Gauge::default()
+        .block(Block::default().title("CPU"))
+        .gauge_style(Style::default().fg(cpu_color(summary.cpu)))
+        .percent(summary.cpu.min(100.0) as u16)
+        .label(format!("{:.1}%", summary.cpu));
+    frame.render_widget(cpu_gauge, chunks[1]);
+
+    let mem_pct = if summary.memory_max > 0 {
+        summary.memory_used as f64 / summary.memory_max as f64 * 100.0
+    } else {
+        0.0
+    };
+    let mem_gauge = Gauge::default()
+        .block(Block::default().title("Memory"))
+        .gauge_style(Style::default().fg(cpu_color(mem_pct)))
+        .percent(mem_pct.min(100.0) as u16)
+        .label(format!(
+            "{:.1}% ({} / {})",
+            mem_pct,
+            format_bytes(summary.memory_used, units),
+            format_bytes(summary.memory_max, units)
+        ));
+    frame.render_widget(mem_gauge, chunks[2]);
+}
+
+fn draw_container_details(
+    frame: &mut Frame,
+    app: &App,
+    container: &crate::models::Container,
+    area: Rect,
+) {
+    let disk_rows = if container.disk_max > 0 { 2 } else { 0 };
+    let usage = app.usage.get(&ItemId::Guest(container.id.clone()));
+    let gauge_rows = 2 + usage_rows(app, &ItemId::Guest(container.id.clone()));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(gauge_rows),
+            Constraint::Length(gauge_rows),
+            Constraint::Length(disk_rows),
+            Constraint::Length(1),
         ])
         .split(area);
 
@@ -317,20 +1533,46 @@ fn draw_container_details(frame: &mut Frame, container: &crate::models::Containe
         crate::models::ContainerType::LXC => {
             Span::styled("LXC Container", Style::default().fg(Color::Blue))
         }
+        crate::models::ContainerType::Service => {
+            Span::styled("systemd unit", Style::default().fg(Color::Cyan))
+        }
+        crate::models::ContainerType::Process => {
+            Span::styled("process", Style::default().fg(Color::Gray))
+        }
+        crate::models::ContainerType::Docker => {
+            Span::styled("Docker container", Style::default().fg(Color::LightBlue))
+        }
     };
 
     let status_span = match container.status {
+        _ if container.missing => Span::styled("Not found", Style::default().fg(Color::DarkGray)),
+        _ if container.is_template => {
+            Span::styled("Template", Style::default().fg(Color::DarkGray))
+        }
         ContainerStatus::Running => Span::styled("Running", Style::default().fg(Color::Green)),
         ContainerStatus::Stopped => Span::styled("Stopped", Style::default().fg(Color::Red)),
     };
 
-    let title_line = Line::from(vec![
+    let mut title_spans = vec![
         Span::styled(
             &container.name,
             Style::default().add_modifier(Modifier::BOLD),
         ),
-        Span::raw(format!(" (ID: {}) | ", container.vmid)),
+        Span::raw(match container.container_type {
+            // Units and Docker containers have no numeric id
+            crate::models::ContainerType::Service | crate::models::ContainerType::Docker => {
+                " | ".to_string()
+            }
+            crate::models::ContainerType::Process => format!(" (PID: {}) | ", container.vmid),
+            _ => format!(" (ID: {}) | ", container.vmid),
+        }),
         type_span,
+    ];
+    if let Some(os) = &container.os_info {
+        title_spans.push(Span::raw(" | OS: "));
+        title_spans.push(Span::raw(os));
+    }
+    title_spans.extend([
         Span::raw(" | Node: "),
         Span::raw(&container.node),
         Span::raw(" | "),
@@ -338,48 +1580,296 @@ fn draw_container_details(frame: &mut Frame, container: &crate::models::Containe
         Span::raw(" | Uptime: "),
         Span::raw(format_uptime(container.uptime)),
     ]);
-    frame.render_widget(Paragraph::new(title_line), chunks[0]);
+    if let Some(restarts) = app.container_restarts(container) {
+        title_spans.extend(restart_detail(restarts, unix_now()));
+    }
+    if let Some(agent) = app.details_for(container).and_then(|d| d.agent) {
+        title_spans.push(Span::raw(" | "));
+        title_spans.push(agent_badge(agent, app.accessibility));
+    }
+    if let Some(firewall) = app.details_for(container).and_then(|d| d.firewall) {
+        title_spans.push(Span::raw(" | "));
+        title_spans.push(firewall_span(firewall));
+    }
+    if let Some(message) = container.lock_message() {
+        title_spans.push(Span::raw(" | "));
+        title_spans.push(Span::styled(
+            format!("🔒 {}", message),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(title_spans)), chunks[0]);
 
     // CPU gauge
+    let cpu = app.container_cpu(container);
     let cpu_gauge = Gauge::default()
-        .block(Block::default().title("CPU"))
-        .gauge_style(Style::default().fg(cpu_color(container.cpu_usage)))
-        .percent(container.cpu_usage.min(100.0) as u16)
-        .label(format!("{:.1}%", container.cpu_usage));
-    frame.render_widget(cpu_gauge, chunks[1]);
+        .block(Block::default().title(format!("CPU (% of {})", app.cpu_mode.label())))
+        .gauge_style(Style::default().fg(cpu_color(cpu)))
+        .percent(cpu.min(100.0) as u16)
+        .label(format!("{:.1}% · {} cores", cpu, container.max_cpu));
+    let cpu_usage =
+        usage.and_then(|u| usage_line(&u.cpu, cpu, |v| app.scale_cpu(container, v), app.clock));
+    draw_gauge(frame, cpu_gauge, cpu_usage, chunks[1]);
 
-    // Memory gauge
-    let mem_pct = container.memory_percent();
-    let mem_label = format!(
-        "{:.1}% ({} / {})",
-        mem_pct,
-        format_bytes(container.memory_used),
-        format_bytes(container.memory_max)
-    );
+    // Memory gauge, driven by the in-guest figure when the balloon driver reports one
+    let guest_memory = app.details_for(container).and_then(|d| d.memory.as_ref());
+    let (mem_pct, mem_label) = match guest_memory {
+        Some(memory) => {
+            let pct = memory.guest_percent();
+            (
+                pct,
+                format!(
+                    "{:.1}% in guest ({} / {}) · allocated {}",
+                    pct,
+                    format_bytes(memory.guest_used, app.units),
+                    format_bytes(memory.guest_total, app.units),
+                    format_bytes(memory.allocated, app.units)
+                ),
+            )
+        }
+        None => {
+            let pct = container.memory_percent();
+            (
+                pct,
+                format!(
+                    "{:.1}% ({} / {})",
+                    pct,
+                    format_bytes(container.memory_used, app.units),
+                    format_bytes(container.memory_max, app.units)
+                ),
+            )
+        }
+    };
     let mem_gauge = Gauge::default()
         .block(Block::default().title("Memory"))
         .gauge_style(Style::default().fg(cpu_color(mem_pct)))
         .percent(mem_pct.min(100.0) as u16)
         .label(mem_label);
-    frame.render_widget(mem_gauge, chunks[2]);
+    // The rolling figures are the host's view even when the gauge shows the
+    // guest's own, which is only fetched for the selection
+    let mem_usage =
+        usage.and_then(|u| usage_line(&u.memory, container.memory_percent(), |v| v, app.clock));
+    draw_gauge(frame, mem_gauge, mem_usage, chunks[2]);
+
+    // Disk gauge; VMs report no usage without the guest agent
+    if disk_rows > 0 {
+        let (pct, label) = match (container.disk_percent(), container.disk_used) {
+            (Some(pct), Some(used)) => (
+                pct,
+                format!(
+                    "{:.1}% ({} / {})",
+                    pct,
+                    format_bytes(used, app.units),
+                    format_bytes(container.disk_max, app.units)
+                ),
+            ),
+            _ => (
+                0.0,
+                format!("n/a of {}", format_bytes(container.disk_max, app.units)),
+            ),
+        };
+        let disk_gauge = Gauge::default()
+            .block(Block::default().title("Disk"))
+            .gauge_style(Style::default().fg(threshold_color(pct, app.thresholds.disk_critical)))
+            .percent(pct.min(100.0) as u16)
+            .label(label);
+        frame.render_widget(disk_gauge, chunks[3]);
+    }
+
+    // The guest's note, replication status, or a note that details were
+    // skipped this cycle
+    if let Some(note) = app.note_for(container) {
+        let line = Line::from(vec![
+            Span::styled("✎ ", Style::default().fg(Color::Cyan)),
+            Span::raw(note.text.clone()),
+        ]);
+        frame.render_widget(Paragraph::new(line), chunks[4]);
+    } else if let Some(job) = app.failing_replication(container) {
+        let now = unix_now();
+        let line = match app.snooze_for(&container.id.to_string(), Rule::Replication) {
+            Some(snooze) => Paragraph::new(format!(
+                "{} (snoozed {})",
+                job.failure_summary(now),
+                snooze.describe(now)
+            ))
+            .style(Style::default().fg(Color::DarkGray)),
+            None => Paragraph::new(job.failure_summary(now)).style(Style::default().fg(Color::Red)),
+        };
+        frame.render_widget(line, chunks[4]);
+    } else if app.details_for(container).is_some_and(|d| d.deferred) {
+        let line = Paragraph::new("Details deferred: request budget exhausted")
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(line, chunks[4]);
+    }
 }
 
-fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
-    let (left_text, style) = match app.input_mode {
-        InputMode::Search => {
-            let text = format!(" Search: {}_ ", app.search_query);
-            (text, Style::default().fg(Color::Yellow))
+/// "FW: on (3 rules)"; a firewall on without rules stands out, as it
+/// usually blocks more than intended.
+fn firewall_span(firewall: FirewallStatus) -> Span<'static> {
+    let color = match firewall {
+        FirewallStatus::On { rules: Some(0) } => Color::Yellow,
+        FirewallStatus::On { .. } => Color::Green,
+        FirewallStatus::Off | FirewallStatus::Unknown => Color::DarkGray,
+    };
+    Span::styled(
+        format!("FW: {}", firewall.label()),
+        Style::default().fg(color),
+    )
+}
+
+/// "GA" for a VM with the guest agent enabled: green when it answers, gray
+/// when it doesn't (crossed out too where colors are swapped or dropped).
+fn agent_badge(agent: AgentStatus, mode: Accessibility) -> Span<'static> {
+    match agent {
+        AgentStatus::Responding => Span::styled("GA", Style::default().fg(Color::Green)),
+        AgentStatus::NotResponding if mode == Accessibility::Standard => {
+            Span::styled("GA", Style::default().fg(Color::DarkGray))
         }
-        InputMode::Normal => {
-            if let Some(ref error) = app.error_message {
-                (
-                    format!(" Error: {} ", error),
-                    Style::default().fg(Color::Red),
-                )
+        AgentStatus::NotResponding => Span::styled(
+            "GA",
+            Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::CROSSED_OUT),
+        ),
+    }
+}
+
+fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let width = area.width as usize;
+    let (left_text, style) = match app.input_mode {
+        _ if let Some(prompt) = &app.note_prompt => {
+            let mut text = format!(" Note on {}: {}_ ", prompt.guest, prompt.text);
+            text.push_str(&fit_hints(
+                NOTE_HINTS,
+                width.saturating_sub(display_width(&text)),
+            ));
+            (
+                truncate_end(&text, width),
+                Style::default().fg(Color::Yellow),
+            )
+        }
+        _ if let Some(prompt) = &app.goto_prompt => {
+            let what = if app.active_panel == Panel::Nodes {
+                "node"
+            } else {
+                "VMID"
+            };
+            let mut text = if prompt.confirmed {
+                format!(" Goto {}: {} ", what, prompt.text)
+            } else {
+                format!(" Goto {}: {}_ ", what, prompt.text)
+            };
+            let mut style = Style::default().fg(Color::Yellow);
+            match app.goto_matches() {
+                (0, _) if !prompt.text.is_empty() => {
+                    text.push_str(" no match");
+                    style = style.fg(Color::LightRed);
+                }
+                (count, Some(position)) if count > 1 => {
+                    text.push_str(&format!(" {}/{} ", position, count));
+                }
+                _ => {}
+            }
+            let hints = if prompt.confirmed {
+                GOTO_CYCLE_HINTS
+            } else {
+                GOTO_HINTS
+            };
+            text.push_str(&fit_hints(
+                hints,
+                width.saturating_sub(display_width(&text)),
+            ));
+            (truncate_end(&text, width), style)
+        }
+        InputMode::Search if let Some(prompt) = &app.preset_prompt => {
+            let mut text = format!(" Save preset as: {}_ ", prompt.name);
+            let mut style = Style::default().fg(Color::Yellow);
+            if prompt.confirm_overwrite {
+                text.push_str(&format!(
+                    " {} exists; Enter again to overwrite",
+                    prompt.name.trim()
+                ));
+                style = style.fg(Color::LightRed);
+            } else {
+                text.push_str(&fit_hints(
+                    PRESET_NAME_HINTS,
+                    width.saturating_sub(display_width(&text)),
+                ));
+            }
+            (truncate_end(&text, width), style)
+        }
+        InputMode::Search => {
+            let flags: Vec<&str> = [
+                (app.search_options.case_sensitive, "Aa"),
+                (app.search_options.whole_word, "W"),
+            ]
+            .into_iter()
+            .filter_map(|(on, flag)| on.then_some(flag))
+            .collect();
+            let prompt = if flags.is_empty() {
+                "Search".to_string()
+            } else {
+                format!("Search [{}]", flags.join("|"))
+            };
+            let mut text = format!(" {}: {}_ ", prompt, app.search_query);
+            let mut style = Style::default().fg(Color::Yellow);
+            // Filtering still works (as plain text), so only flag the problem
+            if let Some(error) = app.search_error() {
+                text.push_str(&format!(" {} (matching as text)", error));
+                style = style.fg(Color::LightRed);
+            } else {
+                text.push_str(&fit_hints(
+                    SEARCH_HINTS,
+                    width.saturating_sub(display_width(&text)),
+                ));
+            }
+            (truncate_end(&text, width), style)
+        }
+        InputMode::Command => {
+            let mut text = format!(" :{}_ ", app.command_input);
+            let mut style = Style::default().fg(Color::Cyan);
+            if let Some(ref error) = app.command_error {
+                text.push_str(&format!(" {}", error));
+                style = style.fg(Color::LightRed);
+            } else if !app.command_candidates.is_empty() {
+                text.push_str(&format!(" {}", app.command_candidates.join("  ")));
+            } else {
+                text.push_str(&fit_hints(
+                    COMMAND_HINTS,
+                    width.saturating_sub(display_width(&text)),
+                ));
+            }
+            (truncate_end(&text, width), style)
+        }
+        InputMode::Normal => {
+            if let Some(ref notice) = app.notice {
+                let message = format!(" {} ", notice);
+                let style = Style::default().fg(Color::Green);
+                draw_banner(
+                    frame,
+                    area,
+                    &message,
+                    style,
+                    &commands::keyed(NOTICE_BANNER_HINTS),
+                );
+                return;
+            } else if let Some(ref error) = app.error_message {
+                // The full text lives in the error popup
+                let message = format!(" Error: {} ", error);
+                let style = Style::default().fg(Color::Red);
+                draw_banner(
+                    frame,
+                    area,
+                    &message,
+                    style,
+                    &commands::keyed(ERROR_BANNER_HINTS),
+                );
+                return;
             } else {
-                let text =
-                    " q:Quit  Tab:Panel  j/k:Nav  r:Refresh  s:Sort  /:Search  ?:Help ".to_string();
-                (text, Style::default().fg(Color::Gray))
+                (
+                    fit_hints(&normal_hints(app), width),
+                    Style::default().fg(Color::Gray),
+                )
             }
         }
     };
@@ -388,116 +1878,3326 @@ fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(status, area);
 }
 
-fn draw_help_popup(frame: &mut Frame) {
-    let area = centered_rect(50, 60, frame.area());
+/// A notice or error on the left of the status line, with the keys a
+/// newcomer needs most kept on the right.
+fn draw_banner(frame: &mut Frame, area: Rect, message: &str, style: Style, hints: &[(&str, &str)]) {
+    let (message, hints) = banner_layout(message, hints, area.width as usize);
+    let [message_area, hints_area] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(display_width(&hints) as u16),
+    ])
+    .areas(area);
+    frame.render_widget(Paragraph::new(message).style(style), message_area);
+    frame.render_widget(
+        Paragraph::new(hints).style(Style::default().fg(Color::Gray)),
+        hints_area,
+    );
+}
+
+/// Columns a message keeps before the hints beside it give way.
+const BANNER_MESSAGE_MIN: usize = 24;
+
+/// Split `width` columns between `message` and `hints`. Hints are dropped
+/// from the left while the message would get fewer than
+/// `BANNER_MESSAGE_MIN` columns (or fewer than it needs, when shorter),
+/// down to the last hint's key alone (help's), which always stays; the
+/// message is then cut in the middle to fit the rest.
+fn banner_layout(message: &str, hints: &[(&str, &str)], width: usize) -> (String, String) {
+    let wanted = display_width(message).min(BANNER_MESSAGE_MIN);
+    let last = hints.last().map_or("?", |&(key, _)| key);
+    let hints = (0..hints.len())
+        .map(|first| fit_hints(&hints[first..], usize::MAX))
+        .chain([format!(" {} ", last)])
+        .find(|text| display_width(text) + wanted <= width)
+        .unwrap_or_else(|| last.to_string());
+    let message = truncate_middle(message, width.saturating_sub(display_width(&hints)));
+    (message, hints)
+}
+
+/// Beside an error or notice, as a command and a label: dropped from the
+/// left as the line narrows.
+const ERROR_BANNER_HINTS: &[(&str, &str)] = &[
+    ("errors", "Details"),
+    ("refresh", "Retry"),
+    ("quit", "Quit"),
+    ("help", "Help"),
+];
+const NOTICE_BANNER_HINTS: &[(&str, &str)] =
+    &[("refresh", "Refresh"), ("quit", "Quit"), ("help", "Help")];
+
+/// Keys the main loop handles itself in text entry and popups.
+const SEARCH_HINTS: &[(&str, &str)] = &[
+    ("Enter", "Apply"),
+    ("Esc", "Clear"),
+    ("Backspace", "Delete"),
+    ("^I", "Case"),
+    ("^O", "Word"),
+    ("^S", "Save preset"),
+];
+const PRESET_NAME_HINTS: &[(&str, &str)] = &[("Enter", "Save"), ("Esc", "Cancel")];
+const NOTE_HINTS: &[(&str, &str)] = &[("Enter", "Save (empty removes)"), ("Esc", "Cancel")];
+const PRESETS_HINTS: &[(&str, &str)] = &[("Enter/1-9", "Apply"), ("d", "Delete"), ("Esc", "Close")];
+const PROVIDERS_HINTS: &[(&str, &str)] = &[
+    ("Enter", "Show only/all"),
+    ("d", "Disable/enable"),
+    ("p", "Re-check permissions"),
+    ("Esc", "Close"),
+];
+const SNOOZE_HINTS: &[(&str, &str)] = &[("Enter/1-4", "Choose"), ("Esc", "Cancel")];
+const GOTO_HINTS: &[(&str, &str)] = &[("Enter", "Select"), ("Esc", "Back")];
+const GOTO_CYCLE_HINTS: &[(&str, &str)] = &[("Enter", "Next match"), ("any key", "Done")];
+const VISUAL_HINTS: &[(&str, &str)] = &[
+    ("j/k", "Extend"),
+    ("Space/Enter", "Mark range"),
+    ("Esc", "Cancel"),
+];
+const COMMAND_HINTS: &[(&str, &str)] = &[("Tab", "Complete"), ("Enter", "Run"), ("Esc", "Cancel")];
+const HELP_HINTS: &[(&str, &str)] = &[("j/k", "Scroll"), ("any key", "Close")];
+const ERROR_HINTS: &[(&str, &str)] = &[("j/k", "Scroll"), ("Esc", "Close")];
+const DEBUG_HINTS: &[(&str, &str)] = &[("F12/Esc", "Close")];
+const LOG_HINTS: &[(&str, &str)] = &[
+    ("j/k", "Scroll"),
+    ("G", "Follow"),
+    ("f", "Level"),
+    ("Esc", "Close"),
+];
+const DRAIN_CONFIRM_HINTS: &[(&str, &str)] = &[("Enter/y", "Start drain"), ("Esc/n", "Cancel")];
+const DRAIN_FINISHED_HINTS: &[(&str, &str)] = &[("Enter/Esc", "Close")];
+const CLONE_HINTS: &[(&str, &str)] = &[
+    ("Enter", "Clone"),
+    ("Tab", "Next field"),
+    ("Space", "Change"),
+    ("Esc", "Cancel"),
+];
+const RESOURCE_HINTS: &[(&str, &str)] =
+    &[("Enter", "Save"), ("Tab", "Next field"), ("Esc", "Cancel")];
+
+/// Hints for what is on screen: the open popup's keys, or the active panel's
+/// commands.
+fn normal_hints(app: &App) -> Vec<(&'static str, &'static str)> {
+    let fixed = if app.show_help {
+        HELP_HINTS
+    } else if app.show_errors || app.show_events {
+        ERROR_HINTS
+    } else if app.log_view.is_some() {
+        LOG_HINTS
+    } else if app.show_debug {
+        DEBUG_HINTS
+    } else if let Some(drain) = &app.drain {
+        match drain.phase {
+            DrainPhase::Confirm => DRAIN_CONFIRM_HINTS,
+            DrainPhase::Running => &[],
+            DrainPhase::Finished => DRAIN_FINISHED_HINTS,
+        }
+    } else if app.presets_popup.is_some() {
+        PRESETS_HINTS
+    } else if app.providers_popup.is_some() {
+        PROVIDERS_HINTS
+    } else if app.snooze_menu.is_some() {
+        SNOOZE_HINTS
+    } else if app.visual_anchor.is_some() {
+        VISUAL_HINTS
+    } else if let Some(form) = &app.clone {
+        match form.phase {
+            ClonePhase::Editing => CLONE_HINTS,
+            ClonePhase::Running { .. } => &[],
+            ClonePhase::Done | ClonePhase::Failed(_) => DRAIN_FINISHED_HINTS,
+        }
+    } else if let Some(form) = &app.resources {
+        match form.phase {
+            ResourcePhase::Editing => RESOURCE_HINTS,
+            ResourcePhase::Saved { .. } | ResourcePhase::Failed(_) => DRAIN_FINISHED_HINTS,
+        }
+    } else {
+        return commands::hints(app.active_panel, |action| app.refusal(action).is_some());
+    };
+    fixed.to_vec()
+}
+
+/// " key:Label  key:Label " with as many hints as fit in `width`, in order,
+/// so the most important ones stay on narrow terminals.
+fn fit_hints(hints: &[(&str, &str)], width: usize) -> String {
+    let mut text = String::new();
+    for (key, label) in hints {
+        let hint = format!(" {}:{} ", key, label);
+        if display_width(&text) + display_width(&hint) > width {
+            break;
+        }
+        text.push_str(&hint);
+    }
+    text
+}
+
+fn draw_error_popup(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 70, frame.area());
 
     frame.render_widget(Clear, area);
 
-    let help_text = vec![
-        Line::from(Span::styled(
-            "Keyboard Shortcuts",
-            Style::default().add_modifier(Modifier::BOLD),
-        )),
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("  q      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Quit application"),
-        ]),
-        Line::from(vec![
-            Span::styled("  Tab    ", Style::default().fg(Color::Cyan)),
-            Span::raw("Switch between panels"),
-        ]),
-        Line::from(vec![
-            Span::styled("  j/Down ", Style::default().fg(Color::Cyan)),
-            Span::raw("Move selection down"),
-        ]),
-        Line::from(vec![
-            Span::styled("  k/Up   ", Style::default().fg(Color::Cyan)),
-            Span::raw("Move selection up"),
-        ]),
-        Line::from(vec![
-            Span::styled("  r      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Refresh data"),
-        ]),
-        Line::from(vec![
-            Span::styled("  s      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Cycle sort field"),
-        ]),
-        Line::from(vec![
-            Span::styled("  S      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Toggle sort order"),
-        ]),
+    let now = SystemTime::now();
+    let mut lines = Vec::new();
+    for entry in &app.error_log {
+        let age = now.duration_since(entry.at).unwrap_or_default();
+        let mut heading = vec![
+            Span::styled(
+                entry.provider.clone(),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                format!(
+                    "  {} ago",
+                    format_duration(age.as_secs(), DurationStyle::AGE)
+                ),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ];
+        if entry.count > 1 {
+            heading.push(Span::styled(
+                format!(" (x{})", entry.count),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        lines.push(Line::from(heading));
+        lines.push(Line::from(entry.message.clone()));
+        lines.push(Line::from(""));
+    }
+
+    let popup = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((app.error_scroll, 0))
+        .block(
+            Block::default()
+                .title(" Errors (j/k: scroll, Esc: close) ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red)),
+        );
+
+    frame.render_widget(popup, area);
+}
+
+fn draw_events_popup(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 70, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    // Without a header clock, still show local times rather than none
+    let clock = match app.clock {
+        Clock::Off => Clock::Local,
+        clock => clock,
+    };
+    let mut lines = Vec::new();
+    if app.events.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No events yet.",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for event in app.events.iter() {
+        let at = UNIX_EPOCH + Duration::from_secs(event.time);
+        let style = match event.level {
+            EventLevel::Error => Style::default().fg(Color::Red),
+            EventLevel::Warning => Style::default().fg(Color::Yellow),
+            EventLevel::Info => Style::default(),
+        };
+        let mut line = vec![Span::styled(
+            format!("{} ", clock_time(at, clock).unwrap_or_default()),
+            Style::default().fg(Color::DarkGray),
+        )];
+        if let Some(node) = &event.node {
+            line.push(Span::styled(
+                format!("{} ", node),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        }
+        line.push(Span::styled(
+            format!("{}: ", event.origin()),
+            Style::default().fg(Color::DarkGray),
+        ));
+        line.push(Span::styled(event.message.clone(), style));
+        lines.push(Line::from(line));
+    }
+
+    let popup = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((app.events_scroll, 0))
+        .block(
+            Block::default()
+                .title(format!(
+                    " Events: {} (j/k: scroll, Esc: close) ",
+                    app.events.len()
+                ))
+                .borders(Borders::ALL),
+        );
+
+    frame.render_widget(popup, area);
+}
+
+/// `F12`: what pulse itself is doing, from `app.diagnostics`, the refresh
+/// schedule and the provider backoffs.
+fn draw_debug_popup(frame: &mut Frame, app: &App, now: Instant) {
+    let area = centered_rect(80, 70, frame.area());
+    frame.render_widget(Clear, area);
+
+    let diagnostics = &app.diagnostics;
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+    let elapsed = |d: Option<Duration>| d.map_or("-".to_string(), format_elapsed);
+
+    let interval = app.schedule.interval(app.refresh_interval);
+    let mut schedule = match app.schedule.next_in(now, app.refresh_interval) {
+        Duration::ZERO => "due now".to_string(),
+        wait => format!("next in {}", format_elapsed(wait)),
+    };
+    schedule.push_str(&format!(", every {}", format_elapsed(interval)));
+    if interval > app.refresh_interval {
+        schedule.push_str(" (stretched while quiet)");
+    }
+    let follow_ups = app.schedule.pending_follow_ups();
+    if follow_ups > 0 {
+        schedule.push_str(&format!(", {} follow-up(s) pending", follow_ups));
+    }
+
+    let mut lines = vec![
         Line::from(vec![
-            Span::styled("  /      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Enter search mode"),
+            Span::styled("Refreshes  ", heading),
+            Span::raw(format!(
+                "{}, last {}, slowest {}",
+                diagnostics.refreshes,
+                elapsed(diagnostics.last_refresh),
+                format_elapsed(diagnostics.slowest_refresh)
+            )),
         ]),
         Line::from(vec![
-            Span::styled("  Esc    ", Style::default().fg(Color::Cyan)),
-            Span::raw("Clear search / Exit mode"),
+            Span::styled("Schedule   ", heading),
+            Span::raw(schedule),
         ]),
         Line::from(vec![
-            Span::styled("  ?      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Toggle this help"),
+            Span::styled("Frames     ", heading),
+            Span::raw(format!(
+                "{}, last {}, average {}, slowest {}",
+                diagnostics.frames,
+                elapsed(diagnostics.last_frame),
+                elapsed(diagnostics.average_frame),
+                format_elapsed(diagnostics.slowest_frame)
+            )),
         ]),
-        Line::from(""),
-        Line::from(Span::styled(
-            "Press any key to close",
-            Style::default().fg(Color::DarkGray),
-        )),
+        Line::raw(""),
+        Line::styled(
+            format!(
+                "{:<16}{:>9}{:>7}{:>9}{:>8}{:>9}  Backoff",
+                "Provider", "Refreshes", "Errors", "Requests", "Last", "Slowest"
+            ),
+            heading,
+        ),
     ];
+    for timings in diagnostics.providers() {
+        let backoff = app
+            .provider_status
+            .iter()
+            .find(|s| s.name == timings.name)
+            .map(|s| &s.backoff)
+            .filter(|b| b.failures() > 0);
+        let backoff = match backoff {
+            Some(b) => match b.retry_in(now) {
+                Some(wait) => format!("{} failed, retry in {}", b.failures(), format_elapsed(wait)),
+                None => format!("{} failed, due", b.failures()),
+            },
+            None => "-".to_string(),
+        };
+        let style = if timings.errors > 0 {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::styled(
+            format!(
+                "{:<16}{:>9}{:>7}{:>9}{:>8}{:>9}  {}",
+                fit(&timings.name, 15),
+                timings.refreshes,
+                timings.errors,
+                timings.requests,
+                format_elapsed(timings.last_duration),
+                format_elapsed(timings.slowest),
+                backoff
+            ),
+            style,
+        ));
+    }
+
+    lines.push(Line::raw(""));
+    lines.push(Line::styled(
+        format!("{:<16}{:>9}{:>11}", "Buffer", "Entries", "Memory"),
+        heading,
+    ));
+    for (name, entries, bytes) in app.buffer_sizes() {
+        lines.push(Line::raw(format!(
+            "{:<16}{:>9}{:>11}",
+            name,
+            entries,
+            format!("~{}", format_bytes(bytes as u64, app.units))
+        )));
+    }
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Diagnostics (F12/Esc: close) ")
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn logs_area(area: Rect) -> Rect {
+    centered_rect(90, 80, area)
+}
+
+/// Rows of log the `l` popup shows in a terminal of `area`, for paging.
+pub fn logs_height(area: Rect) -> usize {
+    logs_area(area).height.saturating_sub(2) as usize
+}
+
+/// `l`: pulse's own log, newest at the bottom. Long lines are scrolled
+/// sideways rather than wrapped so one line stays one row.
+fn draw_logs_popup(frame: &mut Frame, app: &App) {
+    let Some(view) = &app.log_view else {
+        return;
+    };
+    let area = logs_area(frame.area());
+    frame.render_widget(Clear, area);
+
+    let lines = app.log_lines();
+    let window = view.window(&lines, logs_height(frame.area()));
+    let mut rows: Vec<Line> = lines[window]
+        .iter()
+        .map(|line| {
+            let style = match line.level {
+                Level::Error => Style::default().fg(Color::Red),
+                Level::Warn => Style::default().fg(Color::Yellow),
+                Level::Info => Style::default(),
+                Level::Debug | Level::Trace => Style::default().fg(Color::DarkGray),
+            };
+            Line::from(Span::styled(line.text.clone(), style))
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Line::from(Span::styled(
+            "Nothing logged at this level yet.",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    let position = if view.anchor.is_some() {
+        "scrolled"
+    } else {
+        "following"
+    };
+    let popup = Paragraph::new(rows).scroll((0, view.column)).block(
+        Block::default()
+            .title(format!(
+                " Log: {} and up, {} (j/k/g/G: scroll, h/l: sideways, f: level, Esc: close) ",
+                view.min_level.to_string().to_lowercase(),
+                position
+            ))
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn draw_drain_popup(frame: &mut Frame, drain: &Drain) {
+    let area = centered_rect(70, 60, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let mut lines = Vec::new();
+    if drain.steps.is_empty() {
+        lines.push(Line::from("No running guests on this node; nothing to do."));
+    }
+    for step in &drain.steps {
+        let (icon, color) = match step.state {
+            StepState::Pending => ("·", Color::DarkGray),
+            StepState::Running { .. } => ("…", Color::Yellow),
+            StepState::Done => ("✓", Color::Green),
+            StepState::Failed(_) => ("✗", Color::Red),
+        };
+        let action = match &step.action {
+            DrainAction::Migrate { target } => Span::raw(format!("migrate → {}", target)),
+            DrainAction::Shutdown { reason } => Span::styled(
+                format!("shut down ({})", reason),
+                Style::default().fg(Color::Yellow),
+            ),
+            DrainAction::Skip { reason } => Span::styled(
+                format!("skip ({})", reason),
+                Style::default().fg(Color::DarkGray),
+            ),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!(" {} ", icon), Style::default().fg(color)),
+            Span::raw(format!("{}  ", pad_right(&step.label, DRAIN_LABEL_WIDTH))),
+            action,
+        ]));
+        if let StepState::Failed(ref error) = step.state {
+            lines.push(Line::from(Span::styled(
+                format!("     {}", error),
+                Style::default().fg(Color::Red),
+            )));
+        }
+    }
+
+    lines.push(Line::from(""));
+    match drain.phase {
+        DrainPhase::Confirm => lines.push(Line::from(Span::styled(
+            "Enter/y: start   Esc/n: cancel",
+            Style::default().fg(Color::Cyan),
+        ))),
+        DrainPhase::Running => lines.push(Line::from(Span::styled(
+            "Working… one guest at a time, stops at the first failure",
+            Style::default().fg(Color::DarkGray),
+        ))),
+        DrainPhase::Finished => {
+            let failed = drain
+                .steps
+                .iter()
+                .any(|s| matches!(s.state, StepState::Failed(_)));
+            lines.push(Line::from(Span::styled(
+                drain.summary(),
+                Style::default()
+                    .fg(if failed { Color::Red } else { Color::Green })
+                    .add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+    }
 
-    let help = Paragraph::new(help_text).block(
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
         Block::default()
-            .title(" Help ")
+            .title(format!(" Drain {} ", drain.node.name))
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan)),
+            .border_style(Style::default().fg(Color::Yellow)),
     );
 
-    frame.render_widget(help, area);
+    frame.render_widget(popup, area);
 }
 
-// Helper functions
+/// Saved presets in name order, numbered for the 1-9 keys, with their query
+/// and the toggles they set.
+/// Marks an item whose alert is snoozed, in place of the alert badge.
+fn snoozed_span() -> Span<'static> {
+    Span::styled(" zzz", Style::default().fg(Color::DarkGray))
+}
 
-fn create_mini_bar(percent: f64, width: usize) -> String {
-    let filled = ((percent / 100.0) * width as f64).round() as usize;
-    let empty = width.saturating_sub(filled);
-    format!("[{}{}]", "=".repeat(filled), " ".repeat(empty))
+fn draw_snooze_popup(frame: &mut Frame, menu: &SnoozeMenu) {
+    let choices = menu.choices();
+    let rules: Vec<_> = menu.rules.iter().map(|r| r.label()).collect();
+    let height = choices.len() as u16 + 4;
+    let area = centered_rect(40, 100, frame.area());
+    let area = Rect {
+        y: area.y + area.height.saturating_sub(height) / 2,
+        height: height.min(area.height),
+        ..area
+    };
+
+    frame.render_widget(Clear, area);
+
+    let mut lines = vec![Line::from(Span::styled(
+        format!("Alerts: {}", rules.join(", ")),
+        Style::default().fg(Color::DarkGray),
+    ))];
+    lines.push(Line::from(""));
+    for (i, choice) in choices.iter().enumerate() {
+        let label = choice.map_or("wake up now", |length| length.label());
+        let style = if i == menu.selected {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!(" {} ", i + 1), Style::default().fg(Color::DarkGray)),
+            Span::styled(format!(" {} ", label), style),
+        ]));
+    }
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(" Snooze {} ", menu.label))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+    frame.render_widget(popup, area);
 }
 
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() > max_len {
-        format!("{}~", &s[..max_len - 1])
-    } else {
-        s.to_string()
+/// Every provider with its state, endpoint, counts, totals and fetch
+/// figures; `»` marks the one the panels are filtered to.
+fn draw_providers_popup(frame: &mut Frame, app: &App, selected: usize, now: Instant) {
+    let area = centered_rect(90, 50, frame.area());
+    frame.render_widget(Clear, area);
+
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+    let percent = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.0}%", v));
+    let mut lines = vec![Line::styled(
+        format!(
+            "  {:<16}{:<16}{:<20}{:>7}{:>9}{:>6}{:>6}{:>8}{:>7}",
+            "Provider", "State", "Endpoint", "Nodes", "Guests", "CPU", "Mem", "Fetch", "Errors"
+        ),
+        heading,
+    )];
+    if app.provider_status.is_empty() {
+        lines.push(Line::styled(
+            "  No provider has been refreshed yet.",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    for (i, status) in app.provider_status.iter().enumerate() {
+        let (state, color) = if status.dormant && status.disabled {
+            ("not started".to_string(), Color::DarkGray)
+        } else if status.dormant {
+            ("starting".to_string(), Color::Yellow)
+        } else if status.disabled {
+            ("disabled".to_string(), Color::DarkGray)
+        } else if let Some(wait) = status.backoff.retry_in(now) {
+            (format!("down, retry {}", format_elapsed(wait)), Color::Red)
+        } else if status.last_error.is_some() {
+            ("failing".to_string(), Color::Red)
+        } else if status.loading {
+            ("loading".to_string(), Color::Yellow)
+        } else if status.last_success.is_none() {
+            ("not loaded".to_string(), Color::Yellow)
+        } else {
+            ("ok".to_string(), Color::Green)
+        };
+        let endpoint = status.endpoint.as_ref().map_or("-", |endpoint| {
+            endpoint
+                .url
+                .split_once("://")
+                .map_or(endpoint.url.as_str(), |(_, host)| host)
+        });
+        let totals = app.provider_totals(&status.name);
+        let timings = app
+            .diagnostics
+            .providers()
+            .iter()
+            .find(|t| t.name == status.name);
+        let marker = if app.provider_filter.as_ref() == Some(&status.name) {
+            "» "
+        } else {
+            "  "
+        };
+        let name_style = if i == selected {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::raw(marker),
+            Span::styled(format!("{:<15}", fit(&status.name, 15)), name_style),
+            Span::raw(" "),
+            Span::styled(format!("{:<16}", state), Style::default().fg(color)),
+            Span::raw(format!(
+                "{:<20}{:>7}{:>9}{:>6}{:>6}{:>8}{:>7}",
+                fit(endpoint, 19),
+                format!("{}/{}", totals.nodes_online, totals.nodes_total),
+                format!("{}/{}", totals.guests_running, totals.guests_total),
+                percent(totals.cpu),
+                percent(totals.memory),
+                timings.map_or("-".to_string(), |t| format_elapsed(t.last_duration)),
+                timings.map_or(0, |t| t.errors),
+            )),
+        ]));
+    }
+    if !app.duplicates.is_empty() {
+        lines.push(Line::raw(""));
     }
+    for duplicate in &app.duplicates {
+        let outcome = if app.hide_duplicates {
+            format!("showing {}'s", duplicate.first)
+        } else {
+            "[general] hide_duplicates shows them once".to_string()
+        };
+        lines.push(Line::styled(
+            format!(
+                "  ⚠ {}: {} guests alike; {}",
+                duplicate.describe(),
+                duplicate.guests,
+                outcome
+            ),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Providers ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+    frame.render_widget(popup, area);
 }
 
-fn cpu_color(percent: f64) -> Color {
-    if percent >= 90.0 {
-        Color::Red
-    } else if percent >= 70.0 {
-        Color::Yellow
-    } else {
-        Color::Green
+fn draw_presets_popup(frame: &mut Frame, app: &App, selected: usize) {
+    let area = centered_rect(60, 50, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let mut lines = Vec::new();
+    if app.presets.is_empty() {
+        lines.push(Line::from("No presets yet."));
+        lines.push(Line::from(Span::styled(
+            "Search with / and press Ctrl+s to save the filters as one.",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for (i, (name, preset)) in app.presets.iter().enumerate() {
+        let number = if i < 9 {
+            format!("{}", i + 1)
+        } else {
+            " ".to_string()
+        };
+        let style = if i == selected {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        let mut toggles = Vec::new();
+        if preset.case_sensitive {
+            toggles.push("Aa".to_string());
+        }
+        if preset.whole_word {
+            toggles.push("W".to_string());
+        }
+        if preset.recent != RecentFilter::Off {
+            toggles.push(format!("up {}", preset.recent.label()));
+        }
+        if preset.hide_templates {
+            toggles.push("no templates".to_string());
+        }
+        let mut spans = vec![
+            Span::styled(
+                format!(" {} ", number),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled(format!(" {} ", name), style),
+            Span::raw(format!(" {}", preset.query)),
+        ];
+        if !toggles.is_empty() {
+            spans.push(Span::styled(
+                format!("  [{}]", toggles.join(", ")),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        lines.push(Line::from(spans));
     }
+
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(" Filter presets ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+
+    frame.render_widget(popup, area);
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
+fn draw_clone_popup(frame: &mut Frame, form: &CloneForm) {
+    let area = centered_rect(60, 50, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let editing = form.phase == ClonePhase::Editing;
+    let field = |field: CloneField, label: &str, value: String| {
+        let focused = editing && form.field == field;
+        let style = if focused {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        Line::from(vec![
+            Span::styled(
+                format!(" {:<6} ", label),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled(format!(" {} ", value), style),
         ])
-        .split(r);
+    };
+    let node = &form.targets[form.target];
+    let mode = match (form.full, form.is_template) {
+        (true, _) => "full copy",
+        (false, true) => "linked (shares the template's disks)",
+        (false, false) => "linked",
+    };
+    let mut lines = vec![
+        field(CloneField::Vmid, "VMID", format!("{}_", form.vmid)),
+        field(CloneField::Name, "Name", format!("{}_", form.name)),
+        field(
+            CloneField::Target,
+            "Node",
+            if form.target == 0 {
+                format!("{} (same node)", node)
+            } else {
+                node.clone()
+            },
+        ),
+        field(CloneField::Full, "Clone", mode.to_string()),
+        Line::from(""),
+    ];
+    if let Some(error) = &form.error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    match &form.phase {
+        ClonePhase::Editing => lines.push(Line::from(Span::styled(
+            "Tab: next field   Space: change   Enter: clone   Esc: cancel",
+            Style::default().fg(Color::Cyan),
+        ))),
+        ClonePhase::Running { .. } => lines.push(Line::from(Span::styled(
+            "Cloning…",
+            Style::default().fg(Color::Yellow),
+        ))),
+        ClonePhase::Done => {
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "Cloned to {}; it appears with the next refresh",
+                    form.vmid.trim()
+                ),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        ClonePhase::Failed(error) => {
+            lines.push(Line::from(Span::styled(
+                format!("Clone failed: {}", error),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+    }
 
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(format!(" Clone {} ", form.label))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+
+    frame.render_widget(popup, area);
+}
+
+fn draw_resources_popup(frame: &mut Frame, form: &ResourceForm) {
+    let area = centered_rect(60, 40, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let editing = form.phase == ResourcePhase::Editing;
+    let field = |field: ResourceField, label: &str, value: String, note: String| {
+        let focused = editing && form.field == field;
+        let style = if focused {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        Line::from(vec![
+            Span::styled(
+                format!(" {:<6} ", label),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled(format!(" {} ", value), style),
+            Span::styled(note, Style::default().fg(Color::DarkGray)),
         ])
-        .split(popup_layout[1])[1]
+    };
+    let cores_note = match (form.cores.trim().is_empty(), form.node_cores) {
+        (true, 0) => " no limit".to_string(),
+        (true, cores) => format!(" no limit, node has {}", cores),
+        (false, 0) => String::new(),
+        (false, cores) => format!(" node has {}", cores),
+    };
+    let memory_note = match form.node_memory_mb {
+        0 => " MiB".to_string(),
+        total => format!(" MiB, node has {}", total),
+    };
+    let mut lines = vec![
+        field(
+            ResourceField::Cores,
+            "Cores",
+            format!("{}_", form.cores),
+            cores_note,
+        ),
+        field(
+            ResourceField::Memory,
+            "Memory",
+            format!("{}_", form.memory),
+            memory_note,
+        ),
+        Line::from(""),
+    ];
+    if let Some(error) = &form.error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    match &form.phase {
+        ResourcePhase::Editing => {
+            if let Some(warning) = form.warning() {
+                lines.push(Line::from(Span::styled(
+                    format!("⚠ {}", warning),
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
+            lines.push(Line::from(Span::styled(
+                "Tab: next field   Enter: save   Esc: cancel",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        ResourcePhase::Saved { pending } => {
+            let (text, color) = match pending {
+                Some(pending) => (format!("Saved, but {}", pending), Color::Yellow),
+                None => ("Saved and applied".to_string(), Color::Green),
+            };
+            lines.push(Line::from(Span::styled(
+                text,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        ResourcePhase::Failed(error) => {
+            lines.push(Line::from(Span::styled(
+                format!("Change failed: {}", error),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+    }
+
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(format!(" Resources of {} ", form.label))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+
+    frame.render_widget(popup, area);
+}
+
+fn draw_queue_popup(frame: &mut Frame, queue: &ActionQueue) {
+    let area = centered_rect(70, 60, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let mut lines = Vec::new();
+    for item in &queue.items {
+        let (icon, color) = match item.state {
+            ItemState::Pending => ("·", Color::DarkGray),
+            ItemState::Running { .. } => ("…", Color::Yellow),
+            ItemState::Done => ("✓", Color::Green),
+            ItemState::Failed(_) => ("✗", Color::Red),
+            ItemState::Cancelled => ("-", Color::DarkGray),
+        };
+        let state = match &item.state {
+            ItemState::Pending => "pending",
+            ItemState::Running { .. } => "running",
+            ItemState::Done => "done",
+            ItemState::Failed(_) => "failed",
+            ItemState::Cancelled => "cancelled",
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!(" {} ", icon), Style::default().fg(color)),
+            Span::raw(format!("{}  ", pad_right(&item.label, DRAIN_LABEL_WIDTH))),
+            Span::styled(state, Style::default().fg(color)),
+        ]));
+        if let ItemState::Failed(ref error) = item.state
+            && queue.show_details
+        {
+            lines.push(Line::from(Span::styled(
+                format!("     {}", error),
+                Style::default().fg(Color::Red),
+            )));
+        }
+    }
+
+    lines.push(Line::from(""));
+    match queue.phase {
+        QueuePhase::Confirm => lines.push(Line::from(Span::styled(
+            format!(
+                "Enter/y: {} {} guest(s), {} at a time   Esc/n: cancel",
+                queue.action.describe(),
+                queue.items.len(),
+                queue.concurrency
+            ),
+            Style::default().fg(Color::Cyan),
+        ))),
+        QueuePhase::Running => lines.push(Line::from(Span::styled(
+            "Working…   Esc/c: cancel what hasn't started",
+            Style::default().fg(Color::DarkGray),
+        ))),
+        QueuePhase::Finished => {
+            let failed = queue.has_failures();
+            let mut summary = queue.summary();
+            if failed && !queue.show_details {
+                summary.push_str(" — press Enter for details");
+            }
+            lines.push(Line::from(Span::styled(
+                summary,
+                Style::default()
+                    .fg(if failed { Color::Red } else { Color::Green })
+                    .add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+    }
+
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(format!(" Queue: {} ", queue.action.describe()))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+
+    frame.render_widget(popup, area);
+}
+
+fn draw_start_popup(frame: &mut Frame, start: &GuestStart, units: Units) {
+    let area = centered_rect(60, 30, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let mut lines = vec![Line::from(format!(
+        "Start {} on {}?",
+        start.label, start.node
+    ))];
+    if let Some(shortfall) = &start.shortfall {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("⚠ {} — start anyway?", shortfall.describe(units)),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+    lines.push(Line::from(""));
+    match &start.phase {
+        StartPhase::Confirm => lines.push(Line::from(Span::styled(
+            "Enter/y: start   Esc/n: cancel",
+            Style::default().fg(Color::Cyan),
+        ))),
+        StartPhase::Running { .. } => lines.push(Line::from(Span::styled(
+            "Starting…",
+            Style::default().fg(Color::Yellow),
+        ))),
+        StartPhase::Done => {
+            lines.push(Line::from(Span::styled(
+                "Started; it shows as running with the next refresh",
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        StartPhase::Failed(error) => {
+            lines.push(Line::from(Span::styled(
+                format!("Start failed: {}", error),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+    }
+
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(format!(" Start {} ", start.label))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+
+    frame.render_widget(popup, area);
+}
+
+fn draw_node_power_popup(frame: &mut Frame, request: &NodePowerRequest, units: Units) {
+    let area = centered_rect(60, 40, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let verb = match request.command {
+        NodePower::Reboot => "Reboot",
+        NodePower::Shutdown => "Shut down",
+    };
+    let mut lines = vec![
+        Line::from(format!("{} {}?", verb, request.node.name)),
+        Line::from(""),
+    ];
+    lines.extend(request.impact.summary(units).into_iter().map(Line::from));
+    lines.push(Line::from(""));
+    match &request.phase {
+        NodePowerPhase::Confirm => {
+            lines.push(Line::from(vec![
+                Span::raw("Type the node's name to confirm: "),
+                Span::styled(
+                    format!("{}_", request.typed),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+            ]));
+            let hint = if request.confirmed() {
+                "Enter: confirm   Esc: cancel"
+            } else {
+                "Esc: cancel"
+            };
+            lines.push(Line::from(Span::styled(
+                hint,
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        NodePowerPhase::Sent => {
+            lines.push(Line::from(Span::styled(
+                format!("{} sent; the node goes offline shortly", verb),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        NodePowerPhase::Failed(error) => {
+            lines.push(Line::from(Span::styled(
+                format!("{} failed: {}", verb, error),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Esc: close",
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+    }
+
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(format!(" {} {} ", verb, request.node.name))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red)),
+    );
+
+    frame.render_widget(popup, area);
+}
+
+fn draw_help_popup(frame: &mut Frame, scroll: u16, read_only: bool) {
+    let area = centered_rect(50, 90, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let mut help_text = Vec::new();
+    // Every key runs a palette command, so both come from one registry
+    let shown = |c: &&CommandSpec| !(read_only && commands::mutates(c));
+    for command in COMMANDS.iter().filter(shown).filter(|c| !c.keys.is_empty()) {
+        help_text.push(help_line(&command.keys.join("/"), command.help));
+    }
+    help_text.push(help_line(":", "Command palette (Tab completes)"));
+    help_text.extend([
+        Line::from(""),
+        Line::from(Span::styled(
+            "Commands",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+    ]);
+    for command in COMMANDS.iter().filter(|c| c.keys.is_empty()) {
+        help_text.push(Line::from(vec![
+            Span::styled(
+                format!("  :{}", commands::usage(command)),
+                Style::default().fg(Color::Cyan),
+            ),
+            Span::raw(format!("  {}", command.help)),
+        ]));
+    }
+    help_text.extend([
+        Line::from(""),
+        Line::from(Span::styled(
+            "Search",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        help_line("word", "Name contains word"),
+        help_line("f:val", "Field contains val; f is name, node, status,"),
+        help_line("", "type, provider or lock (lock: = any lock)"),
+        help_line("cpu>50", "CPU/mem % compare: cpu, mem with > >= < <= ="),
+        help_line("", "e.g. node:pve2 status:running cpu>50 media"),
+        help_line("Ctrl+I", "Toggle case-sensitive matching (Aa)"),
+        help_line("Ctrl+O", "Toggle whole-word matching (W)"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "j/k to scroll, any other key to close",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ]);
+
+    let help = Paragraph::new(help_text).scroll((scroll, 0)).block(
+        Block::default()
+            .title(" Keyboard Shortcuts (j/k: scroll) ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+
+    frame.render_widget(help, area);
+}
+
+// Helper functions
+
+/// Busiest interfaces with their rates, e.g.
+/// "Network: vmbr0 ↓12 MB/s ↑1 MB/s · eno1 ↓3 KB/s ↑2 KB/s · +4 more".
+/// Interfaces below `NEGLIGIBLE_RATE` are only counted.
+fn network_summary(interfaces: &[InterfaceStat], units: Units) -> Option<String> {
+    if interfaces.is_empty() {
+        return None;
+    }
+    let mut busy: Vec<&InterfaceStat> = interfaces
+        .iter()
+        .filter(|i| i.total_rate() >= NEGLIGIBLE_RATE)
+        .collect();
+    if busy.is_empty() {
+        return Some(format!("Network: idle ({} interfaces)", interfaces.len()));
+    }
+    busy.sort_by(|a, b| b.total_rate().total_cmp(&a.total_rate()));
+    busy.truncate(TOP_INTERFACES);
+
+    let mut parts: Vec<String> = busy
+        .iter()
+        .map(|i| {
+            format!(
+                "{} ↓{} ↑{}",
+                i.name,
+                format_rate(i.rx_rate, units),
+                format_rate(i.tx_rate, units)
+            )
+        })
+        .collect();
+    let hidden = interfaces.len() - busy.len();
+    if hidden > 0 {
+        parts.push(format!("+{} more", hidden));
+    }
+    Some(format!("Network: {}", parts.join(" · ")))
+}
+
+/// Disk and package update lines under the node gauges.
+fn node_extra_lines(app: &App, node: &crate::models::Node) -> Vec<Line<'static>> {
+    let mut lines = app
+        .allocations()
+        .get(&node.id)
+        .map(|allocation| allocation_lines(node, allocation, &app.thresholds, app.units))
+        .unwrap_or_default();
+    lines.extend(pressure_lines(node));
+    lines.extend(disk_lines(node, app.units));
+    lines.extend(update_lines(node));
+    lines
+}
+
+/// Guest memory and cores against the node's, e.g. "Mem allocated: 96.0 GB /
+/// 64.0 GB — 1.5× overcommit", followed by the figure with stopped guests
+/// started too when there are any.
+fn allocation_lines(
+    node: &crate::models::Node,
+    allocation: &Allocation,
+    thresholds: &ThresholdsConfig,
+    units: Units,
+) -> Vec<Line<'static>> {
+    if allocation.memory_all == 0 && allocation.cores_all == 0 {
+        return Vec::new();
+    }
+    let memory = overcommit_ratio(allocation.memory, node.memory_total).map(|ratio| {
+        allocation_line(
+            "Mem",
+            format!(
+                "{} / {}",
+                format_bytes(allocation.memory, units),
+                format_bytes(node.memory_total, units)
+            ),
+            ratio,
+            (allocation.memory_all != allocation.memory).then(|| {
+                (
+                    format_bytes(allocation.memory_all, units),
+                    overcommit_ratio(allocation.memory_all, node.memory_total).unwrap_or(ratio),
+                )
+            }),
+            thresholds,
+        )
+    });
+    let cores = u64::from(node.cpu_cores);
+    let cpu = overcommit_ratio(allocation.cores.into(), cores).map(|ratio| {
+        allocation_line(
+            "CPU",
+            format!("{} / {} cores", allocation.cores, cores),
+            ratio,
+            (allocation.cores_all != allocation.cores).then(|| {
+                (
+                    format!("{} cores", allocation.cores_all),
+                    overcommit_ratio(allocation.cores_all.into(), cores).unwrap_or(ratio),
+                )
+            }),
+            thresholds,
+        )
+    });
+    memory.into_iter().chain(cpu).collect()
+}
+
+fn allocation_line(
+    label: &str,
+    allocated: String,
+    ratio: f64,
+    if_all_started: Option<(String, f64)>,
+    thresholds: &ThresholdsConfig,
+) -> Line<'static> {
+    let mut spans = vec![
+        Span::raw(format!("{} allocated: {} — ", label, allocated)),
+        overcommit_span(ratio, thresholds),
+    ];
+    if let Some((allocated, ratio)) = if_all_started {
+        spans.push(Span::raw(format!(" | if all started: {} — ", allocated)));
+        spans.push(overcommit_span(ratio, thresholds));
+    }
+    Line::from(spans)
+}
+
+fn overcommit_span(ratio: f64, thresholds: &ThresholdsConfig) -> Span<'static> {
+    let text = if ratio > 1.0 {
+        format!("{:.1}× overcommit", ratio)
+    } else {
+        format!("{:.1}×", ratio)
+    };
+    let style = if ratio > thresholds.overcommit_critical {
+        Style::default().fg(Color::Red)
+    } else if ratio > thresholds.overcommit_warning {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    Span::styled(text, style)
+}
+
+/// Pressure stall figures, for providers that read them.
+fn pressure_lines(node: &crate::models::Node) -> Vec<Line<'static>> {
+    let Some(pressure) = node.pressure else {
+        return Vec::new();
+    };
+    vec![
+        Line::raw(format!("PSI cpu some avg10: {:.0}%", pressure.cpu_some)),
+        Line::raw(format!(
+            "PSI memory some avg10: {:.0}%, full avg10: {:.0}%",
+            pressure.memory_some, pressure.memory_full
+        )),
+    ]
+}
+
+/// Pending package updates, "name old → new", counting the rest past
+/// `MAX_UPDATE_LINES`.
+fn update_lines(node: &crate::models::Node) -> Vec<Line<'static>> {
+    let updates = &node.package_updates;
+    if updates.is_empty() {
+        return Vec::new();
+    }
+
+    let mut lines = vec![Line::styled(
+        format!("Updates: {} pending", updates.len()),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    let shown = if updates.len() > MAX_UPDATE_LINES {
+        MAX_UPDATE_LINES - 1
+    } else {
+        updates.len()
+    };
+    lines.extend(updates[..shown].iter().map(|u| {
+        let old = if u.old_version.is_empty() {
+            "new"
+        } else {
+            &u.old_version
+        };
+        Line::raw(format!("  {} {} → {}", u.package, old, u.new_version))
+    }));
+    if shown < updates.len() {
+        lines.push(Line::styled(
+            format!("  +{} more packages", updates.len() - shown),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    lines
+}
+
+/// One line per disk, failing or worn out disks first. Past
+/// `MAX_DISK_LINES` the rest are only counted.
+fn disk_lines(node: &crate::models::Node, units: Units) -> Vec<Line<'static>> {
+    let disks = match &node.disks {
+        NodeDisks::NotReported => return Vec::new(),
+        NodeDisks::Unavailable(reason) => {
+            return vec![Line::styled(
+                format!("Disk info unavailable ({})", reason),
+                Style::default().fg(Color::DarkGray),
+            )];
+        }
+        NodeDisks::Listed(disks) => disks,
+    };
+
+    let mut sorted: Vec<(&Disk, Option<String>)> = disks.iter().map(|d| (d, d.problem())).collect();
+    sorted.sort_by_key(|(_, problem)| problem.is_none());
+
+    let shown = if sorted.len() > MAX_DISK_LINES {
+        MAX_DISK_LINES - 1
+    } else {
+        sorted.len()
+    };
+    let mut lines: Vec<Line> = sorted[..shown]
+        .iter()
+        .map(|(disk, problem)| {
+            let mut text = format!(
+                "{} {} {} {}",
+                disk.dev_path,
+                fit(&disk.model, DISK_MODEL_WIDTH),
+                pad_left(&format_bytes(disk.size, units), MEMORY_COLUMN_WIDTH),
+                if disk.health.is_empty() {
+                    "UNKNOWN"
+                } else {
+                    &disk.health
+                }
+            );
+            if let Some(wearout) = disk.wearout {
+                text.push_str(&format!(" {}% life left", wearout));
+            }
+            match problem {
+                Some(_) => Line::styled(text, Style::default().fg(Color::Red)),
+                None => Line::raw(text),
+            }
+        })
+        .collect();
+    if shown < sorted.len() {
+        lines.push(Line::styled(
+            format!("+{} more disks", sorted.len() - shown),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    lines
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn restart_style(restarts: &RestartCount, now: u64) -> Style {
+    if restarts.is_recent(now) {
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    }
+}
+
+/// Restart count as a right-aligned column of `width` (blank when there were
+/// none); highlighted when the latest restart is recent.
+fn restart_span(restarts: Option<&RestartCount>, now: u64, width: usize) -> Span<'static> {
+    match restarts {
+        Some(restarts) => Span::styled(
+            pad_left(&format!(" ↻{}", restarts.count), width),
+            restart_style(restarts, now),
+        ),
+        None => Span::raw(" ".repeat(width)),
+    }
+}
+
+fn restart_detail(restarts: &RestartCount, now: u64) -> [Span<'static>; 2] {
+    [
+        Span::raw(" | "),
+        Span::styled(restarts.describe(now), restart_style(restarts, now)),
+    ]
+}
+
+fn help_line(key: &str, description: &str) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(
+            format!("  {} ", pad_right(key, HELP_KEY_WIDTH)),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::raw(description.to_string()),
+    ])
+}
+
+fn create_mini_bar(percent: f64, width: usize) -> String {
+    let filled = ((percent / 100.0) * width as f64).round() as usize;
+    let empty = width.saturating_sub(filled);
+    format!("[{}{}]", "=".repeat(filled), " ".repeat(empty))
+}
+
+fn truncate_name(name: &str, width: usize, truncation: Truncation) -> String {
+    match truncation {
+        Truncation::End => truncate_end(name, width),
+        Truncation::Middle => truncate_middle(name, width),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ContainerColumns {
+    name_width: usize,
+    show_node: bool,
+    show_memory: bool,
+    show_disk: bool,
+}
+
+impl ContainerColumns {
+    fn others_width(&self) -> usize {
+        let mut width = ROW_FIXED_WIDTH + CPU_COLUMN_WIDTH + RESTART_COLUMN_WIDTH;
+        if self.show_node {
+            width += NODE_COLUMN_WIDTH;
+        }
+        if self.show_memory {
+            width += MEMORY_COLUMN_WIDTH;
+        }
+        width
+    }
+
+    /// The sortable columns left to right, as `1`–`9` pick them. The memory
+    /// column sorts by what it shows: percent only while sorted by it.
+    fn sort_fields(&self, sort_field: SortField) -> Vec<SortField> {
+        let mut fields = vec![SortField::Status, SortField::Name, SortField::Cpu];
+        if self.show_memory {
+            fields.push(match sort_field {
+                SortField::Memory => SortField::Memory,
+                _ => SortField::MemoryAbs,
+            });
+        }
+        if self.show_disk {
+            fields.push(SortField::Disk);
+        }
+        fields
+    }
+}
+
+/// Decide the name column width and which optional columns fit in
+/// `available` columns. In full-name mode the node and then memory columns
+/// give way to the name.
+fn container_columns(
+    longest_name: usize,
+    configured: Option<usize>,
+    full_names: bool,
+    available: usize,
+) -> ContainerColumns {
+    let mut columns = ContainerColumns {
+        name_width: 0,
+        show_node: true,
+        show_memory: true,
+        show_disk: false,
+    };
+
+    let wanted = if full_names {
+        longest_name
+    } else {
+        configured.unwrap_or_else(|| longest_name.clamp(NAME_WIDTH_MIN, NAME_WIDTH_CAP))
+    };
+
+    if full_names && columns.others_width() + wanted > available {
+        columns.show_node = false;
+    }
+    if full_names && columns.others_width() + wanted > available {
+        columns.show_memory = false;
+    }
+
+    let room = available.saturating_sub(columns.others_width());
+    columns.name_width = wanted.min(room.max(NAME_WIDTH_MIN));
+    columns
+}
+
+/// The C/M/D micro-gauges of a node row: `C[====    ]  45%`.
+#[derive(Debug, PartialEq)]
+struct NodeGauges {
+    disk: bool,
+    labels: bool,
+    numbers: bool,
+    bar_width: usize,
+}
+
+impl NodeGauges {
+    fn count(&self) -> usize {
+        if self.disk { 3 } else { 2 }
+    }
+
+    /// Width of one gauge apart from its bar.
+    fn overhead(&self) -> usize {
+        2 + usize::from(self.labels) + if self.numbers { 5 } else { 0 }
+    }
+
+    /// The widest bar each gauge gets in `available` columns.
+    fn bar_room(&self, available: usize) -> usize {
+        let gaps = self.count() - 1;
+        available.saturating_sub(gaps + self.count() * self.overhead()) / self.count()
+    }
+
+    fn spans(&self, label: &'static str, percent: Option<f64>) -> Vec<Span<'static>> {
+        let mut spans = Vec::new();
+        if self.labels {
+            spans.push(Span::styled(label, Style::default().fg(Color::Gray)));
+        }
+        match percent {
+            Some(percent) => {
+                spans.push(Span::raw(create_mini_bar(percent, self.bar_width)));
+                if self.numbers {
+                    spans.push(Span::styled(
+                        format!(" {:>3.0}%", percent),
+                        Style::default().fg(cpu_color(percent)),
+                    ));
+                }
+            }
+            // Not reported: keep the columns of the other rows aligned
+            None => {
+                spans.push(Span::raw(format!("[{}]", " ".repeat(self.bar_width))));
+                if self.numbers {
+                    spans.push(Span::styled("   --", Style::default().fg(Color::DarkGray)));
+                }
+            }
+        }
+        spans
+    }
+}
+
+/// Fit the node gauges into `available` columns. Bars shrink down to
+/// `GAUGE_BAR_MIN`; below that the disk gauge goes first, then the labels,
+/// then the numbers.
+fn node_gauges(available: usize, with_disk: bool) -> NodeGauges {
+    let mut gauges = NodeGauges {
+        disk: with_disk,
+        labels: true,
+        numbers: true,
+        bar_width: 0,
+    };
+    let drops: [fn(&mut NodeGauges); 3] = [
+        |g| g.disk = false,
+        |g| g.labels = false,
+        |g| g.numbers = false,
+    ];
+    for drop in drops {
+        if gauges.bar_room(available) >= GAUGE_BAR_MIN {
+            break;
+        }
+        drop(&mut gauges);
+    }
+    gauges.bar_width = gauges.bar_room(available).clamp(1, GAUGE_BAR_MAX);
+    gauges
+}
+
+/// Render every span of a row in gray when its provider's data is stale.
+fn dim_if_stale(spans: Vec<Span<'_>>, stale: bool) -> Vec<Span<'_>> {
+    if !stale {
+        return spans;
+    }
+    spans
+        .into_iter()
+        .map(|span| span.patch_style(Style::default().fg(Color::Gray)))
+        .collect()
+}
+
+/// Red from `critical` (a `[thresholds]` value), yellow from 70%.
+fn threshold_color(percent: f64, critical: f64) -> Color {
+    if percent >= critical {
+        Color::Red
+    } else if percent >= 70.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+fn cpu_color(percent: f64) -> Color {
+    threshold_color(percent, 90.0)
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(popup_layout[1])[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{ProviderStatus, SortField};
+    use crate::changes::CHANGE_FADE;
+    use crate::config::Clock;
+    use crate::loader::Loader;
+    use crate::models::{
+        ClusterStatus, ContainerType, GuestDetails, GuestId, HaResource, Node, NodeId,
+        PackageUpdate, Pressure,
+    };
+    use crate::node_power::GuestImpact;
+    use crate::queue::QueuedAction;
+    use crate::resources::GuestResources;
+    use ratatui::{Terminal, backend::TestBackend, buffer::Buffer};
+    use std::sync::{Arc, Mutex, mpsc};
+
+    fn node(name: &str) -> Node {
+        Node {
+            cpu_usage: 50.0,
+            cpu_cores: 8,
+            memory_used: 4 * 1024 * 1024 * 1024,
+            memory_total: 8 * 1024 * 1024 * 1024,
+            uptime: 3600,
+            ..Node::test("homelab", name)
+        }
+    }
+
+    fn container(vmid: u32, name: &str, node: &str) -> Container {
+        Container {
+            cpu_usage: 12.5,
+            max_cpu: 2,
+            memory_used: 512 * 1024 * 1024,
+            memory_max: 1024 * 1024 * 1024,
+            uptime: 3600,
+            ..Container::test("homelab", vmid, name, node)
+        }
+    }
+
+    /// Rows of the buffer as text, skipping the cells hidden behind wide characters.
+    fn rows(buffer: &Buffer) -> Vec<String> {
+        let area = buffer.area;
+        (area.top()..area.bottom())
+            .map(|y| {
+                let mut row = String::new();
+                let mut skip = 0;
+                for x in area.left()..area.right() {
+                    if skip > 0 {
+                        skip -= 1;
+                        continue;
+                    }
+                    let symbol = buffer[(x, y)].symbol();
+                    skip = display_width(symbol).saturating_sub(1);
+                    row.push_str(symbol);
+                }
+                row
+            })
+            .collect()
+    }
+
+    fn render(width: u16, height: u16, draw: impl FnOnce(&mut Frame)) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(draw).unwrap();
+        rows(terminal.backend().buffer())
+    }
+
+    #[test]
+    fn test_network_summary_collapses_quiet_interfaces() {
+        let interface = |name: &str, rx_rate: f64, tx_rate: f64| InterfaceStat {
+            name: name.to_string(),
+            rx_bytes: 0,
+            tx_bytes: 0,
+            rx_rate,
+            tx_rate,
+        };
+        const MB: f64 = 1024.0 * 1024.0;
+
+        assert_eq!(network_summary(&[], Units::Short), None);
+        assert_eq!(
+            network_summary(
+                &[interface("vmbr0", 10.0, 0.0), interface("eno1", 0.0, 0.0)],
+                Units::Short
+            )
+            .as_deref(),
+            Some("Network: idle (2 interfaces)")
+        );
+        assert_eq!(
+            network_summary(
+                &[
+                    interface("eno1", 3.0 * 1024.0, 2.0 * 1024.0),
+                    interface("tap100i0", 100.0, 0.0),
+                    interface("vmbr0", 12.0 * MB, MB),
+                    interface("vmbr1", 0.0, 0.0),
+                ],
+                Units::Short
+            )
+            .as_deref(),
+            Some("Network: vmbr0 ↓12 MB/s ↑1 MB/s · eno1 ↓3 KB/s ↑2 KB/s · +2 more")
+        );
+    }
+
+    #[test]
+    fn test_container_columns_auto_width() {
+        let columns = container_columns(10, None, false, 80);
+        assert_eq!(columns.name_width, 10);
+        assert!(columns.show_node && columns.show_memory);
+
+        // Short names still get a usable column, long ones are capped
+        assert_eq!(
+            container_columns(3, None, false, 80).name_width,
+            NAME_WIDTH_MIN
+        );
+        assert_eq!(
+            container_columns(40, None, false, 80).name_width,
+            NAME_WIDTH_CAP
+        );
+    }
+
+    #[test]
+    fn test_container_columns_configured_width() {
+        assert_eq!(container_columns(40, Some(30), false, 80).name_width, 30);
+        // Never wider than the panel allows
+        assert_eq!(
+            container_columns(40, Some(30), false, 50).name_width,
+            50 - 36
+        );
+    }
+
+    #[test]
+    fn test_container_columns_full_names_drop_columns() {
+        // Plenty of room: everything shown at full width
+        let wide = container_columns(30, None, true, 80);
+        assert_eq!(wide.name_width, 30);
+        assert!(wide.show_node && wide.show_memory);
+
+        // Node column goes first
+        let medium = container_columns(30, None, true, 59);
+        assert_eq!(medium.name_width, 30);
+        assert!(!medium.show_node);
+        assert!(medium.show_memory);
+
+        // Then memory
+        let narrow = container_columns(30, None, true, 50);
+        assert_eq!(narrow.name_width, 30);
+        assert!(!narrow.show_node && !narrow.show_memory);
+
+        // Still too narrow: the name is truncated to what fits
+        let tiny = container_columns(30, None, true, 30);
+        assert_eq!(tiny.name_width, 10);
+    }
+
+    #[test]
+    fn test_column_keys_follow_the_visible_columns() {
+        use SortField::*;
+        let mut wide = container_columns(30, None, true, 80);
+        assert_eq!(wide.sort_fields(Name), [Status, Name, Cpu, MemoryAbs]);
+        // The memory column sorts by percent while it shows percent
+        assert_eq!(wide.sort_fields(Memory), [Status, Name, Cpu, Memory]);
+        wide.show_disk = true;
+        assert_eq!(wide.sort_fields(Name), [Status, Name, Cpu, MemoryAbs, Disk]);
+
+        // A dropped memory column moves disk up to 4
+        let mut narrow = container_columns(30, None, true, 50);
+        narrow.show_disk = true;
+        assert_eq!(narrow.sort_fields(Name), [Status, Name, Cpu, Disk]);
+
+        let legend = sort_legend(&narrow.sort_fields(Disk), Disk, false);
+        assert_eq!(legend.to_string(), " 1 Status  2 Name  3 CPU  4 Disk ▼ ");
+    }
+
+    #[test]
+    fn test_node_gauges_drop_disk_then_labels_then_numbers() {
+        let gauges = |disk, labels, numbers, bar_width| NodeGauges {
+            disk,
+            labels,
+            numbers,
+            bar_width,
+        };
+        assert_eq!(node_gauges(60, true), gauges(true, true, true, 10));
+        assert_eq!(node_gauges(44, true), gauges(true, true, true, 6));
+        // Too narrow for three bars of GAUGE_BAR_MIN: disk goes
+        assert_eq!(node_gauges(37, true), gauges(false, true, true, 10));
+        assert_eq!(node_gauges(25, true), gauges(false, true, true, 4));
+        // Then the labels, then the numbers
+        assert_eq!(node_gauges(24, true), gauges(false, false, true, 4));
+        assert_eq!(node_gauges(20, true), gauges(false, false, false, 7));
+        assert_eq!(node_gauges(8, true), gauges(false, false, false, 1));
+        // Without disk figures there is no disk gauge at any width
+        assert_eq!(node_gauges(60, false), gauges(false, true, true, 10));
+    }
+
+    #[test]
+    fn test_node_row_shows_disk_gauge() {
+        let mut app = App::new();
+        let mut with_disk = node("pve1");
+        with_disk.disk_used = 30;
+        with_disk.disk_total = 100;
+        app.nodes = vec![with_disk, node("pve2")];
+
+        let rows = render(60, 5, |frame| draw_nodes(frame, &app, frame.area()));
+        assert_eq!(
+            rows[1],
+            "│>● pve1       C[===   ]  50% M[===   ]  50% D[==    ]  30%│"
+        );
+        // A node without figures keeps the columns aligned
+        assert_eq!(
+            rows[2],
+            "│ ● pve2       C[===   ]  50% M[===   ]  50% D[      ]   --│"
+        );
+    }
+
+    #[test]
+    fn test_node_rows_align_with_cjk_names() {
+        let mut app = App::new();
+        app.nodes = vec![node("pve1"), node("数据库服务器"), node("서버")];
+
+        let rows = render(40, 6, |frame| draw_nodes(frame, &app, frame.area()));
+        assert_eq!(
+            rows,
+            [
+                "┌ Nodes (3/3) ─────────────────────────┐",
+                "│>● pve1       [==  ]  50% [==  ]  50% │",
+                "│ ● 数据库服~  [==  ]  50% [==  ]  50% │",
+                "│ ● 서버       [==  ]  50% [==  ]  50% │",
+                "│ Σ 3 nodes · CPU avg 50%              │",
+                "└──────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disk_lines_put_problems_first() {
+        let disk = |dev: &str, health: &str, wearout: Option<u8>| Disk {
+            dev_path: dev.to_string(),
+            model: "Samsung SSD 870".to_string(),
+            serial: "S1".to_string(),
+            size: 500 * 1024 * 1024 * 1024,
+            health: health.to_string(),
+            wearout,
+        };
+        let mut node = node("pve1");
+        node.disks = NodeDisks::Listed(vec![
+            disk("/dev/sda", "PASSED", Some(98)),
+            disk("/dev/sdb", "PASSED", None),
+            disk("/dev/sdc", "PASSED", Some(3)),
+            disk("/dev/sdd", "PASSED", Some(90)),
+            disk("/dev/sde", "FAILED", None),
+        ]);
+
+        let lines: Vec<String> = disk_lines(&node, Units::Short)
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "/dev/sdc Samsung SSD 870          500.0 GB PASSED 3% life left",
+                "/dev/sde Samsung SSD 870          500.0 GB FAILED",
+                "/dev/sda Samsung SSD 870          500.0 GB PASSED 98% life left",
+                "+2 more disks",
+            ]
+        );
+
+        node.disks = NodeDisks::Unavailable("token lacks Sys.Audit".to_string());
+        assert_eq!(
+            disk_lines(&node, Units::Short)[0].to_string(),
+            "Disk info unavailable (token lacks Sys.Audit)"
+        );
+        node.disks = NodeDisks::NotReported;
+        assert!(disk_lines(&node, Units::Short).is_empty());
+    }
+
+    #[test]
+    fn test_allocation_lines_show_overcommit() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        let node = node("pve1");
+        let thresholds = ThresholdsConfig::default();
+        let allocation = Allocation {
+            memory: 12 * GB,
+            cores: 8,
+            memory_all: 16 * GB,
+            cores_all: 8,
+        };
+
+        let lines = allocation_lines(&node, &allocation, &thresholds, Units::Short);
+        let text: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "Mem allocated: 12.0 GB / 8.0 GB — 1.5× overcommit | if all started: 16.0 GB — 2.0× overcommit",
+                "CPU allocated: 8 / 8 cores — 1.0×",
+            ]
+        );
+        // 1.5× is above the warning ratio but not the critical one
+        assert_eq!(lines[0].spans[1].style.fg, Some(Color::Yellow));
+        assert_eq!(lines[0].spans[3].style.fg, Some(Color::Red));
+        assert_eq!(lines[1].spans[1].style.fg, None);
+
+        assert!(
+            allocation_lines(&node, &Allocation::default(), &thresholds, Units::Short).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_update_lines_list_packages() {
+        let update = |package: &str, old: &str| PackageUpdate {
+            package: package.to_string(),
+            old_version: old.to_string(),
+            new_version: "2.0".to_string(),
+        };
+        let mut node = node("pve1");
+        assert!(update_lines(&node).is_empty());
+
+        node.package_updates = vec![
+            update("pve-manager", "1.0"),
+            update("libc6", "1.9"),
+            update("proxmox-kernel-6.8", ""),
+            update("zfsutils-linux", "1.5"),
+            update("curl", "1.1"),
+        ];
+        let lines: Vec<String> = update_lines(&node).iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "Updates: 5 pending",
+                "  pve-manager 1.0 → 2.0",
+                "  libc6 1.9 → 2.0",
+                "  proxmox-kernel-6.8 new → 2.0",
+                "  +2 more packages",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pressure_lines_when_reported() {
+        let mut node = node("nas");
+        assert!(pressure_lines(&node).is_empty());
+
+        node.pressure = Some(Pressure {
+            cpu_some: 12.4,
+            memory_some: 3.0,
+            memory_full: 0.6,
+        });
+        let lines: Vec<String> = pressure_lines(&node)
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "PSI cpu some avg10: 12%",
+                "PSI memory some avg10: 3%, full avg10: 1%",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_node_row_flags_failing_disk() {
+        let mut app = App::new();
+        let mut failing = node("pve1");
+        failing.disks = NodeDisks::Listed(vec![Disk {
+            dev_path: "/dev/sda".to_string(),
+            model: String::new(),
+            serial: String::new(),
+            size: 0,
+            health: "FAILED".to_string(),
+            wearout: None,
+        }]);
+        app.nodes = vec![failing];
+
+        let rows = render(50, 3, |frame| draw_nodes(frame, &app, frame.area()));
+        assert_eq!(
+            rows[1],
+            "│>● pve1       C[===  ]  50% M[===  ]  50% ⚠disk │"
+        );
+    }
+
+    #[test]
+    fn test_node_row_shows_pending_updates() {
+        let mut app = App::new();
+        let mut outdated = node("pve1");
+        outdated.pending_updates = Some(14);
+        let mut current = node("pve2");
+        current.pending_updates = Some(0);
+        app.nodes = vec![outdated, current];
+
+        let rows = render(50, 5, |frame| draw_nodes(frame, &app, frame.area()));
+        assert_eq!(
+            rows[1],
+            "│>● pve1       C[===   ]  50% M[===   ]  50% ⬆ 14│"
+        );
+        assert_eq!(
+            rows[2],
+            "│ ● pve2       C[===   ]  50% M[===   ]  50%     │"
+        );
+    }
+
+    #[test]
+    fn test_agent_badge_states() {
+        let badge = agent_badge(AgentStatus::Responding, Accessibility::Standard);
+        assert_eq!(
+            (badge.content.as_ref(), badge.style.fg),
+            ("GA", Some(Color::Green))
+        );
+        let badge = agent_badge(AgentStatus::NotResponding, Accessibility::Standard);
+        assert_eq!(badge.style.fg, Some(Color::DarkGray));
+        assert!(!badge.style.add_modifier.contains(Modifier::CROSSED_OUT));
+        // Without colors, a silent agent still looks different
+        let badge = agent_badge(AgentStatus::NotResponding, Accessibility::Mono);
+        assert!(badge.style.add_modifier.contains(Modifier::CROSSED_OUT));
+    }
+
+    #[test]
+    fn test_firewall_in_details_and_cluster_strip() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = vec![container(101, "nginx", "pve1")];
+        app.guest_details.insert(
+            GuestId::new("homelab", 101),
+            GuestDetails {
+                firewall: Some(FirewallStatus::On { rules: Some(0) }),
+                ..Default::default()
+            },
+        );
+        let mut status = ProviderStatus::new("homelab");
+        status.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: true,
+            nodes_online: 3,
+            nodes_total: 3,
+            firewall: Some(FirewallStatus::Off),
+            ha: Default::default(),
+        });
+        app.provider_status.push(status);
+
+        let rows = render(120, 6, |frame| {
+            draw_container_details(frame, &app, &app.containers[0], frame.area())
+        });
+        assert!(rows[0].contains("| FW: on (0 rules)"));
+        assert!(!rows[0].contains("OS:"));
+
+        app.containers[0].os_info = Some("debian".to_string());
+        let rows = render(120, 6, |frame| {
+            draw_container_details(frame, &app, &app.containers[0], frame.area())
+        });
+        assert!(rows[0].contains("| OS: debian | Node: pve1"));
+        assert_eq!(
+            firewall_span(FirewallStatus::On { rules: Some(0) })
+                .style
+                .fg,
+            Some(Color::Yellow)
+        );
+
+        let rows = render(80, 1, |frame| draw_cluster_strip(frame, &app, frame.area()));
+        assert!(rows[0].starts_with(" cluster homelab: quorate (3/3) FW: off"));
+    }
+
+    #[test]
+    fn test_gauges_show_rolling_average_and_peak() {
+        let mut app = App::new();
+        app.clock = Clock::Utc;
+        app.nodes = vec![Node {
+            cpu_usage: 43.0,
+            ..node("pve1")
+        }];
+        let id = ItemId::Node(app.nodes[0].id.clone());
+        let start = Instant::now();
+        let noon = UNIX_EPOCH + Duration::from_secs(12 * 3600 + 22 * 60);
+        let stats = app.usage.entry(id).or_default();
+        for (secs, cpu) in [(0, 91.0), (5, 20.0), (10, 3.0)] {
+            stats.cpu.push(start + Duration::from_secs(secs), noon, cpu);
+            stats.memory.push(start, noon, 50.0);
+        }
+
+        let node = app.nodes[0].clone();
+        let rows = render(80, 10, |frame| {
+            draw_node_details(frame, &app, &node, frame.area())
+        });
+        assert!(rows[3].starts_with("now 43.0% · avg 38.0% (1m) · peak 91.0% at 12:22:00"));
+        assert!(rows[6].starts_with("now 50.0% · avg 50.0% (1m) · peak 50.0% at 12:22:00"));
+    }
+
+    #[test]
+    fn test_container_rows_align_with_cjk_names() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = vec![
+            container(101, "nginx", "pve1"),
+            container(102, "ウェブサーバー", "東京"),
+            container(103, "db🐘primary-replica", "pve2"),
+        ];
+
+        let rows = render(60, 6, |frame| draw_containers(frame, &app, frame.area()));
+        assert_eq!(
+            rows,
+            [
+                "┌ Containers (3/3) ────────────────────────────────────────┐",
+                "│>● LXC nginx               pve1      12.5%   512 MB       │",
+                "│ ● LXC ウェブサーバー      東京      12.5%   512 MB       │",
+                "│ ● LXC db🐘primary-replica pve2      12.5%   512 MB       │",
+                "│ Σ 3 running · CPU 38% · Mem 1.5 GB                       │",
+                "└ 1 Status  2 Name ▲  3 CPU  4 MemGB ──────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_rows_show_restarts() {
+        let mut app = App::new();
+        app.containers = vec![
+            container(101, "nginx", "pve1"),
+            container(102, "db", "pve1"),
+        ];
+        let restarts = RestartCount::new(unix_now());
+        app.restarts.insert(
+            ItemId::Guest(app.containers[1].id.clone()),
+            RestartCount {
+                count: 12,
+                ..restarts
+            },
+        );
+
+        let rows = render(60, 5, |frame| draw_containers(frame, &app, frame.area()));
+        assert_eq!(
+            rows[1..3],
+            [
+                "│ ● LXC nginx    pve1      12.5%   512 MB                  │",
+                "│ ● LXC db       pve1      12.5%   512 MB ↻12              │",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detailed_counts_in_panel_titles() {
+        let mut app = App::new();
+        let mut down = node("pve2");
+        down.status = NodeStatus::Offline;
+        app.nodes = vec![node("pve1"), down];
+        let mut stopped = container(102, "db", "pve1");
+        stopped.status = ContainerStatus::Stopped;
+        app.containers = vec![container(101, "nginx", "pve1"), stopped];
+
+        let nodes = render(50, 3, |frame| draw_nodes(frame, &app, frame.area()));
+        let containers = render(50, 3, |frame| draw_containers(frame, &app, frame.area()));
+        assert!(nodes[0].contains(" Nodes (1/2) "), "{}", nodes[0]);
+        assert!(
+            containers[0].contains(" Containers (1/2) "),
+            "{}",
+            containers[0]
+        );
+
+        app.detailed_counts = true;
+        let nodes = render(50, 3, |frame| draw_nodes(frame, &app, frame.area()));
+        assert!(
+            nodes[0].contains(" Nodes (1 online / 1 offline) "),
+            "{}",
+            nodes[0]
+        );
+        let mut template = container(9000, "debian", "pve1");
+        template.is_template = true;
+        app.containers.push(template);
+        app.containers[1].status = ContainerStatus::Running;
+        let containers = render(50, 3, |frame| draw_containers(frame, &app, frame.area()));
+        assert!(
+            containers[0].contains(" Containers (2 run / 1 tpl) "),
+            "{}",
+            containers[0]
+        );
+    }
+
+    #[test]
+    fn test_template_rows_are_labelled_and_dimmed() {
+        let mut app = App::new();
+        let mut template = container(9000, "debian", "pve1");
+        template.status = ContainerStatus::Stopped;
+        template.is_template = true;
+        app.containers = vec![container(101, "nginx", "pve1"), template];
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 5)).unwrap();
+        terminal
+            .draw(|frame| draw_containers(frame, &app, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = rows(buffer);
+        assert_eq!(
+            rows[2],
+            "│   TPL debian   pve1      12.5%   512 MB                  │"
+        );
+        assert_eq!(buffer[(4, 2)].fg, Color::DarkGray);
+        assert!(rows[0].contains(" Containers (1/1) "), "{}", rows[0]);
+    }
+
+    #[test]
+    fn test_container_rows_highlight_changes() {
+        let mut app = App::new();
+        let mut gone = container(103, "cache", "pve1");
+        gone.missing = true;
+        app.containers = vec![
+            container(101, "nginx", "pve1"),
+            container(102, "db", "pve1"),
+            gone,
+            container(104, "old", "pve1"),
+        ];
+        let now = Instant::now();
+        let changes = [
+            (
+                now,
+                Change::Usage {
+                    cpu: Some(Delta::Up),
+                    memory: Some(Delta::Down),
+                },
+            ),
+            (now, Change::Added),
+            (now, Change::Removed),
+            // Faded already
+            (now - CHANGE_FADE, Change::Added),
+        ];
+        for (container, change) in app.containers.iter().zip(changes) {
+            app.changes.insert(container.id.clone(), change);
+        }
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 7)).unwrap();
+        terminal
+            .draw(|frame| draw_containers(frame, &app, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let cell = |y: u16, symbol: &str| {
+            (0..60)
+                .map(|x| &buffer[(x, y)])
+                .find(|c| c.symbol() == symbol)
+                .unwrap()
+        };
+
+        assert_eq!(cell(1, "%").bg, theme::TINT_RISE);
+        assert_eq!(cell(1, "M").bg, theme::TINT_FALL);
+        assert_eq!(cell(1, "n").bg, Color::Reset);
+        assert_eq!(cell(2, "d").bg, theme::TINT_NEW);
+        assert!(cell(3, "c").modifier.contains(Modifier::CROSSED_OUT));
+        assert_eq!(cell(4, "o").bg, Color::Reset);
+    }
+
+    #[test]
+    fn test_visual_range_is_drawn_apart_from_marks() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = vec![
+            container(101, "a", "pve1"),
+            container(102, "b", "pve1"),
+            container(103, "c", "pve1"),
+            container(104, "d", "pve1"),
+        ];
+        app.marked.insert(GuestId::new("homelab", 104));
+        app.visual_anchor = Some(GuestId::new("homelab", 101));
+        app.container_index = 2;
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 7)).unwrap();
+        terminal
+            .draw(|frame| draw_containers(frame, &app, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let lines = rows(buffer);
+
+        assert!(lines[0].contains("visual 3"));
+        assert!(lines[1].starts_with("│+"));
+        assert_eq!(buffer[(1, 1)].bg, theme::TINT_VISUAL);
+        assert!(lines[3].starts_with("│>"));
+        assert!(lines[4].starts_with("│*"));
+        assert_eq!(buffer[(1, 4)].bg, Color::Reset);
+
+        app.visual_anchor = None;
+        terminal
+            .draw(|frame| draw_containers(frame, &app, frame.area()))
+            .unwrap();
+        assert!(rows(terminal.backend().buffer())[0].contains("1 marked"));
+    }
+
+    #[test]
+    fn test_grouped_rows_and_summaries() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.containers = vec![
+            container(101, "prod-web-01", "pve1"),
+            container(102, "lab-db-01", "pve1"),
+            container(103, "prod-web-02", "pve2"),
+            container(104, "nas", "pve2"),
+        ];
+        app.set_group_by(GroupBy::Prefix);
+
+        let rows = render(60, 10, |frame| draw_containers(frame, &app, frame.area()));
+        assert_eq!(
+            rows,
+            [
+                "┌ Containers (4/4) by prefix ──────────────────────────────┐",
+                "│ ▾ lab (1/1)                 12.5%   512 MB / 1.0 GB      │",
+                "│ ● LXC lab-db-01   pve1      12.5%   512 MB               │",
+                "│ ▾ prod (2/2)                12.5%   1.0 GB / 2.0 GB      │",
+                "│>● LXC prod-web-01 pve1      12.5%   512 MB               │",
+                "│ ● LXC prod-web-02 pve2      12.5%   512 MB               │",
+                "│ ▾ (other) (1/1)             12.5%   512 MB / 1.0 GB      │",
+                "│ ● LXC nas         pve2      12.5%   512 MB               │",
+                "│ Σ 4 running · CPU 50% · Mem 2.0 GB                       │",
+                "└ 1 Status  2 Name ▲  3 CPU  4 MemGB ──────────────────────┘",
+            ]
+        );
+
+        app.select_next();
+        app.toggle_groups_collapsed();
+        let rows = render(60, 6, |frame| draw_containers(frame, &app, frame.area()));
+        assert_eq!(
+            rows[1..4],
+            [
+                "│ ▸ lab (1/1)                 12.5%   512 MB / 1.0 GB      │",
+                "│>▸ prod (2/2)                12.5%   1.0 GB / 2.0 GB      │",
+                "│ ▸ (other) (1/1)             12.5%   512 MB / 1.0 GB      │",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary_rows_follow_the_filter() {
+        let mut app = App::new();
+        app.active_panel = Panel::Containers;
+        app.nodes = vec![node("pve1"), node("pve2")];
+        app.containers = vec![
+            container(101, "web-01", "pve1"),
+            container(102, "web-02", "pve2"),
+            container(103, "db", "pve2"),
+        ];
+        app.containers[2].status = ContainerStatus::Stopped;
+        let last_row = |app: &App| {
+            let rows = render(60, 7, |frame| draw_containers(frame, app, frame.area()));
+            rows[5].clone()
+        };
+        assert_eq!(
+            last_row(&app),
+            "│ Σ 2 running · 1 stopped · CPU 25% · Mem 1.0 GB           │"
+        );
+
+        app.search_query = "node:pve2".to_string();
+        app.data_changed();
+        assert_eq!(
+            last_row(&app),
+            "│ Σ 1 running · 1 stopped · CPU 12% · Mem 512 MB           │"
+        );
+        let rows = render(60, 5, |frame| draw_nodes(frame, &app, frame.area()));
+        assert_eq!(
+            rows[3],
+            "│ Σ 1 nodes · CPU avg 50% · Mem 4.0 GB/8.0 GB              │"
+        );
+
+        // Off, the list gets the line back
+        app.summary_row = false;
+        assert!(!last_row(&app).contains('Σ'));
+    }
+
+    #[test]
+    fn test_pools_panel_filters_containers() {
+        let mut app = App::new();
+        app.set_panels(vec![Panel::Groups, Panel::Containers])
+            .unwrap();
+        let mut web = container(101, "web", "pve1");
+        web.pool = Some("prod".to_string());
+        let mut db = container(102, "db", "pve1");
+        db.pool = Some("prod".to_string());
+        db.status = ContainerStatus::Stopped;
+        app.containers = vec![web, db, container(103, "scratch", "pve2")];
+        app.aggregate_pools();
+        app.select_next();
+
+        let rows = render(32, 5, |frame| draw_groups(frame, &app, frame.area()));
+        assert_eq!(
+            rows,
+            [
+                "┌ Pools (2) ───────────────────┐",
+                "│ All                2/3  12.5%│",
+                "│>prod               1/2  12.5%│",
+                "│ (no pool)          1/1  12.5%│",
+                "└──────────────────────────────┘",
+            ]
+        );
+
+        let rows = render(60, 3, |frame| draw_containers(frame, &app, frame.area()));
+        assert_eq!(
+            rows[0],
+            "┌ Containers (2/3) in prod ────────────────────────────────┐"
+        );
+    }
+
+    fn accessibility_app(accessibility: Accessibility) -> App {
+        let mut app = App::new();
+        app.accessibility = accessibility;
+        app.last_refresh = Some(Instant::now());
+        app.has_ever_loaded = true;
+        let mut offline = node("pve2");
+        offline.status = NodeStatus::Offline;
+        app.nodes = vec![node("pve1"), offline];
+        let mut stopped = container(102, "db", "pve1");
+        stopped.status = ContainerStatus::Stopped;
+        let mut missing = container(103, "backup", "pve1");
+        missing.missing = true;
+        app.containers = vec![container(101, "nginx", "pve1"), stopped, missing];
+        app
+    }
+
+    fn render_app(app: &App) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    #[test]
+    fn test_colorblind_mode_snapshot() {
+        let buffer = render_app(&accessibility_app(Accessibility::Colorblind));
+        assert_eq!(
+            rows(&buffer)[4..7],
+            [
+                "│>● pve1       [== ] [== ] ││ ● LXC nginx    pve1      12.5%   512 MB          │",
+                "│ ✖ pve2       [== ] [== ] ││ ✖ LXC db       pve1      12.5%   512 MB          │",
+                "│                          ││ ? LXC backup   pve1      12.5%   512 MB     not f│",
+            ]
+        );
+        // Online/running in blue, offline/stopped in orange
+        assert_eq!(buffer[(2, 4)].fg, Color::LightBlue);
+        assert_eq!(buffer[(2, 5)].fg, Color::Indexed(208));
+        assert_eq!(buffer[(30, 5)].fg, Color::Indexed(208));
+        // Gauges below the warning threshold are blue too
+        assert_eq!(buffer[(1, 14)].fg, Color::LightBlue);
+    }
+
+    #[test]
+    fn test_mono_mode_snapshot() {
+        let buffer = render_app(&accessibility_app(Accessibility::Mono));
+        assert_eq!(
+            rows(&buffer)[4..7],
+            [
+                "│>● pve1       [== ] [== ] ││ ● LXC nginx    pve1      12.5%   512 MB          │",
+                "│ ✖ pve2       [== ] [== ] ││ ✖ LXC db       pve1      12.5%   512 MB          │",
+                "│                          ││ ? LXC backup   pve1      12.5%   512 MB     not f│",
+            ]
+        );
+        assert!(
+            buffer
+                .content
+                .iter()
+                .all(|c| c.fg == Color::Reset && c.bg == Color::Reset)
+        );
+        // The selected row stands out without color
+        assert!(buffer[(3, 4)].modifier.contains(Modifier::REVERSED));
+        assert!(!buffer[(3, 5)].modifier.contains(Modifier::REVERSED));
+    }
+
+    #[test]
+    fn test_events_popup_colors_by_level() {
+        use crate::events::Event;
+
+        let mut app = App::new();
+        app.clock = Clock::Utc;
+        let empty = render(80, 20, |frame| draw_events_popup(frame, &app));
+        assert!(empty.iter().any(|r| r.contains("No events yet.")));
+
+        app.events.push(Event::detected(
+            "homelab",
+            3600,
+            Some("pve1"),
+            EventLevel::Error,
+            "node pve1 went offline".to_string(),
+        ));
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal
+            .draw(|frame| draw_events_popup(frame, &app))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = rows(buffer);
+        let (y, row) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, r)| r.contains("01:00:00 pve1 pulse: node pve1 went offline"))
+            .expect("event row");
+        let x = row[..row.find("went").unwrap()].chars().count() as u16;
+        assert_eq!(buffer[(x, y as u16)].fg, Color::Red);
+    }
+
+    #[test]
+    fn test_debug_popup_shows_timings_and_buffers() {
+        let mut app = App::new();
+        let ms = Duration::from_millis;
+        app.diagnostics
+            .record_provider("homelab", ms(340), Some(7), false);
+        app.diagnostics
+            .record_provider("homelab", ms(1200), Some(3), true);
+        app.diagnostics.record_refresh(ms(1250));
+        app.diagnostics.record_frame(ms(4));
+
+        let rows = render(100, 30, |frame| {
+            draw_debug_popup(frame, &app, Instant::now())
+        });
+        for r in &rows {
+            eprintln!("{r}");
+        }
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Refreshes  1, last 1.2s, slowest 1.2s"))
+        );
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Frames     1, last 4ms, average 4ms, slowest 4ms"))
+        );
+        assert!(
+            rows.iter().any(
+                |r| r.contains("homelab                 2      1       10    1.2s     1.2s  -")
+            )
+        );
+        assert!(rows.iter().any(|r| r.contains("cpu trend")));
+    }
+
+    #[test]
+    fn test_logs_popup_colors_by_level_and_scrolls_sideways() {
+        use crate::logging::{LogBuffer, LogView, format_line};
+
+        let mut app = App::new();
+        let log = LogBuffer::new(100);
+        log.push(
+            Level::Debug,
+            format_line(
+                "2026-10-17 14:03:12",
+                Level::Debug,
+                "homelab refreshed in 120ms",
+            ),
+        );
+        log.push(
+            Level::Error,
+            format_line(
+                "2026-10-17 14:03:13",
+                Level::Error,
+                "homelab: connection refused",
+            ),
+        );
+        app.log = Some(log);
+        app.log_view = Some(LogView::default());
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| draw_logs_popup(frame, &app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = rows(buffer);
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Log: debug and up, following"))
+        );
+        let (y, row) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, r)| r.contains("ERROR homelab: connection refused"))
+            .expect("error row");
+        let x = row[..row.find("ERROR").unwrap()].chars().count() as u16;
+        assert_eq!(buffer[(x, y as u16)].fg, Color::Red);
+        assert!(rows.iter().any(|r| r.contains("DEBUG homelab refreshed")));
+
+        // Past the timestamps, and errors only
+        let view = app.log_view.as_mut().unwrap();
+        view.scroll_right();
+        view.cycle_level();
+        view.cycle_level();
+        view.cycle_level();
+        let rows = render(100, 20, |frame| draw_logs_popup(frame, &app));
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("│:13 ERROR homelab: connection refused"))
+        );
+        assert!(!rows.iter().any(|r| r.contains("refreshed")));
+    }
+
+    #[test]
+    fn test_help_lists_palette_commands() {
+        let rows = render(80, 74, |frame| draw_help_popup(frame, 0, false));
+        assert!(rows.iter().any(|r| r.contains(":preset <name>")));
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("'      Jump to a guest by VMID"))
+        );
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("j/Down Move selection down"))
+        );
+        assert!(rows.iter().any(|r| r.contains("any other key to close")));
+
+        // Short terminals scroll to the end
+        let rows = render(80, 30, |frame| draw_help_popup(frame, 36, false));
+        assert!(!rows.iter().any(|r| r.contains("Quit application")));
+        assert!(rows.iter().any(|r| r.contains("any other key to close")));
+    }
+
+    #[test]
+    fn test_start_popup_shows_the_shortfall() {
+        let mut guest = container(105, "web", "pve1");
+        guest.status = ContainerStatus::Stopped;
+        let roomy = GuestStart::new(&guest, Some(&node("pve1")));
+        let rows = render(140, 40, |frame| {
+            draw_start_popup(frame, &roomy, Units::Short)
+        });
+        assert!(rows.iter().any(|r| r.contains("Start 105 web on pve1?")));
+        assert!(!rows.iter().any(|r| r.contains("start anyway")));
+
+        guest.memory_max = 8 * 1024 * 1024 * 1024;
+        let short = GuestStart::new(&guest, Some(&node("pve1")));
+        let rows = render(140, 40, |frame| {
+            draw_start_popup(frame, &short, Units::Short)
+        });
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("pve1 has 4.0 GB free, guest wants 8.0 GB — start anyway?"))
+        );
+        assert!(rows.iter().any(|r| r.contains("Enter/y: start")));
+    }
+
+    #[test]
+    fn test_node_power_popup_previews_and_waits_for_the_name() {
+        let guests = [container(101, "web", "pve1"), container(102, "db", "pve1")];
+        let refs: Vec<&Container> = guests.iter().collect();
+        let ha = [HaResource {
+            guest: guests[1].id.clone(),
+            state: "started".to_string(),
+        }];
+        let impact = GuestImpact::new(&refs, Ok(&ha), 0);
+        let mut request = NodePowerRequest::new(node("pve1").id, NodePower::Reboot, impact);
+        request.typed = "pv".to_string();
+        let rows = render(140, 40, |frame| {
+            draw_node_power_popup(frame, &request, Units::Short)
+        });
+        assert!(rows.iter().any(|r| r.contains("Reboot pve1?")));
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Migrated by HA (1): 102 db"))
+        );
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Stopped, not HA-managed (1): 101 web"))
+        );
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Type the node's name to confirm: pv_"))
+        );
+        assert!(!rows.iter().any(|r| r.contains("Enter: confirm")));
+
+        request.typed = "pve1".to_string();
+        let rows = render(140, 40, |frame| {
+            draw_node_power_popup(frame, &request, Units::Short)
+        });
+        assert!(rows.iter().any(|r| r.contains("Enter: confirm")));
+    }
+
+    #[test]
+    fn test_resources_popup_warns_before_a_pending_change() {
+        let mut guest = container(105, "win", "pve1");
+        guest.container_type = ContainerType::VM;
+        let current = GuestResources {
+            cores: Some(2),
+            sockets: 1,
+            memory_mb: 2048,
+            balloon_mb: None,
+            memory_hotplug: false,
+        };
+        let mut form = ResourceForm::new(&guest, Some(&node("pve1")), current);
+        form.memory = "4096".to_string();
+        let rows = render(140, 40, |frame| draw_resources_popup(frame, &form));
+        assert!(rows.iter().any(|r| r.contains("Resources of 105 win")));
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Memory  4096_  MiB, node has 8192"))
+        );
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("⚠ memory will only apply once the VM restarts"))
+        );
+
+        form.phase = ResourcePhase::Saved {
+            pending: form.warning(),
+        };
+        let rows = render(140, 40, |frame| draw_resources_popup(frame, &form));
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("Saved, but memory will only apply once the VM restarts"))
+        );
+    }
+
+    #[test]
+    fn test_queue_popup_opens_into_the_errors() {
+        let guests = [container(101, "web", "pve1"), container(102, "db", "pve1")];
+        let refs: Vec<&Container> = guests.iter().collect();
+        let mut queue = ActionQueue::new(QueuedAction::Shutdown, &refs, 1);
+        queue.items[0].state = ItemState::Done;
+        queue.items[1].state = ItemState::Failed("VM is locked (backup)".to_string());
+        queue.phase = QueuePhase::Finished;
+        let rows = render(140, 40, |frame| draw_queue_popup(frame, &queue));
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("1 ok, 1 failed — press Enter for details"))
+        );
+        assert!(!rows.iter().any(|r| r.contains("VM is locked")));
+
+        queue.show_details = true;
+        let rows = render(140, 40, |frame| draw_queue_popup(frame, &queue));
+        assert!(rows.iter().any(|r| r.contains("VM is locked (backup)")));
+        assert!(!rows.iter().any(|r| r.contains("press Enter")));
+    }
+
+    #[test]
+    fn test_header_flashes_and_shows_mute() {
+        let mut app = App::new();
+        app.alarm.flash = true;
+        let header = |app: &App| {
+            let mut terminal = Terminal::new(TestBackend::new(120, 3)).unwrap();
+            terminal
+                .draw(|frame| draw_header(frame, app, frame.area()))
+                .unwrap();
+            terminal.backend().buffer().clone()
+        };
+        assert!(!header(&app)[(2, 1)].modifier.contains(Modifier::REVERSED));
+
+        app.alarm.update(Default::default(), Instant::now());
+        app.alarm
+            .update(["test/pve1 down".to_string()].into(), Instant::now());
+        assert!(header(&app)[(2, 1)].modifier.contains(Modifier::REVERSED));
+
+        app.alarm.toggle_mute();
+        let buffer = header(&app);
+        assert!(!buffer[(2, 1)].modifier.contains(Modifier::REVERSED));
+        assert!(rows(&buffer)[1].contains("🔕 muted |"));
+    }
+
+    #[test]
+    fn test_header_shows_problems_first() {
+        let mut app = App::new();
+        app.problems_first = true;
+        let rows = render(120, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains("Sort: Name ^ [problems first] |"));
+
+        app.group_sort_by_node = true;
+        app.sort_field = SortField::Cpu;
+        app.sort_ascending = false;
+        let rows = render(120, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains("Sort: Node → CPU v [problems first] |"));
+    }
+
+    #[test]
+    fn test_ha_problems_stand_out() {
+        let mut app = App::new();
+        app.nodes = vec![node("pve1"), node("pve2")];
+        app.containers = vec![
+            container(101, "nginx", "pve1"),
+            container(102, "db", "pve2"),
+        ];
+        let mut status = ProviderStatus::new("homelab");
+        status.last_success = Some(Instant::now());
+        status.cluster = Some(ClusterStatus {
+            name: "homelab".to_string(),
+            quorate: true,
+            nodes_online: 2,
+            nodes_total: 2,
+            firewall: None,
+            ha: crate::models::HaStatus {
+                errors: vec![GuestId::new("homelab", 102)],
+                fenced: vec!["pve2".to_string()],
+                unknown: Vec::new(),
+            },
+        });
+        app.provider_status = vec![status];
+
+        let header = render(160, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(header[1].contains("| HA errors: 2 |"), "{}", header[1]);
+
+        let mut terminal = Terminal::new(TestBackend::new(90, 5)).unwrap();
+        terminal
+            .draw(|frame| draw_nodes(frame, &app, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = rows(buffer);
+        assert!(rows[1].starts_with("│>● pve1"), "{}", rows[1]);
+        assert!(rows[2].starts_with("│ ⊘ pve2"), "{}", rows[2]);
+        assert!(rows[2].contains(" fenced "));
+        assert!(buffer[(2, 2)].modifier.contains(Modifier::REVERSED));
+        assert!(!buffer[(2, 1)].modifier.contains(Modifier::REVERSED));
+
+        let rows = render(90, 6, |frame| draw_containers(frame, &app, frame.area()));
+        let row = |name: &str| rows.iter().find(|r| r.contains(name)).unwrap().clone();
+        assert!(row(" db ").contains("‼"));
+        assert!(row(" db ").contains(" HA error "));
+        assert!(!row("nginx").contains("HA error"));
+    }
+
+    #[test]
+    fn test_providers_popup_and_header() {
+        let mut app = App::new();
+        app.nodes = vec![node("pve1"), node("pve2")];
+        app.containers = vec![container(101, "nginx", "pve1")];
+        let mut homelab = ProviderStatus::new("homelab");
+        homelab.last_success = Some(Instant::now());
+        let mut office = ProviderStatus::new("office");
+        office.disabled = true;
+        app.provider_status = vec![homelab, office];
+        app.diagnostics
+            .record_provider("homelab", Duration::from_millis(340), Some(4), false);
+        app.provider_filter = Some("homelab".to_string());
+
+        let rows = render(110, 12, |frame| {
+            draw_providers_popup(frame, &app, 1, Instant::now())
+        });
+        let row = |name: &str| rows.iter().find(|r| r.contains(name)).unwrap().clone();
+        assert!(row("homelab").contains(
+            "» homelab         ok              -                       2/2      1/1   50%   50%   340ms      0"
+        ));
+        assert!(row("office").contains("  office          disabled"));
+
+        let rows = render(160, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains("| homelab | office off | Only: homelab |"));
+    }
+
+    /// Answers once the test lets it (if it holds a gate), like a provider
+    /// behind a slow VPN.
+    struct SlowProvider {
+        name: &'static str,
+        node: &'static str,
+        gate: Option<Mutex<mpsc::Receiver<()>>>,
+    }
+
+    impl crate::providers::Provider for SlowProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn fetch_nodes(&self) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+            if let Some(gate) = &self.gate {
+                // A dropped sender releases it too, so a failed test can't hang here
+                let _ = gate.lock().unwrap().recv();
+            }
+            let mut node = node(self.node);
+            node.id = NodeId::new(self.name, self.node);
+            Ok(vec![node])
+        }
+
+        fn fetch_containers(&self) -> Result<Vec<Container>, Box<dyn std::error::Error>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_first_frame_does_not_wait_for_a_slow_provider() {
+        let (release, gate) = mpsc::channel();
+        let shared: Vec<crate::providers::SharedProvider> = vec![
+            Arc::new(SlowProvider {
+                name: "homelab",
+                node: "pve1",
+                gate: None,
+            }),
+            Arc::new(SlowProvider {
+                name: "office",
+                node: "pve9",
+                gate: Some(Mutex::new(gate)),
+            }),
+        ];
+        let providers: Vec<Box<dyn crate::providers::Provider>> = shared
+            .iter()
+            .map(|p| Box::new(p.clone()) as Box<dyn crate::providers::Provider>)
+            .collect();
+        let mut app = App::new();
+        let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+        let mut frame = |app: &App| {
+            terminal.draw(|frame| draw(frame, app)).unwrap();
+            rows(terminal.backend().buffer())
+        };
+        // What the main loop does with the loader between frames, until
+        // `until` holds; a loader that never gets there fails the test
+        let apply_until =
+            |app: &mut App, loader: &mut Loader, until: &dyn Fn(&App, &Loader) -> bool| {
+                let deadline = Instant::now() + Duration::from_secs(10);
+                while !until(app, loader) {
+                    assert!(Instant::now() < deadline, "the loader stopped answering");
+                    for loaded in loader.take() {
+                        app.load(&providers, loaded, Instant::now());
+                    }
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            };
+
+        app.start_loading(&providers);
+        let mut loader = Loader::spawn(shared);
+        let screen = frame(&app);
+        assert!(
+            screen[1].contains("| homelab loading… | office loading…"),
+            "{}",
+            screen[1]
+        );
+        assert!(screen.iter().any(|r| r.starts_with("│ loading…")));
+
+        // The fast provider shows up before the slow one has answered
+        apply_until(&mut app, &mut loader, &|app, _| {
+            !app.is_provider_loading("homelab")
+        });
+        let screen = frame(&app);
+        assert!(screen.iter().any(|r| r.contains("pve1")));
+        assert!(!screen.iter().any(|r| r.contains("pve9")));
+        assert!(
+            screen[1].contains("| homelab | office loading…"),
+            "{}",
+            screen[1]
+        );
+
+        // With the slow one still in flight, a key is handled and drawn
+        app.toggle_help();
+        app.refresh_if_due(&providers, Instant::now(), SystemTime::now());
+        let screen = frame(&app);
+        assert!(app.show_help);
+        assert!(screen.iter().any(|r| r.contains("Keyboard Shortcuts")));
+        assert!(app.is_provider_loading("office"));
+        app.toggle_help();
+
+        release.send(()).unwrap();
+        apply_until(&mut app, &mut loader, &|_, loader| loader.is_done());
+        assert!(!app.is_loading());
+        let screen = frame(&app);
+        assert!(screen.iter().any(|r| r.contains("pve9")));
+        assert!(!screen.iter().any(|r| r.contains("loading…")));
+    }
+
+    #[test]
+    fn test_dormant_providers_are_counted_in_the_header() {
+        let mut app = App::new();
+        let mut statuses = vec![ProviderStatus::new("homelab")];
+        for name in ["dc1", "dc2", "dc3"] {
+            let mut status = ProviderStatus::new(name);
+            status.disabled = true;
+            status.dormant = true;
+            statuses.push(status);
+        }
+        // Turned on, waiting to be built
+        statuses[3].disabled = false;
+        app.provider_status = statuses;
+
+        let rows = render(160, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(
+            rows[1].contains("| homelab | dc3 | 2 not started"),
+            "{}",
+            rows[1]
+        );
+        assert!(!rows[1].contains("dc1"));
+
+        let rows = render(110, 20, |frame| {
+            draw_providers_popup(frame, &app, 0, Instant::now())
+        });
+        let row = |name: &str| rows.iter().find(|r| r.contains(name)).unwrap().clone();
+        assert!(row("dc1").contains("  dc1             not started"));
+        assert!(row("dc3").contains("  dc3             starting"));
+    }
+
+    #[test]
+    fn test_duplicate_providers_are_flagged() {
+        let mut app = App::new();
+        let mut homelab = ProviderStatus::new("homelab");
+        homelab.last_success = Some(Instant::now());
+        let old = ProviderStatus::new("homelab-old");
+        app.provider_status = vec![homelab, old];
+        app.duplicates = vec![crate::duplicates::Duplicate {
+            first: "homelab".to_string(),
+            second: "homelab-old".to_string(),
+            nodes: vec!["pve1".to_string()],
+            guests: 12,
+            whole: true,
+        }];
+
+        let rows = render(160, 20, |frame| {
+            draw_providers_popup(frame, &app, 0, Instant::now())
+        });
+        assert!(rows.iter().any(|r| r.contains(
+            "⚠ homelab and homelab-old appear to report the same cluster: 12 guests alike; [general] hide_duplicates shows them once"
+        )));
+        let rows = render(160, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains("| homelab | homelab-old ⚠ same as homelab |"));
+
+        app.hide_duplicates = true;
+        let rows = render(160, 20, |frame| {
+            draw_providers_popup(frame, &app, 0, Instant::now())
+        });
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("12 guests alike; showing homelab's"))
+        );
+        let rows = render(160, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains("homelab-old ⚠ same as homelab, hidden"));
+    }
+
+    #[test]
+    fn test_header_shows_read_only_lock() {
+        let mut app = App::new();
+        let rows = render(120, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(!rows[1].contains("read-only"));
+
+        app.read_only = true;
+        let rows = render(120, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(
+            rows[1].contains(" PULSE | 🔒 read-only | Nodes: 0/0"),
+            "{}",
+            rows[1]
+        );
+    }
+
+    #[test]
+    fn test_recent_filter_in_header_and_title() {
+        let mut app = App::new();
+        let mut fresh = container(101, "web", "pve1");
+        fresh.uptime = 120;
+        app.containers = vec![fresh, container(102, "db", "pve1")];
+        let rows = render(140, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(!rows[1].contains("recent restarts"));
+
+        app.recent_filter = RecentFilter::Hour;
+        let rows = render(140, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains(" | recent restarts: 1 |"), "{}", rows[1]);
+        let rows = render(60, 6, |frame| draw_containers(frame, &app, frame.area()));
+        assert!(rows[0].contains(" Containers (2/2) up <1h "), "{}", rows[0]);
+    }
+
+    #[test]
+    fn test_header_sparkline_gives_way_to_counts() {
+        let mut app = App::new();
+        let now = Instant::now();
+        for (i, cpu) in [30.0, 55.0, 100.0].into_iter().enumerate() {
+            app.cpu_trend
+                .push(now - Duration::from_secs(200 - 100 * i as u64), cpu);
+        }
+
+        let rows = render(140, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(
+            rows[1].ends_with(" Cluster CPU 100%                    ▂    ▄    █│"),
+            "{}",
+            rows[1]
+        );
+
+        let rows = render(100, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(rows[1].contains("Containers: 0/0"));
+        assert!(!rows[1].contains("Cluster CPU"), "{}", rows[1]);
+    }
+
+    #[test]
+    fn test_header_clock_is_shed_first() {
+        let mut app = App::new();
+        app.clock = Clock::Utc;
+        let now = Instant::now();
+        for (i, cpu) in [30.0, 55.0, 100.0].into_iter().enumerate() {
+            app.cpu_trend
+                .push(now - Duration::from_secs(200 - 100 * i as u64), cpu);
+        }
+        // " 14:03:27 │" at the right edge
+        let clock_at_edge = |row: &str| {
+            let tail: Vec<char> = row.chars().rev().skip(2).take(8).collect();
+            tail.iter().enumerate().all(|(i, c)| {
+                if i == 2 || i == 5 {
+                    *c == ':'
+                } else {
+                    c.is_ascii_digit()
+                }
+            })
+        };
+
+        let rows = render(150, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(clock_at_edge(&rows[1]), "{}", rows[1]);
+        assert!(rows[1].contains("Cluster CPU 100%"), "{}", rows[1]);
+
+        // Room for the trend but not both: the clock goes
+        let rows = render(140, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(!clock_at_edge(&rows[1]), "{}", rows[1]);
+        assert!(rows[1].contains("Cluster CPU 100%"), "{}", rows[1]);
+
+        app.clock = Clock::Off;
+        let rows = render(150, 3, |frame| draw_header(frame, &app, frame.area()));
+        assert!(!clock_at_edge(&rows[1]), "{}", rows[1]);
+    }
+
+    #[test]
+    fn test_command_line_shows_error() {
+        let mut app = App::new();
+        app.enter_command_mode();
+        app.command_input = "sort fast".to_string();
+        app.command_error = Some("sort: bad field".to_string());
+
+        let rows = render(60, 1, |frame| draw_status_bar(frame, &app, frame.area()));
+        assert_eq!(rows[0].trim_end(), " :sort fast_  sort: bad field");
+    }
+
+    #[test]
+    fn test_status_hints_follow_context() {
+        let mut app = App::new();
+        let status = |app: &App, width: u16| {
+            render(width, 1, |frame| draw_status_bar(frame, app, frame.area()))[0]
+                .trim_end()
+                .to_string()
+        };
+
+        assert_eq!(
+            status(&app, 60),
+            " ?:Help  q:Quit  Tab:Panel  d:Drain  R:Refresh node  p:Pin"
+        );
+        // Narrow terminals keep the first hints whole
+        assert_eq!(status(&app, 30), " ?:Help  q:Quit  Tab:Panel");
+
+        app.active_panel = Panel::Containers;
+        assert!(status(&app, 60).contains("t:Console"));
+
+        app.show_help = true;
+        assert_eq!(status(&app, 60), " j/k:Scroll  any key:Close");
+        app.show_help = false;
+
+        app.enter_search_mode();
+        app.search_query = "web".to_string();
+        assert_eq!(
+            status(&app, 60),
+            " Search: web_  Enter:Apply  Esc:Clear  Backspace:Delete"
+        );
+        assert_eq!(status(&app, 40), " Search: web_  Enter:Apply  Esc:Clear");
+        app.toggle_search_case();
+        app.toggle_search_whole_word();
+        assert_eq!(status(&app, 40), " Search [Aa|W]: web_  Enter:Apply");
+        app.toggle_search_case();
+        assert_eq!(status(&app, 40), " Search [W]: web_  Enter:Apply");
+
+        app.open_preset_prompt();
+        app.preset_prompt.as_mut().unwrap().name = "daily".to_string();
+        assert_eq!(
+            status(&app, 50),
+            " Save preset as: daily_  Enter:Save  Esc:Cancel"
+        );
+        app.preset_prompt.as_mut().unwrap().confirm_overwrite = true;
+        assert_eq!(
+            status(&app, 70),
+            " Save preset as: daily_  daily exists; Enter again to overwrite"
+        );
+    }
+
+    #[test]
+    fn test_banner_layout_gives_way_to_the_message_but_keeps_help() {
+        let message = " Error: homelab: connection refused ";
+        let hints = commands::keyed(ERROR_BANNER_HINTS);
+        let layout = |width| banner_layout(message, &hints, width);
+
+        let (text, hints) = layout(100);
+        assert_eq!(text, message);
+        assert_eq!(hints, " E:Details  r:Retry  q:Quit  ?:Help ");
+        // Hints keep their place while the message still gets enough room
+        let (text, hints) = layout(60);
+        assert_eq!(hints, " E:Details  r:Retry  q:Quit  ?:Help ");
+        assert_eq!(text, " Error: hom…ion refused ");
+        assert_eq!(layout(50).1, " r:Retry  q:Quit  ?:Help ");
+        assert_eq!(layout(40).1, " q:Quit  ?:Help ");
+        assert_eq!(layout(35).1, " ?:Help ");
+        assert_eq!(
+            layout(30),
+            (" Error: homel…tion refused ".to_string(), " ? ".to_string())
+        );
+        assert_eq!(layout(10), (" Err…sed ".to_string(), "?".to_string()));
+
+        // A short message leaves the hints alone sooner
+        assert_eq!(
+            banner_layout(" Saved ", &commands::keyed(NOTICE_BANNER_HINTS), 34),
+            (
+                " Saved ".to_string(),
+                " r:Refresh  q:Quit  ?:Help ".to_string()
+            )
+        );
+
+        for width in 1..120 {
+            let (text, hints) = layout(width);
+            assert!(display_width(&text) + display_width(&hints) <= width.max(1));
+            assert!(hints.contains('?'), "{}: {}", width, hints);
+        }
+    }
+
+    #[test]
+    fn test_error_banner_keeps_hints_on_the_right() {
+        let mut app = App::new();
+        app.error_message = Some(format!("homelab: {}", "timed out ".repeat(10)));
+        let rows = render(80, 1, |frame| draw_status_bar(frame, &app, frame.area()));
+        assert!(rows[0].starts_with(" Error: homelab: time…"), "{}", rows[0]);
+        assert!(
+            rows[0].ends_with(" E:Details  r:Retry  q:Quit  ?:Help "),
+            "{}",
+            rows[0]
+        );
+
+        app.notice = Some("Refreshed".to_string());
+        let rows = render(80, 1, |frame| draw_status_bar(frame, &app, frame.area()));
+        assert!(rows[0].starts_with(" Refreshed "));
+        assert!(rows[0].ends_with(" r:Refresh  q:Quit  ?:Help "));
+    }
+
+    #[test]
+    fn test_help_popup_keys_align() {
+        let rows = render(80, 40, |frame| draw_help_popup(frame, 0, false));
+        let descriptions: Vec<usize> = rows
+            .iter()
+            .filter_map(|row| {
+                [
+                    "Quit application",
+                    "Switch between panels",
+                    "Move selection down",
+                ]
+                .iter()
+                .find_map(|d| row.find(d))
+            })
+            .collect();
+        assert_eq!(descriptions.len(), 3);
+        assert!(descriptions.iter().all(|&x| x == descriptions[0]));
+    }
+
+    #[test]
+    fn test_comparison_title_truncates_long_names() {
+        let mut app = App::new();
+        app.nodes = vec![node("数据库服务器-primary-east"), node("pve2")];
+        let a = ItemId::Node(app.nodes[0].id.clone());
+        let b = ItemId::Node(app.nodes[1].id.clone());
+
+        let rows = render(100, 8, |frame| {
+            draw_comparison(frame, &app, &a, &b, frame.area());
+        });
+        assert!(rows[0].starts_with("┌ Compare 数据库服务器-primar~ vs pve2 | Δ CPU +0.0%"));
+    }
+
+    #[test]
+    fn test_startup_failure_replaces_empty_panels() {
+        let mut app = App::new();
+        let mut status = ProviderStatus::new("homelab");
+        status.last_error = Some("error fetching nodes: connection refused".to_string());
+        status.backoff.record_failure(Instant::now());
+        app.provider_status.push(status);
+        app.last_refresh = Some(Instant::now());
+
+        let rows = render(100, 30, |frame| draw(frame, &app)).join("\n");
+        assert!(rows.contains("Could not load data from any provider"));
+        assert!(rows.contains("homelab  retrying in "));
+        assert!(rows.contains("error fetching nodes: connection refused"));
+        assert!(rows.contains("Press r to retry now"));
+        assert!(!rows.contains("No node selected"));
+
+        // As soon as anything has loaded, the normal layout is back
+        app.has_ever_loaded = true;
+        let rows = render(100, 30, |frame| draw(frame, &app)).join("\n");
+        assert!(!rows.contains("Could not load data"));
+        assert!(rows.contains("No node selected"));
+    }
+
+    #[test]
+    fn test_no_failure_summary_before_first_refresh() {
+        let app = App::new();
+        let rows = render(100, 30, |frame| draw(frame, &app)).join("\n");
+        assert!(!rows.contains("Could not load data"));
+    }
 }
@@ -0,0 +1,323 @@
+//! Sorting and search filtering of the node and guest lists as pure
+//! functions over slices. `App` keeps its lists in this order and filters
+//! through them, so the panels and the `--once` output agree; everything
+//! `App` adds on top (problems first, the pool and provider filters, the
+//! fallback for a query that doesn't parse) stays there.
+//!
+//! A guest's CPU depends on the CPU mode and its node's cores, so the
+//! container functions take it as `cpu`, the figure as displayed.
+
+use std::cmp::Ordering;
+
+use crate::app::SortField;
+use crate::models::{Container, ContainerStatus, Node, NodeStatus};
+use crate::query::Query;
+
+/// `ordering` as is when ascending, reversed otherwise.
+fn directed(ordering: Ordering, ascending: bool) -> Ordering {
+    if ascending {
+        ordering
+    } else {
+        ordering.reverse()
+    }
+}
+
+/// Sort `nodes` in place, stably. Nodes have no single volume, so `Disk`
+/// keeps them in name order whatever the direction.
+pub fn sort_nodes(nodes: &mut [Node], field: SortField, ascending: bool) {
+    match field {
+        SortField::Name => nodes.sort_by(|a, b| directed(a.name.cmp(&b.name), ascending)),
+        SortField::Status => {
+            // Ascending puts online nodes first
+            let online = |n: &Node| matches!(n.status, NodeStatus::Online);
+            nodes.sort_by(|a, b| directed(online(b).cmp(&online(a)), ascending));
+        }
+        SortField::Cpu => {
+            nodes.sort_by(|a, b| directed(a.cpu_usage.total_cmp(&b.cpu_usage), ascending))
+        }
+        SortField::Memory => nodes
+            .sort_by(|a, b| directed(a.memory_percent().total_cmp(&b.memory_percent()), ascending)),
+        SortField::MemoryAbs => {
+            nodes.sort_by(|a, b| directed(a.memory_used.cmp(&b.memory_used), ascending))
+        }
+        SortField::Disk => nodes.sort_by(|a, b| a.name.cmp(&b.name)),
+    }
+}
+
+/// Sort `containers` in place, stably. Unknown disk usage sorts below 0%.
+pub fn sort_containers(
+    containers: &mut [Container],
+    field: SortField,
+    ascending: bool,
+    cpu: impl Fn(&Container) -> f64,
+) {
+    match field {
+        SortField::Name => containers.sort_by(|a, b| directed(a.name.cmp(&b.name), ascending)),
+        SortField::Status => {
+            // Ascending puts running guests first
+            let running = |c: &Container| matches!(c.status, ContainerStatus::Running);
+            containers.sort_by(|a, b| directed(running(b).cmp(&running(a)), ascending));
+        }
+        SortField::Cpu => containers.sort_by(|a, b| directed(cpu(a).total_cmp(&cpu(b)), ascending)),
+        SortField::Memory => containers
+            .sort_by(|a, b| directed(a.memory_percent().total_cmp(&b.memory_percent()), ascending)),
+        SortField::MemoryAbs => {
+            containers.sort_by(|a, b| directed(a.memory_used.cmp(&b.memory_used), ascending))
+        }
+        SortField::Disk => {
+            let disk = |c: &Container| c.disk_percent().unwrap_or(-1.0);
+            containers.sort_by(|a, b| directed(disk(a).total_cmp(&disk(b)), ascending));
+        }
+    }
+}
+
+/// Indices of the nodes matching `query`, in list order.
+pub fn filter_nodes(nodes: &[Node], query: &Query) -> Vec<usize> {
+    (0..nodes.len())
+        .filter(|&i| query.matches_node(&nodes[i]))
+        .collect()
+}
+
+/// Those of `candidates` (indices into `containers`, in list order) that
+/// match `query`. `0..containers.len()` checks them all; a narrower query
+/// can start from a broader one's matches instead.
+pub fn filter_containers(
+    containers: &[Container],
+    candidates: impl IntoIterator<Item = usize>,
+    query: &Query,
+    cpu: impl Fn(&Container) -> f64,
+) -> Vec<usize> {
+    candidates
+        .into_iter()
+        .filter(|&i| {
+            let c = &containers[i];
+            query.matches_container(c, cpu(c))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GuestId;
+    use crate::query::{MatchOptions, parse_with};
+    use proptest::prelude::*;
+
+    const FIELDS: [SortField; 6] = [
+        SortField::Name,
+        SortField::Status,
+        SortField::Cpu,
+        SortField::Memory,
+        SortField::MemoryAbs,
+        SortField::Disk,
+    ];
+
+    fn node(name: String, online: bool, cpu: f64, used: u64) -> Node {
+        Node {
+            status: if online {
+                NodeStatus::Online
+            } else {
+                NodeStatus::Offline
+            },
+            cpu_usage: cpu,
+            cpu_cores: 8,
+            memory_used: used,
+            memory_total: 1000,
+            uptime: 1,
+            ..Node::test("homelab", &name)
+        }
+    }
+
+    fn container(
+        vmid: u32,
+        name: String,
+        running: bool,
+        cpu: f64,
+        used: u64,
+        disk: Option<u64>,
+    ) -> Container {
+        Container {
+            status: if running {
+                ContainerStatus::Running
+            } else {
+                ContainerStatus::Stopped
+            },
+            cpu_usage: cpu,
+            max_cpu: 2,
+            memory_used: used,
+            memory_max: 1000,
+            uptime: 1,
+            disk_used: disk,
+            disk_max: 1000,
+            ..Container::test("homelab", vmid, &name, &format!("pve{}", vmid % 3))
+        }
+    }
+
+    fn nodes() -> impl Strategy<Value = Vec<Node>> {
+        prop::collection::vec(("[a-c]{1,3}", any::<bool>(), 0.0..100.0, 0..1000u64), 0..12)
+            .prop_map(|rows| {
+                rows.into_iter()
+                    .map(|(name, online, cpu, used)| node(name, online, cpu, used))
+                    .collect()
+            })
+    }
+
+    /// Guests with distinct VMIDs, so ids tell them apart.
+    fn containers() -> impl Strategy<Value = Vec<Container>> {
+        prop::collection::vec(
+            (
+                "[a-c]{1,3}",
+                any::<bool>(),
+                0.0..100.0,
+                0..1000u64,
+                prop::option::of(0..1000u64),
+            ),
+            0..12,
+        )
+        .prop_map(|rows| {
+            rows.into_iter()
+                .enumerate()
+                .map(|(i, (name, running, cpu, used, disk))| {
+                    container(100 + i as u32, name, running, cpu, used, disk)
+                })
+                .collect()
+        })
+    }
+
+    fn field() -> impl Strategy<Value = SortField> {
+        prop::sample::select(FIELDS.to_vec())
+    }
+
+    /// Queries made of terms a user might type, each extendable by more.
+    fn terms() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::vec(
+            prop::sample::select(vec![
+                "a",
+                "b",
+                "ab",
+                "name:a",
+                "node:pve1",
+                "status:run",
+                "cpu>20",
+                "cpu<80",
+                "mem>=300",
+            ]),
+            0..4,
+        )
+        .prop_map(|terms| terms.into_iter().map(str::to_string).collect())
+    }
+
+    fn query(terms: &[String]) -> Query {
+        parse_with(&terms.join(" "), MatchOptions::default()).unwrap()
+    }
+
+    fn ids(containers: &[Container]) -> Vec<GuestId> {
+        let mut ids: Vec<GuestId> = containers.iter().map(|c| c.id.clone()).collect();
+        ids.sort_by(|a, b| a.id.cmp(&b.id));
+        ids
+    }
+
+    #[test]
+    fn test_sorts_match_the_panels() {
+        let mut guests = vec![
+            container(101, "web".to_string(), true, 10.0, 500, Some(100)),
+            container(102, "db".to_string(), false, 0.0, 0, None),
+            container(103, "cache".to_string(), true, 60.0, 900, Some(10)),
+        ];
+        let vmids = |guests: &[Container]| guests.iter().map(|c| c.vmid).collect::<Vec<_>>();
+        sort_containers(&mut guests, SortField::Cpu, false, |c| c.cpu_usage);
+        assert_eq!(vmids(&guests), [103, 101, 102]);
+        sort_containers(&mut guests, SortField::Disk, true, |c| c.cpu_usage);
+        assert_eq!(vmids(&guests), [102, 103, 101]);
+        sort_containers(&mut guests, SortField::Status, true, |c| c.cpu_usage);
+        assert_eq!(vmids(&guests), [103, 101, 102]);
+
+        let mut hosts = vec![
+            node("pve2".to_string(), true, 5.0, 100),
+            node("pve1".to_string(), false, 50.0, 100),
+        ];
+        sort_nodes(&mut hosts, SortField::Disk, false);
+        assert_eq!(hosts[0].name, "pve1");
+        sort_nodes(&mut hosts, SortField::Status, true);
+        assert_eq!(hosts[0].name, "pve2");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sort_is_a_permutation(mut guests in containers(), field in field(), asc: bool) {
+            let before = ids(&guests);
+            sort_containers(&mut guests, field, asc, |c| c.cpu_usage);
+            prop_assert_eq!(ids(&guests), before);
+        }
+
+        #[test]
+        fn prop_sort_orders_adjacent_items(mut guests in containers(), asc: bool) {
+            sort_containers(&mut guests, SortField::MemoryAbs, asc, |c| c.cpu_usage);
+            for pair in guests.windows(2) {
+                let ordered = if asc {
+                    pair[0].memory_used <= pair[1].memory_used
+                } else {
+                    pair[0].memory_used >= pair[1].memory_used
+                };
+                prop_assert!(ordered);
+            }
+        }
+
+        #[test]
+        fn prop_sort_is_deterministic(
+            guests in containers(),
+            hosts in nodes(),
+            field in field(),
+            asc: bool,
+        ) {
+            // Sorting the same list gives the same order every time, and
+            // sorting it again changes nothing
+            let mut once = guests.clone();
+            sort_containers(&mut once, field, asc, |c| c.cpu_usage);
+            let mut twice = once.clone();
+            sort_containers(&mut twice, field, asc, |c| c.cpu_usage);
+            prop_assert_eq!(
+                once.iter().map(|c| c.vmid).collect::<Vec<_>>(),
+                twice.iter().map(|c| c.vmid).collect::<Vec<_>>()
+            );
+            let mut again = guests.clone();
+            sort_containers(&mut again, field, asc, |c| c.cpu_usage);
+            prop_assert_eq!(
+                once.iter().map(|c| c.vmid).collect::<Vec<_>>(),
+                again.iter().map(|c| c.vmid).collect::<Vec<_>>()
+            );
+
+            let mut nodes_once = hosts.clone();
+            sort_nodes(&mut nodes_once, field, asc);
+            let mut nodes_twice = nodes_once.clone();
+            sort_nodes(&mut nodes_twice, field, asc);
+            prop_assert_eq!(
+                nodes_once.iter().map(|n| &n.name).collect::<Vec<_>>(),
+                nodes_twice.iter().map(|n| &n.name).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn prop_extending_a_query_only_narrows(
+            guests in containers(),
+            hosts in nodes(),
+            base in terms(),
+            extra in terms(),
+        ) {
+            let broad = query(&base);
+            let narrow = query(&[base.clone(), extra].concat());
+            let cpu = |c: &Container| c.cpu_usage;
+
+            let wide = filter_containers(&guests, 0..guests.len(), &broad, cpu);
+            let fewer = filter_containers(&guests, 0..guests.len(), &narrow, cpu);
+            prop_assert!(fewer.iter().all(|i| wide.contains(i)));
+            prop_assert!(fewer.windows(2).all(|w| w[0] < w[1]));
+            // Starting from the broader matches finds the same guests
+            prop_assert_eq!(filter_containers(&guests, wide, &narrow, cpu), fewer);
+
+            let wide = filter_nodes(&hosts, &broad);
+            let fewer = filter_nodes(&hosts, &narrow);
+            prop_assert!(fewer.iter().all(|i| wide.contains(i)));
+        }
+    }
+}
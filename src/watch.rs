@@ -0,0 +1,161 @@
+//! `pulse watch`: the view as plain text, one block per refresh, each under
+//! a line with the time. Output only ever appends, so it reads well through
+//! a screen reader, on a dumb terminal or piped into `tee`. The blocks come
+//! from `output`, like `--once`.
+
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+
+use crate::app::App;
+use crate::output;
+use crate::providers::Provider;
+
+/// How often the wait between refreshes checks for Ctrl+C.
+const INTERRUPT_CHECK: Duration = Duration::from_millis(100);
+
+/// One refresh's block: the time, the summary, the tables with `full`, and
+/// a blank line to separate it from the next.
+pub fn render(app: &App, at: &str, full: bool, color: bool) -> String {
+    let mut out = format!("pulse at {}\n", at);
+    out.push_str(&output::render_summary(app, color));
+    if full {
+        out.push('\n');
+        out.push_str(&output::render_table(app));
+    }
+    out.push('\n');
+    out
+}
+
+/// Refresh and print a block every `interval` until interrupted or stdout
+/// closes (e.g. `| head`), which both end it without an error.
+pub fn run(
+    app: &mut App,
+    providers: &[Box<dyn Provider>],
+    interval: Duration,
+    full: bool,
+    color: bool,
+) -> io::Result<()> {
+    let interrupted = watch_interrupt()?;
+    app.refresh(providers);
+    loop {
+        let at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut stdout = io::stdout().lock();
+        let written = stdout
+            .write_all(render(app, &at, full, color).as_bytes())
+            .and_then(|()| stdout.flush());
+        match written {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            written => written?,
+        }
+        drop(stdout);
+
+        let next = Instant::now() + interval;
+        while Instant::now() < next {
+            if interrupted.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            std::thread::sleep(INTERRUPT_CHECK.min(next - Instant::now()));
+        }
+        // Providers that are down wait out their backoff
+        app.refresh_due(providers);
+    }
+}
+
+/// Set once SIGINT or SIGTERM arrives, so the loop can stop between blocks
+/// and the providers (an ssh tunnel, say) are dropped properly.
+#[cfg(unix)]
+fn watch_interrupt() -> io::Result<Arc<AtomicBool>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let flag = Arc::new(AtomicBool::new(false));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+    let (mut interrupt, mut terminate) = {
+        let _context = runtime.enter();
+        (
+            signal(SignalKind::interrupt())?,
+            signal(SignalKind::terminate())?,
+        )
+    };
+    let raised = flag.clone();
+    std::thread::spawn(move || {
+        runtime.block_on(async {
+            tokio::select! {
+                _ = interrupt.recv() => {}
+                _ = terminate.recv() => {}
+            }
+            raised.store(true, Ordering::Relaxed);
+        })
+    });
+    Ok(flag)
+}
+
+/// Elsewhere Ctrl+C ends pulse the default way.
+#[cfg(not(unix))]
+fn watch_interrupt() -> io::Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ProviderStatus;
+    use crate::models::{Container, ContainerStatus, Node, NodeStatus};
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.nodes = ["pve1", "pve2"]
+            .iter()
+            .map(|name| Node {
+                cpu_usage: 10.0,
+                cpu_cores: 4,
+                memory_used: 1024,
+                memory_total: 4096,
+                uptime: 3600,
+                ..Node::test("homelab", name)
+            })
+            .collect();
+        app.nodes[1].status = NodeStatus::Offline;
+        app.containers = vec![Container {
+            status: ContainerStatus::Stopped,
+            max_cpu: 2,
+            memory_max: 1024,
+            ..Container::test("homelab", 105, "db", "pve1")
+        }];
+        app.provider_status.push(ProviderStatus::new("homelab"));
+        app
+    }
+
+    #[test]
+    fn test_blocks_are_plain_text_under_a_time_line() {
+        let app = app();
+        let block = render(&app, "2026-10-17 14:03:22", false, false);
+        assert_eq!(
+            block,
+            "pulse at 2026-10-17 14:03:22\n\
+             homelab: ok\n\
+             Nodes: 1 of 2 online\n\
+             \x20 pve2 offline\n\
+             Guests: 0 of 1 running\n\
+             \x20 105 db on pve1: stopped\n\
+             \n"
+        );
+        assert!(!block.contains('\x1b'));
+
+        // Colors only when asked for
+        assert!(render(&app, "now", false, true).contains("\x1b[31m"));
+    }
+
+    #[test]
+    fn test_full_blocks_add_the_once_tables() {
+        let app = app();
+        let block = render(&app, "now", true, false);
+        assert!(block.ends_with(&format!("\n{}\n", output::render_table(&app))));
+        assert!(block.contains("VMID"));
+    }
+}